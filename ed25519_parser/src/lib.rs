@@ -4,24 +4,31 @@ use der_parser::error::BerError;
 
 use der_parser::oid::Oid;
 use der_parser::*;
-use nom;
 use nom::IResult;
 
 use std::convert::From;
 
+use aes::{Aes128, Aes256};
+use aes_gcm::aead::{generic_array::GenericArray, Aead, NewAead};
+use aes_gcm::{Aes128Gcm, Aes256Gcm};
+use block_modes::block_padding::Pkcs7;
+use block_modes::{BlockMode, Cbc};
+use ctr::cipher::{NewCipher, StreamCipher};
+use curve25519_dalek::constants::ED25519_BASEPOINT_TABLE;
 use curve25519_dalek::edwards::CompressedEdwardsY;
-use curve25519_dalek::montgomery::MontgomeryPoint;
-use sha2::{Digest, Sha512};
+use curve25519_dalek::scalar::Scalar;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256, Sha512};
+use zeroize::Zeroize;
 // Re-export x25519_dalek structures for convenience
 pub use x25519_dalek::{PublicKey, StaticSecret};
 
 use rand_core::{CryptoRng, RngCore};
 
-use pem;
-
 use std::fmt;
 
 const ED_25519_OID: [u64; 4] = [1, 3, 101, 112];
+const X_25519_OID: [u64; 4] = [1, 3, 101, 110];
 
 // ---- Error handling ----
 
@@ -34,6 +41,15 @@ pub enum ED25519ParserError {
     UnknownOid,
     InvalidData,
     InvalidPEMTag,
+    /// The private key is encrypted, a password is needed
+    PasswordRequired,
+    /// The private key is encrypted with an unsupported algorithm
+    UnsupportedAlgorithm,
+    /// The private key cannot be decrypted, likely because of a wrong password
+    DecryptionError,
+    /// The mnemonic is not a BIP39 one: unexpected word count, word not in the
+    /// English word list, or wrong checksum
+    InvalidMnemonic(bip39::Error),
 }
 impl From<der_parser::error::BerError> for ED25519ParserError {
     fn from(error: der_parser::error::BerError) -> Self {
@@ -49,11 +65,32 @@ impl From<nom::Err<der_parser::error::BerError>> for ED25519ParserError {
 
 impl fmt::Display for ED25519ParserError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // For now, use the debug derived version
-        write!(f, "{:?}", self)
+        match self {
+            ED25519ParserError::BerError(err) => write!(f, "invalid DER encoding: {:?}", err),
+            ED25519ParserError::NomError(err) => write!(f, "invalid key structure: {:?}", err),
+            ED25519ParserError::UnknownOid => write!(f, "not an Ed25519 or X25519 key"),
+            ED25519ParserError::InvalidData => write!(f, "invalid key data"),
+            ED25519ParserError::InvalidPEMTag => write!(f, "unexpected PEM tag"),
+            ED25519ParserError::PasswordRequired => {
+                write!(f, "the private key is encrypted, a password is needed")
+            }
+            ED25519ParserError::UnsupportedAlgorithm => {
+                write!(
+                    f,
+                    "the private key is encrypted with an unsupported algorithm"
+                )
+            }
+            ED25519ParserError::DecryptionError => write!(
+                f,
+                "unable to decrypt the private key, is the password correct?"
+            ),
+            ED25519ParserError::InvalidMnemonic(err) => write!(f, "invalid mnemonic: {}", err),
+        }
     }
 }
 
+impl std::error::Error for ED25519ParserError {}
+
 // ---- Private key ----
 
 /// Expected structure:
@@ -91,7 +128,7 @@ struct DerEd25519PrivateStruct<'a> {
 
 fn parse_ed25519_private_header(
     i: &[u8],
-) -> IResult<&[u8], (BerObjectHeader, DerEd25519PrivateHeader), BerError> {
+) -> IResult<&[u8], (BerObjectHeader, DerEd25519PrivateHeader<'_>), BerError> {
     parse_der_struct!(
         i,
         TAG DerTag::Sequence,
@@ -101,7 +138,7 @@ fn parse_ed25519_private_header(
 
 fn parse_ed25519_private(
     i: &[u8],
-) -> IResult<&[u8], (BerObjectHeader, DerEd25519PrivateStruct), BerError> {
+) -> IResult<&[u8], (BerObjectHeader, DerEd25519PrivateStruct<'_>), BerError> {
     parse_der_struct!(
         i,
         TAG DerTag::Sequence,
@@ -118,6 +155,14 @@ const TAG_OCTETSTRING: u8 = 4;
 /// Parse a DER ED25519 private key, and return the corresponding
 /// `x25519_dalek::StaticSecret`
 pub fn parse_openssl_ed25519_privkey_der(data: &[u8]) -> Result<StaticSecret, ED25519ParserError> {
+    let mut seed = parse_ed25519_seed_der(data)?;
+    let private_key = ed25519_seed_to_x25519(&seed);
+    seed.zeroize();
+    Ok(private_key)
+}
+
+/// Parse a DER ED25519 private key, and return its 32 bytes seed (DATA)
+fn parse_ed25519_seed_der(data: &[u8]) -> Result<[u8; 32], ED25519ParserError> {
     let ed25519_oid = Oid::from(&ED_25519_OID);
     let (_remain, (_header, ed25519_private)) = parse_ed25519_private(data)?;
     if ed25519_private.header.tag.as_oid()? != &ed25519_oid {
@@ -129,9 +174,27 @@ pub fn parse_openssl_ed25519_privkey_der(data: &[u8]) -> Result<StaticSecret, ED
     if data.len() != 34 || data[0] != TAG_OCTETSTRING || data[1] != 32 {
         return Err(ED25519ParserError::InvalidData);
     }
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(&data[2..34]);
+    Ok(seed)
+}
+
+/// Return the `x25519_dalek::StaticSecret` corresponding to an ED25519 private
+/// key `seed`, from RFC8032: `clamping(Sha512(seed)[0..32])`, with `clamping`
+/// operation already done on `StaticSecret` creation
+fn ed25519_seed_to_x25519(seed: &[u8]) -> StaticSecret {
     let mut key_data = [0u8; 32];
-    key_data.copy_from_slice(&Sha512::digest(&data[2..34])[0..32]);
-    Ok(StaticSecret::from(key_data))
+    key_data.copy_from_slice(&Sha512::digest(seed)[0..32]);
+    StaticSecret::from(key_data)
+}
+
+/// Return the ED25519 public key corresponding to a private key `seed`, from
+/// RFC8032: `[s]B`, with `s` the clamped scalar also used by the
+/// `x25519_dalek::StaticSecret`. Its Montgomery form is therefore the
+/// corresponding `x25519_dalek::PublicKey`
+fn ed25519_seed_to_public(seed: &[u8]) -> [u8; 32] {
+    let scalar = Scalar::from_bits(ed25519_seed_to_x25519(seed).to_bytes());
+    (&scalar * &ED25519_BASEPOINT_TABLE).compress().to_bytes()
 }
 
 // ---- Public key ----
@@ -169,7 +232,7 @@ struct DerEd25519PublicStruct<'a> {
 
 fn parse_ed25519_public_header(
     i: &[u8],
-) -> IResult<&[u8], (BerObjectHeader, DerEd25519PublicHeader), BerError> {
+) -> IResult<&[u8], (BerObjectHeader, DerEd25519PublicHeader<'_>), BerError> {
     parse_der_struct!(
         i,
         TAG DerTag::Sequence,
@@ -179,7 +242,7 @@ fn parse_ed25519_public_header(
 
 fn parse_ed25519_public(
     i: &[u8],
-) -> IResult<&[u8], (BerObjectHeader, DerEd25519PublicStruct), BerError> {
+) -> IResult<&[u8], (BerObjectHeader, DerEd25519PublicStruct<'_>), BerError> {
     parse_der_struct!(
         i,
         TAG DerTag::Sequence,
@@ -192,16 +255,45 @@ fn parse_ed25519_public(
 
 /// Parse a DER ED25519 public key, and return the corresponding
 /// `x25519_dalek::PublicKey`
+///
+/// X25519 public keys (OID 1.3.101.110) are also accepted as is, for instance
+/// to encrypt to a key stored on a token which can only perform X25519
 pub fn parse_openssl_ed25519_pubkey_der(data: &[u8]) -> Result<PublicKey, ED25519ParserError> {
-    let ed25519_oid = Oid::from(&ED_25519_OID);
-    let (_remain, (_header, ed25519_public)) = parse_ed25519_public(data)?;
-    if ed25519_public.header.tag.as_oid()? != &ed25519_oid {
+    let (oid, public) = parse_public_der(data)?;
+    if oid == Oid::from(&ED_25519_OID) {
+        ed25519_public_to_x25519(&public)
+    } else if oid == Oid::from(&X_25519_OID) {
+        Ok(PublicKey::from(public))
+    } else {
+        Err(ED25519ParserError::UnknownOid)
+    }
+}
+
+/// Parse a DER ED25519 public key, and return its 32 bytes DATA
+fn parse_ed25519_public_der(data: &[u8]) -> Result<[u8; 32], ED25519ParserError> {
+    let (oid, public) = parse_public_der(data)?;
+    if oid != Oid::from(&ED_25519_OID) {
         return Err(ED25519ParserError::UnknownOid);
     }
+    Ok(public)
+}
+
+/// Parse a DER public key with a 32 bytes DATA, and return its OID and DATA
+fn parse_public_der(data: &[u8]) -> Result<(Oid, [u8; 32]), ED25519ParserError> {
+    let (_remain, (_header, ed25519_public)) = parse_ed25519_public(data)?;
+    let oid = ed25519_public.header.tag.as_oid()?.clone();
     let data = ed25519_public.data.content.as_slice()?;
     if data.len() != 32 {
         return Err(ED25519ParserError::InvalidData);
     }
+    let mut public = [0u8; 32];
+    public.copy_from_slice(data);
+    Ok((oid, public))
+}
+
+/// Return the `x25519_dalek::PublicKey` corresponding to an ED25519 public key
+/// `data` (32 bytes): `to_montgomery(decompress_edwardspoint(data))`
+fn ed25519_public_to_x25519(data: &[u8]) -> Result<PublicKey, ED25519ParserError> {
     if let Some(edwards_val) = CompressedEdwardsY::from_slice(&data[..32]).decompress() {
         Ok(PublicKey::from(edwards_val.to_montgomery().to_bytes()))
     } else {
@@ -209,10 +301,470 @@ pub fn parse_openssl_ed25519_pubkey_der(data: &[u8]) -> Result<PublicKey, ED2551
     }
 }
 
+// ---- Encrypted private key ----
+
+const PBES2_OID: [u64; 7] = [1, 2, 840, 113549, 1, 5, 13];
+const PBKDF2_OID: [u64; 7] = [1, 2, 840, 113549, 1, 5, 12];
+const HMAC_SHA256_OID: [u64; 6] = [1, 2, 840, 113549, 2, 9];
+const HMAC_SHA512_OID: [u64; 6] = [1, 2, 840, 113549, 2, 11];
+const AES128_CBC_OID: [u64; 9] = [2, 16, 840, 1, 101, 3, 4, 1, 2];
+const AES256_CBC_OID: [u64; 9] = [2, 16, 840, 1, 101, 3, 4, 1, 42];
+const AES128_GCM_OID: [u64; 9] = [2, 16, 840, 1, 101, 3, 4, 1, 6];
+const AES256_GCM_OID: [u64; 9] = [2, 16, 840, 1, 101, 3, 4, 1, 46];
+
+const GCM_NONCE_LENGTH: usize = 12;
+const GCM_TAG_LENGTH: u32 = 16;
+
+#[derive(Debug, PartialEq)]
+enum Pbkdf2Prf {
+    HmacSha256,
+    HmacSha512,
+}
+
+#[derive(Debug, PartialEq)]
+enum Pbes2Cipher<'a> {
+    Aes128Cbc { iv: &'a [u8] },
+    Aes256Cbc { iv: &'a [u8] },
+    Aes128Gcm { nonce: &'a [u8] },
+    Aes256Gcm { nonce: &'a [u8] },
+}
+
+impl<'a> Pbes2Cipher<'a> {
+    fn key_length(&self) -> usize {
+        match self {
+            Pbes2Cipher::Aes128Cbc { .. } | Pbes2Cipher::Aes128Gcm { .. } => 16,
+            Pbes2Cipher::Aes256Cbc { .. } | Pbes2Cipher::Aes256Gcm { .. } => 32,
+        }
+    }
+}
+
+/// Expected structure (PKCS#8 `EncryptedPrivateKeyInfo`, from RFC 5958 and
+/// RFC 8018), as produced by `openssl genpkey -algorithm ed25519 -aes256`:
+///
+/// Seq(
+///     Seq(
+///         OID(1.2.840.113549.1.5.13), // PBES2
+///         Seq(
+///             Seq(
+///                 OID(1.2.840.113549.1.5.12), // PBKDF2
+///                 Seq(
+///                     OctetString(SALT),
+///                     Int(ITERATIONS),
+///                     Int(KEY_LENGTH), // Optional
+///                     Seq(OID(PRF), Null), // Optional, HMAC-SHA1 if absent
+///                 ),
+///             ),
+///             Seq(
+///                 OID(CIPHER),
+///                 OctetString(IV), // AES-CBC
+///                 Seq(OctetString(NONCE), Int(TAG_LENGTH)), // AES-GCM
+///             ),
+///         ),
+///     ),
+///     OctetString(ENCRYPTED_DATA), // AES-GCM: followed by the tag
+/// )
+///
+/// Supported PRF are HMAC-SHA256 and HMAC-SHA512, and supported ciphers are
+/// AES-128 and AES-256, in CBC or GCM (with 16 bytes tags) modes.
+/// Once decrypted, ENCRYPTED_DATA is the DER private key
+#[derive(Debug, PartialEq)]
+struct EncryptedPrivateKeyInfo<'a> {
+    salt: &'a [u8],
+    iterations: u32,
+    prf: Pbkdf2Prf,
+    cipher: Pbes2Cipher<'a>,
+    data: &'a [u8],
+}
+
+/// Return the `(OID, parameters)` of an AlgorithmIdentifier
+fn parse_algorithm_identifier<'a, 'b>(
+    object: &'b DerObject<'a>,
+) -> Result<(&'b Oid, Option<&'b DerObject<'a>>), ED25519ParserError> {
+    match object.as_sequence()?.as_slice() {
+        [oid] => Ok((oid.as_oid()?, None)),
+        [oid, parameters] => Ok((oid.as_oid()?, Some(parameters))),
+        _ => Err(ED25519ParserError::InvalidData),
+    }
+}
+
+/// PBKDF2 salt, iterations, optional key length and PRF
+type Pbkdf2Params<'a> = (&'a [u8], u32, Option<u32>, Pbkdf2Prf);
+
+fn parse_pbkdf2_params<'a>(object: &DerObject<'a>) -> Result<Pbkdf2Params<'a>, ED25519ParserError> {
+    let params = object.as_sequence()?;
+    if params.len() < 2 || params.len() > 4 {
+        return Err(ED25519ParserError::InvalidData);
+    }
+    let salt = params[0].as_slice()?;
+    let iterations = params[1].as_u32()?;
+    let mut key_length = None;
+    // Without a PRF, the default one is HMAC-SHA1, which is not supported
+    let mut prf = Err(ED25519ParserError::UnsupportedAlgorithm);
+    for param in &params[2..] {
+        if param.tag == DerTag::Integer {
+            key_length = Some(param.as_u32()?);
+        } else {
+            let (oid, _) = parse_algorithm_identifier(param)?;
+            prf = if oid == &Oid::from(&HMAC_SHA256_OID) {
+                Ok(Pbkdf2Prf::HmacSha256)
+            } else if oid == &Oid::from(&HMAC_SHA512_OID) {
+                Ok(Pbkdf2Prf::HmacSha512)
+            } else {
+                Err(ED25519ParserError::UnsupportedAlgorithm)
+            };
+        }
+    }
+    Ok((salt, iterations, key_length, prf?))
+}
+
+fn parse_pbes2_cipher<'a>(object: &DerObject<'a>) -> Result<Pbes2Cipher<'a>, ED25519ParserError> {
+    let (oid, parameters) = parse_algorithm_identifier(object)?;
+    let parameters = parameters.ok_or(ED25519ParserError::InvalidData)?;
+    let gcm_nonce = || -> Result<&'a [u8], ED25519ParserError> {
+        match parameters.as_sequence()?.as_slice() {
+            [nonce, tag_length] => {
+                let nonce = nonce.as_slice()?;
+                if nonce.len() != GCM_NONCE_LENGTH || tag_length.as_u32()? != GCM_TAG_LENGTH {
+                    return Err(ED25519ParserError::UnsupportedAlgorithm);
+                }
+                Ok(nonce)
+            }
+            // Without a tag length, the default one is 12 bytes
+            _ => Err(ED25519ParserError::UnsupportedAlgorithm),
+        }
+    };
+    if oid == &Oid::from(&AES128_CBC_OID) {
+        Ok(Pbes2Cipher::Aes128Cbc {
+            iv: parameters.as_slice()?,
+        })
+    } else if oid == &Oid::from(&AES256_CBC_OID) {
+        Ok(Pbes2Cipher::Aes256Cbc {
+            iv: parameters.as_slice()?,
+        })
+    } else if oid == &Oid::from(&AES128_GCM_OID) {
+        Ok(Pbes2Cipher::Aes128Gcm {
+            nonce: gcm_nonce()?,
+        })
+    } else if oid == &Oid::from(&AES256_GCM_OID) {
+        Ok(Pbes2Cipher::Aes256Gcm {
+            nonce: gcm_nonce()?,
+        })
+    } else {
+        Err(ED25519ParserError::UnsupportedAlgorithm)
+    }
+}
+
+fn parse_encrypted_private_key_info(
+    data: &[u8],
+) -> Result<EncryptedPrivateKeyInfo<'_>, ED25519ParserError> {
+    let (remain, object) = parse_der(data)?;
+    if !remain.is_empty() {
+        return Err(ED25519ParserError::InvalidData);
+    }
+    let (algorithm, encrypted_data) = match object.as_sequence()?.as_slice() {
+        [algorithm, encrypted_data] => (algorithm, encrypted_data.as_slice()?),
+        _ => return Err(ED25519ParserError::InvalidData),
+    };
+
+    let (oid, parameters) = parse_algorithm_identifier(algorithm)?;
+    if oid != &Oid::from(&PBES2_OID) {
+        return Err(ED25519ParserError::UnsupportedAlgorithm);
+    }
+    let parameters = parameters.ok_or(ED25519ParserError::InvalidData)?;
+    let (kdf, cipher) = match parameters.as_sequence()?.as_slice() {
+        [kdf, cipher] => (kdf, parse_pbes2_cipher(cipher)?),
+        _ => return Err(ED25519ParserError::InvalidData),
+    };
+
+    let (oid, kdf_parameters) = parse_algorithm_identifier(kdf)?;
+    if oid != &Oid::from(&PBKDF2_OID) {
+        return Err(ED25519ParserError::UnsupportedAlgorithm);
+    }
+    let (salt, iterations, key_length, prf) =
+        parse_pbkdf2_params(kdf_parameters.ok_or(ED25519ParserError::InvalidData)?)?;
+    if let Some(key_length) = key_length {
+        if key_length as usize != cipher.key_length() {
+            return Err(ED25519ParserError::InvalidData);
+        }
+    }
+
+    Ok(EncryptedPrivateKeyInfo {
+        salt,
+        iterations,
+        prf,
+        cipher,
+        data: encrypted_data,
+    })
+}
+
+/// Decrypt an `EncryptedPrivateKeyInfo`, returning the inner DER data
+fn decrypt_private_key_info(
+    info: &EncryptedPrivateKeyInfo,
+    password: &[u8],
+) -> Result<Vec<u8>, ED25519ParserError> {
+    let mut key = vec![0u8; info.cipher.key_length()];
+    match info.prf {
+        Pbkdf2Prf::HmacSha256 => {
+            pbkdf2::pbkdf2_hmac::<Sha256>(password, info.salt, info.iterations, &mut key)
+        }
+        Pbkdf2Prf::HmacSha512 => {
+            pbkdf2::pbkdf2_hmac::<Sha512>(password, info.salt, info.iterations, &mut key)
+        }
+    }
+
+    let decrypted = match info.cipher {
+        Pbes2Cipher::Aes128Cbc { iv } => Cbc::<Aes128, Pkcs7>::new_from_slices(&key, iv)
+            .map_err(|_| ED25519ParserError::InvalidData)?
+            .decrypt_vec(info.data)
+            .map_err(|_| ED25519ParserError::DecryptionError),
+        Pbes2Cipher::Aes256Cbc { iv } => Cbc::<Aes256, Pkcs7>::new_from_slices(&key, iv)
+            .map_err(|_| ED25519ParserError::InvalidData)?
+            .decrypt_vec(info.data)
+            .map_err(|_| ED25519ParserError::DecryptionError),
+        Pbes2Cipher::Aes128Gcm { nonce } => Aes128Gcm::new(GenericArray::from_slice(&key))
+            .decrypt(GenericArray::from_slice(nonce), info.data)
+            .map_err(|_| ED25519ParserError::DecryptionError),
+        Pbes2Cipher::Aes256Gcm { nonce } => Aes256Gcm::new(GenericArray::from_slice(&key))
+            .decrypt(GenericArray::from_slice(nonce), info.data)
+            .map_err(|_| ED25519ParserError::DecryptionError),
+    };
+    key.zeroize();
+    decrypted
+}
+
+/// Parse a DER ED25519 private key, encrypted with `password`, and return the
+/// corresponding `x25519_dalek::StaticSecret`
+pub fn parse_openssl_ed25519_privkey_encrypted_der(
+    data: &[u8],
+    password: &[u8],
+) -> Result<StaticSecret, ED25519ParserError> {
+    let info = parse_encrypted_private_key_info(data)?;
+    let mut decrypted = decrypt_private_key_info(&info, password)?;
+    // With AES-CBC, a wrong password may still lead to a valid padding
+    let private_key = parse_openssl_ed25519_privkey_der(&decrypted)
+        .map_err(|_| ED25519ParserError::DecryptionError);
+    decrypted.zeroize();
+    private_key
+}
+
+// ---- OpenSSH ----
+
+const OPENSSH_PRIVATE_TAG: &[u8] = b"OPENSSH PRIVATE KEY";
+const OPENSSH_MAGIC: &[u8] = b"openssh-key-v1\0";
+const SSH_ED25519_KEY_TYPE: &[u8] = b"ssh-ed25519";
+const OPENSSH_NO_ENCRYPTION: &[u8] = b"none";
+const OPENSSH_AES256_CTR: &[u8] = b"aes256-ctr";
+const OPENSSH_BCRYPT: &[u8] = b"bcrypt";
+const AES256_KEY_LENGTH: usize = 32;
+const AES_IV_LENGTH: usize = 16;
+
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
+/// Reader of the SSH encoding (RFC 4251): big-endian `u32`, and `string`s
+/// prefixed by their `u32` length
+struct SshReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> SshReader<'a> {
+    fn read_u32(&mut self) -> Result<u32, ED25519ParserError> {
+        if self.data.len() < 4 {
+            return Err(ED25519ParserError::InvalidData);
+        }
+        let (value, remain) = self.data.split_at(4);
+        self.data = remain;
+        Ok(u32::from_be_bytes([value[0], value[1], value[2], value[3]]))
+    }
+
+    fn read_string(&mut self) -> Result<&'a [u8], ED25519ParserError> {
+        let length = self.read_u32()? as usize;
+        if self.data.len() < length {
+            return Err(ED25519ParserError::InvalidData);
+        }
+        let (value, remain) = self.data.split_at(length);
+        self.data = remain;
+        Ok(value)
+    }
+}
+
+/// Expected structure of a public key blob, with SSH encoding:
+///
+/// string("ssh-ed25519")
+/// string(DATA)
+///
+/// DATA being the same than in the OpenSSL format
+fn parse_openssh_ed25519_pubkey_blob(blob: &[u8]) -> Result<PublicKey, ED25519ParserError> {
+    let mut reader = SshReader { data: blob };
+    if reader.read_string()? != SSH_ED25519_KEY_TYPE {
+        return Err(ED25519ParserError::UnsupportedAlgorithm);
+    }
+    let data = reader.read_string()?;
+    if data.len() != 32 || !reader.data.is_empty() {
+        return Err(ED25519ParserError::InvalidData);
+    }
+    ed25519_public_to_x25519(data)
+}
+
+/// Parse an OpenSSH ED25519 public key, as a line of an `authorized_keys`
+/// file: `[options] ssh-ed25519 AAAA... [comment]`
+pub fn parse_openssh_ed25519_pubkey(line: &[u8]) -> Result<PublicKey, ED25519ParserError> {
+    let line = std::str::from_utf8(line).map_err(|_| ED25519ParserError::InvalidData)?;
+    let mut tokens = line.split_whitespace();
+    if !tokens.any(|token| token.as_bytes() == SSH_ED25519_KEY_TYPE) {
+        // Another type of key (`ssh-rsa`, `ecdsa-sha2-nistp256`, etc.)
+        return Err(ED25519ParserError::UnsupportedAlgorithm);
+    }
+    let blob = tokens.next().ok_or(ED25519ParserError::InvalidData)?;
+    let blob = base64::decode(blob).map_err(|_| ED25519ParserError::InvalidData)?;
+    parse_openssh_ed25519_pubkey_blob(&blob)
+}
+
+/// Parse the OpenSSH ED25519 public keys of an `authorized_keys` file, one
+/// per line. Empty lines and comments are ignored
+pub fn parse_openssh_ed25519_pubkeys_many(
+    data: &[u8],
+) -> Result<Vec<PublicKey>, ED25519ParserError> {
+    let data = std::str::from_utf8(data).map_err(|_| ED25519ParserError::InvalidData)?;
+    let mut output = Vec::new();
+    for line in data.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        output.push(parse_openssh_ed25519_pubkey(line.as_bytes())?);
+    }
+    Ok(output)
+}
+
+/// Expected structure (OpenSSH `PROTOCOL.key`), with SSH encoding:
+///
+/// "openssh-key-v1\0"
+/// string(CIPHER)           // "none", or "aes256-ctr"
+/// string(KDF)              // "none", or "bcrypt"
+/// string(KDF_OPTIONS)      // bcrypt: string(SALT), u32(ROUNDS)
+/// u32(1)                   // Number of keys
+/// string(PUBLIC_KEY_BLOB)
+/// string(PRIVATE_SECTION)  // Encrypted, unless CIPHER is "none"
+///
+/// PRIVATE_SECTION:
+/// u32(CHECK), u32(CHECK)   // Same random value, to detect a wrong password
+/// string("ssh-ed25519")
+/// string(PUBLIC_DATA)
+/// string(SEED || PUBLIC_DATA)
+/// string(COMMENT)
+/// Padding
+///
+/// SEED being the same than DATA in the OpenSSL format
+struct OpensshPrivateKey<'a> {
+    cipher: &'a [u8],
+    kdf: &'a [u8],
+    kdf_options: &'a [u8],
+    private_section: &'a [u8],
+}
+
+fn parse_openssh_private_key(data: &[u8]) -> Result<OpensshPrivateKey<'_>, ED25519ParserError> {
+    if !data.starts_with(OPENSSH_MAGIC) {
+        return Err(ED25519ParserError::InvalidData);
+    }
+    let mut reader = SshReader {
+        data: &data[OPENSSH_MAGIC.len()..],
+    };
+    let cipher = reader.read_string()?;
+    let kdf = reader.read_string()?;
+    let kdf_options = reader.read_string()?;
+    if reader.read_u32()? != 1 {
+        // Several keys in the same file are not supported
+        return Err(ED25519ParserError::InvalidData);
+    }
+    let _public_key_blob = reader.read_string()?;
+    let private_section = reader.read_string()?;
+    Ok(OpensshPrivateKey {
+        cipher,
+        kdf,
+        kdf_options,
+        private_section,
+    })
+}
+
+fn parse_openssh_private_section(section: &[u8]) -> Result<StaticSecret, ED25519ParserError> {
+    let mut reader = SshReader { data: section };
+    if reader.read_u32()? != reader.read_u32()? {
+        return Err(ED25519ParserError::DecryptionError);
+    }
+    if reader.read_string()? != SSH_ED25519_KEY_TYPE {
+        return Err(ED25519ParserError::UnsupportedAlgorithm);
+    }
+    let _public_data = reader.read_string()?;
+    let private_data = reader.read_string()?;
+    if private_data.len() != 64 {
+        return Err(ED25519ParserError::InvalidData);
+    }
+    Ok(ed25519_seed_to_x25519(&private_data[..32]))
+}
+
+fn openssh_pem_contents(data: &[u8]) -> Result<Vec<u8>, ED25519ParserError> {
+    let pem_data = pem::parse(data).map_err(|_| ED25519ParserError::InvalidData)?;
+    if pem_data.tag.as_bytes() != OPENSSH_PRIVATE_TAG {
+        return Err(ED25519ParserError::InvalidPEMTag);
+    }
+    Ok(pem_data.contents)
+}
+
+/// Parse an OpenSSH ED25519 private key (`OPENSSH PRIVATE KEY`, as
+/// `id_ed25519`), and return the corresponding `x25519_dalek::StaticSecret`
+///
+/// If the key is encrypted, `ED25519ParserError::PasswordRequired` is returned:
+/// use `parse_openssh_ed25519_privkey_encrypted` instead
+pub fn parse_openssh_ed25519_privkey(data: &[u8]) -> Result<StaticSecret, ED25519ParserError> {
+    let mut contents = openssh_pem_contents(data)?;
+    let private_key = match parse_openssh_private_key(&contents) {
+        Ok(key) if key.cipher == OPENSSH_NO_ENCRYPTION => {
+            parse_openssh_private_section(key.private_section)
+        }
+        Ok(_) => Err(ED25519ParserError::PasswordRequired),
+        Err(err) => Err(err),
+    };
+    contents.zeroize();
+    private_key
+}
+
+/// Parse an OpenSSH ED25519 private key encrypted with `password` (bcrypt and
+/// AES-256-CTR, the `ssh-keygen` default)
+pub fn parse_openssh_ed25519_privkey_encrypted(
+    data: &[u8],
+    password: &[u8],
+) -> Result<StaticSecret, ED25519ParserError> {
+    let contents = openssh_pem_contents(data)?;
+    let key = parse_openssh_private_key(&contents)?;
+    if key.cipher != OPENSSH_AES256_CTR || key.kdf != OPENSSH_BCRYPT {
+        return Err(ED25519ParserError::UnsupportedAlgorithm);
+    }
+    let mut options = SshReader {
+        data: key.kdf_options,
+    };
+    let salt = options.read_string()?;
+    let rounds = options.read_u32()?;
+
+    let mut key_iv = [0u8; AES256_KEY_LENGTH + AES_IV_LENGTH];
+    bcrypt_pbkdf::bcrypt_pbkdf(password, salt, rounds, &mut key_iv)
+        .map_err(|_| ED25519ParserError::InvalidData)?;
+    let cipher =
+        Aes256Ctr::new_from_slices(&key_iv[..AES256_KEY_LENGTH], &key_iv[AES256_KEY_LENGTH..]);
+    key_iv.zeroize();
+    let mut section = key.private_section.to_vec();
+    cipher
+        .map_err(|_| ED25519ParserError::InvalidData)?
+        .apply_keystream(&mut section);
+    let private_key = parse_openssh_private_section(&section);
+    section.zeroize();
+    private_key
+}
+
 // ---- PEM ----
 
 const PUBLIC_TAG: &[u8] = b"PUBLIC KEY";
 const PRIVATE_TAG: &[u8] = b"PRIVATE KEY";
+const ENCRYPTED_PRIVATE_TAG: &[u8] = b"ENCRYPTED PRIVATE KEY";
 
 /// Parse an OpenSSL ED25519 public key, either in PEM or DER format
 pub fn parse_openssl_ed25519_pubkey(data: &[u8]) -> Result<PublicKey, ED25519ParserError> {
@@ -229,16 +781,103 @@ pub fn parse_openssl_ed25519_pubkey(data: &[u8]) -> Result<PublicKey, ED25519Par
 }
 
 /// Parse an OpenSSL ED25519 private key, either in PEM or DER format
+///
+/// If the key is encrypted, `ED25519ParserError::PasswordRequired` is returned:
+/// use `parse_openssl_ed25519_privkey_encrypted` instead
 pub fn parse_openssl_ed25519_privkey(data: &[u8]) -> Result<StaticSecret, ED25519ParserError> {
     if let Ok(pem_data) = pem::parse(data) {
         // First, try as a PEM
+        if pem_data.tag.as_bytes() == ENCRYPTED_PRIVATE_TAG {
+            return Err(ED25519ParserError::PasswordRequired);
+        }
         if pem_data.tag.as_bytes() != PRIVATE_TAG {
             return Err(ED25519ParserError::InvalidPEMTag);
         }
         parse_openssl_ed25519_privkey_der(&pem_data.contents)
     } else {
         // Fallback to DER format
-        parse_openssl_ed25519_privkey_der(data)
+        parse_openssl_ed25519_privkey_der(data).map_err(|err| {
+            if parse_encrypted_private_key_info(data).is_ok() {
+                ED25519ParserError::PasswordRequired
+            } else {
+                err
+            }
+        })
+    }
+}
+
+/// Parse an OpenSSL ED25519 private key encrypted with `password` (PKCS#8
+/// `ENCRYPTED PRIVATE KEY`), either in PEM or DER format
+pub fn parse_openssl_ed25519_privkey_encrypted(
+    data: &[u8],
+    password: &[u8],
+) -> Result<StaticSecret, ED25519ParserError> {
+    if let Ok(pem_data) = pem::parse(data) {
+        // First, try as a PEM
+        if pem_data.tag.as_bytes() != ENCRYPTED_PRIVATE_TAG {
+            return Err(ED25519ParserError::InvalidPEMTag);
+        }
+        parse_openssl_ed25519_privkey_encrypted_der(&pem_data.contents, password)
+    } else {
+        // Fallback to DER format
+        parse_openssl_ed25519_privkey_encrypted_der(data, password)
+    }
+}
+
+/// Parse an OpenSSL ED25519 private key, either in PEM or DER format, and
+/// return its 32 bytes seed, as expected by ED25519 signature implementations
+///
+/// If the key is encrypted, `ED25519ParserError::PasswordRequired` is returned
+pub fn parse_openssl_ed25519_signing_key(data: &[u8]) -> Result<[u8; 32], ED25519ParserError> {
+    if let Ok(pem_data) = pem::parse(data) {
+        // First, try as a PEM
+        if pem_data.tag.as_bytes() == ENCRYPTED_PRIVATE_TAG {
+            return Err(ED25519ParserError::PasswordRequired);
+        }
+        if pem_data.tag.as_bytes() != PRIVATE_TAG {
+            return Err(ED25519ParserError::InvalidPEMTag);
+        }
+        let mut contents = pem_data.contents;
+        let seed = parse_ed25519_seed_der(&contents);
+        contents.zeroize();
+        seed
+    } else {
+        // Fallback to DER format
+        parse_ed25519_seed_der(data)
+    }
+}
+
+/// Parse an OpenSSL ED25519 public key, either in PEM or DER format, and
+/// return its 32 bytes compressed point, as expected by ED25519 signature
+/// implementations
+pub fn parse_openssl_ed25519_verifying_key(data: &[u8]) -> Result<[u8; 32], ED25519ParserError> {
+    if let Ok(pem_data) = pem::parse(data) {
+        // First, try as a PEM
+        if pem_data.tag.as_bytes() != PUBLIC_TAG {
+            return Err(ED25519ParserError::InvalidPEMTag);
+        }
+        parse_ed25519_public_der(&pem_data.contents)
+    } else {
+        // Fallback to DER format
+        parse_ed25519_public_der(data)
+    }
+}
+
+// ---- Fingerprint ----
+
+/// Stable identifier of a public key, to reference a recipient
+pub trait Fingerprint {
+    /// Return "SHA256:", followed by the hex encoded SHA-256 of the key
+    fn fingerprint(&self) -> String;
+}
+
+impl Fingerprint for PublicKey {
+    /// The fingerprint is the one of the X25519 key, so that the OpenSSL and
+    /// OpenSSH formats of an Ed25519 key have the same one
+    fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(self.as_bytes());
+        let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("SHA256:{}", hex)
     }
 }
 
@@ -297,33 +936,80 @@ where
     // Get the seed
     let mut private = [0u8; 32];
     csprng.fill_bytes(&mut private);
+    let key_pair = keypair_from_private(&private);
+    private.zeroize();
+    key_pair
+}
 
-    // Get the corresponding public key
-    let priv_key = {
-        let mut key_data = [0u8; 32];
-        key_data.copy_from_slice(&Sha512::digest(&private)[0..32]);
-        StaticSecret::from(key_data)
-    };
-    let pubkey = PublicKey::from(&priv_key);
-
-    // Get the public data bytes
-
-    // From "Compact representation of an elliptic curve point" IETF Draft, it
-    // is OK to fix the sign to 0
-    // (cf. https://tools.ietf.org/id/draft-jivsov-ecc-compact-05.html)
-    let edwards = match MontgomeryPoint(*pubkey.as_bytes()).to_edwards(0) {
-        Some(data) => data,
-        // Should never happen
-        None => {
-            return None;
-        }
-    };
+/// Minimum size of a seed, as BIP39 and SLIP-0010
+const SEED_MIN_SIZE: usize = 16;
+/// HMAC key deriving the Ed25519 master key from a seed, as SLIP-0010
+const SLIP10_ED25519_KEY: &[u8] = b"ed25519 seed";
+const BIP39_PBKDF2_ROUNDS: u32 = 2048;
 
-    let public = edwards.compress().to_bytes();
+/// Generate the keypair derived from `seed`, in DER format
+///
+/// The same seed always gives the same keypair, which can then be escrowed as
+/// the seed. The private key is the SLIP-0010 Ed25519 master key of `seed`,
+/// which must be at least 16 bytes long (for instance, from
+/// `mnemonic_to_seed`)
+pub fn generate_keypair_from_seed(seed: &[u8]) -> Option<KeyPair> {
+    if seed.len() < SEED_MIN_SIZE {
+        return None;
+    }
+    let mut mac = Hmac::<Sha512>::new_from_slice(SLIP10_ED25519_KEY).ok()?;
+    mac.update(seed);
+    let mut output = mac.finalize().into_bytes();
+    let mut private = [0u8; 32];
+    private.copy_from_slice(&output[..32]);
+    output.as_mut_slice().zeroize();
+    let key_pair = keypair_from_private(&private);
+    private.zeroize();
+    key_pair
+}
+
+/// Check that `mnemonic` is a BIP39 one, in English
+///
+/// It must have 12, 15, 18, 21 or 24 words, from the BIP39 English word list,
+/// the last one including a valid checksum. Words can be separated by any
+/// whitespace
+pub fn check_mnemonic(mnemonic: &str) -> Result<(), ED25519ParserError> {
+    let mut normalized = mnemonic.split_whitespace().collect::<Vec<_>>().join(" ");
+    let result = bip39::Mnemonic::parse_in_normalized(bip39::Language::English, &normalized);
+    normalized.zeroize();
+    result
+        .map(|_| ())
+        .map_err(ED25519ParserError::InvalidMnemonic)
+}
+
+/// Compute the seed of a BIP39-style `mnemonic`, protected by `passphrase`
+///
+/// Words are separated by single spaces before the derivation, as in BIP39.
+/// The mnemonic itself is not checked (see `check_mnemonic`), and no Unicode
+/// normalization is made: ASCII mnemonics are expected
+pub fn mnemonic_to_seed(mnemonic: &str, passphrase: &str) -> [u8; 64] {
+    let mut mnemonic = mnemonic.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut salt = format!("mnemonic{}", passphrase);
+    let mut seed = [0u8; 64];
+    pbkdf2::pbkdf2_hmac::<Sha512>(
+        mnemonic.as_bytes(),
+        salt.as_bytes(),
+        BIP39_PBKDF2_ROUNDS,
+        &mut seed,
+    );
+    mnemonic.zeroize();
+    salt.zeroize();
+    seed
+}
+
+/// Build the keypair of the Ed25519 seed `private`, in DER format
+fn keypair_from_private(private: &[u8; 32]) -> Option<KeyPair> {
+    // Get the corresponding public key
+    let public = ed25519_seed_to_public(private);
 
     let mut private_der = [0u8; PRIV_KEY_PREFIX.len() + 32];
     private_der[..PRIV_KEY_PREFIX.len()].copy_from_slice(PRIV_KEY_PREFIX);
-    private_der[PRIV_KEY_PREFIX.len()..].copy_from_slice(&private);
+    private_der[PRIV_KEY_PREFIX.len()..].copy_from_slice(private);
 
     let mut public_der = [0u8; PUB_KEY_PREFIX.len() + 32];
     public_der[..PUB_KEY_PREFIX.len()].copy_from_slice(PUB_KEY_PREFIX);
@@ -355,6 +1041,24 @@ mod tests {
     // Many[0] is PEM_PUB
     static PEM_PUB_MANY: &[u8] = include_bytes!("../../samples/test25519_pub_many.pem");
 
+    // openssl pkcs8 -topk8 -v2 aes-256-cbc -v2prf hmacWithSHA256 -in test25519.pem -out test25519_encrypted.pem -passout pass:mla-password
+    static PEM_PRIV_ENCRYPTED: &[u8] = include_bytes!("../../samples/test25519_encrypted.pem");
+    // Same key and password, with AES-256-GCM (not available in `openssl pkcs8`)
+    static DER_PRIV_ENCRYPTED_GCM: &[u8] =
+        include_bytes!("../../samples/test25519_encrypted_gcm.der");
+    static PASSWORD: &[u8] = b"mla-password";
+
+    // OpenSSH versions of test25519.der, and of test25519_pub.der and
+    // test25519_2_pub.pem (as an `authorized_keys` file)
+    static OPENSSH_PRIV: &[u8] = include_bytes!("../../samples/test25519_openssh");
+    // ssh-keygen -p -N mla-password -f test25519_openssh_encrypted
+    static OPENSSH_PRIV_ENCRYPTED: &[u8] =
+        include_bytes!("../../samples/test25519_openssh_encrypted");
+    static OPENSSH_PUB: &[u8] = include_bytes!("../../samples/test25519_openssh.pub");
+    static OPENSSH_AUTHORIZED_KEYS: &[u8] =
+        include_bytes!("../../samples/test25519_authorized_keys");
+    static PEM_PUB_2: &[u8] = include_bytes!("../../samples/test25519_2_pub.pem");
+
     #[test]
     fn parse_and_check_pubkeys_der() {
         let priv_key = parse_openssl_ed25519_privkey_der(DER_PRIV).unwrap();
@@ -365,6 +1069,24 @@ mod tests {
         assert_eq!(computed_pub_key.as_bytes(), pub_key.as_bytes());
     }
 
+    #[test]
+    fn parse_x25519_pubkey_der() {
+        // SubjectPublicKeyInfo of an X25519 key (OID 1.3.101.110), as
+        // exported from a token
+        let x25519 = PublicKey::from(&parse_openssl_ed25519_privkey_der(DER_PRIV).unwrap());
+        let mut der = vec![
+            0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x6e, 0x03, 0x21, 0x00,
+        ];
+        der.extend_from_slice(x25519.as_bytes());
+        let pub_key = parse_openssl_ed25519_pubkey_der(&der).unwrap();
+        assert_eq!(pub_key.as_bytes(), x25519.as_bytes());
+        // Not an Ed25519 key, so not usable for signature verification
+        assert!(matches!(
+            parse_openssl_ed25519_verifying_key(&der),
+            Err(ED25519ParserError::UnknownOid)
+        ));
+    }
+
     #[test]
     fn parse_and_check_pubkeys_multi_format() {
         let pub_key_pem = parse_openssl_ed25519_pubkey(PEM_PUB).unwrap();
@@ -377,6 +1099,62 @@ mod tests {
         assert_eq!(priv_key_der.to_bytes(), priv_key_pem.to_bytes());
     }
 
+    #[test]
+    fn parse_encrypted_privkeys() {
+        let priv_key = parse_openssl_ed25519_privkey_der(DER_PRIV).unwrap();
+        for data in &[PEM_PRIV_ENCRYPTED, DER_PRIV_ENCRYPTED_GCM] {
+            // The password is needed
+            assert!(matches!(
+                parse_openssl_ed25519_privkey(data),
+                Err(ED25519ParserError::PasswordRequired)
+            ));
+            assert!(matches!(
+                parse_openssl_ed25519_privkey_encrypted(data, b"wrong password"),
+                Err(ED25519ParserError::DecryptionError)
+            ));
+            let decrypted = parse_openssl_ed25519_privkey_encrypted(data, PASSWORD).unwrap();
+            assert_eq!(decrypted.to_bytes(), priv_key.to_bytes());
+        }
+        // Not encrypted
+        assert!(parse_openssl_ed25519_privkey_encrypted(PEM_PRIV, PASSWORD).is_err());
+    }
+
+    #[test]
+    fn parse_openssh_keys() {
+        let priv_key = parse_openssl_ed25519_privkey_der(DER_PRIV).unwrap();
+        let pub_key = parse_openssl_ed25519_pubkey_der(DER_PUB).unwrap();
+
+        let openssh_priv_key = parse_openssh_ed25519_privkey(OPENSSH_PRIV).unwrap();
+        assert_eq!(openssh_priv_key.to_bytes(), priv_key.to_bytes());
+        assert!(matches!(
+            parse_openssh_ed25519_privkey(OPENSSH_PRIV_ENCRYPTED),
+            Err(ED25519ParserError::PasswordRequired)
+        ));
+        assert!(matches!(
+            parse_openssh_ed25519_privkey_encrypted(OPENSSH_PRIV_ENCRYPTED, b"wrong password"),
+            Err(ED25519ParserError::DecryptionError)
+        ));
+        let openssh_priv_key =
+            parse_openssh_ed25519_privkey_encrypted(OPENSSH_PRIV_ENCRYPTED, PASSWORD).unwrap();
+        assert_eq!(openssh_priv_key.to_bytes(), priv_key.to_bytes());
+
+        let openssh_pub_key = parse_openssh_ed25519_pubkey(OPENSSH_PUB).unwrap();
+        assert_eq!(openssh_pub_key.as_bytes(), pub_key.as_bytes());
+        let pub_keys = parse_openssh_ed25519_pubkeys_many(OPENSSH_AUTHORIZED_KEYS).unwrap();
+        assert_eq!(pub_keys.len(), 2);
+        assert_eq!(pub_keys[0].as_bytes(), pub_key.as_bytes());
+        assert_eq!(
+            pub_keys[1].as_bytes(),
+            parse_openssl_ed25519_pubkey(PEM_PUB_2).unwrap().as_bytes()
+        );
+
+        // Only ED25519 keys are supported
+        assert!(matches!(
+            parse_openssh_ed25519_pubkey(b"ssh-rsa AAAAB3NzaC1yc2EAAAADAQABAAABAQC7 rsa@test"),
+            Err(ED25519ParserError::UnsupportedAlgorithm)
+        ));
+    }
+
     #[test]
     fn parse_many_pubkeys() {
         let pub_keys_pem = parse_openssl_ed25519_pubkeys_pem_many(PEM_PUB).unwrap();
@@ -391,6 +1169,25 @@ mod tests {
         assert_ne!(pub_key_der.as_bytes(), pub_keys_pem[1].as_bytes());
     }
 
+    #[test]
+    fn parse_signature_keys() {
+        let seed = parse_openssl_ed25519_signing_key(DER_PRIV).unwrap();
+        assert_eq!(parse_openssl_ed25519_signing_key(PEM_PRIV).unwrap(), seed);
+        let public = parse_openssl_ed25519_verifying_key(DER_PUB).unwrap();
+        assert_eq!(
+            parse_openssl_ed25519_verifying_key(PEM_PUB).unwrap(),
+            public
+        );
+        // Keys generated by OpenSSL
+        assert_eq!(ed25519_seed_to_public(&seed), public);
+
+        match parse_openssl_ed25519_signing_key(PEM_PRIV_ENCRYPTED) {
+            Err(ED25519ParserError::PasswordRequired) => {}
+            _ => panic!("The key must be reported as encrypted"),
+        }
+        assert!(parse_openssl_ed25519_verifying_key(PEM_PRIV).is_err());
+    }
+
     #[test]
     fn exports() {
         let mut csprng = OsRng {};
@@ -405,17 +1202,102 @@ mod tests {
 
         let pub_pem_key = keypair.public_as_pem();
         assert_eq!(
-            parse_openssl_ed25519_pubkey(&pub_pem_key.as_bytes())
+            parse_openssl_ed25519_pubkey(pub_pem_key.as_bytes())
                 .unwrap()
                 .as_bytes(),
             pub_key.as_bytes()
         );
         let priv_pem_key = keypair.private_as_pem();
         assert_eq!(
-            &parse_openssl_ed25519_privkey(&priv_pem_key.as_bytes())
+            &parse_openssl_ed25519_privkey(priv_pem_key.as_bytes())
                 .unwrap()
                 .to_bytes(),
             &priv_key.to_bytes()
         );
+
+        // The public key is also valid for ED25519 signatures
+        let seed = parse_openssl_ed25519_signing_key(&keypair.private_der).unwrap();
+        assert_eq!(
+            parse_openssl_ed25519_verifying_key(&keypair.public_der).unwrap(),
+            ed25519_seed_to_public(&seed)
+        );
+    }
+
+    fn to_hex(data: &[u8]) -> String {
+        data.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    #[test]
+    fn seed_exports() {
+        // BIP39 test vector
+        let mnemonic = "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon about";
+        assert_eq!(
+            to_hex(&mnemonic_to_seed(mnemonic, "TREZOR")),
+            "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d18264c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+        );
+        // Words are only separated by spaces
+        assert_eq!(
+            mnemonic_to_seed(&mnemonic.replace(' ', "\n  "), "TREZOR")[..],
+            mnemonic_to_seed(mnemonic, "TREZOR")[..]
+        );
+        assert!(check_mnemonic(mnemonic).is_ok());
+        assert!(check_mnemonic(&mnemonic.replace(' ', "\n  ")).is_ok());
+        // Wrong checksum
+        assert!(check_mnemonic(&mnemonic.replace("about", "abandon")).is_err());
+        // Unknown word
+        assert!(check_mnemonic(&mnemonic.replace("about", "mla")).is_err());
+        // Too few words
+        assert!(check_mnemonic("legal winner thank year wave sausage").is_err());
+
+        // SLIP-0010 test vector 1, master key
+        let seed = [
+            0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d,
+            0x0e, 0x0f,
+        ];
+        let keypair = generate_keypair_from_seed(&seed).unwrap();
+        assert_eq!(
+            to_hex(&parse_openssl_ed25519_signing_key(&keypair.private_der).unwrap()),
+            "2b4be7f19ee27bbf30c667b642d5f4aa69fd169872f8fc3059c08ebae2eb19e7"
+        );
+        assert_eq!(
+            to_hex(&parse_openssl_ed25519_verifying_key(&keypair.public_der).unwrap()),
+            "a4b2856bfec510abab89753fac1ac0e1112364e7d250545963f135f2a33188ed"
+        );
+        // Reproducible
+        assert_eq!(
+            generate_keypair_from_seed(&seed).unwrap().private_der[..],
+            keypair.private_der[..]
+        );
+        // Too short
+        assert!(generate_keypair_from_seed(&seed[..8]).is_none());
+    }
+
+    #[test]
+    fn fingerprints() {
+        let pub_key = parse_openssl_ed25519_pubkey(PEM_PUB).unwrap();
+        let fingerprint = pub_key.fingerprint();
+        assert!(fingerprint.starts_with("SHA256:"));
+        assert_eq!(fingerprint.len(), "SHA256:".len() + 64);
+        // Same key, other formats
+        assert_eq!(
+            parse_openssl_ed25519_pubkey(DER_PUB).unwrap().fingerprint(),
+            fingerprint
+        );
+        assert_eq!(
+            parse_openssh_ed25519_pubkey(OPENSSH_PUB)
+                .unwrap()
+                .fingerprint(),
+            fingerprint
+        );
+        assert_eq!(
+            PublicKey::from(&parse_openssl_ed25519_privkey(PEM_PRIV).unwrap()).fingerprint(),
+            fingerprint
+        );
+        assert_ne!(
+            parse_openssl_ed25519_pubkey(PEM_PUB_2)
+                .unwrap()
+                .fingerprint(),
+            fingerprint
+        );
     }
 }
@@ -0,0 +1,190 @@
+#ifndef MLA_H
+#define MLA_H
+
+/* Generated by cbindgen from mla-ffi, do not edit */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Result of every function
+typedef enum MLAStatus {
+  MLA_STATUS_SUCCESS = 0,
+  // A NULL pointer, or a string which is not valid UTF-8
+  MLA_STATUS_INVALID_ARGUMENT = 1,
+  // A key is not in the expected format (Ed25519, DER or PEM)
+  MLA_STATUS_INVALID_KEY = 2,
+  // I/O error, on the archive or a given file descriptor
+  MLA_STATUS_IO_ERROR = 3,
+  // Error from the MLA library
+  MLA_STATUS_ARCHIVE_ERROR = 4,
+  // The requested file is not in the archive
+  MLA_STATUS_NOT_FOUND = 5,
+  // A callback returned a non-zero value
+  MLA_STATUS_CALLBACK_ERROR = 6,
+  // Unexpected internal error
+  MLA_STATUS_PANIC = 7,
+} MLAStatus;
+
+// Opened archive
+typedef struct MLAReader MLAReader;
+
+// Archive being created
+typedef struct MLAWriter MLAWriter;
+
+// Buffer given by the caller, such as a key
+typedef struct MLABuffer {
+  const uint8_t *data;
+  uintptr_t len;
+} MLABuffer;
+
+// Receive `len` bytes at `data`, and return 0 on success
+//
+// `context` is the pointer given with the callback
+typedef int (*MLAWriteCallback)(void *context, const uint8_t *data, uintptr_t len);
+
+// Receive the NUL-terminated name of a file, and return 0 to continue
+typedef int (*MLAFileCallback)(void *context, const char *name);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Description of the last error of the calling thread, or NULL
+//
+// The string is owned by the library, and valid until the next failing call
+// in this thread
+const char *mla_last_error(void);
+
+// Create an archive at `path`, compressed, and encrypted for the
+// `public_keys_count` Ed25519 public keys (DER or PEM) of `public_keys`, if
+// any. On success, `*writer` is set
+//
+// # Safety
+//
+// Pointers must be valid, `public_keys` pointing to `public_keys_count`
+// buffers (or NULL if there is none)
+MLAStatus mla_writer_new(const char *path,
+                         const MLABuffer *public_keys,
+                         uintptr_t public_keys_count,
+                         MLAWriter **writer);
+
+// Like `mla_writer_new`, the archive being given to `write` instead of being
+// written to a file
+//
+// # Safety
+//
+// Same as `mla_writer_new`. `context` is given as is to `write`
+MLAStatus mla_writer_new_with_callback(MLAWriteCallback write,
+                                       void *context,
+                                       const MLABuffer *public_keys,
+                                       uintptr_t public_keys_count,
+                                       MLAWriter **writer);
+
+// Start a file named `name`, whose content is then given by parts with
+// `mla_writer_append*`, until `mla_writer_end_file`. On success, `*id` is set
+//
+// Several files can be in progress at the same time
+//
+// # Safety
+//
+// Pointers must be valid
+MLAStatus mla_writer_start_file(MLAWriter *writer, const char *name, uint64_t *id);
+
+// Append the `len` bytes of `data` to the file `id`
+//
+// # Safety
+//
+// `writer` must be valid, and `data` point to `len` bytes
+MLAStatus mla_writer_append(MLAWriter *writer, uint64_t id, const uint8_t *data, uintptr_t len);
+
+#if defined(DEFINE_UNIX)
+// Append what is read from `fd` to the file `id`, until the end of `fd`
+//
+// `fd` is read by chunks, and is not closed
+//
+// # Safety
+//
+// `writer` must be valid, and `fd` an open file descriptor
+MLAStatus mla_writer_append_fd(MLAWriter *writer, uint64_t id, int fd);
+#endif
+
+// End the file `id`. No more content can be appended to it
+//
+// # Safety
+//
+// `writer` must be valid
+MLAStatus mla_writer_end_file(MLAWriter *writer, uint64_t id);
+
+// Add a file named `name`, made of the `len` bytes of `data`
+//
+// # Safety
+//
+// Pointers must be valid, `data` pointing to `len` bytes
+MLAStatus mla_writer_add_file(MLAWriter *writer,
+                              const char *name,
+                              const uint8_t *data,
+                              uintptr_t len);
+
+// Write the end of the archive. No more files can be added; the writer must
+// still be freed
+//
+// # Safety
+//
+// `writer` must be valid
+MLAStatus mla_writer_finalize(MLAWriter *writer);
+
+// Free a writer, closing its file. An archive not finalized is unusable
+//
+// # Safety
+//
+// `writer` must be NULL, or returned by `mla_writer_new*` and not already
+// freed
+void mla_writer_free(MLAWriter *writer);
+
+// Open the archive at `path`, with the `private_keys_count` candidate Ed25519
+// private keys (DER or PEM) of `private_keys` if it is encrypted. On success,
+// `*reader` is set
+//
+// # Safety
+//
+// Pointers must be valid, `private_keys` pointing to `private_keys_count`
+// buffers (or NULL if there is none)
+MLAStatus mla_reader_open(const char *path,
+                          const MLABuffer *private_keys,
+                          uintptr_t private_keys_count,
+                          MLAReader **reader);
+
+// Call `callback` with the name of each file of the archive, sorted
+//
+// # Safety
+//
+// `reader` must be valid. `context` is given as is to `callback`
+MLAStatus mla_reader_list(MLAReader *reader, MLAFileCallback callback, void *context);
+
+// Give the content of the file `name` to `write`, by chunks
+//
+// Return `MLA_STATUS_NOT_FOUND` if the file is not in the archive
+//
+// # Safety
+//
+// Pointers must be valid. `context` is given as is to `write`
+MLAStatus mla_reader_extract(MLAReader *reader,
+                             const char *name,
+                             MLAWriteCallback write,
+                             void *context);
+
+// Free a reader, closing its file
+//
+// # Safety
+//
+// `reader` must be NULL, or returned by `mla_reader_open` and not already
+// freed
+void mla_reader_free(MLAReader *reader);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* MLA_H */
@@ -0,0 +1,327 @@
+#ifndef MLA_HPP
+#define MLA_HPP
+
+// C++ wrapper over mla.h, header-only (C++11)
+//
+// Writers and readers own their underlying MLA object, freed on destruction.
+// Failing calls throw an exception derived from `mla::Error`, depending on the
+// `MLAStatus` returned
+
+#include <cstddef>
+#include <cstdint>
+#include <exception>
+#include <istream>
+#include <memory>
+#include <ostream>
+#include <sstream>
+#include <stdexcept>
+#include <string>
+#include <utility>
+#include <vector>
+
+#include "mla.h"
+
+namespace mla {
+
+// ---------- Errors ----------
+
+// Error of a call, with its status and the description of `mla_last_error`
+class Error : public std::runtime_error {
+public:
+    Error(MLAStatus status, const std::string &message)
+        : std::runtime_error(message), status_(status) {}
+
+    MLAStatus status() const noexcept { return status_; }
+
+private:
+    MLAStatus status_;
+};
+
+// MLA_STATUS_INVALID_ARGUMENT
+class InvalidArgument : public Error {
+public:
+    using Error::Error;
+};
+
+// MLA_STATUS_INVALID_KEY
+class InvalidKey : public Error {
+public:
+    using Error::Error;
+};
+
+// MLA_STATUS_IO_ERROR
+class IOError : public Error {
+public:
+    using Error::Error;
+};
+
+// MLA_STATUS_ARCHIVE_ERROR
+class ArchiveError : public Error {
+public:
+    using Error::Error;
+};
+
+// MLA_STATUS_NOT_FOUND
+class NotFound : public Error {
+public:
+    using Error::Error;
+};
+
+// MLA_STATUS_CALLBACK_ERROR, when the callback did not throw itself
+class CallbackError : public Error {
+public:
+    using Error::Error;
+};
+
+// MLA_STATUS_PANIC
+class Panic : public Error {
+public:
+    using Error::Error;
+};
+
+namespace detail {
+
+// Throw the exception corresponding to `status`, if it is not a success
+inline void check(MLAStatus status) {
+    if (status == MLA_STATUS_SUCCESS) {
+        return;
+    }
+    const char *last_error = mla_last_error();
+    const std::string message = last_error != nullptr ? last_error : "unknown error";
+    switch (status) {
+    case MLA_STATUS_INVALID_ARGUMENT:
+        throw InvalidArgument(status, message);
+    case MLA_STATUS_INVALID_KEY:
+        throw InvalidKey(status, message);
+    case MLA_STATUS_IO_ERROR:
+        throw IOError(status, message);
+    case MLA_STATUS_ARCHIVE_ERROR:
+        throw ArchiveError(status, message);
+    case MLA_STATUS_NOT_FOUND:
+        throw NotFound(status, message);
+    case MLA_STATUS_CALLBACK_ERROR:
+        throw CallbackError(status, message);
+    case MLA_STATUS_PANIC:
+        throw Panic(status, message);
+    default:
+        throw Error(status, message);
+    }
+}
+
+// Exceptions must not cross the C API: callbacks keep the first one raised,
+// to be rethrown once the call returns
+struct CallbackContext {
+    std::exception_ptr exception;
+
+    void check(MLAStatus status) {
+        if (exception) {
+            std::exception_ptr raised = exception;
+            exception = nullptr;
+            std::rethrow_exception(raised);
+        }
+        detail::check(status);
+    }
+};
+
+struct StreamContext : CallbackContext {
+    std::ostream *out = nullptr;
+};
+
+inline int write_stream(void *context, const uint8_t *data, uintptr_t len) {
+    StreamContext *stream = static_cast<StreamContext *>(context);
+    try {
+        stream->out->write(reinterpret_cast<const char *>(data),
+                           static_cast<std::streamsize>(len));
+        if (!*stream->out) {
+            throw std::ios_base::failure("unable to write to the output stream");
+        }
+        return 0;
+    } catch (...) {
+        stream->exception = std::current_exception();
+        return -1;
+    }
+}
+
+struct NamesContext : CallbackContext {
+    std::vector<std::string> names;
+};
+
+inline int collect_name(void *context, const char *name) {
+    NamesContext *names = static_cast<NamesContext *>(context);
+    try {
+        names->names.push_back(name);
+        return 0;
+    } catch (...) {
+        names->exception = std::current_exception();
+        return -1;
+    }
+}
+
+// Keys, as given to `mla_writer_new*` and `mla_reader_open`
+inline std::vector<MLABuffer> buffers(const std::vector<std::string> &keys) {
+    std::vector<MLABuffer> result;
+    result.reserve(keys.size());
+    for (const std::string &key : keys) {
+        MLABuffer buffer;
+        buffer.data = reinterpret_cast<const uint8_t *>(key.data());
+        buffer.len = key.size();
+        result.push_back(buffer);
+    }
+    return result;
+}
+
+} // namespace detail
+
+// ---------- Writer ----------
+
+// Archive being created, compressed, and encrypted if public keys are given
+//
+// The archive must be finalized with `finalize`; otherwise, it is unusable
+class ArchiveWriter {
+public:
+    // Create an archive at `path`, encrypted for `public_keys` (Ed25519, DER
+    // or PEM), if any
+    explicit ArchiveWriter(const std::string &path,
+                           const std::vector<std::string> &public_keys = {})
+        : context_(new detail::StreamContext()) {
+        std::vector<MLABuffer> keys = detail::buffers(public_keys);
+        detail::check(
+            mla_writer_new(path.c_str(), keys.data(), keys.size(), &writer_));
+    }
+
+    // Create an archive written to `out`, which must outlive the writer
+    explicit ArchiveWriter(std::ostream &out,
+                           const std::vector<std::string> &public_keys = {})
+        : context_(new detail::StreamContext()) {
+        context_->out = &out;
+        std::vector<MLABuffer> keys = detail::buffers(public_keys);
+        context_->check(mla_writer_new_with_callback(
+            detail::write_stream, context_.get(), keys.data(), keys.size(), &writer_));
+    }
+
+    ~ArchiveWriter() { mla_writer_free(writer_); }
+
+    ArchiveWriter(const ArchiveWriter &) = delete;
+    ArchiveWriter &operator=(const ArchiveWriter &) = delete;
+
+    // The stream context is allocated apart, its address being given to the
+    // C API: moving the writer keeps it valid
+    ArchiveWriter(ArchiveWriter &&other) noexcept
+        : writer_(other.writer_), context_(std::move(other.context_)) {
+        other.writer_ = nullptr;
+    }
+
+    ArchiveWriter &operator=(ArchiveWriter &&other) noexcept {
+        std::swap(writer_, other.writer_);
+        std::swap(context_, other.context_);
+        return *this;
+    }
+
+    // Add a file named `name`, made of `data`
+    void add_file(const std::string &name, const std::string &data) {
+        context_->check(mla_writer_add_file(
+            writer_, name.c_str(), reinterpret_cast<const uint8_t *>(data.data()),
+            data.size()));
+    }
+
+    // Add a file named `name`, made of what is read from `in` until its end
+    void add_file(const std::string &name, std::istream &in) {
+        uint64_t id = start_file(name);
+        std::vector<char> buffer(1024 * 1024);
+        while (in) {
+            in.read(buffer.data(), static_cast<std::streamsize>(buffer.size()));
+            std::streamsize read = in.gcount();
+            if (read > 0) {
+                append(id, buffer.data(), static_cast<size_t>(read));
+            }
+        }
+        if (in.bad()) {
+            throw std::ios_base::failure("unable to read the input stream");
+        }
+        end_file(id);
+    }
+
+    // Start a file named `name`, whose content is then given by parts with
+    // `append`, until `end_file`
+    uint64_t start_file(const std::string &name) {
+        uint64_t id = 0;
+        context_->check(mla_writer_start_file(writer_, name.c_str(), &id));
+        return id;
+    }
+
+    // Append the `len` bytes of `data` to the file `id`
+    void append(uint64_t id, const void *data, size_t len) {
+        context_->check(mla_writer_append(
+            writer_, id, static_cast<const uint8_t *>(data), len));
+    }
+
+    // End the file `id`
+    void end_file(uint64_t id) { context_->check(mla_writer_end_file(writer_, id)); }
+
+    // Write the end of the archive. No more files can be added
+    void finalize() { context_->check(mla_writer_finalize(writer_)); }
+
+private:
+    MLAWriter *writer_ = nullptr;
+    std::unique_ptr<detail::StreamContext> context_;
+};
+
+// ---------- Reader ----------
+
+// Opened archive
+class ArchiveReader {
+public:
+    // Open the archive at `path`, with the candidate `private_keys` (Ed25519,
+    // DER or PEM) if it is encrypted
+    explicit ArchiveReader(const std::string &path,
+                           const std::vector<std::string> &private_keys = {}) {
+        std::vector<MLABuffer> keys = detail::buffers(private_keys);
+        detail::check(
+            mla_reader_open(path.c_str(), keys.data(), keys.size(), &reader_));
+    }
+
+    ~ArchiveReader() { mla_reader_free(reader_); }
+
+    ArchiveReader(const ArchiveReader &) = delete;
+    ArchiveReader &operator=(const ArchiveReader &) = delete;
+
+    ArchiveReader(ArchiveReader &&other) noexcept : reader_(other.reader_) {
+        other.reader_ = nullptr;
+    }
+
+    ArchiveReader &operator=(ArchiveReader &&other) noexcept {
+        std::swap(reader_, other.reader_);
+        return *this;
+    }
+
+    // Names of the files of the archive, sorted
+    std::vector<std::string> list() {
+        detail::NamesContext context;
+        context.check(mla_reader_list(reader_, detail::collect_name, &context));
+        return std::move(context.names);
+    }
+
+    // Write the content of the file `name` to `out`
+    //
+    // Throw `mla::NotFound` if the file is not in the archive
+    void extract(const std::string &name, std::ostream &out) {
+        detail::StreamContext context;
+        context.out = &out;
+        context.check(
+            mla_reader_extract(reader_, name.c_str(), detail::write_stream, &context));
+    }
+
+    // Content of the file `name`
+    std::string read(const std::string &name) {
+        std::ostringstream out;
+        extract(name, out);
+        return out.str();
+    }
+
+private:
+    MLAReader *reader_ = nullptr;
+};
+
+} // namespace mla
+
+#endif /* MLA_HPP */
@@ -0,0 +1,687 @@
+//! C bindings for MLA
+//!
+//! The API is declared in `include/mla.h`, generated with cbindgen (see
+//! README.md). Every function returns a `MLAStatus`; on failure, a description
+//! of the error is available through `mla_last_error`.
+//!
+//! Writers and readers are opaque objects, owned by the caller until given to
+//! `mla_writer_free` / `mla_reader_free`. They must not be used by several
+//! threads at the same time.
+use std::cell::RefCell;
+use std::ffi::{CStr, CString};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::raw::{c_char, c_int, c_void};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+use ed25519_parser::{parse_openssl_ed25519_privkey, parse_openssl_ed25519_pubkey};
+use mla::config::{ArchiveReaderConfig, ArchiveWriterConfig};
+use mla::errors::Error;
+
+/// Size of the chunks read from a file descriptor
+const FD_CHUNK_SIZE: usize = 1024 * 1024;
+
+// ---------- Errors ----------
+
+/// Result of every function
+#[repr(C)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MLAStatus {
+    Success = 0,
+    /// A NULL pointer, or a string which is not valid UTF-8
+    InvalidArgument = 1,
+    /// A key is not in the expected format (Ed25519, DER or PEM)
+    InvalidKey = 2,
+    /// I/O error, on the archive or a given file descriptor
+    IOError = 3,
+    /// Error from the MLA library
+    ArchiveError = 4,
+    /// The requested file is not in the archive
+    NotFound = 5,
+    /// A callback returned a non-zero value
+    CallbackError = 6,
+    /// Unexpected internal error
+    Panic = 7,
+}
+
+struct FfiError(MLAStatus, String);
+
+impl From<Error> for FfiError {
+    fn from(error: Error) -> Self {
+        match error.root() {
+            Error::IOError(_) => FfiError(MLAStatus::IOError, error.to_string()),
+            _ => FfiError(MLAStatus::ArchiveError, error.to_string()),
+        }
+    }
+}
+
+impl From<io::Error> for FfiError {
+    fn from(error: io::Error) -> Self {
+        FfiError(MLAStatus::IOError, error.to_string())
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Run `f`, recording its error, if any, for `mla_last_error`
+///
+/// Panics must not unwind into the caller's code: they are reported as
+/// `MLAStatus::Panic`
+fn ffi_call<F: FnOnce() -> Result<(), FfiError>>(f: F) -> MLAStatus {
+    let (status, message) = match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(())) => return MLAStatus::Success,
+        Ok(Err(FfiError(status, message))) => (status, message),
+        Err(_) => (MLAStatus::Panic, "Unexpected panic".to_string()),
+    };
+    LAST_ERROR.with(|last| *last.borrow_mut() = CString::new(message).ok());
+    status
+}
+
+/// Description of the last error of the calling thread, or NULL
+///
+/// The string is owned by the library, and valid until the next failing call
+/// in this thread
+#[no_mangle]
+pub extern "C" fn mla_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| match &*last.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+// ---------- Arguments ----------
+
+/// Buffer given by the caller, such as a key
+#[repr(C)]
+pub struct MLABuffer {
+    pub data: *const u8,
+    pub len: usize,
+}
+
+/// Receive `len` bytes at `data`, and return 0 on success
+///
+/// `context` is the pointer given with the callback
+pub type MLAWriteCallback =
+    extern "C" fn(context: *mut c_void, data: *const u8, len: usize) -> c_int;
+
+/// Receive the NUL-terminated name of a file, and return 0 to continue
+pub type MLAFileCallback = extern "C" fn(context: *mut c_void, name: *const c_char) -> c_int;
+
+fn invalid_argument(message: &str) -> FfiError {
+    FfiError(MLAStatus::InvalidArgument, message.to_string())
+}
+
+/// # Safety
+///
+/// `ptr` must be NULL or a valid NUL-terminated string
+unsafe fn str_arg<'a>(ptr: *const c_char, what: &str) -> Result<&'a str, FfiError> {
+    if ptr.is_null() {
+        return Err(invalid_argument(&format!("{} is NULL", what)));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map_err(|_| invalid_argument(&format!("{} is not valid UTF-8", what)))
+}
+
+/// # Safety
+///
+/// `data` must be NULL with `len` 0, or point to `len` readable bytes
+unsafe fn slice_arg<'a>(data: *const u8, len: usize) -> Result<&'a [u8], FfiError> {
+    if len == 0 {
+        Ok(&[])
+    } else if data.is_null() {
+        Err(invalid_argument("data is NULL"))
+    } else {
+        Ok(std::slice::from_raw_parts(data, len))
+    }
+}
+
+/// # Safety
+///
+/// `buffers` must be NULL with `count` 0, or point to `count` valid
+/// `MLABuffer`
+unsafe fn buffers_arg<'a>(
+    buffers: *const MLABuffer,
+    count: usize,
+) -> Result<Vec<&'a [u8]>, FfiError> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+    if buffers.is_null() {
+        return Err(invalid_argument("buffers are NULL"));
+    }
+    std::slice::from_raw_parts(buffers, count)
+        .iter()
+        .map(|buffer| slice_arg(buffer.data, buffer.len))
+        .collect()
+}
+
+/// Calls `callback` with written data
+struct CallbackWriter {
+    callback: MLAWriteCallback,
+    context: *mut c_void,
+    /// Set once the callback returned an error
+    failed: bool,
+}
+
+impl CallbackWriter {
+    fn new(callback: MLAWriteCallback, context: *mut c_void) -> Self {
+        CallbackWriter {
+            callback,
+            context,
+            failed: false,
+        }
+    }
+}
+
+impl Write for CallbackWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if (self.callback)(self.context, buf.as_ptr(), buf.len()) != 0 {
+            self.failed = true;
+            return Err(io::Error::other("The write callback failed"));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// ---------- Writer ----------
+
+/// Archive being created
+pub struct MLAWriter {
+    inner: mla::ArchiveWriter<'static, Box<dyn Write>>,
+}
+
+/// Configuration of a new archive: compressed, and encrypted if public keys are
+/// given
+fn writer_config(public_keys: &[&[u8]]) -> Result<ArchiveWriterConfig, FfiError> {
+    let mut config = ArchiveWriterConfig::new();
+    config.enable_layer(mla::Layers::COMPRESS);
+    if !public_keys.is_empty() {
+        let mut keys = Vec::new();
+        for key in public_keys {
+            keys.push(
+                parse_openssl_ed25519_pubkey(key).map_err(|_| {
+                    FfiError(MLAStatus::InvalidKey, "Invalid public key".to_string())
+                })?,
+            );
+        }
+        config
+            .enable_layer(mla::Layers::ENCRYPT)
+            .add_public_keys(&keys);
+    }
+    Ok(config)
+}
+
+/// # Safety
+///
+/// `writer` must be NULL or a valid pointer
+unsafe fn new_writer(
+    dest: Box<dyn Write>,
+    config: ArchiveWriterConfig,
+    writer: *mut *mut MLAWriter,
+) -> Result<(), FfiError> {
+    if writer.is_null() {
+        return Err(invalid_argument("writer is NULL"));
+    }
+    let inner = mla::ArchiveWriter::from_config(dest, config)?;
+    *writer = Box::into_raw(Box::new(MLAWriter { inner }));
+    Ok(())
+}
+
+/// # Safety
+///
+/// `writer` must be NULL or returned by `mla_writer_new*`, and not freed
+unsafe fn writer_arg<'a>(writer: *mut MLAWriter) -> Result<&'a mut MLAWriter, FfiError> {
+    writer
+        .as_mut()
+        .ok_or_else(|| invalid_argument("writer is NULL"))
+}
+
+/// Create an archive at `path`, compressed, and encrypted for the
+/// `public_keys_count` Ed25519 public keys (DER or PEM) of `public_keys`, if
+/// any. On success, `*writer` is set
+///
+/// # Safety
+///
+/// Pointers must be valid, `public_keys` pointing to `public_keys_count`
+/// buffers (or NULL if there is none)
+#[no_mangle]
+pub unsafe extern "C" fn mla_writer_new(
+    path: *const c_char,
+    public_keys: *const MLABuffer,
+    public_keys_count: usize,
+    writer: *mut *mut MLAWriter,
+) -> MLAStatus {
+    ffi_call(|| {
+        let path = str_arg(path, "path")?;
+        let config = writer_config(&buffers_arg(public_keys, public_keys_count)?)?;
+        new_writer(Box::new(File::create(path)?), config, writer)
+    })
+}
+
+/// Like `mla_writer_new`, the archive being given to `write` instead of being
+/// written to a file
+///
+/// # Safety
+///
+/// Same as `mla_writer_new`. `context` is given as is to `write`
+#[no_mangle]
+pub unsafe extern "C" fn mla_writer_new_with_callback(
+    write: Option<MLAWriteCallback>,
+    context: *mut c_void,
+    public_keys: *const MLABuffer,
+    public_keys_count: usize,
+    writer: *mut *mut MLAWriter,
+) -> MLAStatus {
+    ffi_call(|| {
+        let callback = write.ok_or_else(|| invalid_argument("write is NULL"))?;
+        let config = writer_config(&buffers_arg(public_keys, public_keys_count)?)?;
+        new_writer(
+            Box::new(CallbackWriter::new(callback, context)),
+            config,
+            writer,
+        )
+    })
+}
+
+/// Start a file named `name`, whose content is then given by parts with
+/// `mla_writer_append*`, until `mla_writer_end_file`. On success, `*id` is set
+///
+/// Several files can be in progress at the same time
+///
+/// # Safety
+///
+/// Pointers must be valid
+#[no_mangle]
+pub unsafe extern "C" fn mla_writer_start_file(
+    writer: *mut MLAWriter,
+    name: *const c_char,
+    id: *mut u64,
+) -> MLAStatus {
+    ffi_call(|| {
+        let writer = writer_arg(writer)?;
+        let name = str_arg(name, "name")?;
+        if id.is_null() {
+            return Err(invalid_argument("id is NULL"));
+        }
+        *id = writer.inner.start_file(name)?;
+        Ok(())
+    })
+}
+
+/// Append the `len` bytes of `data` to the file `id`
+///
+/// # Safety
+///
+/// `writer` must be valid, and `data` point to `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn mla_writer_append(
+    writer: *mut MLAWriter,
+    id: u64,
+    data: *const u8,
+    len: usize,
+) -> MLAStatus {
+    ffi_call(|| {
+        let writer = writer_arg(writer)?;
+        let data = slice_arg(data, len)?;
+        Ok(writer
+            .inner
+            .append_file_content(id, data.len() as u64, data)?)
+    })
+}
+
+/// Append what is read from `fd` to the file `id`, until the end of `fd`
+///
+/// `fd` is read by chunks, and is not closed
+///
+/// # Safety
+///
+/// `writer` must be valid, and `fd` an open file descriptor
+#[cfg(unix)]
+#[no_mangle]
+pub unsafe extern "C" fn mla_writer_append_fd(
+    writer: *mut MLAWriter,
+    id: u64,
+    fd: c_int,
+) -> MLAStatus {
+    use std::os::unix::io::FromRawFd;
+
+    ffi_call(|| {
+        let writer = writer_arg(writer)?;
+        // The file descriptor is owned by the caller: do not close it
+        let mut file = std::mem::ManuallyDrop::new(File::from_raw_fd(fd));
+        let mut chunk = vec![0u8; FD_CHUNK_SIZE];
+        loop {
+            let count = match file.read(&mut chunk) {
+                Ok(0) => return Ok(()),
+                Ok(count) => count,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err.into()),
+            };
+            writer
+                .inner
+                .append_file_content(id, count as u64, &chunk[..count])?;
+        }
+    })
+}
+
+/// End the file `id`. No more content can be appended to it
+///
+/// # Safety
+///
+/// `writer` must be valid
+#[no_mangle]
+pub unsafe extern "C" fn mla_writer_end_file(writer: *mut MLAWriter, id: u64) -> MLAStatus {
+    ffi_call(|| Ok(writer_arg(writer)?.inner.end_file(id)?))
+}
+
+/// Add a file named `name`, made of the `len` bytes of `data`
+///
+/// # Safety
+///
+/// Pointers must be valid, `data` pointing to `len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn mla_writer_add_file(
+    writer: *mut MLAWriter,
+    name: *const c_char,
+    data: *const u8,
+    len: usize,
+) -> MLAStatus {
+    ffi_call(|| {
+        let writer = writer_arg(writer)?;
+        let name = str_arg(name, "name")?;
+        let data = slice_arg(data, len)?;
+        Ok(writer.inner.add_file(name, data.len() as u64, data)?)
+    })
+}
+
+/// Write the end of the archive. No more files can be added; the writer must
+/// still be freed
+///
+/// # Safety
+///
+/// `writer` must be valid
+#[no_mangle]
+pub unsafe extern "C" fn mla_writer_finalize(writer: *mut MLAWriter) -> MLAStatus {
+    ffi_call(|| {
+        let writer = writer_arg(writer)?;
+        writer.inner.finalize()?;
+        Ok(writer.inner.flush()?)
+    })
+}
+
+/// Free a writer, closing its file. An archive not finalized is unusable
+///
+/// # Safety
+///
+/// `writer` must be NULL, or returned by `mla_writer_new*` and not already
+/// freed
+#[no_mangle]
+pub unsafe extern "C" fn mla_writer_free(writer: *mut MLAWriter) {
+    if !writer.is_null() {
+        drop(Box::from_raw(writer));
+    }
+}
+
+// ---------- Reader ----------
+
+/// Opened archive
+pub struct MLAReader {
+    inner: mla::ArchiveReader<'static, File>,
+}
+
+/// # Safety
+///
+/// `reader` must be NULL or returned by `mla_reader_open`, and not freed
+unsafe fn reader_arg<'a>(reader: *mut MLAReader) -> Result<&'a mut MLAReader, FfiError> {
+    reader
+        .as_mut()
+        .ok_or_else(|| invalid_argument("reader is NULL"))
+}
+
+/// Open the archive at `path`, with the `private_keys_count` candidate Ed25519
+/// private keys (DER or PEM) of `private_keys` if it is encrypted. On success,
+/// `*reader` is set
+///
+/// # Safety
+///
+/// Pointers must be valid, `private_keys` pointing to `private_keys_count`
+/// buffers (or NULL if there is none)
+#[no_mangle]
+pub unsafe extern "C" fn mla_reader_open(
+    path: *const c_char,
+    private_keys: *const MLABuffer,
+    private_keys_count: usize,
+    reader: *mut *mut MLAReader,
+) -> MLAStatus {
+    ffi_call(|| {
+        let path = str_arg(path, "path")?;
+        if reader.is_null() {
+            return Err(invalid_argument("reader is NULL"));
+        }
+        let mut keys = Vec::new();
+        for key in buffers_arg(private_keys, private_keys_count)? {
+            keys.push(
+                parse_openssl_ed25519_privkey(key).map_err(|_| {
+                    FfiError(MLAStatus::InvalidKey, "Invalid private key".to_string())
+                })?,
+            );
+        }
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(&keys);
+        let inner = mla::ArchiveReader::from_config(File::open(path)?, config)?;
+        *reader = Box::into_raw(Box::new(MLAReader { inner }));
+        Ok(())
+    })
+}
+
+/// Call `callback` with the name of each file of the archive, sorted
+///
+/// # Safety
+///
+/// `reader` must be valid. `context` is given as is to `callback`
+#[no_mangle]
+pub unsafe extern "C" fn mla_reader_list(
+    reader: *mut MLAReader,
+    callback: Option<MLAFileCallback>,
+    context: *mut c_void,
+) -> MLAStatus {
+    ffi_call(|| {
+        let reader = reader_arg(reader)?;
+        let callback = callback.ok_or_else(|| invalid_argument("callback is NULL"))?;
+        let mut fnames: Vec<&String> = reader.inner.list_files()?.collect();
+        fnames.sort();
+        for fname in fnames {
+            let name = CString::new(fname.as_str()).map_err(|_| {
+                FfiError(
+                    MLAStatus::ArchiveError,
+                    format!("File name {:?} contains a NUL byte", fname),
+                )
+            })?;
+            if callback(context, name.as_ptr()) != 0 {
+                return Err(FfiError(
+                    MLAStatus::CallbackError,
+                    "The file callback failed".to_string(),
+                ));
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Give the content of the file `name` to `write`, by chunks
+///
+/// Return `MLA_STATUS_NOT_FOUND` if the file is not in the archive
+///
+/// # Safety
+///
+/// Pointers must be valid. `context` is given as is to `write`
+#[no_mangle]
+pub unsafe extern "C" fn mla_reader_extract(
+    reader: *mut MLAReader,
+    name: *const c_char,
+    write: Option<MLAWriteCallback>,
+    context: *mut c_void,
+) -> MLAStatus {
+    ffi_call(|| {
+        let reader = reader_arg(reader)?;
+        let name = str_arg(name, "name")?;
+        let callback = write.ok_or_else(|| invalid_argument("write is NULL"))?;
+        let mut subfile = match reader.inner.get_file(name.to_string())? {
+            Some(subfile) => subfile,
+            None => {
+                return Err(FfiError(
+                    MLAStatus::NotFound,
+                    format!("File {:?} not found", name),
+                ))
+            }
+        };
+        let mut dest = CallbackWriter::new(callback, context);
+        match io::copy(&mut subfile.data, &mut dest) {
+            Ok(_) => Ok(()),
+            Err(err) if dest.failed => Err(FfiError(MLAStatus::CallbackError, err.to_string())),
+            Err(err) => Err(err.into()),
+        }
+    })
+}
+
+/// Free a reader, closing its file
+///
+/// # Safety
+///
+/// `reader` must be NULL, or returned by `mla_reader_open` and not already
+/// freed
+#[no_mangle]
+pub unsafe extern "C" fn mla_reader_free(reader: *mut MLAReader) {
+    if !reader.is_null() {
+        drop(Box::from_raw(reader));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUB_KEY: &[u8] = include_bytes!("../../samples/test25519_pub.pem");
+    const PRIV_KEY: &[u8] = include_bytes!("../../samples/test25519.pem");
+
+    extern "C" fn collect_names(context: *mut c_void, name: *const c_char) -> c_int {
+        let names = unsafe { &mut *(context as *mut Vec<String>) };
+        names.push(
+            unsafe { CStr::from_ptr(name) }
+                .to_str()
+                .unwrap()
+                .to_string(),
+        );
+        0
+    }
+
+    extern "C" fn collect_data(context: *mut c_void, data: *const u8, len: usize) -> c_int {
+        let out = unsafe { &mut *(context as *mut Vec<u8>) };
+        out.extend_from_slice(unsafe { std::slice::from_raw_parts(data, len) });
+        0
+    }
+
+    #[test]
+    fn create_and_read() {
+        let dir = std::env::temp_dir();
+        let archive_path = dir.join(format!("mla-ffi-{}.mla", std::process::id()));
+        let path = CString::new(archive_path.to_string_lossy().as_bytes()).unwrap();
+        let public_key = MLABuffer {
+            data: PUB_KEY.as_ptr(),
+            len: PUB_KEY.len(),
+        };
+        let private_key = MLABuffer {
+            data: PRIV_KEY.as_ptr(),
+            len: PRIV_KEY.len(),
+        };
+
+        unsafe {
+            let mut writer = ptr::null_mut();
+            assert_eq!(
+                mla_writer_new(path.as_ptr(), &public_key, 1, &mut writer),
+                MLAStatus::Success
+            );
+            let name = CString::new("hello.txt").unwrap();
+            assert_eq!(
+                mla_writer_add_file(writer, name.as_ptr(), b"Hello".as_ptr(), 5),
+                MLAStatus::Success
+            );
+            // A file given by parts
+            let name = CString::new("parts.txt").unwrap();
+            let mut id = 0;
+            assert_eq!(
+                mla_writer_start_file(writer, name.as_ptr(), &mut id),
+                MLAStatus::Success
+            );
+            for part in &[&b"ABC"[..], &b"DEF"[..]] {
+                assert_eq!(
+                    mla_writer_append(writer, id, part.as_ptr(), part.len()),
+                    MLAStatus::Success
+                );
+            }
+            assert_eq!(mla_writer_end_file(writer, id), MLAStatus::Success);
+            // Errors are reported
+            assert_eq!(
+                mla_writer_add_file(writer, ptr::null(), ptr::null(), 0),
+                MLAStatus::InvalidArgument
+            );
+            assert!(!mla_last_error().is_null());
+            assert_eq!(mla_writer_finalize(writer), MLAStatus::Success);
+            mla_writer_free(writer);
+
+            // A key is required
+            let mut reader = ptr::null_mut();
+            assert_eq!(
+                mla_reader_open(path.as_ptr(), ptr::null(), 0, &mut reader),
+                MLAStatus::ArchiveError
+            );
+            assert_eq!(
+                mla_reader_open(path.as_ptr(), &private_key, 1, &mut reader),
+                MLAStatus::Success
+            );
+            let mut names: Vec<String> = Vec::new();
+            assert_eq!(
+                mla_reader_list(
+                    reader,
+                    Some(collect_names),
+                    &mut names as *mut Vec<String> as *mut c_void
+                ),
+                MLAStatus::Success
+            );
+            assert_eq!(names, vec!["hello.txt", "parts.txt"]);
+            let mut data: Vec<u8> = Vec::new();
+            let name = CString::new("parts.txt").unwrap();
+            assert_eq!(
+                mla_reader_extract(
+                    reader,
+                    name.as_ptr(),
+                    Some(collect_data),
+                    &mut data as *mut Vec<u8> as *mut c_void
+                ),
+                MLAStatus::Success
+            );
+            assert_eq!(data, b"ABCDEF");
+            let name = CString::new("unknown").unwrap();
+            assert_eq!(
+                mla_reader_extract(
+                    reader,
+                    name.as_ptr(),
+                    Some(collect_data),
+                    &mut data as *mut Vec<u8> as *mut c_void
+                ),
+                MLAStatus::NotFound
+            );
+            mla_reader_free(reader);
+        }
+
+        std::fs::remove_file(archive_path).unwrap();
+    }
+}
@@ -0,0 +1,48 @@
+//! Build `tests/cpp/wrapper.cpp` with the C++ wrapper of `include/mla.hpp`,
+//! against the static library, and run it
+#![cfg(unix)]
+
+use std::path::PathBuf;
+use std::process::Command;
+
+#[test]
+fn cpp_wrapper() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let samples = manifest_dir.join("../samples");
+    // Test binaries are in `target/<profile>/deps`, next to the libraries
+    let library = std::env::current_exe()
+        .unwrap()
+        .parent()
+        .unwrap()
+        .join("libmla_ffi.a");
+    assert!(library.exists(), "{} not built", library.display());
+
+    let out_dir = std::env::temp_dir().join(format!("mla-ffi-cpp-{}", std::process::id()));
+    std::fs::create_dir_all(&out_dir).unwrap();
+    let binary = out_dir.join("wrapper");
+    let compiler = std::env::var("CXX").unwrap_or_else(|_| "c++".to_string());
+    let status = Command::new(compiler)
+        .arg("-std=c++11")
+        .arg("-Wall")
+        .arg("-Werror")
+        .arg("-I")
+        .arg(manifest_dir.join("include"))
+        .arg(manifest_dir.join("tests/cpp/wrapper.cpp"))
+        .arg(&library)
+        .args(["-lpthread", "-ldl", "-lm"])
+        .arg("-o")
+        .arg(&binary)
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let status = Command::new(&binary)
+        .arg(samples.join("test25519_pub.pem"))
+        .arg(samples.join("test25519.pem"))
+        .arg(out_dir.join("archive.mla"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    std::fs::remove_dir_all(out_dir).unwrap();
+}
@@ -0,0 +1,110 @@
+// Use of include/mla.hpp, built and run by tests/cpp.rs
+//
+// Usage: wrapper <public key> <private key> <archive path>
+
+#include <cstdlib>
+#include <fstream>
+#include <iostream>
+#include <sstream>
+#include <string>
+#include <utility>
+#include <vector>
+
+#include "mla.hpp"
+
+#define CHECK(cond)                                                          \
+    do {                                                                     \
+        if (!(cond)) {                                                       \
+            std::cerr << __FILE__ << ":" << __LINE__ << ": " #cond << "\n"; \
+            std::exit(1);                                                    \
+        }                                                                    \
+    } while (0)
+
+static std::string read_file(const char *path) {
+    std::ifstream in(path, std::ios::binary);
+    std::ostringstream content;
+    content << in.rdbuf();
+    return content.str();
+}
+
+int main(int argc, char **argv) {
+    CHECK(argc == 4);
+    const std::string public_key = read_file(argv[1]);
+    const std::string private_key = read_file(argv[2]);
+    const std::string path = argv[3];
+
+    // Archive written to a file, by a moved writer
+    {
+        mla::ArchiveWriter writer(path, {public_key});
+        writer.add_file("hello.txt", "Hello");
+        std::istringstream content("ABCDEF");
+        mla::ArchiveWriter moved(std::move(writer));
+        moved.add_file("stream.txt", content);
+        uint64_t id = moved.start_file("parts.txt");
+        moved.append(id, "ABC", 3);
+        moved.append(id, "DEF", 3);
+        moved.end_file(id);
+        moved.finalize();
+    }
+
+    {
+        mla::ArchiveReader reader(path, {private_key});
+        std::vector<std::string> expected = {"hello.txt", "parts.txt", "stream.txt"};
+        CHECK(reader.list() == expected);
+        CHECK(reader.read("hello.txt") == "Hello");
+        CHECK(reader.read("parts.txt") == "ABCDEF");
+        CHECK(reader.read("stream.txt") == "ABCDEF");
+
+        // Errors are mapped to exceptions
+        bool thrown = false;
+        try {
+            reader.read("unknown");
+        } catch (const mla::NotFound &error) {
+            CHECK(error.status() == MLA_STATUS_NOT_FOUND);
+            thrown = true;
+        }
+        CHECK(thrown);
+    }
+
+    // A key is required
+    bool thrown = false;
+    try {
+        mla::ArchiveReader reader(path);
+    } catch (const mla::ArchiveError &error) {
+        CHECK(std::string(error.what()).size() > 0);
+        thrown = true;
+    }
+    CHECK(thrown);
+
+    thrown = false;
+    try {
+        mla::ArchiveWriter writer(path + ".bad", {"not a key"});
+    } catch (const mla::InvalidKey &) {
+        thrown = true;
+    }
+    CHECK(thrown);
+
+    // Archive given to a stream
+    std::ostringstream out;
+    {
+        mla::ArchiveWriter writer(out);
+        writer.add_file("in_memory.txt", "data");
+        writer.finalize();
+    }
+    CHECK(!out.str().empty());
+
+    // Exceptions raised by the stream are given back to the caller
+    std::ofstream closed;
+    thrown = false;
+    try {
+        closed.exceptions(std::ios::badbit | std::ios::failbit);
+        mla::ArchiveWriter writer(closed);
+        writer.add_file("lost.txt", "data");
+        writer.finalize();
+    } catch (const std::ios_base::failure &) {
+        thrown = true;
+    }
+    CHECK(thrown);
+
+    return 0;
+}
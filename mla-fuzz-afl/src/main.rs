@@ -1,5 +1,6 @@
-#[macro_use]
-extern crate afl;
+#[cfg(fuzzing)]
+use afl::fuzz;
+use bincode::Options;
 use ed25519_parser::{parse_openssl_ed25519_privkey, parse_openssl_ed25519_pubkey};
 use serde::{Deserialize, Serialize};
 use std::fs::File;
@@ -27,8 +28,9 @@ struct TestInput {
 fn run(data: &[u8]) {
     // Retrieve the input as a configuration
     // => Lot of failed here, but eventually AFL will be able to bypass it
-    let test_case: TestInput = bincode::config()
-        .limit(10 * 1024 * 1024)
+    let test_case: TestInput = bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_limit(10 * 1024 * 1024)
         .deserialize_from(data)
         .unwrap_or(TestInput {
             filenames: Vec::new(),
@@ -47,8 +49,9 @@ fn run(data: &[u8]) {
     // Create a MLA Archive
     let mut buf = Vec::new();
     let mut config = ArchiveWriterConfig::new();
+    // Signing needs a dedicated key, which is not part of the fuzzed input
     config
-        .set_layers(test_case.layers)
+        .set_layers(test_case.layers & !Layers::SIGN)
         .add_public_keys(&[public_key]);
     let mut mla = ArchiveWriter::from_config(&mut buf, config).unwrap();
 
@@ -97,7 +100,7 @@ fn run(data: &[u8]) {
         if !filename2content.contains_key(fname) {
             num2id.insert(
                 i as u8,
-                match mla.start_file(&fname) {
+                match mla.start_file(fname) {
                     Err(Error::DuplicateFilename) => {
                         return;
                     }
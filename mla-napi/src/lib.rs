@@ -0,0 +1,206 @@
+//! Node.js bindings for MLA, based on napi-rs
+//!
+//! Files can be added to an archive in a streaming fashion (`startFile`,
+//! `appendFileContent`, `endFile`), and extracted to disk without being loaded
+//! in memory.
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Component, Path, PathBuf};
+
+use ed25519_parser::{parse_openssl_ed25519_privkey, parse_openssl_ed25519_pubkey};
+use mla::config::{ArchiveReaderConfig, ArchiveWriterConfig};
+use napi::bindgen_prelude::Buffer;
+use napi::{Error, Result};
+use napi_derive::napi;
+
+/// Convert a MLA error to a JavaScript exception
+fn to_js_error<E: std::fmt::Display>(error: E) -> Error {
+    Error::from_reason(format!("{}", error))
+}
+
+// ---------- Writer ----------
+
+#[napi]
+pub struct ArchiveWriter {
+    /// `None` once finalized
+    inner: Option<mla::ArchiveWriter<'static, File>>,
+}
+
+#[napi]
+impl ArchiveWriter {
+    /// Create an archive at `path`, compressed and encrypted for the given
+    /// Ed25519 public keys (DER or PEM)
+    ///
+    /// If no public key is given, the archive is only compressed
+    #[napi(constructor)]
+    pub fn new(path: String, public_keys: Option<Vec<Buffer>>) -> Result<Self> {
+        let mut config = ArchiveWriterConfig::new();
+        config.enable_layer(mla::Layers::COMPRESS);
+        let public_keys = public_keys.unwrap_or_default();
+        if !public_keys.is_empty() {
+            let mut keys = Vec::new();
+            for key in public_keys {
+                keys.push(parse_openssl_ed25519_pubkey(&key).map_err(to_js_error)?);
+            }
+            config
+                .enable_layer(mla::Layers::ENCRYPT)
+                .add_public_keys(&keys);
+        }
+        let file = File::create(path).map_err(to_js_error)?;
+        let writer = mla::ArchiveWriter::from_config(file, config).map_err(to_js_error)?;
+        Ok(Self {
+            inner: Some(writer),
+        })
+    }
+
+    fn writer(&mut self) -> Result<&mut mla::ArchiveWriter<'static, File>> {
+        self.inner
+            .as_mut()
+            .ok_or_else(|| Error::from_reason("Archive already finalized"))
+    }
+
+    /// Add a whole file, named `name` in the archive
+    #[napi]
+    pub fn add_file(&mut self, name: String, data: Buffer) -> Result<()> {
+        self.writer()?
+            .add_file(&name, data.len() as u64, &data[..])
+            .map_err(to_js_error)
+    }
+
+    /// Start a new file, whose content is given through `appendFileContent`.
+    /// Return its identifier
+    ///
+    /// Several files can be in progress at the same time
+    #[napi]
+    pub fn start_file(&mut self, name: String) -> Result<i64> {
+        let id = self.writer()?.start_file(&name).map_err(to_js_error)?;
+        Ok(id as i64)
+    }
+
+    /// Add `data` at the end of the file `id`
+    #[napi]
+    pub fn append_file_content(&mut self, id: i64, data: Buffer) -> Result<()> {
+        self.writer()?
+            .append_file_content(id as u64, data.len() as u64, &data[..])
+            .map_err(to_js_error)
+    }
+
+    /// Mark the file `id` as complete
+    #[napi]
+    pub fn end_file(&mut self, id: i64) -> Result<()> {
+        self.writer()?.end_file(id as u64).map_err(to_js_error)
+    }
+
+    /// Write the archive footer and close the file. No more files can be added
+    #[napi]
+    pub fn finalize(&mut self) -> Result<()> {
+        self.writer()?.finalize().map_err(to_js_error)?;
+        // Drop the writer, closing the file
+        self.inner = None;
+        Ok(())
+    }
+}
+
+// ---------- Reader ----------
+
+#[napi]
+pub struct ArchiveReader {
+    inner: mla::ArchiveReader<'static, File>,
+}
+
+/// Compute the path of `file_name` once extracted in `output_dir`, ignoring
+/// root and `.` components, and refusing `..` ones (as `mlar extract` does)
+fn get_extracted_path(output_dir: &Path, file_name: &str) -> Option<PathBuf> {
+    let mut file_dst = output_dir.to_path_buf();
+    for part in Path::new(file_name).components() {
+        match part {
+            Component::Prefix(..) | Component::RootDir | Component::CurDir => continue,
+            Component::ParentDir => return None,
+            Component::Normal(part) => file_dst.push(part),
+        }
+    }
+    Some(file_dst)
+}
+
+#[napi]
+impl ArchiveReader {
+    /// Open the archive at `path`, with candidate Ed25519 private keys (DER or
+    /// PEM) if it is encrypted
+    #[napi(constructor)]
+    pub fn new(path: String, private_keys: Option<Vec<Buffer>>) -> Result<Self> {
+        let mut keys = Vec::new();
+        for key in private_keys.unwrap_or_default() {
+            keys.push(parse_openssl_ed25519_privkey(&key).map_err(to_js_error)?);
+        }
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(&keys);
+        let file = File::open(path).map_err(to_js_error)?;
+        let inner = mla::ArchiveReader::from_config(file, config).map_err(to_js_error)?;
+        Ok(Self { inner })
+    }
+
+    /// Names of the files in the archive, sorted
+    #[napi]
+    pub fn list_files(&mut self) -> Result<Vec<String>> {
+        let mut fnames: Vec<String> = self
+            .inner
+            .list_files()
+            .map_err(to_js_error)?
+            .cloned()
+            .collect();
+        fnames.sort();
+        Ok(fnames)
+    }
+
+    /// Content of the file `name`, or `null` if it is not in the archive
+    ///
+    /// The whole content is loaded in memory; prefer `extractFile` for big
+    /// files
+    #[napi]
+    pub fn read_file(&mut self, name: String) -> Result<Option<Buffer>> {
+        match self.inner.get_file(name).map_err(to_js_error)? {
+            Some(mut subfile) => {
+                let mut data = Vec::new();
+                subfile.data.read_to_end(&mut data).map_err(to_js_error)?;
+                Ok(Some(data.into()))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Stream the file `name` to `path`. Return false if it is not in the
+    /// archive
+    #[napi]
+    pub fn extract_file(&mut self, name: String, path: String) -> Result<bool> {
+        match self.inner.get_file(name).map_err(to_js_error)? {
+            Some(mut subfile) => {
+                let mut output = File::create(path).map_err(to_js_error)?;
+                io::copy(&mut subfile.data, &mut output).map_err(to_js_error)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    /// Extract every file in `output_dir`, and return their names
+    ///
+    /// Files with a `..` in their name are skipped
+    #[napi]
+    pub fn extract_all(&mut self, output_dir: String) -> Result<Vec<String>> {
+        let output_dir = Path::new(&output_dir);
+        let mut extracted = Vec::new();
+        for fname in self.list_files()? {
+            let path = match get_extracted_path(output_dir, &fname) {
+                Some(path) => path,
+                None => continue,
+            };
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).map_err(to_js_error)?;
+            }
+            if self.extract_file(fname.clone(), path.to_string_lossy().into_owned())? {
+                extracted.push(fname);
+            }
+        }
+        Ok(extracted)
+    }
+}
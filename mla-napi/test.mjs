@@ -0,0 +1,43 @@
+// Run with `npm run build:debug && npm test`
+import assert from 'node:assert/strict';
+import { mkdtempSync, readFileSync, rmSync } from 'node:fs';
+import { createRequire } from 'node:module';
+import { tmpdir } from 'node:os';
+import { join } from 'node:path';
+
+const require = createRequire(import.meta.url);
+const { ArchiveReader, ArchiveWriter } = require('./index.js');
+
+const publicKey = readFileSync('../samples/test25519_pub.pem');
+const privateKey = readFileSync('../samples/test25519.pem');
+const dir = mkdtempSync(join(tmpdir(), 'mla-napi-'));
+const archivePath = join(dir, 'test.mla');
+
+try {
+  // Creation, with a streamed file
+  const writer = new ArchiveWriter(archivePath, [publicKey]);
+  writer.addFile('whole.txt', Buffer.from('whole content'));
+  const id = writer.startFile('dir/streamed.txt');
+  writer.appendFileContent(id, Buffer.from('first '));
+  writer.appendFileContent(id, Buffer.from('second'));
+  writer.endFile(id);
+  writer.finalize();
+  assert.throws(() => writer.addFile('late.txt', Buffer.alloc(0)));
+
+  // A private key is required
+  assert.throws(() => new ArchiveReader(archivePath));
+
+  const reader = new ArchiveReader(archivePath, [privateKey]);
+  assert.deepEqual(reader.listFiles(), ['dir/streamed.txt', 'whole.txt']);
+  assert.equal(reader.readFile('whole.txt').toString(), 'whole content');
+  assert.equal(reader.readFile('unknown'), null);
+
+  // Extraction
+  const outputDir = join(dir, 'output');
+  assert.deepEqual(reader.extractAll(outputDir), ['dir/streamed.txt', 'whole.txt']);
+  assert.equal(readFileSync(join(outputDir, 'dir', 'streamed.txt'), 'utf8'), 'first second');
+
+  console.log('OK');
+} finally {
+  rmSync(dir, { recursive: true, force: true });
+}
@@ -0,0 +1,4 @@
+fn main() {
+    // Python symbols are resolved when the module is loaded (needed on macOS)
+    pyo3_build_config::add_extension_module_link_args();
+}
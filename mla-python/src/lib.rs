@@ -0,0 +1,348 @@
+//! Python bindings for MLA, based on PyO3
+//!
+//! Files can be added from `bytes` or file-like objects, and read back as
+//! `bytes` or through file-like objects, without loading them in memory.
+
+// Implementations generated by `#[pymethods]` in this PyO3 version are nested
+#![allow(non_local_definitions)]
+
+use std::fs::File;
+use std::io::Read;
+
+use ed25519_parser::{parse_openssl_ed25519_privkey, parse_openssl_ed25519_pubkey};
+use mla::config::{ArchiveReaderConfig, ArchiveWriterConfig};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyKeyError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+create_exception!(mla, MLAError, PyException);
+
+/// Size of the chunks read from file-like objects
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Convert a MLA error to a Python exception
+fn to_py_error<E: std::fmt::Display>(error: E) -> PyErr {
+    MLAError::new_err(format!("{}", error))
+}
+
+// ---------- Writer ----------
+
+/// Writer of a new archive. As a context manager, the archive is finalized on
+/// exit, unless an exception has been raised
+#[pyclass(unsendable)]
+pub struct MLAWriter {
+    /// `None` once finalized
+    inner: Option<mla::ArchiveWriter<'static, File>>,
+}
+
+#[pymethods]
+impl MLAWriter {
+    /// Create an archive at `path`, compressed and encrypted for the given
+    /// Ed25519 public keys (DER or PEM)
+    ///
+    /// If no public key is given, the archive is only compressed
+    #[new]
+    #[pyo3(signature = (path, public_keys=None))]
+    fn new(path: &str, public_keys: Option<Vec<&[u8]>>) -> PyResult<Self> {
+        let mut config = ArchiveWriterConfig::new();
+        config.enable_layer(mla::Layers::COMPRESS);
+        let public_keys = public_keys.unwrap_or_default();
+        if !public_keys.is_empty() {
+            let mut keys = Vec::new();
+            for key in public_keys {
+                keys.push(parse_openssl_ed25519_pubkey(key).map_err(to_py_error)?);
+            }
+            config
+                .enable_layer(mla::Layers::ENCRYPT)
+                .add_public_keys(&keys);
+        }
+        let file = File::create(path).map_err(to_py_error)?;
+        let writer = mla::ArchiveWriter::from_config(file, config).map_err(to_py_error)?;
+        Ok(Self {
+            inner: Some(writer),
+        })
+    }
+
+    /// Add a file named `name`, with `data` as content: either `bytes`, or a
+    /// binary file-like object, read until its end
+    fn add_file(&mut self, name: &str, data: &PyAny) -> PyResult<()> {
+        if let Ok(content) = data.downcast::<PyBytes>() {
+            let content = content.as_bytes();
+            return self
+                .writer()?
+                .add_file(name, content.len() as u64, content)
+                .map_err(to_py_error);
+        }
+        let id = self.start_file(name)?;
+        loop {
+            let chunk = data.call_method1("read", (READ_CHUNK_SIZE,))?;
+            let chunk: &[u8] = chunk.extract()?;
+            if chunk.is_empty() {
+                break;
+            }
+            self.append_file_content(id, chunk)?;
+        }
+        self.end_file(id)
+    }
+
+    /// Start a new file, whose content is given through
+    /// `append_file_content`. Return its identifier
+    ///
+    /// Several files can be in progress at the same time
+    fn start_file(&mut self, name: &str) -> PyResult<u64> {
+        self.writer()?.start_file(name).map_err(to_py_error)
+    }
+
+    /// Add `data` at the end of the file `id`
+    fn append_file_content(&mut self, id: u64, data: &[u8]) -> PyResult<()> {
+        self.writer()?
+            .append_file_content(id, data.len() as u64, data)
+            .map_err(to_py_error)
+    }
+
+    /// Mark the file `id` as complete
+    fn end_file(&mut self, id: u64) -> PyResult<()> {
+        self.writer()?.end_file(id).map_err(to_py_error)
+    }
+
+    /// Write the archive footer and close the file. No more files can be added
+    fn finalize(&mut self) -> PyResult<()> {
+        self.writer()?.finalize().map_err(to_py_error)?;
+        // Drop the writer, closing the file
+        self.inner = None;
+        Ok(())
+    }
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &mut self,
+        exc_type: Option<&PyAny>,
+        _exc_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> PyResult<bool> {
+        if exc_type.is_none() && self.inner.is_some() {
+            self.finalize()?;
+        }
+        Ok(false)
+    }
+}
+
+impl MLAWriter {
+    fn writer(&mut self) -> PyResult<&mut mla::ArchiveWriter<'static, File>> {
+        self.inner
+            .as_mut()
+            .ok_or_else(|| MLAError::new_err("Archive already finalized"))
+    }
+}
+
+// ---------- Reader ----------
+
+/// Reader of an archive. Iterating over it gives the names of its files,
+/// sorted
+#[pyclass(unsendable)]
+pub struct MLAReader {
+    inner: mla::ArchiveReader<'static, File>,
+}
+
+#[pymethods]
+impl MLAReader {
+    /// Open the archive at `path`, with candidate Ed25519 private keys (DER or
+    /// PEM) if it is encrypted
+    #[new]
+    #[pyo3(signature = (path, private_keys=None))]
+    fn new(path: &str, private_keys: Option<Vec<&[u8]>>) -> PyResult<Self> {
+        let mut keys = Vec::new();
+        for key in private_keys.unwrap_or_default() {
+            keys.push(parse_openssl_ed25519_privkey(key).map_err(to_py_error)?);
+        }
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(&keys);
+        let file = File::open(path).map_err(to_py_error)?;
+        let inner = mla::ArchiveReader::from_config(file, config).map_err(to_py_error)?;
+        Ok(Self { inner })
+    }
+
+    /// Names of the files in the archive, sorted
+    fn list_files(&self) -> PyResult<Vec<String>> {
+        let mut fnames: Vec<String> = self
+            .inner
+            .list_files()
+            .map_err(to_py_error)?
+            .cloned()
+            .collect();
+        fnames.sort();
+        Ok(fnames)
+    }
+
+    /// Content of the file `name`. Raise `KeyError` if it is not in the
+    /// archive
+    ///
+    /// The whole content is loaded in memory; prefer `open` for big files
+    fn read(&mut self, py: Python<'_>, name: String) -> PyResult<PyObject> {
+        match self.inner.get_file(name.clone()).map_err(to_py_error)? {
+            Some(mut subfile) => {
+                let mut data = Vec::new();
+                subfile.data.read_to_end(&mut data).map_err(to_py_error)?;
+                Ok(PyBytes::new(py, &data).into())
+            }
+            None => Err(PyKeyError::new_err(name)),
+        }
+    }
+
+    /// Open the file `name`, as a binary file-like object. Raise `KeyError`
+    /// if it is not in the archive
+    fn open(mut slf: PyRefMut<'_, Self>, name: String) -> PyResult<MLAFile> {
+        let size = match slf.inner.get_file(name.clone()).map_err(to_py_error)? {
+            Some(subfile) => subfile.size,
+            None => return Err(PyKeyError::new_err(name)),
+        };
+        Ok(MLAFile {
+            reader: slf.into(),
+            name,
+            pos: 0,
+            size,
+        })
+    }
+
+    fn __iter__(&self) -> PyResult<MLAEntries> {
+        Ok(MLAEntries {
+            names: self.list_files()?.into_iter(),
+        })
+    }
+
+    fn __len__(&self) -> PyResult<usize> {
+        Ok(self.inner.list_files().map_err(to_py_error)?.count())
+    }
+
+    fn __contains__(&self, name: &str) -> PyResult<bool> {
+        Ok(self
+            .inner
+            .list_files()
+            .map_err(to_py_error)?
+            .any(|fname| fname == name))
+    }
+}
+
+/// Iterator over the names of the files of an archive
+#[pyclass]
+pub struct MLAEntries {
+    names: std::vec::IntoIter<String>,
+}
+
+#[pymethods]
+impl MLAEntries {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> Option<String> {
+        self.names.next()
+    }
+}
+
+/// File of an archive, as a binary file-like object (`read`, `seek`, `tell`)
+///
+/// Content is read on demand, through the `MLAReader` which opened it
+#[pyclass(unsendable)]
+pub struct MLAFile {
+    reader: Py<MLAReader>,
+    name: String,
+    pos: u64,
+    size: u64,
+}
+
+#[pymethods]
+impl MLAFile {
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[getter]
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Read at most `size` bytes, or until the end of the file if `size` is
+    /// negative
+    #[pyo3(signature = (size=-1))]
+    fn read(&mut self, py: Python<'_>, size: i64) -> PyResult<PyObject> {
+        let remaining = self.size.saturating_sub(self.pos);
+        let count = if size < 0 {
+            remaining
+        } else {
+            remaining.min(size as u64)
+        };
+        let mut buf = vec![0u8; count as usize];
+        let read = self
+            .reader
+            .borrow_mut(py)
+            .inner
+            .read_at(&self.name, self.pos, &mut buf)
+            .map_err(to_py_error)?
+            .ok_or_else(|| PyKeyError::new_err(self.name.clone()))?;
+        buf.truncate(read);
+        self.pos += read as u64;
+        Ok(PyBytes::new(py, &buf).into())
+    }
+
+    /// Move to `offset`, relative to the start (`whence` = 0), the current
+    /// position (1) or the end (2) of the file. Return the new position
+    #[pyo3(signature = (offset, whence=0))]
+    fn seek(&mut self, offset: i64, whence: i32) -> PyResult<u64> {
+        let base = match whence {
+            0 => 0,
+            1 => self.pos as i64,
+            2 => self.size as i64,
+            _ => return Err(PyValueError::new_err("Invalid whence")),
+        };
+        match base.checked_add(offset) {
+            Some(pos) if pos >= 0 => {
+                self.pos = pos as u64;
+                Ok(self.pos)
+            }
+            _ => Err(PyValueError::new_err("Invalid seek to a negative position")),
+        }
+    }
+
+    fn tell(&self) -> u64 {
+        self.pos
+    }
+
+    fn readable(&self) -> bool {
+        true
+    }
+
+    fn seekable(&self) -> bool {
+        true
+    }
+
+    fn close(&self) {}
+
+    fn __enter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __exit__(
+        &self,
+        _exc_type: Option<&PyAny>,
+        _exc_value: Option<&PyAny>,
+        _traceback: Option<&PyAny>,
+    ) -> bool {
+        false
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "mla")]
+fn mla_python(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<MLAWriter>()?;
+    m.add_class::<MLAReader>()?;
+    m.add_class::<MLAFile>()?;
+    m.add("MLAError", py.get_type::<MLAError>())?;
+    Ok(())
+}
@@ -0,0 +1,51 @@
+# Run with `maturin develop && pytest`
+import io
+from pathlib import Path
+
+import pytest
+
+import mla
+
+SAMPLES = Path(__file__).resolve().parents[2] / "samples"
+PUBLIC_KEY = (SAMPLES / "test25519_pub.pem").read_bytes()
+PRIVATE_KEY = (SAMPLES / "test25519.pem").read_bytes()
+
+
+def test_archive(tmp_path):
+    archive_path = str(tmp_path / "test.mla")
+    big = bytes(i % 251 for i in range(200_000))
+
+    # Creation, from bytes, a file-like object and by parts
+    with mla.MLAWriter(archive_path, [PUBLIC_KEY]) as writer:
+        writer.add_file("whole.txt", b"whole content")
+        writer.add_file("big.bin", io.BytesIO(big))
+        file_id = writer.start_file("dir/streamed.txt")
+        writer.append_file_content(file_id, b"first ")
+        writer.append_file_content(file_id, b"second")
+        writer.end_file(file_id)
+    with pytest.raises(mla.MLAError):
+        writer.add_file("late.txt", b"")
+
+    # A private key is required
+    with pytest.raises(mla.MLAError):
+        mla.MLAReader(archive_path)
+
+    reader = mla.MLAReader(archive_path, [PRIVATE_KEY])
+    assert list(reader) == ["big.bin", "dir/streamed.txt", "whole.txt"]
+    assert len(reader) == 3
+    assert "whole.txt" in reader
+    assert reader.read("whole.txt") == b"whole content"
+    assert reader.read("dir/streamed.txt") == b"first second"
+    with pytest.raises(KeyError):
+        reader.read("unknown")
+
+    # Streaming
+    with reader.open("big.bin") as entry:
+        assert entry.size == len(big)
+        assert entry.read(10) == big[:10]
+        entry.seek(-10, 2)
+        assert entry.tell() == len(big) - 10
+        assert entry.read() == big[-10:]
+        assert entry.read() == b""
+        entry.seek(100_000)
+        assert entry.read(5) == big[100_000:100_005]
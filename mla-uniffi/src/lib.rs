@@ -0,0 +1,228 @@
+//! Kotlin and Swift bindings for MLA, based on UniFFI
+//!
+//! Only the common operations are exposed: creating an archive from files or
+//! in-memory data, listing and extracting its files.
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::sync::{Arc, Mutex};
+
+use ed25519_parser::{parse_openssl_ed25519_privkey, parse_openssl_ed25519_pubkey};
+use mla::config::{ArchiveReaderConfig, ArchiveWriterConfig};
+
+uniffi::setup_scaffolding!();
+
+// ---------- Errors ----------
+
+#[derive(Debug, uniffi::Error)]
+#[uniffi(flat_error)]
+pub enum MlaError {
+    /// A key is not in the expected format (Ed25519, DER or PEM)
+    InvalidKey,
+    /// The writer has already been finalized
+    AlreadyFinalized,
+    /// I/O error, on the archive or an extracted file
+    IOError(String),
+    /// Error from the MLA library
+    ArchiveError(String),
+}
+
+impl fmt::Display for MlaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // For now, use the debug derived version
+        write!(f, "{:?}", self)
+    }
+}
+
+impl From<mla::errors::Error> for MlaError {
+    fn from(error: mla::errors::Error) -> Self {
+        MlaError::ArchiveError(error.to_string())
+    }
+}
+
+impl From<io::Error> for MlaError {
+    fn from(error: io::Error) -> Self {
+        MlaError::IOError(error.to_string())
+    }
+}
+
+// ---------- Send wrapper ----------
+
+/// UniFFI objects must be `Send + Sync`, while MLA writers and readers hold
+/// their layers as `Box<dyn LayerWriter>` / `Box<dyn LayerReader>`, without a
+/// `Send` bound
+///
+/// The layers built by MLA only own their data (no `Rc`, no thread local), so
+/// moving them across threads is sound. Concurrent accesses are prevented by
+/// the `Mutex` wrapping this type.
+struct AssertSend<T>(T);
+
+unsafe impl<T> Send for AssertSend<T> {}
+
+// ---------- Writer ----------
+
+/// Archive creation
+#[derive(uniffi::Object)]
+pub struct ArchiveWriter {
+    /// `None` once finalized
+    inner: Mutex<Option<AssertSend<mla::ArchiveWriter<'static, File>>>>,
+}
+
+#[uniffi::export]
+impl ArchiveWriter {
+    /// Create an archive at `path`, compressed and encrypted for the given
+    /// Ed25519 public keys (DER or PEM)
+    ///
+    /// If no public key is given, the archive is only compressed
+    #[uniffi::constructor]
+    pub fn new(path: String, public_keys: Vec<Vec<u8>>) -> Result<Arc<Self>, MlaError> {
+        let mut config = ArchiveWriterConfig::new();
+        config.enable_layer(mla::Layers::COMPRESS);
+        if !public_keys.is_empty() {
+            let mut keys = Vec::new();
+            for key in public_keys {
+                keys.push(parse_openssl_ed25519_pubkey(&key).map_err(|_| MlaError::InvalidKey)?);
+            }
+            config
+                .enable_layer(mla::Layers::ENCRYPT)
+                .add_public_keys(&keys);
+        }
+        let writer = mla::ArchiveWriter::from_config(File::create(path)?, config)?;
+        Ok(Arc::new(Self {
+            inner: Mutex::new(Some(AssertSend(writer))),
+        }))
+    }
+
+    /// Add the file at `path`, named `name` in the archive
+    pub fn add_file(&self, name: String, path: String) -> Result<(), MlaError> {
+        let file = File::open(path)?;
+        let size = file.metadata()?.len();
+        self.with_writer(|writer| Ok(writer.add_file(&name, size, file)?))
+    }
+
+    /// Add `data`, named `name` in the archive
+    pub fn add_bytes(&self, name: String, data: Vec<u8>) -> Result<(), MlaError> {
+        self.with_writer(|writer| Ok(writer.add_file(&name, data.len() as u64, data.as_slice())?))
+    }
+
+    /// Write the archive footer and close the file. No more files can be added
+    pub fn finalize(&self) -> Result<(), MlaError> {
+        self.with_writer(|writer| Ok(writer.finalize()?))?;
+        // Drop the writer, closing the file
+        *self.inner.lock().expect("Writer lock poisoned") = None;
+        Ok(())
+    }
+}
+
+impl ArchiveWriter {
+    fn with_writer<F>(&self, f: F) -> Result<(), MlaError>
+    where
+        F: FnOnce(&mut mla::ArchiveWriter<'static, File>) -> Result<(), MlaError>,
+    {
+        match self.inner.lock().expect("Writer lock poisoned").as_mut() {
+            Some(AssertSend(writer)) => f(writer),
+            None => Err(MlaError::AlreadyFinalized),
+        }
+    }
+}
+
+// ---------- Reader ----------
+
+/// Archive reading
+#[derive(uniffi::Object)]
+pub struct ArchiveReader {
+    inner: Mutex<AssertSend<mla::ArchiveReader<'static, File>>>,
+}
+
+#[uniffi::export]
+impl ArchiveReader {
+    /// Open the archive at `path`, with candidate Ed25519 private keys (DER or
+    /// PEM) if it is encrypted
+    #[uniffi::constructor]
+    pub fn new(path: String, private_keys: Vec<Vec<u8>>) -> Result<Arc<Self>, MlaError> {
+        let mut keys = Vec::new();
+        for key in private_keys {
+            keys.push(parse_openssl_ed25519_privkey(&key).map_err(|_| MlaError::InvalidKey)?);
+        }
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(&keys);
+        let reader = mla::ArchiveReader::from_config(File::open(path)?, config)?;
+        Ok(Arc::new(Self {
+            inner: Mutex::new(AssertSend(reader)),
+        }))
+    }
+
+    /// Names of the files in the archive, sorted
+    pub fn list_files(&self) -> Result<Vec<String>, MlaError> {
+        let inner = self.inner.lock().expect("Reader lock poisoned");
+        let mut fnames: Vec<String> = inner.0.list_files()?.cloned().collect();
+        fnames.sort();
+        Ok(fnames)
+    }
+
+    /// Content of the file `name`, or `None` if it is not in the archive
+    ///
+    /// The whole content is loaded in memory; prefer `extract_file` for big
+    /// files
+    pub fn read_file(&self, name: String) -> Result<Option<Vec<u8>>, MlaError> {
+        let mut inner = self.inner.lock().expect("Reader lock poisoned");
+        match inner.0.get_file(name)? {
+            Some(mut subfile) => {
+                let mut data = Vec::new();
+                subfile.data.read_to_end(&mut data)?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Write the file `name` to `path`. Return false if it is not in the
+    /// archive
+    pub fn extract_file(&self, name: String, path: String) -> Result<bool, MlaError> {
+        let mut inner = self.inner.lock().expect("Reader lock poisoned");
+        match inner.0.get_file(name)? {
+            Some(mut subfile) => {
+                io::copy(&mut subfile.data, &mut File::create(path)?)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PUB_KEY: &[u8] = include_bytes!("../../samples/test25519_pub.pem");
+    const PRIV_KEY: &[u8] = include_bytes!("../../samples/test25519.pem");
+
+    #[test]
+    fn create_and_read() {
+        let dir = std::env::temp_dir();
+        let archive_path = dir.join(format!("mla-uniffi-{}.mla", std::process::id()));
+        let archive_path = archive_path.to_string_lossy().into_owned();
+
+        let writer = ArchiveWriter::new(archive_path.clone(), vec![PUB_KEY.to_vec()]).unwrap();
+        writer
+            .add_bytes("hello.txt".to_string(), b"Hello".to_vec())
+            .unwrap();
+        writer.finalize().unwrap();
+        assert!(matches!(
+            writer.add_bytes("late.txt".to_string(), Vec::new()),
+            Err(MlaError::AlreadyFinalized)
+        ));
+
+        // A key is required
+        assert!(ArchiveReader::new(archive_path.clone(), Vec::new()).is_err());
+        let reader = ArchiveReader::new(archive_path.clone(), vec![PRIV_KEY.to_vec()]).unwrap();
+        assert_eq!(reader.list_files().unwrap(), vec!["hello.txt".to_string()]);
+        assert_eq!(
+            reader.read_file("hello.txt".to_string()).unwrap(),
+            Some(b"Hello".to_vec())
+        );
+        assert_eq!(reader.read_file("unknown".to_string()).unwrap(), None);
+
+        std::fs::remove_file(archive_path).unwrap();
+    }
+}
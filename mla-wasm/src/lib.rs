@@ -0,0 +1,99 @@
+//! WebAssembly bindings for MLA, based on wasm-bindgen
+//!
+//! Archives are read from byte slices, for instance the content of a file
+//! dropped in a web page. Nothing relies on a filesystem or on the OS random
+//! generator, so the bindings target `wasm32-unknown-unknown`.
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use ed25519_parser::parse_openssl_ed25519_privkey;
+use js_sys::{Array, Uint8Array};
+use mla::config::ArchiveReaderConfig;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+/// Convert a MLA error to a JavaScript exception
+fn to_js_error<E: std::fmt::Display>(error: E) -> JsError {
+    JsError::new(&format!("{}", error))
+}
+
+/// Reader of an archive held in memory
+#[wasm_bindgen]
+pub struct Archive {
+    inner: mla::ArchiveReader<'static, Cursor<Vec<u8>>>,
+}
+
+#[wasm_bindgen]
+impl Archive {
+    /// Open the archive in `data`, with candidate Ed25519 private keys (DER
+    /// or PEM, as `Uint8Array`) if it is encrypted
+    #[wasm_bindgen(constructor)]
+    pub fn new(data: Vec<u8>, private_keys: Option<Array>) -> Result<Archive, JsError> {
+        let mut keys = Vec::new();
+        if let Some(private_keys) = private_keys {
+            for key in private_keys.iter() {
+                let key = key
+                    .dyn_into::<Uint8Array>()
+                    .map_err(|_| JsError::new("Private keys must be Uint8Array"))?;
+                keys.push(parse_openssl_ed25519_privkey(&key.to_vec()).map_err(to_js_error)?);
+            }
+        }
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(&keys);
+        let inner =
+            mla::ArchiveReader::from_config(Cursor::new(data), config).map_err(to_js_error)?;
+        Ok(Self { inner })
+    }
+
+    /// Names of the entries in the archive, sorted
+    pub fn list(&self) -> Result<Array, JsError> {
+        let mut names: Vec<&String> = self.inner.list_files().map_err(to_js_error)?.collect();
+        names.sort();
+        Ok(names
+            .into_iter()
+            .map(|name| JsValue::from_str(name))
+            .collect())
+    }
+
+    /// Content of the entry `name`, or `undefined` if it is not in the
+    /// archive
+    #[wasm_bindgen(js_name = readEntry)]
+    pub fn read_entry(&mut self, name: String) -> Result<Option<Vec<u8>>, JsError> {
+        match self.inner.get_file(name).map_err(to_js_error)? {
+            Some(mut entry) => {
+                let mut data = Vec::new();
+                entry.data.read_to_end(&mut data).map_err(to_js_error)?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// At most `length` bytes of the entry `name`, from `offset`, or
+    /// `undefined` if it is not in the archive. Only the blocks needed are
+    /// decrypted and decompressed, to preview big entries
+    ///
+    /// As the archive is in memory, offsets fit in a `usize`
+    #[wasm_bindgen(js_name = readEntryRange)]
+    pub fn read_entry_range(
+        &mut self,
+        name: String,
+        offset: usize,
+        length: usize,
+    ) -> Result<Option<Vec<u8>>, JsError> {
+        match self.inner.get_file_seek(name).map_err(to_js_error)? {
+            Some(mut entry) => {
+                entry
+                    .data
+                    .seek(SeekFrom::Start(offset as u64))
+                    .map_err(to_js_error)?;
+                let mut data = Vec::new();
+                (&mut entry.data)
+                    .take(length as u64)
+                    .read_to_end(&mut data)
+                    .map_err(to_js_error)?;
+                Ok(Some(data))
+            }
+            None => Ok(None),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+// Run with `wasm-pack build --target nodejs && node test.mjs`
+import assert from 'node:assert/strict';
+import { readFileSync } from 'node:fs';
+import { createRequire } from 'node:module';
+
+const require = createRequire(import.meta.url);
+const { Archive } = require('./pkg/mla_wasm.js');
+
+// Encrypted and compressed archive, also used by `mla` regression tests
+const data = readFileSync('../samples/archive_v1.mla');
+const privateKey = readFileSync('../samples/test25519.pem');
+
+// A private key is required
+assert.throws(() => new Archive(data));
+
+const archive = new Archive(data, [privateKey]);
+const names = archive.list();
+assert.equal(names.length, 259);
+assert.deepEqual(names.slice(0, 3), ['big', 'file_0', 'file_1']);
+
+// `simple` is 0, 1, ..., 255
+const simple = archive.readEntry('simple');
+assert.deepEqual(Array.from(simple), Array.from({ length: 256 }, (_, i) => i));
+assert.equal(archive.readEntry('unknown'), undefined);
+
+// `big` repeats `simple`, over 10 MB
+const range = archive.readEntryRange('big', 10 * 256 + 250, 12);
+assert.deepEqual(Array.from(range), [250, 251, 252, 253, 254, 255, 0, 1, 2, 3, 4, 5]);
+assert.equal(archive.readEntryRange('big', 10 * 1024 * 1024 - 2, 12).length, 2);
+
+console.log('OK');
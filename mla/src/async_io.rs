@@ -0,0 +1,303 @@
+//! Asynchronous (tokio) wrappers of `ArchiveWriter` and `ArchiveReader`
+//!
+//! Layers are implemented on blocking `Read` / `Write`. Here, they only work
+//! on memory: the writer output is buffered, then flushed to the `AsyncWrite`
+//! after each operation, and the reader works on the archive loaded in memory.
+//! The runtime is therefore never blocked on I/O.
+//!
+//! Compression and encryption are still computed by the calling task: each
+//! call blocks its thread for the time needed to process the data given, up
+//! to a compression block. As the layers, these types are not `Send`, so they
+//! cannot be moved to `tokio::task::spawn_blocking`: use them in a
+//! `tokio::task::LocalSet` or on a runtime of their own, or use the blocking
+//! `ArchiveWriter` and `ArchiveReader` in `spawn_blocking` instead.
+use super::{ArchiveFileID, ArchiveReader, ArchiveWriter, Error};
+use crate::config::{ArchiveReaderConfig, ArchiveWriterConfig};
+use std::cell::RefCell;
+use std::io::{self, Cursor, Read, Write};
+use std::rc::Rc;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use x25519_dalek::PublicKey;
+
+/// Size of the chunks read from, or written to, asynchronous streams
+const ASYNC_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Output of the inner `ArchiveWriter`, drained by `AsyncArchiveWriter`
+#[derive(Clone, Default)]
+struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// `ArchiveWriter` over an `AsyncWrite`
+///
+/// The data pending in memory is at most a compression block, or an
+/// encryption chunk, plus the content given to the last call
+pub struct AsyncArchiveWriter<'a, W: AsyncWrite + Unpin> {
+    archive: ArchiveWriter<'a, SharedBuffer>,
+    buffer: SharedBuffer,
+    dest: W,
+}
+
+impl<'a, W: AsyncWrite + Unpin> AsyncArchiveWriter<'a, W> {
+    /// See `ArchiveWriter::from_config`. The header is written to `dest` on
+    /// the first operation
+    pub fn from_config(dest: W, config: ArchiveWriterConfig) -> Result<Self, Error> {
+        let buffer = SharedBuffer::default();
+        Ok(Self {
+            archive: ArchiveWriter::from_config(buffer.clone(), config)?,
+            buffer,
+            dest,
+        })
+    }
+
+    /// See `ArchiveWriter::new`
+    pub fn new(dest: W, public_keys: &[PublicKey]) -> Result<Self, Error> {
+        let buffer = SharedBuffer::default();
+        Ok(Self {
+            archive: ArchiveWriter::new(buffer.clone(), public_keys)?,
+            buffer,
+            dest,
+        })
+    }
+
+    /// Write what the archive has produced so far to `dest`
+    async fn drain(&mut self) -> Result<(), Error> {
+        let data = std::mem::take(&mut *self.buffer.0.borrow_mut());
+        if !data.is_empty() {
+            self.dest.write_all(&data).await?;
+        }
+        Ok(())
+    }
+
+    /// See `ArchiveWriter::start_file`
+    pub async fn start_file(&mut self, filename: &str) -> Result<ArchiveFileID, Error> {
+        let id = self.archive.start_file(filename)?;
+        self.drain().await?;
+        Ok(id)
+    }
+
+    /// Append `data` to the started file `id`, see
+    /// `ArchiveWriter::append_file_content`
+    ///
+    /// `data` is compressed and encrypted before returning, blocking the
+    /// thread meanwhile (see the module documentation): prefer several calls
+    /// with small parts to a single call with a big content
+    pub async fn append_file_content(
+        &mut self,
+        id: ArchiveFileID,
+        data: &[u8],
+    ) -> Result<(), Error> {
+        self.archive
+            .append_file_content(id, data.len() as u64, data)?;
+        self.drain().await
+    }
+
+    /// See `ArchiveWriter::end_file`
+    pub async fn end_file(&mut self, id: ArchiveFileID) -> Result<(), Error> {
+        self.archive.end_file(id)?;
+        self.drain().await
+    }
+
+    /// Add a file named `filename`, with the content of `src` until its end
+    ///
+    /// Unlike `ArchiveWriter::add_file`, the size does not need to be known in
+    /// advance. The content is processed by parts of 64KB, letting the other
+    /// tasks run in between
+    pub async fn add_file<R: AsyncRead + Unpin>(
+        &mut self,
+        filename: &str,
+        mut src: R,
+    ) -> Result<(), Error> {
+        let id = self.start_file(filename).await?;
+        let mut buf = vec![0u8; ASYNC_BUFFER_SIZE];
+        loop {
+            let read = src.read(&mut buf).await?;
+            if read == 0 {
+                break;
+            }
+            self.append_file_content(id, &buf[..read]).await?;
+            tokio::task::yield_now().await;
+        }
+        self.end_file(id).await
+    }
+
+    /// See `ArchiveWriter::add_hardlink`
+    pub async fn add_hardlink(&mut self, filename: &str, target: &str) -> Result<(), Error> {
+        self.archive.add_hardlink(filename, target)?;
+        self.drain().await
+    }
+
+    /// See `ArchiveWriter::finalize`. `dest` is then flushed
+    pub async fn finalize(&mut self) -> Result<(), Error> {
+        self.archive.finalize()?;
+        self.drain().await?;
+        self.dest.flush().await?;
+        Ok(())
+    }
+
+    /// Return the inner `ArchiveWriter`, for the operations without an
+    /// asynchronous version. Its output is written on the next operation
+    pub fn archive(&mut self) -> &mut ArchiveWriter<'a, impl Write> {
+        &mut self.archive
+    }
+
+    pub fn into_raw(self) -> W {
+        self.dest
+    }
+}
+
+/// `ArchiveReader` over an archive loaded in memory from an `AsyncRead`
+///
+/// Reading an archive needs to seek in it: the whole archive is loaded in
+/// memory by `from_config_in_memory`, and kept until the reader is dropped.
+/// Its size is therefore bounded by the caller. For big archives, prefer
+/// `ArchiveReader` on a `File` in `tokio::task::spawn_blocking`
+pub struct AsyncArchiveReader<'a> {
+    archive: ArchiveReader<'a, Cursor<Vec<u8>>>,
+}
+
+impl<'a> AsyncArchiveReader<'a> {
+    /// Load the whole archive from `src` in memory, until its end. See
+    /// `ArchiveReader::from_config`
+    ///
+    /// Return `Error::ArchiveTooBig` as soon as more than `max_size` bytes
+    /// are read, so that an untrusted stream cannot exhaust the memory
+    pub async fn from_config_in_memory<R: AsyncRead + Unpin>(
+        src: R,
+        config: ArchiveReaderConfig,
+        max_size: u64,
+    ) -> Result<AsyncArchiveReader<'a>, Error> {
+        let mut data = Vec::new();
+        src.take(max_size.saturating_add(1))
+            .read_to_end(&mut data)
+            .await?;
+        if data.len() as u64 > max_size {
+            return Err(Error::ArchiveTooBig(max_size));
+        }
+        Ok(Self {
+            archive: ArchiveReader::from_config(Cursor::new(data), config)?,
+        })
+    }
+
+    /// See `ArchiveReader::list_files`
+    pub fn list_files(&self) -> Result<impl Iterator<Item = &String>, Error> {
+        self.archive.list_files()
+    }
+
+    /// Write the content of `filename` to `dest`
+    ///
+    /// Return `false` if there is no such file in the archive
+    pub async fn read_file<W: AsyncWrite + Unpin>(
+        &mut self,
+        filename: &str,
+        dest: &mut W,
+    ) -> Result<bool, Error> {
+        let mut file = match self.archive.get_file(filename.to_string())? {
+            Some(file) => file,
+            None => return Ok(false),
+        };
+        let mut buf = vec![0u8; ASYNC_BUFFER_SIZE];
+        loop {
+            let read = file.data.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            dest.write_all(&buf[..read]).await?;
+        }
+        dest.flush().await?;
+        Ok(true)
+    }
+
+    /// Return the inner `ArchiveReader`, for the operations without an
+    /// asynchronous version
+    pub fn archive(&mut self) -> &mut ArchiveReader<'a, Cursor<Vec<u8>>> {
+        &mut self.archive
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use x25519_dalek::StaticSecret;
+
+    #[tokio::test]
+    async fn async_archive() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = StaticSecret::new(&mut rng);
+        let content1: Vec<u8> = (0..200_000u32).map(|i| (i % 251) as u8).collect();
+        let content2 = b"interleaved".to_vec();
+
+        let mut mla = AsyncArchiveWriter::new(Vec::new(), &[PublicKey::from(&key)]).unwrap();
+        mla.add_file("file1", content1.as_slice()).await.unwrap();
+        let id = mla.start_file("file2").await.unwrap();
+        mla.append_file_content(id, &content2[..5]).await.unwrap();
+        mla.add_file("empty", &b""[..]).await.unwrap();
+        mla.append_file_content(id, &content2[5..]).await.unwrap();
+        mla.end_file(id).await.unwrap();
+        mla.finalize().await.unwrap();
+        let archive = mla.into_raw();
+
+        // The output is the one of a blocking reader
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_read = ArchiveReader::from_config(Cursor::new(&archive), config).unwrap();
+        let mut rez = Vec::new();
+        mla_read
+            .get_file("file1".to_string())
+            .unwrap()
+            .unwrap()
+            .data
+            .read_to_end(&mut rez)
+            .unwrap();
+        assert_eq!(rez, content1);
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_read =
+            AsyncArchiveReader::from_config_in_memory(archive.as_slice(), config, 1024 * 1024)
+                .await
+                .unwrap();
+        let mut fnames: Vec<String> = mla_read.list_files().unwrap().cloned().collect();
+        fnames.sort();
+        assert_eq!(fnames, vec!["empty", "file1", "file2"]);
+        for (fname, content) in &[("file1", &content1), ("file2", &content2)] {
+            let mut rez = Vec::new();
+            assert!(mla_read.read_file(fname, &mut rez).await.unwrap());
+            assert_eq!(&rez, *content);
+        }
+        assert!(!mla_read
+            .read_file("unknown", &mut Vec::new())
+            .await
+            .unwrap());
+
+        // Archives above the limit are refused, without being fully read
+        let limit = archive.len() as u64 - 1;
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut src = archive.as_slice();
+        match AsyncArchiveReader::from_config_in_memory(&mut src, config, limit).await {
+            Err(Error::ArchiveTooBig(size)) => assert_eq!(size, limit),
+            _ => panic!("an archive above the limit must be refused"),
+        }
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let doubled = archive.repeat(2);
+        let mut src = doubled.as_slice();
+        assert!(matches!(
+            AsyncArchiveReader::from_config_in_memory(&mut src, config, limit).await,
+            Err(Error::ArchiveTooBig(_))
+        ));
+        assert_eq!(src.len() as u64, 2 * archive.len() as u64 - limit - 1);
+    }
+}
@@ -1,12 +1,27 @@
+pub use crate::crypto::ecc::PrivateKeyProvider;
+pub use crate::crypto::hybrid::{HybridPrivateKey, HybridPublicKey};
 use crate::errors::ConfigError;
-use crate::layers::compress::CompressionConfig;
+pub use crate::layers::compress::{train_compression_dictionary, CompressionAlgorithm};
+use crate::layers::compress::{
+    CompressionConfig, CompressionPersistentConfig, CompressionPersistentConfigV2,
+    CompressionReaderConfig,
+};
+pub use crate::layers::encrypt::{
+    ArchiveRng, EncryptionCipher, RecipientKind, RecipientSet, RecipientSlot,
+};
 use crate::layers::encrypt::{
-    EncryptionConfig, EncryptionPersistentConfig, EncryptionReaderConfig,
+    EncryptionConfig, EncryptionPersistentConfig, EncryptionPersistentConfigV1,
+    EncryptionPersistentConfigV2, EncryptionReaderConfig,
 };
+use crate::layers::progress::ProgressHandle;
+pub use crate::layers::progress::ProgressObserver;
+use crate::layers::sign::{SignatureConfig, SignaturePersistentConfig, SignatureReaderConfig};
 use crate::Layers;
+use rand::SeedableRng;
+use rand_chacha::ChaChaRng;
 use serde::{Deserialize, Serialize};
 
-/// This module implements the configuration capabilities of MLA Archive
+// This module implements the configuration capabilities of MLA Archive
 
 /// User's configuration used to prepare an archive
 pub struct ArchiveWriterConfig {
@@ -15,6 +30,28 @@ pub struct ArchiveWriterConfig {
     // Layers specifics
     pub(crate) compress: CompressionConfig,
     pub(crate) encrypt: EncryptionConfig,
+    pub(crate) sign: SignatureConfig,
+
+    // Writer only options
+    pub(crate) output_digest: bool,
+    pub(crate) deterministic: bool,
+    pub(crate) padding: Option<PaddingPolicy>,
+    /// Store identical chunks of content once, see `with_dedup`
+    pub(crate) dedup: bool,
+    /// Observer of the progress, see `with_progress_observer`
+    pub(crate) progress: Option<ProgressHandle>,
+}
+
+/// Padding hiding the sizes of the entries, see
+/// `ArchiveWriterConfig::with_padding_policy`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaddingPolicy {
+    /// Each entry, from its start to its end, is padded to a multiple of this
+    /// size. 0 disables it
+    pub granularity: u64,
+    /// Up to this size of random padding is added after the last entry,
+    /// hiding the total size of the entries
+    pub max_decoy: u64,
 }
 
 /// Internal configuration stored in the header, to be reloaded
@@ -24,8 +61,93 @@ pub struct ArchivePersistentConfig {
 
     // Layers specifics
     pub(crate) encrypt: Option<EncryptionPersistentConfig>,
+    pub(crate) compress: Option<CompressionPersistentConfig>,
+    pub(crate) sign: Option<SignaturePersistentConfig>,
+
+    /// Whether the entries are deduplicated, see
+    /// `ArchiveWriterConfig::with_dedup`
+    pub(crate) dedup: bool,
+}
+
+/// `ArchivePersistentConfig` as stored in format v4 and v5 archives, which
+/// do not record whether the entries are deduplicated
+#[derive(Deserialize)]
+pub(crate) struct ArchivePersistentConfigV4 {
+    layers_enabled: Layers,
+    encrypt: Option<EncryptionPersistentConfig>,
+    compress: Option<CompressionPersistentConfig>,
+    sign: Option<SignaturePersistentConfig>,
+}
+
+impl From<ArchivePersistentConfigV4> for ArchivePersistentConfig {
+    fn from(config: ArchivePersistentConfigV4) -> Self {
+        ArchivePersistentConfig {
+            layers_enabled: config.layers_enabled,
+            encrypt: config.encrypt,
+            compress: config.compress,
+            sign: config.sign,
+            dedup: false,
+        }
+    }
+}
+
+/// `ArchivePersistentConfig` as stored in format v1 archives
+///
+/// These archives only use the default compression block size and encryption
+/// chunk size, which were not recorded
+#[derive(Deserialize)]
+pub(crate) struct ArchivePersistentConfigV1 {
+    layers_enabled: Layers,
+    encrypt: Option<EncryptionPersistentConfigV1>,
+}
+
+impl From<ArchivePersistentConfigV1> for ArchivePersistentConfig {
+    fn from(config: ArchivePersistentConfigV1) -> Self {
+        ArchivePersistentConfig {
+            layers_enabled: config.layers_enabled,
+            encrypt: config.encrypt.map(EncryptionPersistentConfig::from),
+            compress: if config.layers_enabled.contains(Layers::COMPRESS) {
+                Some(CompressionConfig::default().to_persistent())
+            } else {
+                None
+            },
+            sign: None,
+            dedup: false,
+        }
+    }
 }
 
+/// `ArchivePersistentConfig` as stored in format v2 and v3 archives, which
+/// cannot be signed nor encrypted with a password, and are compressed with
+/// Brotli
+#[derive(Deserialize)]
+pub(crate) struct ArchivePersistentConfigV2 {
+    layers_enabled: Layers,
+    encrypt: Option<EncryptionPersistentConfigV2>,
+    compress: Option<CompressionPersistentConfigV2>,
+}
+
+impl From<ArchivePersistentConfigV2> for ArchivePersistentConfig {
+    fn from(config: ArchivePersistentConfigV2) -> Self {
+        ArchivePersistentConfig {
+            layers_enabled: config.layers_enabled,
+            encrypt: config.encrypt.map(EncryptionPersistentConfig::from),
+            compress: config.compress.map(CompressionPersistentConfig::from),
+            sign: None,
+            dedup: false,
+        }
+    }
+}
+
+/// Smallest and biggest encryption chunk size picked by `tune_for_entry_sizes`
+const TUNE_MIN_CHUNK_SIZE: u64 = 64 * 1024;
+const TUNE_MAX_CHUNK_SIZE: u64 = 1024 * 1024;
+/// Smallest and biggest compression block size picked by `tune_for_entry_sizes`
+const TUNE_MIN_BLOCK_SIZE: u64 = 1024 * 1024;
+const TUNE_MAX_BLOCK_SIZE: u64 = 16 * 1024 * 1024;
+/// Number of median-sized entries a compression block should hold
+const TUNE_ENTRIES_PER_BLOCK: u64 = 8;
+
 pub type ConfigResult<'a> = Result<&'a mut ArchiveWriterConfig, ConfigError>;
 
 impl ArchiveWriterConfig {
@@ -35,6 +157,12 @@ impl ArchiveWriterConfig {
             layers_enabled: Layers::EMPTY,
             compress: CompressionConfig::default(),
             encrypt: EncryptionConfig::default(),
+            sign: SignatureConfig::default(),
+            output_digest: false,
+            deterministic: false,
+            padding: None,
+            dedup: false,
+            progress: None,
         }
     }
 
@@ -67,9 +195,137 @@ impl ArchiveWriterConfig {
                     None
                 }
             },
+            compress: {
+                if self.is_layers_enabled(Layers::COMPRESS) {
+                    Some(self.compress.to_persistent())
+                } else {
+                    None
+                }
+            },
+            sign: {
+                if self.is_layers_enabled(Layers::SIGN) {
+                    Some(self.sign.to_persistent())
+                } else {
+                    None
+                }
+            },
+            dedup: self.dedup,
         })
     }
 
+    /// Pick the compression block size and the encryption chunk size from the
+    /// sizes of the entries expected in the archive
+    ///
+    /// The heuristic is keyed on the median entry size:
+    /// - an encryption chunk should roughly hold one entry, so that reading a
+    ///   small entry does not require decrypting much more than itself
+    /// - a compression block should hold several entries, so that small
+    ///   entries still benefit from a shared compression context, while big
+    ///   entries get bigger blocks for a better ratio
+    ///
+    /// Values are kept in ranges giving reasonable performances. If
+    /// `entry_sizes` is empty, the configuration is left unchanged.
+    pub fn tune_for_entry_sizes(&mut self, entry_sizes: &[u64]) -> &mut ArchiveWriterConfig {
+        if entry_sizes.is_empty() {
+            return self;
+        }
+        let mut sorted = entry_sizes.to_vec();
+        sorted.sort_unstable();
+        let median = sorted[sorted.len() / 2];
+
+        let chunk_size = median
+            .checked_next_power_of_two()
+            .unwrap_or(TUNE_MAX_CHUNK_SIZE)
+            .clamp(TUNE_MIN_CHUNK_SIZE, TUNE_MAX_CHUNK_SIZE);
+        let block_size = median
+            .saturating_mul(TUNE_ENTRIES_PER_BLOCK)
+            .checked_next_power_of_two()
+            .unwrap_or(TUNE_MAX_BLOCK_SIZE)
+            .clamp(TUNE_MIN_BLOCK_SIZE, TUNE_MAX_BLOCK_SIZE);
+
+        // Tuning ranges are included in the allowed ones, these cannot fail
+        self.with_encryption_chunk_size(chunk_size as u32)
+            .expect("Chunk size out of range");
+        self.with_compression_block_size(block_size as u32)
+            .expect("Block size out of range");
+        self
+    }
+
+    /// Make the archive reproducible: the same entries, added in the same
+    /// order with the same configuration, give the same archive
+    ///
+    /// The encryption secrets (symmetric key, nonce, ephemeral keys, password
+    /// salt) are derived from `seed` instead of the OS generator, and the
+    /// modification times of files metadata are not recorded. The footer is
+    /// always written sorted.
+    ///
+    /// As archives with different contents created from the same seed share
+    /// their key and nonce, the cipher is set to `AesGcmSiv256`, which resists
+    /// nonce reuse: such archives only reveal which of their chunks are
+    /// identical. Selecting another cipher afterwards is refused by `check`.
+    ///
+    /// SECURITY: anyone knowing `seed` can decrypt the archive, so it must be
+    /// as protected as the archive content
+    pub fn with_deterministic_seed(&mut self, seed: [u8; 32]) -> &mut ArchiveWriterConfig {
+        self.deterministic = true;
+        self.with_encryption_cipher(EncryptionCipher::AesGcmSiv256)
+            .with_rng(ChaChaRng::from_seed(seed))
+    }
+
+    /// Return whether the archive is reproducible, see
+    /// `with_deterministic_seed`
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Pad the entries, and add decoy padding at the end of the entries,
+    /// according to `policy`
+    ///
+    /// Even encrypted, an archive reveals the approximate sizes of its
+    /// entries. With padding, only their sizes rounded to the granularity can
+    /// be inferred, and the random decoy blurs their total size. Padding is
+    /// made of random bytes, and is ignored by readers.
+    ///
+    /// Entries are padded before being compressed, so the stored sizes of
+    /// compressible entries would still reveal their content sizes: the
+    /// compression layer must be disabled, or `check` fails with
+    /// `ConfigError::PaddingWithCompression`.
+    ///
+    /// Entries written interleaved are padded from their start to their end,
+    /// the blocks of the other entries included
+    pub fn with_padding_policy(&mut self, policy: PaddingPolicy) -> &mut ArchiveWriterConfig {
+        self.padding = Some(policy);
+        self
+    }
+
+    /// Return the padding policy, if any
+    pub fn padding_policy(&self) -> Option<PaddingPolicy> {
+        self.padding
+    }
+
+    /// Store the content of the entries by chunks, each distinct chunk being
+    /// stored once, whatever the entries it appears in
+    ///
+    /// Chunks are cut according to the content itself, so that data shifted
+    /// by an insertion (ex: a disk image of the next day) still gives the same
+    /// chunks. The chunks of each entry are listed in the footer, and readers
+    /// transparently reassemble them. See `ArchiveWriter::dedup_stats` for the
+    /// space saved.
+    ///
+    /// The hash of each distinct chunk is kept in memory while writing. As
+    /// shared chunks are only listed in the footer, such archives cannot be
+    /// repaired: the fail-safe reader refuses them with
+    /// `Error::RepairUnsupportedDedup`
+    pub fn with_dedup(&mut self) -> &mut ArchiveWriterConfig {
+        self.dedup = true;
+        self
+    }
+
+    /// Return whether identical chunks are stored once, see `with_dedup`
+    pub fn is_dedup_enabled(&self) -> bool {
+        self.dedup
+    }
+
     /// Check if layers are enabled
     pub fn is_layers_enabled(&self, layer: Layers) -> bool {
         self.layers_enabled.contains(layer)
@@ -79,6 +335,15 @@ impl ArchiveWriterConfig {
     pub fn check(&self) -> Result<(), ConfigError> {
         if self.is_layers_enabled(Layers::ENCRYPT) {
             self.encrypt.check()?;
+            if self.deterministic && self.encryption_cipher() != EncryptionCipher::AesGcmSiv256 {
+                return Err(ConfigError::DeterministicCipherNotMisuseResistant);
+            }
+        }
+        if self.padding.is_some() && self.is_layers_enabled(Layers::COMPRESS) {
+            return Err(ConfigError::PaddingWithCompression);
+        }
+        if self.is_layers_enabled(Layers::SIGN) {
+            self.sign.check()?;
         }
         Ok(())
     }
@@ -93,6 +358,12 @@ impl std::default::Default for ArchiveWriterConfig {
             layers_enabled: Layers::default(),
             compress: CompressionConfig::default(),
             encrypt: EncryptionConfig::default(),
+            sign: SignatureConfig::default(),
+            output_digest: false,
+            deterministic: false,
+            padding: None,
+            dedup: false,
+            progress: None,
         }
     }
 }
@@ -104,6 +375,13 @@ pub struct ArchiveReaderConfig {
 
     // Layers specifics
     pub encrypt: EncryptionReaderConfig,
+    pub compress: CompressionReaderConfig,
+    pub sign: SignatureReaderConfig,
+
+    /// Number of blocks kept in cache, see `with_block_cache`
+    pub(crate) block_cache: usize,
+    /// Observer of the progress, see `with_progress_observer`
+    pub(crate) progress: Option<ProgressHandle>,
 }
 
 impl ArchiveReaderConfig {
@@ -112,6 +390,10 @@ impl ArchiveReaderConfig {
         Self {
             layers_enabled: Layers::EMPTY,
             encrypt: EncryptionReaderConfig::default(),
+            compress: CompressionReaderConfig::default(),
+            sign: SignatureReaderConfig::default(),
+            block_cache: 0,
+            progress: None,
         }
     }
 
@@ -123,7 +405,27 @@ impl ArchiveReaderConfig {
         if self.layers_enabled.contains(Layers::ENCRYPT) {
             match config.encrypt {
                 Some(to_load) => {
-                    self.encrypt.load_persistent(to_load)?;
+                    self.encrypt.load_persistent(&to_load)?;
+                }
+                None => {
+                    return Err(ConfigError::IncoherentPersistentConfig);
+                }
+            }
+        }
+        if self.layers_enabled.contains(Layers::COMPRESS) {
+            match config.compress {
+                Some(to_load) => {
+                    self.compress.load_persistent(to_load)?;
+                }
+                None => {
+                    return Err(ConfigError::IncoherentPersistentConfig);
+                }
+            }
+        }
+        if self.layers_enabled.contains(Layers::SIGN) {
+            match config.sign {
+                Some(to_load) => {
+                    self.sign.load_persistent(to_load)?;
                 }
                 None => {
                     return Err(ConfigError::IncoherentPersistentConfig);
@@ -147,4 +449,29 @@ mod tests {
             .disable_layer(Layers::ENCRYPT);
         assert_eq!(builder.layers_enabled, Layers::COMPRESS);
     }
+
+    #[test]
+    fn tune_for_entry_sizes() {
+        let mut config = ArchiveWriterConfig::new();
+        let default_block = config.compression_block_size();
+        let default_chunk = config.encryption_chunk_size();
+        config.tune_for_entry_sizes(&[]);
+        assert_eq!(config.compression_block_size(), default_block);
+        assert_eq!(config.encryption_chunk_size(), default_chunk);
+
+        // Many small entries
+        config.tune_for_entry_sizes(&[10, 200, 1000, 3000, 1 << 30]);
+        assert_eq!(config.encryption_chunk_size() as u64, TUNE_MIN_CHUNK_SIZE);
+        assert_eq!(config.compression_block_size() as u64, TUNE_MIN_BLOCK_SIZE);
+
+        // Mostly medium-sized entries
+        config.tune_for_entry_sizes(&[300 * 1024, 500 * 1024, 600 * 1024]);
+        assert_eq!(config.encryption_chunk_size(), 512 * 1024);
+        assert_eq!(config.compression_block_size(), 4 * 1024 * 1024);
+
+        // Huge entries
+        config.tune_for_entry_sizes(&[u64::MAX, 1 << 40]);
+        assert_eq!(config.encryption_chunk_size() as u64, TUNE_MAX_CHUNK_SIZE);
+        assert_eq!(config.compression_block_size() as u64, TUNE_MAX_BLOCK_SIZE);
+    }
 }
@@ -1,25 +1,27 @@
 use crate::Error;
 
+// `aes` and `ghash` select at runtime, depending on the CPU, either a hardware
+// accelerated implementation (AES-NI and CLMUL, ARMv8 AES and PMULL) or a
+// constant-time software one.
+// See `crate::crypto::backend` to know which one is in use
 use aes::{
-    block_cipher::{BlockCipher, NewBlockCipher},
+    cipher::{BlockEncrypt, KeyInit, KeyIvInit, StreamCipher, StreamCipherSeek},
     Aes256,
 };
-use aes_ctr;
-use aes_ctr::stream_cipher::{NewStreamCipher, StreamCipher, SyncStreamCipherSeek};
 use generic_array::{typenum::U16, GenericArray};
-use ghash::{
-    universal_hash::{NewUniversalHash, UniversalHash},
-    GHash,
-};
+use ghash::{universal_hash::UniversalHash, GHash};
+use std::slice;
 
 pub use subtle::ConstantTimeEq;
 
 pub const BLOCK_SIZE: usize = 128 / 8;
 pub const TAG_LENGTH: usize = BLOCK_SIZE;
 
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
 // Inspired from RustCrypto's AesGcm implementation
 pub struct AesGcm256 {
-    cipher: aes_ctr::Aes256Ctr,
+    cipher: Aes256Ctr,
     /// Gallois Hash, for data authentication
     ghash: GHash,
     /// Size of the authenticated data, in bits
@@ -59,7 +61,7 @@ impl AesGcm256 {
         ghash.update_padded(associated_data);
 
         // Prepare the cipher for further operations
-        let mut cipher = aes_ctr::Aes256Ctr::new_var(key, &counter_block)?;
+        let mut cipher = Aes256Ctr::new_from_slices(key, &counter_block)?;
         // First block is ignored, as it has been used for the GHash
         cipher.seek(BLOCK_SIZE as u64);
 
@@ -79,18 +81,19 @@ impl AesGcm256 {
         // Finish the current block, if any
         if !self.current_block.is_empty() {
             if (self.current_block.len() + buffer.len()) < BLOCK_SIZE {
-                self.cipher.encrypt(buffer);
+                self.cipher.apply_keystream(buffer);
                 self.current_block.extend_from_slice(buffer);
                 return;
             } else {
                 let (in_block, out_block) =
                     buffer.split_at_mut(BLOCK_SIZE - self.current_block.len());
-                self.cipher.encrypt(in_block);
+                self.cipher.apply_keystream(in_block);
                 self.current_block.extend_from_slice(in_block);
                 // `current_block` length is now BLOCK_SIZE -> update GHash and
                 // clear it
-                self.ghash
-                    .update(GenericArray::from_slice(self.current_block.as_slice()));
+                self.ghash.update(slice::from_ref(GenericArray::from_slice(
+                    self.current_block.as_slice(),
+                )));
                 self.current_block.clear();
 
                 // Deals with the rest of the data, now aligned on BLOCK_SIZE
@@ -102,14 +105,15 @@ impl AesGcm256 {
 
         // Interleaved ghash update
         for chunk in &mut chunks {
-            self.cipher.encrypt(chunk);
-            self.ghash.update(GenericArray::from_slice(chunk));
+            self.cipher.apply_keystream(chunk);
+            self.ghash
+                .update(slice::from_ref(GenericArray::from_slice(chunk)));
         }
 
         // Encrypt and save extra encrypted bytes for further GHash computation
         let rem = chunks.into_remainder();
         if !rem.is_empty() {
-            self.cipher.encrypt(rem);
+            self.cipher.apply_keystream(rem);
             self.current_block.extend_from_slice(rem);
         }
     }
@@ -126,19 +130,19 @@ impl AesGcm256 {
         block[..8].copy_from_slice(&self.associated_data_bits_len.to_be_bytes());
         block[8..].copy_from_slice(&buffer_bits.to_be_bytes());
 
-        self.ghash.update(&block);
+        self.ghash.update(slice::from_ref(&block));
 
         // Final update
-        let mut tag = self.ghash.finalize().into_bytes();
-        self.cipher.seek(0);
-        self.cipher.encrypt(tag.as_mut_slice());
+        let mut tag = self.ghash.finalize();
+        self.cipher.seek(0u64);
+        self.cipher.apply_keystream(tag.as_mut_slice());
         tag
     }
 
     /// Decrypt without considering the associated data
     /// /!\ this mode of decryption is unauthenticated, use it carefully
     pub fn decrypt_unauthenticated(&mut self, buffer: &mut [u8]) {
-        self.cipher.decrypt(buffer);
+        self.cipher.apply_keystream(buffer);
     }
 
     /// Decrypt and compute the associated tag
@@ -147,14 +151,15 @@ impl AesGcm256 {
 
         // Interleaved ghash update
         for chunk in &mut chunks {
-            self.ghash.update(GenericArray::from_slice(chunk));
-            self.cipher.decrypt(chunk);
+            self.ghash
+                .update(slice::from_ref(GenericArray::from_slice(chunk)));
+            self.cipher.apply_keystream(chunk);
         }
 
         let rem = chunks.into_remainder();
         if !rem.is_empty() {
             self.ghash.update_padded(rem);
-            self.cipher.decrypt(rem);
+            self.cipher.apply_keystream(rem);
         }
 
         // Compute "len(associated data) || len(bytes encrypted)"
@@ -163,12 +168,12 @@ impl AesGcm256 {
         block[..8].copy_from_slice(&self.associated_data_bits_len.to_be_bytes());
         block[8..].copy_from_slice(&buffer_bits.to_be_bytes());
 
-        self.ghash.update(&block);
+        self.ghash.update(slice::from_ref(&block));
 
         // Final update
-        let mut tag = self.ghash.clone().finalize().into_bytes();
-        self.cipher.seek(0);
-        self.cipher.encrypt(tag.as_mut_slice());
+        let mut tag = self.ghash.clone().finalize();
+        self.cipher.seek(0u64);
+        self.cipher.apply_keystream(tag.as_mut_slice());
         tag
     }
 }
@@ -176,12 +181,12 @@ impl AesGcm256 {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use aead::{generic_array::GenericArray, AeadMut, NewAead, Payload};
+    use aead::{generic_array::GenericArray, Aead, KeyInit, Payload};
     use aes_gcm::Aes256Gcm;
 
     fn test_against_aesgcm(key: &[u8], nonce: &[u8], associated_data: &[u8], msg: &[u8]) {
         // Full (all at once)
-        let mut extern_cipher = Aes256Gcm::new(&GenericArray::clone_from_slice(key));
+        let extern_cipher = Aes256Gcm::new(&GenericArray::clone_from_slice(key));
         let extern_ciphertext = extern_cipher
             .encrypt(
                 &GenericArray::clone_from_slice(nonce),
@@ -196,7 +201,7 @@ mod tests {
         let mut buf = msg.to_vec();
         crate_cipher.encrypt(&mut buf);
         let tag = crate_cipher.into_tag();
-        assert_eq!(tag.len() as usize, TAG_LENGTH);
+        assert_eq!(tag.len(), TAG_LENGTH);
 
         assert_eq!(
             &extern_ciphertext[..extern_ciphertext.len() - TAG_LENGTH],
@@ -219,7 +224,7 @@ mod tests {
                 crate_cipher.encrypt(chunk);
             }
             let tag = crate_cipher.into_tag();
-            assert_eq!(tag.len() as usize, TAG_LENGTH);
+            assert_eq!(tag.len(), TAG_LENGTH);
 
             assert_eq!(
                 &extern_ciphertext[..extern_ciphertext.len() - TAG_LENGTH],
@@ -259,7 +264,7 @@ mod tests {
         let associated_data = b"\xd6\x09\xb1\xf0\x56\x63\x7a\x0d\x46\xdf\x99\x8d\x88\xe5\x2e\x00\xb2\xc2\x84\x65\x12\x15\x35\x24\xc0\x89\x5e\x81";
         let msg = b"\x08\x00\x0f\x10\x11\x12\x13\x14\x15\x16\x17\x18\x19\x1a\x1b\x1c\x1d\x1e\x1f\x20\x21\x22\x23\x24\x25\x26\x27\x28\x29\x2a\x2b\x2c\x2d\x2e\x2f\x30\x31\x32\x33\x34\x35\x36\x37\x38\x39\x3a\x00\x02";
 
-        let mut extern_cipher = Aes256Gcm::new(&GenericArray::clone_from_slice(key));
+        let extern_cipher = Aes256Gcm::new(&GenericArray::clone_from_slice(key));
         let extern_ciphertext = extern_cipher
             .encrypt(
                 &GenericArray::clone_from_slice(nonce),
@@ -274,7 +279,7 @@ mod tests {
         let mut buf = msg.to_vec();
         crate_cipher.encrypt(&mut buf);
         let tag = crate_cipher.into_tag();
-        assert_eq!(tag.len() as usize, TAG_LENGTH);
+        assert_eq!(tag.len(), TAG_LENGTH);
 
         // Unauthenticated decryption
         let mut crate_cipher = AesGcm256::new(key, nonce, b"").unwrap();
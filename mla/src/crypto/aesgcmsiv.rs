@@ -0,0 +1,66 @@
+use crate::crypto::aesgcm::{Tag, TAG_LENGTH};
+use crate::Error;
+
+// `aes` and `polyval` select at runtime, as for AES-GCM, a hardware accelerated
+// implementation if available
+use aes_gcm_siv::aead::{generic_array::GenericArray, AeadInPlace, KeyInit};
+use aes_gcm_siv::Aes256GcmSiv;
+
+/// Encrypt `buffer` in place with AES-256-GCM-SIV, and return the tag
+///
+/// Unlike `AesGcm256`, this is not a streaming API: the tag (also used as the
+/// IV) depends on the whole plaintext, which must be known before encrypting
+pub fn encrypt(key: &[u8], nonce: &[u8], buffer: &mut [u8]) -> Result<Tag, Error> {
+    Aes256GcmSiv::new(GenericArray::from_slice(key))
+        .encrypt_in_place_detached(GenericArray::from_slice(nonce), b"", buffer)
+        .map_err(|_| Error::AssertionError("AES-GCM-SIV encryption failed".to_string()))
+}
+
+/// Decrypt `buffer` in place with AES-256-GCM-SIV, and check its `tag`
+///
+/// On a wrong tag, `buffer` is left unmodified
+pub fn decrypt(key: &[u8], nonce: &[u8], buffer: &mut [u8], tag: &[u8]) -> Result<(), Error> {
+    if tag.len() != TAG_LENGTH {
+        return Err(Error::AuthenticatedDecryptionWrongTag);
+    }
+    Aes256GcmSiv::new(GenericArray::from_slice(key))
+        .decrypt_in_place_detached(
+            GenericArray::from_slice(nonce),
+            b"",
+            buffer,
+            GenericArray::from_slice(tag),
+        )
+        .map_err(|_| Error::AuthenticatedDecryptionWrongTag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static KEY: [u8; 32] = [1u8; 32];
+    static NONCE: [u8; 12] = [2u8; 12];
+
+    #[test]
+    fn encrypt_decrypt() {
+        let data = b"abcdefghijklmnopqrstuvwxyz".to_vec();
+        let mut buffer = data.clone();
+        let tag = encrypt(&KEY, &NONCE, &mut buffer).unwrap();
+        assert_ne!(buffer, data);
+
+        // Deterministic: same inputs, same output
+        let mut buffer2 = data.clone();
+        assert_eq!(encrypt(&KEY, &NONCE, &mut buffer2).unwrap(), tag);
+        assert_eq!(buffer2, buffer);
+
+        // Tampered data or tag are refused
+        let mut tampered = buffer.clone();
+        tampered[0] ^= 1;
+        assert!(decrypt(&KEY, &NONCE, &mut tampered, &tag).is_err());
+        let mut tampered_tag = tag;
+        tampered_tag[0] ^= 1;
+        assert!(decrypt(&KEY, &NONCE, &mut buffer.clone(), &tampered_tag).is_err());
+
+        decrypt(&KEY, &NONCE, &mut buffer, &tag).unwrap();
+        assert_eq!(buffer, data);
+    }
+}
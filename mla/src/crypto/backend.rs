@@ -0,0 +1,186 @@
+use std::fmt;
+
+/// Implementation used for a cryptographic primitive
+///
+/// The choice is made at runtime by the underlying crates (`aes`, `ghash`),
+/// depending on the CPU features. Without hardware support, a constant-time
+/// software implementation is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// AES-NI instructions (x86, x86_64)
+    AesNi,
+    /// Carry-less multiplication instructions, for GHASH (x86, x86_64)
+    Clmul,
+    /// ARMv8 Cryptography Extensions AES instructions (aarch64)
+    ArmAes,
+    /// ARMv8 Cryptography Extensions polynomial multiplication, for GHASH
+    /// (aarch64)
+    Pmull,
+    /// Constant-time software implementation (bitsliced AES, portable GHASH)
+    Software,
+}
+
+impl Backend {
+    /// Returns whether this implementation relies on dedicated instructions
+    pub fn is_hardware(self) -> bool {
+        self != Backend::Software
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Backend::AesNi => write!(f, "AES-NI (hardware)"),
+            Backend::Clmul => write!(f, "CLMUL (hardware)"),
+            Backend::ArmAes => write!(f, "ARMv8 AES (hardware)"),
+            Backend::Pmull => write!(f, "PMULL (hardware)"),
+            Backend::Software => write!(f, "constant-time software"),
+        }
+    }
+}
+
+/// Implementations in use for the encryption layer (AES-GCM)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CryptoBackends {
+    /// Block cipher, used in CTR mode
+    pub aes: Backend,
+    /// Universal hash, used for authentication
+    pub ghash: Backend,
+}
+
+// The `cpufeatures` tokens of `aes` and `polyval` (on which `ghash` relies) are
+// private. The ones below are declared with the same features and under the
+// same `cfg` as these crates, so that the report matches their own choice:
+// - on x86 and x86_64, AES-NI and CLMUL are always compiled in;
+// - on aarch64, ARMv8 AES and PMULL are only compiled in with
+//   `--cfg aes_armv8` and `--cfg polyval_armv8` (see `.cargo/config`);
+// - `--cfg aes_force_soft` and `--cfg polyval_force_soft` disable them.
+
+#[cfg(all(
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        all(target_arch = "aarch64", aes_armv8)
+    ),
+    not(aes_force_soft)
+))]
+cpufeatures::new!(aes_intrinsics, "aes");
+
+// On aarch64, `aes` implies PMULL
+#[cfg(all(target_arch = "aarch64", polyval_armv8, not(polyval_force_soft)))]
+cpufeatures::new!(mul_intrinsics, "aes");
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    not(polyval_force_soft)
+))]
+cpufeatures::new!(mul_intrinsics, "pclmulqdq");
+
+#[cfg(all(any(target_arch = "x86", target_arch = "x86_64"), not(aes_force_soft)))]
+fn aes_backend() -> Backend {
+    if aes_intrinsics::get() {
+        Backend::AesNi
+    } else {
+        Backend::Software
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", aes_armv8, not(aes_force_soft)))]
+fn aes_backend() -> Backend {
+    if aes_intrinsics::get() {
+        Backend::ArmAes
+    } else {
+        Backend::Software
+    }
+}
+
+#[cfg(not(all(
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        all(target_arch = "aarch64", aes_armv8)
+    ),
+    not(aes_force_soft)
+)))]
+fn aes_backend() -> Backend {
+    Backend::Software
+}
+
+#[cfg(all(
+    any(target_arch = "x86", target_arch = "x86_64"),
+    not(polyval_force_soft)
+))]
+fn ghash_backend() -> Backend {
+    if mul_intrinsics::get() {
+        Backend::Clmul
+    } else {
+        Backend::Software
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", polyval_armv8, not(polyval_force_soft)))]
+fn ghash_backend() -> Backend {
+    if mul_intrinsics::get() {
+        Backend::Pmull
+    } else {
+        Backend::Software
+    }
+}
+
+#[cfg(not(all(
+    any(
+        target_arch = "x86",
+        target_arch = "x86_64",
+        all(target_arch = "aarch64", polyval_armv8)
+    ),
+    not(polyval_force_soft)
+)))]
+fn ghash_backend() -> Backend {
+    Backend::Software
+}
+
+/// Return the implementations used, on this CPU, by the encryption layer
+pub fn active_backends() -> CryptoBackends {
+    CryptoBackends {
+        aes: aes_backend(),
+        ghash: ghash_backend(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backends_consistency() {
+        let backends = active_backends();
+        // Only the expected kind of hardware acceleration can be reported
+        assert!(matches!(
+            backends.aes,
+            Backend::AesNi | Backend::ArmAes | Backend::Software
+        ));
+        assert!(matches!(
+            backends.ghash,
+            Backend::Clmul | Backend::Pmull | Backend::Software
+        ));
+        assert_eq!(backends, active_backends());
+    }
+
+    #[cfg(all(
+        any(target_arch = "x86", target_arch = "x86_64"),
+        not(aes_force_soft),
+        not(polyval_force_soft)
+    ))]
+    #[test]
+    fn backends_x86() {
+        // Cross-check with the standard library detection
+        let backends = active_backends();
+        assert_eq!(
+            backends.aes == Backend::AesNi,
+            is_x86_feature_detected!("aes")
+        );
+        assert_eq!(
+            backends.ghash == Backend::Clmul,
+            is_x86_feature_detected!("pclmulqdq")
+        );
+    }
+}
@@ -12,6 +12,23 @@ const KEY_SIZE: usize = 32;
 const DERIVE_KEY_INFO: &[u8; 14] = b"KEY DERIVATION";
 const ECIES_NONCE: &[u8; 12] = b"ECIES NONCE0";
 
+/// Private key whose X25519 operation is made outside of MLA, for instance by
+/// a hardware token through PKCS#11, so that it never has to be in memory
+///
+/// Providers are used, as private keys, to recover the archive key from its
+/// recipients. See `ArchiveReaderConfig::add_private_key_provider`
+pub trait PrivateKeyProvider: Send {
+    /// Return the X25519 shared secret of the private key and `public`
+    fn diffie_hellman(&self, public: &PublicKey) -> Result<[u8; 32], Error>;
+}
+
+fn expand_shared_secret(shared_secret: &[u8], length: usize) -> Result<Vec<u8>, Error> {
+    let hkdf: Hkdf<Sha256> = Hkdf::new(None, shared_secret);
+    let mut output = vec![0u8; length];
+    hkdf.expand(DERIVE_KEY_INFO, output.as_mut_slice())?;
+    Ok(output)
+}
+
 // Implementation inspired from XSTREAM/x25519hkdf.rs
 // /!\ in XSTREAM/x25519hkdf.rs, the arguments of Hkdf::new seem inverted
 fn derive_key(
@@ -19,12 +36,10 @@ fn derive_key(
     public_key: &PublicKey,
     length: usize,
 ) -> Result<Vec<u8>, Error> {
-    let mut shared_secret = private_key.diffie_hellman(&public_key);
-    let hkdf: Hkdf<Sha256> = Hkdf::new(None, shared_secret.as_bytes());
-    let mut output = vec![0u8; length];
-    hkdf.expand(DERIVE_KEY_INFO, output.as_mut_slice())?;
+    let mut shared_secret = private_key.diffie_hellman(public_key);
+    let output = expand_shared_secret(shared_secret.as_bytes(), length);
     shared_secret.zeroize();
-    Ok(output)
+    output
 }
 
 #[derive(Serialize, Deserialize)]
@@ -89,15 +104,62 @@ pub(crate) fn retrieve_key(
 ) -> Result<Option<[u8; KEY_SIZE]>, Error> {
     // Perform an ECIES to obtain the common key
     let key = derive_key(private_key, &PublicKey::from(persist.public), KEY_SIZE)?;
+    unwrap_key(persist, &key)
+}
 
+/// Try to recover the shared key from the `MultiRecipientPersistent`, the
+/// Diffie-Hellman being made by `provider`
+pub(crate) fn retrieve_key_with_provider(
+    persist: &MultiRecipientPersistent,
+    provider: &dyn PrivateKeyProvider,
+) -> Result<Option<[u8; KEY_SIZE]>, Error> {
+    let mut shared_secret = provider.diffie_hellman(&PublicKey::from(persist.public))?;
+    let key = expand_shared_secret(&shared_secret, KEY_SIZE);
+    shared_secret.zeroize();
+    unwrap_key(persist, &key?)
+}
+
+/// Return the index of the recipient slot `private_key` opens, if any
+pub(crate) fn find_recipient_slot(
+    persist: &MultiRecipientPersistent,
+    private_key: &StaticSecret,
+) -> Result<Option<usize>, Error> {
+    let key = derive_key(private_key, &PublicKey::from(persist.public), KEY_SIZE)?;
+    Ok(unwrap_key_slot(persist, &key)?.map(|(index, mut data)| {
+        data.zeroize();
+        index
+    }))
+}
+
+impl MultiRecipientPersistent {
+    /// Number of recipient slots
+    pub(crate) fn count(&self) -> usize {
+        self.encrypted_keys.len()
+    }
+}
+
+/// Find the shared key encrypted with `key`, the ECIES derived key of one of
+/// the recipients
+fn unwrap_key(
+    persist: &MultiRecipientPersistent,
+    key: &[u8],
+) -> Result<Option<[u8; KEY_SIZE]>, Error> {
+    Ok(unwrap_key_slot(persist, key)?.map(|(_, data)| data))
+}
+
+/// Like `unwrap_key`, also returning the index of the recipient slot
+fn unwrap_key_slot(
+    persist: &MultiRecipientPersistent,
+    key: &[u8],
+) -> Result<Option<(usize, [u8; KEY_SIZE])>, Error> {
     // Try to find the correct key using the tag validation
-    for keytag in persist.encrypted_keys.iter() {
-        let mut cipher = aesgcm::AesGcm256::new(key.as_slice(), ECIES_NONCE, b"")?;
+    for (index, keytag) in persist.encrypted_keys.iter().enumerate() {
+        let mut cipher = aesgcm::AesGcm256::new(key, ECIES_NONCE, b"")?;
         let mut data = [0u8; KEY_SIZE];
         data.copy_from_slice(&keytag.key);
         let tag = cipher.decrypt(&mut data);
         if tag.ct_eq(&keytag.tag).unwrap_u8() == 1 {
-            return Ok(Some(data));
+            return Ok(Some((index, data)));
         }
     }
     Ok(None)
@@ -0,0 +1,361 @@
+use crate::crypto::aesgcm;
+use crate::crypto::aesgcm::ConstantTimeEq;
+use crate::errors::Error;
+use hkdf::Hkdf;
+use ml_kem::kem::Decapsulate;
+use ml_kem::{
+    Ciphertext, EncapsulateDeterministic, Encoded, EncodedSizeUser, KemCore, MlKem768, B32,
+};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::convert::TryFrom;
+use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::Zeroize;
+
+const KEY_SIZE: usize = 32;
+const ECC_KEY_SIZE: usize = 32;
+/// Seeds `d` and `z` of the ML-KEM key generation
+const KEM_SEED_SIZE: usize = 64;
+/// Size of an ML-KEM-768 encapsulation key
+const KEM_PUBLIC_KEY_SIZE: usize = 1184;
+const DERIVE_KEY_INFO: &[u8; 14] = b"MLA HYBRID KEY";
+const HYBRID_NONCE: &[u8; 12] = b"HYBRID NONCE";
+
+type EncapsulationKey = <MlKem768 as KemCore>::EncapsulationKey;
+type DecapsulationKey = <MlKem768 as KemCore>::DecapsulationKey;
+
+/// Public key of a hybrid recipient: X25519 and ML-KEM-768
+///
+/// The shared key is only recovered with both private parts, so the archive
+/// stays confidential as long as one of the two algorithms is not broken
+#[derive(Clone)]
+pub struct HybridPublicKey {
+    ecc: PublicKey,
+    kem: EncapsulationKey,
+}
+
+impl HybridPublicKey {
+    /// Serialized size: X25519 public key, then ML-KEM encapsulation key
+    pub const SIZE: usize = ECC_KEY_SIZE + KEM_PUBLIC_KEY_SIZE;
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != Self::SIZE {
+            return Err(Error::InvalidECCKeyFormat);
+        }
+        let mut ecc = [0u8; ECC_KEY_SIZE];
+        ecc.copy_from_slice(&bytes[..ECC_KEY_SIZE]);
+        let kem = Encoded::<EncapsulationKey>::try_from(&bytes[ECC_KEY_SIZE..])
+            .map_err(|_| Error::InvalidECCKeyFormat)?;
+        Ok(Self {
+            ecc: PublicKey::from(ecc),
+            kem: EncapsulationKey::from_bytes(&kem),
+        })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend_from_slice(self.ecc.as_bytes());
+        bytes.extend_from_slice(&self.kem.as_bytes());
+        bytes
+    }
+
+    /// Stable identifier of the key, to reference a recipient: "SHA256:",
+    /// followed by the hex encoded SHA-256 of its serialization
+    pub fn fingerprint(&self) -> String {
+        let digest = Sha256::digest(self.to_bytes());
+        let hex: String = digest.iter().map(|byte| format!("{:02x}", byte)).collect();
+        format!("SHA256:{}", hex)
+    }
+}
+
+/// Private key of a hybrid recipient, see `HybridPublicKey`
+///
+/// Only the seeds are kept: the ML-KEM decapsulation key is expanded from them
+/// when needed
+#[derive(Clone)]
+pub struct HybridPrivateKey {
+    ecc: StaticSecret,
+    kem_seed: [u8; KEM_SEED_SIZE],
+}
+
+impl HybridPrivateKey {
+    /// Serialized size: X25519 private key, then ML-KEM seeds
+    pub const SIZE: usize = ECC_KEY_SIZE + KEM_SEED_SIZE;
+
+    pub fn generate<T: RngCore + CryptoRng>(csprng: &mut T) -> Self {
+        let mut kem_seed = [0u8; KEM_SEED_SIZE];
+        csprng.fill_bytes(&mut kem_seed);
+        Self {
+            ecc: StaticSecret::new(csprng),
+            kem_seed,
+        }
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        if bytes.len() != Self::SIZE {
+            return Err(Error::InvalidECCKeyFormat);
+        }
+        let mut ecc = [0u8; ECC_KEY_SIZE];
+        ecc.copy_from_slice(&bytes[..ECC_KEY_SIZE]);
+        let mut kem_seed = [0u8; KEM_SEED_SIZE];
+        kem_seed.copy_from_slice(&bytes[ECC_KEY_SIZE..]);
+        let key = Self {
+            ecc: StaticSecret::from(ecc),
+            kem_seed,
+        };
+        ecc.zeroize();
+        kem_seed.zeroize();
+        Ok(key)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(Self::SIZE);
+        bytes.extend_from_slice(&self.ecc.to_bytes());
+        bytes.extend_from_slice(&self.kem_seed);
+        bytes
+    }
+
+    pub fn public_key(&self) -> HybridPublicKey {
+        HybridPublicKey {
+            ecc: PublicKey::from(&self.ecc),
+            kem: self.kem_keys().1,
+        }
+    }
+
+    fn kem_keys(&self) -> (DecapsulationKey, EncapsulationKey) {
+        let mut d = [0u8; 32];
+        let mut z = [0u8; 32];
+        d.copy_from_slice(&self.kem_seed[..32]);
+        z.copy_from_slice(&self.kem_seed[32..]);
+        let keys = MlKem768::generate_deterministic(&B32::from(d), &B32::from(z));
+        d.zeroize();
+        z.zeroize();
+        keys
+    }
+}
+
+impl Zeroize for HybridPrivateKey {
+    fn zeroize(&mut self) {
+        self.ecc.zeroize();
+        self.kem_seed.zeroize();
+    }
+}
+
+impl Drop for HybridPrivateKey {
+    fn drop(&mut self) {
+        self.kem_seed.zeroize();
+    }
+}
+
+/// Combine both shared secrets into the key wrapping the shared key
+///
+/// The X25519 public keys are also hashed, as in X-Wing, ML-KEM being already
+/// bound to its ciphertext
+fn derive_key(
+    kem_secret: &[u8],
+    ecc_secret: &[u8],
+    ephemeral: &PublicKey,
+    recipient: &PublicKey,
+) -> Result<[u8; KEY_SIZE], Error> {
+    let mut ikm = Vec::with_capacity(kem_secret.len() + ecc_secret.len() + 2 * ECC_KEY_SIZE);
+    ikm.extend_from_slice(kem_secret);
+    ikm.extend_from_slice(ecc_secret);
+    ikm.extend_from_slice(ephemeral.as_bytes());
+    ikm.extend_from_slice(recipient.as_bytes());
+    let hkdf: Hkdf<Sha256> = Hkdf::new(None, &ikm);
+    ikm.zeroize();
+    let mut output = [0u8; KEY_SIZE];
+    hkdf.expand(DERIVE_KEY_INFO, &mut output)?;
+    Ok(output)
+}
+
+#[derive(Serialize, Deserialize)]
+struct HybridKeyAndTag {
+    /// ML-KEM-768 ciphertext, for this recipient
+    kem_ciphertext: Vec<u8>,
+    key: [u8; KEY_SIZE],
+    tag: [u8; aesgcm::TAG_LENGTH],
+}
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct HybridRecipientsPersistent {
+    /// Ephemeral X25519 public key, shared by the recipients
+    public: [u8; ECC_KEY_SIZE],
+    encrypted_keys: Vec<HybridKeyAndTag>,
+}
+
+/// Wrap the shared `key` for each hybrid recipient, and return a serializable
+/// structure (Key-wrapping made thanks to AesGcm256)
+pub(crate) fn store_key_for_hybrid_recipients<T>(
+    recipients: &[HybridPublicKey],
+    key: &[u8; KEY_SIZE],
+    csprng: &mut T,
+) -> Result<HybridRecipientsPersistent, Error>
+where
+    T: RngCore + CryptoRng,
+{
+    let ephemeral = StaticSecret::new(&mut *csprng);
+    let public = PublicKey::from(&ephemeral);
+    let mut encrypted_keys = Vec::new();
+    for recipient in recipients.iter() {
+        let mut ecc_secret = ephemeral.diffie_hellman(&recipient.ecc);
+        let mut m = [0u8; 32];
+        csprng.fill_bytes(&mut m);
+        let (kem_ciphertext, kem_secret) = recipient
+            .kem
+            .encapsulate_deterministic(&B32::from(m))
+            .map_err(|_| Error::InvalidECCKeyFormat)?;
+        let mut kem_secret: [u8; KEY_SIZE] = kem_secret.into();
+        m.zeroize();
+        let mut wrapping_key =
+            derive_key(&kem_secret, ecc_secret.as_bytes(), &public, &recipient.ecc)?;
+        kem_secret.zeroize();
+        ecc_secret.zeroize();
+
+        // The wrapping key is only used once: no need for a random NONCE
+        let mut cipher = aesgcm::AesGcm256::new(&wrapping_key, HYBRID_NONCE, b"")?;
+        wrapping_key.zeroize();
+        let mut encrypted_key = [0u8; KEY_SIZE];
+        encrypted_key.copy_from_slice(key);
+        cipher.encrypt(&mut encrypted_key);
+        let mut tag = [0u8; aesgcm::TAG_LENGTH];
+        tag.copy_from_slice(&cipher.into_tag());
+        encrypted_keys.push(HybridKeyAndTag {
+            kem_ciphertext: kem_ciphertext.to_vec(),
+            key: encrypted_key,
+            tag,
+        });
+    }
+
+    Ok(HybridRecipientsPersistent {
+        public: *public.as_bytes(),
+        encrypted_keys,
+    })
+}
+
+/// Try to recover the shared key from the `HybridRecipientsPersistent`, using
+/// the private key `private_key`
+pub(crate) fn retrieve_key_with_hybrid(
+    persist: &HybridRecipientsPersistent,
+    private_key: &HybridPrivateKey,
+) -> Result<Option<[u8; KEY_SIZE]>, Error> {
+    Ok(retrieve_key_slot_with_hybrid(persist, private_key)?.map(|(_, data)| data))
+}
+
+/// Return the index of the recipient slot `private_key` opens, if any
+pub(crate) fn find_hybrid_recipient_slot(
+    persist: &HybridRecipientsPersistent,
+    private_key: &HybridPrivateKey,
+) -> Result<Option<usize>, Error> {
+    Ok(
+        retrieve_key_slot_with_hybrid(persist, private_key)?.map(|(index, mut data)| {
+            data.zeroize();
+            index
+        }),
+    )
+}
+
+impl HybridRecipientsPersistent {
+    /// Number of recipient slots
+    pub(crate) fn count(&self) -> usize {
+        self.encrypted_keys.len()
+    }
+}
+
+/// Like `retrieve_key_with_hybrid`, also returning the index of the recipient
+/// slot
+fn retrieve_key_slot_with_hybrid(
+    persist: &HybridRecipientsPersistent,
+    private_key: &HybridPrivateKey,
+) -> Result<Option<(usize, [u8; KEY_SIZE])>, Error> {
+    let ephemeral = PublicKey::from(persist.public);
+    let recipient = PublicKey::from(&private_key.ecc);
+    let mut ecc_secret = private_key.ecc.diffie_hellman(&ephemeral);
+    let (decapsulation_key, _) = private_key.kem_keys();
+
+    // Try to find the correct key using the tag validation
+    for (index, keytag) in persist.encrypted_keys.iter().enumerate() {
+        let kem_ciphertext =
+            match Ciphertext::<MlKem768>::try_from(keytag.kem_ciphertext.as_slice()) {
+                Ok(kem_ciphertext) => kem_ciphertext,
+                Err(_) => continue,
+            };
+        // On a ciphertext for another recipient, ML-KEM returns an unrelated
+        // secret (implicit rejection), so the tag does not match
+        let mut kem_secret: [u8; KEY_SIZE] = match decapsulation_key.decapsulate(&kem_ciphertext) {
+            Ok(kem_secret) => kem_secret.into(),
+            Err(_) => continue,
+        };
+        let mut wrapping_key =
+            derive_key(&kem_secret, ecc_secret.as_bytes(), &ephemeral, &recipient)?;
+        kem_secret.zeroize();
+
+        let mut cipher = aesgcm::AesGcm256::new(&wrapping_key, HYBRID_NONCE, b"")?;
+        wrapping_key.zeroize();
+        let mut data = [0u8; KEY_SIZE];
+        data.copy_from_slice(&keytag.key);
+        let tag = cipher.decrypt(&mut data);
+        if tag.ct_eq(&keytag.tag).unwrap_u8() == 1 {
+            ecc_secret.zeroize();
+            return Ok(Some((index, data)));
+        }
+        data.zeroize();
+    }
+    ecc_secret.zeroize();
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    #[test]
+    fn hybrid_recipients() {
+        // Create fake recipients
+        let mut csprng = ChaChaRng::from_entropy();
+        let recipients_priv: Vec<HybridPrivateKey> = (0..3)
+            .map(|_| HybridPrivateKey::generate(&mut csprng))
+            .collect();
+        let recipients_pub: Vec<HybridPublicKey> =
+            recipients_priv.iter().map(|k| k.public_key()).collect();
+
+        let key = csprng.gen::<[u8; KEY_SIZE]>();
+        let persist = store_key_for_hybrid_recipients(&recipients_pub, &key, &mut csprng).unwrap();
+
+        // Ensure each recipient can retrieve the shared key
+        for private_key in recipients_priv.iter() {
+            let ret_key = retrieve_key_with_hybrid(&persist, private_key)
+                .unwrap()
+                .unwrap();
+            assert_eq!(ret_key, key);
+        }
+
+        // Another key cannot
+        let other = HybridPrivateKey::generate(&mut csprng);
+        assert!(retrieve_key_with_hybrid(&persist, &other)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn hybrid_keys_serialization() {
+        let mut csprng = ChaChaRng::from_entropy();
+        let private_key = HybridPrivateKey::generate(&mut csprng);
+        let bytes = private_key.to_bytes();
+        assert_eq!(bytes.len(), HybridPrivateKey::SIZE);
+        let reloaded = HybridPrivateKey::from_bytes(&bytes).unwrap();
+        assert_eq!(reloaded.to_bytes(), bytes);
+
+        // The public key is recomputed from the private one
+        let public_bytes = private_key.public_key().to_bytes();
+        assert_eq!(public_bytes.len(), HybridPublicKey::SIZE);
+        assert_eq!(reloaded.public_key().to_bytes(), public_bytes);
+        let public_key = HybridPublicKey::from_bytes(&public_bytes).unwrap();
+        assert_eq!(public_key.to_bytes(), public_bytes);
+
+        assert!(HybridPublicKey::from_bytes(&bytes).is_err());
+    }
+}
@@ -0,0 +1,155 @@
+use std::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use std::ops::{Deref, DerefMut};
+use std::ptr::{self, NonNull};
+
+use zeroize::Zeroize;
+
+/// Heap allocation for secrets (private keys, symmetric keys)
+///
+/// Where the platform allows, the memory is locked (`mlock`), so it is not
+/// swapped out, and excluded from core dumps (`MADV_DONTDUMP`). These
+/// protections are best effort: for instance, the amount of locked memory may
+/// be limited by `RLIMIT_MEMLOCK`.
+///
+/// As locks do not stack, each value gets its own pages. The value is zeroized
+/// on drop.
+pub struct Locked<T: Zeroize> {
+    ptr: NonNull<T>,
+    /// Whether the memory has been successfully locked
+    locked: bool,
+}
+
+impl<T: Zeroize> Locked<T> {
+    /// Move `value` to its own locked pages
+    pub fn new(value: T) -> Self {
+        let layout = Self::layout();
+        // Safe as the layout size is never 0
+        let ptr = NonNull::new(unsafe { alloc(layout) } as *mut T)
+            .unwrap_or_else(|| handle_alloc_error(layout));
+        // Safe as `ptr` is valid and aligned for `T`, and not initialized yet
+        unsafe { ptr.as_ptr().write(value) };
+        let locked = sys::lock(ptr.as_ptr() as *mut u8, layout.size());
+        Self { ptr, locked }
+    }
+
+    /// Return whether the memory is effectively locked
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Whole pages, aligned on pages
+    fn layout() -> Layout {
+        let page_size = sys::page_size();
+        let size = std::cmp::max(std::mem::size_of::<T>(), 1);
+        let size = size.div_ceil(page_size) * page_size;
+        let align = std::cmp::max(page_size, std::mem::align_of::<T>());
+        Layout::from_size_align(size, align).expect("Invalid layout for locked memory")
+    }
+}
+
+impl<T: Zeroize> Deref for Locked<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safe as `ptr` is initialized in `new`, and lives as long as `self`
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+impl<T: Zeroize> DerefMut for Locked<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safe as `ptr` is initialized in `new`, and lives as long as `self`
+        unsafe { self.ptr.as_mut() }
+    }
+}
+
+impl<T: Zeroize + Clone> Clone for Locked<T> {
+    fn clone(&self) -> Self {
+        Locked::new(self.deref().clone())
+    }
+}
+
+impl<T: Zeroize> Drop for Locked<T> {
+    fn drop(&mut self) {
+        let layout = Self::layout();
+        // Safe as `ptr` is initialized, and not used anymore after this call
+        unsafe {
+            self.ptr.as_mut().zeroize();
+            ptr::drop_in_place(self.ptr.as_ptr());
+        }
+        if self.locked {
+            sys::unlock(self.ptr.as_ptr() as *mut u8, layout.size());
+        }
+        // Safe as `ptr` has been allocated with the same layout
+        unsafe { dealloc(self.ptr.as_ptr() as *mut u8, layout) };
+    }
+}
+
+// `Locked` owns its value, like a `Box`
+unsafe impl<T: Zeroize + Send> Send for Locked<T> {}
+unsafe impl<T: Zeroize + Sync> Sync for Locked<T> {}
+
+#[cfg(unix)]
+mod sys {
+    pub(super) fn page_size() -> usize {
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+        if page_size > 0 {
+            page_size as usize
+        } else {
+            4096
+        }
+    }
+
+    pub(super) fn lock(ptr: *mut u8, len: usize) -> bool {
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        unsafe {
+            libc::madvise(ptr as *mut libc::c_void, len, libc::MADV_DONTDUMP);
+        }
+        unsafe { libc::mlock(ptr as *const libc::c_void, len) == 0 }
+    }
+
+    pub(super) fn unlock(ptr: *mut u8, len: usize) {
+        unsafe {
+            libc::munlock(ptr as *const libc::c_void, len);
+        }
+        // Pages are given back to the allocator
+        #[cfg(any(target_os = "linux", target_os = "android"))]
+        unsafe {
+            libc::madvise(ptr as *mut libc::c_void, len, libc::MADV_DODUMP);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod sys {
+    pub(super) fn page_size() -> usize {
+        4096
+    }
+
+    pub(super) fn lock(_ptr: *mut u8, _len: usize) -> bool {
+        false
+    }
+
+    pub(super) fn unlock(_ptr: *mut u8, _len: usize) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locked_value() {
+        let mut value = Locked::new([1u8; 32]);
+        assert_eq!(*value, [1u8; 32]);
+        value[0] = 2;
+        let copy = value.clone();
+        assert_eq!(copy[0], 2);
+        assert_eq!(copy[1..], [1u8; 31]);
+
+        // Each value has its own pages
+        let page_size = sys::page_size();
+        assert_eq!(&*value as *const _ as usize % page_size, 0);
+        assert_eq!(&*copy as *const _ as usize % page_size, 0);
+        assert_ne!(&*value as *const _, &*copy as *const _);
+    }
+}
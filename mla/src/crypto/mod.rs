@@ -1,3 +1,8 @@
 pub mod aesgcm;
+pub mod aesgcmsiv;
+pub mod backend;
 pub mod ecc;
 pub mod hash;
+pub mod hybrid;
+pub mod locked;
+pub mod password;
@@ -0,0 +1,154 @@
+use crate::crypto::aesgcm;
+use crate::crypto::aesgcm::ConstantTimeEq;
+use crate::errors::ConfigError;
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
+
+const KEY_SIZE: usize = 32;
+const SALT_SIZE: usize = 16;
+const PASSWORD_NONCE: &[u8; 12] = b"PASSWD NONCE";
+
+/// Argon2id costs used for new archives, from the second recommended option
+/// of RFC 9106 (memory cost is in KiB)
+const M_COST: u32 = 64 * 1024;
+const T_COST: u32 = 3;
+const P_COST: u32 = 4;
+/// Biggest costs accepted from an archive header, so that a crafted archive
+/// cannot exhaust the memory or the time of the reader
+const MAX_M_COST: u32 = 1024 * 1024;
+const MAX_T_COST: u32 = 64;
+const MAX_P_COST: u32 = 16;
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct PasswordRecipientPersistent {
+    salt: [u8; SALT_SIZE],
+    /// Argon2id costs
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+    /// Shared key, encrypted with the key derived from the password
+    key: [u8; KEY_SIZE],
+    tag: [u8; aesgcm::TAG_LENGTH],
+}
+
+fn derive_key(
+    password: &[u8],
+    salt: &[u8],
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+) -> Result<[u8; KEY_SIZE], ConfigError> {
+    let params = Params::new(m_cost, t_cost, p_cost, Some(KEY_SIZE))
+        .map_err(|_| ConfigError::PasswordKeyDerivationError)?;
+    let mut output = [0u8; KEY_SIZE];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password, salt, &mut output)
+        .map_err(|_| ConfigError::PasswordKeyDerivationError)?;
+    Ok(output)
+}
+
+/// Wrap the shared `key` with a key derived from `password` (Argon2id), and
+/// return a serializable structure (Key-wrapping made thanks to AesGcm256)
+pub(crate) fn store_key_for_password<T>(
+    password: &[u8],
+    key: &[u8; KEY_SIZE],
+    csprng: &mut T,
+) -> Result<PasswordRecipientPersistent, ConfigError>
+where
+    T: RngCore + CryptoRng,
+{
+    let mut salt = [0u8; SALT_SIZE];
+    csprng.fill_bytes(&mut salt);
+    let mut derived_key = derive_key(password, &salt, M_COST, T_COST, P_COST)?;
+
+    // As the salt is random, the derived key is only used once: no need for a
+    // random NONCE
+    let mut cipher = aesgcm::AesGcm256::new(&derived_key, PASSWORD_NONCE, b"")
+        .map_err(|_| ConfigError::PasswordKeyDerivationError)?;
+    derived_key.zeroize();
+    let mut encrypted_key = [0u8; KEY_SIZE];
+    encrypted_key.copy_from_slice(key);
+    cipher.encrypt(&mut encrypted_key);
+    let mut tag = [0u8; aesgcm::TAG_LENGTH];
+    tag.copy_from_slice(&cipher.into_tag());
+
+    Ok(PasswordRecipientPersistent {
+        salt,
+        m_cost: M_COST,
+        t_cost: T_COST,
+        p_cost: P_COST,
+        key: encrypted_key,
+        tag,
+    })
+}
+
+/// Try to recover the shared key from the `PasswordRecipientPersistent`, using `password`
+pub(crate) fn retrieve_key_with_password(
+    persist: &PasswordRecipientPersistent,
+    password: &[u8],
+) -> Result<Option<[u8; KEY_SIZE]>, ConfigError> {
+    if persist.m_cost > MAX_M_COST || persist.t_cost > MAX_T_COST || persist.p_cost > MAX_P_COST {
+        return Err(ConfigError::IncoherentPersistentConfig);
+    }
+    let mut derived_key = derive_key(
+        password,
+        &persist.salt,
+        persist.m_cost,
+        persist.t_cost,
+        persist.p_cost,
+    )?;
+
+    let mut cipher = aesgcm::AesGcm256::new(&derived_key, PASSWORD_NONCE, b"")
+        .map_err(|_| ConfigError::PasswordKeyDerivationError)?;
+    derived_key.zeroize();
+    let mut data = [0u8; KEY_SIZE];
+    data.copy_from_slice(&persist.key);
+    let tag = cipher.decrypt(&mut data);
+    if tag.ct_eq(&persist.tag).unwrap_u8() == 1 {
+        Ok(Some(data))
+    } else {
+        data.zeroize();
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    #[test]
+    fn password_recipient() {
+        let mut csprng = ChaChaRng::from_entropy();
+        let key = csprng.gen::<[u8; KEY_SIZE]>();
+        let persist = store_key_for_password(b"correct horse", &key, &mut csprng).unwrap();
+
+        // The right password retrieves the shared key
+        let ret_key = retrieve_key_with_password(&persist, b"correct horse")
+            .unwrap()
+            .unwrap();
+        assert_eq!(ret_key, key);
+
+        // Another one does not
+        assert!(retrieve_key_with_password(&persist, b"battery staple")
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn password_recipient_costs() {
+        let mut csprng = ChaChaRng::from_entropy();
+        let key = csprng.gen::<[u8; KEY_SIZE]>();
+        let mut persist = store_key_for_password(b"password", &key, &mut csprng).unwrap();
+
+        // Costs from a crafted header are bounded
+        persist.m_cost = MAX_M_COST + 1;
+        assert!(matches!(
+            retrieve_key_with_password(&persist, b"password"),
+            Err(ConfigError::IncoherentPersistentConfig)
+        ));
+    }
+}
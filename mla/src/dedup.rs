@@ -0,0 +1,193 @@
+//! Content-defined chunking, used to store identical parts of the entries
+//! once, see `ArchiveWriterConfig::with_dedup`
+use crate::crypto::hash::Sha256Hash;
+use crate::ArchiveFileID;
+use std::collections::HashMap;
+
+/// Chunks are at least this size, except the last one of an entry
+pub(crate) const MIN_CHUNK_SIZE: usize = 16 * 1024;
+/// Chunks are at most this size
+pub(crate) const MAX_CHUNK_SIZE: usize = 256 * 1024;
+/// Chunks are cut where the top `AVG_CHUNK_BITS` bits of the rolling hash are
+/// 0, giving chunks of about `MIN_CHUNK_SIZE + 2 ** AVG_CHUNK_BITS` bytes
+const AVG_CHUNK_BITS: u32 = 16;
+const CUT_MASK: u64 = ((1 << AVG_CHUNK_BITS) - 1) << (64 - AVG_CHUNK_BITS);
+
+/// Random values mixed in the rolling hash for each byte, from SplitMix64
+/// seeded with 0. Changing them changes where chunks are cut, but archives
+/// stay readable: readers only follow the chunk lists of the footer
+const fn gear_table() -> [u64; 256] {
+    let mut table = [0u64; 256];
+    let mut state: u64 = 0;
+    let mut i = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+const GEAR: [u64; 256] = gear_table();
+
+/// Split a content into chunks whose boundaries only depend on the bytes
+/// around them (Gear rolling hash), so that a content shifted by an insertion
+/// or a deletion still gives the same chunks after it
+#[derive(Default)]
+struct Chunker {
+    /// Content not cut yet
+    buffer: Vec<u8>,
+    /// Rolling hash up to `scanned`
+    hash: u64,
+    /// Number of bytes of `buffer` already hashed
+    scanned: usize,
+}
+
+impl Chunker {
+    /// Add `data` to the content, returning the chunks it completes
+    fn push(&mut self, data: &[u8]) -> Vec<Vec<u8>> {
+        self.buffer.extend_from_slice(data);
+        let mut chunks = Vec::new();
+        while let Some(cut) = self.find_cut() {
+            chunks.push(self.buffer.drain(..cut).collect());
+            self.hash = 0;
+            self.scanned = 0;
+        }
+        chunks
+    }
+
+    /// Length of the next chunk, if its end is in the buffer
+    fn find_cut(&mut self) -> Option<usize> {
+        while self.scanned < self.buffer.len() {
+            self.hash = (self.hash << 1).wrapping_add(GEAR[self.buffer[self.scanned] as usize]);
+            self.scanned += 1;
+            if self.scanned >= MAX_CHUNK_SIZE
+                || (self.scanned >= MIN_CHUNK_SIZE && self.hash & CUT_MASK == 0)
+            {
+                return Some(self.scanned);
+            }
+        }
+        None
+    }
+
+    /// Return the last chunk of the content, if any
+    fn finish(self) -> Option<Vec<u8>> {
+        if self.buffer.is_empty() {
+            None
+        } else {
+            Some(self.buffer)
+        }
+    }
+}
+
+/// Space saved by deduplication, see `ArchiveWriter::dedup_stats`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Size of the content of the entries, in bytes
+    pub content_size: u64,
+    /// Size of the content actually stored, in bytes
+    pub stored_size: u64,
+    /// Number of chunks the entries are made of
+    pub chunks: u64,
+    /// Number of them actually stored, the others being duplicates
+    pub stored_chunks: u64,
+}
+
+impl DedupStats {
+    /// Size saved by deduplication, in bytes
+    pub fn saved_size(&self) -> u64 {
+        self.content_size - self.stored_size
+    }
+}
+
+/// State of the writer in deduplication mode
+#[derive(Default)]
+pub(crate) struct Deduplicator {
+    /// SHA-256 of each chunk stored, with the offset of its `FileContent`
+    chunks: HashMap<Sha256Hash, u64>,
+    /// Chunker of each opened file
+    chunkers: HashMap<ArchiveFileID, Chunker>,
+    pub(crate) stats: DedupStats,
+}
+
+impl Deduplicator {
+    /// Add `data` to the content of file `id`, returning the chunks it
+    /// completes
+    pub(crate) fn push(&mut self, id: ArchiveFileID, data: &[u8]) -> Vec<Vec<u8>> {
+        self.chunkers.entry(id).or_default().push(data)
+    }
+
+    /// Return the last chunk of file `id`, if any, forgetting it
+    pub(crate) fn finish(&mut self, id: ArchiveFileID) -> Option<Vec<u8>> {
+        self.chunkers.remove(&id).and_then(Chunker::finish)
+    }
+
+    /// Offset of the `FileContent` holding a chunk of hash `hash`, if already
+    /// stored
+    pub(crate) fn find(&self, hash: &Sha256Hash) -> Option<u64> {
+        self.chunks.get(hash).copied()
+    }
+
+    /// Record that the chunk of hash `hash` is stored at `offset`
+    pub(crate) fn insert(&mut self, hash: Sha256Hash, offset: u64) {
+        self.chunks.insert(hash, offset);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{RngCore, SeedableRng};
+    use rand_chacha::ChaChaRng;
+
+    /// Split `data` in chunks, pushed by pieces of `step` bytes
+    fn split(data: &[u8], step: usize) -> Vec<Vec<u8>> {
+        let mut chunker = Chunker::default();
+        let mut chunks = Vec::new();
+        for piece in data.chunks(step) {
+            chunks.extend(chunker.push(piece));
+        }
+        chunks.extend(chunker.finish());
+        chunks
+    }
+
+    #[test]
+    fn chunk_sizes() {
+        let mut data = vec![0u8; 4 * 1024 * 1024];
+        ChaChaRng::seed_from_u64(0).fill_bytes(&mut data);
+        let chunks = split(&data, 10_000);
+        assert_eq!(chunks.concat(), data);
+        let (last, others) = chunks.split_last().unwrap();
+        assert!(!last.is_empty() && last.len() <= MAX_CHUNK_SIZE);
+        for chunk in others {
+            assert!(chunk.len() >= MIN_CHUNK_SIZE && chunk.len() <= MAX_CHUNK_SIZE);
+        }
+        // Chunks do not depend on how the content is given
+        assert_eq!(split(&data, 1 << 20), chunks);
+
+        // Constant content has no boundary: chunks are as big as possible
+        let chunks = split(&vec![0u8; 3 * MAX_CHUNK_SIZE], 10_000);
+        assert_eq!(chunks.len(), 3);
+    }
+
+    #[test]
+    fn insertion_resistance() {
+        let mut data = vec![0u8; 4 * 1024 * 1024];
+        ChaChaRng::seed_from_u64(0).fill_bytes(&mut data);
+        let mut shifted = data[..1000].to_vec();
+        shifted.extend_from_slice(b"inserted");
+        shifted.extend_from_slice(&data[1000..]);
+
+        // Only the chunks around the insertion differ
+        let chunks = split(&data, 10_000);
+        let shifted_chunks = split(&shifted, 10_000);
+        let common = shifted_chunks
+            .iter()
+            .filter(|chunk| chunks.contains(chunk))
+            .count();
+        assert!(common >= chunks.len() - 2);
+    }
+}
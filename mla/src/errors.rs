@@ -1,6 +1,6 @@
 use crate::ArchiveFileID;
-use aes_ctr::stream_cipher::InvalidKeyNonceLength;
 use bincode;
+use ctr::cipher::InvalidLength as InvalidKeyNonceLength;
 use hkdf::InvalidLength;
 use std::error;
 use std::fmt;
@@ -12,7 +12,7 @@ pub enum Error {
     IOError(io::Error),
     /// Wrong magic, must be "MLA"
     WrongMagic,
-    /// Unsupported version, must be 1
+    /// Unsupported version, must be between 1 and 6
     UnsupportedVersion,
     /// Supplied ECC key is not in the expected format
     InvalidECCKeyFormat,
@@ -61,17 +61,225 @@ pub enum Error {
     AuthenticatedDecryptionWrongTag,
     /// Unable to expand while using the HKDF
     HKDFInvalidKeyLength,
+    /// Appending to an archive encrypted with AES-GCM would reuse nonces. Only
+    /// AES-GCM-SIV, or unencrypted, archives can be appended to
+    AppendUnsupportedCipher,
+    /// The archive is not signed by any of the verification keys, or its
+    /// content does not match the signature
+    SignatureVerificationFailed,
+    /// A volume of a multi-volume archive is missing. Its number is given
+    MissingVolume(u32),
+    /// A volume is not the expected one (wrong magic, from another archive,
+    /// or out of order). Its expected number is given
+    WrongVolume(u32),
+    /// A `PrivateKeyProvider` failed, for instance as its token is missing
+    PrivateKeyProviderError(String),
+    /// Deduplicated archives cannot be repaired: the chunks an entry shares
+    /// with previous ones are only listed in the footer
+    RepairUnsupportedDedup,
+    /// The archive to load in memory is bigger than the given limit, in bytes
+    ArchiveTooBig(u64),
+    /// `source` occurred at `context` (layer, offset, entry), see
+    /// `Error::root` to match on the error itself
+    WithContext {
+        context: ErrorContext,
+        source: Box<Error>,
+    },
+}
+
+/// Where an error occurred, as far as known
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ErrorContext {
+    /// Layer which failed, such as "encryption" or "compression"
+    pub layer: Option<&'static str>,
+    /// Position of the failure, in the data produced by `layer`
+    pub offset: Option<u64>,
+    /// Name of the entry being read
+    pub entry: Option<String>,
+}
+
+impl fmt::Display for ErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        if let Some(entry) = &self.entry {
+            parts.push(format!("entry \"{}\"", entry));
+        }
+        match (self.layer, self.offset) {
+            (Some(layer), Some(offset)) => {
+                parts.push(format!("{} layer at offset {}", layer, offset))
+            }
+            (Some(layer), None) => parts.push(format!("{} layer", layer)),
+            (None, Some(offset)) => parts.push(format!("offset {}", offset)),
+            (None, None) => {}
+        }
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+impl Error {
+    /// Return the error itself, without its context if any
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::WithContext { source, .. } => source.root(),
+            _ => self,
+        }
+    }
+
+    /// Return where the error occurred, if known
+    pub fn context(&self) -> Option<&ErrorContext> {
+        match self {
+            Error::WithContext { context, .. } => Some(context),
+            _ => None,
+        }
+    }
+
+    /// Return a stable identifier of the error, such as
+    /// "authenticated_decryption_wrong_tag", for programs handling it
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::IOError(_) => "io",
+            Error::WrongMagic => "wrong_magic",
+            Error::UnsupportedVersion => "unsupported_version",
+            Error::InvalidECCKeyFormat => "invalid_ecc_key_format",
+            Error::WrongBlockSubFileType => "wrong_block_subfile_type",
+            Error::UTF8ConversionError(_) => "utf8_conversion",
+            Error::FilenameTooLong => "filename_too_long",
+            Error::WrongArchiveWriterState { .. } => "wrong_archive_writer_state",
+            Error::AssertionError(_) => "assertion",
+            Error::WrongReaderState(_) => "wrong_reader_state",
+            Error::WrongWriterState(_) => "wrong_writer_state",
+            Error::InvalidCipherInit(_) => "invalid_cipher_init",
+            Error::RandError(_) => "rand",
+            Error::PrivateKeyNeeded => "private_key_needed",
+            Error::DeserializationError => "deserialization",
+            Error::SerializationError => "serialization",
+            Error::MissingMetadata => "missing_metadata",
+            Error::BadAPIArgument(_) => "bad_api_argument",
+            Error::EndOfStream => "end_of_stream",
+            Error::ConfigError(err) => err.code(),
+            Error::DuplicateFilename => "duplicate_filename",
+            Error::AuthenticatedDecryptionWrongTag => "authenticated_decryption_wrong_tag",
+            Error::HKDFInvalidKeyLength => "hkdf_invalid_key_length",
+            Error::AppendUnsupportedCipher => "append_unsupported_cipher",
+            Error::SignatureVerificationFailed => "signature_verification_failed",
+            Error::MissingVolume(_) => "missing_volume",
+            Error::WrongVolume(_) => "wrong_volume",
+            Error::PrivateKeyProviderError(_) => "private_key_provider",
+            Error::RepairUnsupportedDedup => "repair_unsupported_dedup",
+            Error::ArchiveTooBig(_) => "archive_too_big",
+            Error::WithContext { source, .. } => source.code(),
+        }
+    }
+
+    /// Record that the error occurred in `layer`, at `offset` of its data,
+    /// unless a deeper layer is already known
+    pub(crate) fn with_layer(self, layer: &'static str, offset: u64) -> Error {
+        self.with_context(|context| {
+            if context.layer.is_none() {
+                context.layer = Some(layer);
+                context.offset = Some(offset);
+            }
+        })
+    }
+
+    /// Record that the error occurred while reading the entry `name`
+    pub(crate) fn with_entry(self, name: &str) -> Error {
+        self.with_context(|context| {
+            if context.entry.is_none() {
+                context.entry = Some(name.to_string());
+            }
+        })
+    }
+
+    fn with_context<F: FnOnce(&mut ErrorContext)>(self, update: F) -> Error {
+        match self {
+            Error::WithContext {
+                mut context,
+                source,
+            } => {
+                update(&mut context);
+                Error::WithContext { context, source }
+            }
+            error => {
+                let mut context = ErrorContext::default();
+                update(&mut context);
+                Error::WithContext {
+                    context,
+                    source: Box::new(error),
+                }
+            }
+        }
+    }
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // For now, use the debug derived version
-        write!(f, "{:?}", self)
+        match self {
+            Error::IOError(err) => write!(f, "I/O error: {}", err),
+            Error::WrongMagic => write!(f, "not a MLA archive (wrong magic)"),
+            Error::UnsupportedVersion => write!(f, "unsupported format version"),
+            Error::InvalidECCKeyFormat => write!(f, "invalid key format"),
+            Error::WrongBlockSubFileType => write!(f, "unknown block type"),
+            Error::UTF8ConversionError(err) => write!(f, "invalid UTF-8: {}", err),
+            Error::FilenameTooLong => write!(f, "file name too long"),
+            Error::WrongArchiveWriterState {
+                current_state,
+                expected_state,
+            } => write!(
+                f,
+                "archive writer in state {} instead of {}",
+                current_state, expected_state
+            ),
+            Error::AssertionError(msg) => write!(f, "internal error: {}", msg),
+            Error::WrongReaderState(msg) => write!(f, "unexpected reader state: {}", msg),
+            Error::WrongWriterState(msg) => write!(f, "unexpected writer state: {}", msg),
+            Error::InvalidCipherInit(_) => write!(f, "invalid key or nonce length"),
+            Error::RandError(err) => write!(f, "random generator error: {}", err),
+            Error::PrivateKeyNeeded => write!(f, "a private key is needed"),
+            Error::DeserializationError => write!(f, "unable to deserialize data"),
+            Error::SerializationError => write!(f, "unable to serialize data"),
+            Error::MissingMetadata => write!(f, "missing archive index (a repair may be needed)"),
+            Error::BadAPIArgument(msg) => write!(f, "bad argument: {}", msg),
+            Error::EndOfStream => write!(f, "end of stream"),
+            Error::ConfigError(err) => write!(f, "{}", err),
+            Error::DuplicateFilename => write!(f, "file name already used"),
+            Error::AuthenticatedDecryptionWrongTag => {
+                write!(f, "wrong authentication tag, data is corrupted or altered")
+            }
+            Error::HKDFInvalidKeyLength => write!(f, "invalid HKDF output length"),
+            Error::AppendUnsupportedCipher => write!(
+                f,
+                "only archives encrypted with AES-GCM-SIV, or unencrypted, can be appended to"
+            ),
+            Error::SignatureVerificationFailed => {
+                write!(
+                    f,
+                    "the archive is not signed by any of the verification keys"
+                )
+            }
+            Error::MissingVolume(number) => write!(f, "volume {} is missing", number),
+            Error::WrongVolume(number) => write!(f, "volume {} is not the expected one", number),
+            Error::PrivateKeyProviderError(msg) => write!(f, "private key provider error: {}", msg),
+            Error::RepairUnsupportedDedup => write!(
+                f,
+                "deduplicated archives cannot be repaired, as their shared content is only indexed in the footer"
+            ),
+            Error::ArchiveTooBig(limit) => {
+                write!(f, "archive bigger than the limit of {} bytes", limit)
+            }
+            Error::WithContext { context, source } => write!(f, "{} ({})", source, context),
+        }
     }
 }
 
 impl From<io::Error> for Error {
     fn from(error: io::Error) -> Self {
+        // Layers report their errors through `Read` and `Write`, as
+        // `io::Error`s: get the original error back
+        if error.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+            // Safe to unwrap, as the inner error has been checked
+            return *error.into_inner().unwrap().downcast::<Error>().unwrap();
+        }
         Error::IOError(error)
     }
 }
@@ -102,7 +310,8 @@ impl From<bincode::ErrorKind> for Error {
 
 impl From<Error> for io::Error {
     fn from(error: Error) -> Self {
-        io::Error::new(io::ErrorKind::Other, format!("{}", error))
+        // Keep the error, to get it back with `From<io::Error>`
+        io::Error::other(error)
     }
 }
 
@@ -128,6 +337,7 @@ impl error::Error for Error {
             Error::UTF8ConversionError(err) => Some(err),
             Error::RandError(err) => Some(err),
             Error::ConfigError(err) => Some(err),
+            Error::WithContext { source, .. } => Some(source.as_ref()),
             _ => None,
         }
     }
@@ -174,8 +384,48 @@ pub enum FailSafeReadError {
 
 impl fmt::Display for FailSafeReadError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // For now, use the debug derived version
-        write!(f, "{:?}", self)
+        match self {
+            FailSafeReadError::NoError => write!(f, "no error"),
+            FailSafeReadError::UnexpectedEOFOnNextBlock => {
+                write!(f, "unexpected end of data while reading the next block")
+            }
+            FailSafeReadError::IOErrorOnNextBlock(err) => {
+                write!(f, "I/O error while reading the next block: {}", err)
+            }
+            FailSafeReadError::ErrorOnNextBlock(err) => {
+                write!(f, "error while reading the next block: {}", err)
+            }
+            FailSafeReadError::ErrorInFile(err, fname) => {
+                write!(f, "error in file \"{}\": {}", fname, err)
+            }
+            FailSafeReadError::ArchiveFileIDReuse(id) => write!(f, "file id {} reused", id),
+            FailSafeReadError::FilenameReuse(fname) => write!(f, "file name \"{}\" reused", fname),
+            FailSafeReadError::ArchiveFileIDAlreadyClose(id) => {
+                write!(f, "content for the already ended file id {}", id)
+            }
+            FailSafeReadError::ContentForUnknownFile(id) => {
+                write!(f, "content for the unknown file id {}", id)
+            }
+            FailSafeReadError::EOFForUnknownFile(id) => {
+                write!(f, "end of the unknown file id {}", id)
+            }
+            FailSafeReadError::UnfinishedFiles {
+                filenames,
+                stopping_error,
+            } => write!(
+                f,
+                "{} (unfinished files: {})",
+                stopping_error,
+                filenames.join(", ")
+            ),
+            FailSafeReadError::EndOfOriginalArchiveData => {
+                write!(f, "end of the original archive data")
+            }
+            FailSafeReadError::FailSafeReadInternalError => write!(f, "internal error"),
+            FailSafeReadError::HashDiffers { .. } => {
+                write!(f, "the content does not match its hash")
+            }
+        }
     }
 }
 
@@ -195,17 +445,75 @@ pub enum ConfigError {
     IncoherentPersistentConfig,
     // Compression specifics
     CompressionLevelOutOfRange,
+    CompressionBlockSizeOutOfRange,
+    /// The dictionary is empty, too big, or the algorithm is not Zstd
+    CompressionDictionaryInvalid,
     // Encryption specifics
     EncryptionKeyIsMissing,
+    EncryptionChunkSizeOutOfRange,
     PrivateKeyNotSet,
     PrivateKeyNotFound,
     ECIESComputationError,
+    PasswordKeyDerivationError,
+    WrongPassword,
+    /// A deterministic archive must be encrypted with `AesGcmSiv256`, see
+    /// `ArchiveWriterConfig::with_deterministic_seed`
+    DeterministicCipherNotMisuseResistant,
+    /// Padding is applied before compression, which would shrink entries
+    /// again: it needs the compression layer to be disabled, see
+    /// `ArchiveWriterConfig::with_padding_policy`
+    PaddingWithCompression,
+    // Signature specifics
+    SigningKeyIsMissing,
+}
+
+impl ConfigError {
+    /// Return a stable identifier of the error, see `Error::code`
+    pub fn code(&self) -> &'static str {
+        match self {
+            ConfigError::IncoherentPersistentConfig => "incoherent_persistent_config",
+            ConfigError::CompressionLevelOutOfRange => "compression_level_out_of_range",
+            ConfigError::CompressionBlockSizeOutOfRange => "compression_block_size_out_of_range",
+            ConfigError::CompressionDictionaryInvalid => "compression_dictionary_invalid",
+            ConfigError::EncryptionKeyIsMissing => "encryption_key_is_missing",
+            ConfigError::EncryptionChunkSizeOutOfRange => "encryption_chunk_size_out_of_range",
+            ConfigError::PrivateKeyNotSet => "private_key_not_set",
+            ConfigError::PrivateKeyNotFound => "private_key_not_found",
+            ConfigError::ECIESComputationError => "ecies_computation",
+            ConfigError::PasswordKeyDerivationError => "password_key_derivation",
+            ConfigError::WrongPassword => "wrong_password",
+            ConfigError::DeterministicCipherNotMisuseResistant => {
+                "deterministic_cipher_not_misuse_resistant"
+            }
+            ConfigError::PaddingWithCompression => "padding_with_compression",
+            ConfigError::SigningKeyIsMissing => "signing_key_is_missing",
+        }
+    }
 }
 
 impl fmt::Display for ConfigError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        // For now, use the debug derived version
-        write!(f, "{:?}", self)
+        let message = match self {
+            ConfigError::IncoherentPersistentConfig => "incoherent archive configuration",
+            ConfigError::CompressionLevelOutOfRange => "compression level out of range",
+            ConfigError::CompressionBlockSizeOutOfRange => "compression block size out of range",
+            ConfigError::CompressionDictionaryInvalid => "invalid compression dictionary",
+            ConfigError::EncryptionKeyIsMissing => "encryption key is missing",
+            ConfigError::EncryptionChunkSizeOutOfRange => "encryption chunk size out of range",
+            ConfigError::PrivateKeyNotSet => "no private key set",
+            ConfigError::PrivateKeyNotFound => "none of the private keys is a recipient",
+            ConfigError::ECIESComputationError => "unable to compute the recipient key",
+            ConfigError::PasswordKeyDerivationError => "unable to derive the password key",
+            ConfigError::WrongPassword => "wrong password",
+            ConfigError::DeterministicCipherNotMisuseResistant => {
+                "a deterministic archive must be encrypted with AES-GCM-SIV"
+            }
+            ConfigError::PaddingWithCompression => {
+                "padding cannot hide the sizes of compressed entries, the compression layer must be disabled"
+            }
+            ConfigError::SigningKeyIsMissing => "signing key is missing",
+        };
+        write!(f, "{}", message)
     }
 }
 
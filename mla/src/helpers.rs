@@ -1,9 +1,17 @@
 /// Helpers for common operation with MLA Archives
-use super::{ArchiveFileBlock, ArchiveFileID, ArchiveReader, ArchiveWriter, Error};
-use std::collections::HashMap;
+use super::{ArchiveFileBlock, ArchiveFileID, ArchiveFooter, ArchiveReader, ArchiveWriter, Error};
+#[cfg(feature = "tar")]
+use super::{EntryType, FileMetadata};
+use crate::layers::progress::EntriesProgress;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::hash::BuildHasher;
 use std::io::{self, Read, Seek, SeekFrom, Write};
 
+/// Size of the buffer used to copy a block to several outputs
+const LINEAR_EXTRACT_BUFFER_SIZE: usize = 64 * 1024;
+/// Size of the buffers used to compare contents, see `diff`
+const DIFF_BUFFER_SIZE: usize = 64 * 1024;
+
 /// Extract an Archive linearly.
 ///
 /// `export` maps filenames to Write objects, which will receives the
@@ -18,10 +26,49 @@ use std::io::{self, Read, Seek, SeekFrom, Write};
 /// encryption tag).
 /// Linear extraction avoids these costs by reading once and only once each byte,
 /// and by reducing the amount of seeks.
+///
+/// Hard links (see `ArchiveWriter::add_hardlink`) receive the content of the
+/// file they point to.
+///
+/// Deduplicated files (see `ArchiveWriterConfig::with_dedup`) may share
+/// chunks stored with other files: they are extracted afterwards, from their
+/// chunks.
+///
+/// Every entry met is reported to the progress observer of the archive, if
+/// any (see `ArchiveReaderConfig::with_progress_observer`).
 pub fn linear_extract<W1: Write, R: Read + Seek, S: BuildHasher>(
     archive: &mut ArchiveReader<R>,
     export: &mut HashMap<&String, W1, S>,
 ) -> Result<(), Error> {
+    // Hard links never appear in a `FileStart`, but share the `FileInfo` of
+    // their target. Group names by the offset of their `FileStart` to find
+    // them back
+    let mut offset2filenames: HashMap<u64, Vec<String>> = HashMap::new();
+    let mut deduplicated: HashSet<String> = HashSet::new();
+    if let Some(ArchiveFooter { files_info }) = &archive.metadata {
+        for (fname, file_info) in files_info {
+            if file_info.deduplicated && export.contains_key(fname) {
+                deduplicated.insert(fname.clone());
+            }
+            if let Some(offset) = file_info.offsets.first() {
+                offset2filenames
+                    .entry(*offset)
+                    .or_default()
+                    .push(fname.clone());
+            }
+        }
+    }
+    let mut filename2aliases: HashMap<String, Vec<String>> = HashMap::new();
+    for fnames in offset2filenames.values() {
+        if fnames.len() > 1 {
+            for fname in fnames {
+                filename2aliases.insert(fname.clone(), fnames.clone());
+            }
+        }
+    }
+
+    let mut progress = archive.config.progress.clone().map(EntriesProgress::new);
+
     // Seek at the beginning
     archive.src.seek(SeekFrom::Start(0))?;
 
@@ -29,22 +76,34 @@ pub fn linear_extract<W1: Write, R: Read + Seek, S: BuildHasher>(
     // read calls (like the ones on ArchiveFileBlock reading)
     let mut src = io::BufReader::new(&mut archive.src);
 
-    // Associate an ID in the archive to the corresponding filename
+    // Associate an ID in the archive to the corresponding filenames
     // Do not directly associate to the writer to keep an easier fn API
-    let mut id2filename: HashMap<ArchiveFileID, String> = HashMap::new();
+    let mut id2filenames: HashMap<ArchiveFileID, Vec<String>> = HashMap::new();
 
     'read_block: loop {
         match ArchiveFileBlock::from(&mut src)? {
             ArchiveFileBlock::FileStart { filename, id } => {
-                // If the starting file is meant to be extracted, get the
-                // corresponding writer
-                if export.contains_key(&filename) {
-                    id2filename.insert(id, filename.clone());
+                if let Some(progress) = &mut progress {
+                    progress.started(id, &filename);
+                }
+                // If the starting file, or one of its hard links, is meant to
+                // be extracted, get the corresponding writers
+                let fnames: Vec<String> = filename2aliases
+                    .remove(&filename)
+                    .unwrap_or_else(|| vec![filename])
+                    .into_iter()
+                    .filter(|fname| export.contains_key(fname) && !deduplicated.contains(fname))
+                    .collect();
+                if !fnames.is_empty() {
+                    id2filenames.insert(id, fnames);
                 }
             }
             ArchiveFileBlock::EndOfFile { id, .. } => {
-                // Drop the corresponding writer
-                id2filename.remove(&id);
+                if let Some(progress) = &mut progress {
+                    progress.completed(id);
+                }
+                // Drop the corresponding writers
+                id2filenames.remove(&id);
             }
             ArchiveFileBlock::FileContent { length, id, .. } => {
                 // Write a block to the corresponding output, if any
@@ -52,23 +111,60 @@ pub fn linear_extract<W1: Write, R: Read + Seek, S: BuildHasher>(
                 let copy_src = &mut (&mut src).take(length);
                 // Is the file considered?
                 let mut extracted: bool = false;
-                if let Some(fname) = id2filename.get(&id) {
-                    if let Some(writer) = export.get_mut(fname) {
-                        io::copy(copy_src, writer)?;
+                match id2filenames.get(&id).map(|fnames| fnames.as_slice()) {
+                    Some([fname]) => {
+                        if let Some(writer) = export.get_mut(fname) {
+                            io::copy(copy_src, writer)?;
+                            extracted = true;
+                        }
+                    }
+                    Some(fnames) => {
+                        // Several names for the same content, write the block
+                        // to each of them
+                        let mut buf = vec![0; LINEAR_EXTRACT_BUFFER_SIZE];
+                        loop {
+                            let read = copy_src.read(&mut buf)?;
+                            if read == 0 {
+                                break;
+                            }
+                            for fname in fnames {
+                                if let Some(writer) = export.get_mut(fname) {
+                                    writer.write_all(&buf[..read])?;
+                                }
+                            }
+                        }
                         extracted = true;
                     }
+                    None => {}
                 };
                 if !extracted {
                     // Exhaust the block to Sink to forward the reader
                     io::copy(copy_src, &mut io::sink())?;
                 }
+                if let Some(progress) = &progress {
+                    progress.data(id, length);
+                }
+            }
+            ArchiveFileBlock::Padding { length, .. } => {
+                io::copy(&mut (&mut src).take(length), &mut io::sink())?;
             }
-            ArchiveFileBlock::EndOfArchiveData {} => {
+            ArchiveFileBlock::EndOfArchiveData => {
                 // Proper termination
                 break 'read_block;
             }
         }
     }
+
+    let mut deduplicated: Vec<String> = deduplicated.into_iter().collect();
+    deduplicated.sort();
+    for fname in deduplicated {
+        let mut file = archive.get_file(fname.clone())?.ok_or_else(|| {
+            Error::WrongReaderState("[linear_extract] Unable to find the file".to_string())
+        })?;
+        if let Some(writer) = export.get_mut(&fname) {
+            io::copy(&mut file.data, writer)?;
+        }
+    }
     Ok(())
 }
 
@@ -100,6 +196,252 @@ impl<'a, 'b, W: Write> Write for StreamWriter<'a, 'b, W> {
     }
 }
 
+// ---------- Archive comparison ----------
+
+/// Change of an entry between two archives, see `diff`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum EntryChange {
+    /// The entry is only in the new archive
+    Added,
+    /// The entry is only in the old archive
+    Removed,
+    /// The entry is in both archives, with a different type or content
+    Modified,
+}
+
+impl std::fmt::Display for EntryChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            EntryChange::Added => write!(f, "added"),
+            EntryChange::Removed => write!(f, "removed"),
+            EntryChange::Modified => write!(f, "modified"),
+        }
+    }
+}
+
+/// Compare the entries of the archives `old` and `new`, and return the ones
+/// which differ, sorted by name
+///
+/// Entries are compared on their type, size and SHA-256. Hashes are taken from
+/// the footers, or from the `EndOfFile` blocks for archives without them, so
+/// contents are not read. With `compare_content`, contents of the same size
+/// are read and compared byte per byte instead, without relying on the
+/// recorded hashes
+pub fn diff<R1: Read + Seek, R2: Read + Seek>(
+    old: &mut ArchiveReader<R1>,
+    new: &mut ArchiveReader<R2>,
+    compare_content: bool,
+) -> Result<Vec<(String, EntryChange)>, Error> {
+    let old_sizes: BTreeMap<String, u64> = old
+        .list_files_info()?
+        .map(|info| (info.name.to_string(), info.size))
+        .collect();
+    let new_sizes: BTreeMap<String, u64> = new
+        .list_files_info()?
+        .map(|info| (info.name.to_string(), info.size))
+        .collect();
+
+    let mut names: Vec<&String> = old_sizes.keys().chain(new_sizes.keys()).collect();
+    names.sort();
+    names.dedup();
+    let mut changes = Vec::new();
+    for name in names {
+        let change = match (old_sizes.get(name), new_sizes.get(name)) {
+            (Some(_), None) => EntryChange::Removed,
+            (None, Some(_)) => EntryChange::Added,
+            (Some(old_size), Some(new_size)) if old_size != new_size => EntryChange::Modified,
+            (Some(size), Some(_)) => {
+                if same_entry(old, new, name, *size, compare_content)? {
+                    continue;
+                }
+                EntryChange::Modified
+            }
+            (None, None) => continue,
+        };
+        changes.push((name.clone(), change));
+    }
+    Ok(changes)
+}
+
+/// Return whether the entry `name`, of `size` bytes in both archives, has the
+/// same type and content in both
+fn same_entry<R1: Read + Seek, R2: Read + Seek>(
+    old: &mut ArchiveReader<R1>,
+    new: &mut ArchiveReader<R2>,
+    name: &str,
+    size: u64,
+    compare_content: bool,
+) -> Result<bool, Error> {
+    let old_type = old
+        .get_metadata(name)?
+        .map(|metadata| metadata.entry_type.clone())
+        .unwrap_or_default();
+    let new_type = new
+        .get_metadata(name)?
+        .map(|metadata| metadata.entry_type.clone())
+        .unwrap_or_default();
+    if old_type != new_type {
+        return Ok(false);
+    }
+    // Entries without content (symbolic links, directories) or empty files
+    if size == 0 {
+        return Ok(true);
+    }
+    if !compare_content {
+        return Ok(old.get_hash(name)? == new.get_hash(name)?);
+    }
+
+    let missing = || Error::BadAPIArgument(format!("[diff] Unable to find \"{}\"", name));
+    let mut old_file = old.get_file(name.to_string())?.ok_or_else(missing)?;
+    let mut new_file = new.get_file(name.to_string())?.ok_or_else(missing)?;
+    let mut old_buf = vec![0u8; DIFF_BUFFER_SIZE];
+    let mut new_buf = vec![0u8; DIFF_BUFFER_SIZE];
+    let mut remaining = size;
+    while remaining > 0 {
+        let len = std::cmp::min(remaining, DIFF_BUFFER_SIZE as u64) as usize;
+        old_file.data.read_exact(&mut old_buf[..len])?;
+        new_file.data.read_exact(&mut new_buf[..len])?;
+        if old_buf[..len] != new_buf[..len] {
+            return Ok(false);
+        }
+        remaining -= len as u64;
+    }
+    Ok(true)
+}
+
+// ---------- Tar conversion ----------
+
+/// Write the files of `archive` to the tar `dest`, in name order, streaming
+/// their content
+///
+/// Names and sizes are kept. If recorded, metadata is converted too: symbolic
+/// links and directories become tar entries of the same type, with their
+/// permissions, owners and modification times. Files without metadata are
+/// stored read-only. Absolute names are made relative, as tar requires
+///
+/// `dest` is not finished, so that other entries can be appended
+#[cfg(feature = "tar")]
+pub fn to_tar<R: Read + Seek, W: Write>(
+    archive: &mut ArchiveReader<R>,
+    dest: &mut tar::Builder<W>,
+) -> Result<(), Error> {
+    let mut fnames: Vec<String> = archive.list_files()?.cloned().collect();
+    fnames.sort();
+    for fname in fnames {
+        let metadata = archive.get_metadata(&fname)?.cloned().unwrap_or_default();
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(metadata.mode.unwrap_or(0o444));
+        if let Some(uid) = metadata.uid {
+            header.set_uid(uid.into());
+        }
+        if let Some(gid) = metadata.gid {
+            header.set_gid(gid.into());
+        }
+        if let Some(mtime) = metadata.mtime {
+            header.set_mtime(std::cmp::max(mtime, 0) as u64);
+        }
+
+        // Force relative path, the trivial way (does not support Windows paths)
+        let path = if std::path::Path::new(&fname).is_absolute() {
+            format!("./{}", fname)
+        } else {
+            fname.clone()
+        };
+        match &metadata.entry_type {
+            EntryType::Symlink(target) => {
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                dest.append_link(&mut header, &path, target)?;
+            }
+            EntryType::Directory => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                dest.append_data(&mut header, &path, io::empty())?;
+            }
+            EntryType::File => {
+                let subfile = archive.get_file(fname.clone())?.ok_or_else(|| {
+                    Error::BadAPIArgument(format!("[to_tar] Unable to find \"{}\"", fname))
+                })?;
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(subfile.size);
+                dest.append_data(&mut header, &path, subfile.data)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Add the entries of the tar `src` to `archive`, streaming their content,
+/// and return the names of the ignored entries
+///
+/// Names and sizes are kept, and hard links are added as such. If
+/// `with_metadata` is set, permissions, owners and modification times are
+/// recorded, and symbolic links and directories are added without content;
+/// otherwise, they are ignored. Other entries (devices, FIFOs, ...) are always
+/// ignored
+#[cfg(feature = "tar")]
+pub fn from_tar<R: Read, W: Write>(
+    src: &mut tar::Archive<R>,
+    archive: &mut ArchiveWriter<W>,
+    with_metadata: bool,
+) -> Result<Vec<String>, Error> {
+    use std::convert::TryFrom;
+
+    let mut ignored = Vec::new();
+    for entry in src.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        // Directories names may end with a '/'
+        let name = entry
+            .path()?
+            .to_string_lossy()
+            .trim_end_matches('/')
+            .to_string();
+        let link_name = entry
+            .link_name()?
+            .map(|target| target.to_string_lossy().to_string());
+        let entry_type = match (header.entry_type(), link_name) {
+            (tar::EntryType::Regular, _) | (tar::EntryType::Continuous, _) => EntryType::File,
+            (tar::EntryType::Link, Some(target)) => {
+                match archive.add_hardlink(&name, target.trim_end_matches('/')) {
+                    // The target has been ignored
+                    Err(Error::BadAPIArgument(_)) => ignored.push(name),
+                    result => result?,
+                }
+                continue;
+            }
+            (tar::EntryType::Symlink, Some(target)) if with_metadata => EntryType::Symlink(target),
+            (tar::EntryType::Directory, _) if with_metadata => EntryType::Directory,
+            _ => {
+                ignored.push(name);
+                continue;
+            }
+        };
+
+        if !with_metadata {
+            let size = entry.size();
+            archive.add_file(&name, size, entry)?;
+            continue;
+        }
+        let metadata = FileMetadata {
+            mode: header.mode().ok().map(|mode| mode & 0o7777),
+            uid: header.uid().ok().and_then(|uid| u32::try_from(uid).ok()),
+            gid: header.gid().ok().and_then(|gid| u32::try_from(gid).ok()),
+            mtime: header
+                .mtime()
+                .ok()
+                .and_then(|mtime| i64::try_from(mtime).ok()),
+            entry_type,
+        };
+        let size = match metadata.entry_type {
+            EntryType::File => entry.size(),
+            _ => 0,
+        };
+        archive.add_file_with_metadata(&name, size, entry.take(size), metadata)?;
+    }
+    Ok(ignored)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -203,4 +545,135 @@ mod tests {
             .unwrap();
         assert_eq!(content2.as_slice(), fake_file.as_slice());
     }
+
+    #[test]
+    fn archives_diff() {
+        let build = |files: &[(&str, &[u8])]| {
+            let mut mla =
+                ArchiveWriter::from_config(Vec::new(), ArchiveWriterConfig::new()).unwrap();
+            for (name, content) in files {
+                mla.add_file(name, content.len() as u64, *content).unwrap();
+            }
+            mla.finalize().unwrap();
+            mla.into_raw()
+        };
+        let old = build(&[
+            ("same", b"content"),
+            ("removed", b"old"),
+            ("resized", b"short"),
+            ("modified", b"abc"),
+        ]);
+        let new = build(&[
+            ("same", b"content"),
+            ("added", b"new"),
+            ("resized", b"longer"),
+            ("modified", b"abd"),
+        ]);
+
+        for compare_content in &[false, true] {
+            let mut old_read =
+                ArchiveReader::from_config(Cursor::new(&old), ArchiveReaderConfig::new()).unwrap();
+            let mut new_read =
+                ArchiveReader::from_config(Cursor::new(&new), ArchiveReaderConfig::new()).unwrap();
+            assert_eq!(
+                diff(&mut old_read, &mut new_read, *compare_content).unwrap(),
+                vec![
+                    ("added".to_string(), EntryChange::Added),
+                    ("modified".to_string(), EntryChange::Modified),
+                    ("removed".to_string(), EntryChange::Removed),
+                    ("resized".to_string(), EntryChange::Modified),
+                ]
+            );
+        }
+    }
+
+    #[cfg(feature = "tar")]
+    #[test]
+    fn tar_conversion() {
+        // Build an archive with a file, a hard link, a directory and a symlink
+        let mut mla = ArchiveWriter::from_config(Vec::new(), ArchiveWriterConfig::new()).unwrap();
+        let file_metadata = FileMetadata {
+            entry_type: EntryType::File,
+            mode: Some(0o640),
+            uid: Some(1000),
+            gid: Some(100),
+            mtime: Some(1_600_000_000),
+        };
+        mla.add_file_with_metadata("dir/file", 5, &b"hello"[..], file_metadata.clone())
+            .unwrap();
+        mla.add_hardlink("dir/link", "dir/file").unwrap();
+        let dir_metadata = FileMetadata {
+            entry_type: EntryType::Directory,
+            mode: Some(0o755),
+            ..Default::default()
+        };
+        mla.add_file_with_metadata("dir", 0, io::empty(), dir_metadata.clone())
+            .unwrap();
+        let symlink_metadata = FileMetadata {
+            entry_type: EntryType::Symlink("file".to_string()),
+            mode: Some(0o777),
+            ..Default::default()
+        };
+        mla.add_file_with_metadata("dir/symlink", 0, io::empty(), symlink_metadata)
+            .unwrap();
+        mla.finalize().unwrap();
+
+        // MLA -> tar
+        let mut mla_read =
+            ArchiveReader::from_config(Cursor::new(mla.into_raw()), ArchiveReaderConfig::new())
+                .unwrap();
+        let mut builder = tar::Builder::new(Vec::new());
+        to_tar(&mut mla_read, &mut builder).unwrap();
+        let tar_data = builder.into_inner().unwrap();
+
+        // tar -> MLA, without metadata: only files are kept
+        let mut mla = ArchiveWriter::from_config(Vec::new(), ArchiveWriterConfig::new()).unwrap();
+        let ignored =
+            from_tar(&mut tar::Archive::new(tar_data.as_slice()), &mut mla, false).unwrap();
+        assert_eq!(ignored, vec!["dir", "dir/symlink"]);
+        mla.finalize().unwrap();
+        let mla_read =
+            ArchiveReader::from_config(Cursor::new(mla.into_raw()), ArchiveReaderConfig::new())
+                .unwrap();
+        let mut fnames: Vec<String> = mla_read.list_files().unwrap().cloned().collect();
+        fnames.sort();
+        assert_eq!(fnames, vec!["dir/file", "dir/link"]);
+        assert_eq!(mla_read.get_metadata("dir/file").unwrap(), None);
+
+        // tar -> MLA, with metadata
+        let mut mla = ArchiveWriter::from_config(Vec::new(), ArchiveWriterConfig::new()).unwrap();
+        let ignored =
+            from_tar(&mut tar::Archive::new(tar_data.as_slice()), &mut mla, true).unwrap();
+        assert!(ignored.is_empty());
+        mla.finalize().unwrap();
+        let mut mla_read =
+            ArchiveReader::from_config(Cursor::new(mla.into_raw()), ArchiveReaderConfig::new())
+                .unwrap();
+        assert_eq!(
+            mla_read.get_metadata("dir/file").unwrap(),
+            Some(&file_metadata)
+        );
+        let metadata = mla_read.get_metadata("dir").unwrap().unwrap();
+        assert_eq!(metadata.entry_type, dir_metadata.entry_type);
+        assert_eq!(metadata.mode, dir_metadata.mode);
+        assert_eq!(
+            mla_read
+                .get_metadata("dir/symlink")
+                .unwrap()
+                .unwrap()
+                .entry_type,
+            EntryType::Symlink("file".to_string())
+        );
+        for fname in &["dir/file", "dir/link"] {
+            let mut content = Vec::new();
+            mla_read
+                .get_file(fname.to_string())
+                .unwrap()
+                .unwrap()
+                .data
+                .read_to_end(&mut content)
+                .unwrap();
+            assert_eq!(content, b"hello");
+        }
+    }
 }
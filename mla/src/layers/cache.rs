@@ -0,0 +1,243 @@
+use std::collections::VecDeque;
+use std::io;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::config::ArchiveReaderConfig;
+use crate::layers::traits::LayerReader;
+use crate::{Error, Layers};
+
+/// Size of the cached blocks, if there is neither compression nor encryption to
+/// align on
+const DEFAULT_CACHE_BLOCK_SIZE: u64 = 128 * 1024;
+
+// ---------- Config ----------
+
+impl ArchiveReaderConfig {
+    /// Keep up to `blocks` recently read blocks in memory, once decrypted and
+    /// decompressed
+    ///
+    /// Blocks are aligned on compression blocks (or on encryption chunks, if the
+    /// archive is not compressed): repeated seeks in the same entries, for
+    /// instance while parsing an archived database, no longer decrypt and
+    /// decompress the same data again. The memory used is up to `blocks` times
+    /// the compression block size (4MB by default).
+    ///
+    /// A value of 0, the default, disables the cache
+    pub fn with_block_cache(&mut self, blocks: usize) -> &mut ArchiveReaderConfig {
+        self.block_cache = blocks;
+        self
+    }
+
+    /// Return the maximum number of blocks kept in cache
+    pub fn block_cache(&self) -> usize {
+        self.block_cache
+    }
+
+    /// Size of the cached blocks, once the persistent configuration is loaded
+    fn cache_block_size(&self) -> u64 {
+        if self.layers_enabled.contains(Layers::COMPRESS) {
+            self.compression_block_size() as u64
+        } else if self.layers_enabled.contains(Layers::ENCRYPT) {
+            self.encryption_chunk_size() as u64
+        } else {
+            DEFAULT_CACHE_BLOCK_SIZE
+        }
+    }
+}
+
+// ---------- Reader ----------
+
+/// Layer keeping the last read blocks of its inner layer, with a LRU policy
+///
+/// It is meant to be the upper layer, so that cached data is already decrypted
+/// and decompressed
+pub struct CacheLayerReader<'a, R: Read + Seek> {
+    inner: Box<dyn 'a + LayerReader<'a, R>>,
+    block_size: u64,
+    capacity: usize,
+    /// Cached blocks with their number, the most recently used last
+    blocks: VecDeque<(u64, Vec<u8>)>,
+    /// Position in the inner layer, to avoid needless seeks while reading
+    /// sequentially. `None` if unknown
+    inner_pos: Option<u64>,
+    /// Size of the inner layer data, known once initialized
+    len: u64,
+    /// Current position
+    pos: u64,
+}
+
+impl<'a, R: 'a + Read + Seek> CacheLayerReader<'a, R> {
+    pub fn new(inner: Box<dyn 'a + LayerReader<'a, R>>, config: &ArchiveReaderConfig) -> Self {
+        Self {
+            inner,
+            block_size: config.cache_block_size(),
+            capacity: config.block_cache,
+            blocks: VecDeque::with_capacity(config.block_cache),
+            inner_pos: None,
+            len: 0,
+            pos: 0,
+        }
+    }
+
+    /// Read the block `block_num` from the inner layer
+    fn load_block(&mut self, block_num: u64) -> Result<Vec<u8>, Error> {
+        let start = block_num * self.block_size;
+        if start >= self.len {
+            return Ok(Vec::new());
+        }
+        if self.inner_pos != Some(start) {
+            // Invalidate the position, in case of error
+            self.inner_pos = None;
+            self.inner.seek(SeekFrom::Start(start))?;
+        }
+        let mut data = Vec::with_capacity(self.block_size as usize);
+        (&mut self.inner)
+            .take(self.block_size)
+            .read_to_end(&mut data)?;
+        self.inner_pos = Some(start + data.len() as u64);
+        Ok(data)
+    }
+
+    /// Move the block `block_num` in last position of `blocks`, loading it if
+    /// needed
+    fn use_block(&mut self, block_num: u64) -> Result<(), Error> {
+        match self.blocks.iter().position(|(num, _)| *num == block_num) {
+            Some(index) => {
+                let block = self.blocks.remove(index).expect("Index out of cache");
+                self.blocks.push_back(block);
+            }
+            None => {
+                let data = self.load_block(block_num)?;
+                if self.blocks.len() >= self.capacity {
+                    self.blocks.pop_front();
+                }
+                self.blocks.push_back((block_num, data));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a, R: 'a + Read + Seek> LayerReader<'a, R> for CacheLayerReader<'a, R> {
+    fn into_inner(self) -> Option<Box<dyn 'a + LayerReader<'a, R>>> {
+        Some(self.inner)
+    }
+
+    fn into_raw(self: Box<Self>) -> R {
+        self.inner.into_raw()
+    }
+
+    fn initialize(&mut self) -> Result<(), Error> {
+        // Recursive call
+        self.inner.initialize()?;
+
+        self.len = self.inner.seek(SeekFrom::End(0))?;
+        self.inner_pos = Some(self.len);
+        Ok(())
+    }
+}
+
+impl<'a, R: 'a + Read + Seek> Read for CacheLayerReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let block_num = self.pos / self.block_size;
+        let offset = (self.pos % self.block_size) as usize;
+        self.use_block(block_num)?;
+        let (_, data) = self.blocks.back().expect("Block just used");
+        if offset >= data.len() {
+            // End of stream
+            return Ok(0);
+        }
+        let size = std::cmp::min(buf.len(), data.len() - offset);
+        buf[..size].copy_from_slice(&data[offset..offset + size]);
+        self.pos += size as u64;
+        Ok(size)
+    }
+}
+
+impl<'a, R: 'a + Read + Seek> Seek for CacheLayerReader<'a, R> {
+    /// Only update the position, blocks are loaded on read
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(offset) => checked_add_signed(self.pos, offset),
+            SeekFrom::End(offset) => checked_add_signed(self.len, offset),
+        };
+        match new_pos {
+            Some(new_pos) => {
+                self.pos = new_pos;
+                Ok(new_pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative position",
+            )),
+        }
+    }
+}
+
+pub(crate) fn checked_add_signed(base: u64, offset: i64) -> Option<u64> {
+    if offset >= 0 {
+        base.checked_add(offset as u64)
+    } else {
+        base.checked_sub(offset.unsigned_abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::layers::raw::RawLayerReader;
+    use std::io::Cursor;
+
+    #[test]
+    fn cache_layer() {
+        let data: Vec<u8> = (0..(DEFAULT_CACHE_BLOCK_SIZE * 3 + 10))
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let mut config = ArchiveReaderConfig::new();
+        config.with_block_cache(2);
+        let mut cache_r = CacheLayerReader::new(
+            Box::new(RawLayerReader::new(Cursor::new(data.as_slice()))),
+            &config,
+        );
+        cache_r.initialize().unwrap();
+
+        // Sequential read
+        let mut output = Vec::new();
+        cache_r.read_to_end(&mut output).unwrap();
+        assert_eq!(output, data);
+        // Only the last blocks are kept
+        let cached: Vec<u64> = cache_r.blocks.iter().map(|(num, _)| *num).collect();
+        assert_eq!(cached, vec![2, 3]);
+
+        // Read from a cached block, then from an evicted one
+        let block_size = DEFAULT_CACHE_BLOCK_SIZE as usize;
+        let mut buf = [0u8; 5];
+        for &pos in &[block_size * 2 + 7, 3, data.len() - 5] {
+            assert_eq!(
+                cache_r.seek(SeekFrom::Start(pos as u64)).unwrap(),
+                pos as u64
+            );
+            cache_r.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, &data[pos..pos + 5]);
+        }
+        let cached: Vec<u64> = cache_r.blocks.iter().map(|(num, _)| *num).collect();
+        assert_eq!(cached, vec![0, 3]);
+
+        // Relative seeks
+        assert_eq!(
+            cache_r.seek(SeekFrom::End(-10)).unwrap(),
+            data.len() as u64 - 10
+        );
+        assert_eq!(
+            cache_r.seek(SeekFrom::Current(-1)).unwrap(),
+            data.len() as u64 - 11
+        );
+        assert!(cache_r
+            .seek(SeekFrom::Current(-(data.len() as i64)))
+            .is_err());
+        cache_r.seek(SeekFrom::End(0)).unwrap();
+        assert_eq!(cache_r.read(&mut buf).unwrap(), 0);
+    }
+}
@@ -1,13 +1,19 @@
 use brotli;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use lz4_flex;
 use serde::{Deserialize, Serialize};
+use zstd;
 
 use crate::layers::traits::{LayerFailSafeReader, LayerReader, LayerWriter};
-use crate::{Error, BINCODE_MAX_DESERIALIZE};
+use crate::{bincode_options, Error};
+use bincode::Options;
+use std::collections::HashMap;
 use std::io;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 
-use crate::config::{ArchiveWriterConfig, ConfigResult};
+use crate::config::{ArchiveReaderConfig, ArchiveWriterConfig, ConfigResult};
 use crate::errors::ConfigError;
 
 // ---------- Config ----------
@@ -17,46 +23,623 @@ use crate::errors::ConfigError;
 /// implies decompressing a whole block to obtain just the last byte.
 ///
 /// According to benchmarking on compression of representative data, 4MB seems
-/// to be a good choice
-const UNCOMPRESSED_DATA_SIZE: u32 = 4 * 1024 * 1024;
-
-/// A bigger value means a better compression ratio, but a slower compression
-///
-/// According to benchmarking on compression of representative data, level 5
-/// seems to be a good choice
-const DEFAULT_COMPRESSION_LEVEL: u32 = 5;
+/// to be a good default choice. This is also the value used by format v1
+/// archives, which do not record it.
+pub(crate) const UNCOMPRESSED_DATA_SIZE: u32 = 4 * 1024 * 1024;
+/// Allowed range for the uncompressed block size
+pub(crate) const MIN_UNCOMPRESSED_DATA_SIZE: u32 = 64 * 1024;
+pub(crate) const MAX_UNCOMPRESSED_DATA_SIZE: u32 = 64 * 1024 * 1024;
+/// Maximum size of a compression dictionary, which is kept in memory and
+/// stored in the header. Trained Zstandard dictionaries are usually ~100KB
+pub(crate) const MAX_COMPRESSION_DICTIONARY_SIZE: usize = 1024 * 1024;
 
 /// Default value which seems advised by brotli libraries
 const BROTLI_LOG_WINDOW: u32 = 22;
 
+/// Algorithm used to compress blocks. It is recorded in the header
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum CompressionAlgorithm {
+    /// Brotli, the default: a good compression ratio, for a reasonable speed
+    #[default]
+    Brotli,
+    /// Zstandard: faster than Brotli, for a comparable ratio
+    Zstd,
+    /// LZ4: much faster, for a lower ratio. There is a single level, 0
+    Lz4,
+}
+
+impl CompressionAlgorithm {
+    /// Allowed compression levels; bigger values cause denser, but slower
+    /// compression
+    pub fn levels(self) -> std::ops::RangeInclusive<u32> {
+        match self {
+            CompressionAlgorithm::Brotli => 0..=11,
+            CompressionAlgorithm::Zstd => 1..=22,
+            CompressionAlgorithm::Lz4 => 0..=0,
+        }
+    }
+
+    /// Level used if none is set
+    ///
+    /// For Brotli, according to benchmarking on compression of representative
+    /// data, level 5 seems to be a good choice
+    pub fn default_level(self) -> u32 {
+        match self {
+            CompressionAlgorithm::Brotli => 5,
+            CompressionAlgorithm::Zstd => 3,
+            CompressionAlgorithm::Lz4 => 0,
+        }
+    }
+}
+
+/// Configuration stored in the header, to be reloaded
+#[derive(Serialize, Deserialize)]
+pub struct CompressionPersistentConfig {
+    /// Uncompressed size of each compressed block, except the last one
+    uncompressed_block_size: u32,
+    algorithm: CompressionAlgorithm,
+    /// Zstandard dictionary used for every block, if any
+    dictionary: Option<Vec<u8>>,
+}
+
+impl CompressionPersistentConfig {
+    /// Algorithm used to compress blocks
+    pub(crate) fn algorithm(&self) -> CompressionAlgorithm {
+        self.algorithm
+    }
+
+    /// Whether blocks are compressed with a dictionary
+    pub(crate) fn has_dictionary(&self) -> bool {
+        self.dictionary.is_some()
+    }
+}
+
+/// `CompressionPersistentConfig` as stored in format v2 and v3 archives,
+/// always compressed with Brotli
+#[derive(Deserialize)]
+pub(crate) struct CompressionPersistentConfigV2 {
+    uncompressed_block_size: u32,
+}
+
+impl From<CompressionPersistentConfigV2> for CompressionPersistentConfig {
+    fn from(config: CompressionPersistentConfigV2) -> Self {
+        CompressionPersistentConfig {
+            uncompressed_block_size: config.uncompressed_block_size,
+            algorithm: CompressionAlgorithm::Brotli,
+            dictionary: None,
+        }
+    }
+}
+
+fn is_block_size_valid(size: u32) -> bool {
+    (MIN_UNCOMPRESSED_DATA_SIZE..=MAX_UNCOMPRESSED_DATA_SIZE).contains(&size)
+}
+
+fn is_dictionary_valid(algorithm: CompressionAlgorithm, dictionary: &[u8]) -> bool {
+    algorithm == CompressionAlgorithm::Zstd
+        && !dictionary.is_empty()
+        && dictionary.len() <= MAX_COMPRESSION_DICTIONARY_SIZE
+}
+
+/// Train a Zstandard dictionary of at most `max_size` bytes on `samples`, for
+/// `ArchiveWriterConfig::with_compression_dictionary`
+///
+/// Samples should be representative of the files to archive, such as a few
+/// thousands of them. Training fails if there are too few samples
+pub fn train_compression_dictionary<S: AsRef<[u8]>>(
+    samples: &[S],
+    max_size: usize,
+) -> Result<Vec<u8>, Error> {
+    let max_size = std::cmp::min(max_size, MAX_COMPRESSION_DICTIONARY_SIZE);
+    Ok(zstd::dict::from_samples(samples, max_size)?)
+}
+
 pub struct CompressionConfig {
+    algorithm: CompressionAlgorithm,
     compression_level: u32,
+    uncompressed_block_size: u32,
+    /// Number of threads compressing blocks; with 1, blocks are compressed on
+    /// the fly by the writer
+    threads: usize,
+    /// Zstandard dictionary, shared with the worker threads
+    dictionary: Option<Arc<Vec<u8>>>,
 }
 
 impl std::default::Default for CompressionConfig {
     fn default() -> Self {
         CompressionConfig {
-            compression_level: DEFAULT_COMPRESSION_LEVEL,
+            algorithm: CompressionAlgorithm::default(),
+            compression_level: CompressionAlgorithm::default().default_level(),
+            uncompressed_block_size: UNCOMPRESSED_DATA_SIZE,
+            threads: 1,
+            dictionary: None,
+        }
+    }
+}
+
+impl CompressionConfig {
+    pub fn to_persistent(&self) -> CompressionPersistentConfig {
+        CompressionPersistentConfig {
+            uncompressed_block_size: self.uncompressed_block_size,
+            algorithm: self.algorithm,
+            dictionary: self
+                .dictionary
+                .as_ref()
+                .map(|dictionary| dictionary.to_vec()),
         }
     }
 }
 
 impl ArchiveWriterConfig {
-    /// Set the compression level
-    /// compression level (0-11); bigger values cause denser, but slower compression
-    pub fn with_compression_level(&mut self, compression_level: u32) -> ConfigResult {
-        if compression_level > 11 {
+    /// Set the compression algorithm, reset the compression level to its
+    /// default one, and remove the dictionary. It is recorded in the header
+    pub fn with_compression_algorithm(
+        &mut self,
+        algorithm: CompressionAlgorithm,
+    ) -> &mut ArchiveWriterConfig {
+        self.compress.algorithm = algorithm;
+        self.compress.compression_level = algorithm.default_level();
+        self.compress.dictionary = None;
+        self
+    }
+
+    /// Return the compression algorithm
+    pub fn compression_algorithm(&self) -> CompressionAlgorithm {
+        self.compress.algorithm
+    }
+
+    /// Set the compression level, in the range of the algorithm (see
+    /// `CompressionAlgorithm::levels`), which must then be set first
+    pub fn with_compression_level(&mut self, compression_level: u32) -> ConfigResult<'_> {
+        if !self
+            .compress
+            .algorithm
+            .levels()
+            .contains(&compression_level)
+        {
             Err(ConfigError::CompressionLevelOutOfRange)
         } else {
             self.compress.compression_level = compression_level;
             Ok(self)
         }
     }
+
+    /// Set the uncompressed size of compressed blocks
+    ///
+    /// Bigger blocks give a better compression ratio, but a slower random
+    /// access. The value must be in
+    /// [`MIN_UNCOMPRESSED_DATA_SIZE`, `MAX_UNCOMPRESSED_DATA_SIZE`]
+    pub fn with_compression_block_size(&mut self, block_size: u32) -> ConfigResult<'_> {
+        if !is_block_size_valid(block_size) {
+            Err(ConfigError::CompressionBlockSizeOutOfRange)
+        } else {
+            self.compress.uncompressed_block_size = block_size;
+            Ok(self)
+        }
+    }
+
+    /// Return the uncompressed size of compressed blocks
+    pub fn compression_block_size(&self) -> u32 {
+        self.compress.uncompressed_block_size
+    }
+
+    /// Compress every block with a Zstandard `dictionary`, for instance from
+    /// `train_compression_dictionary`. It improves the compression ratio of
+    /// many small and similar files
+    ///
+    /// The algorithm must then be set first, to `Zstd`. The dictionary is
+    /// stored in the header, and may be up to `MAX_COMPRESSION_DICTIONARY_SIZE`
+    pub fn with_compression_dictionary(&mut self, dictionary: Vec<u8>) -> ConfigResult<'_> {
+        if !is_dictionary_valid(self.compress.algorithm, &dictionary) {
+            Err(ConfigError::CompressionDictionaryInvalid)
+        } else {
+            self.compress.dictionary = Some(Arc::new(dictionary));
+            Ok(self)
+        }
+    }
+
+    /// Return the compression dictionary, if any
+    pub fn compression_dictionary(&self) -> Option<&[u8]> {
+        self.compress.dictionary.as_deref().map(Vec::as_slice)
+    }
+
+    /// Compress blocks in parallel, using `threads` worker threads (0 for the
+    /// number of CPUs). The default, 1, compresses blocks in the writer thread
+    ///
+    /// Blocks are still written in order, and readable as usual. Up to two
+    /// uncompressed blocks per thread are kept in memory
+    pub fn with_threads(&mut self, threads: usize) -> &mut ArchiveWriterConfig {
+        self.compress.threads = if threads == 0 {
+            thread::available_parallelism()
+                .map(|threads| threads.get())
+                .unwrap_or(1)
+        } else {
+            threads
+        };
+        self
+    }
+
+    /// Return the number of threads compressing blocks
+    pub fn threads(&self) -> usize {
+        self.compress.threads
+    }
+}
+
+pub struct CompressionReaderConfig {
+    /// Uncompressed size of each compressed block, except the last one
+    uncompressed_block_size: u32,
+    algorithm: CompressionAlgorithm,
+    dictionary: Option<Arc<Vec<u8>>>,
+}
+
+impl std::default::Default for CompressionReaderConfig {
+    fn default() -> Self {
+        Self {
+            uncompressed_block_size: UNCOMPRESSED_DATA_SIZE,
+            algorithm: CompressionAlgorithm::default(),
+            dictionary: None,
+        }
+    }
+}
+
+impl CompressionReaderConfig {
+    pub fn load_persistent(
+        &mut self,
+        config: CompressionPersistentConfig,
+    ) -> Result<(), ConfigError> {
+        if !is_block_size_valid(config.uncompressed_block_size) {
+            return Err(ConfigError::IncoherentPersistentConfig);
+        }
+        if let Some(dictionary) = &config.dictionary {
+            if !is_dictionary_valid(config.algorithm, dictionary) {
+                return Err(ConfigError::IncoherentPersistentConfig);
+            }
+        }
+        self.uncompressed_block_size = config.uncompressed_block_size;
+        self.algorithm = config.algorithm;
+        self.dictionary = config.dictionary.map(Arc::new);
+        Ok(())
+    }
+}
+
+impl ArchiveReaderConfig {
+    /// Return the uncompressed size of compressed blocks, as read from the header
+    pub fn compression_block_size(&self) -> u32 {
+        self.compress.uncompressed_block_size
+    }
+
+    /// Return the compression algorithm, as read from the header
+    pub fn compression_algorithm(&self) -> CompressionAlgorithm {
+        self.compress.algorithm
+    }
+
+    /// Return the compression dictionary, as read from the header
+    pub fn compression_dictionary(&self) -> Option<&[u8]> {
+        self.compress.dictionary.as_deref().map(Vec::as_slice)
+    }
+}
+
+// ---------- Algorithms ----------
+
+/// Compressor of a single block
+#[allow(clippy::large_enum_variant)]
+enum Compressor<W: Write> {
+    Brotli(brotli::CompressorWriter<W>),
+    Zstd(zstd::stream::write::Encoder<'static, W>),
+    Lz4(lz4_flex::frame::FrameEncoder<W>),
+}
+
+impl<W: Write> Compressor<W> {
+    fn new(
+        algorithm: CompressionAlgorithm,
+        level: u32,
+        dictionary: Option<&[u8]>,
+        inner: W,
+    ) -> io::Result<Self> {
+        Ok(match algorithm {
+            CompressionAlgorithm::Brotli => Compressor::Brotli(brotli::CompressorWriter::new(
+                inner,
+                0,
+                level,
+                BROTLI_LOG_WINDOW,
+            )),
+            CompressionAlgorithm::Zstd => Compressor::Zstd(match dictionary {
+                Some(dictionary) => {
+                    zstd::stream::write::Encoder::with_dictionary(inner, level as i32, dictionary)?
+                }
+                None => zstd::stream::write::Encoder::new(inner, level as i32)?,
+            }),
+            CompressionAlgorithm::Lz4 => Compressor::Lz4(lz4_flex::frame::FrameEncoder::new(inner)),
+        })
+    }
+
+    /// End the compressed block, and return the inner writer
+    fn finish(self) -> io::Result<W> {
+        match self {
+            Compressor::Brotli(compress) => Ok(compress.into_inner()),
+            Compressor::Zstd(compress) => compress.finish(),
+            Compressor::Lz4(compress) => compress.finish().map_err(io::Error::other),
+        }
+    }
+}
+
+impl<W: Write> Write for Compressor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Compressor::Brotli(compress) => compress.write(buf),
+            Compressor::Zstd(compress) => compress.write(buf),
+            Compressor::Lz4(compress) => compress.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Compressor::Brotli(compress) => compress.flush(),
+            Compressor::Zstd(compress) => compress.flush(),
+            Compressor::Lz4(compress) => compress.flush(),
+        }
+    }
+}
+
+/// Compress `data` as a single block
+fn compress_block(
+    algorithm: CompressionAlgorithm,
+    level: u32,
+    dictionary: Option<&[u8]>,
+    data: &[u8],
+) -> io::Result<Vec<u8>> {
+    let mut compress =
+        Compressor::new(algorithm, level, dictionary, Vec::with_capacity(data.len()))?;
+    compress.write_all(data)?;
+    compress.finish()
+}
+
+/// Compression of blocks by a pool of worker threads, used instead of
+/// `Compressor` when several threads are configured
+///
+/// Full blocks are sent to the workers, and the compressed ones are written
+/// in their original order. To bound the memory usage, at most two blocks per
+/// thread are waiting to be written
+///
+/// On flush, the pending blocks are written, and the current one continues in
+/// the calling thread, as without workers, to be flushed too
+struct ParallelCompressor {
+    /// Uncompressed data of the current block
+    block: Vec<u8>,
+    /// Blocks to compress, with their number. Dropped to stop the workers
+    jobs: Option<mpsc::Sender<(u64, Vec<u8>)>>,
+    results: mpsc::Receiver<(u64, io::Result<Vec<u8>>)>,
+    workers: Vec<thread::JoinHandle<()>>,
+    /// Compressed blocks received before their predecessors
+    received: HashMap<u64, io::Result<Vec<u8>>>,
+    /// Number of blocks sent to the workers, and already written
+    submitted: u64,
+    written: u64,
+    max_pending: u64,
+}
+
+impl ParallelCompressor {
+    fn new(
+        algorithm: CompressionAlgorithm,
+        level: u32,
+        dictionary: Option<Arc<Vec<u8>>>,
+        threads: usize,
+    ) -> Self {
+        let (jobs, jobs_receiver) = mpsc::channel::<(u64, Vec<u8>)>();
+        let jobs_receiver = Arc::new(Mutex::new(jobs_receiver));
+        let (results_sender, results) = mpsc::channel();
+        let workers = (0..threads)
+            .map(|_| {
+                let jobs = jobs_receiver.clone();
+                let results = results_sender.clone();
+                let dictionary = dictionary.clone();
+                thread::spawn(move || loop {
+                    // The lock is released before compressing
+                    let job = jobs.lock().expect("Jobs lock poisoned").recv();
+                    let (num, data) = match job {
+                        Ok(job) => job,
+                        // No more blocks
+                        Err(_) => break,
+                    };
+                    let compressed = compress_block(
+                        algorithm,
+                        level,
+                        dictionary.as_deref().map(Vec::as_slice),
+                        &data,
+                    );
+                    if results.send((num, compressed)).is_err() {
+                        break;
+                    }
+                })
+            })
+            .collect();
+        Self {
+            block: Vec::new(),
+            jobs: Some(jobs),
+            results,
+            workers,
+            received: HashMap::new(),
+            submitted: 0,
+            written: 0,
+            max_pending: 2 * threads as u64,
+        }
+    }
+
+    /// Add the beginning of `buf` to the current block, of at most
+    /// `block_size` bytes. Return the number of bytes taken
+    ///
+    /// Completed blocks are written to `inner`, and their compressed sizes
+    /// pushed to `compressed_sizes`
+    fn write<W: Write>(
+        &mut self,
+        buf: &[u8],
+        block_size: u32,
+        inner: &mut W,
+        compressed_sizes: &mut Vec<u32>,
+    ) -> io::Result<usize> {
+        // As in `CompressionLayerWriter`, a full block is only sent when more
+        // data comes, the last block being sent on `finish`
+        if self.block.len() == block_size as usize {
+            self.submit(inner, compressed_sizes)?;
+        }
+        let size = std::cmp::min(block_size as usize - self.block.len(), buf.len());
+        self.block.extend_from_slice(&buf[..size]);
+        Ok(size)
+    }
+
+    /// Send the current block to the workers
+    fn submit<W: Write>(
+        &mut self,
+        inner: &mut W,
+        compressed_sizes: &mut Vec<u32>,
+    ) -> io::Result<()> {
+        while self.submitted - self.written >= self.max_pending {
+            self.write_next(inner, compressed_sizes)?;
+        }
+        let block = std::mem::take(&mut self.block);
+        let sent = match &self.jobs {
+            Some(jobs) => jobs.send((self.submitted, block)).is_ok(),
+            None => false,
+        };
+        if !sent {
+            return Err(Error::WrongWriterState(
+                "[Compression Layer] Compression threads are stopped".to_string(),
+            )
+            .into());
+        }
+        self.submitted += 1;
+        Ok(())
+    }
+
+    /// Wait for the next block, in order, and write it
+    fn write_next<W: Write>(
+        &mut self,
+        inner: &mut W,
+        compressed_sizes: &mut Vec<u32>,
+    ) -> io::Result<()> {
+        let compressed = loop {
+            if let Some(compressed) = self.received.remove(&self.written) {
+                break compressed?;
+            }
+            match self.results.recv() {
+                Ok((num, compressed)) => {
+                    self.received.insert(num, compressed);
+                }
+                Err(_) => {
+                    return Err(Error::WrongWriterState(
+                        "[Compression Layer] A compression thread has stopped".to_string(),
+                    )
+                    .into());
+                }
+            }
+        };
+        inner.write_all(&compressed)?;
+        compressed_sizes.push(compressed.len() as u32);
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Wait for every block sent to the workers, and write them in order
+    fn write_pending<W: Write>(
+        &mut self,
+        inner: &mut W,
+        compressed_sizes: &mut Vec<u32>,
+    ) -> io::Result<()> {
+        while self.written < self.submitted {
+            self.write_next(inner, compressed_sizes)?;
+        }
+        Ok(())
+    }
+
+    /// Send the last block, write every remaining one and stop the workers
+    ///
+    /// Return the uncompressed size of the last block
+    fn finish<W: Write>(
+        &mut self,
+        inner: &mut W,
+        compressed_sizes: &mut Vec<u32>,
+    ) -> io::Result<u32> {
+        let last_block_size = self.block.len() as u32;
+        if !self.block.is_empty() {
+            self.submit(inner, compressed_sizes)?;
+        }
+        self.write_pending(inner, compressed_sizes)?;
+        // Workers end once the jobs channel is closed
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            if worker.join().is_err() {
+                return Err(Error::WrongWriterState(
+                    "[Compression Layer] A compression thread has panicked".to_string(),
+                )
+                .into());
+            }
+        }
+        Ok(last_block_size)
+    }
+}
+
+/// Decompressor of a single block
+///
+/// Decompressors stop at the end of their block, but they may have read
+/// further in `inner`, up to `buffer_size` bytes
+#[allow(clippy::large_enum_variant)]
+enum Decompressor<R: Read> {
+    Brotli(brotli::Decompressor<R>),
+    Zstd(zstd::stream::read::Decoder<'static, BufReader<R>>),
+    Lz4(lz4_flex::frame::FrameDecoder<R>),
+}
+
+impl<R: Read> Decompressor<R> {
+    fn new(
+        algorithm: CompressionAlgorithm,
+        dictionary: Option<&[u8]>,
+        inner: R,
+        buffer_size: usize,
+    ) -> io::Result<Self> {
+        Ok(match algorithm {
+            CompressionAlgorithm::Brotli => {
+                Decompressor::Brotli(brotli::Decompressor::new(inner, buffer_size))
+            }
+            CompressionAlgorithm::Zstd => {
+                let inner = BufReader::with_capacity(buffer_size, inner);
+                let decoder = match dictionary {
+                    Some(dictionary) => {
+                        zstd::stream::read::Decoder::with_dictionary(inner, dictionary)?
+                    }
+                    None => zstd::stream::read::Decoder::with_buffer(inner)?,
+                };
+                Decompressor::Zstd(decoder.single_frame())
+            }
+            // LZ4 frames are read exactly, without buffering
+            CompressionAlgorithm::Lz4 => {
+                Decompressor::Lz4(lz4_flex::frame::FrameDecoder::new(inner))
+            }
+        })
+    }
+
+    fn into_inner(self) -> R {
+        match self {
+            Decompressor::Brotli(decompressor) => decompressor.into_inner(),
+            Decompressor::Zstd(decompressor) => decompressor.finish().into_inner(),
+            Decompressor::Lz4(decompressor) => decompressor.into_inner(),
+        }
+    }
+}
+
+impl<R: Read> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Decompressor::Brotli(decompressor) => decompressor.read(buf),
+            Decompressor::Zstd(decompressor) => decompressor.read(buf),
+            Decompressor::Lz4(decompressor) => decompressor.read(buf),
+        }
+    }
 }
 
 // ---------- Reader ----------
 
 /// See `CompressionLayerWriter` for more information
+#[allow(clippy::large_enum_variant)]
 enum CompressionLayerReaderState<R: Read> {
     /// Ready contains the real inner destination
     Ready(R),
@@ -65,7 +648,7 @@ enum CompressionLayerReaderState<R: Read> {
     InData {
         read: u32,
         uncompressed_size: u32,
-        decompressor: brotli::Decompressor<R>,
+        decompressor: Decompressor<R>,
     },
     /// Empty is a placeholder to allow state replacement
     Empty,
@@ -80,25 +663,25 @@ struct SizesInfo {
 }
 
 impl SizesInfo {
-    /// Get the uncompressed block size of block `block_num`
-    fn uncompressed_block_size_at(&self, block_num: usize) -> u32 {
+    /// Get the uncompressed block size of block `block_num`, `block_size`
+    /// being the uncompressed size of full blocks
+    fn uncompressed_block_size_at(&self, block_num: usize, block_size: u32) -> u32 {
         if block_num < self.compressed_sizes.len() - 1 {
-            UNCOMPRESSED_DATA_SIZE
+            block_size
         } else {
             self.last_block_size
         }
     }
 
     /// Get the compressed block at position `uncompressed_pos`
-    fn compressed_block_size_at(&self, uncompressed_pos: u64) -> u32 {
-        let block_num = uncompressed_pos / (UNCOMPRESSED_DATA_SIZE as u64);
+    fn compressed_block_size_at(&self, uncompressed_pos: u64, block_size: u32) -> u32 {
+        let block_num = uncompressed_pos / (block_size as u64);
         self.compressed_sizes[block_num as usize]
     }
 
     /// Maximum uncompressed available position
-    fn max_uncompressed_pos(&self) -> u64 {
-        (self.compressed_sizes.len() as u64 - 1) * UNCOMPRESSED_DATA_SIZE as u64
-            + self.last_block_size as u64
+    fn max_uncompressed_pos(&self, block_size: u32) -> u64 {
+        (self.compressed_sizes.len() as u64 - 1) * block_size as u64 + self.last_block_size as u64
     }
 }
 
@@ -124,6 +707,10 @@ pub struct CompressionLayerReader<'a, R: 'a + Read> {
     // corrected with `sizes_info` may seems unsync; `underlayer_pos` is the one
     // to trust.
     underlayer_pos: u64,
+    /// Uncompressed size of full blocks, from config
+    uncompressed_block_size: u32,
+    algorithm: CompressionAlgorithm,
+    dictionary: Option<Arc<Vec<u8>>>,
 }
 
 impl<R: Read> CompressionLayerReaderState<R> {
@@ -140,21 +727,35 @@ impl<R: Read> CompressionLayerReaderState<R> {
 }
 
 impl<'a, R: 'a + Read> CompressionLayerReader<'a, R> {
-    pub fn new(mut inner: Box<dyn 'a + LayerReader<'a, R>>) -> Result<Self, Error> {
-        let underlayer_pos = inner.seek(SeekFrom::Current(0))? as u64;
+    pub fn new(
+        mut inner: Box<dyn 'a + LayerReader<'a, R>>,
+        config: &CompressionReaderConfig,
+    ) -> Result<Self, Error> {
+        let underlayer_pos = inner.stream_position()?;
         Ok(Self {
             state: CompressionLayerReaderState::Ready(inner),
             sizes_info: None,
             underlayer_pos,
+            uncompressed_block_size: config.uncompressed_block_size,
+            algorithm: config.algorithm,
+            dictionary: config.dictionary.clone(),
         })
     }
 
+    /// Compressed size of each block, as read from the footer
+    pub fn compressed_sizes(&self) -> Result<&[u32], Error> {
+        match &self.sizes_info {
+            Some(sizes_info) => Ok(&sizes_info.compressed_sizes),
+            None => Err(Error::MissingMetadata),
+        }
+    }
+
     /// Returns whether `uncompressed_pos` is in the data stream
     /// If no index is used, always return `true`
     fn pos_in_stream(&self, uncompressed_pos: u64) -> bool {
         match &self.sizes_info {
             Some(sizes_info) => {
-                let pos_max = sizes_info.max_uncompressed_pos();
+                let pos_max = sizes_info.max_uncompressed_pos(self.uncompressed_block_size);
                 uncompressed_pos < pos_max
             }
             None => true,
@@ -167,9 +768,9 @@ impl<'a, R: 'a + Read> CompressionLayerReader<'a, R> {
         &self,
         inner: S,
         uncompressed_pos: u64,
-    ) -> Result<brotli::Decompressor<S>, Error> {
+    ) -> Result<Decompressor<S>, Error> {
         // Ensure it's a starting position
-        if uncompressed_pos % (UNCOMPRESSED_DATA_SIZE as u64) != 0 {
+        if !uncompressed_pos.is_multiple_of(self.uncompressed_block_size as u64) {
             return Err(Error::BadAPIArgument(
                 "[new_decompressor_at] not a starting position".to_string(),
             ));
@@ -184,10 +785,14 @@ impl<'a, R: 'a + Read> CompressionLayerReader<'a, R> {
         match &self.sizes_info {
             Some(sizes_info) => {
                 // Use index for faster decompression
-                Ok(brotli::Decompressor::new(
+                Ok(Decompressor::new(
+                    self.algorithm,
+                    self.dictionary.as_deref().map(Vec::as_slice),
                     inner,
-                    sizes_info.compressed_block_size_at(uncompressed_pos) as usize,
-                ))
+                    sizes_info
+                        .compressed_block_size_at(uncompressed_pos, self.uncompressed_block_size)
+                        as usize,
+                )?)
             }
             None => Err(Error::MissingMetadata),
         }
@@ -198,7 +803,7 @@ impl<'a, R: 'a + Read> CompressionLayerReader<'a, R> {
     /// `uncompressed_pos` must be a compressed block's starting position
     fn uncompressed_block_size_at(&self, uncompressed_pos: u64) -> Result<u32, Error> {
         // Ensure it's a starting position
-        if uncompressed_pos % (UNCOMPRESSED_DATA_SIZE as u64) != 0 {
+        if !uncompressed_pos.is_multiple_of(self.uncompressed_block_size as u64) {
             return Err(Error::BadAPIArgument(
                 "[uncompressed_block_size_at] not a starting position".to_string(),
             ));
@@ -215,8 +820,9 @@ impl<'a, R: 'a + Read> CompressionLayerReader<'a, R> {
                 // Use index for faster decompression
 
                 // Get the uncompressed block size
-                let block_num = uncompressed_pos / (UNCOMPRESSED_DATA_SIZE as u64);
-                Ok(sizes_info.uncompressed_block_size_at(block_num as usize))
+                let block_num = uncompressed_pos / (self.uncompressed_block_size as u64);
+                Ok(sizes_info
+                    .uncompressed_block_size_at(block_num as usize, self.uncompressed_block_size))
             }
             None => Err(Error::MissingMetadata),
         }
@@ -231,7 +837,7 @@ impl<'a, R: 'a + Read> CompressionLayerReader<'a, R> {
         uncompressed_pos: u64,
     ) -> Result<(), Error> {
         // Ensure it's a starting position
-        if uncompressed_pos % (UNCOMPRESSED_DATA_SIZE as u64) != 0 {
+        if !uncompressed_pos.is_multiple_of(self.uncompressed_block_size as u64) {
             return Err(Error::BadAPIArgument(
                 "[sync_inner_with_uncompressed_pos] not a starting position".to_string(),
             ));
@@ -244,7 +850,7 @@ impl<'a, R: 'a + Read> CompressionLayerReader<'a, R> {
         }
 
         // Find the right block
-        let block_num = uncompressed_pos / (UNCOMPRESSED_DATA_SIZE as u64);
+        let block_num = uncompressed_pos / (self.uncompressed_block_size as u64);
         match &self.sizes_info {
             Some(SizesInfo {
                 compressed_sizes, ..
@@ -280,12 +886,10 @@ impl<'a, R: 'a + Read + Seek> LayerReader<'a, R> for CompressionLayerReader<'a,
                 let pos = inner.seek(SeekFrom::End(-4))?;
                 let len = inner.read_u32::<LittleEndian>()? as u64;
 
-                // Read SizesInfo
-                inner.seek(SeekFrom::Start(pos - len))?;
-                self.sizes_info = match bincode::config()
-                    .limit(BINCODE_MAX_DESERIALIZE)
-                    .deserialize_from(inner.take(len))
-                {
+                // Read SizesInfo, a length past the start meaning a broken footer
+                let start = pos.checked_sub(len).ok_or(Error::DeserializationError)?;
+                inner.seek(SeekFrom::Start(start))?;
+                self.sizes_info = match bincode_options().deserialize_from(inner.take(len)) {
                     Ok(sinfo) => Some(sinfo),
                     _ => {
                         return Err(Error::DeserializationError);
@@ -316,9 +920,18 @@ impl<'a, R: 'a + Read + Seek> Read for CompressionLayerReader<'a, R> {
         let old_state = std::mem::replace(&mut self.state, CompressionLayerReaderState::Empty);
         match old_state {
             CompressionLayerReaderState::Ready(mut inner) => {
-                self.sync_inner_with_uncompressed_pos(&mut inner, self.underlayer_pos)?;
+                let uncompressed_size = match self
+                    .sync_inner_with_uncompressed_pos(&mut inner, self.underlayer_pos)
+                    .and_then(|_| self.uncompressed_block_size_at(self.underlayer_pos))
+                {
+                    Ok(uncompressed_size) => uncompressed_size,
+                    Err(err) => {
+                        // Keep the inner layer, for later seeks to other blocks
+                        self.state = CompressionLayerReaderState::Ready(inner);
+                        return Err(err.into());
+                    }
+                };
                 let decompressor = self.new_decompressor_at(inner, self.underlayer_pos)?;
-                let uncompressed_size = self.uncompressed_block_size_at(self.underlayer_pos)?;
                 self.state = CompressionLayerReaderState::InData {
                     read: 0,
                     uncompressed_size,
@@ -343,7 +956,16 @@ impl<'a, R: 'a + Read + Seek> Read for CompressionLayerReader<'a, R> {
                     return self.read(buf);
                 }
                 let size = std::cmp::min((uncompressed_size - read) as usize, buf.len());
-                let read_add = decompressor.read(&mut buf[..size])?;
+                let read_add = match decompressor.read(&mut buf[..size]) {
+                    Ok(read_add) => read_add,
+                    Err(err) => {
+                        // Keep the inner layer, for later seeks to other blocks
+                        self.state = CompressionLayerReaderState::Ready(decompressor.into_inner());
+                        return Err(Error::from(err)
+                            .with_layer("compression", self.underlayer_pos)
+                            .into());
+                    }
+                };
                 self.underlayer_pos += read_add as u64;
                 self.state = CompressionLayerReaderState::InData {
                     read: read + read_add as u32,
@@ -367,25 +989,73 @@ impl<'a, R: Read + Seek> Seek for CompressionLayerReader<'a, R> {
         // Seeking may instantiate a decompressor, and therefore position the
         // inner layer at the end of the asked position's compressed block
         match &self.sizes_info {
-            Some(_sizes_info) => {
+            Some(sizes_info) => {
                 match pos {
+                    SeekFrom::Start(pos)
+                        if pos == sizes_info.max_uncompressed_pos(self.uncompressed_block_size) =>
+                    {
+                        // End of the stream: there is no block to decompress,
+                        // and further reads return 0
+                        let old_state =
+                            std::mem::replace(&mut self.state, CompressionLayerReaderState::Empty);
+                        self.state = CompressionLayerReaderState::Ready(old_state.into_inner());
+                        self.underlayer_pos = pos;
+                        Ok(pos)
+                    }
                     SeekFrom::Start(pos) => {
                         // Find the right block
-                        let inside_block = pos % (UNCOMPRESSED_DATA_SIZE as u64);
+                        let inside_block = pos % (self.uncompressed_block_size as u64);
                         let rounded_pos = pos - inside_block;
 
+                        // Forward in the current block: only advance the
+                        // decompressor
+                        if let CompressionLayerReaderState::InData {
+                            read,
+                            uncompressed_size,
+                            decompressor,
+                        } = &mut self.state
+                        {
+                            let block_start = self.underlayer_pos - *read as u64;
+                            if block_start == rounded_pos
+                                && pos >= self.underlayer_pos
+                                && inside_block <= *uncompressed_size as u64
+                            {
+                                let to_skip = pos - self.underlayer_pos;
+                                let skipped =
+                                    io::copy(&mut decompressor.take(to_skip), &mut io::sink())?;
+                                *read += skipped as u32;
+                                self.underlayer_pos += skipped;
+                                return Ok(self.underlayer_pos);
+                            }
+                        }
+
                         // Move the underlayer at the start of the block
                         let old_state =
                             std::mem::replace(&mut self.state, CompressionLayerReaderState::Empty);
                         let mut inner = old_state.into_inner();
-                        self.sync_inner_with_uncompressed_pos(&mut inner, rounded_pos)?;
+                        let uncompressed_size = match self
+                            .sync_inner_with_uncompressed_pos(&mut inner, rounded_pos)
+                            .and_then(|_| self.uncompressed_block_size_at(rounded_pos))
+                        {
+                            Ok(uncompressed_size) => uncompressed_size,
+                            Err(err) => {
+                                // Keep the inner layer, for later seeks to other blocks
+                                self.state = CompressionLayerReaderState::Ready(inner);
+                                return Err(err.into());
+                            }
+                        };
 
                         // New decompressor at the start of the block
                         let mut decompressor = self.new_decompressor_at(inner, rounded_pos)?;
-                        let uncompressed_size = self.uncompressed_block_size_at(rounded_pos)?;
 
                         // Move forward inside the block to reach the expected position
-                        io::copy(&mut (&mut decompressor).take(inside_block), &mut io::sink())?;
+                        if let Err(err) =
+                            io::copy(&mut (&mut decompressor).take(inside_block), &mut io::sink())
+                        {
+                            self.state =
+                                CompressionLayerReaderState::Ready(decompressor.into_inner());
+                            return Err(err);
+                        }
                         self.state = CompressionLayerReaderState::InData {
                             read: inside_block as u32,
                             uncompressed_size,
@@ -401,10 +1071,6 @@ impl<'a, R: Read + Seek> Seek for CompressionLayerReader<'a, R> {
                         } else {
                             self.seek(SeekFrom::Start((pos + self.underlayer_pos as i64) as u64))
                         }
-
-                        // TODO: Possible optimization:
-                        // - if pos is positive and inside the current block,
-                        // just advance the decompressor
                     }
                     SeekFrom::End(pos) => {
                         if pos > 0 {
@@ -412,7 +1078,11 @@ impl<'a, R: Read + Seek> Seek for CompressionLayerReader<'a, R> {
                             return Err(Error::EndOfStream.into());
                         }
 
-                        let end_pos = (&self.sizes_info).as_ref().unwrap().max_uncompressed_pos();
+                        let end_pos = self
+                            .sizes_info
+                            .as_ref()
+                            .unwrap()
+                            .max_uncompressed_pos(self.uncompressed_block_size);
                         let distance_from_end = -pos;
                         self.seek(SeekFrom::Start(end_pos - distance_from_end as u64))
                     }
@@ -443,9 +1113,8 @@ impl<W: Write> WriterWithCount<W> {
 
 impl<W: Write> Write for WriterWithCount<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.inner.write(buf).map(|i| {
+        self.inner.write(buf).inspect(|&i| {
             self.pos += i as u32;
-            i
         })
     }
 
@@ -454,12 +1123,13 @@ impl<W: Write> Write for WriterWithCount<W> {
     }
 }
 
+#[allow(clippy::large_enum_variant)]
 enum CompressionLayerWriterState<W: Write> {
     /// Ready contains the real inner destination
     Ready(W),
     /// How many uncompressed bytes have already been written for the current
     /// block
-    InData(u32, brotli::CompressorWriter<WriterWithCount<W>>),
+    InData(u32, Compressor<WriterWithCount<W>>),
     /// Empty is a placeholder to allow state replacement
     Empty,
 }
@@ -472,12 +1142,13 @@ enum CompressionLayerWriterState<W: Write> {
 /// Pro:
 /// * no need to store the compressed size
 /// * compression can be streamed (storing the compressed size before the
-/// compressed block leads to either seekable stream, which is not an option
-/// here, or full-memory compression before actual write, which add limits to
-/// the memory footprint)
+///   compressed block leads to either seekable stream, which is not an option
+///   here, or full-memory compression before actual write, which add limits to
+///   the memory footprint)
+///
 /// Cons:
 /// * if the index is lost, a slow decompression with a block size of 1 is
-/// needed to found the CompressedBlock boundaries
+///   needed to found the CompressedBlock boundaries
 pub struct CompressionLayerWriter<'a, W: 'a + Write> {
     state: CompressionLayerWriterState<Box<dyn 'a + LayerWriter<'a, W>>>,
     // Ordered list of compressed size of block of `UNCOMPRESSED_DATA_SIZE`
@@ -488,16 +1159,25 @@ pub struct CompressionLayerWriter<'a, W: 'a + Write> {
     // / C])`, with `C = UNCOMPRESSED_DATA_SIZE`
     compressed_sizes: Vec<u32>,
     // From config
+    algorithm: CompressionAlgorithm,
     compression_level: u32,
+    uncompressed_block_size: u32,
+    dictionary: Option<Arc<Vec<u8>>>,
+    /// If set, blocks are compressed by worker threads, `state` staying `Ready`
+    /// except for a block continued after a flush
+    parallel: Option<ParallelCompressor>,
 }
 
 impl<W: Write> CompressionLayerWriterState<W> {
     fn into_inner(self) -> W {
         match self {
             CompressionLayerWriterState::Ready(inner) => inner,
-            CompressionLayerWriterState::InData(_written, compress) => {
-                compress.into_inner().into_inner()
-            }
+            CompressionLayerWriterState::InData(_written, compress) => compress
+                .finish()
+                .unwrap_or_else(|err| {
+                    panic!("[Writer] Unable to end the compressed block: {}", err)
+                })
+                .into_inner(),
             // `panic!` explicitly called to avoid propagating an error which
             // must never happens (ie, calling `into_inner` in an inconsistent
             // internal state)
@@ -514,7 +1194,41 @@ impl<'a, W: 'a + Write> CompressionLayerWriter<'a, W> {
         Self {
             state: CompressionLayerWriterState::Ready(inner),
             compressed_sizes: Vec::new(),
+            algorithm: config.algorithm,
             compression_level: config.compression_level,
+            uncompressed_block_size: config.uncompressed_block_size,
+            dictionary: config.dictionary.clone(),
+            parallel: if config.threads > 1 {
+                Some(ParallelCompressor::new(
+                    config.algorithm,
+                    config.compression_level,
+                    config.dictionary.clone(),
+                    config.threads,
+                ))
+            } else {
+                None
+            },
+        }
+    }
+
+    /// Resume an existing stream, after its first blocks, of sizes
+    /// `compressed_sizes`
+    ///
+    /// The compression level is not recorded in archives: the default one of
+    /// the algorithm is used
+    pub fn resume(
+        inner: Box<dyn 'a + LayerWriter<'a, W>>,
+        config: &CompressionReaderConfig,
+        compressed_sizes: Vec<u32>,
+    ) -> CompressionLayerWriter<'a, W> {
+        Self {
+            state: CompressionLayerWriterState::Ready(inner),
+            compressed_sizes,
+            algorithm: config.algorithm,
+            compression_level: config.algorithm.default_level(),
+            uncompressed_block_size: config.uncompressed_block_size,
+            dictionary: config.dictionary.clone(),
+            parallel: None,
         }
     }
 }
@@ -534,12 +1248,23 @@ impl<'a, W: 'a + Write> LayerWriter<'a, W> for CompressionLayerWriter<'a, W> {
         let old_state = std::mem::replace(&mut self.state, CompressionLayerWriterState::Empty);
         let mut last_block_size = 0;
         let mut inner = match old_state {
-            CompressionLayerWriterState::Ready(inner) => inner,
+            CompressionLayerWriterState::Ready(mut inner) => {
+                if let Some(parallel) = &mut self.parallel {
+                    last_block_size = parallel.finish(&mut inner, &mut self.compressed_sizes)?;
+                }
+                inner
+            }
             CompressionLayerWriterState::InData(written, compress) => {
-                let inner_count = compress.into_inner();
+                let inner_count = compress.finish()?;
                 self.compressed_sizes.push(inner_count.pos);
                 last_block_size = written;
-                inner_count.into_inner()
+                let mut inner = inner_count.into_inner();
+                if let Some(parallel) = &mut self.parallel {
+                    // Block continued after a flush: the workers have
+                    // nothing left, and only have to be stopped
+                    parallel.finish(&mut inner, &mut self.compressed_sizes)?;
+                }
+                inner
             }
             CompressionLayerWriterState::Empty => {
                 // Should never happens, except if an error already occurs before
@@ -554,13 +1279,12 @@ impl<'a, W: 'a + Write> LayerWriter<'a, W> for CompressionLayerWriter<'a, W> {
         // The values is restored just after the operation (non-thread safe, but
         // in a multi-thread env, we will already required a lock for the
         // writing)
-        let compressed_sizes = std::mem::replace(&mut self.compressed_sizes, Vec::new());
+        let compressed_sizes = std::mem::take(&mut self.compressed_sizes);
         let sinfo = SizesInfo {
             compressed_sizes,
             last_block_size,
         };
-        if bincode::config()
-            .limit(BINCODE_MAX_DESERIALIZE)
+        if bincode_options()
             .serialize_into(&mut inner, &sinfo)
             .is_err()
         {
@@ -586,37 +1310,53 @@ impl<'a, W: 'a + Write> LayerWriter<'a, W> for CompressionLayerWriter<'a, W> {
 
 impl<'a, W: 'a + Write> Write for CompressionLayerWriter<'a, W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // With threads, a block continued after a flush is completed as
+        // without them
+        if let (Some(parallel), CompressionLayerWriterState::Ready(inner)) =
+            (&mut self.parallel, &mut self.state)
+        {
+            return parallel.write(
+                buf,
+                self.uncompressed_block_size,
+                inner,
+                &mut self.compressed_sizes,
+            );
+        }
+
         // Use this mem::replace trick to be able to get back the compressor
         // inner and freely move from CompressionLayerWriterState to others
         let old_state = std::mem::replace(&mut self.state, CompressionLayerWriterState::Empty);
         match old_state {
             CompressionLayerWriterState::Ready(inner) => {
                 let inner_count = WriterWithCount::new(inner);
-                let mut compress = brotli::CompressorWriter::new(
-                    inner_count,
-                    0,
+                let mut compress = Compressor::new(
+                    self.algorithm,
                     self.compression_level,
-                    BROTLI_LOG_WINDOW,
-                );
-                let size = std::cmp::min(UNCOMPRESSED_DATA_SIZE as usize, buf.len());
+                    self.dictionary.as_deref().map(Vec::as_slice),
+                    inner_count,
+                )?;
+                let size = std::cmp::min(self.uncompressed_block_size as usize, buf.len());
                 let written = compress.write(&buf[..size])?;
                 self.state = CompressionLayerWriterState::InData(written as u32, compress);
                 Ok(written)
             }
             CompressionLayerWriterState::InData(written, mut compress) => {
-                if written > UNCOMPRESSED_DATA_SIZE {
+                if written > self.uncompressed_block_size {
                     return Err(Error::WrongReaderState(
                         "[Compression Layer] Too much written".to_string(),
                     ).into());
                 }
-                if written == UNCOMPRESSED_DATA_SIZE {
-                    let inner_count = compress.into_inner();
+                if written == self.uncompressed_block_size {
+                    let inner_count = compress.finish()?;
                     self.compressed_sizes.push(inner_count.pos);
                     self.state = CompressionLayerWriterState::Ready(inner_count.into_inner());
                     // Start a new block, fill it with new values!
                     return self.write(buf);
                 }
-                let size = std::cmp::min((UNCOMPRESSED_DATA_SIZE - written) as usize, buf.len());
+                let size = std::cmp::min(
+                    (self.uncompressed_block_size - written) as usize,
+                    buf.len(),
+                );
                 let written_add = compress.write(&buf[..size])?;
                 self.state =
                     CompressionLayerWriterState::InData(written + written_add as u32, compress);
@@ -629,6 +1369,25 @@ impl<'a, W: 'a + Write> Write for CompressionLayerWriter<'a, W> {
     }
 
     fn flush(&mut self) -> io::Result<()> {
+        if let (Some(parallel), CompressionLayerWriterState::Ready(inner)) =
+            (&mut self.parallel, &mut self.state)
+        {
+            parallel.write_pending(inner, &mut self.compressed_sizes)?;
+            if !parallel.block.is_empty() {
+                // Continue the current block in this thread, to flush it
+                let block = std::mem::take(&mut parallel.block);
+                let old_state =
+                    std::mem::replace(&mut self.state, CompressionLayerWriterState::Empty);
+                let mut compress = Compressor::new(
+                    self.algorithm,
+                    self.compression_level,
+                    self.dictionary.as_deref().map(Vec::as_slice),
+                    WriterWithCount::new(old_state.into_inner()),
+                )?;
+                compress.write_all(&block)?;
+                self.state = CompressionLayerWriterState::InData(block.len() as u32, compress);
+            }
+        }
         match &mut self.state {
             CompressionLayerWriterState::Ready(inner) => inner.flush(),
             CompressionLayerWriterState::InData(_written, compress) => compress.flush(),
@@ -644,12 +1403,22 @@ impl<'a, W: 'a + Write> Write for CompressionLayerWriter<'a, W> {
 
 pub struct CompressionLayerFailSafeReader<'a, R: 'a + Read> {
     state: CompressionLayerReaderState<Box<dyn 'a + LayerFailSafeReader<'a, R>>>,
+    /// Uncompressed size of full blocks, from config
+    uncompressed_block_size: u32,
+    algorithm: CompressionAlgorithm,
+    dictionary: Option<Arc<Vec<u8>>>,
 }
 
 impl<'a, R: 'a + Read> CompressionLayerFailSafeReader<'a, R> {
-    pub fn new(inner: Box<dyn 'a + LayerFailSafeReader<'a, R>>) -> Result<Self, Error> {
+    pub fn new(
+        inner: Box<dyn 'a + LayerFailSafeReader<'a, R>>,
+        config: &CompressionReaderConfig,
+    ) -> Result<Self, Error> {
         Ok(Self {
             state: CompressionLayerReaderState::Ready(inner),
+            uncompressed_block_size: config.uncompressed_block_size,
+            algorithm: config.algorithm,
+            dictionary: config.dictionary.clone(),
         })
     }
 }
@@ -682,11 +1451,16 @@ impl<'a, R: 'a + Read> Read for CompressionLayerFailSafeReader<'a, R> {
                 // will stop on the first byte of the next CompressionBlock.
                 // This is slower, but we don't have index, and
                 // therefore we don't know the compressed block size
-                let decompressor = brotli::Decompressor::new(inner, 1);
+                let decompressor = Decompressor::new(
+                    self.algorithm,
+                    self.dictionary.as_deref().map(Vec::as_slice),
+                    inner,
+                    1,
+                )?;
                 self.state = CompressionLayerReaderState::InData {
                     read: 0,
                     // Default values, for "repair" mode
-                    uncompressed_size: UNCOMPRESSED_DATA_SIZE,
+                    uncompressed_size: self.uncompressed_block_size,
                     decompressor,
                 };
                 self.read(buf)
@@ -704,10 +1478,10 @@ impl<'a, R: 'a + Read> Read for CompressionLayerFailSafeReader<'a, R> {
                 }
                 if read == uncompressed_size {
                     // Consume the rest of the current decompressor. Due to the
-                    // brotli implementation, a few bytes might remains, even if
-                    // we already obtain the expected number of bytes. Thanks to
-                    // the brotli format, the decompressor is able to stop at
-                    // the end of the current block.
+                    // implementations, a few bytes might remains (for instance,
+                    // a frame end mark), even if we already obtain the expected
+                    // number of bytes. Thanks to the formats, the decompressor
+                    // is able to stop at the end of the current block.
                     io::copy(&mut decompressor, &mut io::sink())?;
                     // Start a new block, fill it with new values
                     self.state = CompressionLayerReaderState::Ready(decompressor.into_inner());
@@ -740,7 +1514,9 @@ mod tests {
     use rand::distributions::{Alphanumeric, Distribution, Standard};
     use rand::rngs::StdRng;
     use rand::SeedableRng;
+    use std::cell::RefCell;
     use std::io::{Cursor, Read, Write};
+    use std::rc::Rc;
     use std::time::Instant;
 
     // Use few UNCOMPRESSED_DATA_SIZE to force few blocks, and
@@ -858,7 +1634,7 @@ mod tests {
     fn compress_layer() {
         // Compress then decompress with dedicated Layer structs
 
-        for data in vec![get_data(), get_uncompressable_data()] {
+        for data in [get_data(), get_uncompressable_data()] {
             let bytes = data.as_slice();
 
             let file = Vec::new();
@@ -871,8 +1647,13 @@ mod tests {
             comp.finalize().unwrap();
             let file = comp.into_raw();
             let buf = Cursor::new(file.as_slice());
-            let mut decomp =
-                Box::new(CompressionLayerReader::new(Box::new(RawLayerReader::new(buf))).unwrap());
+            let mut decomp = Box::new(
+                CompressionLayerReader::new(
+                    Box::new(RawLayerReader::new(buf)),
+                    &CompressionReaderConfig::default(),
+                )
+                .unwrap(),
+            );
             decomp.initialize().unwrap();
             let mut buf = Vec::new();
             decomp.read_to_end(&mut buf).unwrap();
@@ -891,7 +1672,7 @@ mod tests {
     fn compress_failsafe_layer() {
         // Compress then decompress with Fail-Safe Layer structs
 
-        for data in vec![get_data(), get_uncompressable_data()] {
+        for data in [get_data(), get_uncompressable_data()] {
             let bytes = data.as_slice();
 
             let file = Vec::new();
@@ -904,9 +1685,10 @@ mod tests {
             comp.finalize().unwrap();
             let file = comp.into_raw();
             let mut decomp = Box::new(
-                CompressionLayerFailSafeReader::new(Box::new(RawLayerFailSafeReader::new(
-                    file.as_slice(),
-                )))
+                CompressionLayerFailSafeReader::new(
+                    Box::new(RawLayerFailSafeReader::new(file.as_slice())),
+                    &CompressionReaderConfig::default(),
+                )
                 .unwrap(),
             );
             let mut buf = Vec::new();
@@ -927,7 +1709,7 @@ mod tests {
     fn compress_failsafe_truncated() {
         // Compress then decompress with Fail-Safe Layer structs, while truncating the intermediate buffer
 
-        for data in vec![get_data(), get_uncompressable_data()] {
+        for data in [get_data(), get_uncompressable_data()] {
             let bytes = data.as_slice();
 
             let file = Vec::new();
@@ -944,9 +1726,10 @@ mod tests {
             let stop = file.len() / 2;
 
             let mut decomp = Box::new(
-                CompressionLayerFailSafeReader::new(Box::new(RawLayerFailSafeReader::new(
-                    &file[..stop],
-                )))
+                CompressionLayerFailSafeReader::new(
+                    Box::new(RawLayerFailSafeReader::new(&file[..stop])),
+                    &CompressionReaderConfig::default(),
+                )
                 .unwrap(),
             );
             let mut buf = Vec::new();
@@ -987,8 +1770,13 @@ mod tests {
 
         let file = comp.into_raw();
         let buf = Cursor::new(file.as_slice());
-        let mut decomp =
-            Box::new(CompressionLayerReader::new(Box::new(RawLayerReader::new(buf))).unwrap());
+        let mut decomp = Box::new(
+            CompressionLayerReader::new(
+                Box::new(RawLayerReader::new(buf)),
+                &CompressionReaderConfig::default(),
+            )
+            .unwrap(),
+        );
         decomp.initialize().unwrap();
 
         // Check the footer has been correctly re-read
@@ -1000,7 +1788,7 @@ mod tests {
 
     #[test]
     fn seek_with_footer() {
-        for data in vec![get_data(), get_uncompressable_data()] {
+        for data in [get_data(), get_uncompressable_data()] {
             let bytes = data.as_slice();
 
             let file = Vec::new();
@@ -1013,8 +1801,13 @@ mod tests {
 
             let file = comp.into_raw();
             let buf = Cursor::new(file.as_slice());
-            let mut decomp =
-                Box::new(CompressionLayerReader::new(Box::new(RawLayerReader::new(buf))).unwrap());
+            let mut decomp = Box::new(
+                CompressionLayerReader::new(
+                    Box::new(RawLayerReader::new(buf)),
+                    &CompressionReaderConfig::default(),
+                )
+                .unwrap(),
+            );
             decomp.initialize().unwrap();
 
             // Seek in the first block
@@ -1062,6 +1855,14 @@ mod tests {
             let mut buf = [0u8; 5];
             decomp.read_exact(&mut buf).unwrap();
             assert_eq!(&buf, &bytes[pos as usize..(pos + 5) as usize]);
+
+            // Seek at the end
+            let pos = decomp.seek(SeekFrom::End(0)).unwrap();
+            assert_eq!(pos, SIZE as u64);
+            assert_eq!(decomp.read(&mut buf).unwrap(), 0);
+            let pos = decomp.seek(SeekFrom::Start(3)).unwrap();
+            decomp.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, &bytes[pos as usize..(pos + 5) as usize]);
         }
     }
 
@@ -1073,22 +1874,80 @@ mod tests {
         };
 
         assert_eq!(
-            sizes_info.uncompressed_block_size_at(1),
+            sizes_info.uncompressed_block_size_at(1, UNCOMPRESSED_DATA_SIZE),
             UNCOMPRESSED_DATA_SIZE
         );
-        assert_eq!(sizes_info.uncompressed_block_size_at(3), 42);
+        assert_eq!(
+            sizes_info.uncompressed_block_size_at(3, UNCOMPRESSED_DATA_SIZE),
+            42
+        );
 
         assert_eq!(
-            sizes_info.max_uncompressed_pos(),
+            sizes_info.max_uncompressed_pos(UNCOMPRESSED_DATA_SIZE),
             2 * UNCOMPRESSED_DATA_SIZE as u64 + 42
         );
 
         assert_eq!(
-            sizes_info.compressed_block_size_at(UNCOMPRESSED_DATA_SIZE as u64 + 1),
+            sizes_info.compressed_block_size_at(
+                UNCOMPRESSED_DATA_SIZE as u64 + 1,
+                UNCOMPRESSED_DATA_SIZE
+            ),
             2
         );
     }
 
+    #[test]
+    fn compress_custom_block_size() {
+        // Use a smaller block size, and check it is used on both sides
+        let data = get_data();
+        let bytes = data.as_slice();
+        let block_size = MIN_UNCOMPRESSED_DATA_SIZE;
+
+        let mut config = ArchiveWriterConfig::new();
+        assert!(config.with_compression_block_size(0).is_err());
+        config.with_compression_block_size(block_size).unwrap();
+        let mut comp = Box::new(CompressionLayerWriter::new(
+            Box::new(RawLayerWriter::new(Vec::new())),
+            &config.compress,
+        ));
+        comp.write_all(bytes).unwrap();
+        comp.finalize().unwrap();
+        let expected_blocks = SIZE.div_ceil(block_size as usize);
+        assert_eq!(comp.compressed_sizes.len(), expected_blocks);
+
+        let mut reader_config = CompressionReaderConfig::default();
+        reader_config
+            .load_persistent(config.compress.to_persistent())
+            .unwrap();
+        let file = comp.into_raw();
+        let buf = Cursor::new(file.as_slice());
+        let mut decomp = Box::new(
+            CompressionLayerReader::new(Box::new(RawLayerReader::new(buf)), &reader_config)
+                .unwrap(),
+        );
+        decomp.initialize().unwrap();
+        let pos = decomp
+            .seek(SeekFrom::Start(block_size as u64 * 3 + 7))
+            .unwrap();
+        let mut buf = [0u8; 5];
+        decomp.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, &bytes[pos as usize..(pos + 5) as usize]);
+
+        // Fail-safe reader
+        let mut decomp = Box::new(
+            CompressionLayerFailSafeReader::new(
+                Box::new(RawLayerFailSafeReader::new(file.as_slice())),
+                &reader_config,
+            )
+            .unwrap(),
+        );
+        let mut buf = Vec::new();
+        // The last block being complete, this ends with an error on the footer,
+        // read as the next block
+        assert!(decomp.read_to_end(&mut buf).is_err());
+        assert_eq!(buf.as_slice(), bytes);
+    }
+
     #[test]
     fn compress_config() {
         // Check the compression level is indeed use
@@ -1129,16 +1988,304 @@ mod tests {
         // Check content
         let buf = Cursor::new(file.as_slice());
         let mut buf_out = Vec::new();
-        let mut decomp =
-            Box::new(CompressionLayerReader::new(Box::new(RawLayerReader::new(buf))).unwrap());
+        let mut decomp = Box::new(
+            CompressionLayerReader::new(
+                Box::new(RawLayerReader::new(buf)),
+                &CompressionReaderConfig::default(),
+            )
+            .unwrap(),
+        );
         decomp.initialize().unwrap();
         decomp.read_to_end(&mut buf_out).unwrap();
         let buf2 = Cursor::new(file2.as_slice());
         let mut buf2_out = Vec::new();
-        let mut decomp =
-            Box::new(CompressionLayerReader::new(Box::new(RawLayerReader::new(buf2))).unwrap());
+        let mut decomp = Box::new(
+            CompressionLayerReader::new(
+                Box::new(RawLayerReader::new(buf2)),
+                &CompressionReaderConfig::default(),
+            )
+            .unwrap(),
+        );
         decomp.initialize().unwrap();
         decomp.read_to_end(&mut buf2_out).unwrap();
         assert_eq!(buf_out, buf2_out);
     }
+
+    #[test]
+    fn compress_algorithms() {
+        let data = get_data();
+        let bytes = data.as_slice();
+
+        for algorithm in &[
+            CompressionAlgorithm::Brotli,
+            CompressionAlgorithm::Zstd,
+            CompressionAlgorithm::Lz4,
+        ] {
+            let mut config = ArchiveWriterConfig::new();
+            config.with_compression_algorithm(*algorithm);
+            assert_eq!(config.compress.compression_level, algorithm.default_level());
+            assert!(config.with_compression_level(23).is_err());
+            let mut comp = Box::new(CompressionLayerWriter::new(
+                Box::new(RawLayerWriter::new(Vec::new())),
+                &config.compress,
+            ));
+            comp.write_all(bytes).unwrap();
+            comp.finalize().unwrap();
+            let file = comp.into_raw();
+            // LZ4 has no entropy coding, and cannot compress random
+            // alphanumeric characters
+            if *algorithm != CompressionAlgorithm::Lz4 {
+                assert!(file.len() < bytes.len());
+            }
+
+            // The algorithm is recorded
+            let mut reader_config = CompressionReaderConfig::default();
+            reader_config
+                .load_persistent(config.compress.to_persistent())
+                .unwrap();
+            assert_eq!(reader_config.algorithm, *algorithm);
+
+            // Read, with a seek in a middle block
+            let mut decomp = Box::new(
+                CompressionLayerReader::new(
+                    Box::new(RawLayerReader::new(Cursor::new(file.as_slice()))),
+                    &reader_config,
+                )
+                .unwrap(),
+            );
+            decomp.initialize().unwrap();
+            let mut buf = Vec::new();
+            decomp.read_to_end(&mut buf).unwrap();
+            assert_eq!(buf.as_slice(), bytes);
+            let pos = UNCOMPRESSED_DATA_SIZE as u64 + 42;
+            decomp.seek(SeekFrom::Start(pos)).unwrap();
+            let mut buf = [0u8; 5];
+            decomp.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf, &bytes[pos as usize..pos as usize + 5]);
+
+            // Fail-safe reader, without the index
+            let mut decomp = Box::new(
+                CompressionLayerFailSafeReader::new(
+                    Box::new(RawLayerFailSafeReader::new(file.as_slice())),
+                    &reader_config,
+                )
+                .unwrap(),
+            );
+            let mut buf = Vec::new();
+            decomp.read_to_end(&mut buf).unwrap();
+            assert_eq!(buf.as_slice(), bytes);
+        }
+    }
+
+    #[test]
+    fn compress_dictionary() {
+        // Small and similar files, as JSON logs
+        let log = |i: u32| {
+            format!(
+                "{{\"timestamp\":{},\"level\":\"INFO\",\"host\":\"server-{}\",\"message\":\"request {} handled in {} ms\"}}\n",
+                1_600_000_000 + i * 7,
+                i % 13,
+                i * 31,
+                i % 97
+            )
+            .into_bytes()
+        };
+        let samples: Vec<Vec<u8>> = (0..2000).map(log).collect();
+        let dictionary = train_compression_dictionary(&samples, 4096).unwrap();
+        assert!(dictionary.len() <= 4096);
+        let data: Vec<u8> = (5000..5020).flat_map(log).collect();
+
+        // Only for Zstd, and reset with the algorithm
+        let mut config = ArchiveWriterConfig::new();
+        assert!(config
+            .with_compression_dictionary(dictionary.clone())
+            .is_err());
+        config.with_compression_algorithm(CompressionAlgorithm::Zstd);
+        assert!(config.with_compression_dictionary(Vec::new()).is_err());
+        config
+            .with_compression_dictionary(dictionary.clone())
+            .unwrap();
+        assert_eq!(config.compression_dictionary(), Some(dictionary.as_slice()));
+
+        let compress = |config: &ArchiveWriterConfig| {
+            let mut comp = Box::new(CompressionLayerWriter::new(
+                Box::new(RawLayerWriter::new(Vec::new())),
+                &config.compress,
+            ));
+            comp.write_all(&data).unwrap();
+            comp.finalize().unwrap();
+            comp.into_raw()
+        };
+        let file = compress(&config);
+        let mut without = ArchiveWriterConfig::new();
+        without.with_compression_algorithm(CompressionAlgorithm::Zstd);
+        assert!(file.len() < compress(&without).len());
+
+        // The dictionary is recorded
+        let mut reader_config = CompressionReaderConfig::default();
+        reader_config
+            .load_persistent(config.compress.to_persistent())
+            .unwrap();
+        assert_eq!(reader_config.dictionary.as_deref(), Some(&dictionary));
+
+        let mut decomp = Box::new(
+            CompressionLayerReader::new(
+                Box::new(RawLayerReader::new(Cursor::new(file.as_slice()))),
+                &reader_config,
+            )
+            .unwrap(),
+        );
+        decomp.initialize().unwrap();
+        let mut buf = Vec::new();
+        decomp.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, data);
+
+        let mut decomp = Box::new(
+            CompressionLayerFailSafeReader::new(
+                Box::new(RawLayerFailSafeReader::new(file.as_slice())),
+                &reader_config,
+            )
+            .unwrap(),
+        );
+        let mut buf = Vec::new();
+        decomp.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, data);
+    }
+
+    #[test]
+    fn compress_parallel() {
+        let data = get_data();
+        let bytes = data.as_slice();
+
+        let mut config = ArchiveWriterConfig::new();
+        config.with_threads(4);
+        // Small blocks, for more blocks than the ones in flight
+        config
+            .with_compression_block_size(MIN_UNCOMPRESSED_DATA_SIZE)
+            .unwrap();
+        let mut comp = Box::new(CompressionLayerWriter::new(
+            Box::new(RawLayerWriter::new(Vec::new())),
+            &config.compress,
+        ));
+        // Use several small writes, for blocks to be split across them
+        for chunk in bytes.chunks(4321) {
+            comp.write_all(chunk).unwrap();
+        }
+        comp.finalize().unwrap();
+        let file = comp.into_raw();
+        assert!(file.len() < bytes.len());
+        let mut reader_config = CompressionReaderConfig::default();
+        reader_config
+            .load_persistent(config.compress.to_persistent())
+            .unwrap();
+
+        // Blocks are written in order, and readable as usual
+        let mut decomp = Box::new(
+            CompressionLayerReader::new(
+                Box::new(RawLayerReader::new(Cursor::new(file.as_slice()))),
+                &reader_config,
+            )
+            .unwrap(),
+        );
+        decomp.initialize().unwrap();
+        let mut buf = Vec::new();
+        decomp.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf.as_slice(), bytes);
+        let pos = MIN_UNCOMPRESSED_DATA_SIZE as u64 * 42 + 42;
+        decomp.seek(SeekFrom::Start(pos)).unwrap();
+        let mut buf = [0u8; 5];
+        decomp.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, &bytes[pos as usize..pos as usize + 5]);
+
+        let mut decomp = Box::new(
+            CompressionLayerFailSafeReader::new(
+                Box::new(RawLayerFailSafeReader::new(file.as_slice())),
+                &reader_config,
+            )
+            .unwrap(),
+        );
+        let mut buf = Vec::new();
+        // The last block being complete, this ends with an error on the footer,
+        // read as the next block
+        assert!(decomp.read_to_end(&mut buf).is_err());
+        assert_eq!(buf.as_slice(), bytes);
+    }
+
+    /// Output kept readable while the writer is still in use
+    #[derive(Clone, Default)]
+    struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedOutput {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn compress_parallel_flush() {
+        let data = get_data();
+        let bytes = data.as_slice();
+
+        let mut config = ArchiveWriterConfig::new();
+        config.with_threads(4);
+        config
+            .with_compression_block_size(MIN_UNCOMPRESSED_DATA_SIZE)
+            .unwrap();
+        let mut reader_config = CompressionReaderConfig::default();
+        reader_config
+            .load_persistent(config.compress.to_persistent())
+            .unwrap();
+        let output = SharedOutput::default();
+        let mut comp = Box::new(CompressionLayerWriter::new(
+            Box::new(RawLayerWriter::new(output.clone())),
+            &config.compress,
+        ));
+
+        // Flush in the middle of a block, after several complete ones
+        let flushed = MIN_UNCOMPRESSED_DATA_SIZE as usize * 10 + 1234;
+        for chunk in bytes[..flushed].chunks(4321) {
+            comp.write_all(chunk).unwrap();
+        }
+        comp.flush().unwrap();
+        let file = output.0.borrow().clone();
+        let mut decomp = Box::new(
+            CompressionLayerFailSafeReader::new(
+                Box::new(RawLayerFailSafeReader::new(file.as_slice())),
+                &reader_config,
+            )
+            .unwrap(),
+        );
+        let mut buf = Vec::new();
+        // Everything written before the flush is available
+        let _ = decomp.read_to_end(&mut buf);
+        assert_eq!(buf.as_slice(), &bytes[..flushed]);
+
+        // The flushed block is completed, then threads are used again
+        for chunk in bytes[flushed..].chunks(4321) {
+            comp.write_all(chunk).unwrap();
+        }
+        comp.flush().unwrap();
+        comp.finalize().unwrap();
+        let file = output.0.borrow().clone();
+        let mut decomp = Box::new(
+            CompressionLayerReader::new(
+                Box::new(RawLayerReader::new(Cursor::new(file.as_slice()))),
+                &reader_config,
+            )
+            .unwrap(),
+        );
+        decomp.initialize().unwrap();
+        let mut buf = Vec::new();
+        decomp.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf.as_slice(), bytes);
+        let pos = MIN_UNCOMPRESSED_DATA_SIZE as u64 * 10 + 42;
+        decomp.seek(SeekFrom::Start(pos)).unwrap();
+        let mut buf = [0u8; 5];
+        decomp.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, &bytes[pos as usize..pos as usize + 5]);
+    }
 }
@@ -0,0 +1,141 @@
+use std::io;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use sha2::{Digest, Sha256};
+
+use crate::config::ArchiveWriterConfig;
+use crate::crypto::hash::Sha256Hash;
+use crate::layers::traits::LayerWriter;
+use crate::Error;
+
+// ---------- Config ----------
+
+impl ArchiveWriterConfig {
+    /// Compute a SHA256 of the whole output, available through
+    /// `ArchiveWriter::output_digest` once the archive is finalized
+    ///
+    /// This avoids re-reading the resulting archive to get its fingerprint
+    pub fn enable_output_digest(&mut self) -> &mut ArchiveWriterConfig {
+        self.output_digest = true;
+        self
+    }
+
+    /// Return whether the output digest will be computed
+    pub fn is_output_digest_enabled(&self) -> bool {
+        self.output_digest
+    }
+}
+
+// ---------- Writer ----------
+
+/// Shared slot receiving the digest once the layer is finalized
+///
+/// The layer ends up boxed in the layer stack, so the archive writer keeps
+/// this handle to retrieve the result
+#[derive(Clone, Default)]
+pub(crate) struct DigestHandle {
+    digest: Arc<Mutex<Option<Sha256Hash>>>,
+}
+
+impl DigestHandle {
+    /// Return the digest, if the corresponding layer has been finalized
+    pub(crate) fn get(&self) -> Option<Sha256Hash> {
+        *self.digest.lock().expect("Digest lock poisoned")
+    }
+}
+
+/// Layer maintaining a running SHA256 of everything written through it
+///
+/// It does not modify the data, and is not recorded in the archive
+pub struct DigestLayerWriter<'a, W: 'a + Write> {
+    inner: Box<dyn 'a + LayerWriter<'a, W>>,
+    hash: Sha256,
+    handle: DigestHandle,
+}
+
+impl<'a, W: 'a + Write> DigestLayerWriter<'a, W> {
+    pub(crate) fn new(inner: Box<dyn 'a + LayerWriter<'a, W>>, handle: DigestHandle) -> Self {
+        Self {
+            inner,
+            hash: Sha256::new(),
+            handle,
+        }
+    }
+
+    /// Continue the digest of an output already made of the bytes hashed in
+    /// `hash`
+    pub(crate) fn resume(
+        inner: Box<dyn 'a + LayerWriter<'a, W>>,
+        handle: DigestHandle,
+        hash: Sha256,
+    ) -> Self {
+        Self {
+            inner,
+            hash,
+            handle,
+        }
+    }
+}
+
+impl<'a, W: 'a + Write> LayerWriter<'a, W> for DigestLayerWriter<'a, W> {
+    fn into_inner(self) -> Option<Box<dyn 'a + LayerWriter<'a, W>>> {
+        Some(self.inner)
+    }
+
+    fn into_raw(self: Box<Self>) -> W {
+        self.inner.into_raw()
+    }
+
+    fn finalize(&mut self) -> Result<(), Error> {
+        // Upper layers have already written their last bytes through this one
+        self.inner.finalize()?;
+        let mut digest = Sha256Hash::default();
+        digest.copy_from_slice(&self.hash.finalize_reset());
+        *self.handle.digest.lock().expect("Digest lock poisoned") = Some(digest);
+        Ok(())
+    }
+}
+
+impl<'a, W: 'a + Write> Write for DigestLayerWriter<'a, W> {
+    /// Wrapper on inner, hashing the bytes actually written
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hash.update(&buf[..written]);
+        Ok(written)
+    }
+
+    /// Wrapper on inner
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::layers::raw::RawLayerWriter;
+
+    static DATA: [u8; 4] = [1, 2, 3, 4];
+
+    #[test]
+    fn digest_layer() {
+        let handle = DigestHandle::default();
+        let file = Vec::new();
+        let mut dig_w = Box::new(DigestLayerWriter::new(
+            Box::new(RawLayerWriter::new(file)),
+            handle.clone(),
+        ));
+        dig_w.write_all(&DATA).unwrap();
+        dig_w.write_all(&DATA).unwrap();
+        // Not available before finalization
+        assert_eq!(handle.get(), None);
+        dig_w.finalize().unwrap();
+
+        let out = dig_w.into_raw();
+        assert_eq!(out.len(), DATA.len() * 2);
+        let expected = Sha256::digest(&out);
+        assert_eq!(handle.get().unwrap(), expected.as_slice());
+    }
+}
@@ -1,16 +1,31 @@
-use crate::crypto::aesgcm::{AesGcm256, ConstantTimeEq, Tag, TAG_LENGTH};
-use crate::crypto::ecc::{retrieve_key, store_key_for_multi_recipients, MultiRecipientPersistent};
+use crate::crypto::aesgcm::{AesGcm256, ConstantTimeEq, TAG_LENGTH};
+use crate::crypto::aesgcmsiv;
+use crate::crypto::ecc::{
+    find_recipient_slot, retrieve_key, retrieve_key_with_provider, store_key_for_multi_recipients,
+    MultiRecipientPersistent, PrivateKeyProvider,
+};
+use crate::crypto::hybrid::{
+    find_hybrid_recipient_slot, retrieve_key_with_hybrid, store_key_for_hybrid_recipients,
+    HybridPrivateKey, HybridPublicKey, HybridRecipientsPersistent,
+};
+use crate::crypto::locked::Locked;
+use crate::crypto::password::{
+    retrieve_key_with_password, store_key_for_password, PasswordRecipientPersistent,
+};
 
 use crate::layers::traits::{LayerFailSafeReader, LayerReader, LayerWriter};
 use crate::Error;
 use std::io;
 use std::io::{BufReader, Cursor, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
-use crate::config::{ArchiveReaderConfig, ArchiveWriterConfig};
+use crate::config::{ArchiveReaderConfig, ArchiveWriterConfig, ConfigResult};
 use crate::errors::ConfigError;
-use rand::{Rng, SeedableRng};
+use rand::{CryptoRng, Rng, RngCore, SeedableRng};
 use rand_chacha::ChaChaRng;
 use x25519_dalek::{PublicKey, StaticSecret};
+use zeroize::{Zeroize, Zeroizing};
 
 use serde::{Deserialize, Serialize};
 
@@ -18,7 +33,12 @@ const CIPHER_BUF_SIZE: u64 = 4096;
 const KEY_SIZE: usize = 32;
 // This is the size of the nonce taken as input
 const NONCE_SIZE: usize = 8;
-const CHUNK_SIZE: u64 = 128 * 1024;
+/// Default chunk size. This is also the value used by format v1 archives,
+/// which do not record it
+pub(crate) const CHUNK_SIZE: u64 = 128 * 1024;
+/// Allowed range for the chunk size
+pub(crate) const MIN_CHUNK_SIZE: u64 = 4 * 1024;
+pub(crate) const MAX_CHUNK_SIZE: u64 = 16 * 1024 * 1024;
 
 // This is the Nonce as expected by AesGcm
 const NONCE_AES_SIZE: usize = 96 / 8;
@@ -28,10 +48,12 @@ type Nonce = [u8; NONCE_AES_SIZE];
 ///
 /// AesGcm expect a 96 bits nonce.
 /// The nonce build as:
+/// ```text
 /// {
 ///   - 8 byte nonce, unique per archive
 ///   - 4 byte counter, unique per chunk and incremental
 /// }
+/// ```
 ///
 /// Inspired from the construction in TLS or STREAM from "Online
 /// Authenticated-Encryption and its Nonce-Reuse Misuse-Resistance"
@@ -44,20 +66,242 @@ fn build_nonce(nonce_prefix: [u8; NONCE_SIZE], current_ctr: u32) -> Nonce {
 
 // ---------- Config ----------
 
+/// Authenticated cipher used to encrypt chunks
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum EncryptionCipher {
+    /// AES-256-GCM, the default
+    #[default]
+    AesGcm256,
+    /// AES-256-GCM-SIV, resistant to nonce misuse: if the nonce is ever reused
+    /// (for instance, after a RNG failure), only the equality of chunks leaks
+    ///
+    /// Chunks are encrypted once complete, so they are buffered while writing,
+    /// and the last, truncated, chunk of an archive cannot be recovered by the
+    /// fail-safe reader
+    AesGcmSiv256,
+}
+
 /// Configuration stored in the header, to be reloaded
 #[derive(Serialize, Deserialize)]
 pub struct EncryptionPersistentConfig {
     multi_recipient: MultiRecipientPersistent,
     nonce: [u8; NONCE_SIZE],
+    /// Size of the encrypted chunks, without their tag
+    chunk_size: u32,
+    cipher: EncryptionCipher,
+    /// Shared key wrapped with a password, if any
+    password: Option<PasswordRecipientPersistent>,
+    /// Shared key wrapped for the hybrid (X25519 and ML-KEM) recipients, if any
+    hybrid: Option<HybridRecipientsPersistent>,
+}
+
+impl EncryptionPersistentConfig {
+    /// Cipher used to encrypt chunks
+    pub(crate) fn cipher(&self) -> EncryptionCipher {
+        self.cipher
+    }
+
+    /// Size of the encrypted chunks, including their tag
+    pub(crate) fn chunk_tag_size(&self) -> u64 {
+        chunk_tag_size(u64::from(self.chunk_size))
+    }
+
+    /// Wrap `key`, the shared key this configuration is for, for
+    /// `recipients` instead of the current ones
+    ///
+    /// The nonce, the chunk parameters, the password and the hybrid
+    /// recipients are kept
+    pub(crate) fn rekey<T: RngCore + CryptoRng>(
+        &mut self,
+        key: &[u8; KEY_SIZE],
+        recipients: &[PublicKey],
+        csprng: &mut T,
+    ) -> Result<(), ConfigError> {
+        if recipients.is_empty() && self.password.is_none() && self.hybrid.is_none() {
+            return Err(ConfigError::EncryptionKeyIsMissing);
+        }
+        self.multi_recipient = store_key_for_multi_recipients(recipients, key, csprng)
+            .map_err(|_| ConfigError::ECIESComputationError)?;
+        Ok(())
+    }
+
+    /// Return the recipient slots, X25519 ones first, then hybrid ones, then
+    /// the password one, with the index of the key opening each of them among
+    /// `private_keys` or `hybrid_private_keys`, if any
+    pub(crate) fn recipient_slots(
+        &self,
+        private_keys: &[StaticSecret],
+        hybrid_private_keys: &[HybridPrivateKey],
+    ) -> Vec<RecipientSlot> {
+        let mut slots: Vec<RecipientSlot> = (0..self.multi_recipient.count())
+            .map(|_| RecipientSlot::new(RecipientKind::X25519))
+            .collect();
+        for (key_index, private_key) in private_keys.iter().enumerate() {
+            if let Ok(Some(index)) = find_recipient_slot(&self.multi_recipient, private_key) {
+                slots[index].key_index = Some(key_index);
+            }
+        }
+        if let Some(persist) = &self.hybrid {
+            let first = slots.len();
+            slots.extend((0..persist.count()).map(|_| RecipientSlot::new(RecipientKind::Hybrid)));
+            for (key_index, private_key) in hybrid_private_keys.iter().enumerate() {
+                if let Ok(Some(index)) = find_hybrid_recipient_slot(persist, private_key) {
+                    slots[first + index].key_index = Some(key_index);
+                }
+            }
+        }
+        if self.password.is_some() {
+            slots.push(RecipientSlot::new(RecipientKind::Password));
+        }
+        slots
+    }
+}
+
+/// Kind of a recipient slot, see `RecipientSlot`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecipientKind {
+    /// X25519 public key (from an Ed25519 one)
+    X25519,
+    /// Hybrid X25519 and ML-KEM-768 public key
+    Hybrid,
+    /// Password
+    Password,
+}
+
+/// Slot of an encrypted archive, holding the archive key for one recipient
+///
+/// Recipients' public keys are not stored in the archive: a slot is only
+/// identified by trying private keys on it, see `recipient_slots`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipientSlot {
+    pub kind: RecipientKind,
+    /// Index, among the given private keys of this kind, of the one opening
+    /// this slot, if any
+    pub key_index: Option<usize>,
+}
+
+impl RecipientSlot {
+    fn new(kind: RecipientKind) -> Self {
+        Self {
+            kind,
+            key_index: None,
+        }
+    }
+}
+
+/// `EncryptionPersistentConfig` as stored in format v1 archives
+#[derive(Deserialize)]
+pub(crate) struct EncryptionPersistentConfigV1 {
+    multi_recipient: MultiRecipientPersistent,
+    nonce: [u8; NONCE_SIZE],
+}
+
+impl From<EncryptionPersistentConfigV1> for EncryptionPersistentConfig {
+    fn from(config: EncryptionPersistentConfigV1) -> Self {
+        EncryptionPersistentConfig {
+            multi_recipient: config.multi_recipient,
+            nonce: config.nonce,
+            chunk_size: CHUNK_SIZE as u32,
+            cipher: EncryptionCipher::AesGcm256,
+            password: None,
+            hybrid: None,
+        }
+    }
+}
+
+/// `EncryptionPersistentConfig` as stored in format v2 and v3 archives,
+/// without password
+#[derive(Deserialize)]
+pub(crate) struct EncryptionPersistentConfigV2 {
+    multi_recipient: MultiRecipientPersistent,
+    nonce: [u8; NONCE_SIZE],
+    chunk_size: u32,
+    cipher: EncryptionCipher,
+}
+
+impl From<EncryptionPersistentConfigV2> for EncryptionPersistentConfig {
+    fn from(config: EncryptionPersistentConfigV2) -> Self {
+        EncryptionPersistentConfig {
+            multi_recipient: config.multi_recipient,
+            nonce: config.nonce,
+            chunk_size: config.chunk_size,
+            cipher: config.cipher,
+            password: None,
+            hybrid: None,
+        }
+    }
+}
+
+fn is_chunk_size_valid(size: u64) -> bool {
+    (MIN_CHUNK_SIZE..=MAX_CHUNK_SIZE).contains(&size)
 }
 
+/// Set of recipients' public keys, meant to be built once and shared between
+/// several archives
+///
+/// Keys are deduplicated on insertion, and the set is reference-counted: cloning
+/// it, or handing it to an `ArchiveWriterConfig`, does not copy the keys.
+#[derive(Clone, Default)]
+pub struct RecipientSet {
+    keys: Arc<Vec<PublicKey>>,
+}
+
+impl RecipientSet {
+    /// Build a set from `keys`, ignoring duplicates
+    pub fn new(keys: &[PublicKey]) -> Self {
+        let mut set = Self::default();
+        set.extend(keys);
+        set
+    }
+
+    /// Add `keys` to the set, ignoring the ones already present
+    pub fn extend(&mut self, keys: &[PublicKey]) {
+        // Only copy the inner vector if it is shared with another user
+        let inner = Arc::make_mut(&mut self.keys);
+        for key in keys {
+            if !inner.iter().any(|k| k.as_bytes() == key.as_bytes()) {
+                inner.push(*key);
+            }
+        }
+    }
+
+    /// Public keys of the recipients
+    pub fn keys(&self) -> &[PublicKey] {
+        &self.keys
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Random generator usable for the encryption secrets, see
+/// `ArchiveWriterConfig::with_rng`
+pub trait ArchiveRng: RngCore + CryptoRng + Send {}
+
+impl<T: RngCore + CryptoRng + Send> ArchiveRng for T {}
+
 pub struct EncryptionConfig {
     /// Public keys with which to encrypt the symmetric encryption key below
-    ecc_keys: Vec<PublicKey>,
+    ecc_keys: RecipientSet,
+    /// Password with which to also encrypt the symmetric encryption key
+    password: Option<Zeroizing<String>>,
+    /// Hybrid public keys with which to also encrypt the symmetric encryption key
+    hybrid_keys: Vec<HybridPublicKey>,
     /// Symmetric encryption Key
-    key: [u8; KEY_SIZE],
+    key: Locked<[u8; KEY_SIZE]>,
     /// Symmetric encryption nonce
     nonce: [u8; NONCE_SIZE],
+    /// Size of the encrypted chunks, without their tag
+    chunk_size: u64,
+    cipher: EncryptionCipher,
+    /// User provided generator, for the ECIES ephemeral key. If not set, a
+    /// `ChaChaRng` seeded from the OS is used
+    rng: Option<Mutex<Box<dyn ArchiveRng>>>,
 }
 
 impl std::default::Default for EncryptionConfig {
@@ -78,20 +322,40 @@ impl std::default::Default for EncryptionConfig {
         // and this function is documented as "secure" in
         // https://docs.rs/rand/0.7.3/rand/trait.SeedableRng.html#method.from_entropy
         let mut csprng = ChaChaRng::from_entropy();
-        let key = csprng.gen::<[u8; KEY_SIZE]>();
+        // Generate the key directly in its final location
+        let mut key = Locked::new([0u8; KEY_SIZE]);
+        csprng.fill_bytes(&mut *key);
         let nonce = csprng.gen::<[u8; NONCE_SIZE]>();
         EncryptionConfig {
-            ecc_keys: Vec::new(),
+            ecc_keys: RecipientSet::default(),
+            password: None,
+            hybrid_keys: Vec::new(),
             key,
             nonce,
+            chunk_size: CHUNK_SIZE,
+            cipher: EncryptionCipher::default(),
+            rng: None,
         }
     }
 }
 
 impl EncryptionConfig {
+    /// Return a new generator, seeded from the one set with
+    /// `ArchiveWriterConfig::with_rng` if any, or from the OS otherwise
+    pub(crate) fn derive_rng(&self) -> ChaChaRng {
+        match &self.rng {
+            Some(rng) => {
+                let mut seed = [0u8; 32];
+                rng.lock().expect("RNG lock poisoned").fill_bytes(&mut seed);
+                ChaChaRng::from_seed(seed)
+            }
+            None => ChaChaRng::from_entropy(),
+        }
+    }
+
     /// Consistency check
     pub fn check(&self) -> Result<(), ConfigError> {
-        if self.ecc_keys.is_empty() {
+        if self.ecc_keys.is_empty() && self.password.is_none() && self.hybrid_keys.is_empty() {
             Err(ConfigError::EncryptionKeyIsMissing)
         } else {
             Ok(())
@@ -99,24 +363,76 @@ impl EncryptionConfig {
     }
 
     pub fn to_persistent(&self) -> Result<EncryptionPersistentConfig, ConfigError> {
-        let mut rng = ChaChaRng::from_entropy();
-        if let Ok(multi_recipient) =
-            store_key_for_multi_recipients(&self.ecc_keys, &self.key, &mut rng)
-        {
-            Ok(EncryptionPersistentConfig {
-                multi_recipient,
-                nonce: self.nonce,
-            })
-        } else {
-            Err(ConfigError::ECIESComputationError)
+        match &self.rng {
+            Some(rng) => {
+                let mut rng = rng.lock().expect("RNG lock poisoned");
+                let mut rng: &mut dyn ArchiveRng = &mut **rng;
+                self.to_persistent_with_rng(&mut rng)
+            }
+            None => self.to_persistent_with_rng(&mut ChaChaRng::from_entropy()),
         }
     }
+
+    fn to_persistent_with_rng<T: RngCore + CryptoRng>(
+        &self,
+        rng: &mut T,
+    ) -> Result<EncryptionPersistentConfig, ConfigError> {
+        let multi_recipient = store_key_for_multi_recipients(self.ecc_keys.keys(), &self.key, rng)
+            .map_err(|_| ConfigError::ECIESComputationError)?;
+        let password = match &self.password {
+            Some(password) => Some(store_key_for_password(password.as_bytes(), &self.key, rng)?),
+            None => None,
+        };
+        let hybrid = if self.hybrid_keys.is_empty() {
+            None
+        } else {
+            Some(
+                store_key_for_hybrid_recipients(&self.hybrid_keys, &self.key, rng)
+                    .map_err(|_| ConfigError::ECIESComputationError)?,
+            )
+        };
+        Ok(EncryptionPersistentConfig {
+            multi_recipient,
+            nonce: self.nonce,
+            chunk_size: self.chunk_size as u32,
+            cipher: self.cipher,
+            password,
+            hybrid,
+        })
+    }
 }
 
 impl ArchiveWriterConfig {
     /// Set public keys to use
     pub fn add_public_keys(&mut self, keys: &[PublicKey]) -> &mut ArchiveWriterConfig {
-        self.encrypt.ecc_keys.extend_from_slice(keys);
+        self.encrypt.ecc_keys.extend(keys);
+        self
+    }
+
+    /// Also allow to decrypt the archive with `password`, in addition to the
+    /// private keys of the recipients
+    ///
+    /// The symmetric key is wrapped with a key derived from the password using
+    /// Argon2id, which is intentionally costly (64 MiB, a fraction of a second)
+    pub fn with_password(&mut self, password: &str) -> &mut ArchiveWriterConfig {
+        self.encrypt.password = Some(Zeroizing::new(password.to_string()));
+        self
+    }
+
+    /// Set hybrid public keys to use, each combining X25519 with ML-KEM-768
+    ///
+    /// These recipients can still decrypt the archive if one of the two
+    /// algorithms is broken, for instance X25519 by a quantum computer
+    pub fn add_hybrid_public_keys(&mut self, keys: &[HybridPublicKey]) -> &mut ArchiveWriterConfig {
+        self.encrypt.hybrid_keys.extend_from_slice(keys);
+        self
+    }
+
+    /// Use an already built set of recipients, replacing the ones previously set
+    ///
+    /// The set is shared, not copied, so it can be reused for many archives
+    pub fn set_recipients(&mut self, recipients: &RecipientSet) -> &mut ArchiveWriterConfig {
+        self.encrypt.ecc_keys = recipients.clone();
         self
     }
 
@@ -129,20 +445,77 @@ impl ArchiveWriterConfig {
     pub fn encryption_nonce(&self) -> &[u8; NONCE_SIZE] {
         &self.encrypt.nonce
     }
+
+    /// Set the size of encrypted chunks
+    ///
+    /// Each chunk is authenticated separately: smaller chunks speed up random
+    /// access, at the cost of a tag per chunk. The value must be in
+    /// [`MIN_CHUNK_SIZE`, `MAX_CHUNK_SIZE`]
+    pub fn with_encryption_chunk_size(&mut self, chunk_size: u32) -> ConfigResult<'_> {
+        if !is_chunk_size_valid(chunk_size as u64) {
+            Err(ConfigError::EncryptionChunkSizeOutOfRange)
+        } else {
+            self.encrypt.chunk_size = chunk_size as u64;
+            Ok(self)
+        }
+    }
+
+    /// Return the size of encrypted chunks
+    pub fn encryption_chunk_size(&self) -> u32 {
+        self.encrypt.chunk_size as u32
+    }
+
+    /// Set the cipher used to encrypt chunks. It is recorded in the header
+    pub fn with_encryption_cipher(&mut self, cipher: EncryptionCipher) -> &mut ArchiveWriterConfig {
+        self.encrypt.cipher = cipher;
+        self
+    }
+
+    /// Return the cipher used to encrypt chunks
+    pub fn encryption_cipher(&self) -> EncryptionCipher {
+        self.encrypt.cipher
+    }
+
+    /// Use `rng` to generate the encryption secrets (symmetric key, nonce and
+    /// ECIES ephemeral key), instead of a `ChaChaRng` seeded from the OS
+    ///
+    /// The symmetric key and nonce are immediately generated again from `rng`.
+    /// This is intended for certified or DRBG-backed generators; a
+    /// deterministic generator must only be used for tests
+    pub fn with_rng<R: 'static + ArchiveRng>(&mut self, mut rng: R) -> &mut ArchiveWriterConfig {
+        rng.fill_bytes(&mut *self.encrypt.key);
+        rng.fill_bytes(&mut self.encrypt.nonce);
+        self.encrypt.rng = Some(Mutex::new(Box::new(rng)));
+        self
+    }
 }
 
 pub struct EncryptionReaderConfig {
     /// Private key(s) to use
-    private_keys: Vec<StaticSecret>,
+    private_keys: Vec<Locked<StaticSecret>>,
+    /// Hybrid private key(s) to use
+    hybrid_private_keys: Vec<Locked<HybridPrivateKey>>,
+    /// Private keys kept outside of MLA, used as `private_keys`
+    key_providers: Vec<Box<dyn PrivateKeyProvider>>,
+    /// Password to use, if no private key matches
+    password: Option<Zeroizing<String>>,
     /// Symmetric encryption key and nonce, if decrypted successfully from header
-    encrypt_parameters: Option<([u8; KEY_SIZE], [u8; NONCE_SIZE])>,
+    encrypt_parameters: Option<(Locked<[u8; KEY_SIZE]>, [u8; NONCE_SIZE])>,
+    /// Size of the encrypted chunks, without their tag
+    chunk_size: u64,
+    cipher: EncryptionCipher,
 }
 
 impl std::default::Default for EncryptionReaderConfig {
     fn default() -> Self {
         Self {
             private_keys: Vec::new(),
+            hybrid_private_keys: Vec::new(),
+            key_providers: Vec::new(),
+            password: None,
             encrypt_parameters: None,
+            chunk_size: CHUNK_SIZE,
+            cipher: EncryptionCipher::default(),
         }
     }
 }
@@ -150,15 +523,21 @@ impl std::default::Default for EncryptionReaderConfig {
 impl EncryptionReaderConfig {
     pub fn load_persistent(
         &mut self,
-        config: EncryptionPersistentConfig,
+        config: &EncryptionPersistentConfig,
     ) -> Result<(), ConfigError> {
-        if self.private_keys.is_empty() {
+        if !self.has_private_keys() && self.password.is_none() {
             return Err(ConfigError::PrivateKeyNotSet);
         }
+        if !is_chunk_size_valid(config.chunk_size as u64) {
+            return Err(ConfigError::IncoherentPersistentConfig);
+        }
+        self.chunk_size = config.chunk_size as u64;
+        self.cipher = config.cipher;
         for private_key in &self.private_keys {
             match retrieve_key(&config.multi_recipient, private_key) {
-                Ok(Some(key)) => {
-                    self.encrypt_parameters = Some((key, config.nonce));
+                Ok(Some(mut key)) => {
+                    self.encrypt_parameters = Some((Locked::new(key), config.nonce));
+                    key.zeroize();
                     break;
                 }
                 _ => {
@@ -167,37 +546,148 @@ impl EncryptionReaderConfig {
             };
         }
 
+        if self.encrypt_parameters.is_none() {
+            for provider in &self.key_providers {
+                if let Ok(Some(mut key)) =
+                    retrieve_key_with_provider(&config.multi_recipient, provider.as_ref())
+                {
+                    self.encrypt_parameters = Some((Locked::new(key), config.nonce));
+                    key.zeroize();
+                    break;
+                }
+            }
+        }
+
+        if self.encrypt_parameters.is_none() {
+            if let Some(persist) = &config.hybrid {
+                for private_key in &self.hybrid_private_keys {
+                    if let Ok(Some(mut key)) = retrieve_key_with_hybrid(persist, private_key) {
+                        self.encrypt_parameters = Some((Locked::new(key), config.nonce));
+                        key.zeroize();
+                        break;
+                    }
+                }
+            }
+        }
+
+        if self.encrypt_parameters.is_none() {
+            if let Some(password) = &self.password {
+                let key = match &config.password {
+                    Some(persist) => retrieve_key_with_password(persist, password.as_bytes())?,
+                    None => None,
+                };
+                match key {
+                    Some(mut key) => {
+                        self.encrypt_parameters = Some((Locked::new(key), config.nonce));
+                        key.zeroize();
+                    }
+                    None if !self.has_private_keys() => {
+                        return Err(ConfigError::WrongPassword);
+                    }
+                    None => {}
+                }
+            }
+        }
+
         if self.encrypt_parameters.is_none() {
             return Err(ConfigError::PrivateKeyNotFound);
         }
         Ok(())
     }
+
+    /// Return whether private keys, of any kind, have been set
+    fn has_private_keys(&self) -> bool {
+        !self.private_keys.is_empty()
+            || !self.hybrid_private_keys.is_empty()
+            || !self.key_providers.is_empty()
+    }
 }
 
 impl ArchiveReaderConfig {
     /// Set private key to use
+    ///
+    /// Keys are copied in locked memory, see `Locked`
     pub fn add_private_keys(&mut self, keys: &[StaticSecret]) -> &mut ArchiveReaderConfig {
-        self.encrypt.private_keys.extend_from_slice(keys);
+        self.encrypt
+            .private_keys
+            .extend(keys.iter().map(|key| Locked::new(key.clone())));
         self
     }
 
+    /// Set hybrid private key to use, see
+    /// `ArchiveWriterConfig::add_hybrid_public_keys`
+    ///
+    /// Keys are copied in locked memory, see `Locked`
+    pub fn add_hybrid_private_keys(
+        &mut self,
+        keys: &[HybridPrivateKey],
+    ) -> &mut ArchiveReaderConfig {
+        self.encrypt
+            .hybrid_private_keys
+            .extend(keys.iter().map(|key| Locked::new(key.clone())));
+        self
+    }
+
+    /// Add a private key kept outside of MLA, such as on a hardware token.
+    /// It is tried after the private keys given with `add_private_keys`
+    pub fn add_private_key_provider<P: 'static + PrivateKeyProvider>(
+        &mut self,
+        provider: P,
+    ) -> &mut ArchiveReaderConfig {
+        self.encrypt.key_providers.push(Box::new(provider));
+        self
+    }
+
+    /// Use `password` to decrypt the archive, if none of the private keys
+    /// matches
+    pub fn with_password(&mut self, password: &str) -> &mut ArchiveReaderConfig {
+        self.encrypt.password = Some(Zeroizing::new(password.to_string()));
+        self
+    }
+
+    /// Return the size of encrypted chunks, as read from the header
+    pub fn encryption_chunk_size(&self) -> u32 {
+        self.encrypt.chunk_size as u32
+    }
+
+    /// Return the cipher used to encrypt chunks, as read from the header
+    pub fn encryption_cipher(&self) -> EncryptionCipher {
+        self.encrypt.cipher
+    }
+
     /// Retrieve key and nonce used for encryption
     pub fn get_encrypt_parameters(&self) -> Option<([u8; KEY_SIZE], [u8; NONCE_SIZE])> {
-        self.encrypt.encrypt_parameters
+        self.encrypt
+            .encrypt_parameters
+            .as_ref()
+            .map(|(key, nonce)| (**key, *nonce))
     }
 }
 
 // ---------- Writer ----------
 
+/// Encryption state of the current chunk
+#[allow(clippy::large_enum_variant)]
+enum ChunkEncryptor {
+    /// Data is encrypted on the fly
+    Streaming(AesGcm256),
+    /// Data is kept until the chunk is complete, as AES-GCM-SIV needs the whole
+    /// plaintext to compute its synthetic IV
+    Buffered(Vec<u8>),
+}
+
 pub struct EncryptionLayerWriter<'a, W: 'a + Write> {
     inner: Box<dyn 'a + LayerWriter<'a, W>>,
-    cipher: AesGcm256,
+    encryptor: ChunkEncryptor,
     /// Symmetric encryption Key
-    key: [u8; KEY_SIZE],
+    key: Locked<[u8; KEY_SIZE]>,
     /// Symmetric encryption nonce prefix, see `build_nonce`
     nonce_prefix: [u8; NONCE_SIZE],
     current_chunk_offset: u64,
     current_ctr: u32,
+    /// Size of the encrypted chunks, without their tag
+    chunk_size: u64,
+    cipher: EncryptionCipher,
 }
 
 impl<'a, W: 'a + Write> EncryptionLayerWriter<'a, W> {
@@ -207,25 +697,83 @@ impl<'a, W: 'a + Write> EncryptionLayerWriter<'a, W> {
     ) -> Result<Self, Error> {
         Ok(Self {
             inner,
-            key: config.key,
+            key: config.key.clone(),
             nonce_prefix: config.nonce,
-            cipher: AesGcm256::new(&config.key, &build_nonce(config.nonce, 0), b"")?,
+            encryptor: Self::new_encryptor(config.cipher, &config.key, config.nonce, 0)?,
             current_chunk_offset: 0,
             current_ctr: 0,
+            chunk_size: config.chunk_size,
+            cipher: config.cipher,
         })
     }
 
-    fn renew_cipher(&mut self) -> Result<Tag, Error> {
-        // Prepare a new cipher
-        self.current_ctr += 1;
-        self.current_chunk_offset = 0;
-        let cipher = AesGcm256::new(
+    /// Resume an existing stream at the start of chunk `ctr`, using the key
+    /// and nonce retrieved from its header
+    ///
+    /// Chunks from `ctr` are encrypted again with the nonces they already
+    /// used, which is only acceptable with `EncryptionCipher::AesGcmSiv256`
+    pub fn resume(
+        inner: Box<dyn 'a + LayerWriter<'a, W>>,
+        config: &EncryptionReaderConfig,
+        ctr: u32,
+    ) -> Result<Self, Error> {
+        match &config.encrypt_parameters {
+            Some((key, nonce)) => Ok(Self {
+                inner,
+                key: key.clone(),
+                nonce_prefix: *nonce,
+                encryptor: Self::new_encryptor(config.cipher, key, *nonce, ctr)?,
+                current_chunk_offset: 0,
+                current_ctr: ctr,
+                chunk_size: config.chunk_size,
+                cipher: config.cipher,
+            }),
+            None => Err(Error::PrivateKeyNeeded),
+        }
+    }
+
+    fn new_encryptor(
+        cipher: EncryptionCipher,
+        key: &[u8; KEY_SIZE],
+        nonce_prefix: [u8; NONCE_SIZE],
+        ctr: u32,
+    ) -> Result<ChunkEncryptor, Error> {
+        Ok(match cipher {
+            EncryptionCipher::AesGcm256 => ChunkEncryptor::Streaming(AesGcm256::new(
+                key,
+                &build_nonce(nonce_prefix, ctr),
+                b"",
+            )?),
+            EncryptionCipher::AesGcmSiv256 => ChunkEncryptor::Buffered(Vec::new()),
+        })
+    }
+
+    /// Write the end of the current chunk (remaining data, if buffered, and
+    /// tag), and prepare the next one
+    fn end_chunk(&mut self) -> Result<(), Error> {
+        let encryptor = Self::new_encryptor(
+            self.cipher,
             &self.key,
-            &build_nonce(self.nonce_prefix, self.current_ctr),
-            b"",
+            self.nonce_prefix,
+            self.current_ctr + 1,
         )?;
-        let old_cipher = std::mem::replace(&mut self.cipher, cipher);
-        Ok(old_cipher.into_tag())
+        match std::mem::replace(&mut self.encryptor, encryptor) {
+            ChunkEncryptor::Streaming(cipher) => {
+                self.inner.write_all(&cipher.into_tag())?;
+            }
+            ChunkEncryptor::Buffered(mut data) => {
+                let tag = aesgcmsiv::encrypt(
+                    &*self.key,
+                    &build_nonce(self.nonce_prefix, self.current_ctr),
+                    &mut data,
+                )?;
+                self.inner.write_all(&data)?;
+                self.inner.write_all(&tag)?;
+            }
+        }
+        self.current_ctr += 1;
+        self.current_chunk_offset = 0;
+        Ok(())
     }
 }
 
@@ -239,9 +787,8 @@ impl<'a, W: 'a + Write> LayerWriter<'a, W> for EncryptionLayerWriter<'a, W> {
     }
 
     fn finalize(&mut self) -> Result<(), Error> {
-        // Write the tag of the current chunk
-        let tag = self.renew_cipher()?;
-        self.inner.write_all(&tag)?;
+        // Write the end of the current chunk
+        self.end_chunk()?;
 
         // Recursive call
         self.inner.finalize()
@@ -251,28 +798,33 @@ impl<'a, W: 'a + Write> LayerWriter<'a, W> for EncryptionLayerWriter<'a, W> {
 impl<'a, W: Write> Write for EncryptionLayerWriter<'a, W> {
     #[allow(clippy::comparison_chain)]
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        if self.current_chunk_offset > CHUNK_SIZE {
+        if self.current_chunk_offset > self.chunk_size {
             // Should never happen
             return Err(
                 Error::WrongWriterState("[EncryptWriter] Chunk too big".to_string()).into(),
             );
-        } else if self.current_chunk_offset == CHUNK_SIZE {
-            // Prepare a new cipher
-            let tag = self.renew_cipher()?;
-            // Write the previous chunk tag
-            self.inner.write_all(&tag)?;
+        } else if self.current_chunk_offset == self.chunk_size {
+            // Write the end of the previous chunk, and prepare a new one
+            self.end_chunk()?;
         }
 
-        // StreamingCipher is working in place, so we use a temporary buffer
         let size = std::cmp::min(
             std::cmp::min(CIPHER_BUF_SIZE, buf.len() as u64),
-            CHUNK_SIZE - self.current_chunk_offset,
+            self.chunk_size - self.current_chunk_offset,
         );
-        let mut buf_tmp = Vec::with_capacity(size as usize);
-        let buf_src = BufReader::new(buf);
-        io::copy(&mut buf_src.take(size), &mut buf_tmp)?;
-        self.cipher.encrypt(&mut buf_tmp);
-        self.inner.write_all(&buf_tmp)?;
+        match &mut self.encryptor {
+            ChunkEncryptor::Streaming(cipher) => {
+                // StreamingCipher is working in place, so we use a temporary buffer
+                let mut buf_tmp = Vec::with_capacity(size as usize);
+                let buf_src = BufReader::new(buf);
+                io::copy(&mut buf_src.take(size), &mut buf_tmp)?;
+                cipher.encrypt(&mut buf_tmp);
+                self.inner.write_all(&buf_tmp)?;
+            }
+            ChunkEncryptor::Buffered(data) => {
+                data.extend_from_slice(&buf[..size as usize]);
+            }
+        }
         self.current_chunk_offset += size;
         Ok(size as usize)
     }
@@ -288,11 +840,13 @@ impl<'a, W: Write> Write for EncryptionLayerWriter<'a, W> {
 // keep the struct separated for any possible future difference
 pub struct EncryptionLayerReader<'a, R: Read + Seek> {
     inner: Box<dyn 'a + LayerReader<'a, R>>,
-    cipher: AesGcm256,
-    key: [u8; KEY_SIZE],
+    key: Locked<[u8; KEY_SIZE]>,
     nonce: [u8; NONCE_SIZE],
     chunk_cache: Cursor<Vec<u8>>,
     current_chunk_number: u32,
+    /// Size of the encrypted chunks, without their tag
+    chunk_size: u64,
+    cipher: EncryptionCipher,
 }
 
 impl<'a, R: 'a + Read + Seek> EncryptionLayerReader<'a, R> {
@@ -300,14 +854,15 @@ impl<'a, R: 'a + Read + Seek> EncryptionLayerReader<'a, R> {
         inner: Box<dyn 'a + LayerReader<'a, R>>,
         config: &EncryptionReaderConfig,
     ) -> Result<Self, Error> {
-        match config.encrypt_parameters {
+        match &config.encrypt_parameters {
             Some((key, nonce)) => Ok(Self {
                 inner,
-                cipher: AesGcm256::new(&key, &build_nonce(nonce, 0), b"")?,
-                key,
-                nonce,
-                chunk_cache: Cursor::new(Vec::with_capacity(CHUNK_SIZE as usize)),
+                key: key.clone(),
+                nonce: *nonce,
+                chunk_cache: Cursor::new(Vec::with_capacity(config.chunk_size as usize)),
                 current_chunk_number: 0,
+                chunk_size: config.chunk_size,
+                cipher: config.cipher,
             }),
             None => Err(Error::PrivateKeyNeeded),
         }
@@ -316,19 +871,13 @@ impl<'a, R: 'a + Read + Seek> EncryptionLayerReader<'a, R> {
     /// Load the `self.current_chunk_number` chunk in cache
     /// Assume the inner layer is in the correct position
     fn load_in_cache(&mut self) -> Result<Option<()>, Error> {
-        self.cipher = AesGcm256::new(
-            &self.key,
-            &build_nonce(self.nonce, self.current_chunk_number),
-            b"",
-        )?;
-
         // Clear current, now useless, allocated memory
         self.chunk_cache.get_mut().clear();
 
         // Load the current encrypted chunk and the corresponding tag in memory
-        let mut data_and_tag = Vec::with_capacity(CHUNK_SIZE as usize + TAG_LENGTH);
+        let mut data_and_tag = Vec::with_capacity(self.chunk_size as usize + TAG_LENGTH);
         let data_and_tag_read = (&mut self.inner)
-            .take(CHUNK_SIZE + TAG_LENGTH as u64)
+            .take(self.chunk_size + TAG_LENGTH as u64)
             .read_to_end(&mut data_and_tag)?;
         // If the inner is at the end of the stream, we cannot read any
         // additional byte -> we must stop
@@ -336,7 +885,7 @@ impl<'a, R: 'a + Read + Seek> EncryptionLayerReader<'a, R> {
             return Ok(None);
         }
 
-        // If it is the last block, we may have read less than `CHUNK_SIZE +
+        // If it is the last block, we may have read less than `chunk_size +
         // TAG_LENGTH` bytes. But the `TAG_LENGTH` last bytes are always the tag
         // bytes -> extract it
         let mut tag = [0u8; TAG_LENGTH];
@@ -345,13 +894,21 @@ impl<'a, R: 'a + Read + Seek> EncryptionLayerReader<'a, R> {
         let mut data = data_and_tag;
 
         // Decrypt and verify the current chunk
-        let expected_tag = self.cipher.decrypt(data.as_mut_slice());
-        if expected_tag.ct_eq(&tag).unwrap_u8() != 1 {
-            Err(Error::AuthenticatedDecryptionWrongTag)
-        } else {
-            self.chunk_cache = Cursor::new(data);
-            Ok(Some(()))
-        }
+        decrypt_chunk(
+            self.cipher,
+            &self.key,
+            &build_nonce(self.nonce, self.current_chunk_number),
+            &mut data,
+            &tag,
+        )
+        .map_err(|err| {
+            err.with_layer(
+                "encryption",
+                u64::from(self.current_chunk_number) * self.chunk_size,
+            )
+        })?;
+        self.chunk_cache = Cursor::new(data);
+        Ok(Some(()))
     }
 }
 
@@ -376,7 +933,7 @@ impl<'a, R: 'a + Read + Seek> LayerReader<'a, R> for EncryptionLayerReader<'a, R
 
 impl<'a, R: 'a + Read + Seek> Read for EncryptionLayerReader<'a, R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let cache_to_consume = CHUNK_SIZE - self.chunk_cache.position();
+        let cache_to_consume = self.chunk_size - self.chunk_cache.position();
         if cache_to_consume == 0 {
             // Cache totally consumed, renew it
             self.current_chunk_number += 1;
@@ -392,32 +949,56 @@ impl<'a, R: 'a + Read + Seek> Read for EncryptionLayerReader<'a, R> {
     }
 }
 
-// Returns how many chunk are present at position `position`
-const CHUNK_TAG_SIZE: u64 = CHUNK_SIZE + TAG_LENGTH as u64;
+/// Decrypt and authenticate a whole chunk, in place
+fn decrypt_chunk(
+    cipher: EncryptionCipher,
+    key: &[u8; KEY_SIZE],
+    nonce: &Nonce,
+    data: &mut [u8],
+    tag: &[u8; TAG_LENGTH],
+) -> Result<(), Error> {
+    match cipher {
+        EncryptionCipher::AesGcm256 => {
+            let expected_tag = AesGcm256::new(key, nonce, b"")?.decrypt(data);
+            if expected_tag.ct_eq(tag).unwrap_u8() != 1 {
+                return Err(Error::AuthenticatedDecryptionWrongTag);
+            }
+            Ok(())
+        }
+        EncryptionCipher::AesGcmSiv256 => aesgcmsiv::decrypt(key, nonce, data, tag),
+    }
+}
+
+// Size of a chunk, including its tag
+fn chunk_tag_size(chunk_size: u64) -> u64 {
+    chunk_size + TAG_LENGTH as u64
+}
 
-fn no_tag_position_to_tag_position(position: u64) -> u64 {
-    let cur_chunk = position / CHUNK_SIZE;
-    let cur_chunk_pos = position % CHUNK_SIZE;
-    cur_chunk * CHUNK_TAG_SIZE + cur_chunk_pos
+fn no_tag_position_to_tag_position(position: u64, chunk_size: u64) -> u64 {
+    let cur_chunk = position / chunk_size;
+    let cur_chunk_pos = position % chunk_size;
+    cur_chunk * chunk_tag_size(chunk_size) + cur_chunk_pos
 }
 
-fn tag_position_to_no_tag_position(position: u64) -> u64 {
+fn tag_position_to_no_tag_position(position: u64, chunk_size: u64) -> u64 {
     // Assume the position is not inside a tag. If so, round to the end of the
     // current chunk
-    let cur_chunk = position / CHUNK_TAG_SIZE;
-    let cur_chunk_pos = position % CHUNK_TAG_SIZE;
-    cur_chunk * CHUNK_SIZE + std::cmp::min(cur_chunk_pos, CHUNK_SIZE)
+    let cur_chunk = position / chunk_tag_size(chunk_size);
+    let cur_chunk_pos = position % chunk_tag_size(chunk_size);
+    cur_chunk * chunk_size + std::cmp::min(cur_chunk_pos, chunk_size)
 }
 
 impl<'a, R: 'a + Read + Seek> Seek for EncryptionLayerReader<'a, R> {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
         // `pos` is the position without considering tags
+        let chunk_size = self.chunk_size;
+        let chunk_tag_size = chunk_tag_size(chunk_size);
         match pos {
             SeekFrom::Start(pos) => {
-                let tag_position = no_tag_position_to_tag_position(pos);
-                let chunk_number = tag_position / CHUNK_TAG_SIZE;
-                let pos_chunk_start = chunk_number * CHUNK_TAG_SIZE;
-                let pos_in_chunk = tag_position % CHUNK_TAG_SIZE;
+                let tag_position = no_tag_position_to_tag_position(pos, chunk_size);
+                let chunk_number = tag_position / chunk_tag_size;
+                let pos_chunk_start = chunk_number * chunk_tag_size;
+                let pos_in_chunk = tag_position % chunk_tag_size;
 
                 // Seek the inner layer at the beginning of the chunk
                 self.inner.seek(SeekFrom::Start(pos_chunk_start))?;
@@ -430,18 +1011,19 @@ impl<'a, R: 'a + Read + Seek> Seek for EncryptionLayerReader<'a, R> {
             }
             SeekFrom::Current(value) => {
                 // Inner layer is at the start of the next chunk. The last chunk
-                // may not be CHUNK_SIZE long.
-                let current_inner = tag_position_to_no_tag_position(self.inner.seek(pos)?);
+                // may not be `chunk_size` long.
+                let current_inner =
+                    tag_position_to_no_tag_position(self.inner.seek(pos)?, chunk_size);
                 let current_inner_chunk = {
-                    let chunk_nb = current_inner / CHUNK_SIZE;
+                    let chunk_nb = current_inner / chunk_size;
                     if chunk_nb == 0 {
-                        // Only one chunk, witch is not CHUNK_SIZE long
+                        // Only one chunk, witch is not `chunk_size` long
                         0
                     } else {
                         chunk_nb - 1
                     }
                 };
-                let current = current_inner_chunk * CHUNK_SIZE + self.chunk_cache.position();
+                let current = current_inner_chunk * chunk_size + self.chunk_cache.position();
                 if value == 0 {
                     // Optimization
                     Ok(current)
@@ -456,12 +1038,12 @@ impl<'a, R: 'a + Read + Seek> Seek for EncryptionLayerReader<'a, R> {
                 }
 
                 // The last chunk always have a TAG at its end, and might not be
-                // CHUNK_SIZE long -> we need to remove the TAG size while
+                // `chunk_size` long -> we need to remove the TAG size while
                 // converting from tag-aware position to tag-unaware position
                 let end_inner_pos = self.inner.seek(SeekFrom::End(0))?;
-                let cur_chunk = end_inner_pos / CHUNK_TAG_SIZE;
-                let cur_chunk_pos = end_inner_pos % CHUNK_TAG_SIZE;
-                let end_pos = cur_chunk * CHUNK_SIZE + cur_chunk_pos - TAG_LENGTH as u64;
+                let cur_chunk = end_inner_pos / chunk_tag_size;
+                let cur_chunk_pos = end_inner_pos % chunk_tag_size;
+                let end_pos = cur_chunk * chunk_size + cur_chunk_pos - TAG_LENGTH as u64;
                 self.seek(SeekFrom::Start((pos + end_pos as i64) as u64))
             }
         }
@@ -472,11 +1054,17 @@ impl<'a, R: 'a + Read + Seek> Seek for EncryptionLayerReader<'a, R> {
 
 pub struct EncryptionLayerFailSafeReader<'a, R: Read> {
     inner: Box<dyn 'a + LayerFailSafeReader<'a, R>>,
-    cipher: AesGcm256,
-    key: [u8; KEY_SIZE],
+    cipher: EncryptionCipher,
+    key: Locked<[u8; KEY_SIZE]>,
     nonce: [u8; NONCE_SIZE],
     current_chunk_number: u32,
-    current_chunk_offset: u64,
+    /// Size of the encrypted chunks, without their tag
+    chunk_size: u64,
+    /// Decrypted chunk being read. Chunks are loaded entirely, including their
+    /// tag, to be authenticated before being returned
+    chunk_cache: Cursor<Vec<u8>>,
+    /// Set once a chunk has been returned without being authenticated
+    unauthenticated: Arc<AtomicBool>,
 }
 
 impl<'a, R: 'a + Read> EncryptionLayerFailSafeReader<'a, R> {
@@ -484,18 +1072,27 @@ impl<'a, R: 'a + Read> EncryptionLayerFailSafeReader<'a, R> {
         inner: Box<dyn 'a + LayerFailSafeReader<'a, R>>,
         config: &EncryptionReaderConfig,
     ) -> Result<Self, Error> {
-        match config.encrypt_parameters {
+        match &config.encrypt_parameters {
             Some((key, nonce)) => Ok(Self {
                 inner,
-                cipher: AesGcm256::new(&key, &build_nonce(nonce, 0), b"")?,
-                key,
-                nonce,
+                cipher: config.cipher,
+                key: key.clone(),
+                nonce: *nonce,
                 current_chunk_number: 0,
-                current_chunk_offset: 0,
+                chunk_size: config.chunk_size,
+                chunk_cache: Cursor::new(Vec::new()),
+                unauthenticated: Arc::new(AtomicBool::new(false)),
             }),
             None => Err(Error::PrivateKeyNeeded),
         }
     }
+
+    /// Flag set once data has been returned without being authenticated,
+    /// ie. from a truncated chunk. It remains readable once `self` has been
+    /// boxed into a layer stack
+    pub fn unauthenticated_flag(&self) -> Arc<AtomicBool> {
+        self.unauthenticated.clone()
+    }
 }
 
 impl<'a, R: 'a + Read> LayerFailSafeReader<'a, R> for EncryptionLayerFailSafeReader<'a, R> {
@@ -508,34 +1105,63 @@ impl<'a, R: 'a + Read> LayerFailSafeReader<'a, R> for EncryptionLayerFailSafeRea
     }
 }
 
+impl<'a, R: Read> EncryptionLayerFailSafeReader<'a, R> {
+    /// Load and decrypt the next chunk
+    ///
+    /// Complete chunks must be authenticated. A shorter chunk is either the
+    /// last one, or one truncated while being written: if it fails to
+    /// authenticate, it is considered truncated, and all its bytes are
+    /// decrypted without authentication (AES-GCM only; AES-GCM-SIV needs the
+    /// tag to decrypt, so a truncated chunk is lost). Bytes past `chunk_size`
+    /// are then part of a truncated tag, and are dropped
+    fn load_chunk(&mut self) -> io::Result<Vec<u8>> {
+        let mut data = Vec::with_capacity(chunk_tag_size(self.chunk_size) as usize);
+        let read = (&mut self.inner)
+            .take(chunk_tag_size(self.chunk_size))
+            .read_to_end(&mut data)?;
+        if read == 0 {
+            return Ok(data);
+        }
+        let nonce = build_nonce(self.nonce, self.current_chunk_number);
+        let is_complete = read as u64 == chunk_tag_size(self.chunk_size);
+        // Keep the encrypted content of a shorter chunk, as AES-GCM decrypts in
+        // place even if the authentication fails
+        let encrypted = if is_complete {
+            None
+        } else {
+            Some(data[..std::cmp::min(read as u64, self.chunk_size) as usize].to_vec())
+        };
+
+        let authenticated = if read < TAG_LENGTH {
+            Err(Error::AuthenticatedDecryptionWrongTag)
+        } else {
+            let mut tag = [0u8; TAG_LENGTH];
+            tag.copy_from_slice(&data[read - TAG_LENGTH..]);
+            data.truncate(read - TAG_LENGTH);
+            decrypt_chunk(self.cipher, &self.key, &nonce, &mut data, &tag)
+        };
+        match (authenticated, encrypted, self.cipher) {
+            (Ok(()), _, _) => {}
+            (Err(_), Some(mut encrypted), EncryptionCipher::AesGcm256) => {
+                // Truncated chunk: recover what can be
+                AesGcm256::new(&*self.key, &nonce, b"")?.decrypt_unauthenticated(&mut encrypted);
+                data = encrypted;
+                self.unauthenticated.store(true, Ordering::SeqCst);
+            }
+            (Err(err), _, _) => return Err(err.into()),
+        }
+        self.current_chunk_number += 1;
+        Ok(data)
+    }
+}
+
 impl<'a, R: Read> Read for EncryptionLayerFailSafeReader<'a, R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        if self.current_chunk_offset == CHUNK_SIZE {
-            // Ignore the tag and renew the cipher
-            io::copy(
-                &mut (&mut self.inner).take(TAG_LENGTH as u64),
-                &mut io::sink(),
-            )?;
-            self.current_chunk_number += 1;
-            self.current_chunk_offset = 0;
-            self.cipher = AesGcm256::new(
-                &self.key,
-                &build_nonce(self.nonce, self.current_chunk_number),
-                b"",
-            )?;
-            return self.read(buf);
+        if self.chunk_cache.position() == self.chunk_cache.get_ref().len() as u64 {
+            let data = self.load_chunk()?;
+            self.chunk_cache = Cursor::new(data);
         }
-
-        // AesGcm256 is working in place, so we use a temporary buffer
-        let mut buf_tmp = [0u8; CIPHER_BUF_SIZE as usize];
-        let size = std::cmp::min(CIPHER_BUF_SIZE as usize, buf.len());
-        // Read at most the chunk size, to detect when renewal is needed
-        let size = std::cmp::min((CHUNK_SIZE - self.current_chunk_offset) as usize, size);
-        let len = self.inner.read(&mut buf_tmp[..size])?;
-        self.current_chunk_offset += len as u64;
-        self.cipher.decrypt_unauthenticated(&mut buf_tmp[..len]);
-        (&buf_tmp[..len]).read_exact(&mut buf[..len])?;
-        Ok(len)
+        self.chunk_cache.read(buf)
     }
 }
 
@@ -546,6 +1172,7 @@ mod tests {
     use rand::distributions::{Alphanumeric, Distribution};
     use rand::rngs::StdRng;
     use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
     use std::io::{Cursor, Read, Seek, SeekFrom, Write};
 
     use crate::layers::raw::{RawLayerFailSafeReader, RawLayerReader, RawLayerWriter};
@@ -560,9 +1187,13 @@ mod tests {
             EncryptionLayerWriter::new(
                 Box::new(RawLayerWriter::new(file)),
                 &EncryptionConfig {
-                    ecc_keys: Vec::new(),
-                    key: KEY,
+                    ecc_keys: RecipientSet::default(),
+                    key: Locked::new(KEY),
                     nonce: NONCE,
+                    chunk_size: CHUNK_SIZE,
+                    cipher: EncryptionCipher::AesGcm256,
+                    rng: None,
+                    ..Default::default()
                 },
             )
             .unwrap(),
@@ -585,7 +1216,10 @@ mod tests {
         let buf = Cursor::new(out.as_slice());
         let config = EncryptionReaderConfig {
             private_keys: Vec::new(),
-            encrypt_parameters: Some((KEY, NONCE)),
+            encrypt_parameters: Some((Locked::new(KEY), NONCE)),
+            chunk_size: CHUNK_SIZE,
+            cipher: EncryptionCipher::AesGcm256,
+            ..Default::default()
         };
         let mut encrypt_r =
             EncryptionLayerReader::new(Box::new(RawLayerReader::new(buf)), &config).unwrap();
@@ -602,7 +1236,10 @@ mod tests {
 
         let config = EncryptionReaderConfig {
             private_keys: Vec::new(),
-            encrypt_parameters: Some((KEY, NONCE)),
+            encrypt_parameters: Some((Locked::new(KEY), NONCE)),
+            chunk_size: CHUNK_SIZE,
+            cipher: EncryptionCipher::AesGcm256,
+            ..Default::default()
         };
         let mut encrypt_r = EncryptionLayerFailSafeReader::new(
             Box::new(RawLayerFailSafeReader::new(out.as_slice())),
@@ -611,8 +1248,8 @@ mod tests {
         .unwrap();
         let mut output = Vec::new();
         encrypt_r.read_to_end(&mut output).unwrap();
-        // Extra output expected, due to the ignored tag in the last chunk
-        assert_eq!(output[..FAKE_FILE.len()], FAKE_FILE);
+        // The last chunk is authenticated, its tag being removed
+        assert_eq!(output, FAKE_FILE);
     }
 
     #[test]
@@ -625,7 +1262,10 @@ mod tests {
 
         let config = EncryptionReaderConfig {
             private_keys: Vec::new(),
-            encrypt_parameters: Some((KEY, NONCE)),
+            encrypt_parameters: Some((Locked::new(KEY), NONCE)),
+            chunk_size: CHUNK_SIZE,
+            cipher: EncryptionCipher::AesGcm256,
+            ..Default::default()
         };
         let mut encrypt_r = EncryptionLayerFailSafeReader::new(
             Box::new(RawLayerFailSafeReader::new(&out[..stop])),
@@ -636,6 +1276,29 @@ mod tests {
         encrypt_r.read_to_end(&mut output).unwrap();
         // Thanks to the encrypt layer construction, we can recover `stop` bytes
         assert_eq!(output.as_slice(), &FAKE_FILE[..stop]);
+        assert!(encrypt_r.unauthenticated_flag().load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn recipient_set() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key1 = PublicKey::from(&StaticSecret::new(&mut rng));
+        let key2 = PublicKey::from(&StaticSecret::new(&mut rng));
+
+        // Duplicates are ignored
+        let set = RecipientSet::new(&[key1, key2, key1]);
+        assert_eq!(set.len(), 2);
+
+        // Extending a shared set does not modify the other owners
+        let mut set2 = set.clone();
+        set2.extend(&[PublicKey::from(&StaticSecret::new(&mut rng))]);
+        assert_eq!(set.len(), 2);
+        assert_eq!(set2.len(), 3);
+
+        let mut config = ArchiveWriterConfig::new();
+        config.set_recipients(&set);
+        assert_eq!(config.encrypt.ecc_keys.len(), 2);
+        assert!(config.encrypt.check().is_ok());
     }
 
     #[test]
@@ -648,7 +1311,10 @@ mod tests {
         let buf = Cursor::new(out.as_slice());
         let config = EncryptionReaderConfig {
             private_keys: Vec::new(),
-            encrypt_parameters: Some((KEY, NONCE)),
+            encrypt_parameters: Some((Locked::new(KEY), NONCE)),
+            chunk_size: CHUNK_SIZE,
+            cipher: EncryptionCipher::AesGcm256,
+            ..Default::default()
         };
         let mut encrypt_r =
             EncryptionLayerReader::new(Box::new(RawLayerReader::new(buf)), &config).unwrap();
@@ -658,9 +1324,12 @@ mod tests {
         assert_eq!(output, FAKE_FILE);
 
         // Seek and decrypt twice the same thing
-        let pos = encrypt_r.seek(SeekFrom::Current(0)).unwrap();
+        let pos = encrypt_r.stream_position().unwrap();
         // test the current position retrievial
-        assert_eq!(pos, tag_position_to_no_tag_position(FAKE_FILE.len() as u64));
+        assert_eq!(
+            pos,
+            tag_position_to_no_tag_position(FAKE_FILE.len() as u64, CHUNK_SIZE)
+        );
         // decrypt twice the same thing, with an offset
         let pos = encrypt_r.seek(SeekFrom::Start(5)).unwrap();
         assert_eq!(pos, 5);
@@ -680,9 +1349,13 @@ mod tests {
             EncryptionLayerWriter::new(
                 Box::new(RawLayerWriter::new(file)),
                 &EncryptionConfig {
-                    ecc_keys: Vec::new(),
-                    key: KEY,
+                    ecc_keys: RecipientSet::default(),
+                    key: Locked::new(KEY),
                     nonce: NONCE,
+                    chunk_size: CHUNK_SIZE,
+                    cipher: EncryptionCipher::AesGcm256,
+                    rng: None,
+                    ..Default::default()
                 },
             )
             .unwrap(),
@@ -705,7 +1378,10 @@ mod tests {
         let buf = Cursor::new(out.as_slice());
         let config = EncryptionReaderConfig {
             private_keys: Vec::new(),
-            encrypt_parameters: Some((KEY, NONCE)),
+            encrypt_parameters: Some((Locked::new(KEY), NONCE)),
+            chunk_size: CHUNK_SIZE,
+            cipher: EncryptionCipher::AesGcm256,
+            ..Default::default()
         };
         let mut encrypt_r =
             EncryptionLayerReader::new(Box::new(RawLayerReader::new(buf)), &config).unwrap();
@@ -721,4 +1397,175 @@ mod tests {
         encrypt_r.read_to_end(&mut output).unwrap();
         assert_eq!(output.as_slice(), &data[CHUNK_SIZE as usize..]);
     }
+
+    #[test]
+    fn encrypt_custom_chunk_size() {
+        let mut config = ArchiveWriterConfig::new();
+        assert!(config.with_encryption_chunk_size(0).is_err());
+        config
+            .with_encryption_chunk_size(MIN_CHUNK_SIZE as u32)
+            .unwrap();
+        let chunk_size = config.encrypt.chunk_size;
+
+        let mut encrypt_w = Box::new(
+            EncryptionLayerWriter::new(Box::new(RawLayerWriter::new(Vec::new())), &config.encrypt)
+                .unwrap(),
+        );
+        let length = (chunk_size * 3 + 5) as usize;
+        let mut rng: StdRng = SeedableRng::from_seed([0u8; 32]);
+        let data: Vec<u8> = Alphanumeric
+            .sample_iter(&mut rng)
+            .take(length)
+            .map(|c| c as u8)
+            .collect();
+        encrypt_w.write_all(&data).unwrap();
+        encrypt_w.finalize().unwrap();
+        let out = encrypt_w.into_raw();
+        assert_eq!(out.len(), length + 4 * TAG_LENGTH);
+
+        let reader_config = EncryptionReaderConfig {
+            private_keys: Vec::new(),
+            encrypt_parameters: Some((config.encrypt.key.clone(), config.encrypt.nonce)),
+            chunk_size,
+            cipher: config.encrypt.cipher,
+            ..Default::default()
+        };
+        let mut encrypt_r = EncryptionLayerReader::new(
+            Box::new(RawLayerReader::new(Cursor::new(out.as_slice()))),
+            &reader_config,
+        )
+        .unwrap();
+        encrypt_r.initialize().unwrap();
+        let mut output = Vec::new();
+        encrypt_r.read_to_end(&mut output).unwrap();
+        assert_eq!(output, data);
+
+        let pos = encrypt_r.seek(SeekFrom::Start(chunk_size * 2 + 3)).unwrap();
+        let mut output = Vec::new();
+        encrypt_r.read_to_end(&mut output).unwrap();
+        assert_eq!(output.as_slice(), &data[pos as usize..]);
+
+        let mut encrypt_r = EncryptionLayerFailSafeReader::new(
+            Box::new(RawLayerFailSafeReader::new(out.as_slice())),
+            &reader_config,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        encrypt_r.read_to_end(&mut output).unwrap();
+        assert_eq!(output, data);
+
+        // Fail-safe: the truncated chunk is recovered, without authentication
+        let chunk_tag = chunk_size as usize + TAG_LENGTH;
+        let stop = chunk_tag + chunk_size as usize / 2;
+        let mut encrypt_r = EncryptionLayerFailSafeReader::new(
+            Box::new(RawLayerFailSafeReader::new(&out[..stop])),
+            &reader_config,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        encrypt_r.read_to_end(&mut output).unwrap();
+        assert_eq!(output.as_slice(), &data[..stop - TAG_LENGTH]);
+        assert!(encrypt_r.unauthenticated_flag().load(Ordering::SeqCst));
+
+        // Fail-safe: truncated in the tag, the bytes of the partial tag are
+        // not part of the content
+        let stop = chunk_tag * 2 - TAG_LENGTH / 2;
+        let mut encrypt_r = EncryptionLayerFailSafeReader::new(
+            Box::new(RawLayerFailSafeReader::new(&out[..stop])),
+            &reader_config,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        encrypt_r.read_to_end(&mut output).unwrap();
+        assert_eq!(output.as_slice(), &data[..chunk_size as usize * 2]);
+
+        // Fail-safe: a corrupted complete chunk stops the recovery
+        let mut corrupted = out.clone();
+        corrupted[chunk_tag + 1] ^= 1;
+        let mut encrypt_r = EncryptionLayerFailSafeReader::new(
+            Box::new(RawLayerFailSafeReader::new(corrupted.as_slice())),
+            &reader_config,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        assert!(encrypt_r.read_to_end(&mut output).is_err());
+        assert_eq!(output.as_slice(), &data[..chunk_size as usize]);
+    }
+
+    #[test]
+    fn encrypt_gcm_siv() {
+        let mut config = ArchiveWriterConfig::new();
+        config
+            .with_encryption_chunk_size(MIN_CHUNK_SIZE as u32)
+            .unwrap()
+            .with_encryption_cipher(EncryptionCipher::AesGcmSiv256);
+        assert_eq!(config.encryption_cipher(), EncryptionCipher::AesGcmSiv256);
+        let chunk_size = config.encrypt.chunk_size;
+
+        let mut encrypt_w = Box::new(
+            EncryptionLayerWriter::new(Box::new(RawLayerWriter::new(Vec::new())), &config.encrypt)
+                .unwrap(),
+        );
+        let length = (chunk_size * 2 + 5) as usize;
+        let mut rng: StdRng = SeedableRng::from_seed([0u8; 32]);
+        let data: Vec<u8> = Alphanumeric
+            .sample_iter(&mut rng)
+            .take(length)
+            .map(|c| c as u8)
+            .collect();
+        encrypt_w.write_all(&data).unwrap();
+        encrypt_w.finalize().unwrap();
+        let out = encrypt_w.into_raw();
+        assert_eq!(out.len(), length + 3 * TAG_LENGTH);
+        assert_ne!(&out[..chunk_size as usize], &data[..chunk_size as usize]);
+
+        let reader_config = EncryptionReaderConfig {
+            private_keys: Vec::new(),
+            encrypt_parameters: Some((config.encrypt.key.clone(), config.encrypt.nonce)),
+            chunk_size,
+            cipher: config.encrypt.cipher,
+            ..Default::default()
+        };
+        let mut encrypt_r = EncryptionLayerReader::new(
+            Box::new(RawLayerReader::new(Cursor::new(out.as_slice()))),
+            &reader_config,
+        )
+        .unwrap();
+        encrypt_r.initialize().unwrap();
+        let mut output = Vec::new();
+        encrypt_r.read_to_end(&mut output).unwrap();
+        assert_eq!(output, data);
+
+        let pos = encrypt_r.seek(SeekFrom::Start(chunk_size + 3)).unwrap();
+        let mut output = Vec::new();
+        encrypt_r.read_to_end(&mut output).unwrap();
+        assert_eq!(output.as_slice(), &data[pos as usize..]);
+
+        // Reading with the wrong cipher fails authentication
+        let wrong_config = EncryptionReaderConfig {
+            private_keys: Vec::new(),
+            encrypt_parameters: Some((config.encrypt.key.clone(), config.encrypt.nonce)),
+            chunk_size,
+            cipher: EncryptionCipher::AesGcm256,
+            ..Default::default()
+        };
+        let mut encrypt_r = EncryptionLayerReader::new(
+            Box::new(RawLayerReader::new(Cursor::new(out.as_slice()))),
+            &wrong_config,
+        )
+        .unwrap();
+        assert!(encrypt_r.initialize().is_err());
+
+        // Fail-safe: only complete chunks are recovered
+        let stop = (chunk_size as usize + TAG_LENGTH) * 2 - 1;
+        let mut encrypt_r = EncryptionLayerFailSafeReader::new(
+            Box::new(RawLayerFailSafeReader::new(&out[..stop])),
+            &reader_config,
+        )
+        .unwrap();
+        let mut output = Vec::new();
+        // The truncated chunk fails to authenticate
+        assert!(encrypt_r.read_to_end(&mut output).is_err());
+        assert_eq!(output.as_slice(), &data[..chunk_size as usize]);
+    }
 }
@@ -1,5 +1,9 @@
+pub mod cache;
 pub mod compress;
+pub mod digest;
 pub mod encrypt;
 pub mod position;
+pub mod progress;
 pub mod raw;
+pub mod sign;
 pub mod traits;
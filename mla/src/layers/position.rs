@@ -23,6 +23,11 @@ impl<'a, W: 'a + Write> PositionLayerWriter<'a, W> {
         self.pos
     }
 
+    /// Start counting from `pos`, for instance when resuming an existing stream
+    pub fn set_position(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+
     pub fn reset_position(&mut self) -> u64 {
         let before = self.pos;
         self.pos = 0;
@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::sync::{Arc, Mutex};
+
+use crate::config::{ArchiveReaderConfig, ArchiveWriterConfig};
+use crate::layers::traits::{LayerFailSafeReader, LayerReader, LayerWriter};
+use crate::{ArchiveFileID, Error};
+
+// ---------- Observer ----------
+
+/// Receive the progress of an archive being written or read, for instance to
+/// display a progress bar
+///
+/// Every method does nothing by default. They are called synchronously, from
+/// the thread using the archive, and should therefore return quickly
+pub trait ProgressObserver: Send {
+    /// `count` bytes have been written to the destination, once through
+    /// every layer (compression, encryption, ...)
+    fn bytes_written(&mut self, _count: u64) {}
+
+    /// `count` bytes have been read from the source, before going through the
+    /// layers
+    fn bytes_read(&mut self, _count: u64) {}
+
+    /// The entry `name` starts
+    fn entry_started(&mut self, _name: &str) {}
+
+    /// `count` bytes of the content of the entry `name` have been processed
+    fn entry_data(&mut self, _name: &str, _count: u64) {}
+
+    /// The entry `name` is complete
+    fn entry_completed(&mut self, _name: &str) {}
+}
+
+/// Shared access to the observer, from the layer and from the archive
+/// writer or reader
+#[derive(Clone)]
+pub(crate) struct ProgressHandle {
+    observer: Arc<Mutex<dyn ProgressObserver>>,
+}
+
+impl ProgressHandle {
+    fn new<O: 'static + ProgressObserver>(observer: O) -> Self {
+        Self {
+            observer: Arc::new(Mutex::new(observer)),
+        }
+    }
+
+    /// Call `notification` on the observer
+    pub(crate) fn notify<F: FnOnce(&mut dyn ProgressObserver)>(&self, notification: F) {
+        notification(&mut *self.observer.lock().expect("Progress lock poisoned"));
+    }
+}
+
+/// Entries being processed, to report them by name from their id
+pub(crate) struct EntriesProgress {
+    handle: ProgressHandle,
+    names: HashMap<ArchiveFileID, String>,
+}
+
+impl EntriesProgress {
+    pub(crate) fn new(handle: ProgressHandle) -> Self {
+        Self {
+            handle,
+            names: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn started(&mut self, id: ArchiveFileID, name: &str) {
+        self.handle.notify(|observer| observer.entry_started(name));
+        self.names.insert(id, name.to_string());
+    }
+
+    pub(crate) fn data(&self, id: ArchiveFileID, count: u64) {
+        if let Some(name) = self.names.get(&id) {
+            self.handle
+                .notify(|observer| observer.entry_data(name, count));
+        }
+    }
+
+    pub(crate) fn completed(&mut self, id: ArchiveFileID) {
+        if let Some(name) = self.names.remove(&id) {
+            self.handle
+                .notify(|observer| observer.entry_completed(&name));
+        }
+    }
+}
+
+// ---------- Config ----------
+
+impl ArchiveWriterConfig {
+    /// Notify `observer` of the entries added, and of the bytes written to
+    /// the destination
+    pub fn with_progress_observer<O: 'static + ProgressObserver>(
+        &mut self,
+        observer: O,
+    ) -> &mut ArchiveWriterConfig {
+        self.progress = Some(ProgressHandle::new(observer));
+        self
+    }
+}
+
+impl ArchiveReaderConfig {
+    /// Notify `observer` of the bytes read from the source
+    ///
+    /// Entries are also reported while they are read sequentially, by
+    /// `helpers::linear_extract` and `ArchiveFailSafeReader::convert_to_archive`
+    pub fn with_progress_observer<O: 'static + ProgressObserver>(
+        &mut self,
+        observer: O,
+    ) -> &mut ArchiveReaderConfig {
+        self.progress = Some(ProgressHandle::new(observer));
+        self
+    }
+}
+
+// ---------- Writer ----------
+
+/// Layer reporting the bytes written through it
+///
+/// It does not modify the data, and is not recorded in the archive
+pub struct ProgressLayerWriter<'a, W: 'a + Write> {
+    inner: Box<dyn 'a + LayerWriter<'a, W>>,
+    handle: ProgressHandle,
+}
+
+impl<'a, W: 'a + Write> ProgressLayerWriter<'a, W> {
+    pub(crate) fn new(inner: Box<dyn 'a + LayerWriter<'a, W>>, handle: ProgressHandle) -> Self {
+        Self { inner, handle }
+    }
+}
+
+impl<'a, W: 'a + Write> LayerWriter<'a, W> for ProgressLayerWriter<'a, W> {
+    fn into_inner(self) -> Option<Box<dyn 'a + LayerWriter<'a, W>>> {
+        Some(self.inner)
+    }
+
+    fn into_raw(self: Box<Self>) -> W {
+        self.inner.into_raw()
+    }
+
+    fn finalize(&mut self) -> Result<(), Error> {
+        self.inner.finalize()
+    }
+}
+
+impl<'a, W: 'a + Write> Write for ProgressLayerWriter<'a, W> {
+    /// Wrapper on inner, reporting the bytes actually written
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.handle
+            .notify(|observer| observer.bytes_written(written as u64));
+        Ok(written)
+    }
+
+    /// Wrapper on inner
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// ---------- Reader ----------
+
+/// Layer reporting the bytes read through it
+pub struct ProgressLayerReader<'a, R: 'a + Read + Seek> {
+    inner: Box<dyn 'a + LayerReader<'a, R>>,
+    handle: ProgressHandle,
+}
+
+impl<'a, R: 'a + Read + Seek> ProgressLayerReader<'a, R> {
+    pub(crate) fn new(inner: Box<dyn 'a + LayerReader<'a, R>>, handle: ProgressHandle) -> Self {
+        Self { inner, handle }
+    }
+}
+
+impl<'a, R: 'a + Read + Seek> LayerReader<'a, R> for ProgressLayerReader<'a, R> {
+    fn into_inner(self) -> Option<Box<dyn 'a + LayerReader<'a, R>>> {
+        Some(self.inner)
+    }
+
+    fn into_raw(self: Box<Self>) -> R {
+        self.inner.into_raw()
+    }
+
+    fn initialize(&mut self) -> Result<(), Error> {
+        self.inner.initialize()
+    }
+}
+
+impl<'a, R: 'a + Read + Seek> Seek for ProgressLayerReader<'a, R> {
+    /// Wrapper on inner
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+impl<'a, R: 'a + Read + Seek> Read for ProgressLayerReader<'a, R> {
+    /// Wrapper on inner, reporting the bytes actually read
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(into)?;
+        self.handle
+            .notify(|observer| observer.bytes_read(read as u64));
+        Ok(read)
+    }
+}
+
+// ---------- FailSafeReader ----------
+
+/// Layer reporting the bytes read through it, in fail-safe mode
+pub struct ProgressLayerFailSafeReader<'a, R: 'a + Read> {
+    inner: Box<dyn 'a + LayerFailSafeReader<'a, R>>,
+    handle: ProgressHandle,
+}
+
+impl<'a, R: 'a + Read> ProgressLayerFailSafeReader<'a, R> {
+    pub(crate) fn new(
+        inner: Box<dyn 'a + LayerFailSafeReader<'a, R>>,
+        handle: ProgressHandle,
+    ) -> Self {
+        Self { inner, handle }
+    }
+}
+
+impl<'a, R: 'a + Read> LayerFailSafeReader<'a, R> for ProgressLayerFailSafeReader<'a, R> {
+    fn into_inner(self) -> Option<Box<dyn 'a + LayerFailSafeReader<'a, R>>> {
+        Some(self.inner)
+    }
+
+    fn into_raw(self: Box<Self>) -> R {
+        self.inner.into_raw()
+    }
+}
+
+impl<'a, R: 'a + Read> Read for ProgressLayerFailSafeReader<'a, R> {
+    /// Wrapper on inner, reporting the bytes actually read
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(into)?;
+        self.handle
+            .notify(|observer| observer.bytes_read(read as u64));
+        Ok(read)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::layers::raw::{RawLayerReader, RawLayerWriter};
+    use std::io::Cursor;
+
+    static DATA: [u8; 4] = [1, 2, 3, 4];
+
+    /// Sum the bytes reported, shared with the test
+    #[derive(Clone, Default)]
+    struct Counter(Arc<Mutex<(u64, u64)>>);
+
+    impl ProgressObserver for Counter {
+        fn bytes_written(&mut self, count: u64) {
+            self.0.lock().unwrap().0 += count;
+        }
+
+        fn bytes_read(&mut self, count: u64) {
+            self.0.lock().unwrap().1 += count;
+        }
+    }
+
+    #[test]
+    fn progress_layer() {
+        let counter = Counter::default();
+        let handle = ProgressHandle::new(counter.clone());
+
+        let file = Vec::new();
+        let mut prog_w = Box::new(ProgressLayerWriter::new(
+            Box::new(RawLayerWriter::new(file)),
+            handle.clone(),
+        ));
+        prog_w.write_all(&DATA).unwrap();
+        prog_w.write_all(&DATA).unwrap();
+        prog_w.finalize().unwrap();
+        assert_eq!(counter.0.lock().unwrap().0, DATA.len() as u64 * 2);
+
+        let buf = Cursor::new(prog_w.into_raw());
+        let mut prog_r = Box::new(ProgressLayerReader::new(
+            Box::new(RawLayerReader::new(buf)),
+            handle,
+        ));
+        prog_r.initialize().unwrap();
+        prog_r.seek(SeekFrom::Start(2)).unwrap();
+        let mut output = Vec::new();
+        prog_r.read_to_end(&mut output).unwrap();
+        assert_eq!(output.len(), DATA.len() * 2 - 2);
+        assert_eq!(counter.0.lock().unwrap().1, output.len() as u64);
+    }
+}
@@ -0,0 +1,471 @@
+use std::convert::TryFrom;
+use std::io;
+use std::io::{Read, Seek, SeekFrom, Write};
+
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, PUBLIC_KEY_LENGTH, SIGNATURE_LENGTH};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha512};
+use zeroize::Zeroize;
+
+use crate::config::{ArchiveReaderConfig, ArchiveWriterConfig};
+use crate::errors::ConfigError;
+use crate::layers::cache::checked_add_signed;
+use crate::layers::traits::{LayerFailSafeReader, LayerReader, LayerWriter};
+use crate::{Error, Layers};
+
+/// Prefix of the signed message, followed by the SHA512 of the archive
+const SIGNATURE_CONTEXT: &[u8] = b"MLA signature";
+
+/// Return the message signed for an archive hashing to `hash`
+fn signed_message(hash: &[u8]) -> Vec<u8> {
+    [SIGNATURE_CONTEXT, hash].concat()
+}
+
+/// `Keypair` is not `Clone`
+fn copy_keypair(key: &Keypair) -> Keypair {
+    let mut bytes = key.to_bytes();
+    let copy = Keypair::from_bytes(&bytes).expect("Keypair serialization must be reversible");
+    bytes.zeroize();
+    copy
+}
+
+// ---------- Config ----------
+
+/// Configuration stored in the header, to be reloaded
+#[derive(Serialize, Deserialize)]
+pub struct SignaturePersistentConfig {
+    /// Public keys of the signers, in the order of their signatures
+    signers: Vec<[u8; PUBLIC_KEY_LENGTH]>,
+}
+
+impl SignaturePersistentConfig {
+    /// Number of signers, and so of signatures at the end of the archive
+    pub(crate) fn count(&self) -> usize {
+        self.signers.len()
+    }
+
+    /// Length of the signatures, at the end of the archive
+    pub(crate) fn signatures_length(&self) -> u64 {
+        (self.signers.len() * SIGNATURE_LENGTH) as u64
+    }
+}
+
+#[derive(Default)]
+pub struct SignatureConfig {
+    /// Keys with which to sign the archive
+    keys: Vec<Keypair>,
+}
+
+impl SignatureConfig {
+    /// Consistency check
+    pub fn check(&self) -> Result<(), ConfigError> {
+        if self.keys.is_empty() {
+            Err(ConfigError::SigningKeyIsMissing)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn to_persistent(&self) -> SignaturePersistentConfig {
+        SignaturePersistentConfig {
+            signers: self.keys.iter().map(|key| key.public.to_bytes()).collect(),
+        }
+    }
+}
+
+impl ArchiveWriterConfig {
+    /// Sign the archive with `key`, enabling the `SIGN` layer
+    ///
+    /// Each key added produces its own signature. Readers can then
+    /// authenticate the archive, see `ArchiveReaderConfig::add_verification_keys`
+    pub fn add_signing_key(&mut self, key: &Keypair) -> &mut ArchiveWriterConfig {
+        self.sign.keys.push(copy_keypair(key));
+        self.enable_layer(Layers::SIGN)
+    }
+}
+
+#[derive(Default)]
+pub struct SignatureReaderConfig {
+    /// Trusted public keys, one of which must have signed the archive
+    verification_keys: Vec<PublicKey>,
+    /// Public keys of the signers, as announced by the header
+    signers: Vec<PublicKey>,
+}
+
+impl SignatureReaderConfig {
+    pub fn load_persistent(
+        &mut self,
+        config: SignaturePersistentConfig,
+    ) -> Result<(), ConfigError> {
+        self.signers = config
+            .signers
+            .iter()
+            .map(|key| PublicKey::from_bytes(key))
+            .collect::<Result<_, _>>()
+            .map_err(|_| ConfigError::IncoherentPersistentConfig)?;
+        Ok(())
+    }
+
+    /// Public keys of the signers, as announced by the header
+    pub fn signers(&self) -> &[PublicKey] {
+        &self.signers
+    }
+
+    /// Length of the signatures, at the end of the archive
+    fn signatures_length(&self) -> u64 {
+        (self.signers.len() * SIGNATURE_LENGTH) as u64
+    }
+}
+
+impl ArchiveReaderConfig {
+    /// Only accept archives signed by one of `keys`
+    ///
+    /// The signature is checked while opening the archive, which is then read
+    /// once entirely. Without verification keys, signatures are ignored
+    pub fn add_verification_keys(&mut self, keys: &[PublicKey]) -> &mut ArchiveReaderConfig {
+        self.sign.verification_keys.extend_from_slice(keys);
+        self
+    }
+}
+
+/// Check that the archive in `src`, starting at `start`, is signed by one of
+/// the verification keys of `config`. Nothing is checked if there is none
+///
+/// The position of `src` is left unspecified
+pub(crate) fn verify_signatures<R: Read + Seek>(
+    src: &mut R,
+    start: u64,
+    config: &SignatureReaderConfig,
+) -> Result<(), Error> {
+    if config.verification_keys.is_empty() {
+        return Ok(());
+    }
+
+    // Everything before the signatures is signed, including the header
+    let end = src.seek(SeekFrom::End(0))?;
+    let signed_length = match end.checked_sub(start + config.signatures_length()) {
+        Some(length) => length,
+        None => return Err(Error::SignatureVerificationFailed),
+    };
+    src.seek(SeekFrom::Start(start))?;
+    let mut hash = Sha512::new();
+    io::copy(&mut (&mut *src).take(signed_length), &mut hash)?;
+    let message = signed_message(&hash.finalize());
+
+    let mut signature = [0u8; SIGNATURE_LENGTH];
+    for signer in &config.signers {
+        src.read_exact(&mut signature)?;
+        if !config.verification_keys.contains(signer) {
+            continue;
+        }
+        if let Ok(signature) = Signature::try_from(&signature[..]) {
+            if signer.verify_strict(&message, &signature).is_ok() {
+                return Ok(());
+            }
+        }
+    }
+    Err(Error::SignatureVerificationFailed)
+}
+
+// ---------- Writer ----------
+
+/// Layer signing everything written through it, the signatures being appended
+/// on finalization
+pub struct SignatureLayerWriter<'a, W: 'a + Write> {
+    inner: Box<dyn 'a + LayerWriter<'a, W>>,
+    hash: Sha512,
+    keys: Vec<Keypair>,
+}
+
+impl<'a, W: 'a + Write> SignatureLayerWriter<'a, W> {
+    pub fn new(inner: Box<dyn 'a + LayerWriter<'a, W>>, config: &SignatureConfig) -> Self {
+        Self {
+            inner,
+            hash: Sha512::new(),
+            keys: config.keys.iter().map(copy_keypair).collect(),
+        }
+    }
+}
+
+impl<'a, W: 'a + Write> LayerWriter<'a, W> for SignatureLayerWriter<'a, W> {
+    fn into_inner(self) -> Option<Box<dyn 'a + LayerWriter<'a, W>>> {
+        Some(self.inner)
+    }
+
+    fn into_raw(self: Box<Self>) -> W {
+        self.inner.into_raw()
+    }
+
+    fn finalize(&mut self) -> Result<(), Error> {
+        // Upper layers have already written their last bytes through this one
+        let message = signed_message(&self.hash.finalize_reset());
+        for key in &self.keys {
+            self.inner.write_all(&key.sign(&message).to_bytes())?;
+        }
+
+        // Recursive call
+        self.inner.finalize()
+    }
+}
+
+impl<'a, W: 'a + Write> Write for SignatureLayerWriter<'a, W> {
+    /// Wrapper on inner, hashing the bytes actually written
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.hash.update(&buf[..written]);
+        Ok(written)
+    }
+
+    /// Wrapper on inner
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// ---------- Reader ----------
+
+/// Layer hiding the signatures at the end of the archive
+///
+/// Signatures are checked beforehand, see `verify_signatures`
+pub struct SignatureLayerReader<'a, R: Read + Seek> {
+    inner: Box<dyn 'a + LayerReader<'a, R>>,
+    signatures_length: u64,
+    /// End of the signed data, known once initialized
+    data_end: u64,
+    /// Current position
+    pos: u64,
+}
+
+impl<'a, R: Read + Seek> SignatureLayerReader<'a, R> {
+    pub fn new(inner: Box<dyn 'a + LayerReader<'a, R>>, config: &SignatureReaderConfig) -> Self {
+        Self {
+            inner,
+            signatures_length: config.signatures_length(),
+            data_end: 0,
+            pos: 0,
+        }
+    }
+}
+
+impl<'a, R: Read + Seek> LayerReader<'a, R> for SignatureLayerReader<'a, R> {
+    fn into_inner(self) -> Option<Box<dyn 'a + LayerReader<'a, R>>> {
+        Some(self.inner)
+    }
+
+    fn into_raw(self: Box<Self>) -> R {
+        self.inner.into_raw()
+    }
+
+    fn initialize(&mut self) -> Result<(), Error> {
+        // Recursive call
+        self.inner.initialize()?;
+
+        let end = self.inner.seek(SeekFrom::End(0))?;
+        self.data_end = match end.checked_sub(self.signatures_length) {
+            Some(data_end) => data_end,
+            None => {
+                return Err(Error::WrongReaderState(
+                    "[SignatureLayer] Signatures are missing".to_string(),
+                ))
+            }
+        };
+        self.pos = self.inner.seek(SeekFrom::Start(0))?;
+        Ok(())
+    }
+}
+
+impl<'a, R: Read + Seek> Read for SignatureLayerReader<'a, R> {
+    /// Wrapper on inner, stopping before the signatures
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        let remaining = self.data_end.saturating_sub(self.pos);
+        let size = std::cmp::min(remaining, into.len() as u64) as usize;
+        let read = self.inner.read(&mut into[..size])?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for SignatureLayerReader<'a, R> {
+    /// Seek in the signed data, its end being the start of the signatures
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(offset) => checked_add_signed(self.pos, offset),
+            SeekFrom::End(offset) => checked_add_signed(self.data_end, offset),
+        };
+        match new_pos {
+            Some(new_pos) => {
+                self.pos = self.inner.seek(SeekFrom::Start(new_pos))?;
+                Ok(self.pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative position",
+            )),
+        }
+    }
+}
+
+// ---------- FailSafeReader ----------
+
+/// Layer hiding the signatures at the end of the archive, by holding back the
+/// last bytes read
+pub struct SignatureLayerFailSafeReader<'a, R: Read> {
+    inner: Box<dyn 'a + LayerFailSafeReader<'a, R>>,
+    signatures_length: usize,
+    /// Bytes read from inner but not returned yet, as they may be signatures
+    pending: Vec<u8>,
+}
+
+impl<'a, R: Read> SignatureLayerFailSafeReader<'a, R> {
+    pub fn new(
+        inner: Box<dyn 'a + LayerFailSafeReader<'a, R>>,
+        config: &SignatureReaderConfig,
+    ) -> Self {
+        Self {
+            inner,
+            signatures_length: config.signatures_length() as usize,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<'a, R: Read> LayerFailSafeReader<'a, R> for SignatureLayerFailSafeReader<'a, R> {
+    fn into_inner(self) -> Option<Box<dyn 'a + LayerFailSafeReader<'a, R>>> {
+        Some(self.inner)
+    }
+
+    fn into_raw(self: Box<Self>) -> R {
+        self.inner.into_raw()
+    }
+}
+
+impl<'a, R: Read> Read for SignatureLayerFailSafeReader<'a, R> {
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        if into.is_empty() {
+            return Ok(0);
+        }
+        let mut buf = vec![0u8; into.len()];
+        loop {
+            // Only the bytes followed by enough data for the signatures are
+            // known to be signed data
+            if self.pending.len() > self.signatures_length {
+                let count = std::cmp::min(self.pending.len() - self.signatures_length, into.len());
+                into[..count].copy_from_slice(&self.pending[..count]);
+                self.pending.drain(..count);
+                return Ok(count);
+            }
+            let read = self.inner.read(&mut buf)?;
+            if read == 0 {
+                // End of stream: pending bytes are the signatures
+                return Ok(0);
+            }
+            self.pending.extend_from_slice(&buf[..read]);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::layers::raw::{RawLayerFailSafeReader, RawLayerReader, RawLayerWriter};
+    use rand::SeedableRng;
+    use rand_chacha::ChaChaRng;
+    use std::io::Cursor;
+
+    static DATA: [u8; 4] = [1, 2, 3, 4];
+
+    fn signed_data(config: &SignatureConfig) -> Vec<u8> {
+        let mut sign_w = Box::new(SignatureLayerWriter::new(
+            Box::new(RawLayerWriter::new(Vec::new())),
+            config,
+        ));
+        sign_w.write_all(&DATA).unwrap();
+        sign_w.write_all(&DATA).unwrap();
+        sign_w.finalize().unwrap();
+        sign_w.into_raw()
+    }
+
+    #[test]
+    fn sign_layer() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = Keypair::generate(&mut rng);
+        let other_key = Keypair::generate(&mut rng);
+        let mut config = ArchiveWriterConfig::new();
+        config.add_signing_key(&key);
+        let out = signed_data(&config.sign);
+        assert_eq!(out.len(), DATA.len() * 2 + SIGNATURE_LENGTH);
+
+        let mut reader_config = SignatureReaderConfig::default();
+        reader_config
+            .load_persistent(config.sign.to_persistent())
+            .unwrap();
+        assert_eq!(reader_config.signers(), &[key.public]);
+
+        // Signatures are hidden
+        let mut sign_r = Box::new(SignatureLayerReader::new(
+            Box::new(RawLayerReader::new(Cursor::new(out.as_slice()))),
+            &reader_config,
+        ));
+        sign_r.initialize().unwrap();
+        let mut output = Vec::new();
+        sign_r.read_to_end(&mut output).unwrap();
+        assert_eq!(&output[..DATA.len()], &DATA);
+        assert_eq!(&output[DATA.len()..], &DATA);
+        sign_r.seek(SeekFrom::End(-1)).unwrap();
+        let mut output = Vec::new();
+        sign_r.read_to_end(&mut output).unwrap();
+        assert_eq!(output.as_slice(), &DATA[DATA.len() - 1..]);
+
+        let mut sign_r = Box::new(SignatureLayerFailSafeReader::new(
+            Box::new(RawLayerFailSafeReader::new(out.as_slice())),
+            &reader_config,
+        ));
+        let mut output = Vec::new();
+        sign_r.read_to_end(&mut output).unwrap();
+        assert_eq!(output.len(), DATA.len() * 2);
+
+        // Nothing is checked without verification keys
+        verify_signatures(&mut Cursor::new(Vec::new()), 0, &reader_config).unwrap();
+
+        // Good key
+        reader_config.verification_keys = vec![other_key.public, key.public];
+        verify_signatures(&mut Cursor::new(out.as_slice()), 0, &reader_config).unwrap();
+
+        // Tampered data
+        let mut tampered = out.clone();
+        tampered[1] ^= 1;
+        assert!(matches!(
+            verify_signatures(&mut Cursor::new(tampered.as_slice()), 0, &reader_config),
+            Err(Error::SignatureVerificationFailed)
+        ));
+
+        // Signed by another key
+        reader_config.verification_keys = vec![other_key.public];
+        assert!(matches!(
+            verify_signatures(&mut Cursor::new(out.as_slice()), 0, &reader_config),
+            Err(Error::SignatureVerificationFailed)
+        ));
+    }
+
+    #[test]
+    fn several_signers() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let keys = [Keypair::generate(&mut rng), Keypair::generate(&mut rng)];
+        let mut config = ArchiveWriterConfig::new();
+        config.add_signing_key(&keys[0]).add_signing_key(&keys[1]);
+        let out = signed_data(&config.sign);
+        assert_eq!(out.len(), DATA.len() * 2 + 2 * SIGNATURE_LENGTH);
+
+        let mut reader_config = SignatureReaderConfig::default();
+        reader_config
+            .load_persistent(config.sign.to_persistent())
+            .unwrap();
+        // Any of the signers is enough
+        for key in &keys {
+            reader_config.verification_keys = vec![key.public];
+            verify_signatures(&mut Cursor::new(out.as_slice()), 0, &reader_config).unwrap();
+        }
+    }
+}
@@ -4,6 +4,7 @@ use std::io::{Read, Seek, Write};
 /// Trait to be implemented by layer writers
 pub trait LayerWriter<'a, W: Write>: Write {
     /// Unwraps the inner writer
+    #[allow(dead_code)]
     fn into_inner(self) -> Option<Box<dyn 'a + LayerWriter<'a, W>>>;
 
     /// Unwraps the original I/O writer
@@ -38,10 +39,12 @@ pub trait LayerReader<'a, R: Read + Seek>: Read + Seek {
 /// Trait to be implemented by layer for their fail-safe mode reading
 pub trait LayerFailSafeReader<'a, R: Read>: Read {
     /// Unwraps the inner reader
+    #[allow(dead_code)]
     fn into_inner(self) -> Option<Box<dyn 'a + LayerFailSafeReader<'a, R>>>;
 
     /// Unwraps the original I/O reader
     // Use a Box<Self> to be able to move out the inner value; without it, self
     // is used, which is an unsized 'dyn X' and therefore cannot be moved
+    #[allow(dead_code)]
     fn into_raw(self: Box<Self>) -> R;
 }
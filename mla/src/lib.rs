@@ -1,40 +1,74 @@
-use std::collections::HashMap;
-use std::convert::{TryFrom, TryInto};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
 use std::io;
 use std::io::{Read, Seek, SeekFrom, Write};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 #[macro_use]
 extern crate bitflags;
+use bincode::Options;
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use serde::{Deserialize, Serialize};
 
+mod dedup;
+pub use crate::dedup::DedupStats;
+use crate::dedup::Deduplicator;
 mod layers;
+use crate::layers::cache::{checked_add_signed, CacheLayerReader};
 use crate::layers::compress::{
     CompressionLayerFailSafeReader, CompressionLayerReader, CompressionLayerWriter,
 };
+use crate::layers::digest::{DigestHandle, DigestLayerWriter};
 use crate::layers::encrypt::{
     EncryptionLayerFailSafeReader, EncryptionLayerReader, EncryptionLayerWriter,
 };
 use crate::layers::position::PositionLayerWriter;
+use crate::layers::progress::{
+    EntriesProgress, ProgressHandle, ProgressLayerFailSafeReader, ProgressLayerReader,
+    ProgressLayerWriter,
+};
 use crate::layers::raw::{RawLayerFailSafeReader, RawLayerReader, RawLayerWriter};
+use crate::layers::sign::{
+    verify_signatures, SignatureLayerFailSafeReader, SignatureLayerReader, SignatureLayerWriter,
+};
 use crate::layers::traits::{LayerFailSafeReader, LayerReader, LayerWriter};
 pub mod errors;
-use crate::errors::{Error, FailSafeReadError};
+use crate::errors::{ConfigError, Error, FailSafeReadError};
 
 pub mod config;
-use crate::config::{ArchivePersistentConfig, ArchiveReaderConfig, ArchiveWriterConfig};
+use crate::config::{
+    ArchivePersistentConfig, ArchivePersistentConfigV1, ArchivePersistentConfigV2,
+    ArchivePersistentConfigV4, ArchiveReaderConfig, ArchiveWriterConfig, CompressionAlgorithm,
+    EncryptionCipher, HybridPrivateKey, PaddingPolicy, RecipientSlot,
+};
 
 #[doc(hidden)]
 pub mod crypto;
+use crate::crypto::aesgcm::TAG_LENGTH;
+pub use crate::crypto::backend::{active_backends, Backend, CryptoBackends};
 use crate::crypto::hash::{HashWrapperReader, Sha256Hash};
+use rand::{CryptoRng, RngCore};
+use rand_chacha::ChaChaRng;
 use sha2::{Digest, Sha256};
-use x25519_dalek::PublicKey;
+use x25519_dalek::{PublicKey, StaticSecret};
 
 pub mod helpers;
 
+#[cfg(feature = "fs")]
+pub mod volume;
+
+#[cfg(feature = "async")]
+pub mod async_io;
+
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11;
+
 // -------- Constants --------
 
 const MLA_MAGIC: &[u8; 3] = b"MLA";
-const MLA_FORMAT_VERSION: u32 = 1;
+const MLA_FORMAT_VERSION: u32 = 6;
 /// Maximum number of UTF-8 characters supported in each file's "name" (which is free
 /// to be used as a filename, an absolute path, or... ?). 32KiB was chosen because it
 /// supports any path a Windows NT, Linux, FreeBSD, OpenBSD, or NetBSD kernel supports.
@@ -43,18 +77,31 @@ const FILENAME_MAX_SIZE: u64 = 65536;
 /// malformed files
 pub(crate) const BINCODE_MAX_DESERIALIZE: u64 = 512 * 1024 * 1024;
 
+/// Bincode options of the objects stored in archives: integers have a fixed
+/// size (as in bincode 1.2, used by the first versions of the format), and
+/// objects are limited to `BINCODE_MAX_DESERIALIZE`
+pub(crate) fn bincode_options() -> impl bincode::Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .allow_trailing_bytes()
+        .with_limit(BINCODE_MAX_DESERIALIZE)
+}
+
 bitflags! {
     /// Available layers. Order is relevant:
     /// ```ascii-art
     /// [File to blocks decomposition]
     /// [Compression (COMPRESS)]
     /// [Encryption (ENCRYPT)]
+    /// [Signature (SIGN)]
     /// [Raw File I/O]
     /// ```
     #[derive(Serialize, Deserialize)]
     pub struct Layers: u8 {
         const ENCRYPT = 0b0000_0001;
         const COMPRESS = 0b0000_0010;
+        /// Enabled by `ArchiveWriterConfig::add_signing_key`
+        const SIGN = 0b0000_0100;
         /// Recommended layering
         const DEFAULT = Self::ENCRYPT.bits | Self::COMPRESS.bits;
         /// No additional layer (ie, for debugging purpose)
@@ -71,12 +118,56 @@ impl std::default::Default for Layers {
 
 pub type ArchiveFileID = u64;
 
+/// Type of an entry, as recorded in its `FileMetadata`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub enum EntryType {
+    /// Regular file, made of the entry's content
+    #[default]
+    File,
+    /// Symbolic link to the given target. The entry has no content
+    Symlink(String),
+    /// Directory. The entry has no content
+    Directory,
+}
+
+/// Optional metadata of an entry, set with `ArchiveWriter::set_file_metadata`
+///
+/// MLA only stores it: restoring it on extraction is up to the caller
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub entry_type: EntryType,
+    /// Permission bits (`st_mode & 0o7777` on Unix)
+    pub mode: Option<u32>,
+    /// Owner user and group IDs
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Last modification time, in seconds since the Unix epoch
+    pub mtime: Option<i64>,
+}
+
 // -------- MLA Format Header --------
 
-struct ArchiveHeader {
+/// Header of an archive, readable without any key
+///
+/// See `ArchiveHeader::inspect` to describe an archive from it
+pub struct ArchiveHeader {
+    /// Format version, as read from the archive
+    format_version: u32,
     config: ArchivePersistentConfig,
 }
 
+/// Deserialize an object from the header or the footer, limiting its size
+fn deserialize_with_limit<T, R>(src: &mut R) -> Result<T, Error>
+where
+    T: serde::de::DeserializeOwned,
+    R: Read,
+{
+    match bincode_options().deserialize_from(src) {
+        Ok(config) => Ok(config),
+        _ => Err(Error::DeserializationError),
+    }
+}
+
 impl ArchiveHeader {
     fn from<T: Read>(src: &mut T) -> Result<Self, Error> {
         let mut buf = vec![00u8; MLA_MAGIC.len()];
@@ -85,26 +176,31 @@ impl ArchiveHeader {
             return Err(Error::WrongMagic);
         }
         let version = src.read_u32::<LittleEndian>()?;
-        if version != MLA_FORMAT_VERSION {
-            return Err(Error::UnsupportedVersion);
-        }
-        let config: ArchivePersistentConfig = match bincode::config()
-            .limit(BINCODE_MAX_DESERIALIZE)
-            .deserialize_from(src)
-        {
-            Ok(config) => config,
+        let config = match version {
+            // Format v1 only differs by its persistent configuration, which
+            // does not record layers' block sizes
+            1 => deserialize_with_limit::<ArchivePersistentConfigV1, _>(src)?.into(),
+            // Formats v2 and v3 only differ by their footer, and cannot be
+            // signed
+            2 | 3 => deserialize_with_limit::<ArchivePersistentConfigV2, _>(src)?.into(),
+            // Formats v4 and v5 only differ by their footer, and do not
+            // record deduplication
+            4 | 5 => deserialize_with_limit::<ArchivePersistentConfigV4, _>(src)?.into(),
+            MLA_FORMAT_VERSION => deserialize_with_limit(src)?,
             _ => {
-                return Err(Error::DeserializationError);
+                return Err(Error::UnsupportedVersion);
             }
         };
-        Ok(ArchiveHeader { config })
+        Ok(ArchiveHeader {
+            format_version: version,
+            config,
+        })
     }
 
     fn dump<T: Write>(&self, dest: &mut T) -> Result<(), Error> {
         dest.write_all(MLA_MAGIC)?;
         dest.write_u32::<LittleEndian>(MLA_FORMAT_VERSION)?;
-        if bincode::config()
-            .limit(BINCODE_MAX_DESERIALIZE)
+        if bincode_options()
             .serialize_into(dest, &self.config)
             .is_err()
         {
@@ -114,6 +210,177 @@ impl ArchiveHeader {
     }
 }
 
+/// Description of an archive, as returned by `ArchiveHeader::inspect`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveInspection {
+    /// Format version of the archive
+    pub format_version: u32,
+    /// Enabled layers
+    pub layers: Layers,
+    /// Compression algorithm, if the compression layer is enabled
+    pub compression: Option<CompressionAlgorithm>,
+    /// Whether blocks are compressed with a dictionary
+    pub compression_dictionary: bool,
+    /// Cipher, if the encryption layer is enabled
+    pub cipher: Option<EncryptionCipher>,
+    /// Recipient slots, without the keys opening them (see `recipient_slots`)
+    pub recipients: Vec<RecipientSlot>,
+    /// Number of signers announced by the header
+    pub signers: usize,
+    /// Size of the archive, in bytes
+    pub size: u64,
+    /// Number of entries, if the footer can be read without a key
+    pub entries: Option<usize>,
+    /// Whether the archive appears truncated, if it can be told without a key
+    pub truncated: Option<bool>,
+}
+
+impl ArchiveHeader {
+    /// Describe the archive read from `src`, from its header and, if it is
+    /// not encrypted, its footer. No key is needed
+    ///
+    /// The content of encrypted archives cannot be read, so their entries
+    /// are not counted, and they only appear truncated if their last
+    /// encrypted chunk cannot even hold a tag. Signatures are not verified
+    pub fn inspect<R: Read + Seek>(mut src: R) -> Result<ArchiveInspection, Error> {
+        let archive_start = src.stream_position()?;
+        let header = ArchiveHeader::from(&mut src)?;
+        let header_end = src.stream_position()?;
+        let end = src.seek(SeekFrom::End(0))?;
+
+        let config = &header.config;
+        let layers = config.layers_enabled;
+        let compress = config
+            .compress
+            .as_ref()
+            .filter(|_| layers.contains(Layers::COMPRESS));
+        let encrypt = config
+            .encrypt
+            .as_ref()
+            .filter(|_| layers.contains(Layers::ENCRYPT));
+        let sign = config
+            .sign
+            .as_ref()
+            .filter(|_| layers.contains(Layers::SIGN));
+
+        let mut inspection = ArchiveInspection {
+            format_version: header.format_version,
+            layers,
+            compression: compress.map(|compress| compress.algorithm()),
+            compression_dictionary: compress.is_some_and(|compress| compress.has_dictionary()),
+            cipher: encrypt.map(|encrypt| encrypt.cipher()),
+            recipients: encrypt.map_or_else(Vec::new, |encrypt| encrypt.recipient_slots(&[], &[])),
+            signers: sign.map_or(0, |sign| sign.count()),
+            size: end - archive_start,
+            entries: None,
+            truncated: None,
+        };
+
+        let signatures_length = sign.map_or(0, |sign| sign.signatures_length());
+        let data_length = match (end - header_end).checked_sub(signatures_length) {
+            Some(length) => length,
+            None => {
+                inspection.truncated = Some(true);
+                return Ok(inspection);
+            }
+        };
+        if let Some(encrypt) = encrypt {
+            // Every chunk, including the last one, ends with its tag
+            let last_chunk = data_length % encrypt.chunk_tag_size();
+            inspection.truncated =
+                Some(data_length == 0 || (last_chunk != 0 && last_chunk < TAG_LENGTH as u64));
+            return Ok(inspection);
+        }
+
+        // Without encryption, the footer is readable
+        src.seek(SeekFrom::Start(archive_start))?;
+        match ArchiveReader::from_config(src, ArchiveReaderConfig::new()) {
+            Ok(mla) => {
+                inspection.entries = Some(mla.list_files()?.count());
+                inspection.truncated = Some(false);
+            }
+            Err(_) => inspection.truncated = Some(true),
+        }
+        Ok(inspection)
+    }
+}
+
+/// Copy the archive read from `src` to `dest`, changing the recipients able
+/// to decrypt it to `recipients`
+///
+/// `config` must be able to decrypt the archive (private key or password).
+/// Only the header is rewritten, with the shared key wrapped for the new
+/// recipients: the encrypted content is copied as is. Recipients are not
+/// identified in the header, so `recipients` is the complete new set: the
+/// current recipients not listed can no longer decrypt the archive. The
+/// password and the hybrid recipients, if any, are kept. `csprng` generates
+/// the ephemeral key wrapping the shared key.
+///
+/// The shared key itself is unchanged: a removed recipient who already
+/// unwrapped it, from this archive or a copy, can still decrypt the content.
+/// Only re-encrypting the archive (such as with `mlar convert`) prevents it.
+///
+/// Signed archives, whose signature covers the header, and archives in an
+/// older format version are not supported.
+pub fn rekey<R: Read, W: Write, T: RngCore + CryptoRng>(
+    mut src: R,
+    mut dest: W,
+    mut config: ArchiveReaderConfig,
+    recipients: &[PublicKey],
+    csprng: &mut T,
+) -> Result<(), Error> {
+    let mut header = ArchiveHeader::from(&mut src)?;
+    if header.format_version != MLA_FORMAT_VERSION {
+        return Err(Error::BadAPIArgument(
+            "[rekey] Only the current format version can be rekeyed".to_string(),
+        ));
+    }
+    let layers = header.config.layers_enabled;
+    if layers.contains(Layers::SIGN) {
+        return Err(Error::BadAPIArgument(
+            "[rekey] Signed archives cannot be rekeyed".to_string(),
+        ));
+    }
+    let encrypt = match header.config.encrypt.as_mut() {
+        Some(encrypt) if layers.contains(Layers::ENCRYPT) => encrypt,
+        _ => {
+            return Err(Error::BadAPIArgument(
+                "[rekey] The archive is not encrypted".to_string(),
+            ));
+        }
+    };
+    config.encrypt.load_persistent(encrypt)?;
+    let (key, _) = config
+        .get_encrypt_parameters()
+        .ok_or(Error::PrivateKeyNeeded)?;
+    let key = zeroize::Zeroizing::new(key);
+    encrypt.rekey(&key, recipients, csprng)?;
+
+    header.dump(&mut dest)?;
+    io::copy(&mut src, &mut dest)?;
+    Ok(())
+}
+
+/// Return the recipient slots of the archive read from `src`, none if it is
+/// not encrypted
+///
+/// Only the header is read. Slots opened by one of `private_keys` or
+/// `hybrid_private_keys` report its index, for instance to display the
+/// fingerprint of the recipient.
+pub fn recipient_slots<R: Read>(
+    mut src: R,
+    private_keys: &[StaticSecret],
+    hybrid_private_keys: &[HybridPrivateKey],
+) -> Result<Vec<RecipientSlot>, Error> {
+    let header = ArchiveHeader::from(&mut src)?;
+    match &header.config.encrypt {
+        Some(encrypt) if header.config.layers_enabled.contains(Layers::ENCRYPT) => {
+            Ok(encrypt.recipient_slots(private_keys, hybrid_private_keys))
+        }
+        _ => Ok(Vec::new()),
+    }
+}
+
 // -------- MLA Format Footer --------
 
 struct ArchiveFooter {
@@ -126,7 +393,7 @@ impl ArchiveFooter {
     /// ```ascii-art
     /// [files_info][files_info length]
     /// ```
-
+    ///
     /// Performs zero-copy serialization of a footer
     fn serialize_into<W: Write>(
         mut dest: W,
@@ -136,8 +403,10 @@ impl ArchiveFooter {
         let mut serialization_len = 0;
 
         // Combine `files_info` and `ids_info` to ArchiveFooter.files_info,
-        // avoiding copies (only references)
-        let mut tmp: HashMap<&String, &FileInfo> = HashMap::new();
+        // avoiding copies (only references). Entries are sorted, so that the
+        // footer does not depend on the HashMap order; the serialization is
+        // the same as a HashMap one
+        let mut tmp: BTreeMap<&String, &FileInfo> = BTreeMap::new();
         for (k, i) in files_info {
             let v = ids_info.get(i).ok_or_else(|| {
                 Error::WrongWriterState(
@@ -147,11 +416,7 @@ impl ArchiveFooter {
             tmp.insert(k, v);
         }
 
-        if bincode::config()
-            .limit(BINCODE_MAX_DESERIALIZE)
-            .serialize_into(&mut dest, &tmp)
-            .is_err()
-        {
+        if bincode_options().serialize_into(&mut dest, &tmp).is_err() {
             return Err(Error::SerializationError);
         };
         serialization_len += match bincode::serialized_size(&tmp) {
@@ -166,25 +431,51 @@ impl ArchiveFooter {
         Ok(())
     }
 
-    /// Parses and instantiates a footer from serialized data
-    fn deserialize_from<R: Read + Seek>(mut src: R) -> Result<ArchiveFooter, Error> {
+    /// Parses and instantiates a footer from serialized data, written in
+    /// format `format_version`
+    fn deserialize_from<R: Read + Seek>(
+        mut src: R,
+        format_version: u32,
+    ) -> Result<ArchiveFooter, Error> {
         // Read the footer length
         let pos = src.seek(SeekFrom::End(-4))?;
         let len = src.read_u32::<LittleEndian>()? as u64;
 
-        // Prepare for deserialization
-        src.seek(SeekFrom::Start(pos - len))?;
+        // Prepare for deserialization, a length past the start meaning a broken
+        // footer
+        let start = pos.checked_sub(len).ok_or(Error::DeserializationError)?;
+        src.seek(SeekFrom::Start(start))?;
 
         // Read files_info
-        let files_info: HashMap<String, FileInfo> = match bincode::config()
-            .limit(BINCODE_MAX_DESERIALIZE)
-            .deserialize_from(&mut src.take(len))
-        {
-            Ok(finfo) => finfo,
-            _ => {
-                return Err(Error::DeserializationError);
-            }
+        let mut src = src.take(len);
+        let files_info: HashMap<String, FileInfo> = if format_version < 3 {
+            // Formats v1 and v2 do not index content blocks
+            deserialize_with_limit::<HashMap<String, FileInfoV1>, _>(&mut src)?
+                .into_iter()
+                .map(|(fname, finfo)| (fname, finfo.into()))
+                .collect()
+        } else if format_version == 3 {
+            // Format v3 does not record files metadata
+            deserialize_with_limit::<HashMap<String, FileInfoV3>, _>(&mut src)?
+                .into_iter()
+                .map(|(fname, finfo)| (fname, finfo.into()))
+                .collect()
+        } else if format_version == 4 {
+            // Format v4 does not deduplicate files content
+            deserialize_with_limit::<HashMap<String, FileInfoV4>, _>(&mut src)?
+                .into_iter()
+                .map(|(fname, finfo)| (fname, finfo.into()))
+                .collect()
+        } else {
+            deserialize_with_limit(&mut src)?
         };
+        // The footer must be entirely consumed. Otherwise, it has not been
+        // written with the layout of `format_version`, for instance by a
+        // development version of MLA changing the footer without a new format
+        // version, and is refused instead of being misread
+        if src.limit() != 0 {
+            return Err(Error::DeserializationError);
+        }
         Ok(ArchiveFooter { files_info })
     }
 }
@@ -197,6 +488,7 @@ impl ArchiveFooter {
 enum ArchiveFileBlockType {
     FileStart = 0x00,
     FileContent = 0x01,
+    Padding = 0x02,
 
     EndOfArchiveData = 0xFE,
     EndOfFile = 0xFF,
@@ -210,6 +502,8 @@ impl TryFrom<u8> for ArchiveFileBlockType {
             Ok(ArchiveFileBlockType::FileStart)
         } else if value == ArchiveFileBlockType::FileContent as u8 {
             Ok(ArchiveFileBlockType::FileContent)
+        } else if value == ArchiveFileBlockType::Padding as u8 {
+            Ok(ArchiveFileBlockType::Padding)
         } else if value == ArchiveFileBlockType::EndOfFile as u8 {
             Ok(ArchiveFileBlockType::EndOfFile)
         } else if value == ArchiveFileBlockType::EndOfArchiveData as u8 {
@@ -240,6 +534,10 @@ pub enum ArchiveFileBlock<T: Read> {
     },
     /// End of file (last block) - contains the SHA256 of the whole file
     EndOfFile { id: ArchiveFileID, hash: Sha256Hash },
+    /// Random bytes, ignored by readers, hiding the size of the entries (see
+    /// `ArchiveWriterConfig::with_padding_policy`). As for `FileContent`,
+    /// `data` is None on parsing
+    Padding { length: u64, data: Option<T> },
     /// End of archive data (no more files after that)
     EndOfArchiveData,
 }
@@ -259,7 +557,7 @@ where
                     return Err(Error::FilenameTooLong);
                 }
                 dest.write_u64::<LittleEndian>(length)?;
-                dest.write_all(&bytes)?;
+                dest.write_all(bytes)?;
                 Ok(())
             }
             ArchiveFileBlock::FileContent { length, data, id } => {
@@ -285,6 +583,21 @@ where
                 dest.write_all(hash)?;
                 Ok(())
             }
+            ArchiveFileBlock::Padding { length, data } => {
+                dest.write_u8(ArchiveFileBlockType::Padding as u8)?;
+                dest.write_u64::<LittleEndian>(*length)?;
+                match data {
+                    None => {
+                        return Err(Error::AssertionError(String::from(
+                            "Data missing in padding",
+                        )));
+                    }
+                    Some(content) => {
+                        io::copy(&mut content.take(*length), dest)?;
+                    }
+                }
+                Ok(())
+            }
             ArchiveFileBlock::EndOfArchiveData => {
                 dest.write_u8(ArchiveFileBlockType::EndOfArchiveData as u8)?;
                 Ok(())
@@ -326,6 +639,12 @@ where
                 src.read_exact(&mut hash)?;
                 Ok(ArchiveFileBlock::EndOfFile { id, hash })
             }
+            ArchiveFileBlockType::Padding => {
+                let length = src.read_u64::<LittleEndian>()?;
+                // As for FileContent, `src` now starts at the beginning of the
+                // padding
+                Ok(ArchiveFileBlock::Padding { length, data: None })
+            }
             ArchiveFileBlockType::EndOfArchiveData => Ok(ArchiveFileBlock::EndOfArchiveData),
         }
     }
@@ -348,7 +667,7 @@ impl ArchiveWriterState {
         &mut self,
         id: ArchiveFileID,
         src: R,
-    ) -> Result<HashWrapperReader<R>, Error> {
+    ) -> Result<HashWrapperReader<'_, R>, Error> {
         let hash = match self {
             ArchiveWriterState::OpenedFiles { hashes, .. } => match hashes.get_mut(&id) {
                 Some(hash) => hash,
@@ -370,9 +689,9 @@ impl ArchiveWriterState {
 }
 
 /// Used to check whether the current state is the one expected
-/// ```
+/// ```ignore
 /// check_state!(self.state, ArchiveWriterState::XXX)
-/// ``̀
+/// ```
 macro_rules! check_state {
     ( $x:expr, $y:ident ) => {{
         match $x {
@@ -388,9 +707,9 @@ macro_rules! check_state {
 }
 
 /// Used to check whether the current state is `OpenedFiles`, with the expected file opened
-/// ```
+/// ```ignore
 /// check_state_file_opened!(self.state, file_id)
-/// ``̀
+/// ```
 macro_rules! check_state_file_opened {
     ( $x:expr, $y:expr ) => {{
         match $x {
@@ -416,9 +735,6 @@ pub struct ArchiveWriter<'a, W: 'a + Write> {
     /// MLA Archive format writer
     ///
     /// Configuration
-    // config is not used for now after archive creation,
-    // but it could in the future
-    #[allow(dead_code)]
     config: ArchiveWriterConfig,
     ///
     /// Internals part:
@@ -444,18 +760,51 @@ pub struct ArchiveWriter<'a, W: 'a + Write> {
     next_id: ArchiveFileID,
     /// Current file being written (for continuous block detection)
     current_id: ArchiveFileID,
+    /// Digest of the output, if requested
+    digest: Option<DigestHandle>,
+    /// Padding policy, with the generator of the padding, if any
+    padding: Option<(PaddingPolicy, ChaChaRng)>,
+    /// Entries reported to the progress observer, if any
+    progress: Option<EntriesProgress>,
+    /// Chunks already stored, in deduplication mode
+    dedup: Option<Deduplicator>,
 }
 
+/// Size of the reads splitting the content of files into chunks, in
+/// deduplication mode
+const DEDUP_READ_SIZE: usize = 64 * 1024;
+
 // This is an unstable feature for now (`Vec.remove_item`), use a function
 // instead to keep stable compatibility
 pub fn vec_remove_item<T: std::cmp::PartialEq>(vec: &mut Vec<T>, item: &T) -> Option<T> {
-    let pos = match vec.iter().position(|x| *x == *item) {
-        Some(x) => x,
-        None => return None,
-    };
+    let pos = vec.iter().position(|x| *x == *item)?;
     Some(vec.remove(pos))
 }
 
+/// Size of a serialized `ArchiveFileBlock::Padding`, without its content
+const PADDING_HEADER_SIZE: u64 = 1 + 8;
+
+/// Length of the padding content to add after `footprint` bytes, to reach a
+/// multiple of `granularity`, if needed
+fn padding_length(footprint: u64, granularity: u64) -> Option<u64> {
+    if granularity == 0 || footprint.is_multiple_of(granularity) {
+        return None;
+    }
+    // The padding block header must also fit
+    let padded = (footprint + PADDING_HEADER_SIZE).div_ceil(granularity) * granularity;
+    Some(padded - footprint - PADDING_HEADER_SIZE)
+}
+
+/// Endless reader of random bytes, used as padding content
+struct RandomReader<'a, R: RngCore>(&'a mut R);
+
+impl<'a, R: RngCore> Read for RandomReader<'a, R> {
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        self.0.fill_bytes(into);
+        Ok(into.len())
+    }
+}
+
 impl<'a, W: Write> ArchiveWriter<'a, W> {
     pub fn from_config(dest: W, config: ArchiveWriterConfig) -> Result<Self, Error> {
         // Ensure config is correct
@@ -463,7 +812,26 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
 
         // Write archive header
         let mut dest: Box<dyn LayerWriter<W>> = Box::new(RawLayerWriter::new(dest));
+        let progress = if let Some(handle) = &config.progress {
+            dest = Box::new(ProgressLayerWriter::new(dest, handle.clone()));
+            Some(EntriesProgress::new(handle.clone()))
+        } else {
+            None
+        };
+        // The digest covers the whole output, including the header
+        let digest = if config.is_output_digest_enabled() {
+            let handle = DigestHandle::default();
+            dest = Box::new(DigestLayerWriter::new(dest, handle.clone()));
+            Some(handle)
+        } else {
+            None
+        };
+        // The signature covers the header
+        if config.is_layers_enabled(Layers::SIGN) {
+            dest = Box::new(SignatureLayerWriter::new(dest, &config.sign));
+        }
         ArchiveHeader {
+            format_version: MLA_FORMAT_VERSION,
             config: config.to_persistent()?,
             // TODO public_key hashes for easier decryption
         }
@@ -481,6 +849,17 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
         let mut final_dest = Box::new(PositionLayerWriter::new(dest));
         final_dest.reset_position();
 
+        // Seeded after the encryption secrets, which are generated first
+        let padding = config
+            .padding_policy()
+            .map(|policy| (policy, config.encrypt.derive_rng()));
+
+        let dedup = if config.is_dedup_enabled() {
+            Some(Deduplicator::default())
+        } else {
+            None
+        };
+
         // Build initial archive
         Ok(ArchiveWriter {
             config,
@@ -493,6 +872,10 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
             ids_info: HashMap::new(),
             next_id: 0,
             current_id: 0,
+            digest,
+            padding,
+            progress,
+            dedup,
         })
     }
 
@@ -522,6 +905,21 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
         }
         self.state = ArchiveWriterState::Finalized;
 
+        if let Some((policy, rng)) = &mut self.padding {
+            // Random decoy, hiding the total size of the entries
+            let length = match policy.max_decoy.checked_add(1) {
+                Some(bound) => rng.next_u64() % bound,
+                None => rng.next_u64(),
+            };
+            if length > 0 {
+                ArchiveFileBlock::Padding {
+                    length,
+                    data: Some(RandomReader(rng)),
+                }
+                .dump(&mut self.dest)?;
+            }
+        }
+
         // Mark the end of the data
 
         // Use std::io::Empty as a readable placeholder type
@@ -534,6 +932,23 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
         Ok(())
     }
 
+    /// Return the SHA256 of the archive written, ie. the same value as
+    /// `sha256sum` on the output
+    ///
+    /// Only available once the archive is finalized, and if
+    /// `ArchiveWriterConfig::enable_output_digest` has been called
+    pub fn output_digest(&self) -> Option<Sha256Hash> {
+        self.digest.as_ref().and_then(|handle| handle.get())
+    }
+
+    /// Return the space saved by deduplication so far, if
+    /// `ArchiveWriterConfig::with_dedup` has been called
+    ///
+    /// The content of files still opened is only partly accounted
+    pub fn dedup_stats(&self) -> Option<DedupStats> {
+        self.dedup.as_ref().map(|dedup| dedup.stats)
+    }
+
     /// Add the current offset to the corresponding list if the file id is not
     /// the current one, ie. if blocks are not continuous
     fn mark_continuous_block(&mut self, id: ArchiveFileID) -> Result<(), Error> {
@@ -553,10 +968,13 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
     }
 
     /// Set the EoF offset to the current offset for the corresponding file id
-    fn mark_eof(&mut self, id: ArchiveFileID) -> Result<(), Error> {
+    fn mark_eof(&mut self, id: ArchiveFileID, hash: Sha256Hash) -> Result<(), Error> {
         let offset = self.dest.position();
         match self.ids_info.get_mut(&id) {
-            Some(file_info) => file_info.eof_offset = offset,
+            Some(file_info) => {
+                file_info.eof_offset = offset;
+                file_info.hash = Some(hash);
+            }
             None => {
                 return Err(Error::WrongWriterState(
                     "[mark_eof] Unable to find the ID".to_string(),
@@ -566,6 +984,21 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
         Ok(())
     }
 
+    /// Index the content block about to be written at the current offset,
+    /// with the position of its data in the file
+    fn mark_content_block(&mut self, id: ArchiveFileID) -> Result<(), Error> {
+        let offset = self.dest.position();
+        match self.ids_info.get_mut(&id) {
+            Some(file_info) => file_info.content_blocks.push((file_info.size, offset)),
+            None => {
+                return Err(Error::WrongWriterState(
+                    "[mark_content_block] Unable to find the ID".to_string(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
     /// Add the current block size to the total size of the corresponding file id
     fn extend_file_size(&mut self, id: ArchiveFileID, block_size: u64) -> Result<(), Error> {
         match self.ids_info.get_mut(&id) {
@@ -579,6 +1012,11 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
         Ok(())
     }
 
+    /// Start a new file named `filename`, and return its id
+    ///
+    /// Its content is then given with `append_file_content`, until `end_file`.
+    /// Several files can be started at once, their content being interleaved
+    /// in the archive: there is no need to buffer a whole file before adding it
     pub fn start_file(&mut self, filename: &str) -> Result<ArchiveFileID, Error> {
         check_state!(self.state, OpenedFiles);
 
@@ -599,6 +1037,10 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
                 offsets: vec![self.dest.position()],
                 size: 0,
                 eof_offset: 0,
+                content_blocks: Vec::new(),
+                metadata: None,
+                hash: None,
+                deduplicated: self.dedup.is_some(),
             },
         );
         // Use std::io::Empty as a readable placeholder type
@@ -620,9 +1062,15 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
                 ));
             }
         }
+        if let Some(progress) = &mut self.progress {
+            progress.started(id, filename);
+        }
         Ok(id)
     }
 
+    /// Append `size` bytes read from `src` to the started file `id`
+    ///
+    /// Fails if `id` has not been started, or has already been ended
     pub fn append_file_content<U: Read>(
         &mut self,
         id: ArchiveFileID,
@@ -635,8 +1083,12 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
             // Avoid creating 0-sized block
             return Ok(());
         }
+        if self.dedup.is_some() {
+            return self.append_dedup_content(id, size, src);
+        }
 
         self.mark_continuous_block(id)?;
+        self.mark_content_block(id)?;
         self.extend_file_size(id, size)?;
         let src = self.state.wrap_with_hash(id, src)?;
 
@@ -645,26 +1097,101 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
             length: size,
             data: Some(src),
         }
-        .dump(&mut self.dest)
+        .dump(&mut self.dest)?;
+        if let Some(progress) = &self.progress {
+            progress.data(id, size);
+        }
+        Ok(())
+    }
+
+    /// Split `size` bytes read from `src` into chunks, appending them to the
+    /// started file `id`. Chunks already stored are only referenced
+    fn append_dedup_content<U: Read>(
+        &mut self,
+        id: ArchiveFileID,
+        size: u64,
+        src: U,
+    ) -> Result<(), Error> {
+        let mut src = src.take(size);
+        let mut buf = vec![0u8; DEDUP_READ_SIZE];
+        loop {
+            let count = self.state.wrap_with_hash(id, &mut src)?.read(&mut buf)?;
+            if count == 0 {
+                break;
+            }
+            let chunks = match &mut self.dedup {
+                Some(dedup) => dedup.push(id, &buf[..count]),
+                None => Vec::new(),
+            };
+            for chunk in chunks {
+                self.add_chunk(id, &chunk)?;
+            }
+            if let Some(progress) = &self.progress {
+                progress.data(id, count as u64);
+            }
+        }
+        Ok(())
+    }
+
+    /// Append `chunk` to the content of file `id`, storing it in a new
+    /// `FileContent` block unless an identical chunk has already been stored
+    fn add_chunk(&mut self, id: ArchiveFileID, chunk: &[u8]) -> Result<(), Error> {
+        let hash: Sha256Hash = Sha256::digest(chunk).into();
+        let length = chunk.len() as u64;
+        let stored = self.dedup.as_ref().and_then(|dedup| dedup.find(&hash));
+        let offset = match stored {
+            Some(offset) => offset,
+            None => {
+                self.mark_continuous_block(id)?;
+                let offset = self.dest.position();
+                ArchiveFileBlock::FileContent {
+                    id,
+                    length,
+                    data: Some(chunk),
+                }
+                .dump(&mut self.dest)?;
+                offset
+            }
+        };
+        if let Some(dedup) = &mut self.dedup {
+            dedup.stats.content_size += length;
+            dedup.stats.chunks += 1;
+            if stored.is_none() {
+                dedup.insert(hash, offset);
+                dedup.stats.stored_size += length;
+                dedup.stats.stored_chunks += 1;
+            }
+        }
+        match self.ids_info.get_mut(&id) {
+            Some(file_info) => {
+                file_info.content_blocks.push((file_info.size, offset));
+                file_info.size += length;
+            }
+            None => {
+                return Err(Error::WrongWriterState(
+                    "[AddChunk] Unable to find the ID".to_string(),
+                ))
+            }
+        }
+        Ok(())
     }
 
+    /// Mark the file `id` as complete. No more content can be appended to it
     pub fn end_file(&mut self, id: ArchiveFileID) -> Result<(), Error> {
         check_state_file_opened!(&self.state, &id);
 
+        // In deduplication mode, the end of the content is the last chunk
+        if let Some(chunk) = self.dedup.as_mut().and_then(|dedup| dedup.finish(id)) {
+            self.add_chunk(id, &chunk)?;
+        }
+
         let hash = match &mut self.state {
             ArchiveWriterState::OpenedFiles { ids, hashes } => {
                 let hash = hashes.remove(&id).ok_or_else(|| {
                     Error::WrongWriterState("[EndFile] Unable to retrieve the hash".to_string())
                 })?;
                 vec_remove_item(ids, &id);
-                hash.finalize().try_into().or_else(
-                    // Never happens, as hash is a Sha256, to a Sha256Hash
-                    |_| {
-                        Err(Error::WrongWriterState(
-                            "[EndFile] Hash with wrong size".to_string(),
-                        ))
-                    },
-                )?
+                hash.finalize().into()
             }
             _ => {
                 // Never happens, due to `check_state_file_opened!`
@@ -675,19 +1202,126 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
         };
 
         self.mark_continuous_block(id)?;
-        self.mark_eof(id)?;
+        self.mark_eof(id, hash)?;
         // Use std::io::Empty as a readable placeholder type
         ArchiveFileBlock::EndOfFile::<std::io::Empty> { id, hash }.dump(&mut self.dest)?;
+        if let Some((policy, rng)) = &mut self.padding {
+            let start = self
+                .ids_info
+                .get(&id)
+                .and_then(|file_info| file_info.offsets.first())
+                .copied()
+                .ok_or_else(|| {
+                    Error::WrongWriterState("[EndFile] Unable to find offset".to_string())
+                })?;
+            let footprint = self.dest.position() - start;
+            if let Some(length) = padding_length(footprint, policy.granularity) {
+                ArchiveFileBlock::Padding {
+                    length,
+                    data: Some(RandomReader(rng)),
+                }
+                .dump(&mut self.dest)?;
+            }
+        }
+        if let Some(progress) = &mut self.progress {
+            progress.completed(id);
+        }
 
         Ok(())
     }
 
+    /// Like `start_file`, returning a `FileWriter` handle on the new file
+    pub fn start_file_writer<'w>(
+        &'w mut self,
+        filename: &str,
+    ) -> Result<FileWriter<'w, 'a, W>, Error> {
+        let id = self.start_file(filename)?;
+        Ok(FileWriter { archive: self, id })
+    }
+
+    /// Return a `FileWriter` handle on the started file `id`
+    ///
+    /// The handle borrows the archive: to interleave the content of several
+    /// files, get a new handle for each write. Fails if `id` has not been
+    /// started, or has already been ended
+    pub fn file_writer<'w>(
+        &'w mut self,
+        id: ArchiveFileID,
+    ) -> Result<FileWriter<'w, 'a, W>, Error> {
+        check_state_file_opened!(&self.state, &id);
+        Ok(FileWriter { archive: self, id })
+    }
+
+    /// Add a whole file, made of `size` bytes read from `src`
     pub fn add_file<U: Read>(&mut self, filename: &str, size: u64, src: U) -> Result<(), Error> {
         let id = self.start_file(filename)?;
         self.append_file_content(id, size, src)?;
         self.end_file(id)
     }
 
+    /// Record `metadata` for the opened file `id`, replacing any previous one
+    ///
+    /// Hard links to the file share it. As hard links, metadata only lives in
+    /// the footer: it is not recovered by the fail-safe reader
+    pub fn set_file_metadata(
+        &mut self,
+        id: ArchiveFileID,
+        mut metadata: FileMetadata,
+    ) -> Result<(), Error> {
+        check_state_file_opened!(&self.state, &id);
+
+        if self.config.is_deterministic() {
+            metadata.mtime = None;
+        }
+        match self.ids_info.get_mut(&id) {
+            Some(file_info) => file_info.metadata = Some(metadata),
+            None => {
+                return Err(Error::WrongWriterState(
+                    "[SetFileMetadata] Unable to find the ID".to_string(),
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Like `add_file`, also recording `metadata` for the file
+    pub fn add_file_with_metadata<U: Read>(
+        &mut self,
+        filename: &str,
+        size: u64,
+        src: U,
+        metadata: FileMetadata,
+    ) -> Result<(), Error> {
+        let id = self.start_file(filename)?;
+        self.set_file_metadata(id, metadata)?;
+        self.append_file_content(id, size, src)?;
+        self.end_file(id)
+    }
+
+    /// Add `filename` as another name for the already added file `target`
+    ///
+    /// The content is stored once, and is available under both names. The
+    /// link only lives in the footer: it is not recovered by the fail-safe
+    /// reader
+    pub fn add_hardlink(&mut self, filename: &str, target: &str) -> Result<(), Error> {
+        check_state!(self.state, OpenedFiles);
+
+        if self.files_info.contains_key(filename) {
+            return Err(Error::DuplicateFilename);
+        }
+        let id = match self.files_info.get(target) {
+            Some(id) => *id,
+            None => {
+                return Err(Error::BadAPIArgument(format!(
+                    "[AddHardlink] Unknown target \"{}\"",
+                    target
+                )))
+            }
+        };
+        self.files_info.insert(filename.to_string(), id);
+        Ok(())
+    }
+
     /// Unwraps the inner writer
     pub fn into_raw(self) -> W {
         self.dest.into_raw()
@@ -698,95 +1332,546 @@ impl<'a, W: Write> ArchiveWriter<'a, W> {
     }
 }
 
-// -------- Reader --------
+/// Handle on a started file, appending what is written to its content
+///
+/// Each `write` call stores a new block of content: for many small writes,
+/// prefer buffering them. Writing once the file has been ended fails
+pub struct FileWriter<'w, 'a, W: 'a + Write> {
+    archive: &'w mut ArchiveWriter<'a, W>,
+    id: ArchiveFileID,
+}
 
-#[derive(Debug)]
-pub struct ArchiveFile<T: Read> {
-    /// File inside a MLA Archive
-    pub filename: String,
-    pub data: T,
-    pub size: u64,
+impl<'w, 'a, W: Write> FileWriter<'w, 'a, W> {
+    /// Id of the file, to get a handle back with `ArchiveWriter::file_writer`
+    pub fn id(&self) -> ArchiveFileID {
+        self.id
+    }
+
+    /// Mark the file as complete, as `ArchiveWriter::end_file`
+    pub fn end(self) -> Result<(), Error> {
+        self.archive.end_file(self.id)
+    }
 }
 
-#[derive(PartialEq, Debug)]
-enum BlocksToFileReaderState {
-    // Remaining size
-    InFile(usize),
-    Ready,
-    Finish,
+impl<'w, 'a, W: Write> Write for FileWriter<'w, 'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.archive
+            .append_file_content(self.id, buf.len() as u64, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.archive.flush()
+    }
 }
 
-#[derive(Debug)]
-pub struct BlocksToFileReader<'a, R: Read + Seek> {
-    /// This structure wraps the internals to get back a file's content
-    src: &'a mut R,
-    state: BlocksToFileReaderState,
-    /// id of the File being read
-    id: ArchiveFileID,
-    /// position in `offsets` of the last offset used
-    current_offset: usize,
-    /// List of offsets of continuous blocks corresponding to where the file can be read
-    offsets: Vec<u64>,
+/// Destination which can be shrunk, needed by `ArchiveWriter::append_to`
+pub trait Truncate {
+    /// Shrink the destination to `len` bytes
+    fn truncate(&mut self, len: u64) -> io::Result<()>;
 }
 
-impl<'a, R: Read + Seek> BlocksToFileReader<'a, R> {
-    fn new(src: &mut R, id: ArchiveFileID, offsets: Vec<u64>) -> BlocksToFileReader<R> {
-        BlocksToFileReader {
-            src,
-            state: BlocksToFileReaderState::Ready,
-            id,
-            current_offset: 0,
-            offsets,
-        }
+#[cfg(feature = "fs")]
+impl Truncate for std::fs::File {
+    fn truncate(&mut self, len: u64) -> io::Result<()> {
+        self.set_len(len)
     }
+}
 
-    /// Move `self.src` to the next continuous block
-    fn move_to_next_block(&mut self) -> Result<(), Error> {
-        self.current_offset += 1;
-        if self.current_offset >= self.offsets.len() {
+impl Truncate for io::Cursor<Vec<u8>> {
+    fn truncate(&mut self, len: u64) -> io::Result<()> {
+        self.get_mut().truncate(len as usize);
+        Ok(())
+    }
+}
+
+impl<T: Truncate + ?Sized> Truncate for &mut T {
+    fn truncate(&mut self, len: u64) -> io::Result<()> {
+        (**self).truncate(len)
+    }
+}
+
+/// Data needed to resume an archive, read from its last layer
+struct ArchiveResumeInfo {
+    files_info: HashMap<String, ArchiveFileID>,
+    ids_info: HashMap<ArchiveFileID, FileInfo>,
+    next_id: ArchiveFileID,
+    /// Position of the `EndOfArchiveData` block, where new data will be written
+    data_end: u64,
+}
+
+impl ArchiveResumeInfo {
+    fn read_from<R: Read + Seek>(src: &mut R) -> Result<Self, Error> {
+        let ArchiveFooter { files_info: footer } =
+            ArchiveFooter::deserialize_from(&mut *src, MLA_FORMAT_VERSION)?;
+
+        // The footer is just after the EndOfArchiveData block
+        let pos = src.seek(SeekFrom::End(-4))?;
+        let len = src.read_u32::<LittleEndian>()? as u64;
+        let data_end = pos
+            .checked_sub(len + 1)
+            .ok_or(Error::DeserializationError)?;
+        src.seek(SeekFrom::Start(data_end))?;
+        match ArchiveFileBlock::from(&mut *src)? {
+            ArchiveFileBlock::EndOfArchiveData => {}
+            _ => {
+                return Err(Error::WrongReaderState(
+                    "[ArchiveWriter] Footer must follow a EndOfArchiveData".to_string(),
+                ))
+            }
+        }
+
+        // IDs are not stored in the footer. As they are given in increasing
+        // order, and each file starts at a new offset, they can be recovered
+        // by sorting files on their first offset; hardlinks share it
+        let mut entries = footer.into_iter().collect::<Vec<_>>();
+        if entries.iter().any(|(_, finfo)| finfo.offsets.is_empty()) {
+            return Err(Error::WrongReaderState(
+                "[ArchiveWriter] A file must have at least one offset".to_string(),
+            ));
+        }
+        entries.sort_by_key(|(_, finfo)| finfo.offsets[0]);
+        let mut files_info = HashMap::new();
+        let mut ids_info = HashMap::new();
+        let mut next_id = 0;
+        let mut last_offset = None;
+        for (filename, finfo) in entries {
+            if last_offset != Some(finfo.offsets[0]) {
+                last_offset = Some(finfo.offsets[0]);
+                next_id += 1;
+            }
+            files_info.insert(filename, next_id - 1);
+            ids_info.insert(next_id - 1, finfo);
+        }
+
+        // Double-check the assumption on the last file, as new IDs must not
+        // collide with the existing ones
+        if let Some(offset) = last_offset {
+            src.seek(SeekFrom::Start(offset))?;
+            match ArchiveFileBlock::from(&mut *src)? {
+                ArchiveFileBlock::FileStart { id, .. } if id + 1 == next_id => {}
+                _ => {
+                    return Err(Error::WrongReaderState(
+                        "[ArchiveWriter] Unexpected file IDs".to_string(),
+                    ))
+                }
+            }
+        }
+
+        Ok(ArchiveResumeInfo {
+            files_info,
+            ids_info,
+            next_id,
+            data_end,
+        })
+    }
+
+    /// Index the chunks of the deduplicated files, read from `src`, so that
+    /// new files can reference them
+    fn stored_chunks<R: Read + Seek>(&self, src: &mut R) -> Result<Deduplicator, Error> {
+        let mut offsets = self
+            .ids_info
+            .values()
+            .filter(|file_info| file_info.deduplicated)
+            .flat_map(|file_info| file_info.content_blocks.iter().map(|(_, offset)| *offset))
+            .collect::<Vec<_>>();
+        // Read the chunks in the order of the archive
+        offsets.sort_unstable();
+        offsets.dedup();
+        let mut dedup = Deduplicator::default();
+        for offset in offsets {
+            src.seek(SeekFrom::Start(offset))?;
+            match ArchiveFileBlock::from(&mut *src)? {
+                ArchiveFileBlock::FileContent { length, .. }
+                    if length <= dedup::MAX_CHUNK_SIZE as u64 =>
+                {
+                    let mut chunk = vec![0u8; length as usize];
+                    src.read_exact(&mut chunk)?;
+                    dedup.insert(Sha256::digest(&chunk).into(), offset);
+                }
+                _ => {
+                    return Err(Error::WrongReaderState(
+                        "[ArchiveWriter] A chunk must be a FileContent".to_string(),
+                    ))
+                }
+            }
+        }
+        Ok(dedup)
+    }
+}
+
+impl<'a, W: 'a + Read + Write + Seek + Truncate> ArchiveWriter<'a, W> {
+    /// Reopen the finalized archive `dest` to add new files
+    ///
+    /// `config` must provide a recipient's private key if the archive is
+    /// encrypted. Existing data is neither decompressed nor re-encrypted: only
+    /// the last, partial, compressed block and encrypted chunk are rewritten,
+    /// followed by the new files and an updated footer. Files already present
+    /// cannot be modified, and the compression level used is the default one.
+    ///
+    /// AES-GCM archives are refused (`Error::AppendUnsupportedCipher`), as
+    /// rewriting their last chunk would reuse its nonce; with AES-GCM-SIV,
+    /// this only leaks whether both versions of the chunk are equal. Signed
+    /// archives are refused too.
+    ///
+    /// New files of a deduplicated archive are deduplicated, against the
+    /// chunks already stored as well: these are read once to be indexed.
+    /// Writer options, which are not recorded in the archive, are not
+    /// restored: see `append_to_with_options` to set them again.
+    ///
+    /// `dest` is truncated once the archive has been read: until the new
+    /// writer is finalized, the archive is only readable in fail-safe mode,
+    /// as an interrupted one.
+    pub fn append_to(dest: W, config: ArchiveReaderConfig) -> Result<Self, Error> {
+        Self::append_to_with_options(dest, config, ArchiveWriterConfig::default())
+    }
+
+    /// As `append_to`, with the writer options of `options`
+    ///
+    /// Only the options which do not change the archive configuration are
+    /// used: the padding policy (which requires an archive without the
+    /// compression layer, see `ArchiveWriterConfig::with_padding_policy`) and
+    /// the output digest, which then covers the whole archive, original bytes
+    /// included. Layers, compression and encryption settings are the ones of
+    /// the archive.
+    pub fn append_to_with_options(
+        mut dest: W,
+        mut config: ArchiveReaderConfig,
+        options: ArchiveWriterConfig,
+    ) -> Result<Self, Error> {
+        dest.seek(SeekFrom::Start(0))?;
+        let header = ArchiveHeader::from(&mut dest)?;
+        if header.format_version != MLA_FORMAT_VERSION {
+            // The footer would not match the header version
+            return Err(Error::BadAPIArgument(
+                "[ArchiveWriter] Only the current format version can be appended to".to_string(),
+            ));
+        }
+        let dedup = header.config.dedup;
+        config.load_persistent(header.config)?;
+        let layers = config.layers_enabled;
+        if layers.contains(Layers::SIGN) {
+            // The new content could not be signed by the original signers
+            return Err(Error::BadAPIArgument(
+                "[ArchiveWriter] Signed archives cannot be appended to".to_string(),
+            ));
+        }
+        if layers.contains(Layers::ENCRYPT)
+            && config.encryption_cipher() != EncryptionCipher::AesGcmSiv256
+        {
+            return Err(Error::AppendUnsupportedCipher);
+        }
+        if options.padding_policy().is_some() && layers.contains(Layers::COMPRESS) {
+            return Err(ConfigError::PaddingWithCompression.into());
+        }
+
+        // Pin the current position (after header) as the new 0
+        let mut raw_src = Box::new(RawLayerReader::new(dest));
+        let header_size = raw_src.stream_position()?;
+        raw_src.reset_position()?;
+        let mut src: Box<dyn 'a + LayerReader<'a, W>> = raw_src;
+        if layers.contains(Layers::ENCRYPT) {
+            src = Box::new(EncryptionLayerReader::new(src, &config.encrypt)?);
+        }
+
+        // Only the end of the data is rewritten: find the last compressed
+        // block to keep, and read the beginning of the following one
+        let (resume_info, deduplicator, compressed_sizes, block_prefix, mut src) =
+            if layers.contains(Layers::COMPRESS) {
+                let mut comp = CompressionLayerReader::new(src, &config.compress)?;
+                comp.initialize()?;
+                let resume_info = ArchiveResumeInfo::read_from(&mut comp)?;
+                let deduplicator = if dedup {
+                    Some(resume_info.stored_chunks(&mut comp)?)
+                } else {
+                    None
+                };
+                let block_size = config.compression_block_size() as u64;
+                let block_num = (resume_info.data_end / block_size) as usize;
+                let compressed_sizes = comp
+                    .compressed_sizes()?
+                    .get(..block_num)
+                    .ok_or(Error::DeserializationError)?
+                    .to_vec();
+                let block_start = block_num as u64 * block_size;
+                comp.seek(SeekFrom::Start(block_start))?;
+                let mut block_prefix = vec![0u8; (resume_info.data_end - block_start) as usize];
+                comp.read_exact(&mut block_prefix)?;
+                let src = comp.into_inner().ok_or_else(|| {
+                    Error::AssertionError("Compression layer without inner layer".to_string())
+                })?;
+                (
+                    resume_info,
+                    deduplicator,
+                    Some(compressed_sizes),
+                    block_prefix,
+                    src,
+                )
+            } else {
+                src.initialize()?;
+                let resume_info = ArchiveResumeInfo::read_from(&mut src)?;
+                let deduplicator = if dedup {
+                    Some(resume_info.stored_chunks(&mut src)?)
+                } else {
+                    None
+                };
+                (resume_info, deduplicator, None, Vec::new(), src)
+            };
+        let kept_size: u64 = match &compressed_sizes {
+            Some(sizes) => sizes.iter().map(|size| *size as u64).sum(),
+            None => resume_info.data_end,
+        };
+
+        // Same for the last encrypted chunk
+        let (kept_raw_size, chunk_num, chunk_prefix) = if layers.contains(Layers::ENCRYPT) {
+            let chunk_size = config.encryption_chunk_size() as u64;
+            let chunk_num = kept_size / chunk_size;
+            let chunk_start = chunk_num * chunk_size;
+            src.seek(SeekFrom::Start(chunk_start))?;
+            let mut chunk_prefix = vec![0u8; (kept_size - chunk_start) as usize];
+            src.read_exact(&mut chunk_prefix)?;
+            let chunk_num = u32::try_from(chunk_num).map_err(|_| Error::DeserializationError)?;
+            (
+                chunk_num as u64 * (chunk_size + TAG_LENGTH as u64),
+                chunk_num,
+                chunk_prefix,
+            )
+        } else {
+            (kept_size, 0, Vec::new())
+        };
+
+        let mut dest = src.into_raw();
+        dest.truncate(header_size + kept_raw_size)?;
+        // The digest covers the whole output: hash the bytes kept
+        let digest = if options.is_output_digest_enabled() {
+            dest.seek(SeekFrom::Start(0))?;
+            let mut hash = Sha256::new();
+            io::copy(
+                &mut (&mut dest).take(header_size + kept_raw_size),
+                &mut hash,
+            )?;
+            Some((DigestHandle::default(), hash))
+        } else {
+            None
+        };
+        dest.seek(SeekFrom::Start(header_size + kept_raw_size))?;
+
+        // Resume layers, rewriting the partial chunk and block
+        let mut dest: Box<dyn LayerWriter<W>> = Box::new(RawLayerWriter::new(dest));
+        let digest = match digest {
+            Some((handle, hash)) => {
+                dest = Box::new(DigestLayerWriter::resume(dest, handle.clone(), hash));
+                Some(handle)
+            }
+            None => None,
+        };
+        if layers.contains(Layers::ENCRYPT) {
+            dest = Box::new(EncryptionLayerWriter::resume(
+                dest,
+                &config.encrypt,
+                chunk_num,
+            )?);
+            dest.write_all(&chunk_prefix)?;
+        }
+        if let Some(compressed_sizes) = compressed_sizes {
+            dest = Box::new(CompressionLayerWriter::resume(
+                dest,
+                &config.compress,
+                compressed_sizes,
+            ));
+            dest.write_all(&block_prefix)?;
+        }
+        let mut final_dest = Box::new(PositionLayerWriter::new(dest));
+        final_dest.set_position(resume_info.data_end);
+
+        // Seeded from the one of `options`, if any
+        let padding = options
+            .padding_policy()
+            .map(|policy| (policy, options.encrypt.derive_rng()));
+
+        let mut writer_config = options;
+        writer_config.set_layers(layers);
+        if dedup {
+            writer_config.with_dedup();
+        }
+        Ok(ArchiveWriter {
+            config: writer_config,
+            dest: final_dest,
+            state: ArchiveWriterState::OpenedFiles {
+                ids: Vec::new(),
+                hashes: HashMap::new(),
+            },
+            files_info: resume_info.files_info,
+            ids_info: resume_info.ids_info,
+            next_id: resume_info.next_id,
+            current_id: resume_info.next_id,
+            digest,
+            padding,
+            progress: None,
+            dedup: deduplicator,
+        })
+    }
+}
+
+// -------- Reader --------
+
+#[derive(Debug)]
+pub struct ArchiveFile<T: Read> {
+    /// File inside a MLA Archive
+    pub filename: String,
+    pub data: T,
+    pub size: u64,
+}
+
+#[derive(PartialEq, Debug)]
+enum BlocksToFileReaderState {
+    // Before the `FileStart` block, the file id being unknown
+    Start,
+    // Remaining size
+    InFile(usize),
+    Ready,
+    Finish,
+}
+
+/// Position of a reader in the continuous blocks of a file, see
+/// `BlocksToFileReader`
+#[derive(Debug)]
+struct FileBlocksCursor {
+    state: BlocksToFileReaderState,
+    /// id of the File being read
+    id: ArchiveFileID,
+    /// position in `offsets` of the last offset used
+    current_offset: usize,
+    /// List of offsets of continuous blocks corresponding to where the file can be read
+    offsets: Vec<u64>,
+    /// Whether `offsets` are the ones of the `FileContent` blocks making a
+    /// deduplicated file, to be read one after the other whatever their file
+    chunks: bool,
+}
+
+impl FileBlocksCursor {
+    fn new(id: ArchiveFileID, offsets: Vec<u64>) -> Self {
+        FileBlocksCursor {
+            state: BlocksToFileReaderState::Ready,
+            id,
+            current_offset: 0,
+            offsets,
+            chunks: false,
+        }
+    }
+
+    /// Cursor on the chunks of a deduplicated file, listed by its
+    /// `content_blocks`
+    fn chunks(content_blocks: &[(u64, u64)]) -> Self {
+        FileBlocksCursor {
+            state: BlocksToFileReaderState::Ready,
+            id: 0,
+            current_offset: 0,
+            offsets: content_blocks.iter().map(|(_, offset)| *offset).collect(),
+            chunks: true,
+        }
+    }
+
+    /// Cursor on the `FileStart` block of a file, at `offsets[0]`
+    fn at_start(offsets: Vec<u64>) -> Self {
+        FileBlocksCursor {
+            state: BlocksToFileReaderState::Start,
+            id: 0,
+            current_offset: 0,
+            offsets,
+            chunks: false,
+        }
+    }
+
+    /// Move `src` to the next continuous block
+    fn move_to_next_block<R: Seek>(&mut self, src: &mut R) -> Result<(), Error> {
+        self.current_offset += 1;
+        if self.current_offset >= self.offsets.len() {
             return Err(Error::WrongReaderState(
                 "[BlocksToFileReader] No more continuous blocks".to_string(),
             ));
         }
-        self.src
-            .seek(SeekFrom::Start(self.offsets[self.current_offset]))?;
+        src.seek(SeekFrom::Start(self.offsets[self.current_offset]))?;
         Ok(())
     }
-}
 
-impl<'a, T: Read + Seek> Read for BlocksToFileReader<'a, T> {
-    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+    /// Read the file content from `src`, which must be where the previous
+    /// call left it
+    fn read<R: Read + Seek>(&mut self, src: &mut R, into: &mut [u8]) -> io::Result<usize> {
         let (remaining, count) = match self.state {
+            BlocksToFileReaderState::Start => match ArchiveFileBlock::from(&mut *src)? {
+                ArchiveFileBlock::FileStart { id, .. } => {
+                    self.id = id;
+                    self.state = BlocksToFileReaderState::Ready;
+                    return self.read(src, into);
+                }
+                _ => {
+                    return Err(Error::WrongReaderState(
+                        "[BlocksToFileReader] A file must start with a FileStart".to_string(),
+                    )
+                    .into());
+                }
+            },
+            BlocksToFileReaderState::Ready if self.chunks => {
+                // Each chunk is in its own block, wherever it is
+                let offset = match self.offsets.get(self.current_offset) {
+                    Some(offset) => *offset,
+                    None => {
+                        self.state = BlocksToFileReaderState::Finish;
+                        return Ok(0);
+                    }
+                };
+                self.current_offset += 1;
+                src.seek(SeekFrom::Start(offset))?;
+                match ArchiveFileBlock::from(&mut *src)? {
+                    ArchiveFileBlock::FileContent { length, .. } => {
+                        let count = src.by_ref().take(length).read(into)?;
+                        (length as usize - count, count)
+                    }
+                    _ => {
+                        return Err(Error::WrongReaderState(
+                            "[BlocksToFileReader] A chunk must be a FileContent".to_string(),
+                        )
+                        .into());
+                    }
+                }
+            }
             BlocksToFileReaderState::Ready => {
                 // Start a new block FileContent
-                match ArchiveFileBlock::from(&mut self.src)? {
+                match ArchiveFileBlock::from(&mut *src)? {
                     ArchiveFileBlock::FileContent { length, id, .. } => {
                         if id != self.id {
-                            self.move_to_next_block()?;
-                            return self.read(into);
+                            self.move_to_next_block(src)?;
+                            return self.read(src, into);
                         }
-                        let count = self.src.by_ref().take(length as u64).read(into)?;
+                        let count = src.by_ref().take(length).read(into)?;
                         let length_usize = length as usize;
                         (length_usize - count, count)
                     }
                     ArchiveFileBlock::EndOfFile { id, .. } => {
                         if id != self.id {
-                            self.move_to_next_block()?;
-                            return self.read(into);
+                            self.move_to_next_block(src)?;
+                            return self.read(src, into);
                         }
                         self.state = BlocksToFileReaderState::Finish;
                         return Ok(0);
                     }
                     ArchiveFileBlock::FileStart { id, .. } => {
                         if id != self.id {
-                            self.move_to_next_block()?;
-                            return self.read(into);
+                            self.move_to_next_block(src)?;
+                            return self.read(src, into);
                         }
                         return Err(Error::WrongReaderState(
                             "[BlocksToFileReader] Start with a wrong block type".to_string(),
                         )
                         .into());
                     }
+                    ArchiveFileBlock::Padding { .. } => {
+                        // Padding follows an EndOfFile: these continuous
+                        // blocks are over
+                        self.move_to_next_block(src)?;
+                        return self.read(src, into);
+                    }
                     ArchiveFileBlock::EndOfArchiveData => {
                         return Err(Error::WrongReaderState(
                             "[BlocksToFileReader] Try to read the end of the archive".to_string(),
@@ -796,7 +1881,7 @@ impl<'a, T: Read + Seek> Read for BlocksToFileReader<'a, T> {
                 }
             }
             BlocksToFileReaderState::InFile(remaining) => {
-                let count = self.src.by_ref().take(remaining as u64).read(into)?;
+                let count = src.by_ref().take(remaining as u64).read(into)?;
                 (remaining - count, count)
             }
             BlocksToFileReaderState::Finish => {
@@ -813,90 +1898,602 @@ impl<'a, T: Read + Seek> Read for BlocksToFileReader<'a, T> {
     }
 }
 
-#[derive(Serialize, Deserialize)]
-#[cfg_attr(test, derive(PartialEq, Debug))]
-struct FileInfo {
-    /// File information to save in the footer
-    ///
-    /// Offsets of continuous chunks of `ArchiveFileBlock`
-    offsets: Vec<u64>,
-    /// Size of the file, in bytes
-    size: u64,
-    /// Offset of the ArchiveFileBlock::EndOfFile
-    ///
-    /// This offset is used to retrieve information from the EoF tag, such as
-    /// the file hash
-    eof_offset: u64,
+#[derive(Debug)]
+pub struct BlocksToFileReader<'a, R: Read + Seek> {
+    /// This structure wraps the internals to get back a file's content
+    src: &'a mut R,
+    cursor: FileBlocksCursor,
 }
 
-pub struct ArchiveReader<'a, R: 'a + Read + Seek> {
-    /// MLA Archive format Reader
+impl<'a, R: Read + Seek> BlocksToFileReader<'a, R> {
+    fn new(src: &mut R, id: ArchiveFileID, offsets: Vec<u64>) -> BlocksToFileReader<'_, R> {
+        BlocksToFileReader {
+            src,
+            cursor: FileBlocksCursor::new(id, offsets),
+        }
+    }
+}
 
-    /// User's reading configuration
-    // config is not used for now after reader creation,
-    // but it could in the future
-    #[allow(dead_code)]
-    config: ArchiveReaderConfig,
-    /// Source
-    src: Box<dyn 'a + LayerReader<'a, R>>,
-    /// Metadata (from footer if any)
-    metadata: Option<ArchiveFooter>,
+impl<'a, T: Read + Seek> Read for BlocksToFileReader<'a, T> {
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        self.cursor.read(self.src, into)
+    }
 }
 
-impl<'b, R: 'b + Read + Seek> ArchiveReader<'b, R> {
-    pub fn from_config(mut src: R, mut config: ArchiveReaderConfig) -> Result<Self, Error> {
-        let header = ArchiveHeader::from(&mut src)?;
-        config.load_persistent(header.config)?;
+// -------- Entries iteration --------
 
-        // Pin the current position (after header) as the new 0
-        let mut raw_src = Box::new(RawLayerReader::new(src));
-        raw_src.reset_position()?;
+/// Source of the `EntryReader`s of an `Entries`, shared between them
+struct SharedSource<'a, T: Read + Seek> {
+    src: &'a mut T,
+    /// Current position of `src`, to only seek when an entry reads elsewhere
+    position: u64,
+}
 
-        // Enable layers depending on user option. Order is relevant
-        let mut src: Box<dyn 'b + LayerReader<'b, R>> = raw_src;
-        if config.layers_enabled.contains(Layers::ENCRYPT) {
-            src = Box::new(EncryptionLayerReader::new(src, &config.encrypt)?);
+impl<'a, T: Read + Seek> SharedSource<'a, T> {
+    fn seek_to(&mut self, position: u64) -> io::Result<()> {
+        if self.position != position {
+            self.src.seek(SeekFrom::Start(position))?;
+            self.position = position;
         }
-        if config.layers_enabled.contains(Layers::COMPRESS) {
-            src = Box::new(CompressionLayerReader::new(src)?);
-        }
-        src.initialize()?;
-
-        // Read the footer
-        let metadata = Some(ArchiveFooter::deserialize_from(&mut src)?);
-
-        // Reset the position for further uses
-        src.seek(SeekFrom::Start(0))?;
+        Ok(())
+    }
+}
 
-        Ok(ArchiveReader {
-            config,
-            src,
-            metadata,
-        })
+impl<'a, T: Read + Seek> Read for SharedSource<'a, T> {
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        let count = self.src.read(into)?;
+        self.position += count as u64;
+        Ok(count)
     }
+}
 
-    pub fn new(src: R) -> Result<Self, Error> {
-        Self::from_config(src, ArchiveReaderConfig::new())
+impl<'a, T: Read + Seek> Seek for SharedSource<'a, T> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.position = self.src.seek(pos)?;
+        Ok(self.position)
     }
+}
 
-    /// Return an iterator on filenames present in the archive
-    ///
-    /// Order is not relevant, and may change
-    pub fn list_files(&self) -> Result<impl Iterator<Item = &String>, Error> {
-        if let Some(ArchiveFooter { files_info, .. }) = &self.metadata {
-            Ok(files_info.keys())
-        } else {
-            Err(Error::MissingMetadata)
+/// Iterator on the entries of an archive, in archive order, see
+/// `ArchiveReader::entries`
+pub struct Entries<'a, T: Read + Seek> {
+    files_info: &'a HashMap<String, FileInfo>,
+    /// Names of the entries not yielded yet, by position of their first block
+    names: std::vec::IntoIter<&'a String>,
+    src: Rc<RefCell<SharedSource<'a, T>>>,
+    progress: Option<ProgressHandle>,
+}
+
+impl<'a, T: Read + Seek> Entries<'a, T> {
+    fn open(&self, name: &str) -> Result<EntryReader<'a, T>, Error> {
+        let file_info = &self.files_info[name];
+        // Blocks are only read on the first read of the entry, so that
+        // skipping it is free
+        let position = *file_info.offsets.first().ok_or_else(|| {
+            Error::WrongReaderState(
+                "[ArchiveReader] A file must have at least one offset".to_string(),
+            )
+        })?;
+        if let Some(progress) = &self.progress {
+            progress.notify(|observer| observer.entry_started(name));
         }
+        Ok(EntryReader {
+            name: name.to_string(),
+            size: file_info.size,
+            metadata: file_info.metadata.clone(),
+            src: Rc::clone(&self.src),
+            cursor: if file_info.deduplicated {
+                FileBlocksCursor::chunks(&file_info.content_blocks)
+            } else {
+                FileBlocksCursor::at_start(file_info.offsets.clone())
+            },
+            position,
+            progress: self.progress.clone(),
+        })
     }
+}
 
-    pub fn get_hash<'a>(&'a mut self, filename: &str) -> Result<Option<Sha256Hash>, Error> {
+impl<'a, T: Read + Seek> Iterator for Entries<'a, T> {
+    type Item = Result<EntryReader<'a, T>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let name = self.names.next()?;
+        Some(self.open(name))
+    }
+}
+
+/// Entry of an archive, yielded by `Entries`. Its content is read through
+/// `Read`, by blocks, without being buffered
+pub struct EntryReader<'a, T: Read + Seek> {
+    pub name: String,
+    /// Size of the content, in bytes
+    pub size: u64,
+    pub metadata: Option<FileMetadata>,
+    src: Rc<RefCell<SharedSource<'a, T>>>,
+    cursor: FileBlocksCursor,
+    /// Position of `src` for the next read
+    position: u64,
+    /// Reporter of the read content, until it is complete
+    progress: Option<ProgressHandle>,
+}
+
+impl<'a, T: Read + Seek> Read for EntryReader<'a, T> {
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        if self.cursor.state == BlocksToFileReaderState::Finish {
+            return Ok(0);
+        }
+        let mut src = self.src.borrow_mut();
+        src.seek_to(self.position)?;
+        let count = self
+            .cursor
+            .read(&mut *src, into)
+            .map_err(|err| Error::from(err).with_entry(&self.name))?;
+        self.position = src.position;
+
+        if let Some(progress) = &self.progress {
+            let name = &self.name;
+            if count > 0 {
+                progress.notify(|observer| observer.entry_data(name, count as u64));
+            } else if self.cursor.state == BlocksToFileReaderState::Finish {
+                progress.notify(|observer| observer.entry_completed(name));
+            }
+        }
+        Ok(count)
+    }
+}
+
+/// Find the `ArchiveFileBlock::FileContent` of file `id` by walking its
+/// continuous blocks, for archives without `FileInfo::content_blocks`
+fn find_content_blocks<R: Read + Seek>(
+    src: &mut R,
+    id: ArchiveFileID,
+    offsets: &[u64],
+) -> Result<Vec<(u64, u64)>, Error> {
+    let mut content_blocks = Vec::new();
+    let mut file_pos = 0;
+    for offset in offsets {
+        src.seek(SeekFrom::Start(*offset))?;
+        loop {
+            let block_offset = src.stream_position()?;
+            match ArchiveFileBlock::from(&mut *src)? {
+                ArchiveFileBlock::FileStart { id: block_id, .. } if block_id == id => {}
+                ArchiveFileBlock::FileContent {
+                    id: block_id,
+                    length,
+                    ..
+                } if block_id == id => {
+                    content_blocks.push((file_pos, block_offset));
+                    file_pos += length;
+                    src.seek(SeekFrom::Current(length as i64))?;
+                }
+                ArchiveFileBlock::EndOfFile { id: block_id, .. } if block_id == id => {
+                    return Ok(content_blocks);
+                }
+                // Block of another file: these continuous blocks are over
+                _ => break,
+            }
+        }
+    }
+    Ok(content_blocks)
+}
+
+/// Reader on a file's content supporting `Seek`, see
+/// `ArchiveReader::get_file_seek`
+///
+/// Seeking only reads the header of the content block holding the new
+/// position: the underlying layers directly jump to the corresponding
+/// compressed block and encrypted chunk.
+pub struct SeekableFileReader<'a, R: Read + Seek> {
+    src: &'a mut R,
+    /// Position of each content block's data in the file, with the block offset
+    content_blocks: Vec<(u64, u64)>,
+    size: u64,
+    /// Current position in the file
+    pos: u64,
+    /// Bytes remaining in the current content block, if `src` is positioned
+    /// at `pos`
+    remaining: Option<u64>,
+}
+
+impl<'a, R: Read + Seek> SeekableFileReader<'a, R> {
+    /// Move `src` to the current position, in the content block holding it
+    fn sync_src(&mut self) -> Result<u64, Error> {
+        // Blocks are sorted by position, the first one starting at 0
+        let index = match self
+            .content_blocks
+            .binary_search_by_key(&self.pos, |(pos, _)| *pos)
+        {
+            Ok(index) => index,
+            // Checked by `get_file_seek`, but the footer may still be crafted
+            Err(0) => {
+                return Err(Error::WrongReaderState(
+                    "[SeekableFileReader] Content blocks must start at position 0".to_string(),
+                ))
+            }
+            Err(index) => index - 1,
+        };
+        let (block_pos, block_offset) = self.content_blocks[index];
+        self.src.seek(SeekFrom::Start(block_offset))?;
+        let length = match ArchiveFileBlock::from(&mut *self.src)? {
+            ArchiveFileBlock::FileContent { length, .. } => length,
+            _ => {
+                return Err(Error::WrongReaderState(
+                    "[SeekableFileReader] Offset must point to a FileContent".to_string(),
+                ))
+            }
+        };
+        let inside_block = self.pos - block_pos;
+        if inside_block >= length {
+            return Err(Error::WrongReaderState(
+                "[SeekableFileReader] Content blocks do not match the file size".to_string(),
+            ));
+        }
+        self.src.seek(SeekFrom::Current(inside_block as i64))?;
+        Ok(length - inside_block)
+    }
+}
+
+impl<'a, R: Read + Seek> Read for SeekableFileReader<'a, R> {
+    fn read(&mut self, into: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.size || into.is_empty() {
+            return Ok(0);
+        }
+        let remaining = match self.remaining {
+            Some(remaining) if remaining > 0 => remaining,
+            _ => self.sync_src()?,
+        };
+        let count = self.src.by_ref().take(remaining).read(into)?;
+        if count == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "[SeekableFileReader] Truncated content block",
+            ));
+        }
+        self.pos += count as u64;
+        self.remaining = Some(remaining - count as u64);
+        Ok(count)
+    }
+}
+
+impl<'a, R: Read + Seek> Seek for SeekableFileReader<'a, R> {
+    /// Seeking past the end is allowed; reads then return no data
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(offset) => checked_add_signed(self.pos, offset),
+            SeekFrom::End(offset) => checked_add_signed(self.size, offset),
+        };
+        match new_pos {
+            Some(new_pos) => {
+                if new_pos != self.pos {
+                    self.pos = new_pos;
+                    self.remaining = None;
+                }
+                Ok(new_pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative position",
+            )),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+struct FileInfo {
+    /// File information to save in the footer
+    ///
+    /// Offsets of continuous chunks of `ArchiveFileBlock`
+    offsets: Vec<u64>,
+    /// Size of the file, in bytes
+    size: u64,
+    /// Offset of the ArchiveFileBlock::EndOfFile
+    ///
+    /// This offset is used to retrieve information from the EoF tag, such as
+    /// the file hash
+    eof_offset: u64,
+    /// Offsets of the file's `ArchiveFileBlock::FileContent`, each with the
+    /// position of its data in the file
+    ///
+    /// This index is used to seek in the file. Format v1 and v2 archives lack
+    /// it
+    content_blocks: Vec<(u64, u64)>,
+    /// Metadata, if recorded. Format v1 to v3 archives lack it
+    metadata: Option<FileMetadata>,
+    /// SHA-256 of the file content, also recorded in its `EndOfFile` block
+    ///
+    /// Having it in the footer avoids reading each file to list hashes.
+    /// Format v1 to v3 archives lack it
+    hash: Option<Sha256Hash>,
+    /// Whether the content is deduplicated (see
+    /// `ArchiveWriterConfig::with_dedup`): `content_blocks` then lists the
+    /// chunks making it, which may belong to other files, and is the only way
+    /// to read it. Format v1 to v4 archives lack it
+    deduplicated: bool,
+}
+
+/// `FileInfo` as stored in format v1 and v2 archives
+#[derive(Deserialize)]
+struct FileInfoV1 {
+    offsets: Vec<u64>,
+    size: u64,
+    eof_offset: u64,
+}
+
+impl From<FileInfoV1> for FileInfo {
+    fn from(finfo: FileInfoV1) -> Self {
+        FileInfo {
+            offsets: finfo.offsets,
+            size: finfo.size,
+            eof_offset: finfo.eof_offset,
+            content_blocks: Vec::new(),
+            metadata: None,
+            hash: None,
+            deduplicated: false,
+        }
+    }
+}
+
+/// `FileInfo` as stored in format v3 archives
+#[derive(Deserialize)]
+struct FileInfoV3 {
+    offsets: Vec<u64>,
+    size: u64,
+    eof_offset: u64,
+    content_blocks: Vec<(u64, u64)>,
+}
+
+impl From<FileInfoV3> for FileInfo {
+    fn from(finfo: FileInfoV3) -> Self {
+        FileInfo {
+            offsets: finfo.offsets,
+            size: finfo.size,
+            eof_offset: finfo.eof_offset,
+            content_blocks: finfo.content_blocks,
+            metadata: None,
+            hash: None,
+            deduplicated: false,
+        }
+    }
+}
+
+/// `FileInfo` as stored in format v4 archives
+#[derive(Deserialize)]
+struct FileInfoV4 {
+    offsets: Vec<u64>,
+    size: u64,
+    eof_offset: u64,
+    content_blocks: Vec<(u64, u64)>,
+    metadata: Option<FileMetadata>,
+    hash: Option<Sha256Hash>,
+}
+
+impl From<FileInfoV4> for FileInfo {
+    fn from(finfo: FileInfoV4) -> Self {
+        FileInfo {
+            offsets: finfo.offsets,
+            size: finfo.size,
+            eof_offset: finfo.eof_offset,
+            content_blocks: finfo.content_blocks,
+            metadata: finfo.metadata,
+            hash: finfo.hash,
+            deduplicated: false,
+        }
+    }
+}
+
+/// Size of a serialized `ArchiveFileBlock::EndOfFile`: type, id and hash
+const EOF_BLOCK_SIZE: u64 = 1 + 8 + 32;
+
+/// Information on a file, read from the archive footer, see
+/// `ArchiveReader::list_files_info`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct ArchiveFileInfo<'a> {
+    pub name: &'a str,
+    /// Size of the file content, in bytes
+    pub size: u64,
+    /// Position of the first block of the file in the archive data, before
+    /// compression and encryption
+    pub offset: u64,
+    /// Bytes taken by the file blocks in the archive data, headers included,
+    /// before compression and encryption. `None` if the file is interleaved
+    /// with others
+    pub footprint: Option<u64>,
+    /// SHA-256 of the file content, if recorded in the footer (format v4)
+    pub hash: Option<&'a Sha256Hash>,
+}
+
+impl<'a> ArchiveFileInfo<'a> {
+    fn new(name: &'a str, finfo: &'a FileInfo) -> Self {
+        let offset = finfo.offsets.first().copied().unwrap_or(0);
+        let footprint = if finfo.offsets.len() == 1 {
+            Some(finfo.eof_offset + EOF_BLOCK_SIZE - offset)
+        } else {
+            None
+        };
+        ArchiveFileInfo {
+            name,
+            size: finfo.size,
+            offset,
+            footprint,
+            hash: finfo.hash.as_ref(),
+        }
+    }
+}
+
+/// Integrity of a file, see `ArchiveReader::check_integrity`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum FileIntegrityStatus {
+    /// The content has been read through every layer, and its size and hash
+    /// are the recorded ones
+    Valid,
+    /// The content could not be read, for instance because of a wrong
+    /// encryption tag
+    ReadError(String),
+    /// The size of the content is not the one recorded in the footer
+    SizeMismatch { expected: u64, actual: u64 },
+    /// The hash of the content is not the one recorded at the end of the file
+    HashMismatch,
+}
+
+impl std::fmt::Display for FileIntegrityStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FileIntegrityStatus::Valid => write!(f, "ok"),
+            FileIntegrityStatus::ReadError(error) => write!(f, "unreadable ({})", error),
+            FileIntegrityStatus::SizeMismatch { expected, actual } => {
+                write!(
+                    f,
+                    "size mismatch ({} bytes instead of {})",
+                    actual, expected
+                )
+            }
+            FileIntegrityStatus::HashMismatch => write!(f, "hash mismatch"),
+        }
+    }
+}
+
+/// Outcome of `ArchiveReader::check_integrity`
+#[derive(Debug)]
+pub struct IntegrityReport {
+    /// Status of each file, sorted by name
+    pub files: Vec<(String, FileIntegrityStatus)>,
+}
+
+impl IntegrityReport {
+    /// Return whether every file is valid
+    pub fn is_valid(&self) -> bool {
+        self.files
+            .iter()
+            .all(|(_, status)| *status == FileIntegrityStatus::Valid)
+    }
+}
+
+pub struct ArchiveReader<'a, R: 'a + Read + Seek> {
+    /// MLA Archive format Reader
+
+    /// User's reading configuration
+    config: ArchiveReaderConfig,
+    /// Source
+    src: Box<dyn 'a + LayerReader<'a, R>>,
+    /// Metadata (from footer if any)
+    metadata: Option<ArchiveFooter>,
+}
+
+impl<'b, R: 'b + Read + Seek> ArchiveReader<'b, R> {
+    pub fn from_config(mut src: R, mut config: ArchiveReaderConfig) -> Result<Self, Error> {
+        let archive_start = src.stream_position()?;
+        let header = ArchiveHeader::from(&mut src)?;
+        let format_version = header.format_version;
+        config.load_persistent(header.config)?;
+
+        // Authenticate the archive, if requested, before going further
+        let header_end = src.stream_position()?;
+        verify_signatures(&mut src, archive_start, &config.sign)?;
+        src.seek(SeekFrom::Start(header_end))?;
+
+        // Pin the current position (after header) as the new 0
+        let mut raw_src = Box::new(RawLayerReader::new(src));
+        raw_src.reset_position()?;
+
+        // Enable layers depending on user option. Order is relevant
+        let mut src: Box<dyn 'b + LayerReader<'b, R>> = raw_src;
+        if let Some(handle) = &config.progress {
+            src = Box::new(ProgressLayerReader::new(src, handle.clone()));
+        }
+        if config.layers_enabled.contains(Layers::SIGN) {
+            src = Box::new(SignatureLayerReader::new(src, &config.sign));
+        }
+        if config.layers_enabled.contains(Layers::ENCRYPT) {
+            src = Box::new(EncryptionLayerReader::new(src, &config.encrypt)?);
+        }
+        if config.layers_enabled.contains(Layers::COMPRESS) {
+            src = Box::new(CompressionLayerReader::new(src, &config.compress)?);
+        }
+        if config.block_cache > 0 {
+            src = Box::new(CacheLayerReader::new(src, &config));
+        }
+        src.initialize()?;
+
+        // Read the footer
+        let metadata = Some(ArchiveFooter::deserialize_from(&mut src, format_version)?);
+
+        // Reset the position for further uses
+        src.seek(SeekFrom::Start(0))?;
+
+        Ok(ArchiveReader {
+            config,
+            src,
+            metadata,
+        })
+    }
+
+    pub fn new(src: R) -> Result<Self, Error> {
+        Self::from_config(src, ArchiveReaderConfig::new())
+    }
+
+    /// Public keys of the archive signers, as announced by its header
+    ///
+    /// These keys are only authenticated if verification keys have been set,
+    /// see `ArchiveReaderConfig::add_verification_keys`
+    pub fn signers(&self) -> &[ed25519_dalek::PublicKey] {
+        self.config.sign.signers()
+    }
+
+    /// Return an iterator on filenames present in the archive
+    ///
+    /// Order is not relevant, and may change
+    pub fn list_files(&self) -> Result<impl Iterator<Item = &String>, Error> {
+        if let Some(ArchiveFooter { files_info, .. }) = &self.metadata {
+            Ok(files_info.keys())
+        } else {
+            Err(Error::MissingMetadata)
+        }
+    }
+
+    /// Return an iterator on the information of the files present in the
+    /// archive (name, size, offset, ...)
+    ///
+    /// Information is read from the footer, without reading the files. Order
+    /// is not relevant, and may change
+    pub fn list_files_info(&self) -> Result<impl Iterator<Item = ArchiveFileInfo<'_>>, Error> {
+        if let Some(ArchiveFooter { files_info }) = &self.metadata {
+            Ok(files_info
+                .iter()
+                .map(|(fname, finfo)| ArchiveFileInfo::new(fname, finfo)))
+        } else {
+            Err(Error::MissingMetadata)
+        }
+    }
+
+    /// Return the metadata recorded for `filename`, or `None` if the file is
+    /// not in the archive or has no metadata
+    pub fn get_metadata(&self, filename: &str) -> Result<Option<&FileMetadata>, Error> {
+        if let Some(ArchiveFooter { files_info }) = &self.metadata {
+            Ok(files_info
+                .get(filename)
+                .and_then(|finfo| finfo.metadata.as_ref()))
+        } else {
+            Err(Error::MissingMetadata)
+        }
+    }
+
+    /// Return the SHA-256 of the content of `filename`, or `None` if there is
+    /// no such file
+    ///
+    /// The hash is taken from the footer if recorded there, without reading
+    /// the file. Otherwise, it is read from the file `EndOfFile` block
+    pub fn get_hash(&mut self, filename: &str) -> Result<Option<Sha256Hash>, Error> {
         if let Some(ArchiveFooter { files_info }) = &self.metadata {
             // Get file relative information
             let file_info = match files_info.get(filename) {
                 None => return Ok(None),
                 Some(finfo) => finfo,
             };
+            if let Some(hash) = file_info.hash {
+                return Ok(Some(hash));
+            }
             // Set the inner layer at the start of the EoF tag
             self.src.seek(SeekFrom::Start(file_info.eof_offset))?;
 
@@ -916,7 +2513,7 @@ impl<'b, R: 'b + Read + Seek> ArchiveReader<'b, R> {
     pub fn get_file<'a>(
         &'a mut self,
         filename: String,
-    ) -> Result<Option<ArchiveFile<BlocksToFileReader<Box<dyn 'b + LayerReader<'b, R>>>>>, Error>
+    ) -> Result<Option<ArchiveFile<BlocksToFileReader<'a, Box<dyn 'b + LayerReader<'b, R>>>>>, Error>
     {
         if let Some(ArchiveFooter { files_info }) = &self.metadata {
             // Get file relative information
@@ -924,6 +2521,17 @@ impl<'b, R: 'b + Read + Seek> ArchiveReader<'b, R> {
                 None => return Ok(None),
                 Some(finfo) => finfo,
             };
+            if file_info.deduplicated {
+                // Its chunks are read wherever they are
+                return Ok(Some(ArchiveFile {
+                    filename,
+                    data: BlocksToFileReader {
+                        src: &mut self.src,
+                        cursor: FileBlocksCursor::chunks(&file_info.content_blocks),
+                    },
+                    size: file_info.size,
+                }));
+            }
             if file_info.offsets.is_empty() {
                 return Err(Error::WrongReaderState(
                     "[ArchiveReader] A file must have at least one offset".to_string(),
@@ -933,7 +2541,9 @@ impl<'b, R: 'b + Read + Seek> ArchiveReader<'b, R> {
             self.src.seek(SeekFrom::Start(file_info.offsets[0]))?;
 
             // Read file information header
-            let id_file_block = match ArchiveFileBlock::from(&mut self.src)? {
+            let id_file_block = match ArchiveFileBlock::from(&mut self.src)
+                .map_err(|err| err.with_entry(&filename))?
+            {
                 ArchiveFileBlock::FileStart { id, .. } => id,
                 _ => {
                     return Err(Error::WrongReaderState(
@@ -954,29 +2564,232 @@ impl<'b, R: 'b + Read + Seek> ArchiveReader<'b, R> {
             Err(Error::MissingMetadata)
         }
     }
-}
 
-// This code is very similar with MLAArchiveReader
+    /// Like `get_file`, but the returned file also implements `Seek`
+    ///
+    /// Thanks to the index of content blocks in the footer, reading at a given
+    /// position only decompresses and decrypts the blocks around it. Format v1
+    /// and v2 archives lack this index: the file's blocks are walked first.
+    #[allow(clippy::type_complexity)]
+    pub fn get_file_seek<'a>(
+        &'a mut self,
+        filename: String,
+    ) -> Result<Option<ArchiveFile<SeekableFileReader<'a, Box<dyn 'b + LayerReader<'b, R>>>>>, Error>
+    {
+        if let Some(ArchiveFooter { files_info }) = &self.metadata {
+            // Get file relative information
+            let file_info = match files_info.get(&filename) {
+                None => return Ok(None),
+                Some(finfo) => finfo,
+            };
+            let content_blocks = if file_info.content_blocks.is_empty() && file_info.size > 0 {
+                if file_info.offsets.is_empty() {
+                    return Err(Error::WrongReaderState(
+                        "[ArchiveReader] A file must have at least one offset".to_string(),
+                    ));
+                }
+                self.src.seek(SeekFrom::Start(file_info.offsets[0]))?;
+                let id = match ArchiveFileBlock::from(&mut self.src)? {
+                    ArchiveFileBlock::FileStart { id, .. } => id,
+                    _ => {
+                        return Err(Error::WrongReaderState(
+                            "[ArchiveReader] A file must start with a FileStart".to_string(),
+                        ));
+                    }
+                };
+                find_content_blocks(&mut self.src, id, &file_info.offsets)?
+            } else {
+                file_info.content_blocks.clone()
+            };
+            // A malformed footer must not make the reader look before the
+            // first block
+            if file_info.size > 0 && content_blocks.first().map(|(pos, _)| *pos) != Some(0) {
+                return Err(Error::WrongReaderState(
+                    "[ArchiveReader] Content blocks must start at position 0".to_string(),
+                ));
+            }
 
-pub struct ArchiveFailSafeReader<'a, R: 'a + Read> {
-    /// MLA Archive format Reader (fail-safe)
+            Ok(Some(ArchiveFile {
+                filename,
+                data: SeekableFileReader {
+                    src: &mut self.src,
+                    content_blocks,
+                    size: file_info.size,
+                    pos: 0,
+                    remaining: None,
+                },
+                size: file_info.size,
+            }))
+        } else {
+            Err(Error::MissingMetadata)
+        }
+    }
 
-    /// User's reading configuration
-    // config is not used for now after reader creation,
-    // but it could in the future
-    #[allow(dead_code)]
-    config: ArchiveReaderConfig,
-    /// Source
-    src: Box<dyn 'a + LayerFailSafeReader<'a, R>>,
-}
+    /// Return an iterator on the entries of the archive, in archive order,
+    /// whose content can be read as they come
+    ///
+    /// Contents are read by blocks, without buffering them, so that memory
+    /// use does not depend on the size of the entries. Entries are ordered by
+    /// the position of their first block: reading them in that order reads
+    /// the archive linearly, and skipped entries are not read at all.
+    /// Interleaved entries, or entries read out of order, are still valid, at
+    /// the cost of seeks.
+    ///
+    /// Entries are reported to the progress observer of the archive, if any
+    /// (see `ArchiveReaderConfig::with_progress_observer`).
+    pub fn entries<'a>(
+        &'a mut self,
+    ) -> Result<Entries<'a, Box<dyn 'b + LayerReader<'b, R>>>, Error> {
+        let files_info = match &self.metadata {
+            Some(ArchiveFooter { files_info }) => files_info,
+            None => return Err(Error::MissingMetadata),
+        };
+        let mut names: Vec<&String> = files_info.keys().collect();
+        names.sort_by_key(|name| (files_info[*name].offsets.first().copied(), *name));
+
+        self.src.seek(SeekFrom::Start(0))?;
+        Ok(Entries {
+            files_info,
+            names: names.into_iter(),
+            src: Rc::new(RefCell::new(SharedSource {
+                src: &mut self.src,
+                position: 0,
+            })),
+            progress: self.config.progress.clone(),
+        })
+    }
+
+    /// Read the content of `filename`, starting at `offset`, into `buf`
+    ///
+    /// Return the number of bytes read, which is lower than `buf.len()` only
+    /// if the end of the file is reached, or `None` if the file is not in the
+    /// archive
+    pub fn read_at(
+        &mut self,
+        filename: &str,
+        offset: u64,
+        buf: &mut [u8],
+    ) -> Result<Option<usize>, Error> {
+        let mut file = match self.get_file_seek(filename.to_string())? {
+            Some(file) => file,
+            None => return Ok(None),
+        };
+        file.data.seek(SeekFrom::Start(offset))?;
+        let mut read = 0;
+        while read < buf.len() {
+            let count = file.data.read(&mut buf[read..])?;
+            if count == 0 {
+                break;
+            }
+            read += count;
+        }
+        Ok(Some(read))
+    }
+
+    /// Read every file through all the layers, checking their size and hash
+    /// against the ones recorded when the archive was created
+    ///
+    /// Encryption tags, and the signature if verification keys are set, are
+    /// checked along the way. Errors on a file are reported in its status;
+    /// errors preventing to read the archive index are returned.
+    pub fn check_integrity(&mut self) -> Result<IntegrityReport, Error> {
+        let mut fnames: Vec<String> = self.list_files()?.cloned().collect();
+        fnames.sort();
+        let mut files = Vec::with_capacity(fnames.len());
+        for fname in fnames {
+            let status = self.check_file_integrity(&fname);
+            files.push((fname, status));
+        }
+        Ok(IntegrityReport { files })
+    }
+
+    fn check_file_integrity(&mut self, filename: &str) -> FileIntegrityStatus {
+        let expected_hash = match self.get_hash(filename) {
+            Ok(Some(hash)) => hash,
+            Ok(None) => return FileIntegrityStatus::ReadError("missing hash".to_string()),
+            Err(error) => return FileIntegrityStatus::ReadError(error.to_string()),
+        };
+        let mut file = match self.get_file(filename.to_string()) {
+            Ok(Some(file)) => file,
+            Ok(None) => return FileIntegrityStatus::ReadError("missing file".to_string()),
+            Err(error) => return FileIntegrityStatus::ReadError(error.to_string()),
+        };
+        let mut hash = Sha256::new();
+        let size = match io::copy(&mut file.data, &mut hash) {
+            Ok(size) => size,
+            Err(error) => return FileIntegrityStatus::ReadError(error.to_string()),
+        };
+        if size != file.size {
+            return FileIntegrityStatus::SizeMismatch {
+                expected: file.size,
+                actual: size,
+            };
+        }
+        if hash.finalize()[..] != expected_hash[..] {
+            return FileIntegrityStatus::HashMismatch;
+        }
+        FileIntegrityStatus::Valid
+    }
+}
+
+// This code is very similar with MLAArchiveReader
+
+pub struct ArchiveFailSafeReader<'a, R: 'a + Read> {
+    /// MLA Archive format Reader (fail-safe)
+
+    /// User's reading configuration
+    config: ArchiveReaderConfig,
+    /// Source
+    src: Box<dyn 'a + LayerFailSafeReader<'a, R>>,
+    /// Set by the encryption layer, if any, once it returns unauthenticated
+    /// data
+    unauthenticated: Option<Arc<AtomicBool>>,
+}
 
 // Size of the repaired file blocks
 const CACHE_SIZE: usize = 8 * 1024 * 1024; // 8MB
 
+/// Recovery status of a file, after a fail-safe conversion
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum FileRecoveryStatus {
+    /// The whole file has been recovered, and its hash checked
+    Complete,
+    /// Only the first `offset` bytes of the file have been recovered
+    Truncated { offset: u64 },
+    /// The first `offset` bytes of the file have been recovered, but the last
+    /// ones come from a truncated encrypted chunk, which cannot be
+    /// authenticated
+    Unauthenticated { offset: u64 },
+}
+
+impl std::fmt::Display for FileRecoveryStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FileRecoveryStatus::Complete => write!(f, "complete"),
+            FileRecoveryStatus::Truncated { offset } => {
+                write!(f, "truncated at offset {}", offset)
+            }
+            FileRecoveryStatus::Unauthenticated { offset } => {
+                write!(f, "truncated at offset {}, unauthenticated", offset)
+            }
+        }
+    }
+}
+
+/// Outcome of `ArchiveFailSafeReader::convert_to_archive_with_report`
+#[derive(Debug)]
+pub struct RepairReport {
+    /// Reason the conversion terminates, as returned by `convert_to_archive`
+    pub status: FailSafeReadError,
+    /// Recovery status of each file met, in the order they start in the
+    /// original archive
+    pub files: Vec<(String, FileRecoveryStatus)>,
+}
+
 /// Used to update the error state only if it was NoError
-/// ```
+/// ```ignore
 /// update_error!(error_var, FailSafeReadError::...)
-/// ``̀
+/// ```
 macro_rules! update_error {
     ( $x:ident = $y:expr ) => {
         #[allow(clippy::single_match)]
@@ -992,19 +2805,36 @@ macro_rules! update_error {
 impl<'b, R: 'b + Read> ArchiveFailSafeReader<'b, R> {
     pub fn from_config(mut src: R, mut config: ArchiveReaderConfig) -> Result<Self, Error> {
         let header = ArchiveHeader::from(&mut src)?;
+        if header.config.dedup {
+            // Chunks shared with previous files are only listed in the footer
+            return Err(Error::RepairUnsupportedDedup);
+        }
         config.load_persistent(header.config)?;
 
         // Enable layers depending on user option. Order is relevant
         let mut src: Box<dyn 'b + LayerFailSafeReader<'b, R>> =
             Box::new(RawLayerFailSafeReader::new(src));
+        if let Some(handle) = &config.progress {
+            src = Box::new(ProgressLayerFailSafeReader::new(src, handle.clone()));
+        }
+        if config.layers_enabled.contains(Layers::SIGN) {
+            src = Box::new(SignatureLayerFailSafeReader::new(src, &config.sign));
+        }
+        let mut unauthenticated = None;
         if config.layers_enabled.contains(Layers::ENCRYPT) {
-            src = Box::new(EncryptionLayerFailSafeReader::new(src, &config.encrypt)?);
+            let encrypt = EncryptionLayerFailSafeReader::new(src, &config.encrypt)?;
+            unauthenticated = Some(encrypt.unauthenticated_flag());
+            src = Box::new(encrypt);
         }
         if config.layers_enabled.contains(Layers::COMPRESS) {
-            src = Box::new(CompressionLayerFailSafeReader::new(src)?);
+            src = Box::new(CompressionLayerFailSafeReader::new(src, &config.compress)?);
         }
 
-        Ok(Self { config, src })
+        Ok(Self {
+            config,
+            src,
+            unauthenticated,
+        })
     }
 
     pub fn new(src: R) -> Result<Self, Error> {
@@ -1014,11 +2844,20 @@ impl<'b, R: 'b + Read> ArchiveFailSafeReader<'b, R> {
     /// Fail-safe / best-effort conversion of the current archive to a correct
     /// one. On success, returns the reason conversion terminates (ideally,
     /// EndOfOriginalArchiveData)
-    #[allow(clippy::cognitive_complexity)]
     pub fn convert_to_archive<W: Write>(
         &mut self,
         output: &mut ArchiveWriter<W>,
     ) -> Result<FailSafeReadError, Error> {
+        Ok(self.convert_to_archive_with_report(output)?.status)
+    }
+
+    /// Like `convert_to_archive`, also reporting how much of each file has
+    /// been recovered
+    #[allow(clippy::cognitive_complexity)]
+    pub fn convert_to_archive_with_report<W: Write>(
+        &mut self,
+        output: &mut ArchiveWriter<W>,
+    ) -> Result<RepairReport, Error> {
         let mut error = FailSafeReadError::NoError;
 
         // Associate an id retrieved from the archive to repair, to the
@@ -1030,6 +2869,20 @@ impl<'b, R: 'b + Read> ArchiveFailSafeReader<'b, R> {
         let mut id_failsafe_done = Vec::new();
         // Associate an id retrieved from the archive with its ongoing Hash
         let mut id_failsafe2hash: HashMap<ArchiveFileID, Sha256> = HashMap::new();
+        // Associate an id retrieved from the archive with the size recovered
+        let mut id_failsafe2size: HashMap<ArchiveFileID, u64> = HashMap::new();
+        // IDs from the archive, in their starting order
+        let mut id_failsafe_order = Vec::new();
+        // IDs from the archive whose content is partly unauthenticated
+        let mut id_failsafe_unauthenticated = Vec::new();
+        let unauthenticated = self.unauthenticated.clone();
+        let is_unauthenticated = move || {
+            unauthenticated
+                .as_ref()
+                .is_some_and(|flag| flag.load(Ordering::SeqCst))
+        };
+        // Entries reported to the progress observer, if any
+        let mut progress = self.config.progress.clone().map(EntriesProgress::new);
 
         'read_block: loop {
             match ArchiveFileBlock::from(&mut self.src) {
@@ -1074,6 +2927,11 @@ impl<'b, R: 'b + Read> ArchiveFailSafeReader<'b, R> {
                             };
                             id_failsafe2id_output.insert(id, id_output);
                             id_failsafe2hash.insert(id, Sha256::default());
+                            id_failsafe2size.insert(id, 0);
+                            id_failsafe_order.push(id);
+                            if let Some(progress) = &mut progress {
+                                progress.started(id, &filename);
+                            }
                         }
                         ArchiveFileBlock::FileContent { length, id, .. } => {
                             let id_output = match id_failsafe2id_output.get(&id) {
@@ -1097,8 +2955,11 @@ impl<'b, R: 'b + Read> ArchiveFailSafeReader<'b, R> {
                             let hash = id_failsafe2hash.get_mut(&id).expect(
                                 "`id_failsafe2hash` not more sync with `id_failsafe2id_output`",
                             );
+                            let size = id_failsafe2size.get_mut(&id).expect(
+                                "`id_failsafe2size` not more sync with `id_failsafe2id_output`",
+                            );
 
-                            let src = &mut (&mut self.src).take(length as u64);
+                            let src = &mut (&mut self.src).take(length);
                             'content: loop {
                                 let mut buf = Vec::with_capacity(CACHE_SIZE);
                                 'buf_fill: loop {
@@ -1113,12 +2974,19 @@ impl<'b, R: 'b + Read> ArchiveFailSafeReader<'b, R> {
                                             buf.push(mini_buf[0]);
                                         }
                                         Err(err) => {
-                                            // Stop reconstruction
+                                            // Stop reconstruction, keeping
+                                            // the bytes recovered until there
                                             output.append_file_content(
                                                 id_output,
                                                 buf.len() as u64,
                                                 buf.as_slice(),
                                             )?;
+                                            *size += buf.len() as u64;
+                                            if is_unauthenticated()
+                                                && !id_failsafe_unauthenticated.contains(&id)
+                                            {
+                                                id_failsafe_unauthenticated.push(id);
+                                            }
                                             update_error!(
                                                 error = FailSafeReadError::ErrorInFile(
                                                     err,
@@ -1139,6 +3007,15 @@ impl<'b, R: 'b + Read> ArchiveFailSafeReader<'b, R> {
                                     buf.as_slice(),
                                 )?;
                                 hash.update(buf.as_slice());
+                                *size += buf.len() as u64;
+                                if is_unauthenticated()
+                                    && !id_failsafe_unauthenticated.contains(&id)
+                                {
+                                    id_failsafe_unauthenticated.push(id);
+                                }
+                                if let Some(progress) = &progress {
+                                    progress.data(id, buf.len() as u64);
+                                }
                                 if buf.len() < CACHE_SIZE {
                                     // EOF
                                     break 'content;
@@ -1183,6 +3060,16 @@ impl<'b, R: 'b + Read> ArchiveFailSafeReader<'b, R> {
 
                             output.end_file(id_output)?;
                             id_failsafe_done.push(id);
+                            if let Some(progress) = &mut progress {
+                                progress.completed(id);
+                            }
+                        }
+                        ArchiveFileBlock::Padding { length, .. } => {
+                            let src = &mut (&mut self.src).take(length);
+                            if let Err(err) = io::copy(src, &mut io::sink()) {
+                                update_error!(error = FailSafeReadError::IOErrorOnNextBlock(err));
+                                break 'read_block;
+                            }
                         }
                         ArchiveFileBlock::EndOfArchiveData => {
                             // Expected end
@@ -1220,15 +3107,43 @@ impl<'b, R: 'b + Read> ArchiveFailSafeReader<'b, R> {
         }
 
         output.finalize()?;
-        Ok(error)
+
+        let files = id_failsafe_order
+            .iter()
+            .map(|id| {
+                let fname = id_failsafe2filename
+                    .get(id)
+                    .expect("`id_failsafe2filename` not more sync with `id_failsafe_order`")
+                    .clone();
+                let status = if id_failsafe_unauthenticated.contains(id) {
+                    FileRecoveryStatus::Unauthenticated {
+                        offset: id_failsafe2size[id],
+                    }
+                } else if id_failsafe_done.contains(id) {
+                    FileRecoveryStatus::Complete
+                } else {
+                    FileRecoveryStatus::Truncated {
+                        offset: id_failsafe2size[id],
+                    }
+                };
+                (fname, status)
+            })
+            .collect();
+        Ok(RepairReport {
+            status: error,
+            files,
+        })
     }
 }
 
 #[cfg(test)]
 pub(crate) mod tests {
     use super::*;
+    use crate::config::HybridPrivateKey;
+    use crate::errors::ConfigError;
+    use crate::helpers::linear_extract;
     use ed25519_parser::{parse_openssl_ed25519_privkey, parse_openssl_ed25519_pubkey};
-    use hex;
+
     use rand::SeedableRng;
     use rand_chacha::ChaChaRng;
     use std::io::{Cursor, Empty, Read};
@@ -1237,9 +3152,13 @@ pub(crate) mod tests {
     #[test]
     fn read_dump_header() {
         let header = ArchiveHeader {
+            format_version: MLA_FORMAT_VERSION,
             config: ArchivePersistentConfig {
                 layers_enabled: Layers::default(),
                 encrypt: None,
+                compress: None,
+                sign: None,
+                dedup: false,
             },
         };
         let mut buf = Vec::new();
@@ -1280,6 +3199,72 @@ pub(crate) mod tests {
         println!("{:?}", buf);
     }
 
+    #[test]
+    fn footer_layout() {
+        // Layout of the footer in the current format version. Changing it
+        // requires a new `MLA_FORMAT_VERSION`, with a reader for the previous
+        // layout in `ArchiveFooter::deserialize_from`
+        let mut files_info = HashMap::new();
+        files_info.insert("a".to_string(), 0);
+        let mut ids_info = HashMap::new();
+        ids_info.insert(
+            0,
+            FileInfo {
+                offsets: vec![1],
+                size: 2,
+                eof_offset: 3,
+                content_blocks: vec![(0, 4)],
+                metadata: None,
+                hash: Some([5; 32]),
+                deduplicated: true,
+            },
+        );
+        let mut buf = Vec::new();
+        ArchiveFooter::serialize_into(&mut buf, &files_info, &ids_info).unwrap();
+
+        let mut expected = Vec::new();
+        // files_info: one entry, named "a"
+        expected.extend_from_slice(&1u64.to_le_bytes());
+        expected.extend_from_slice(&1u64.to_le_bytes());
+        expected.extend_from_slice(b"a");
+        // offsets, size, eof_offset
+        expected.extend_from_slice(&1u64.to_le_bytes());
+        expected.extend_from_slice(&1u64.to_le_bytes());
+        expected.extend_from_slice(&2u64.to_le_bytes());
+        expected.extend_from_slice(&3u64.to_le_bytes());
+        // content_blocks
+        expected.extend_from_slice(&1u64.to_le_bytes());
+        expected.extend_from_slice(&0u64.to_le_bytes());
+        expected.extend_from_slice(&4u64.to_le_bytes());
+        // metadata, hash, deduplicated
+        expected.push(0);
+        expected.push(1);
+        expected.extend_from_slice(&[5; 32]);
+        expected.push(1);
+        // footer length
+        let len = expected.len() as u32;
+        expected.extend_from_slice(&len.to_le_bytes());
+        assert_eq!(buf, expected);
+
+        let footer =
+            ArchiveFooter::deserialize_from(Cursor::new(&buf), MLA_FORMAT_VERSION).unwrap();
+        assert_eq!(footer.files_info.get("a"), ids_info.get(&0));
+
+        // A footer written with another layout is refused, even if the fields
+        // of the expected one could be read from it
+        let mut longer = buf[..buf.len() - 4].to_vec();
+        longer.push(0);
+        longer.extend_from_slice(&(len + 1).to_le_bytes());
+        assert!(matches!(
+            ArchiveFooter::deserialize_from(Cursor::new(&longer), MLA_FORMAT_VERSION),
+            Err(Error::DeserializationError)
+        ));
+        assert!(matches!(
+            ArchiveFooter::deserialize_from(Cursor::new(&buf), 4),
+            Err(Error::DeserializationError)
+        ));
+    }
+
     #[test]
     fn blocks_to_file() {
         // Create several blocks
@@ -1316,7 +3301,7 @@ pub(crate) mod tests {
         expected_output.extend(fake_content);
         expected_output.extend(fake_content2);
         assert_eq!(output, expected_output);
-        assert_eq!(reader.state, BlocksToFileReaderState::Finish);
+        assert_eq!(reader.cursor.state, BlocksToFileReaderState::Finish);
     }
 
     #[test]
@@ -1366,6 +3351,204 @@ pub(crate) mod tests {
         assert_eq!(rez2, vec![5, 6, 7, 8, 9, 10, 11, 12]);
     }
 
+    #[test]
+    fn writer_custom_rng() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = StaticSecret::new(&mut rng);
+
+        let build = |seed| {
+            let mut config = ArchiveWriterConfig::new();
+            config
+                .set_layers(Layers::default())
+                .add_public_keys(&[PublicKey::from(&key)])
+                // Deterministic RNG for the test only
+                .with_rng(ChaChaRng::seed_from_u64(seed));
+            let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+            mla.add_file("my_file", 4, &[1, 2, 3, 4][..]).unwrap();
+            mla.finalize().unwrap();
+            mla.into_raw()
+        };
+
+        // The RNG is the only source of randomness
+        let archive = build(1);
+        assert_eq!(archive, build(1));
+        assert_ne!(archive, build(2));
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_read = ArchiveReader::from_config(Cursor::new(archive), config).unwrap();
+        let mut file = mla_read.get_file("my_file".to_string()).unwrap().unwrap();
+        let mut rez = Vec::new();
+        file.data.read_to_end(&mut rez).unwrap();
+        assert_eq!(rez, vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn deterministic_archive() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = StaticSecret::new(&mut rng);
+        let metadata = FileMetadata {
+            mode: Some(0o640),
+            mtime: Some(1_600_000_000),
+            ..Default::default()
+        };
+
+        let build = |seed| {
+            let mut config = ArchiveWriterConfig::new();
+            config
+                .set_layers(Layers::default())
+                .add_public_keys(&[PublicKey::from(&key)])
+                .with_deterministic_seed(seed);
+            assert!(config.is_deterministic());
+            let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+            // Enough files for the HashMap order to vary between runs
+            for i in 0..32 {
+                let content = vec![i as u8; i];
+                mla.add_file_with_metadata(
+                    &format!("file{}", i),
+                    content.len() as u64,
+                    content.as_slice(),
+                    metadata.clone(),
+                )
+                .unwrap();
+            }
+            mla.finalize().unwrap();
+            mla.into_raw()
+        };
+
+        let archive = build([1; 32]);
+        assert_eq!(archive, build([1; 32]));
+        assert_ne!(archive, build([2; 32]));
+
+        // The key and nonce only depend on the seed, so AES-GCM is refused
+        let mut config = ArchiveWriterConfig::new();
+        config
+            .set_layers(Layers::ENCRYPT)
+            .add_public_keys(&[PublicKey::from(&key)])
+            .with_deterministic_seed([1; 32]);
+        assert_eq!(config.encryption_cipher(), EncryptionCipher::AesGcmSiv256);
+        config.with_encryption_cipher(EncryptionCipher::AesGcm256);
+        assert!(matches!(
+            ArchiveWriter::from_config(Vec::new(), config),
+            Err(Error::ConfigError(
+                ConfigError::DeterministicCipherNotMisuseResistant
+            ))
+        ));
+
+        // Modification times are not recorded
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mla_read = ArchiveReader::from_config(Cursor::new(archive), config).unwrap();
+        assert_eq!(
+            mla_read.get_metadata("file1").unwrap(),
+            Some(&FileMetadata {
+                mtime: None,
+                ..metadata
+            })
+        );
+    }
+
+    #[test]
+    fn padding() {
+        assert_eq!(padding_length(512, 512), None);
+        assert_eq!(padding_length(10, 0), None);
+        assert_eq!(
+            padding_length(10, 512),
+            Some(512 - 10 - PADDING_HEADER_SIZE)
+        );
+        // The padding header does not fit in the remaining bytes
+        assert_eq!(
+            padding_length(508, 512),
+            Some(1024 - 508 - PADDING_HEADER_SIZE)
+        );
+
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = StaticSecret::new(&mut rng);
+        let files = vec![
+            ("a".to_string(), vec![1u8; 10]),
+            ("b".to_string(), vec![2u8; 600]),
+            ("c".to_string(), Vec::new()),
+        ];
+        let config_with = |layers: Layers, policy: Option<PaddingPolicy>| {
+            let mut config = ArchiveWriterConfig::new();
+            config
+                .set_layers(layers)
+                .add_public_keys(&[PublicKey::from(&key)]);
+            if let Some(policy) = policy {
+                config.with_padding_policy(policy);
+            }
+            config
+        };
+        let build = |policy: Option<PaddingPolicy>, files: &[(String, Vec<u8>)]| {
+            let config = config_with(Layers::ENCRYPT, policy);
+            let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+            for (fname, content) in files {
+                mla.add_file(fname, content.len() as u64, content.as_slice())
+                    .unwrap();
+            }
+            mla.finalize().unwrap();
+            mla.into_raw()
+        };
+        let policy = PaddingPolicy {
+            granularity: 512,
+            max_decoy: 4096,
+        };
+
+        // With the default layers, entries would be padded before being
+        // compressed, and their stored sizes would differ again
+        assert!(matches!(
+            ArchiveWriter::from_config(Vec::new(), config_with(Layers::default(), Some(policy))),
+            Err(Error::ConfigError(ConfigError::PaddingWithCompression))
+        ));
+
+        // Stored sizes do not depend on the entry sizes, up to the granularity
+        let exact = PaddingPolicy {
+            granularity: 512,
+            max_decoy: 0,
+        };
+        let small = build(Some(exact), &[("a".to_string(), vec![1u8; 200])]);
+        let big = build(Some(exact), &[("a".to_string(), vec![2u8; 380])]);
+        assert_eq!(small.len(), big.len());
+        assert_ne!(
+            build(None, &[("a".to_string(), vec![1u8; 200])]).len(),
+            build(None, &[("a".to_string(), vec![2u8; 380])]).len()
+        );
+
+        let archive = build(Some(policy), &files);
+        assert!(archive.len() > build(None, &files).len() + 1024);
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_read = ArchiveReader::from_config(Cursor::new(&archive), config).unwrap();
+        for info in mla_read.list_files_info().unwrap() {
+            assert_eq!(info.offset % policy.granularity, 0);
+        }
+        for (fname, content) in &files {
+            let mut file = mla_read.get_file(fname.clone()).unwrap().unwrap();
+            let mut rez = Vec::new();
+            file.data.read_to_end(&mut rez).unwrap();
+            assert_eq!(&rez, content);
+        }
+
+        // Padding is skipped by linear readers
+        let mut export: HashMap<&String, Vec<u8>> =
+            files.iter().map(|(fname, _)| (fname, Vec::new())).collect();
+        linear_extract(&mut mla_read, &mut export).unwrap();
+        for (fname, content) in &files {
+            assert_eq!(&export[fname], content);
+        }
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_fsread =
+            ArchiveFailSafeReader::from_config(archive.as_slice(), config).unwrap();
+        let mut mla_w = ArchiveWriter::from_config(Vec::new(), ArchiveWriterConfig::new()).unwrap();
+        match mla_fsread.convert_to_archive(&mut mla_w).unwrap() {
+            FailSafeReadError::EndOfOriginalArchiveData => {}
+            status => panic!("Unexpected status: {}", status),
+        }
+    }
+
     #[allow(clippy::type_complexity)]
     pub(crate) fn build_archive(
         layers: Option<Layers>,
@@ -1470,6 +3653,195 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn file_writer_handles() {
+        let file = Vec::new();
+        let mut mla = ArchiveWriter::from_config(file, ArchiveWriterConfig::new()).unwrap();
+
+        // Interleave the content of two files through handles
+        let id1 = mla.start_file_writer("file1").unwrap().id();
+        let mut file2 = mla.start_file_writer("file2").unwrap();
+        file2.write_all(b"Hello").unwrap();
+        let id2 = file2.id();
+        mla.file_writer(id1).unwrap().write_all(b"ABC").unwrap();
+        mla.file_writer(id2).unwrap().write_all(b", world").unwrap();
+        mla.file_writer(id1).unwrap().write_all(b"DEF").unwrap();
+        mla.file_writer(id2).unwrap().end().unwrap();
+
+        // No more content once the file is ended
+        assert!(mla.file_writer(id2).is_err());
+        assert!(mla.append_file_content(id2, 1, &b"!"[..]).is_err());
+        mla.end_file(id1).unwrap();
+        mla.finalize().unwrap();
+
+        let mut mla_read =
+            ArchiveReader::from_config(Cursor::new(mla.into_raw()), ArchiveReaderConfig::new())
+                .unwrap();
+        for (fname, content) in &[("file1", &b"ABCDEF"[..]), ("file2", &b"Hello, world"[..])] {
+            let mut file = mla_read.get_file(fname.to_string()).unwrap().unwrap();
+            assert_eq!(file.size, content.len() as u64);
+            let mut rez = Vec::new();
+            file.data.read_to_end(&mut rez).unwrap();
+            assert_eq!(rez, *content);
+        }
+    }
+
+    #[test]
+    fn block_cache() {
+        // Build an archive with 3 interleaved files
+        let (mla, key, files) = build_archive(None, true);
+
+        let dest = mla.into_raw();
+        let buf = Cursor::new(dest.as_slice());
+        let mut config = ArchiveReaderConfig::new();
+        config
+            .add_private_keys(std::slice::from_ref(&key))
+            .with_block_cache(2);
+        let mut mla_read = ArchiveReader::from_config(buf, config).unwrap();
+
+        // Read twice, the second time from the cache
+        for _ in 0..2 {
+            for (fname, content) in &files {
+                let mut file = mla_read.get_file(fname.clone()).unwrap().unwrap();
+                let mut rez = Vec::new();
+                file.data.read_to_end(&mut rez).unwrap();
+                assert_eq!(&rez, content);
+            }
+        }
+    }
+
+    #[test]
+    fn get_file_seek() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = StaticSecret::new(&mut rng);
+        let mut config = ArchiveWriterConfig::new();
+        config.add_public_keys(&[PublicKey::from(&key)]);
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+
+        // Interleave a small file with a file spanning several compressed
+        // blocks, both split in several content blocks
+        let small: Vec<u8> = (0..100).collect();
+        let big: Vec<u8> = (0..5 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        let id_small = mla.start_file("small").unwrap();
+        let id_big = mla.start_file("big").unwrap();
+        for (part_small, part_big) in small.chunks(20).zip(big.chunks(1024 * 1024)) {
+            mla.append_file_content(id_small, part_small.len() as u64, part_small)
+                .unwrap();
+            mla.append_file_content(id_big, part_big.len() as u64, part_big)
+                .unwrap();
+        }
+        mla.end_file(id_small).unwrap();
+        mla.end_file(id_big).unwrap();
+        mla.add_file("empty", 0, std::io::empty()).unwrap();
+        mla.finalize().unwrap();
+        let files = vec![("small".to_string(), small), ("big".to_string(), big)];
+
+        let dest = mla.into_raw();
+        let buf = Cursor::new(dest.as_slice());
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_read = ArchiveReader::from_config(buf, config).unwrap();
+
+        for (fname, content) in &files {
+            let step = std::cmp::max(content.len() / 7, 1);
+            let mut file = mla_read.get_file_seek(fname.clone()).unwrap().unwrap();
+            // Backward and forward seeks, across content blocks
+            for start in (0..content.len()).step_by(step).rev() {
+                assert_eq!(
+                    file.data.seek(SeekFrom::Start(start as u64)).unwrap(),
+                    start as u64
+                );
+                let mut rez = vec![0; std::cmp::min(step + 3, content.len() - start)];
+                file.data.read_exact(&mut rez).unwrap();
+                assert_eq!(rez.as_slice(), &content[start..start + rez.len()]);
+            }
+            file.data.seek(SeekFrom::End(-2)).unwrap();
+            file.data.seek(SeekFrom::Current(-1)).unwrap();
+            let mut rez = Vec::new();
+            file.data.read_to_end(&mut rez).unwrap();
+            assert_eq!(rez.as_slice(), &content[content.len() - 3..]);
+            assert!(file
+                .data
+                .seek(SeekFrom::Current(-(content.len() as i64) - 1))
+                .is_err());
+
+            // Read at a given offset, including after the end
+            let mut rez = [0; 4];
+            let offset = content.len() as u64 / 2;
+            let count = mla_read.read_at(fname, offset, &mut rez).unwrap().unwrap();
+            assert_eq!(
+                &rez[..count],
+                &content[offset as usize..offset as usize + count]
+            );
+            let count = mla_read
+                .read_at(fname, content.len() as u64 + 1, &mut rez)
+                .unwrap()
+                .unwrap();
+            assert_eq!(count, 0);
+        }
+        assert_eq!(mla_read.read_at("empty", 0, &mut [0; 4]).unwrap(), Some(0));
+        assert!(mla_read
+            .read_at("unknown", 0, &mut [0; 4])
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn get_file_seek_malformed_footer() {
+        let mut config = ArchiveWriterConfig::new();
+        config.set_layers(Layers::EMPTY);
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        mla.add_file("data", 4, &b"ABCD"[..]).unwrap();
+        mla.add_file("empty", 0, std::io::empty()).unwrap();
+        mla.finalize().unwrap();
+        let archive = mla.into_raw();
+
+        // Rewrite the footer, which ends the archive without layers
+        let craft = |change: &dyn Fn(&mut HashMap<String, FileInfo>)| {
+            let mut footer =
+                ArchiveFooter::deserialize_from(Cursor::new(&archive), MLA_FORMAT_VERSION).unwrap();
+            change(&mut footer.files_info);
+            let footer_len = (&archive[archive.len() - 4..])
+                .read_u32::<LittleEndian>()
+                .unwrap();
+            let mut crafted = archive[..archive.len() - 4 - footer_len as usize].to_vec();
+            let mut files_info = HashMap::new();
+            let mut ids_info = HashMap::new();
+            for (id, (fname, finfo)) in footer.files_info.into_iter().enumerate() {
+                files_info.insert(fname, id as ArchiveFileID);
+                ids_info.insert(id as ArchiveFileID, finfo);
+            }
+            ArchiveFooter::serialize_into(&mut crafted, &files_info, &ids_info).unwrap();
+            crafted
+        };
+        let crafted_archives = [
+            // First content block not at position 0
+            craft(&|files_info| {
+                let finfo = files_info.get_mut("data").unwrap();
+                finfo.content_blocks[0].0 = 1;
+            }),
+            // Non-empty file without content blocks, none being found
+            craft(&|files_info| {
+                files_info.get_mut("empty").unwrap().size = 10;
+            }),
+        ];
+        for (crafted, fname) in crafted_archives.iter().zip(["data", "empty"]) {
+            let mut mla_read = ArchiveReader::from_config(
+                Cursor::new(crafted.as_slice()),
+                ArchiveReaderConfig::new(),
+            )
+            .unwrap();
+            assert!(matches!(
+                mla_read.get_file_seek(fname.to_string()),
+                Err(Error::WrongReaderState(_))
+            ));
+            assert!(matches!(
+                mla_read.read_at(fname, 0, &mut [0; 4]),
+                Err(Error::WrongReaderState(_))
+            ));
+        }
+    }
+
     #[test]
     fn mla_multi_layering() {
         // Test the building-then-reading of a file using different layering
@@ -1711,24 +4083,87 @@ pub(crate) mod tests {
     }
 
     #[test]
-    fn avoid_duplicate_filename() {
-        let buf = Vec::new();
-        let config = ArchiveWriterConfig::new();
-        let mut mla = ArchiveWriter::from_config(buf, config).unwrap();
-        mla.add_file("Test", 4, vec![1, 2, 3, 4].as_slice())
-            .unwrap();
-        assert!(mla
-            .add_file("Test", 4, vec![1, 2, 3, 4].as_slice())
-            .is_err());
-        assert!(mla.start_file("Test").is_err());
-    }
+    fn convert_failsafe_report() {
+        use rand::RngCore;
 
-    #[test]
-    fn check_file_size() {
-        // Build an archive with 3 non-interleaved files and another with
-        // interleaved files
-        for interleaved in &[false, true] {
-            let (mla, key, files) = build_archive(None, *interleaved);
+        // Use a deterministic RNG in tests, for reproductability. DO NOT DO THIS IS IN ANY RELEASED BINARY!
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = StaticSecret::new(&mut rng);
+        let mut config = ArchiveWriterConfig::new();
+        config
+            .set_layers(Layers::ENCRYPT)
+            .add_public_keys(&[PublicKey::from(&key)]);
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        let complete = vec![1u8; 1000];
+        let mut partial = vec![0u8; 200 * 1024];
+        rng.fill_bytes(&mut partial);
+        mla.add_file("complete", complete.len() as u64, complete.as_slice())
+            .unwrap();
+        mla.add_file("partial", partial.len() as u64, partial.as_slice())
+            .unwrap();
+        mla.finalize().unwrap();
+        let footer_size = bincode::serialized_size(&mla.files_info).unwrap() as usize + 4;
+        let dest = mla.into_raw();
+
+        // Truncate in the middle of the last encrypted chunk
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_fsread =
+            ArchiveFailSafeReader::from_config(&dest[..dest.len() - footer_size - 50_000], config)
+                .unwrap();
+        let mut mla_w = ArchiveWriter::new(Vec::new(), &[PublicKey::from(&key)]).unwrap();
+        let report = mla_fsread
+            .convert_to_archive_with_report(&mut mla_w)
+            .unwrap();
+        assert_eq!(
+            report.files[0],
+            ("complete".to_string(), FileRecoveryStatus::Complete)
+        );
+        let offset = match report.files[1] {
+            (ref fname, FileRecoveryStatus::Unauthenticated { offset }) if fname == "partial" => {
+                offset
+            }
+            _ => panic!("Unexpected report {:?}", report),
+        };
+        // The content of the truncated chunk is recovered too, without
+        // authentication
+        assert!(offset > 128 * 1024);
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_read =
+            ArchiveReader::from_config(Cursor::new(mla_w.into_raw()), config).unwrap();
+        let mut buf = Vec::new();
+        mla_read
+            .get_file("partial".to_string())
+            .unwrap()
+            .unwrap()
+            .data
+            .read_to_end(&mut buf)
+            .unwrap();
+        assert_eq!(buf.len() as u64, offset);
+        assert_eq!(buf.as_slice(), &partial[..buf.len()]);
+    }
+
+    #[test]
+    fn avoid_duplicate_filename() {
+        let buf = Vec::new();
+        let config = ArchiveWriterConfig::new();
+        let mut mla = ArchiveWriter::from_config(buf, config).unwrap();
+        mla.add_file("Test", 4, vec![1, 2, 3, 4].as_slice())
+            .unwrap();
+        assert!(mla
+            .add_file("Test", 4, vec![1, 2, 3, 4].as_slice())
+            .is_err());
+        assert!(mla.start_file("Test").is_err());
+    }
+
+    #[test]
+    fn check_file_size() {
+        // Build an archive with 3 non-interleaved files and another with
+        // interleaved files
+        for interleaved in &[false, true] {
+            let (mla, key, files) = build_archive(None, *interleaved);
 
             for (fname, data) in &files {
                 let id = mla.files_info.get(fname).unwrap();
@@ -1821,6 +4256,53 @@ pub(crate) mod tests {
             hasher.update(content);
             let result = hasher.finalize();
             assert_eq!(result.as_slice(), hash);
+
+            // Hashes are recorded in the footer, as in the EndOfFile blocks
+            let file_info = &mla_read.metadata.as_ref().unwrap().files_info[&filename];
+            assert_eq!(file_info.hash, Some(hash));
+            mla_read
+                .src
+                .seek(SeekFrom::Start(file_info.eof_offset))
+                .unwrap();
+            match ArchiveFileBlock::from(&mut mla_read.src).unwrap() {
+                ArchiveFileBlock::EndOfFile { hash: eof_hash, .. } => assert_eq!(eof_hash, hash),
+                _ => panic!("eof_offset must point to a EoF"),
+            }
+        }
+    }
+
+    #[test]
+    fn list_files_info() {
+        for interleaved in &[false, true] {
+            let (mla, key, files) = build_archive(None, *interleaved);
+
+            let dest = mla.into_raw();
+            let buf = Cursor::new(dest.as_slice());
+            let mut config = ArchiveReaderConfig::new();
+            config.add_private_keys(std::slice::from_ref(&key));
+            let mla_read = ArchiveReader::from_config(buf, config).unwrap();
+
+            let mut infos: Vec<ArchiveFileInfo> = mla_read.list_files_info().unwrap().collect();
+            infos.sort_by_key(|info| info.name);
+            assert_eq!(infos.len(), files.len());
+            for (info, (filename, content)) in infos.iter().zip(&files) {
+                assert_eq!(info.name, filename.as_str());
+                assert_eq!(info.size, content.len() as u64);
+                assert_eq!(info.hash.unwrap(), Sha256::digest(content).as_slice());
+            }
+
+            if *interleaved {
+                // Only my_file3 is written at once
+                let footprints: Vec<bool> =
+                    infos.iter().map(|info| info.footprint.is_some()).collect();
+                assert_eq!(footprints, vec![false, false, true]);
+            } else {
+                // Files follow each other
+                assert_eq!(infos[0].offset, 0);
+                for pair in infos.windows(2) {
+                    assert_eq!(pair[0].offset + pair[0].footprint.unwrap(), pair[1].offset);
+                }
+            }
         }
     }
 
@@ -1849,7 +4331,7 @@ pub(crate) mod tests {
         for i in 0..=255 {
             files.insert(
                 format!("file_{}", i).to_string(),
-                std::iter::repeat(i).take(0x1000).collect::<Vec<u8>>(),
+                std::iter::repeat_n(i, 0x1000).collect::<Vec<u8>>(),
             );
         }
 
@@ -1912,7 +4394,7 @@ pub(crate) mod tests {
             .rev()
             .map(|i| {
                 let id = name2id.get(&fnames[i]).unwrap();
-                mla.append_file_content(id.clone(), 32, &files.get(&fnames[i]).unwrap()[..32])
+                mla.append_file_content(*id, 32, &files.get(&fnames[i]).unwrap()[..32])
                     .unwrap();
             })
             .for_each(drop);
@@ -1922,7 +4404,7 @@ pub(crate) mod tests {
             .map(|i| {
                 let id = name2id.get(&fnames[i]).unwrap();
                 let data = &files.get(&fnames[i]).unwrap()[32..];
-                mla.append_file_content(id.clone(), data.len() as u64, data)
+                mla.append_file_content(*id, data.len() as u64, data)
                     .unwrap();
             })
             .for_each(drop);
@@ -1932,7 +4414,7 @@ pub(crate) mod tests {
             .rev()
             .map(|i| {
                 let id = name2id.get(&fnames[i]).unwrap();
-                mla.end_file(id.clone()).unwrap();
+                mla.end_file(*id).unwrap();
             })
             .for_each(drop);
 
@@ -2019,6 +4501,412 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn get_file_seek_format_v1() {
+        // Format v1 archives lack the content blocks index
+        let pem_priv: &'static [u8] = include_bytes!("../../samples/test25519.pem");
+        let mla_data: &'static [u8] = include_bytes!("../../samples/archive_v1.mla");
+        let files = make_format_regression_files();
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(&[parse_openssl_ed25519_privkey(pem_priv).unwrap()]);
+        let mut mla_read = ArchiveReader::from_config(Cursor::new(mla_data), config).unwrap();
+
+        for fname in &["simple", "file_42", "big"] {
+            let content = files.get(*fname).unwrap();
+            let mut file = mla_read.get_file_seek(fname.to_string()).unwrap().unwrap();
+            for start in &[content.len() - 10, 0, content.len() / 3] {
+                file.data.seek(SeekFrom::Start(*start as u64)).unwrap();
+                let mut rez = vec![0; 10];
+                file.data.read_exact(&mut rez).unwrap();
+                assert_eq!(rez.as_slice(), &content[*start..*start + 10]);
+            }
+        }
+    }
+
+    #[test]
+    fn custom_block_sizes() {
+        // Block sizes are recorded in the header, and used by both readers
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = StaticSecret::new(&mut rng);
+        let mut config = ArchiveWriterConfig::new();
+        config
+            .set_layers(Layers::default())
+            .add_public_keys(&[PublicKey::from(&key)])
+            .tune_for_entry_sizes(&[100, 200, 300]);
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        let files = make_format_regression_files();
+        for (fname, content) in files.iter() {
+            mla.add_file(fname, content.len() as u64, content.as_slice())
+                .unwrap();
+        }
+        mla.finalize().unwrap();
+        let mla_data = mla.into_raw();
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_read = ArchiveReader::from_config(Cursor::new(&mla_data), config).unwrap();
+        assert_eq!(mla_read.config.compression_block_size(), 1024 * 1024);
+        for (fname, content) in files.iter() {
+            let mut buf = Vec::new();
+            mla_read
+                .get_file(fname.clone())
+                .unwrap()
+                .unwrap()
+                .data
+                .read_to_end(&mut buf)
+                .unwrap();
+            assert_eq!(&buf, content);
+        }
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_fsread =
+            ArchiveFailSafeReader::from_config(mla_data.as_slice(), config).unwrap();
+        let mut mla_w = ArchiveWriter::from_config(Vec::new(), ArchiveWriterConfig::new()).unwrap();
+        match mla_fsread.convert_to_archive(&mut mla_w).unwrap() {
+            FailSafeReadError::EndOfOriginalArchiveData => {}
+            err => panic!("Unexpected repair result: {:?}", err),
+        }
+    }
+
+    #[test]
+    fn hardlinks() {
+        let mut mla = ArchiveWriter::from_config(Vec::new(), ArchiveWriterConfig::new()).unwrap();
+        let fake_file = vec![1, 2, 3, 4];
+        mla.add_file("my_file", fake_file.len() as u64, fake_file.as_slice())
+            .unwrap();
+        mla.add_hardlink("my_link", "my_file").unwrap();
+        assert!(matches!(
+            mla.add_hardlink("my_link", "my_file"),
+            Err(Error::DuplicateFilename)
+        ));
+        assert!(matches!(
+            mla.add_hardlink("other_link", "unknown"),
+            Err(Error::BadAPIArgument(_))
+        ));
+        mla.finalize().unwrap();
+        let mla_data = mla.into_raw();
+
+        let mut mla_read =
+            ArchiveReader::from_config(Cursor::new(&mla_data), ArchiveReaderConfig::new()).unwrap();
+        let mut fnames: Vec<String> = mla_read.list_files().unwrap().cloned().collect();
+        fnames.sort();
+        assert_eq!(fnames, vec!["my_file".to_string(), "my_link".to_string()]);
+        let hash = mla_read.get_hash("my_file").unwrap();
+        assert_eq!(mla_read.get_hash("my_link").unwrap(), hash);
+        for fname in &fnames {
+            let mut out = Vec::new();
+            mla_read
+                .get_file(fname.clone())
+                .unwrap()
+                .unwrap()
+                .data
+                .read_to_end(&mut out)
+                .unwrap();
+            assert_eq!(out, fake_file);
+        }
+
+        // Linear extraction must fill both names
+        let mut export: HashMap<&String, Vec<u8>> =
+            fnames.iter().map(|fname| (fname, Vec::new())).collect();
+        linear_extract(&mut mla_read, &mut export).unwrap();
+        for fname in &fnames {
+            assert_eq!(export.get(fname).unwrap(), &fake_file);
+        }
+    }
+
+    #[test]
+    fn dedup_archive() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut day1 = vec![0u8; 2 * 1024 * 1024];
+        rng.fill_bytes(&mut day1);
+        // The next day, some bytes are inserted and some are modified
+        let mut day2 = day1[..300_000].to_vec();
+        day2.extend_from_slice(b"inserted");
+        day2.extend_from_slice(&day1[300_000..]);
+        day2[1_500_000..1_500_100].copy_from_slice(&[0u8; 100]);
+        let files = vec![
+            ("day1".to_string(), day1.clone()),
+            ("day2".to_string(), day2.clone()),
+            ("copy".to_string(), day1.clone()),
+            ("empty".to_string(), Vec::new()),
+        ];
+
+        let key = StaticSecret::new(&mut rng);
+        let mut config = ArchiveWriterConfig::new();
+        config
+            .set_layers(Layers::default())
+            .add_public_keys(&[PublicKey::from(&key)])
+            .with_dedup();
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        for (fname, content) in &files {
+            // Chunks do not depend on how the content is given
+            let id = mla.start_file(fname).unwrap();
+            for piece in content.chunks(100_000) {
+                mla.append_file_content(id, piece.len() as u64, piece)
+                    .unwrap();
+            }
+            mla.end_file(id).unwrap();
+        }
+        mla.finalize().unwrap();
+        let stats = mla.dedup_stats().unwrap();
+        assert_eq!(
+            stats.content_size,
+            files.iter().map(|(_, content)| content.len() as u64).sum()
+        );
+        // Only the chunks around the changes of day2 are stored again
+        assert!(stats.stored_size < (day1.len() + day1.len() / 2) as u64);
+        assert!(stats.stored_chunks < stats.chunks);
+        let archive = mla.into_raw();
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_read = ArchiveReader::from_config(Cursor::new(&archive), config).unwrap();
+        for (fname, content) in &files {
+            let mut out = Vec::new();
+            mla_read
+                .get_file(fname.clone())
+                .unwrap()
+                .unwrap()
+                .data
+                .read_to_end(&mut out)
+                .unwrap();
+            assert_eq!(&out, content);
+        }
+        let mut buf = vec![0u8; 1000];
+        assert_eq!(
+            mla_read.read_at("day2", 1_499_500, &mut buf).unwrap(),
+            Some(buf.len())
+        );
+        assert_eq!(&buf[..], &day2[1_499_500..1_500_500]);
+
+        // Entries are read from their chunks too
+        for entry in mla_read.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content).unwrap();
+            assert!(files.contains(&(entry.name.clone(), content)));
+        }
+
+        let fnames: Vec<String> = files.iter().map(|(fname, _)| fname.clone()).collect();
+        let mut export: HashMap<&String, Vec<u8>> =
+            fnames.iter().map(|fname| (fname, Vec::new())).collect();
+        linear_extract(&mut mla_read, &mut export).unwrap();
+        for (fname, content) in &files {
+            assert_eq!(export.get(fname).unwrap(), content);
+        }
+        assert!(mla_read.check_integrity().unwrap().is_valid());
+    }
+
+    #[test]
+    fn dedup_archive_repair_refused() {
+        // Two files sharing a chunk, the second one having no content of its
+        // own in the stream
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let mut content = vec![0u8; 512 * 1024];
+        rng.fill_bytes(&mut content);
+        let mut config = ArchiveWriterConfig::new();
+        config.set_layers(Layers::COMPRESS).with_dedup();
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        mla.add_file("original", content.len() as u64, content.as_slice())
+            .unwrap();
+        mla.add_file("copy", content.len() as u64, content.as_slice())
+            .unwrap();
+        mla.finalize().unwrap();
+        let stats = mla.dedup_stats().unwrap();
+        assert!(stats.stored_chunks < stats.chunks);
+        let archive = mla.into_raw();
+
+        // Whole or truncated, the archive is refused instead of losing the
+        // files after the first shared chunk
+        for data in [&archive[..], &archive[..archive.len() / 2]] {
+            match ArchiveFailSafeReader::from_config(data, ArchiveReaderConfig::new()) {
+                Err(Error::RepairUnsupportedDedup) => {}
+                _ => panic!("Deduplicated archives must not be repaired"),
+            }
+        }
+
+        // The regular reader is not affected
+        let mut mla_read =
+            ArchiveReader::from_config(Cursor::new(&archive), ArchiveReaderConfig::new()).unwrap();
+        assert!(mla_read.check_integrity().unwrap().is_valid());
+    }
+
+    #[test]
+    fn file_metadata() {
+        let mut mla = ArchiveWriter::from_config(Vec::new(), ArchiveWriterConfig::new()).unwrap();
+        let fake_file = vec![1, 2, 3, 4];
+        let metadata = FileMetadata {
+            mode: Some(0o640),
+            uid: Some(1000),
+            gid: Some(100),
+            mtime: Some(1_600_000_000),
+            ..Default::default()
+        };
+        mla.add_file_with_metadata(
+            "my_file",
+            fake_file.len() as u64,
+            fake_file.as_slice(),
+            metadata.clone(),
+        )
+        .unwrap();
+        let symlink = FileMetadata {
+            entry_type: EntryType::Symlink("my_file".to_string()),
+            ..Default::default()
+        };
+        mla.add_file_with_metadata("my_symlink", 0, std::io::empty(), symlink.clone())
+            .unwrap();
+        mla.add_hardlink("my_link", "my_file").unwrap();
+        mla.add_file("no_metadata", fake_file.len() as u64, fake_file.as_slice())
+            .unwrap();
+        mla.finalize().unwrap();
+        let mla_data = mla.into_raw();
+
+        let mla_read =
+            ArchiveReader::from_config(Cursor::new(&mla_data), ArchiveReaderConfig::new()).unwrap();
+        assert_eq!(mla_read.get_metadata("my_file").unwrap(), Some(&metadata));
+        assert_eq!(mla_read.get_metadata("my_link").unwrap(), Some(&metadata));
+        assert_eq!(mla_read.get_metadata("my_symlink").unwrap(), Some(&symlink));
+        assert_eq!(mla_read.get_metadata("no_metadata").unwrap(), None);
+        assert_eq!(mla_read.get_metadata("unknown").unwrap(), None);
+    }
+
+    #[test]
+    fn output_digest() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = StaticSecret::new(&mut rng);
+        let mut config = ArchiveWriterConfig::new();
+        config
+            .set_layers(Layers::default())
+            .add_public_keys(&[PublicKey::from(&key)])
+            .enable_output_digest();
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        let fake_file = vec![1, 2, 3, 4];
+        mla.add_file("my_file", fake_file.len() as u64, fake_file.as_slice())
+            .unwrap();
+        // Not available until the archive is complete
+        assert_eq!(mla.output_digest(), None);
+        mla.finalize().unwrap();
+        let digest = mla.output_digest().unwrap();
+
+        // Digest must match the whole output, header included
+        let mla_data = mla.into_raw();
+        assert_eq!(&digest, Sha256::digest(&mla_data).as_slice());
+
+        // Disabled by default
+        let mut mla = ArchiveWriter::from_config(Vec::new(), ArchiveWriterConfig::new()).unwrap();
+        mla.finalize().unwrap();
+        assert_eq!(mla.output_digest(), None);
+    }
+
+    /// Record the notifications, shared with the test
+    #[derive(Clone, Default)]
+    struct ProgressRecorder {
+        events: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+        bytes: std::sync::Arc<std::sync::Mutex<u64>>,
+    }
+
+    impl crate::config::ProgressObserver for ProgressRecorder {
+        fn bytes_written(&mut self, count: u64) {
+            *self.bytes.lock().unwrap() += count;
+        }
+
+        fn bytes_read(&mut self, count: u64) {
+            *self.bytes.lock().unwrap() += count;
+        }
+
+        fn entry_started(&mut self, name: &str) {
+            self.events.lock().unwrap().push(format!("start {}", name));
+        }
+
+        fn entry_data(&mut self, name: &str, count: u64) {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("data {} {}", name, count));
+        }
+
+        fn entry_completed(&mut self, name: &str) {
+            self.events.lock().unwrap().push(format!("end {}", name));
+        }
+    }
+
+    #[test]
+    fn entries() {
+        for interleaved in &[false, true] {
+            let (mla, key, files) = build_archive(None, *interleaved);
+            let archive = mla.into_raw();
+            let mut config = ArchiveReaderConfig::new();
+            config.add_private_keys(std::slice::from_ref(&key));
+            let mut mla_read = ArchiveReader::from_config(Cursor::new(&archive), config).unwrap();
+
+            // Entries come in archive order, and are read as they come
+            let mut read = Vec::new();
+            for entry in mla_read.entries().unwrap() {
+                let mut entry = entry.unwrap();
+                let mut content = Vec::new();
+                entry.read_to_end(&mut content).unwrap();
+                assert_eq!(content.len() as u64, entry.size);
+                read.push((entry.name, content));
+            }
+            assert_eq!(read, files);
+
+            // Entries can also be read in another order
+            let mut entries: Vec<_> = mla_read.entries().unwrap().map(Result::unwrap).collect();
+            for (entry, (fname, content)) in entries.iter_mut().zip(files.iter()).rev() {
+                let mut read = Vec::new();
+                entry.read_to_end(&mut read).unwrap();
+                assert_eq!(&entry.name, fname);
+                assert_eq!(&read, content);
+            }
+        }
+    }
+
+    #[test]
+    fn progress_observer() {
+        let expected = vec![
+            "start my_file",
+            "start other",
+            "data my_file 4",
+            "data other 2",
+            "end other",
+            "data my_file 1",
+            "end my_file",
+        ];
+
+        // Writing
+        let recorder = ProgressRecorder::default();
+        let mut config = ArchiveWriterConfig::new();
+        config.with_progress_observer(recorder.clone());
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        let id = mla.start_file("my_file").unwrap();
+        let id_other = mla.start_file("other").unwrap();
+        mla.append_file_content(id, 4, &[1, 2, 3, 4][..]).unwrap();
+        mla.append_file_content(id_other, 2, &[5, 6][..]).unwrap();
+        mla.end_file(id_other).unwrap();
+        mla.append_file_content(id, 1, &[7][..]).unwrap();
+        mla.end_file(id).unwrap();
+        mla.finalize().unwrap();
+        let mla_data = mla.into_raw();
+        assert_eq!(*recorder.events.lock().unwrap(), expected);
+        // The whole output is reported, header included
+        assert_eq!(*recorder.bytes.lock().unwrap(), mla_data.len() as u64);
+
+        // Reading
+        let recorder = ProgressRecorder::default();
+        let mut config = ArchiveReaderConfig::new();
+        config.with_progress_observer(recorder.clone());
+        let mut mla_read = ArchiveReader::from_config(Cursor::new(&mla_data), config).unwrap();
+        // Only the bytes read through the layers are reported
+        let footer_bytes = *recorder.bytes.lock().unwrap();
+        assert!(footer_bytes > 0);
+        let mut export: HashMap<&String, Vec<u8>> = HashMap::new();
+        linear_extract(&mut mla_read, &mut export).unwrap();
+        assert_eq!(*recorder.events.lock().unwrap(), expected);
+        assert!(*recorder.bytes.lock().unwrap() > footer_bytes);
+    }
+
     #[test]
     fn empty_blocks() {
         // Add a file with containning an empty block - it should works
@@ -2054,4 +4942,732 @@ pub(crate) mod tests {
             .unwrap();
         assert_eq!(out.as_slice(), fake_file.as_slice());
     }
+
+    #[test]
+    fn append_to() {
+        use rand::RngCore;
+
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = StaticSecret::new(&mut rng);
+        // Spans several encrypted chunks and compressed blocks
+        let mut big_file = vec![0u8; 300 * 1024];
+        rng.fill_bytes(&mut big_file);
+        let small_file = vec![1, 2, 3, 4];
+
+        for layers in &[
+            Layers::EMPTY,
+            Layers::COMPRESS,
+            Layers::ENCRYPT,
+            Layers::DEFAULT,
+        ] {
+            let mut config = ArchiveWriterConfig::new();
+            config
+                .set_layers(*layers)
+                .add_public_keys(&[PublicKey::from(&key)])
+                .with_encryption_cipher(EncryptionCipher::AesGcmSiv256)
+                .with_encryption_chunk_size(4096)
+                .unwrap()
+                .with_compression_block_size(64 * 1024)
+                .unwrap();
+            let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+            mla.add_file("big", big_file.len() as u64, big_file.as_slice())
+                .unwrap();
+            mla.add_file("small", small_file.len() as u64, small_file.as_slice())
+                .unwrap();
+            mla.add_hardlink("link", "small").unwrap();
+            mla.finalize().unwrap();
+            let mut archive = Cursor::new(mla.into_raw());
+
+            let mut config = ArchiveReaderConfig::new();
+            config.add_private_keys(std::slice::from_ref(&key));
+            let mut mla = ArchiveWriter::append_to(&mut archive, config).unwrap();
+            assert!(matches!(
+                mla.add_file("small", 1, &[0][..]),
+                Err(Error::DuplicateFilename)
+            ));
+            mla.add_file("new", 1000, &big_file[..1000]).unwrap();
+            mla.add_hardlink("new_link", "big").unwrap();
+            mla.finalize().unwrap();
+            drop(mla);
+            let archive = archive.into_inner();
+
+            let mut config = ArchiveReaderConfig::new();
+            config.add_private_keys(std::slice::from_ref(&key));
+            let mut mla_read = ArchiveReader::from_config(Cursor::new(&archive), config).unwrap();
+            let mut fnames: Vec<String> = mla_read.list_files().unwrap().cloned().collect();
+            fnames.sort();
+            assert_eq!(fnames, vec!["big", "link", "new", "new_link", "small"]);
+            for (fname, content) in &[
+                ("big", &big_file[..]),
+                ("small", &small_file[..]),
+                ("link", &small_file[..]),
+                ("new", &big_file[..1000]),
+                ("new_link", &big_file[..]),
+            ] {
+                let mut out = Vec::new();
+                mla_read
+                    .get_file(fname.to_string())
+                    .unwrap()
+                    .unwrap()
+                    .data
+                    .read_to_end(&mut out)
+                    .unwrap();
+                assert_eq!(&out[..], *content);
+            }
+
+            // The resulting archive is also readable in fail-safe mode
+            let mut config = ArchiveReaderConfig::new();
+            config.add_private_keys(std::slice::from_ref(&key));
+            let mut mla_fsread =
+                ArchiveFailSafeReader::from_config(archive.as_slice(), config).unwrap();
+            let mut mla_w =
+                ArchiveWriter::from_config(Vec::new(), ArchiveWriterConfig::new()).unwrap();
+            match mla_fsread.convert_to_archive(&mut mla_w).unwrap() {
+                FailSafeReadError::EndOfOriginalArchiveData => {}
+                err => panic!("Unexpected repair result: {:?}", err),
+            }
+        }
+    }
+
+    #[test]
+    fn append_to_options() {
+        use rand::RngCore;
+
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = StaticSecret::new(&mut rng);
+        let mut big_file = vec![0u8; 300 * 1024];
+        rng.fill_bytes(&mut big_file);
+        let create = |layers: Layers, dedup: bool| {
+            let mut config = ArchiveWriterConfig::new();
+            config
+                .set_layers(layers)
+                .add_public_keys(&[PublicKey::from(&key)])
+                .with_encryption_cipher(EncryptionCipher::AesGcmSiv256);
+            if dedup {
+                config.with_dedup();
+            }
+            let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+            mla.add_file("big", big_file.len() as u64, big_file.as_slice())
+                .unwrap();
+            mla.finalize().unwrap();
+            Cursor::new(mla.into_raw())
+        };
+        let reader_config = || {
+            let mut config = ArchiveReaderConfig::new();
+            config.add_private_keys(std::slice::from_ref(&key));
+            config
+        };
+        let read = |archive: &[u8], fname: &str| {
+            let mut mla_read =
+                ArchiveReader::from_config(Cursor::new(archive), reader_config()).unwrap();
+            let mut out = Vec::new();
+            mla_read
+                .get_file(fname.to_string())
+                .unwrap()
+                .unwrap()
+                .data
+                .read_to_end(&mut out)
+                .unwrap();
+            out
+        };
+
+        // Deduplication is restored, the chunks already stored included
+        let mut archive = create(Layers::DEFAULT, true);
+        let size = archive.get_ref().len();
+        let mut mla = ArchiveWriter::append_to(&mut archive, reader_config()).unwrap();
+        mla.add_file("copy", big_file.len() as u64, big_file.as_slice())
+            .unwrap();
+        mla.finalize().unwrap();
+        let stats = mla.dedup_stats().unwrap();
+        assert!(stats.chunks > 1);
+        assert_eq!(stats.stored_chunks, 0);
+        drop(mla);
+        let archive = archive.into_inner();
+        assert!(archive.len() < size + 4096);
+        assert_eq!(read(&archive, "copy"), big_file);
+        assert_eq!(read(&archive, "big"), big_file);
+
+        // The digest covers the whole archive, and new files are padded
+        let policy = PaddingPolicy {
+            granularity: 512,
+            max_decoy: 0,
+        };
+        let mut sizes = Vec::new();
+        for length in &[200, 380] {
+            let mut archive = create(Layers::ENCRYPT, false);
+            let mut options = ArchiveWriterConfig::new();
+            options.enable_output_digest().with_padding_policy(policy);
+            let mut mla =
+                ArchiveWriter::append_to_with_options(&mut archive, reader_config(), options)
+                    .unwrap();
+            mla.add_file("new", *length, &big_file[..*length as usize])
+                .unwrap();
+            mla.finalize().unwrap();
+            let digest = mla.output_digest().unwrap();
+            drop(mla);
+            let archive = archive.into_inner();
+            assert_eq!(digest, Sha256::digest(&archive).as_slice());
+            assert_eq!(read(&archive, "new"), &big_file[..*length as usize]);
+            sizes.push(archive.len());
+        }
+        assert_eq!(sizes[0], sizes[1]);
+
+        // Padding is refused with compression
+        let mut archive = create(Layers::DEFAULT, false);
+        let mut options = ArchiveWriterConfig::new();
+        options.with_padding_policy(policy);
+        assert!(matches!(
+            ArchiveWriter::append_to_with_options(&mut archive, reader_config(), options),
+            Err(Error::ConfigError(ConfigError::PaddingWithCompression))
+        ));
+    }
+
+    #[test]
+    fn signed_archive() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let signing_key = ed25519_dalek::Keypair::generate(&mut rng);
+        let other_key = ed25519_dalek::Keypair::generate(&mut rng);
+        let key = StaticSecret::new(&mut rng);
+        let files = make_format_regression_files();
+
+        for layering in &[Layers::EMPTY, Layers::COMPRESS, Layers::DEFAULT] {
+            let mut config = ArchiveWriterConfig::new();
+            config
+                .set_layers(*layering)
+                .add_public_keys(&[PublicKey::from(&key)])
+                .add_signing_key(&signing_key);
+            let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+            for fname in &["simple", "file_1", "big"] {
+                let content = files.get(*fname).unwrap();
+                mla.add_file(fname, content.len() as u64, content.as_slice())
+                    .unwrap();
+            }
+            mla.finalize().unwrap();
+            let archive = mla.into_raw();
+
+            let reader_config = |verification_key: &ed25519_dalek::Keypair| {
+                let mut config = ArchiveReaderConfig::new();
+                config
+                    .add_private_keys(std::slice::from_ref(&key))
+                    .add_verification_keys(&[verification_key.public]);
+                config
+            };
+
+            // Authenticated, then read as usual
+            let mut mla_read = ArchiveReader::from_config(
+                Cursor::new(archive.as_slice()),
+                reader_config(&signing_key),
+            )
+            .unwrap();
+            assert_eq!(mla_read.signers(), &[signing_key.public]);
+            for fname in &["simple", "file_1", "big"] {
+                let mut file = mla_read.get_file(fname.to_string()).unwrap().unwrap();
+                let mut rez = Vec::new();
+                file.data.read_to_end(&mut rez).unwrap();
+                assert_eq!(&rez, files.get(*fname).unwrap());
+            }
+
+            // Signatures are ignored in fail-safe mode
+            let mut config = ArchiveReaderConfig::new();
+            config.add_private_keys(std::slice::from_ref(&key));
+            let mut mla_fsread =
+                ArchiveFailSafeReader::from_config(archive.as_slice(), config).unwrap();
+            let mut mla_w =
+                ArchiveWriter::from_config(Vec::new(), ArchiveWriterConfig::new()).unwrap();
+            assert!(matches!(
+                mla_fsread.convert_to_archive(&mut mla_w).unwrap(),
+                FailSafeReadError::EndOfOriginalArchiveData
+            ));
+
+            // Another signer
+            assert!(matches!(
+                ArchiveReader::from_config(
+                    Cursor::new(archive.as_slice()),
+                    reader_config(&other_key)
+                ),
+                Err(Error::SignatureVerificationFailed)
+            ));
+
+            // Tampered data, just before the signature
+            let mut tampered = archive.clone();
+            let pos = tampered.len() - ed25519_dalek::SIGNATURE_LENGTH - 1;
+            tampered[pos] ^= 1;
+            assert!(matches!(
+                ArchiveReader::from_config(
+                    Cursor::new(tampered.as_slice()),
+                    reader_config(&signing_key)
+                ),
+                Err(Error::SignatureVerificationFailed)
+            ));
+        }
+
+        // An unsigned archive is refused when a signature is expected
+        let (mla, key, _files) = build_archive(None, false);
+        let archive = mla.into_raw();
+        let mut config = ArchiveReaderConfig::new();
+        config
+            .add_private_keys(std::slice::from_ref(&key))
+            .add_verification_keys(&[signing_key.public]);
+        assert!(matches!(
+            ArchiveReader::from_config(Cursor::new(archive.as_slice()), config),
+            Err(Error::SignatureVerificationFailed)
+        ));
+    }
+
+    #[test]
+    fn append_to_aes_gcm() {
+        // Resuming an AES-GCM archive would reuse nonces
+        let (mla, key, _files) = build_archive(None, false);
+        let archive = mla.into_raw();
+        let mut dest = Cursor::new(archive.clone());
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        assert!(matches!(
+            ArchiveWriter::append_to(&mut dest, config),
+            Err(Error::AppendUnsupportedCipher)
+        ));
+        // The archive is left untouched
+        assert_eq!(dest.into_inner(), archive);
+    }
+
+    #[test]
+    fn rekey_archive() {
+        let (mla, key, files) = build_archive(None, false);
+        let archive = mla.into_raw();
+        let mut rng = ChaChaRng::seed_from_u64(1);
+        let new_key = StaticSecret::new(&mut rng);
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut rekeyed = Vec::new();
+        rekey(
+            archive.as_slice(),
+            &mut rekeyed,
+            config,
+            &[PublicKey::from(&new_key)],
+            &mut rng,
+        )
+        .unwrap();
+        // Only the header changes
+        assert_eq!(rekeyed.len(), archive.len());
+
+        // The new recipient can read the archive
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&new_key));
+        let mut mla_read = ArchiveReader::from_config(Cursor::new(&rekeyed), config).unwrap();
+        for (fname, content) in files.iter() {
+            let mut file = mla_read.get_file(fname.to_string()).unwrap().unwrap();
+            let mut rez = Vec::new();
+            file.data.read_to_end(&mut rez).unwrap();
+            assert_eq!(&rez, content);
+        }
+
+        // The former one can no longer
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        assert!(matches!(
+            ArchiveReader::from_config(Cursor::new(&rekeyed), config),
+            Err(Error::ConfigError(ConfigError::PrivateKeyNotFound))
+        ));
+
+        // A private key is needed to rekey
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(&[StaticSecret::new(&mut rng)]);
+        assert!(matches!(
+            rekey(archive.as_slice(), Vec::new(), config, &[], &mut rng),
+            Err(Error::ConfigError(ConfigError::PrivateKeyNotFound))
+        ));
+        // Without any password, at least one recipient is needed
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        assert!(matches!(
+            rekey(archive.as_slice(), Vec::new(), config, &[], &mut rng),
+            Err(Error::ConfigError(ConfigError::EncryptionKeyIsMissing))
+        ));
+
+        // Signed archives cannot be rekeyed
+        let signing_key = ed25519_dalek::Keypair::generate(&mut rng);
+        let mut config = ArchiveWriterConfig::new();
+        config
+            .add_public_keys(&[PublicKey::from(&key)])
+            .add_signing_key(&signing_key);
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        mla.finalize().unwrap();
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        assert!(matches!(
+            rekey(
+                mla.into_raw().as_slice(),
+                Vec::new(),
+                config,
+                &[PublicKey::from(&new_key)],
+                &mut rng
+            ),
+            Err(Error::BadAPIArgument(_))
+        ));
+    }
+
+    #[test]
+    fn password_archive() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let key = StaticSecret::new(&mut rng);
+        let files = make_format_regression_files();
+        let content = files.get("simple").unwrap();
+
+        let write_archive = |config: ArchiveWriterConfig| {
+            let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+            mla.add_file("simple", content.len() as u64, content.as_slice())
+                .unwrap();
+            mla.finalize().unwrap();
+            mla.into_raw()
+        };
+        let check_content = |archive: &[u8], config: ArchiveReaderConfig| {
+            let mut mla_read = ArchiveReader::from_config(Cursor::new(archive), config).unwrap();
+            let mut file = mla_read.get_file("simple".to_string()).unwrap().unwrap();
+            let mut rez = Vec::new();
+            file.data.read_to_end(&mut rez).unwrap();
+            assert_eq!(&rez, content);
+        };
+
+        // Password only, without any recipient
+        let mut config = ArchiveWriterConfig::default();
+        config.with_password("correct horse");
+        let archive = write_archive(config);
+        let mut config = ArchiveReaderConfig::new();
+        config.with_password("correct horse");
+        check_content(archive.as_slice(), config);
+
+        let mut config = ArchiveReaderConfig::new();
+        config.with_password("battery staple");
+        assert!(matches!(
+            ArchiveReader::from_config(Cursor::new(archive.as_slice()), config),
+            Err(Error::ConfigError(ConfigError::WrongPassword))
+        ));
+        assert!(matches!(
+            ArchiveReader::from_config(Cursor::new(archive.as_slice()), ArchiveReaderConfig::new()),
+            Err(Error::PrivateKeyNeeded)
+        ));
+
+        // Either a private key or the password opens the archive
+        let mut config = ArchiveWriterConfig::default();
+        config
+            .add_public_keys(&[PublicKey::from(&key)])
+            .with_password("correct horse");
+        let archive = write_archive(config);
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        check_content(archive.as_slice(), config);
+        let mut config = ArchiveReaderConfig::new();
+        config
+            .add_private_keys(&[StaticSecret::new(&mut rng)])
+            .with_password("correct horse");
+        check_content(archive.as_slice(), config);
+    }
+
+    #[test]
+    fn hybrid_archive() {
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let hybrid_key = HybridPrivateKey::generate(&mut rng);
+        let key = StaticSecret::new(&mut rng);
+        let files = make_format_regression_files();
+        let content = files.get("simple").unwrap();
+
+        // Hybrid recipient, alongside a classic one
+        let mut config = ArchiveWriterConfig::default();
+        config
+            .add_public_keys(&[PublicKey::from(&key)])
+            .add_hybrid_public_keys(&[hybrid_key.public_key()]);
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        mla.add_file("simple", content.len() as u64, content.as_slice())
+            .unwrap();
+        mla.finalize().unwrap();
+        let archive = mla.into_raw();
+
+        let check_content = |config: ArchiveReaderConfig| {
+            let mut mla_read =
+                ArchiveReader::from_config(Cursor::new(archive.as_slice()), config).unwrap();
+            let mut file = mla_read.get_file("simple".to_string()).unwrap().unwrap();
+            let mut rez = Vec::new();
+            file.data.read_to_end(&mut rez).unwrap();
+            assert_eq!(&rez, content);
+        };
+
+        // Either key opens the archive
+        let mut config = ArchiveReaderConfig::new();
+        config.add_hybrid_private_keys(std::slice::from_ref(&hybrid_key));
+        check_content(config);
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        check_content(config);
+
+        // Another hybrid key does not
+        let mut config = ArchiveReaderConfig::new();
+        config.add_hybrid_private_keys(&[HybridPrivateKey::generate(&mut rng)]);
+        assert!(matches!(
+            ArchiveReader::from_config(Cursor::new(archive.as_slice()), config),
+            Err(Error::ConfigError(ConfigError::PrivateKeyNotFound))
+        ));
+    }
+
+    #[test]
+    fn recipient_slots_of_archive() {
+        use crate::config::{RecipientKind, RecipientSlot};
+
+        let mut rng = ChaChaRng::seed_from_u64(0);
+        let keys: Vec<StaticSecret> = (0..3).map(|_| StaticSecret::new(&mut rng)).collect();
+        let hybrid_key = HybridPrivateKey::generate(&mut rng);
+        let mut config = ArchiveWriterConfig::default();
+        config
+            .add_public_keys(&[PublicKey::from(&keys[0]), PublicKey::from(&keys[1])])
+            .add_hybrid_public_keys(&[hybrid_key.public_key()])
+            .with_password("password");
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        mla.finalize().unwrap();
+        let archive = mla.into_raw();
+
+        // Keys are matched with the slot they open, whatever their order
+        let slots = recipient_slots(
+            archive.as_slice(),
+            &[keys[2].clone(), keys[1].clone()],
+            std::slice::from_ref(&hybrid_key),
+        )
+        .unwrap();
+        assert_eq!(
+            slots,
+            vec![
+                RecipientSlot {
+                    kind: RecipientKind::X25519,
+                    key_index: None
+                },
+                RecipientSlot {
+                    kind: RecipientKind::X25519,
+                    key_index: Some(1)
+                },
+                RecipientSlot {
+                    kind: RecipientKind::Hybrid,
+                    key_index: Some(0)
+                },
+                RecipientSlot {
+                    kind: RecipientKind::Password,
+                    key_index: None
+                },
+            ]
+        );
+
+        // Without encryption, there is no slot
+        let mut config = ArchiveWriterConfig::new();
+        config.set_layers(Layers::COMPRESS);
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        mla.finalize().unwrap();
+        assert!(recipient_slots(mla.into_raw().as_slice(), &keys, &[])
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn inspect_archive() {
+        use crate::config::{RecipientKind, RecipientSlot};
+
+        // Encrypted archive: the footer cannot be read without a key
+        let (mla, _key, _files) = build_archive(None, false);
+        let archive = mla.into_raw();
+        let inspection = ArchiveHeader::inspect(Cursor::new(&archive)).unwrap();
+        assert_eq!(
+            inspection,
+            ArchiveInspection {
+                format_version: MLA_FORMAT_VERSION,
+                layers: Layers::default(),
+                compression: Some(CompressionAlgorithm::Brotli),
+                compression_dictionary: false,
+                cipher: Some(EncryptionCipher::AesGcm256),
+                recipients: vec![RecipientSlot {
+                    kind: RecipientKind::X25519,
+                    key_index: None
+                }],
+                signers: 0,
+                size: archive.len() as u64,
+                entries: None,
+                truncated: Some(false),
+            }
+        );
+
+        // Only the header is left
+        let mut header = archive.as_slice();
+        ArchiveHeader::from(&mut header).unwrap();
+        let header_length = archive.len() - header.len();
+        let inspection =
+            ArchiveHeader::inspect(Cursor::new(&archive[..header_length + 1])).unwrap();
+        assert_eq!(inspection.truncated, Some(true));
+
+        // Without encryption, entries are counted
+        let (mla, _key, files) = build_archive(Some(Layers::COMPRESS), false);
+        let archive = mla.into_raw();
+        let inspection = ArchiveHeader::inspect(Cursor::new(&archive)).unwrap();
+        assert_eq!(inspection.layers, Layers::COMPRESS);
+        assert_eq!(inspection.cipher, None);
+        assert!(inspection.recipients.is_empty());
+        assert_eq!(inspection.entries, Some(files.len()));
+        assert_eq!(inspection.truncated, Some(false));
+
+        // Truncating it loses its footer
+        let inspection =
+            ArchiveHeader::inspect(Cursor::new(&archive[..archive.len() - 4])).unwrap();
+        assert_eq!(inspection.entries, None);
+        assert_eq!(inspection.truncated, Some(true));
+
+        // Not an archive
+        assert!(matches!(
+            ArchiveHeader::inspect(Cursor::new(b"not an archive")),
+            Err(Error::WrongMagic)
+        ));
+    }
+
+    #[test]
+    fn private_key_provider() {
+        use crate::config::PrivateKeyProvider;
+
+        /// Key on a token, as seen from MLA
+        struct TokenKey(StaticSecret);
+
+        impl PrivateKeyProvider for TokenKey {
+            fn diffie_hellman(&self, public: &PublicKey) -> Result<[u8; 32], Error> {
+                Ok(*self.0.diffie_hellman(public).as_bytes())
+            }
+        }
+
+        /// Token which is not plugged
+        struct MissingToken;
+
+        impl PrivateKeyProvider for MissingToken {
+            fn diffie_hellman(&self, _public: &PublicKey) -> Result<[u8; 32], Error> {
+                Err(Error::PrivateKeyProviderError("No token".to_string()))
+            }
+        }
+
+        let (mla, key, files) = build_archive(None, false);
+        let archive = mla.into_raw();
+
+        // Failing providers are skipped
+        let mut config = ArchiveReaderConfig::new();
+        config
+            .add_private_key_provider(MissingToken)
+            .add_private_key_provider(TokenKey(key));
+        let mut mla_read =
+            ArchiveReader::from_config(Cursor::new(archive.as_slice()), config).unwrap();
+        for (fname, content) in files.iter() {
+            let mut file = mla_read.get_file(fname.clone()).unwrap().unwrap();
+            let mut rez = Vec::new();
+            file.data.read_to_end(&mut rez).unwrap();
+            assert_eq!(&rez, content);
+        }
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_key_provider(MissingToken);
+        assert!(matches!(
+            ArchiveReader::from_config(Cursor::new(archive.as_slice()), config),
+            Err(Error::ConfigError(ConfigError::PrivateKeyNotFound))
+        ));
+    }
+
+    #[test]
+    fn check_integrity() {
+        let (mla, key, files) = build_archive(None, false);
+        let archive = mla.into_raw();
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_read = ArchiveReader::from_config(Cursor::new(&archive), config).unwrap();
+        let report = mla_read.check_integrity().unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.files.len(), files.len());
+
+        // Without encryption, a modified content is caught by its hash
+        let (mla, _key, _files) = build_archive(Some(Layers::EMPTY), false);
+        let mut archive = mla.into_raw();
+        let pos = archive
+            .windows(4)
+            .position(|window| window == [9, 10, 11, 12])
+            .unwrap();
+        archive[pos] ^= 1;
+        let mut mla_read =
+            ArchiveReader::from_config(Cursor::new(&archive), ArchiveReaderConfig::new()).unwrap();
+        let report = mla_read.check_integrity().unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.files,
+            vec![
+                ("my_file1".to_string(), FileIntegrityStatus::Valid),
+                ("my_file2".to_string(), FileIntegrityStatus::HashMismatch),
+                ("my_file3".to_string(), FileIntegrityStatus::Valid),
+            ]
+        );
+
+        // With encryption, by the tag of the chunk
+        let mut config = ArchiveWriterConfig::new();
+        config
+            .set_layers(Layers::ENCRYPT)
+            .add_public_keys(&[PublicKey::from(&key)])
+            .with_encryption_chunk_size(4096)
+            .unwrap();
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        let content = vec![0x42u8; 64 * 1024];
+        mla.add_file("first", content.len() as u64, content.as_slice())
+            .unwrap();
+        mla.add_file("second", content.len() as u64, content.as_slice())
+            .unwrap();
+        mla.finalize().unwrap();
+        let mut archive = mla.into_raw();
+        // Inside the content of "first", far from the header
+        archive[32 * 1024] ^= 1;
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_read = ArchiveReader::from_config(Cursor::new(&archive), config).unwrap();
+        let report = mla_read.check_integrity().unwrap();
+        assert!(matches!(
+            report.files[0],
+            (ref fname, FileIntegrityStatus::ReadError(_)) if fname == "first"
+        ));
+        assert_eq!(
+            report.files[1],
+            ("second".to_string(), FileIntegrityStatus::Valid)
+        );
+    }
+
+    #[test]
+    fn error_context() {
+        let key = StaticSecret::from([1u8; 32]);
+        let mut config = ArchiveWriterConfig::new();
+        config
+            .set_layers(Layers::ENCRYPT)
+            .add_public_keys(&[PublicKey::from(&key)])
+            .with_encryption_chunk_size(4096)
+            .unwrap();
+        let mut mla = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        let content = vec![0x42u8; 64 * 1024];
+        mla.add_file("first", content.len() as u64, content.as_slice())
+            .unwrap();
+        mla.finalize().unwrap();
+        let mut archive = mla.into_raw();
+        archive[32 * 1024] ^= 1;
+
+        let mut config = ArchiveReaderConfig::new();
+        config.add_private_keys(std::slice::from_ref(&key));
+        let mut mla_read = ArchiveReader::from_config(Cursor::new(&archive), config).unwrap();
+        let mut entry = mla_read.entries().unwrap().next().unwrap().unwrap();
+        let error = Error::from(io::copy(&mut entry, &mut io::sink()).unwrap_err());
+
+        // The error is the failing tag, with where it occurred
+        assert!(matches!(
+            error.root(),
+            Error::AuthenticatedDecryptionWrongTag
+        ));
+        assert_eq!(error.code(), "authenticated_decryption_wrong_tag");
+        let context = error.context().unwrap();
+        assert_eq!(context.layer, Some("encryption"));
+        assert_eq!(context.offset.unwrap() % 4096, 0);
+        assert_eq!(context.entry.as_deref(), Some("first"));
+        assert!(error.to_string().starts_with("wrong authentication tag"));
+        assert!(error
+            .to_string()
+            .contains("entry \"first\", encryption layer at offset "));
+    }
 }
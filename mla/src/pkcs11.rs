@@ -0,0 +1,280 @@
+//! Private keys on a PKCS#11 token (HSM, smart card, YubiKey, ...), used
+//! through `PrivateKeyProvider` to decrypt archives
+//!
+//! A key is designated by a PKCS#11 URI (RFC 7512), for instance
+//! `pkcs11:token=MLA;object=archive-key?module-path=/usr/lib/softhsm/libsofthsm2.so`.
+//! The supported attributes are `token`, `slot-id`, `object` and `id` in the
+//! path, `module-path` and `pin-value` in the query; the other ones are
+//! ignored.
+//!
+//! The key must be an X25519 one (`CKK_EC_MONTGOMERY`). The Diffie-Hellman
+//! is made on the token, with `CKM_ECDH1_DERIVE`, so the private key is never
+//! in memory.
+use crate::config::PrivateKeyProvider;
+use crate::errors::Error;
+use cryptoki::context::{CInitializeArgs, Pkcs11};
+use cryptoki::mechanism::elliptic_curve::{EcKdf, Ecdh1DeriveParams};
+use cryptoki::mechanism::Mechanism;
+use cryptoki::object::{Attribute, AttributeType, KeyType, ObjectClass, ObjectHandle};
+use cryptoki::session::{Session, UserType};
+use cryptoki::types::AuthPin;
+use std::sync::Mutex;
+use x25519_dalek::PublicKey;
+use zeroize::Zeroize;
+
+const URI_SCHEME: &str = "pkcs11:";
+const SHARED_SECRET_SIZE: usize = 32;
+
+/// Initialized modules, by path. A module is initialized once per process,
+/// and stays loaded, as several keys or threads may use it
+static MODULES: Mutex<Vec<(String, Pkcs11)>> = Mutex::new(Vec::new());
+
+fn to_error<E: std::fmt::Display>(error: E) -> Error {
+    Error::PrivateKeyProviderError(error.to_string())
+}
+
+fn uri_error(message: &str) -> Error {
+    Error::BadAPIArgument(format!("[PKCS#11 URI] {}", message))
+}
+
+/// Decode the `%XX` escapes of an URI attribute value
+fn percent_decode(value: &str) -> Result<Vec<u8>, Error> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| uri_error("Invalid percent-encoding"))?;
+            out.push(byte);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    Ok(out)
+}
+
+fn percent_decode_str(value: &str) -> Result<String, Error> {
+    String::from_utf8(percent_decode(value)?).map_err(|_| uri_error("Invalid UTF-8 value"))
+}
+
+/// Attributes of a PKCS#11 URI designating a private key
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Pkcs11Uri {
+    /// Path of the PKCS#11 module (shared library) of the token
+    pub module_path: String,
+    /// Label of the token
+    pub token: Option<String>,
+    pub slot_id: Option<u64>,
+    /// Label of the key
+    pub object: Option<String>,
+    /// Identifier of the key
+    pub id: Option<Vec<u8>>,
+    pub pin: Option<String>,
+}
+
+impl Pkcs11Uri {
+    pub fn parse(uri: &str) -> Result<Self, Error> {
+        if !uri.starts_with(URI_SCHEME) {
+            return Err(uri_error("Must start with \"pkcs11:\""));
+        }
+        let uri = &uri[URI_SCHEME.len()..];
+        let (path, query) = match uri.find('?') {
+            Some(pos) => (&uri[..pos], &uri[pos + 1..]),
+            None => (uri, ""),
+        };
+
+        let mut parsed = Pkcs11Uri::default();
+        let mut module_path = None;
+        let attributes = path
+            .split(';')
+            .map(|attribute| (attribute, true))
+            .chain(query.split('&').map(|attribute| (attribute, false)));
+        for (attribute, in_path) in attributes {
+            if attribute.is_empty() {
+                continue;
+            }
+            let (name, value) = match attribute.find('=') {
+                Some(pos) => (&attribute[..pos], &attribute[pos + 1..]),
+                None => return Err(uri_error("Attributes must be name=value")),
+            };
+            match (name, in_path) {
+                ("token", true) => parsed.token = Some(percent_decode_str(value)?),
+                ("slot-id", true) => {
+                    parsed.slot_id = Some(
+                        percent_decode_str(value)?
+                            .parse()
+                            .map_err(|_| uri_error("Invalid slot-id"))?,
+                    )
+                }
+                ("object", true) => parsed.object = Some(percent_decode_str(value)?),
+                ("id", true) => parsed.id = Some(percent_decode(value)?),
+                ("module-path", false) => module_path = Some(percent_decode_str(value)?),
+                ("pin-value", false) => parsed.pin = Some(percent_decode_str(value)?),
+                // Not needed to find the key (manufacturer, type, ...)
+                _ => {}
+            }
+        }
+        parsed.module_path = module_path.ok_or_else(|| uri_error("module-path is required"))?;
+        Ok(parsed)
+    }
+}
+
+/// Return the module at `path`, initialized
+fn module(path: &str) -> Result<Pkcs11, Error> {
+    let mut modules = MODULES.lock().expect("PKCS#11 modules lock poisoned");
+    if let Some((_, module)) = modules.iter().find(|(module_path, _)| module_path == path) {
+        return Ok(module.clone());
+    }
+    let module = Pkcs11::new(path).map_err(to_error)?;
+    module
+        .initialize(CInitializeArgs::OsThreads)
+        .map_err(to_error)?;
+    modules.push((path.to_string(), module.clone()));
+    Ok(module)
+}
+
+/// X25519 private key on a PKCS#11 token
+pub struct Pkcs11PrivateKey {
+    session: Session,
+    key: ObjectHandle,
+}
+
+impl Pkcs11PrivateKey {
+    /// Open the private key designated by `uri`, logging in with `pin` if
+    /// given, with the URI `pin-value` otherwise
+    ///
+    /// The URI must match exactly one token, and one key on it
+    pub fn open(uri: &str, pin: Option<&str>) -> Result<Self, Error> {
+        let uri = Pkcs11Uri::parse(uri)?;
+        let module = module(&uri.module_path)?;
+
+        let mut slots = Vec::new();
+        for slot in module.get_slots_with_token().map_err(to_error)? {
+            if uri.slot_id.is_some_and(|slot_id| slot.id() != slot_id) {
+                continue;
+            }
+            if let Some(token) = &uri.token {
+                if module.get_token_info(slot).map_err(to_error)?.label() != token.as_str() {
+                    continue;
+                }
+            }
+            slots.push(slot);
+        }
+        let slot = match slots.as_slice() {
+            [slot] => *slot,
+            [] => return Err(to_error("No token matches the URI")),
+            _ => {
+                return Err(to_error(
+                    "Several tokens match the URI, use token or slot-id",
+                ))
+            }
+        };
+
+        let session = module.open_ro_session(slot).map_err(to_error)?;
+        if let Some(pin) = pin.or_else(|| uri.pin.as_deref()) {
+            session
+                .login(UserType::User, Some(&AuthPin::new(pin.to_string())))
+                .map_err(to_error)?;
+        }
+
+        let mut template = vec![
+            Attribute::Class(ObjectClass::PRIVATE_KEY),
+            Attribute::KeyType(KeyType::EC_MONTGOMERY),
+        ];
+        if let Some(object) = &uri.object {
+            template.push(Attribute::Label(object.as_bytes().to_vec()));
+        }
+        if let Some(id) = &uri.id {
+            template.push(Attribute::Id(id.clone()));
+        }
+        let keys = session.find_objects(&template).map_err(to_error)?;
+        let key = match keys.as_slice() {
+            [key] => *key,
+            [] => return Err(to_error("No X25519 private key matches the URI")),
+            _ => return Err(to_error("Several keys match the URI, use object or id")),
+        };
+        Ok(Self { session, key })
+    }
+}
+
+impl PrivateKeyProvider for Pkcs11PrivateKey {
+    fn diffie_hellman(&self, public: &PublicKey) -> Result<[u8; 32], Error> {
+        let params = Ecdh1DeriveParams::new(EcKdf::null(), public.as_bytes());
+        // The shared secret is derived as a session object, to be read back
+        let template = [
+            Attribute::Class(ObjectClass::SECRET_KEY),
+            Attribute::KeyType(KeyType::GENERIC_SECRET),
+            Attribute::ValueLen((SHARED_SECRET_SIZE as u64).into()),
+            Attribute::Token(false),
+            Attribute::Sensitive(false),
+            Attribute::Extractable(true),
+        ];
+        let secret = self
+            .session
+            .derive_key(&Mechanism::Ecdh1Derive(params), self.key, &template)
+            .map_err(to_error)?;
+        let attributes = self.session.get_attributes(secret, &[AttributeType::Value]);
+        // Best effort, as the object is removed with the session anyway
+        let _ = self.session.destroy_object(secret);
+
+        let mut attributes = attributes.map_err(to_error)?;
+        let mut shared_secret = [0u8; SHARED_SECRET_SIZE];
+        let found = match attributes.as_slice() {
+            [Attribute::Value(value)] if value.len() == SHARED_SECRET_SIZE => {
+                shared_secret.copy_from_slice(value);
+                true
+            }
+            _ => false,
+        };
+        for attribute in attributes.iter_mut() {
+            if let Attribute::Value(value) = attribute {
+                value.zeroize();
+            }
+        }
+        if !found {
+            return Err(to_error("Unexpected shared secret from the token"));
+        }
+        Ok(shared_secret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_uri() {
+        let uri = Pkcs11Uri::parse(
+            "pkcs11:token=My%20Token;manufacturer=Test;id=%01%ff;object=key?module-path=/usr/lib/libtest.so&pin-value=1234",
+        )
+        .unwrap();
+        assert_eq!(
+            uri,
+            Pkcs11Uri {
+                module_path: "/usr/lib/libtest.so".to_string(),
+                token: Some("My Token".to_string()),
+                slot_id: None,
+                object: Some("key".to_string()),
+                id: Some(vec![1, 0xff]),
+                pin: Some("1234".to_string()),
+            }
+        );
+
+        assert_eq!(
+            Pkcs11Uri::parse("pkcs11:slot-id=3?module-path=mod.so")
+                .unwrap()
+                .slot_id,
+            Some(3)
+        );
+        // The module is required, in the query
+        assert!(Pkcs11Uri::parse("pkcs11:object=key").is_err());
+        assert!(Pkcs11Uri::parse("pkcs11:module-path=mod.so").is_err());
+        assert!(Pkcs11Uri::parse("file:key?module-path=mod.so").is_err());
+        assert!(Pkcs11Uri::parse("pkcs11:id=%1?module-path=mod.so").is_err());
+    }
+}
@@ -0,0 +1,381 @@
+//! Multi-volume archives, for size-limited media
+//!
+//! `VolumeWriter` splits the archive it receives into files of at most a
+//! given size, named `<base>.001`, `<base>.002`, etc. `VolumeReader` reads
+//! them back as a single archive, for `ArchiveReader` or
+//! `ArchiveFailSafeReader`.
+//!
+//! Each volume starts with a header:
+//! ```text
+//! magic: b"MLAV"
+//! archive_id: [u8; 16]   // Random, shared by the volumes of an archive
+//! index: u32             // Little endian, starting at 1
+//! last: u8               // 1 for the last volume, 0 otherwise
+//! ```
+//! followed by its part of the archive. The volumes can therefore be
+//! checked for order, origin and completeness before being read.
+use crate::errors::Error;
+use crate::layers::cache::checked_add_signed;
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaChaRng;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+const VOLUME_MAGIC: &[u8; 4] = b"MLAV";
+const ARCHIVE_ID_SIZE: usize = 16;
+/// Offset of the `last` flag in a volume header
+const LAST_FLAG_OFFSET: u64 = (VOLUME_MAGIC.len() + ARCHIVE_ID_SIZE + 4) as u64;
+pub const VOLUME_HEADER_SIZE: u64 = LAST_FLAG_OFFSET + 1;
+
+/// Return the path of the volume `index` of the archive `base`
+///
+/// For instance, `archive.mla` volume 2 is `archive.mla.002`
+pub fn volume_path<P: AsRef<Path>>(base: P, index: u32) -> PathBuf {
+    let mut name = OsString::from(base.as_ref().as_os_str());
+    name.push(format!(".{:03}", index));
+    PathBuf::from(name)
+}
+
+/// Write an archive to volumes of at most `volume_size` bytes each, headers
+/// included
+///
+/// `finish` must be called once the archive is finalized, to mark the last
+/// volume
+pub struct VolumeWriter {
+    base: PathBuf,
+    volume_size: u64,
+    archive_id: [u8; ARCHIVE_ID_SIZE],
+    /// Current volume, and its size
+    current: File,
+    current_size: u64,
+    paths: Vec<PathBuf>,
+}
+
+impl VolumeWriter {
+    /// Create the first volume of the archive `base`
+    pub fn new<P: AsRef<Path>>(base: P, volume_size: u64) -> Result<Self, Error> {
+        if volume_size <= VOLUME_HEADER_SIZE {
+            return Err(Error::BadAPIArgument(format!(
+                "Volume size must be greater than {}",
+                VOLUME_HEADER_SIZE
+            )));
+        }
+        let mut archive_id = [0u8; ARCHIVE_ID_SIZE];
+        ChaChaRng::from_entropy().fill_bytes(&mut archive_id);
+        let base = base.as_ref().to_path_buf();
+        let (current, path) = create_volume(&base, &archive_id, 1)?;
+        Ok(Self {
+            base,
+            volume_size,
+            archive_id,
+            current,
+            current_size: VOLUME_HEADER_SIZE,
+            paths: vec![path],
+        })
+    }
+
+    /// Mark the current volume as the last one, and return the paths of the
+    /// volumes, in order
+    pub fn finish(mut self) -> io::Result<Vec<PathBuf>> {
+        self.current.seek(SeekFrom::Start(LAST_FLAG_OFFSET))?;
+        self.current.write_u8(1)?;
+        self.current.flush()?;
+        Ok(self.paths)
+    }
+}
+
+/// Create the volume `index`, with its header
+fn create_volume(
+    base: &Path,
+    archive_id: &[u8; ARCHIVE_ID_SIZE],
+    index: u32,
+) -> io::Result<(File, PathBuf)> {
+    let path = volume_path(base, index);
+    let mut file = File::create(&path)?;
+    file.write_all(VOLUME_MAGIC)?;
+    file.write_all(archive_id)?;
+    file.write_u32::<LittleEndian>(index)?;
+    file.write_u8(0)?;
+    Ok((file, path))
+}
+
+impl Write for VolumeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        if self.current_size >= self.volume_size {
+            self.current.flush()?;
+            let index = self.paths.len() as u32 + 1;
+            let (file, path) = create_volume(&self.base, &self.archive_id, index)?;
+            self.current = file;
+            self.current_size = VOLUME_HEADER_SIZE;
+            self.paths.push(path);
+        }
+        let available = (self.volume_size - self.current_size) as usize;
+        let written = self.current.write(&buf[..buf.len().min(available)])?;
+        self.current_size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.current.flush()
+    }
+}
+
+/// Read the volumes of an archive, as a single archive
+pub struct VolumeReader {
+    /// Volumes, with the offset of their content in the archive
+    volumes: Vec<(File, u64)>,
+    len: u64,
+    pos: u64,
+}
+
+impl VolumeReader {
+    /// Open the volumes of the archive `base` (`<base>.001`, etc.)
+    ///
+    /// Fails with `Error::MissingVolume` if a volume is missing, and
+    /// `Error::WrongVolume` if a volume is not the expected one
+    pub fn open<P: AsRef<Path>>(base: P) -> Result<Self, Error> {
+        Self::open_volumes(base.as_ref(), false)
+    }
+
+    /// Open the volumes of the archive `base`, until the first missing one
+    ///
+    /// This is intended for `ArchiveFailSafeReader`, to recover what the
+    /// available volumes contain
+    pub fn open_available<P: AsRef<Path>>(base: P) -> Result<Self, Error> {
+        Self::open_volumes(base.as_ref(), true)
+    }
+
+    fn open_volumes(base: &Path, allow_missing: bool) -> Result<Self, Error> {
+        let mut volumes = Vec::new();
+        let mut archive_id = None;
+        let mut len = 0;
+        let mut index = 1;
+        loop {
+            let mut file = match File::open(volume_path(base, index)) {
+                Ok(file) => file,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                    if allow_missing && index > 1 {
+                        break;
+                    }
+                    return Err(Error::MissingVolume(index));
+                }
+                Err(err) => return Err(err.into()),
+            };
+            let mut magic = [0u8; 4];
+            file.read_exact(&mut magic)?;
+            let mut volume_id = [0u8; ARCHIVE_ID_SIZE];
+            file.read_exact(&mut volume_id)?;
+            let volume_index = file.read_u32::<LittleEndian>()?;
+            let last = file.read_u8()?;
+            if magic != *VOLUME_MAGIC
+                || *archive_id.get_or_insert(volume_id) != volume_id
+                || volume_index != index
+            {
+                return Err(Error::WrongVolume(index));
+            }
+
+            let size = file.seek(SeekFrom::End(0))? - VOLUME_HEADER_SIZE;
+            volumes.push((file, len));
+            len += size;
+            if last != 0 {
+                break;
+            }
+            index += 1;
+        }
+        Ok(Self {
+            volumes,
+            len,
+            pos: 0,
+        })
+    }
+}
+
+impl Read for VolumeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos >= self.len {
+            return Ok(0);
+        }
+        // Last volume starting at or before the current position
+        let current = self
+            .volumes
+            .iter()
+            .rposition(|(_, start)| *start <= self.pos)
+            .unwrap_or(0);
+        let end = match self.volumes.get(current + 1) {
+            Some((_, next_start)) => *next_start,
+            None => self.len,
+        };
+        let (file, start) = &mut self.volumes[current];
+        file.seek(SeekFrom::Start(VOLUME_HEADER_SIZE + self.pos - *start))?;
+        let size = buf.len().min((end - self.pos) as usize);
+        let read = file.read(&mut buf[..size])?;
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for VolumeReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(offset) => checked_add_signed(self.pos, offset),
+            SeekFrom::End(offset) => checked_add_signed(self.len, offset),
+        };
+        match new_pos {
+            Some(new_pos) => {
+                self.pos = new_pos;
+                Ok(new_pos)
+            }
+            None => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Invalid seek to a negative position",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ArchiveReaderConfig, ArchiveWriterConfig};
+    use crate::{ArchiveFailSafeReader, ArchiveReader, ArchiveWriter, Layers};
+    use std::fs;
+    use std::io::Cursor;
+
+    /// Return the base path of a test archive, in a fresh directory
+    fn test_base(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mla_volume_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.join("archive.mla")
+    }
+
+    /// Write an archive of two files in volumes of 4KB, return the files
+    fn write_archive(base: &Path) -> Vec<(String, Vec<u8>)> {
+        let files: Vec<(String, Vec<u8>)> = vec![
+            (
+                "file1".to_string(),
+                (0..20_000u32).map(|i| (i % 251) as u8).collect(),
+            ),
+            (
+                "file2".to_string(),
+                (0..10_000u32).map(|i| (i % 241) as u8).collect(),
+            ),
+        ];
+        let mut config = ArchiveWriterConfig::new();
+        config.set_layers(Layers::EMPTY);
+        let mut mla =
+            ArchiveWriter::from_config(VolumeWriter::new(base, 4096).unwrap(), config).unwrap();
+        for (fname, content) in &files {
+            mla.add_file(fname, content.len() as u64, content.as_slice())
+                .unwrap();
+        }
+        mla.finalize().unwrap();
+        let paths = mla.into_raw().finish().unwrap();
+        assert!(paths.len() > 3);
+        for (i, path) in paths.iter().enumerate() {
+            assert_eq!(*path, volume_path(base, i as u32 + 1));
+            assert!(fs::metadata(path).unwrap().len() <= 4096);
+        }
+        files
+    }
+
+    #[test]
+    fn volumes() {
+        let base = test_base("volumes");
+        let files = write_archive(&base);
+
+        let mut mla_read = ArchiveReader::from_config(
+            VolumeReader::open(&base).unwrap(),
+            ArchiveReaderConfig::new(),
+        )
+        .unwrap();
+        for (fname, content) in &files {
+            let mut rez = Vec::new();
+            mla_read
+                .get_file(fname.to_string())
+                .unwrap()
+                .unwrap()
+                .data
+                .read_to_end(&mut rez)
+                .unwrap();
+            assert_eq!(&rez, content);
+        }
+        fs::remove_dir_all(base.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn volumes_errors() {
+        let base = test_base("volumes_errors");
+        let files = write_archive(&base);
+        let saved = base.with_extension("saved");
+
+        // Out of order
+        fs::rename(volume_path(&base, 2), &saved).unwrap();
+        fs::copy(volume_path(&base, 3), volume_path(&base, 2)).unwrap();
+        assert!(matches!(
+            VolumeReader::open(&base),
+            Err(Error::WrongVolume(2))
+        ));
+
+        // Missing
+        fs::remove_file(volume_path(&base, 2)).unwrap();
+        assert!(matches!(
+            VolumeReader::open(&base),
+            Err(Error::MissingVolume(2))
+        ));
+
+        // Volume of another archive
+        let other = base.with_file_name("other.mla");
+        write_archive(&other);
+        fs::copy(volume_path(&other, 2), volume_path(&base, 2)).unwrap();
+        assert!(matches!(
+            VolumeReader::open(&base),
+            Err(Error::WrongVolume(2))
+        ));
+
+        // Without its last volume, the archive is incomplete
+        fs::rename(&saved, volume_path(&base, 2)).unwrap();
+        let last = (1..)
+            .take_while(|index| volume_path(&base, *index).exists())
+            .last()
+            .unwrap();
+        fs::remove_file(volume_path(&base, last)).unwrap();
+        assert!(matches!(
+            VolumeReader::open(&base),
+            Err(Error::MissingVolume(index)) if index == last
+        ));
+
+        // But the content of the available volumes can still be recovered
+        let mut mla_fsread = ArchiveFailSafeReader::from_config(
+            VolumeReader::open_available(&base).unwrap(),
+            ArchiveReaderConfig::new(),
+        )
+        .unwrap();
+        let mut config = ArchiveWriterConfig::new();
+        config.set_layers(Layers::EMPTY);
+        let mut mla_w = ArchiveWriter::from_config(Vec::new(), config).unwrap();
+        mla_fsread.convert_to_archive(&mut mla_w).unwrap();
+        let mut mla_read =
+            ArchiveReader::from_config(Cursor::new(mla_w.into_raw()), ArchiveReaderConfig::new())
+                .unwrap();
+        let (fname, content) = &files[0];
+        let mut rez = Vec::new();
+        mla_read
+            .get_file(fname.to_string())
+            .unwrap()
+            .unwrap()
+            .data
+            .read_to_end(&mut rez)
+            .unwrap();
+        assert_eq!(&rez, content);
+
+        fs::remove_dir_all(base.parent().unwrap()).unwrap();
+    }
+}
@@ -1,23 +1,49 @@
 use clap::{App, Arg, ArgMatches, SubCommand};
 use ed25519_parser::{
-    generate_keypair, parse_openssl_ed25519_privkey, parse_openssl_ed25519_pubkey,
+    check_mnemonic, generate_keypair, generate_keypair_from_seed, mnemonic_to_seed,
+    parse_openssh_ed25519_privkey, parse_openssh_ed25519_privkey_encrypted,
+    parse_openssh_ed25519_pubkeys_many, parse_openssl_ed25519_privkey,
+    parse_openssl_ed25519_privkey_encrypted, parse_openssl_ed25519_pubkey,
+    parse_openssl_ed25519_signing_key, parse_openssl_ed25519_verifying_key, ED25519ParserError,
+    Fingerprint,
 };
 use glob::Pattern;
-use hex;
 use humansize::{file_size_opts, FileSize};
-use mla::config::{ArchiveReaderConfig, ArchiveWriterConfig};
-use mla::errors::{Error, FailSafeReadError};
-use mla::helpers::linear_extract;
-use mla::{ArchiveFailSafeReader, ArchiveFile, ArchiveReader, ArchiveWriter, Layers};
+use mla::config::{
+    train_compression_dictionary, ArchiveReaderConfig, ArchiveWriterConfig, CompressionAlgorithm,
+    EncryptionCipher, HybridPrivateKey, HybridPublicKey, PaddingPolicy, ProgressObserver,
+    RecipientKind, RecipientSet,
+};
+#[cfg(unix)]
+use mla::crypto::locked::Locked;
+use mla::errors::{ConfigError, Error, FailSafeReadError};
+use mla::helpers::{diff as mla_diff, from_tar as tar_to_mla, to_tar as mla_to_tar, EntryChange};
+#[cfg(feature = "pkcs11")]
+use mla::pkcs11::{Pkcs11PrivateKey, Pkcs11Uri};
+use mla::volume::{volume_path, VolumeReader, VolumeWriter};
+use mla::{
+    recipient_slots, ArchiveFailSafeReader, ArchiveFileInfo, ArchiveHeader, ArchiveReader,
+    ArchiveWriter, EntryType, FileIntegrityStatus, FileMetadata, Layers,
+};
 use rand::SeedableRng;
 use rand_chacha::ChaChaRng;
+use regex::bytes::Regex;
+use serde::de::{self, Deserializer};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+use std::ffi::OsStr;
 use std::fs::{self, File};
 use std::io;
-use std::io::{Read, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
 use std::path::{Component, Path, PathBuf};
-use tar::{Builder, Header};
-use x25519_dalek;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tar::Builder;
+use zeroize::Zeroize;
 
 // ----- Utils ------
 
@@ -27,6 +53,19 @@ use x25519_dalek;
 enum OutputTypes {
     Stdout,
     File { file: File },
+    Volumes { volumes: VolumeWriter },
+}
+
+impl OutputTypes {
+    /// To be called once the archive is finalized
+    fn finish(self) -> io::Result<()> {
+        if let OutputTypes::Volumes { volumes } = self {
+            for path in volumes.finish()? {
+                eprintln!("{}", path.display());
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Write for OutputTypes {
@@ -34,6 +73,7 @@ impl Write for OutputTypes {
         match self {
             OutputTypes::Stdout => io::stdout().write(buf),
             OutputTypes::File { file } => file.write(buf),
+            OutputTypes::Volumes { volumes } => volumes.write(buf),
         }
     }
 
@@ -41,11 +81,61 @@ impl Write for OutputTypes {
         match self {
             OutputTypes::Stdout => io::stdout().flush(),
             OutputTypes::File { file } => file.flush(),
+            OutputTypes::Volumes { volumes } => volumes.flush(),
+        }
+    }
+}
+
+/// Archive to read: a file, or the volumes of a multi-volume archive
+enum InputTypes {
+    File { file: File },
+    Volumes { volumes: VolumeReader },
+}
+
+impl Read for InputTypes {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            InputTypes::File { file } => file.read(buf),
+            InputTypes::Volumes { volumes } => volumes.read(buf),
         }
     }
 }
 
+impl Seek for InputTypes {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            InputTypes::File { file } => file.seek(pos),
+            InputTypes::Volumes { volumes } => volumes.seek(pos),
+        }
+    }
+}
+
+/// PEM tags of the hybrid (X25519 and ML-KEM-768) keys, see `keygen`
+const HYBRID_PRIVATE_KEY_TAG: &str = "MLA HYBRID PRIVATE KEY";
+const HYBRID_PUBLIC_KEY_TAG: &str = "MLA HYBRID PUBLIC KEY";
+
+/// Return the content of `data`, if it is a PEM with the given `tag`
+fn parse_hybrid_pem(data: &[u8], tag: &str) -> Option<Vec<u8>> {
+    match pem::parse(data) {
+        Ok(pem_data) if pem_data.tag == tag => Some(pem_data.contents),
+        Ok(mut pem_data) => {
+            pem_data.contents.zeroize();
+            None
+        }
+        Err(_) => None,
+    }
+}
+
 fn open_ecc_private_keys(matches: &ArgMatches) -> Result<Vec<x25519_dalek::StaticSecret>, Error> {
+    load_ecc_private_keys(matches, |private_key| private_key)
+}
+
+/// ECC private keys among the private keys arguments, each one given to
+/// `store` as soon as parsed (for instance, to move it to locked memory)
+fn load_ecc_private_keys<K>(
+    matches: &ArgMatches,
+    store: impl Fn(x25519_dalek::StaticSecret) -> K,
+) -> Result<Vec<K>, Error> {
     let mut private_keys = Vec::new();
     if let Some(private_key_args) = matches.values_of_os("private_keys") {
         for private_key_arg in private_key_args {
@@ -53,34 +143,441 @@ fn open_ecc_private_keys(matches: &ArgMatches) -> Result<Vec<x25519_dalek::Stati
             // Load the the ECC key in-memory and parse it
             let mut buf = Vec::new();
             file.read_to_end(&mut buf)?;
-            match parse_openssl_ed25519_privkey(&buf) {
-                Err(_) => return Err(Error::InvalidECCKeyFormat),
-                Ok(private_key) => private_keys.push(private_key),
+            // Hybrid keys are loaded by `open_hybrid_private_keys`
+            if let Some(mut contents) = parse_hybrid_pem(&buf, HYBRID_PRIVATE_KEY_TAG) {
+                contents.zeroize();
+                buf.zeroize();
+                continue;
+            }
+            let private_key = match parse_openssl_ed25519_privkey(&buf) {
+                // Not an OpenSSL PEM, maybe an OpenSSH one
+                Err(ED25519ParserError::InvalidPEMTag) => parse_openssh_ed25519_privkey(&buf),
+                result => result,
             };
+            let private_key = match private_key {
+                Err(ED25519ParserError::PasswordRequired) => {
+                    parse_encrypted_private_key(matches, &buf)
+                }
+                result => result.map_err(|_| Error::InvalidECCKeyFormat),
+            };
+            // Do not leave the serialized key in memory
+            buf.zeroize();
+            private_keys.push(store(private_key?));
         }
     };
     Ok(private_keys)
 }
 
-fn open_ecc_public_keys(matches: &ArgMatches) -> Result<Vec<x25519_dalek::PublicKey>, Error> {
+/// Hybrid private keys among the private keys arguments
+fn open_hybrid_private_keys(matches: &ArgMatches) -> Result<Vec<HybridPrivateKey>, Error> {
+    load_hybrid_private_keys(matches, |private_key| private_key)
+}
+
+/// See `load_ecc_private_keys`
+fn load_hybrid_private_keys<K>(
+    matches: &ArgMatches,
+    store: impl Fn(HybridPrivateKey) -> K,
+) -> Result<Vec<K>, Error> {
+    let mut private_keys = Vec::new();
+    if let Some(private_key_args) = matches.values_of_os("private_keys") {
+        for private_key_arg in private_key_args {
+            let mut buf = fs::read(private_key_arg)?;
+            let contents = parse_hybrid_pem(&buf, HYBRID_PRIVATE_KEY_TAG);
+            buf.zeroize();
+            if let Some(mut contents) = contents {
+                let private_key = HybridPrivateKey::from_bytes(&contents);
+                contents.zeroize();
+                private_keys.push(store(private_key?));
+            }
+        }
+    }
+    Ok(private_keys)
+}
+
+/// Read a password from the first line of `path`
+fn read_password_file(path: &OsStr) -> Result<String, Error> {
+    let mut content = fs::read_to_string(path)?;
+    let value = content.trim_end_matches(&['\r', '\n'][..]).to_string();
+    content.zeroize();
+    Ok(value)
+}
+
+/// Password of the encrypted private keys, asked once for all (for instance,
+/// `grep` opens the keys from several threads)
+static PRIVATE_KEYS_PASSWORD: Mutex<Option<String>> = Mutex::new(None);
+
+/// Parse an encrypted private key (OpenSSL or OpenSSH format), with the
+/// password from `--password-file`, or asked on the terminal
+fn parse_encrypted_private_key(
+    matches: &ArgMatches,
+    data: &[u8],
+) -> Result<x25519_dalek::StaticSecret, Error> {
+    let mut password = PRIVATE_KEYS_PASSWORD
+        .lock()
+        .expect("Password lock poisoned");
+    if password.is_none() {
+        *password = Some(match matches.value_of_os("password_file") {
+            Some(path) => read_password_file(path)?,
+            None => rpassword::prompt_password("Private key password: ")?,
+        });
+    }
+    // Safe to use unwrap() as the password has been set above
+    let password = password.as_ref().unwrap().as_bytes();
+    let private_key = match parse_openssl_ed25519_privkey_encrypted(data, password) {
+        Err(ED25519ParserError::InvalidPEMTag) => {
+            parse_openssh_ed25519_privkey_encrypted(data, password)
+        }
+        result => result,
+    };
+    match private_key {
+        Ok(private_key) => Ok(private_key),
+        Err(ED25519ParserError::DecryptionError) => {
+            eprintln!(" [!] Unable to decrypt the private key, is the password correct?");
+            Err(Error::InvalidECCKeyFormat)
+        }
+        Err(_) => Err(Error::InvalidECCKeyFormat),
+    }
+}
+
+/// Password of the archive, asked once for all
+static ARCHIVE_PASSWORD: Mutex<Option<String>> = Mutex::new(None);
+
+/// Return the password of the archive to read, from `--password-file`, or
+/// asked on the terminal
+fn archive_password(matches: &ArgMatches) -> Result<String, Error> {
+    let mut password = ARCHIVE_PASSWORD.lock().expect("Password lock poisoned");
+    if password.is_none() {
+        *password = Some(match matches.value_of_os("password_file") {
+            Some(path) => read_password_file(path)?,
+            None => rpassword::prompt_password("Archive password: ")?,
+        });
+    }
+    // Safe to use unwrap() as the password has been set above
+    Ok(password.as_ref().unwrap().clone())
+}
+
+/// Return the password of the archive to create, from `--password-file`, or
+/// asked twice on the terminal
+fn new_archive_password(matches: &ArgMatches) -> Result<String, Error> {
+    if let Some(path) = matches.value_of_os("password_file") {
+        return read_password_file(path);
+    }
+    let password = rpassword::prompt_password("Archive password: ")?;
+    let mut confirmation = rpassword::prompt_password("Confirm the archive password: ")?;
+    let matching = password == confirmation;
+    confirmation.zeroize();
+    if matching {
+        Ok(password)
+    } else {
+        Err(Error::BadAPIArgument("Passwords do not match".to_string()))
+    }
+}
+
+/// Public keys paths from the arguments or, if none, from the profile
+fn public_key_paths(matches: &ArgMatches, profile: &Profile) -> Vec<PathBuf> {
+    match matches.values_of_os("public_keys") {
+        Some(public_key_args) => public_key_args.map(PathBuf::from).collect(),
+        None => profile.recipients.clone(),
+    }
+}
+
+fn open_ecc_public_keys(paths: &[PathBuf]) -> Result<Vec<x25519_dalek::PublicKey>, Error> {
     let mut public_keys = Vec::new();
-    if let Some(public_key_args) = matches.values_of_os("public_keys") {
-        for public_key_arg in public_key_args {
-            let mut file = File::open(public_key_arg)?;
-            // Load the the ECC key in-memory and parse it
-            let mut buf = Vec::new();
-            file.read_to_end(&mut buf)?;
-            match parse_openssl_ed25519_pubkey(&buf) {
+    for path in paths {
+        let mut file = File::open(path)?;
+        // Load the the ECC key in-memory and parse it
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        // Hybrid keys are loaded by `open_hybrid_public_keys`
+        if parse_hybrid_pem(&buf, HYBRID_PUBLIC_KEY_TAG).is_some() {
+            continue;
+        }
+        match parse_openssl_ed25519_pubkey(&buf) {
+            Ok(public_key) => public_keys.push(public_key),
+            // OpenSSH `authorized_keys` file, or a single `.pub` key
+            Err(_) => match parse_openssh_ed25519_pubkeys_many(&buf) {
+                Ok(keys) if !keys.is_empty() => public_keys.extend(keys),
+                _ => return Err(Error::InvalidECCKeyFormat),
+            },
+        };
+    }
+    Ok(public_keys)
+}
+
+/// Hybrid public keys among the public keys
+fn open_hybrid_public_keys(paths: &[PathBuf]) -> Result<Vec<HybridPublicKey>, Error> {
+    let mut public_keys = Vec::new();
+    for path in paths {
+        if let Some(contents) = parse_hybrid_pem(&fs::read(path)?, HYBRID_PUBLIC_KEY_TAG) {
+            public_keys.push(HybridPublicKey::from_bytes(&contents)?);
+        }
+    }
+    Ok(public_keys)
+}
+
+/// Ed25519 keys to sign archives with (DER or PEM format)
+fn open_signing_keys(matches: &ArgMatches) -> Result<Vec<ed25519_dalek::Keypair>, Error> {
+    let mut signing_keys = Vec::new();
+    if let Some(signing_key_args) = matches.values_of_os("signing_keys") {
+        for signing_key_arg in signing_key_args {
+            let mut buf = fs::read(signing_key_arg)?;
+            let seed = parse_openssl_ed25519_signing_key(&buf);
+            buf.zeroize();
+            let mut seed = match seed {
+                Ok(seed) => seed,
+                Err(ED25519ParserError::PasswordRequired) => {
+                    eprintln!(" [!] Encrypted signing keys are not supported");
+                    return Err(Error::InvalidECCKeyFormat);
+                }
                 Err(_) => return Err(Error::InvalidECCKeyFormat),
-                Ok(public_key) => public_keys.push(public_key),
             };
+            let secret = ed25519_dalek::SecretKey::from_bytes(&seed);
+            seed.zeroize();
+            let secret = secret.map_err(|_| Error::InvalidECCKeyFormat)?;
+            let public = ed25519_dalek::PublicKey::from(&secret);
+            signing_keys.push(ed25519_dalek::Keypair { secret, public });
         }
     }
-    Ok(public_keys)
+    Ok(signing_keys)
+}
+
+/// Ed25519 public keys of the trusted signers (DER or PEM format)
+fn open_verification_keys(matches: &ArgMatches) -> Result<Vec<ed25519_dalek::PublicKey>, Error> {
+    let mut verification_keys = Vec::new();
+    if let Some(verification_key_args) = matches.values_of_os("verification_keys") {
+        for verification_key_arg in verification_key_args {
+            let buf = fs::read(verification_key_arg)?;
+            let public = parse_openssl_ed25519_verifying_key(&buf)
+                .map_err(|_| Error::InvalidECCKeyFormat)?;
+            verification_keys.push(
+                ed25519_dalek::PublicKey::from_bytes(&public)
+                    .map_err(|_| Error::InvalidECCKeyFormat)?,
+            );
+        }
+    }
+    Ok(verification_keys)
+}
+
+/// Layers selectable with --layers
+const LAYER_NAMES: [&str; 2] = ["compress", "encrypt"];
+/// Compression algorithms selectable with --compression-algo
+const COMPRESSION_ALGORITHM_NAMES: [&str; 3] = ["brotli", "zstd", "lz4"];
+
+/// Settings of 'create' shared through a TOML file, given with --profile.
+/// Arguments override its values
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Profile {
+    /// Public keys of the recipients, as --pubkey
+    recipients: Vec<PathBuf>,
+    /// Layers to use, as --layers
+    #[serde(deserialize_with = "deserialize_layers")]
+    layers: Option<Vec<String>>,
+    compression: CompressionProfile,
+    padding: PaddingProfile,
+    /// Glob patterns of the files to keep, as --include
+    include: Vec<String>,
+    /// Glob patterns of the files to skip, as --exclude
+    exclude: Vec<String>,
+    /// Output path, as --output. "{date}" and "{time}" are replaced by the
+    /// current UTC date (YYYYMMDD) and time (HHMMSS)
+    output: Option<String>,
+}
+
+/// `[compression]` table of a profile
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct CompressionProfile {
+    /// As --compression-algo
+    #[serde(deserialize_with = "deserialize_algorithm")]
+    algorithm: Option<String>,
+    /// As --compression-level
+    level: Option<u32>,
+    /// As --comp-dict
+    dictionary: Option<PathBuf>,
+    /// As --threads
+    threads: Option<usize>,
+}
+
+/// `[padding]` table of a profile, sizes being numbers of bytes or strings
+/// with a suffix (ex: "64K")
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct PaddingProfile {
+    /// As --pad
+    #[serde(deserialize_with = "deserialize_size")]
+    granularity: Option<u64>,
+    /// As --pad-decoy
+    #[serde(deserialize_with = "deserialize_size")]
+    max_decoy: Option<u64>,
+}
+
+/// Check `value` is one of `names`
+fn check_name<E: de::Error>(value: &str, names: &'static [&'static str]) -> Result<(), E> {
+    if names.contains(&value) {
+        Ok(())
+    } else {
+        Err(E::unknown_variant(value, names))
+    }
+}
+
+fn deserialize_layers<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Vec<String>>, D::Error> {
+    let layers = Vec::<String>::deserialize(deserializer)?;
+    for layer in &layers {
+        check_name::<D::Error>(layer, &LAYER_NAMES)?;
+    }
+    Ok(Some(layers))
+}
+
+fn deserialize_algorithm<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let algorithm = String::deserialize(deserializer)?;
+    check_name::<D::Error>(&algorithm, &COMPRESSION_ALGORITHM_NAMES)?;
+    Ok(Some(algorithm))
+}
+
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+    match Size::deserialize(deserializer)? {
+        Size::Bytes(size) => Ok(Some(size)),
+        Size::Text(text) => match parse_size(&text) {
+            Some(size) => Ok(Some(size)),
+            None => Err(de::Error::custom(format!("invalid size {:?}", text))),
+        },
+    }
+}
+
+impl Profile {
+    /// Load the profile at `path`, exiting with an error pointing at the
+    /// offending key if it is invalid
+    fn load(path: &Path) -> Result<Profile, Error> {
+        let text = fs::read_to_string(path)?;
+        // Relative paths are relative to the profile
+        let directory = path.parent().unwrap_or_else(|| Path::new(""));
+        match Profile::parse(&text, directory) {
+            Ok(profile) => Ok(profile),
+            Err(message) => {
+                eprintln!(" [!] Invalid profile {:?}: {}", path, message);
+                std::process::exit(1);
+            }
+        }
+    }
+
+    fn parse(text: &str, directory: &Path) -> Result<Profile, String> {
+        // Syntax, types and names errors are reported by toml with the key
+        // and its line
+        let mut profile: Profile = toml::from_str(text).map_err(|err| err.to_string())?;
+
+        for (i, recipient) in profile.recipients.iter_mut().enumerate() {
+            let path = directory.join(recipient.as_path());
+            if let Err(err) = fs::metadata(&path) {
+                return Err(format!(
+                    "recipients[{}]: unable to read {:?} ({})",
+                    i, path, err
+                ));
+            }
+            *recipient = path;
+        }
+
+        let compression = &mut profile.compression;
+        if let Some(dictionary) = &mut compression.dictionary {
+            let path = directory.join(dictionary.as_path());
+            if let Err(err) = fs::metadata(&path) {
+                return Err(format!(
+                    "compression.dictionary: unable to read {:?} ({})",
+                    path, err
+                ));
+            }
+            *dictionary = path;
+        }
+        if let Some(level) = compression.level {
+            let algorithm = match (&compression.algorithm, &compression.dictionary) {
+                (Some(algorithm), _) => compression_algorithm(algorithm),
+                // Dictionaries imply Zstandard
+                (None, Some(_)) => CompressionAlgorithm::Zstd,
+                (None, None) => CompressionAlgorithm::default(),
+            };
+            let levels = algorithm.levels();
+            if !levels.contains(&level) {
+                return Err(format!(
+                    "compression.level: {} is not in [{} .. {}]",
+                    level,
+                    levels.start(),
+                    levels.end()
+                ));
+            }
+        }
+
+        for (key, patterns) in &[("include", &profile.include), ("exclude", &profile.exclude)] {
+            for (i, pattern) in patterns.iter().enumerate() {
+                if let Err(err) = Pattern::new(pattern) {
+                    return Err(format!(
+                        "{}[{}]: invalid glob pattern {:?} ({})",
+                        key, i, pattern, err
+                    ));
+                }
+            }
+        }
+
+        if let Some(output) = &profile.output {
+            if profile_output(output, UNIX_EPOCH).contains('{') {
+                return Err(format!(
+                    "output: unknown placeholder in {:?}, only {{date}} and {{time}} are supported",
+                    output
+                ));
+            }
+        }
+
+        Ok(profile)
+    }
+}
+
+/// Output path of a profile, with the placeholders replaced according to `now`
+fn profile_output(template: &str, now: SystemTime) -> String {
+    let seconds = now
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days(seconds / 86_400);
+    let time = seconds % 86_400;
+    template
+        .replace("{date}", &format!("{:04}{:02}{:02}", year, month, day))
+        .replace(
+            "{time}",
+            &format!("{:02}{:02}{:02}", time / 3600, time / 60 % 60, time % 60),
+        )
+}
+
+/// (year, month, day) of the `days`-th day after 1970-01-01, from
+/// http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (era * 400 + yoe + u64::from(month <= 2), month, day)
 }
 
 /// Return the ArchiveWriterConfig corresponding to provided arguments
 fn config_from_matches(matches: &ArgMatches) -> ArchiveWriterConfig {
+    config_from_profile(matches, &Profile::default())
+}
+
+/// Return the ArchiveWriterConfig corresponding to provided arguments, the
+/// ones not given being taken from `profile`
+fn config_from_profile(matches: &ArgMatches, profile: &Profile) -> ArchiveWriterConfig {
     let mut config = ArchiveWriterConfig::new();
 
     // Get layers
@@ -90,6 +587,8 @@ fn config_from_matches(matches: &ArgMatches) -> ArchiveWriterConfig {
         for layer in matches.values_of("layers").unwrap() {
             layers.push(layer);
         }
+    } else if let Some(profile_layers) = &profile.layers {
+        layers.extend(profile_layers.iter().map(String::as_str));
     } else {
         // Default
         layers.push("compress");
@@ -107,47 +606,153 @@ fn config_from_matches(matches: &ArgMatches) -> ArchiveWriterConfig {
     }
 
     // Encryption specifics
-    if matches.is_present("public_keys") {
+    let public_key_paths = public_key_paths(matches, profile);
+    if !public_key_paths.is_empty() {
         if !config.is_layers_enabled(Layers::ENCRYPT) {
             eprintln!(
                 "[WARNING] 'public_keys' argument ignored, because 'encrypt' layer is not enabled"
             );
         } else {
-            let public_keys = match open_ecc_public_keys(matches) {
+            let public_keys = match open_ecc_public_keys(&public_key_paths) {
                 Ok(public_keys) => public_keys,
                 Err(error) => {
                     panic!("[ERROR] Unable to open public keys: {}", error);
                 }
             };
             config.add_public_keys(&public_keys);
+            let hybrid_public_keys = match open_hybrid_public_keys(&public_key_paths) {
+                Ok(public_keys) => public_keys,
+                Err(error) => {
+                    panic!("[ERROR] Unable to open public keys: {}", error);
+                }
+            };
+            config.add_hybrid_public_keys(&hybrid_public_keys);
+        }
+    }
+    if matches.is_present("password") {
+        if !config.is_layers_enabled(Layers::ENCRYPT) {
+            eprintln!(
+                "[WARNING] 'password' argument ignored, because 'encrypt' layer is not enabled"
+            );
+        } else {
+            let mut password = match new_archive_password(matches) {
+                Ok(password) => password,
+                Err(error) => {
+                    panic!("[ERROR] Unable to get the password: {}", error);
+                }
+            };
+            config.with_password(&password);
+            password.zeroize();
+        }
+    }
+    if let Some(cipher) = matches.value_of("cipher") {
+        if !config.is_layers_enabled(Layers::ENCRYPT) {
+            eprintln!(
+                "[WARNING] 'cipher' argument ignored, because 'encrypt' layer is not enabled"
+            );
+        } else if cipher == "aes-gcm-siv" {
+            config.with_encryption_cipher(EncryptionCipher::AesGcmSiv256);
+        } else {
+            config.with_encryption_cipher(EncryptionCipher::AesGcm256);
+        }
+    }
+
+    // Signature specifics
+    if matches.is_present("signing_keys") {
+        let signing_keys = match open_signing_keys(matches) {
+            Ok(signing_keys) => signing_keys,
+            Err(error) => {
+                panic!("[ERROR] Unable to open signing keys: {}", error);
+            }
+        };
+        for signing_key in &signing_keys {
+            config.add_signing_key(signing_key);
         }
     }
 
     // Compression specifics
-    if matches.is_present("compression_level") {
+    let compression = &profile.compression;
+    let algorithm = matches
+        .value_of("compression_algo")
+        .or(compression.algorithm.as_deref());
+    if let Some(algorithm) = algorithm {
+        if !config.is_layers_enabled(Layers::COMPRESS) {
+            eprintln!("[WARNING] 'compression_algo' argument ignored, because 'compress' layer is not enabled");
+        } else {
+            config.with_compression_algorithm(compression_algorithm(algorithm));
+        }
+    }
+    let dictionary_path = matches
+        .value_of_os("comp_dict")
+        .or_else(|| compression.dictionary.as_ref().map(|path| path.as_os_str()));
+    if let Some(dictionary_path) = dictionary_path {
+        if !config.is_layers_enabled(Layers::COMPRESS) {
+            eprintln!(
+                "[WARNING] 'comp_dict' argument ignored, because 'compress' layer is not enabled"
+            );
+        } else {
+            // Dictionaries are specific to Zstandard, used unless asked otherwise
+            if algorithm.is_none() {
+                config.with_compression_algorithm(CompressionAlgorithm::Zstd);
+            }
+            let dictionary = fs::read(dictionary_path).unwrap_or_else(|err| {
+                panic!("[ERROR] Unable to read the compression dictionary: {}", err)
+            });
+            if config.with_compression_dictionary(dictionary).is_err() {
+                panic!("comp_dict needs the 'zstd' compression algorithm, and a non-empty dictionary of at most 1M");
+            }
+        }
+    }
+    let compression_level = match matches.value_of("compression_level") {
+        Some(level) => Some(level.parse().expect("compression_level must be an int")),
+        None => compression.level,
+    };
+    if let Some(comp_level) = compression_level {
         if !config.is_layers_enabled(Layers::COMPRESS) {
             eprintln!("[WARNING] 'compression_level' argument ignored, because 'compress' layer is not enabled");
         } else {
-            let comp_level: u32 = matches
-                .value_of("compression_level")
-                .unwrap()
-                .parse()
-                .expect("compression_level must be an int");
-            if comp_level > 11 {
-                panic!("compression_level must be in [0 .. 11]");
+            let levels = config.compression_algorithm().levels();
+            if !levels.contains(&comp_level) {
+                panic!(
+                    "compression_level must be in [{} .. {}]",
+                    levels.start(),
+                    levels.end()
+                );
             }
             config.with_compression_level(comp_level).unwrap();
         }
     }
+    let threads = match matches.value_of("threads") {
+        Some(threads) => Some(threads.parse().expect("threads must be an int")),
+        None => compression.threads,
+    };
+    if let Some(threads) = threads {
+        if !config.is_layers_enabled(Layers::COMPRESS) {
+            eprintln!(
+                "[WARNING] 'threads' argument ignored, because 'compress' layer is not enabled"
+            );
+        } else {
+            config.with_threads(threads);
+        }
+    }
 
     config
 }
 
+/// Compression algorithm of name `name`, among 'brotli', 'zstd' and 'lz4'
+fn compression_algorithm(name: &str) -> CompressionAlgorithm {
+    match name {
+        "zstd" => CompressionAlgorithm::Zstd,
+        "lz4" => CompressionAlgorithm::Lz4,
+        _ => CompressionAlgorithm::Brotli,
+    }
+}
+
 fn destination_from_output_argument(output_argument: &str) -> Result<OutputTypes, Error> {
     let destination = if output_argument != "-" {
         let path = Path::new(&output_argument);
         OutputTypes::File {
-            file: File::create(&path)?,
+            file: File::create(path)?,
         }
     } else {
         OutputTypes::Stdout
@@ -157,17 +762,80 @@ fn destination_from_output_argument(output_argument: &str) -> Result<OutputTypes
 
 /// Return an ArchiveWriter corresponding to provided arguments
 fn writer_from_matches<'a>(matches: &ArgMatches) -> Result<ArchiveWriter<'a, OutputTypes>, Error> {
-    let config = config_from_matches(matches);
-
     // Safe to use unwrap() because the option is required()
     let output = matches.value_of("output").unwrap();
+    writer_from_config(matches, output, config_from_matches(matches))
+}
 
-    let destination = destination_from_output_argument(output)?;
+/// Return an ArchiveWriter using `config`, writing to `output` as specified by
+/// provided arguments
+fn writer_from_config<'a>(
+    matches: &ArgMatches,
+    output: &str,
+    config: ArchiveWriterConfig,
+) -> Result<ArchiveWriter<'a, OutputTypes>, Error> {
+    let destination = match matches.value_of("volume_size") {
+        Some(volume_size) => {
+            let volume_size = match parse_size(volume_size) {
+                Some(size) => size,
+                None => {
+                    eprintln!(" [!] Invalid size {:?}", volume_size);
+                    std::process::exit(1);
+                }
+            };
+            if output == "-" {
+                eprintln!(" [!] Volumes must be written to files");
+                std::process::exit(1);
+            }
+            OutputTypes::Volumes {
+                volumes: VolumeWriter::new(output, volume_size)?,
+            }
+        }
+        None => destination_from_output_argument(output)?,
+    };
 
     // Instantiate output writer
     ArchiveWriter::from_config(destination, config)
 }
 
+/// PIN of the PKCS#11 tokens, asked once
+#[cfg(feature = "pkcs11")]
+static TOKEN_PIN: Mutex<Option<String>> = Mutex::new(None);
+
+/// Add the private keys designated by PKCS#11 `uris`. Unless given in the URI,
+/// the PIN is asked on the terminal; an empty one skips the login
+#[cfg(feature = "pkcs11")]
+fn add_pkcs11_keys(config: &mut ArchiveReaderConfig, uris: Vec<&str>) {
+    for uri in uris {
+        let key = Pkcs11Uri::parse(uri).and_then(|parsed| {
+            if parsed.pin.is_some() {
+                return Pkcs11PrivateKey::open(uri, None);
+            }
+            let mut pin = TOKEN_PIN.lock().expect("PIN lock poisoned");
+            if pin.is_none() {
+                *pin = Some(rpassword::prompt_password("Token PIN: ")?);
+            }
+            // Safe to use unwrap() as the PIN has been set above
+            let pin = pin.as_deref().unwrap();
+            Pkcs11PrivateKey::open(uri, Some(pin).filter(|pin| !pin.is_empty()))
+        });
+        match key {
+            Ok(key) => {
+                config.add_private_key_provider(key);
+            }
+            Err(error) => {
+                panic!("[ERROR] Unable to open the PKCS#11 key: {}", error);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "pkcs11"))]
+fn add_pkcs11_keys(_config: &mut ArchiveReaderConfig, _uris: Vec<&str>) {
+    eprintln!("[ERROR] '--pkcs11-uri' is only available with mlar built with the 'pkcs11' feature");
+    std::process::exit(1);
+}
+
 /// Return the ArchiveReaderConfig corresponding to provided arguments
 fn readerconfig_from_matches(matches: &ArgMatches) -> ArchiveReaderConfig {
     let mut config = ArchiveReaderConfig::new();
@@ -180,67 +848,272 @@ fn readerconfig_from_matches(matches: &ArgMatches) -> ArchiveReaderConfig {
             }
         };
         config.add_private_keys(&private_keys);
+        let hybrid_private_keys = match open_hybrid_private_keys(matches) {
+            Ok(private_keys) => private_keys,
+            Err(error) => {
+                panic!("[ERROR] Unable to open private keys: {}", error);
+            }
+        };
+        config.add_hybrid_private_keys(&hybrid_private_keys);
+    }
+    if let Some(uris) = matches.values_of("pkcs11_uri") {
+        add_pkcs11_keys(&mut config, uris.collect());
+    }
+    if matches.is_present("verification_keys") {
+        let verification_keys = match open_verification_keys(matches) {
+            Ok(verification_keys) => verification_keys,
+            Err(error) => {
+                panic!("[ERROR] Unable to open verification keys: {}", error);
+            }
+        };
+        config.add_verification_keys(&verification_keys);
+    }
+    if matches.is_present("progress") {
+        config.with_progress_observer(ProgressForwarder);
     }
 
     config
 }
 
-fn open_mla_file<'a>(matches: &ArgMatches) -> Result<ArchiveReader<'a, File>, Error> {
-    let config = readerconfig_from_matches(matches);
+/// Open the archive given as input. A path ending with ".001" is the first of
+/// the volumes of a multi-volume archive, which must all be available unless
+/// `partial` is set
+fn open_input(matches: &ArgMatches, partial: bool) -> Result<InputTypes, Error> {
+    // Safe to use unwrap() because the option is required()
+    open_input_path(matches.value_of("input").unwrap(), partial)
+}
+
+fn open_input_path(mla_file: &str, partial: bool) -> Result<InputTypes, Error> {
+    match mla_file.strip_suffix(".001") {
+        Some(base) if partial => Ok(InputTypes::Volumes {
+            volumes: VolumeReader::open_available(base)?,
+        }),
+        Some(base) => Ok(InputTypes::Volumes {
+            volumes: VolumeReader::open(base)?,
+        }),
+        None => Ok(InputTypes::File {
+            file: File::open(Path::new(&mla_file))?,
+        }),
+    }
+}
 
+fn open_mla_file<'a>(matches: &ArgMatches) -> Result<ArchiveReader<'a, InputTypes>, Error> {
     // Safe to use unwrap() because the option is required()
-    let mla_file = matches.value_of("input").unwrap();
-    let path = Path::new(&mla_file);
-    let file = File::open(&path)?;
+    open_mla_path(matches, matches.value_of("input").unwrap())
+}
+
+/// Open the archive `mla_file`, with the keys given in `matches`
+fn open_mla_path<'a>(
+    matches: &ArgMatches,
+    mla_file: &str,
+) -> Result<ArchiveReader<'a, InputTypes>, Error> {
+    let config = readerconfig_from_matches(matches);
 
     // Instantiate reader
-    ArchiveReader::from_config(file, config)
+    match ArchiveReader::from_config(open_input_path(mla_file, false)?, config) {
+        // Without private key, the archive may still be opened with a password
+        Err(Error::PrivateKeyNeeded) if !matches.is_present("private_keys") => {
+            let mut config = readerconfig_from_matches(matches);
+            config.with_password(&archive_password(matches)?);
+            ArchiveReader::from_config(open_input_path(mla_file, false)?, config)
+        }
+        result => result,
+    }
 }
 
 // Utils: common code to load a mla_file from arguments, fail-safe mode
 fn open_failsafe_mla_file<'a>(
     matches: &ArgMatches,
-) -> Result<ArchiveFailSafeReader<'a, File>, Error> {
+) -> Result<ArchiveFailSafeReader<'a, InputTypes>, Error> {
     let config = readerconfig_from_matches(matches);
 
+    // Instantiate reader. Missing volumes are tolerated, to recover the
+    // content of the available ones
+    match ArchiveFailSafeReader::from_config(open_input(matches, true)?, config) {
+        // Without private key, the archive may still be opened with a password
+        Err(Error::PrivateKeyNeeded) if !matches.is_present("private_keys") => {
+            let mut config = readerconfig_from_matches(matches);
+            config.with_password(&archive_password(matches)?);
+            ArchiveFailSafeReader::from_config(open_input(matches, true)?, config)
+        }
+        result => result,
+    }
+}
+
+/// Size of the archive given as input, all its available volumes included
+fn input_size(matches: &ArgMatches) -> u64 {
     // Safe to use unwrap() because the option is required()
     let mla_file = matches.value_of("input").unwrap();
-    let path = Path::new(&mla_file);
-    let file = File::open(&path)?;
+    match mla_file.strip_suffix(".001") {
+        Some(base) => (1..)
+            .map_while(|index| fs::metadata(volume_path(base, index)).ok())
+            .map(|metadata| metadata.len())
+            .sum(),
+        None => fs::metadata(mla_file).map_or(0, |metadata| metadata.len()),
+    }
+}
 
-    // Instantiate reader
-    ArchiveFailSafeReader::from_config(file, config)
+/// What the progress of an operation is measured on
+#[derive(Clone, Copy, PartialEq)]
+enum ProgressBasis {
+    /// Content of the entries
+    EntriesData,
+    /// Bytes read from the archive
+    BytesRead,
 }
 
-fn add_file_to_tar<R: Read, W: Write>(
-    tar_file: &mut Builder<W>,
-    sub_file: ArchiveFile<R>,
-) -> Result<(), Error> {
-    // Use indexes to avoid in-memory copy
-    let mut header = Header::new_gnu();
-    header.set_size(sub_file.size);
-    header.set_mode(0o444); // Create files as read-only
-    header.set_cksum();
-
-    // Force relative path, the trivial way (does not support Windows paths)
-    let filename = {
-        if Path::new(&sub_file.filename).is_absolute() {
-            format!("./{}", sub_file.filename)
-        } else {
-            sub_file.filename
-        }
-    };
+/// Width of the progress bar, in characters
+const PROGRESS_BAR_WIDTH: usize = 30;
+/// Minimum delay between two progress reports
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(200);
 
-    if let Err(why) = tar_file.append_data(&mut header, &filename, sub_file.data) {
-        panic!(
-            "Error while adding file \"{}\" to tarball: {}",
-            filename, why
-        );
-    }
-    Ok(())
+/// Progress of the current operation, reported on stderr as a progress bar,
+/// or as one JSON object per line
+struct ProgressReporter {
+    json: bool,
+    basis: ProgressBasis,
+    total: u64,
+    done: u64,
+    entries_completed: u64,
+    current_entry: String,
+    start: Instant,
+    last_report: Option<Instant>,
 }
 
-/// Arguments for action 'extract' to match file names in the archive
+impl ProgressReporter {
+    fn advance(&mut self, basis: ProgressBasis, count: u64) {
+        if basis == self.basis {
+            // Seeks may read some bytes several times: stay within the total
+            self.done = std::cmp::min(self.done + count, self.total);
+            self.report(false);
+        }
+    }
+
+    /// Estimated remaining time, extrapolated from the elapsed one
+    fn eta(&self) -> Option<Duration> {
+        if self.done == 0 {
+            return None;
+        }
+        let remaining = self.total.saturating_sub(self.done) as f64 / self.done as f64;
+        Some(self.start.elapsed().mul_f64(remaining))
+    }
+
+    /// Report the progress, at most every `PROGRESS_INTERVAL` unless `force`
+    fn report(&mut self, force: bool) {
+        let now = Instant::now();
+        match self.last_report {
+            Some(last) if !force && now.duration_since(last) < PROGRESS_INTERVAL => return,
+            _ => self.last_report = Some(now),
+        }
+        let eta = self.eta();
+        if self.json {
+            eprintln!(
+                "{}",
+                json!({
+                    "done": self.done,
+                    "total": self.total,
+                    "entries_completed": self.entries_completed,
+                    "entry": self.current_entry,
+                    "eta_seconds": eta.map(|eta| eta.as_secs()),
+                })
+            );
+            return;
+        }
+        let ratio = if self.total == 0 {
+            1.0
+        } else {
+            (self.done as f64 / self.total as f64).min(1.0)
+        };
+        let filled = (ratio * PROGRESS_BAR_WIDTH as f64) as usize;
+        let eta = match eta {
+            Some(eta) => format!("{:02}:{:02}", eta.as_secs() / 60, eta.as_secs() % 60),
+            None => "--:--".to_string(),
+        };
+        let line = format!(
+            "[{}{}] {:3}% {} / {} - ETA {} - {} entries",
+            "#".repeat(filled),
+            "-".repeat(PROGRESS_BAR_WIDTH - filled),
+            (ratio * 100.0) as u32,
+            self.done.file_size(file_size_opts::CONVENTIONAL).unwrap(),
+            self.total.file_size(file_size_opts::CONVENTIONAL).unwrap(),
+            eta,
+            self.entries_completed,
+        );
+        // Pad to erase the end of a longer previous line
+        eprint!("\r{:<80}", line);
+    }
+
+    /// Report the completion of the operation
+    fn finish(&mut self) {
+        // Headers and skipped content are not part of the measured bytes
+        self.done = self.total;
+        self.report(true);
+        if !self.json {
+            eprintln!();
+        }
+    }
+}
+
+/// Progress of the current operation, if `--progress` is given
+static PROGRESS: Mutex<Option<ProgressReporter>> = Mutex::new(None);
+
+/// Forward the notifications of the library to `PROGRESS`
+struct ProgressForwarder;
+
+impl ProgressForwarder {
+    fn with_reporter<F: FnOnce(&mut ProgressReporter)>(action: F) {
+        if let Some(reporter) = PROGRESS.lock().expect("Progress lock poisoned").as_mut() {
+            action(reporter);
+        }
+    }
+}
+
+impl ProgressObserver for ProgressForwarder {
+    fn bytes_read(&mut self, count: u64) {
+        Self::with_reporter(|reporter| reporter.advance(ProgressBasis::BytesRead, count));
+    }
+
+    fn entry_started(&mut self, name: &str) {
+        Self::with_reporter(|reporter| reporter.current_entry = name.to_string());
+    }
+
+    fn entry_data(&mut self, _name: &str, count: u64) {
+        Self::with_reporter(|reporter| reporter.advance(ProgressBasis::EntriesData, count));
+    }
+
+    fn entry_completed(&mut self, _name: &str) {
+        Self::with_reporter(|reporter| {
+            reporter.entries_completed += 1;
+            reporter.report(false);
+        });
+    }
+}
+
+/// Start reporting the progress, measured on `basis` up to `total`, if
+/// `--progress` is given
+fn start_progress(matches: &ArgMatches, basis: ProgressBasis, total: u64) {
+    if let Some(format) = matches.value_of("progress") {
+        *PROGRESS.lock().expect("Progress lock poisoned") = Some(ProgressReporter {
+            json: format == "json",
+            basis,
+            total,
+            done: 0,
+            entries_completed: 0,
+            current_entry: String::new(),
+            start: Instant::now(),
+            last_report: None,
+        });
+    }
+}
+
+/// Report the completion of the operation, if its progress is reported
+fn finish_progress() {
+    if let Some(mut reporter) = PROGRESS.lock().expect("Progress lock poisoned").take() {
+        reporter.finish();
+    }
+}
+
+/// Arguments for action 'extract' to match file names in the archive
 enum ExtractFileNameMatcher {
     /// Match a list of files, where the order does not matter
     Files(HashSet<String>),
@@ -262,7 +1135,7 @@ impl ExtractFileNameMatcher {
                     .map(|pat| {
                         Pattern::new(pat)
                             .map_err(|err| {
-                                eprintln!("[!] Invalid glob pattern {:?} ({:?})", pat, err);
+                                eprintln!("[!] Invalid glob pattern {:?} ({})", pat, err);
                             })
                             .expect("Invalid glob pattern")
                     })
@@ -279,13 +1152,78 @@ impl ExtractFileNameMatcher {
                 files.is_empty() || files.contains(file_name)
             }
             ExtractFileNameMatcher::GlobPatterns(ref patterns) => {
-                patterns.is_empty() || patterns.iter().any(|pat| pat.matches(&file_name))
+                patterns.is_empty() || patterns.iter().any(|pat| pat.matches(file_name))
             }
             ExtractFileNameMatcher::Anything => true,
         }
     }
 }
 
+/// A file name pattern of `--include` / `--exclude`
+enum NamePattern {
+    Glob(Pattern),
+    Regex(Regex),
+}
+
+/// `--include` / `--exclude` filters of 'create', 'list' and 'extract',
+/// applied on file names only (no file data is read)
+struct NameFilter {
+    include: Vec<NamePattern>,
+    exclude: Vec<NamePattern>,
+}
+impl NameFilter {
+    fn from_matches(matches: &ArgMatches) -> Self {
+        NameFilter::from_profile(matches, &Profile::default())
+    }
+    /// Filters from provided arguments or, for those not given, the glob
+    /// patterns of `profile`
+    fn from_profile(matches: &ArgMatches, profile: &Profile) -> Self {
+        let use_regex = matches.is_present("regex");
+        let parse = |arg: &str, profile_patterns: &[String]| -> Vec<NamePattern> {
+            let (patterns, use_regex): (Vec<&str>, bool) = match matches.values_of(arg) {
+                Some(patterns) => (patterns.collect(), use_regex),
+                None => (profile_patterns.iter().map(String::as_str).collect(), false),
+            };
+            patterns
+                .into_iter()
+                .map(|pat| {
+                    if use_regex {
+                        match Regex::new(pat) {
+                            Ok(regex) => NamePattern::Regex(regex),
+                            Err(err) => {
+                                eprintln!("[!] Invalid regex {:?} ({})", pat, err);
+                                std::process::exit(1);
+                            }
+                        }
+                    } else {
+                        match Pattern::new(pat) {
+                            Ok(pattern) => NamePattern::Glob(pattern),
+                            Err(err) => {
+                                eprintln!("[!] Invalid glob pattern {:?} ({})", pat, err);
+                                std::process::exit(1);
+                            }
+                        }
+                    }
+                })
+                .collect()
+        };
+        NameFilter {
+            include: parse("include", &profile.include),
+            exclude: parse("exclude", &profile.exclude),
+        }
+    }
+    /// A file is kept if it matches one of the includes (if any), and none of
+    /// the excludes
+    fn match_file_name(&self, file_name: &str) -> bool {
+        let matches = |pat: &NamePattern| match pat {
+            NamePattern::Glob(pattern) => pattern.matches(file_name),
+            NamePattern::Regex(regex) => regex.is_match(file_name.as_bytes()),
+        };
+        (self.include.is_empty() || self.include.iter().any(matches))
+            && !self.exclude.iter().any(matches)
+    }
+}
+
 /// Compute the full path of the final file, using defensive measures
 /// similar as what tar-rs does for `Entry::unpack_in`:
 /// https://github.com/alexcrichton/tar-rs/blob/0.4.26/src/entry.rs#L344
@@ -321,7 +1259,27 @@ fn create_file<P1: AsRef<Path>>(
     output_dir: P1,
     fname: &str,
 ) -> Result<Option<(File, PathBuf)>, Error> {
-    let extracted_path = match get_extracted_path(output_dir.as_ref(), &fname) {
+    let extracted_path = match create_parent_directory(output_dir, fname)? {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+    Ok(Some((
+        File::create(&extracted_path).map_err(|err| {
+            let err = Error::from(err);
+            report_error(&format!("Unable to create \"{}\"", fname), &err);
+            err
+        })?,
+        extracted_path,
+    )))
+}
+
+/// Create the parent directories of `fname` in a given output directory, and
+/// return the path of `fname`, or `None` if it must be skipped
+fn create_parent_directory<P1: AsRef<Path>>(
+    output_dir: P1,
+    fname: &str,
+) -> Result<Option<PathBuf>, Error> {
+    let extracted_path = match get_extracted_path(output_dir.as_ref(), fname) {
         Some(p) => p,
         None => return Ok(None),
     };
@@ -337,23 +1295,48 @@ fn create_file<P1: AsRef<Path>>(
             return Ok(None);
         }
     };
-    if !containing_directory.exists() {
-        fs::create_dir_all(&containing_directory).map_err(|err| {
-            eprintln!(
-                " [!] Error while creating output directory path for \"{}\" ({:?})",
-                output_dir.as_ref().display(),
+    // Create the directories one at a time, refusing symbolic links: an
+    // extracted link must not lead to directories being created outside of
+    // the output directory, before the check below
+    let relative = containing_directory
+        .strip_prefix(output_dir.as_ref())
+        .unwrap_or_else(|_| Path::new(""));
+    let mut directory = output_dir.as_ref().to_path_buf();
+    for part in relative.components() {
+        directory.push(part);
+        match directory.symlink_metadata() {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                eprintln!(
+                    " [!] Skipping file \"{}\" because its path goes through a symbolic link, {}",
+                    fname,
+                    directory.display()
+                );
+                return Ok(None);
+            }
+            Ok(metadata) if metadata.is_dir() => {}
+            _ => fs::create_dir(&directory).map_err(|err| {
+                let err = Error::from(err);
+                report_error(
+                    &format!(
+                        "Error while creating output directory path for \"{}\"",
+                        output_dir.as_ref().display()
+                    ),
+                    &err,
+                );
                 err
-            );
-            err
-        })?;
+            })?,
+        }
     }
 
     // Ensure that the containing directory is in the output dir
-    let containing_directory = fs::canonicalize(&containing_directory).map_err(|err| {
-        eprintln!(
-            " [!] Error while canonicalizing extracted file output directory path \"{}\" ({:?})",
-            containing_directory.display(),
-            err
+    let containing_directory = fs::canonicalize(containing_directory).map_err(|err| {
+        let err = Error::from(err);
+        report_error(
+            &format!(
+                "Error while canonicalizing extracted file output directory path \"{}\"",
+                containing_directory.display()
+            ),
+            &err,
         );
         err
     })?;
@@ -364,75 +1347,482 @@ fn create_file<P1: AsRef<Path>>(
         );
         return Ok(None);
     }
-    Ok(Some((
-        File::create(&extracted_path).map_err(|err| {
-            eprintln!(" [!] Unable to create \"{}\" ({:?})", fname, err);
-            err
-        })?,
-        extracted_path,
-    )))
+    Ok(Some(extracted_path))
+}
+
+#[cfg(unix)]
+fn create_symlink(path: &Path, target: &str) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(not(unix))]
+fn create_symlink(_path: &Path, _target: &str) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "symbolic links are only extracted on Unix",
+    ))
 }
 
-/// Wrapper with Write, to append data to a file
+/// Create the directories and symbolic links of `entries`, which have no
+/// content, in a given output directory
 ///
-/// This wrapper is used to avoid opening all files simultaneously, potentially
-/// reaching the filesystem limit, but rather appending to file on-demand
-/// This could be enhanced with a limited pool of active file, but this
-/// optimisation doesn't seems necessary for now
-struct FileWriter {
-    /// Target file for data appending
-    path: PathBuf,
+/// They are created once regular files are extracted, so that no file is
+/// written through an extracted symbolic link. The names and paths of the
+/// entries actually created are added to `created`
+fn create_special_entries(
+    output_dir: &Path,
+    entries: &[(String, EntryType)],
+    verbose: bool,
+    created: &mut Vec<(String, PathBuf)>,
+) -> Result<(), Error> {
+    for (fname, entry_type) in entries {
+        let path = match create_parent_directory(output_dir, fname)? {
+            Some(path) => path,
+            None => continue,
+        };
+        if verbose {
+            println!("{}", fname);
+        }
+        let res = match entry_type {
+            // An existing symbolic link is not taken for a directory
+            EntryType::Directory
+                if path
+                    .symlink_metadata()
+                    .is_ok_and(|metadata| metadata.is_dir()) =>
+            {
+                Ok(())
+            }
+            EntryType::Directory => fs::create_dir(&path),
+            EntryType::Symlink(target) => create_symlink(&path, target),
+            EntryType::File => Ok(()),
+        };
+        match res {
+            Ok(()) => created.push((fname.clone(), path)),
+            Err(err) => report_error(&format!("Unable to create \"{}\"", fname), &err.into()),
+        }
+    }
+    Ok(())
 }
 
-impl Write for FileWriter {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        fs::OpenOptions::new()
-            .append(true)
-            .open(&self.path)?
-            .write(buf)
+/// Convert a time recorded in `FileMetadata`, in seconds since the Unix epoch
+fn metadata_time(time: i64) -> SystemTime {
+    if time >= 0 {
+        UNIX_EPOCH + Duration::from_secs(time as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(time.unsigned_abs())
     }
+}
 
-    fn flush(&mut self) -> io::Result<()> {
-        Ok(())
+/// Restore the modification time, ownership and permissions of an extracted
+/// file from its `metadata`
+///
+/// The last component of `path` is not followed: a symbolic link only gets
+/// its ownership restored, and other entries are opened with `O_NOFOLLOW`
+/// before being modified through their descriptor
+#[cfg(unix)]
+fn restore_metadata(path: &Path, metadata: &FileMetadata) -> io::Result<()> {
+    use std::os::unix::fs::{fchown, lchown, OpenOptionsExt, PermissionsExt};
+
+    if let EntryType::Symlink(_) = metadata.entry_type {
+        if metadata.uid.is_some() || metadata.gid.is_some() {
+            match lchown(path, metadata.uid, metadata.gid) {
+                // Only root can give files away: keep the current owner, as tar
+                Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {}
+                res => res?,
+            }
+        }
+        return Ok(());
+    }
+
+    // Opening the file before changing its permissions, which may forbid it
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(path)?;
+    if let Some(mtime) = metadata.mtime {
+        file.set_modified(metadata_time(mtime))?;
+    }
+    if metadata.uid.is_some() || metadata.gid.is_some() {
+        match fchown(&file, metadata.uid, metadata.gid) {
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {}
+            res => res?,
+        }
+    }
+    // After ownership, whose change may clear the setuid and setgid bits
+    if let Some(mode) = metadata.mode {
+        file.set_permissions(fs::Permissions::from_mode(mode))?;
+    }
+    Ok(())
+}
+
+/// Only the modification time is restored on other platforms
+#[cfg(not(unix))]
+fn restore_metadata(path: &Path, metadata: &FileMetadata) -> io::Result<()> {
+    match (metadata.mtime, &metadata.entry_type) {
+        (Some(mtime), EntryType::File) | (Some(mtime), EntryType::Directory) => {
+            File::open(path)?.set_modified(metadata_time(mtime))
+        }
+        _ => Ok(()),
     }
 }
 
 // ----- Commands ------
 
+/// Identify a file with several names (hard links) by its (device, inode)
+#[cfg(unix)]
+fn hardlink_key(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    if metadata.nlink() > 1 {
+        Some((metadata.dev(), metadata.ino()))
+    } else {
+        None
+    }
+}
+
+/// Hard links are not detected on other platforms
+#[cfg(not(unix))]
+fn hardlink_key(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// Permissions, owner user ID and owner group ID of a file
+#[cfg(unix)]
+fn unix_mode_owner(metadata: &fs::Metadata) -> (Option<u32>, Option<u32>, Option<u32>) {
+    use std::os::unix::fs::MetadataExt;
+    (
+        Some(metadata.mode() & 0o7777),
+        Some(metadata.uid()),
+        Some(metadata.gid()),
+    )
+}
+
+/// Unix permissions and owners are not available on other platforms
+#[cfg(not(unix))]
+fn unix_mode_owner(_metadata: &fs::Metadata) -> (Option<u32>, Option<u32>, Option<u32>) {
+    (None, None, None)
+}
+
+/// Build the `FileMetadata` of a file to add, for 'create --preserve'
+fn file_metadata(metadata: &fs::Metadata, entry_type: EntryType) -> FileMetadata {
+    let (mode, uid, gid) = unix_mode_owner(metadata);
+    let mtime = metadata
+        .modified()
+        .ok()
+        .and_then(|mtime| match mtime.duration_since(UNIX_EPOCH) {
+            Ok(since) => i64::try_from(since.as_secs()).ok(),
+            Err(err) => i64::try_from(err.duration().as_secs())
+                .ok()
+                .map(|before| -before),
+        });
+    FileMetadata {
+        entry_type,
+        mode,
+        uid,
+        gid,
+        mtime,
+    }
+}
+
+/// Padding policy of --pad and --pad-decoy, falling back on `profile`
+fn padding_from_matches(matches: &ArgMatches, profile: &PaddingProfile) -> Option<PaddingPolicy> {
+    let size_arg = |value: &str| match parse_size(value) {
+        Some(size) => size,
+        None => {
+            eprintln!(" [!] Invalid size {:?}", value);
+            std::process::exit(1);
+        }
+    };
+    let granularity = match matches.value_of("pad") {
+        Some(granularity) => Some(size_arg(granularity)),
+        None => profile.granularity,
+    };
+    if granularity.is_none() && (matches.is_present("pad_decoy") || profile.max_decoy.is_some()) {
+        eprintln!(" [!] A maximum decoy size needs a padding granularity, with --pad or \"padding.granularity\" in the profile");
+        std::process::exit(1);
+    }
+    granularity.map(|granularity| {
+        // Without an explicit maximum, the decoy is up to one padding block
+        let max_decoy = match matches.value_of("pad_decoy") {
+            Some(max_decoy) => size_arg(max_decoy),
+            None => profile.max_decoy.unwrap_or(granularity),
+        };
+        PaddingPolicy {
+            granularity,
+            max_decoy,
+        }
+    })
+}
+
 fn create(matches: &ArgMatches) -> Result<(), Error> {
-    let mut mla = writer_from_matches(matches)?;
+    let profile = match matches.value_of_os("profile") {
+        Some(path) => Profile::load(Path::new(path))?,
+        None => Profile::default(),
+    };
+    let mut config = config_from_profile(matches, &profile);
+    let name_filter = NameFilter::from_profile(matches, &profile);
+    let mut files: Vec<&str> = matches
+        .values_of("files")
+        .map(|files| files.collect())
+        .unwrap_or_default();
+    files.retain(|filename| name_filter.match_file_name(filename));
+    if matches.is_present("autotune") {
+        // Pick block sizes according to the files to add
+        let mut sizes = Vec::new();
+        for filename in &files {
+            sizes.push(fs::metadata(filename)?.len());
+        }
+        config.tune_for_entry_sizes(&sizes);
+    }
+    if matches.is_present("digest") {
+        config.enable_output_digest();
+    }
+    if matches.is_present("dedup") {
+        config.with_dedup();
+    }
+    if let Some(policy) = padding_from_matches(matches, &profile.padding) {
+        // Entries are padded before compression, which would shrink them again
+        if config.is_layers_enabled(Layers::COMPRESS) {
+            eprintln!(" [!] Padding cannot hide the sizes of compressed files, the 'compress' layer must be disabled (ex: -l encrypt)");
+            std::process::exit(1);
+        }
+        config.with_padding_policy(policy);
+    }
+    if matches.is_present("deterministic") {
+        // Archives of different contents from the same seed share their key
+        // and nonce, which only AES-GCM-SIV withstands
+        if matches.value_of("cipher") == Some("aes-gcm") {
+            eprintln!(" [!] A deterministic archive is encrypted with AES-GCM-SIV, \"--cipher aes-gcm\" cannot be used");
+            std::process::exit(1);
+        }
+        // The encryption secrets are derived from the seed file content
+        let seed = match matches.value_of_os("seed_file") {
+            Some(seed_file) => Sha256::digest(&fs::read(seed_file)?).into(),
+            None if config.is_layers_enabled(Layers::ENCRYPT) => {
+                eprintln!(" [!] A seed file (--seed-file) is needed to create a deterministic encrypted archive");
+                std::process::exit(1);
+            }
+            None => [0u8; 32],
+        };
+        config.with_deterministic_seed(seed);
+        files.sort_unstable();
+    }
+    let progress = matches.is_present("progress");
+    if progress {
+        // Only the content of regular files is measured
+        let total = files
+            .iter()
+            .filter_map(|filename| fs::metadata(filename).ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum();
+        start_progress(matches, ProgressBasis::EntriesData, total);
+        config.with_progress_observer(ProgressForwarder);
+    }
+    let output = match (matches.value_of("output"), &profile.output) {
+        (Some(output), _) => output.to_string(),
+        (None, Some(template)) => {
+            let output = profile_output(template, SystemTime::now());
+            eprintln!("Output: {}", output);
+            output
+        }
+        (None, None) => {
+            eprintln!(" [!] An output is needed, with --output or \"output\" in the profile");
+            std::process::exit(1);
+        }
+    };
+    let mut mla = writer_from_config(matches, &output, config)?;
 
-    if let Some(files) = matches.values_of("files") {
-        for filename in files {
+    // Hard links: (device, inode) -> first name added
+    let preserve_hardlinks = matches.is_present("preserve_hardlinks");
+    let mut hardlink_targets: HashMap<(u64, u64), String> = HashMap::new();
+    let preserve = matches.is_present("preserve");
+    // Number of entries, and size of their content, for --stats
+    let mut entries: u64 = 0;
+    let mut content_size: u64 = 0;
+
+    for filename in files {
+        entries += 1;
+        if !progress {
             eprintln!("{}", filename);
-            let file = File::open(&Path::new(&filename))?;
-            let length = file.metadata()?.len();
-            mla.add_file(filename, length, file)?;
         }
+        if preserve {
+            // Symbolic links and directories are stored as such, without
+            // content
+            let metadata = fs::symlink_metadata(filename)?;
+            let entry_type = if metadata.file_type().is_symlink() {
+                let target = fs::read_link(filename)?;
+                Some(EntryType::Symlink(target.to_string_lossy().to_string()))
+            } else if metadata.is_dir() {
+                Some(EntryType::Directory)
+            } else {
+                None
+            };
+            if let Some(entry_type) = entry_type {
+                let metadata = file_metadata(&metadata, entry_type);
+                mla.add_file_with_metadata(filename, 0, io::empty(), metadata)?;
+                continue;
+            }
+        }
+        let file = File::open(Path::new(&filename))?;
+        let metadata = file.metadata()?;
+        if preserve_hardlinks {
+            if let Some(key) = hardlink_key(&metadata) {
+                if let Some(target) = hardlink_targets.get(&key) {
+                    mla.add_hardlink(filename, target)?;
+                    continue;
+                }
+                hardlink_targets.insert(key, filename.to_string());
+            }
+        }
+        content_size += metadata.len();
+        if preserve {
+            let size = metadata.len();
+            let metadata = file_metadata(&metadata, EntryType::File);
+            mla.add_file_with_metadata(filename, size, file, metadata)?;
+        } else {
+            mla.add_file(filename, metadata.len(), file)?;
+        }
+    }
+
+    mla.finalize()?;
+    finish_progress();
+    if let Some(digest) = mla.output_digest() {
+        // stdout may be the archive itself
+        eprintln!("SHA256: {}", hex::encode(digest));
+    }
+    if matches.is_present("stats") {
+        let size = |size: u64| size.file_size(file_size_opts::CONVENTIONAL).unwrap();
+        eprintln!("Entries: {}", entries);
+        eprintln!("Content: {}", size(content_size));
+        if let Some(stats) = mla.dedup_stats() {
+            let saved = if stats.content_size > 0 {
+                stats.saved_size() as f64 * 100.0 / stats.content_size as f64
+            } else {
+                0.0
+            };
+            eprintln!(
+                "Stored after deduplication: {} ({} saved, {:.1}%)",
+                size(stats.stored_size),
+                size(stats.saved_size()),
+                saved
+            );
+            eprintln!(
+                "Chunks: {} stored out of {}",
+                stats.stored_chunks, stats.chunks
+            );
+        }
+    }
+    mla.into_raw().finish()?;
+    Ok(())
+}
+
+fn append(matches: &ArgMatches) -> Result<(), Error> {
+    let config = readerconfig_from_matches(matches);
+    let files: Vec<&str> = match matches.values_of("files") {
+        Some(files) => files.collect(),
+        None => Vec::new(),
+    };
+    // Check the files before touching the archive, which stays unreadable
+    // until the new files are added
+    for filename in &files {
+        fs::metadata(filename).map_err(|err| {
+            let err = Error::from(err);
+            report_error(&format!("Unable to open \"{}\"", filename), &err);
+            err
+        })?;
+    }
+
+    // Options not recorded in the archive
+    let mut options = ArchiveWriterConfig::new();
+    if let Some(policy) = padding_from_matches(matches, &PaddingProfile::default()) {
+        options.with_padding_policy(policy);
+    }
+    if matches.is_present("digest") {
+        options.enable_output_digest();
+    }
+
+    // Safe to use unwrap() because the option is required()
+    let mla_file = matches.value_of("input").unwrap();
+    let file = fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(mla_file)?;
+    let mut mla = match ArchiveWriter::append_to_with_options(file, config, options) {
+        Ok(mla) => mla,
+        Err(Error::AppendUnsupportedCipher) => {
+            eprintln!(" [!] Only archives created with \"--cipher aes-gcm-siv\", or without encryption, can be appended to");
+            std::process::exit(1);
+        }
+        Err(Error::ConfigError(ConfigError::PaddingWithCompression)) => {
+            eprintln!(" [!] Padding cannot hide the sizes of compressed files: only archives without the 'compress' layer can be padded");
+            std::process::exit(1);
+        }
+        Err(err) => return Err(err),
     };
 
+    for filename in files {
+        eprintln!("{}", filename);
+        let file = File::open(Path::new(&filename))?;
+        let length = file.metadata()?.len();
+        match mla.add_file(filename, length, file) {
+            Err(Error::DuplicateFilename) => {
+                eprintln!(" [!] Skipping \"{}\", already in the archive", filename);
+            }
+            res => res?,
+        }
+    }
+
     mla.finalize()?;
+    if let Some(digest) = mla.output_digest() {
+        eprintln!("SHA256: {}", hex::encode(digest));
+    }
     Ok(())
 }
 
 fn list(matches: &ArgMatches) -> Result<(), Error> {
+    let name_filter = NameFilter::from_matches(matches);
     let mut mla = open_mla_file(matches)?;
 
-    let mut iter: Vec<String> = mla.list_files()?.cloned().collect();
+    if matches.is_present("json") {
+        // Everything is read from the footer
+        let mut infos: Vec<ArchiveFileInfo> = mla
+            .list_files_info()?
+            .filter(|info| name_filter.match_file_name(info.name))
+            .collect();
+        infos.sort_by_key(|info| info.name);
+        let entries: Vec<serde_json::Value> = infos
+            .iter()
+            .map(|info| {
+                json!({
+                    "name": info.name,
+                    "size": info.size,
+                    "offset": info.offset,
+                    "footprint": info.footprint,
+                    "sha256": info.hash.map(hex::encode),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::Value::from(entries));
+        return Ok(());
+    }
+
+    let mut iter: Vec<(String, u64)> = mla
+        .list_files_info()?
+        .filter(|info| name_filter.match_file_name(info.name))
+        .map(|info| (info.name.to_string(), info.size))
+        .collect();
     iter.sort();
-    for fname in iter {
-        if matches.is_present("verbose") {
-            let mla_file = mla.get_file(fname)?.expect("Unable to get the file");
-            let filename = mla_file.filename;
-            let size = mla_file
-                .size
-                .file_size(file_size_opts::CONVENTIONAL)
-                .unwrap();
+    for (fname, size) in iter {
+        if matches.is_present("hash") {
+            let hash = mla.get_hash(&fname)?.expect("Unable to get the hash");
+            println!("{}  {}", hex::encode(hash), fname);
+        } else if matches.is_present("verbose") {
+            let size = size.file_size(file_size_opts::CONVENTIONAL).unwrap();
             if matches.occurrences_of("verbose") == 1 {
-                println!("{} - {}", filename, size);
+                println!("{} - {}", fname, size);
             } else if matches.occurrences_of("verbose") >= 2 {
-                let hash = mla.get_hash(&filename)?.expect("Unable to get the hash");
-                println!("{} - {} ({})", filename, size, hex::encode(hash),);
+                let hash = mla.get_hash(&fname)?.expect("Unable to get the hash");
+                println!("{} - {} ({})", fname, size, hex::encode(hash),);
             }
         } else {
             println!("{}", fname);
@@ -441,90 +1831,275 @@ fn list(matches: &ArgMatches) -> Result<(), Error> {
     Ok(())
 }
 
+/// Parse a size, with an optional binary suffix (ex: "512", "10K", "3M", "1G")
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (number, multiplier) = match value.chars().last()?.to_ascii_uppercase() {
+        'K' => (&value[..value.len() - 1], 1u64 << 10),
+        'M' => (&value[..value.len() - 1], 1u64 << 20),
+        'G' => (&value[..value.len() - 1], 1u64 << 30),
+        'T' => (&value[..value.len() - 1], 1u64 << 40),
+        _ => (value, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// Days from 1970-01-01 to the given date, from
+/// http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+/// Parse a UTC date, "YYYY-MM-DD" or "YYYY-MM-DDTHH:MM:SS", to seconds since the
+/// Unix epoch
+fn parse_date(value: &str) -> Option<i64> {
+    let value = value.trim();
+    let (date, time) = match value.split_once('T') {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+    let date: Vec<i64> = date
+        .split('-')
+        .map(|part| part.parse().ok())
+        .collect::<Option<_>>()?;
+    let (year, month, day) = match date.as_slice() {
+        [year, month, day] if (1..=12).contains(month) && (1..=31).contains(day) => {
+            (*year, *month, *day)
+        }
+        _ => return None,
+    };
+    let seconds = match time {
+        None => 0,
+        Some(time) => match time
+            .split(':')
+            .map(|part| part.parse().ok())
+            .collect::<Option<Vec<i64>>>()?
+            .as_slice()
+        {
+            [hour, minute, second]
+                if (0..24).contains(hour)
+                    && (0..60).contains(minute)
+                    && (0..60).contains(second) =>
+            {
+                hour * 3600 + minute * 60 + second
+            }
+            _ => return None,
+        },
+    };
+    Some(days_from_civil(year, month, day) * 86_400 + seconds)
+}
+
+fn find(matches: &ArgMatches) -> Result<(), Error> {
+    let mut patterns = Vec::new();
+    for arg_pattern in matches.values_of("name").into_iter().flatten() {
+        match Pattern::new(arg_pattern) {
+            Ok(pat) => patterns.push(pat),
+            Err(err) => {
+                eprintln!(" [!] Invalid glob pattern {:?} ({})", arg_pattern, err);
+                std::process::exit(1);
+            }
+        }
+    }
+    let size_bound = |arg: &str| match matches.value_of(arg) {
+        None => None,
+        Some(value) => match parse_size(value) {
+            Some(size) => Some(size),
+            None => {
+                eprintln!(" [!] Invalid size {:?}", value);
+                std::process::exit(1);
+            }
+        },
+    };
+    let larger_than = size_bound("larger_than");
+    let smaller_than = size_bound("smaller_than");
+    let date_bound = |arg: &str| match matches.value_of(arg) {
+        None => None,
+        Some(value) => match parse_date(value) {
+            Some(date) => Some(date),
+            None => {
+                eprintln!(" [!] Invalid date {:?}, expected YYYY-MM-DD", value);
+                std::process::exit(1);
+            }
+        },
+    };
+    let newer_than = date_bound("newer_than");
+    let older_than = date_bound("older_than");
+
+    let mla = open_mla_file(matches)?;
+    // Sizes and modification times are read from the footer, without reading
+    // the files
+    let mut infos: Vec<(&str, u64)> = mla
+        .list_files_info()?
+        .map(|info| (info.name, info.size))
+        .collect();
+    infos.sort();
+    let mut found = Vec::new();
+    for (fname, size) in infos {
+        // A file matches if its name matches one of the patterns
+        if !patterns.is_empty() && !patterns.iter().any(|pat| pat.matches(fname)) {
+            continue;
+        }
+        if larger_than.is_some_and(|bound| size <= bound)
+            || smaller_than.is_some_and(|bound| size >= bound)
+        {
+            continue;
+        }
+        // Files without a recorded modification time are only kept without
+        // a date bound
+        if newer_than.is_some() || older_than.is_some() {
+            let mtime = match mla.get_metadata(fname)?.and_then(|metadata| metadata.mtime) {
+                Some(mtime) => mtime,
+                None => continue,
+            };
+            if newer_than.is_some_and(|bound| mtime <= bound)
+                || older_than.is_some_and(|bound| mtime >= bound)
+            {
+                continue;
+            }
+        }
+        found.push((fname, size));
+    }
+
+    if matches.is_present("json") {
+        let entries: Vec<_> = found
+            .iter()
+            .map(|(name, size)| json!({"name": name, "size": size}))
+            .collect();
+        println!("{}", serde_json::Value::from(entries));
+    } else {
+        for (fname, _) in found {
+            println!("{}", fname);
+        }
+    }
+    Ok(())
+}
+
 fn extract(matches: &ArgMatches) -> Result<(), Error> {
-    let file_name_matcher = ExtractFileNameMatcher::from_matches(&matches);
+    let file_name_matcher = ExtractFileNameMatcher::from_matches(matches);
+    let name_filter = NameFilter::from_matches(matches);
     let output_dir = Path::new(matches.value_of_os("outputdir").unwrap());
     let verbose = matches.is_present("verbose");
 
+    start_progress(matches, ProgressBasis::BytesRead, input_size(matches));
     let mut mla = open_mla_file(matches)?;
 
     // Create the output directory, if it does not exist
     if !output_dir.exists() {
-        fs::create_dir(&output_dir).map_err(|err| {
-            eprintln!(
-                " [!] Error while creating output directory \"{}\" ({:?})",
-                output_dir.display(),
-                err
+        fs::create_dir(output_dir).map_err(|err| {
+            let err = Error::from(err);
+            report_error(
+                &format!(
+                    "Error while creating output directory \"{}\"",
+                    output_dir.display()
+                ),
+                &err,
             );
             err
         })?;
     }
-    let output_dir = fs::canonicalize(&output_dir).map_err(|err| {
-        eprintln!(
-            " [!] Error while canonicalizing output directory path \"{}\" ({:?})",
-            output_dir.display(),
-            err
+    let output_dir = fs::canonicalize(output_dir).map_err(|err| {
+        let err = Error::from(err);
+        report_error(
+            &format!(
+                "Error while canonicalizing output directory path \"{}\"",
+                output_dir.display()
+            ),
+            &err,
         );
         err
     })?;
 
-    let mut iter: Vec<String> = mla.list_files()?.cloned().collect();
+    let mut iter: Vec<String> = mla
+        .list_files()?
+        .filter(|fname| name_filter.match_file_name(fname))
+        // Filter files according to glob patterns or files given as parameters
+        .filter(|fname| file_name_matcher.match_file_name(fname))
+        .cloned()
+        .collect();
     iter.sort();
 
-    if let ExtractFileNameMatcher::Anything = file_name_matcher {
-        // Optimisation: use linear extraction
-        if verbose {
-            println!("Extracting the whole archive using a linear extraction");
+    // Directories and symbolic links have no content: they are created last
+    let mut files = Vec::new();
+    let mut special_entries = Vec::new();
+    for fname in iter {
+        match mla.get_metadata(&fname)? {
+            None
+            | Some(FileMetadata {
+                entry_type: EntryType::File,
+                ..
+            }) => files.push(fname),
+            Some(metadata) => special_entries.push((fname, metadata.entry_type.clone())),
         }
-        let mut export: HashMap<&String, FileWriter> = HashMap::new();
-        for fname in &iter {
-            match create_file(&output_dir, fname)? {
-                Some((_file, path)) => {
-                    export.insert(fname, FileWriter { path });
-                }
-                None => continue,
-            }
-        }
-        return linear_extract(&mut mla, &mut export);
     }
 
-    for fname in iter {
-        // Filter files according to glob patterns or files given as parameters
-        if !file_name_matcher.match_file_name(&fname) {
-            continue;
-        }
-
-        // Look for the file in the archive
-        let mut sub_file = match mla.get_file(fname.clone()) {
+    // Entries are read in archive order and by blocks, so that the archive is
+    // read linearly and memory use does not depend on the size of the files.
+    // With filters, only the selected files are read
+    let selected: HashSet<&String> = files.iter().collect();
+    // Entries actually created, whose metadata may be restored
+    let mut created = Vec::new();
+    for entry in mla.entries()? {
+        let mut entry = match entry {
+            Ok(entry) => entry,
             Err(err) => {
-                eprintln!(
-                    " [!] Error while looking up subfile \"{}\" ({:?})",
-                    fname, err
-                );
-                continue;
-            }
-            Ok(None) => {
-                eprintln!(
-                    " [!] Subfile \"{}\" indexed in metadata could not be found",
-                    fname
-                );
+                report_error("Error while looking up subfile", &err);
                 continue;
             }
-            Ok(Some(subfile)) => subfile,
         };
-        let (mut extracted_file, _path) = match create_file(&output_dir, &fname)? {
+        if !selected.contains(&entry.name) {
+            continue;
+        }
+        let (mut extracted_file, path) = match create_file(&output_dir, &entry.name)? {
             Some(file) => file,
             None => continue,
         };
+        created.push((entry.name.clone(), path));
 
         if verbose {
-            println!("{}", fname);
+            println!("{}", entry.name);
         }
-        io::copy(&mut sub_file.data, &mut extracted_file).map_err(|err| {
-            eprintln!(" [!] Unable to extract \"{}\" ({:?})", fname, err);
+        io::copy(&mut entry, &mut extracted_file).map_err(|err| {
+            let err = Error::from(err);
+            report_error(&format!("Unable to extract \"{}\"", entry.name), &err);
             err
         })?;
     }
+    finish_progress();
+    create_special_entries(&output_dir, &special_entries, verbose, &mut created)?;
+
+    if matches.is_present("preserve") {
+        for (fname, path) in created {
+            let metadata = match mla.get_metadata(&fname)? {
+                Some(metadata) => metadata,
+                None => continue,
+            };
+            // Entries created later must not have redirected the path outside
+            // of the output directory
+            let inside = path
+                .parent()
+                .and_then(|parent| fs::canonicalize(parent).ok())
+                .is_some_and(|parent| parent.starts_with(&output_dir));
+            if !inside {
+                eprintln!(
+                    " [!] Skipping the metadata of \"{}\" because it is outside of the output directory",
+                    fname
+                );
+                continue;
+            }
+            if let Err(err) = restore_metadata(&path, metadata) {
+                report_error(
+                    &format!("Unable to restore the metadata of \"{}\"", fname),
+                    &err.into(),
+                );
+            }
+        }
+    }
     Ok(())
 }
 
@@ -534,6 +2109,8 @@ fn cat(matches: &ArgMatches) -> Result<(), Error> {
     let mut destination = destination_from_output_argument(output)?;
 
     let mut mla = open_mla_file(matches)?;
+    // Set if a requested file is not displayed, for a failing exit code
+    let mut missing = false;
     if matches.is_present("glob") {
         // For each glob patterns, enumerate matching files and display them
         let mut archive_files: Vec<String> = mla.list_files()?.cloned().collect();
@@ -542,17 +2119,21 @@ fn cat(matches: &ArgMatches) -> Result<(), Error> {
             let pat = match Pattern::new(arg_pattern) {
                 Ok(pat) => pat,
                 Err(err) => {
-                    eprintln!(" [!] Invalid glob pattern {:?} ({:?})", arg_pattern, err);
+                    eprintln!(" [!] Invalid glob pattern {:?} ({})", arg_pattern, err);
+                    missing = true;
                     continue;
                 }
             };
+            let mut found = false;
             for fname in archive_files.iter() {
                 if !pat.matches(fname) {
                     continue;
                 }
+                found = true;
                 match mla.get_file(fname.to_string()) {
                     Err(err) => {
-                        eprintln!(" [!] Error while looking up file \"{}\" ({:?})", fname, err);
+                        report_error(&format!("Error while looking up file \"{}\"", fname), &err);
+                        missing = true;
                         continue;
                     }
                     Ok(None) => {
@@ -560,38 +2141,51 @@ fn cat(matches: &ArgMatches) -> Result<(), Error> {
                             " [!] Subfile \"{}\" indexed in metadata could not be found",
                             fname
                         );
+                        missing = true;
                         continue;
                     }
                     Ok(Some(mut subfile)) => {
                         io::copy(&mut subfile.data, &mut destination).map_err(|err| {
-                            eprintln!(" [!] Unable to extract \"{}\" ({:?})", fname, err);
+                            let err = Error::from(err);
+                            report_error(&format!("Unable to extract \"{}\"", fname), &err);
                             err
                         })?;
                     }
                 }
             }
+            if !found {
+                eprintln!(" [!] No file matching \"{}\"", arg_pattern);
+                missing = true;
+            }
         }
     } else {
         // Retrieve all the files that are specified
         for fname in files_values {
             match mla.get_file(fname.to_string()) {
                 Err(err) => {
-                    eprintln!(" [!] Error while looking up file \"{}\" ({:?})", fname, err);
+                    report_error(&format!("Error while looking up file \"{}\"", fname), &err);
+                    missing = true;
                     continue;
                 }
                 Ok(None) => {
                     eprintln!(" [!] File not found: \"{}\"", fname);
+                    missing = true;
                     continue;
                 }
                 Ok(Some(mut subfile)) => {
                     io::copy(&mut subfile.data, &mut destination).map_err(|err| {
-                        eprintln!(" [!] Unable to extract \"{}\" ({:?})", fname, err);
+                        let err = Error::from(err);
+                        report_error(&format!("Unable to extract \"{}\"", fname), &err);
                         err
                     })?;
                 }
             }
         }
     }
+    destination.flush()?;
+    if missing {
+        std::process::exit(1);
+    }
     Ok(())
 }
 
@@ -600,42 +2194,49 @@ fn to_tar(matches: &ArgMatches) -> Result<(), Error> {
 
     // Safe to use unwrap() because the option is required()
     let output = matches.value_of("output").unwrap();
-    let path = Path::new(&output);
-    let mut tar_file = Builder::new(File::create(&path)?);
+    let mut tar_file = Builder::new(destination_from_output_argument(output)?);
+    mla_to_tar(&mut mla, &mut tar_file)?;
+    tar_file.finish()?;
+    Ok(())
+}
 
-    let iter = mla.list_files()?;
-    let fnames: Vec<String> = iter.cloned().collect();
-    for fname in fnames {
-        let sub_file = match mla.get_file(fname.clone()) {
-            Err(err) => {
-                eprintln!(
-                    " [!] Error while looking up subfile \"{}\" ({:?})",
-                    fname, err
-                );
-                continue;
-            }
-            Ok(None) => {
-                eprintln!(
-                    " [!] Subfile \"{}\" indexed in metadata could not be found",
-                    fname
-                );
-                continue;
-            }
-            Ok(Some(subfile)) => subfile,
-        };
-        if let Err(err) = add_file_to_tar(&mut tar_file, sub_file) {
-            eprintln!(" [!] Unable to add subfile \"{}\" ({:?})", fname, err);
-        }
+fn from_tar(matches: &ArgMatches) -> Result<(), Error> {
+    // Safe to use unwrap() because the option is required()
+    let input = matches.value_of("input").unwrap();
+    let source: Box<dyn Read> = if input == "-" {
+        Box::new(io::stdin())
+    } else {
+        Box::new(File::open(input)?)
+    };
+    let mut mla = writer_from_matches(matches)?;
+
+    let ignored = tar_to_mla(
+        &mut tar::Archive::new(source),
+        &mut mla,
+        matches.is_present("preserve"),
+    )?;
+    for name in ignored {
+        eprintln!(
+            "[WARNING] '{}' ignored: only files, hard links and, with '--preserve', directories and symbolic links are supported",
+            name
+        );
     }
+    mla.finalize()?;
     Ok(())
 }
 
 fn repair(matches: &ArgMatches) -> Result<(), Error> {
+    start_progress(matches, ProgressBasis::BytesRead, input_size(matches));
     let mut mla = open_failsafe_mla_file(matches)?;
     let mut mla_out = writer_from_matches(matches)?;
 
     // Convert
-    let status = mla.convert_to_archive(&mut mla_out)?;
+    let report = mla.convert_to_archive_with_report(&mut mla_out)?;
+    finish_progress();
+    for (fname, file_status) in &report.files {
+        println!("{}: {}", fname, file_status);
+    }
+    let status = report.status;
     match status {
         FailSafeReadError::NoError => {}
         FailSafeReadError::EndOfOriginalArchiveData => {
@@ -665,7 +2266,7 @@ fn convert(matches: &ArgMatches) -> Result<(), Error> {
         eprintln!("{}", fname);
         let sub_file = match mla.get_file(fname.clone()) {
             Err(err) => {
-                eprintln!("Error while adding {} ({:?})", fname, err);
+                eprintln!("Error while adding {} ({})", fname, err);
                 continue;
             }
             Ok(None) => {
@@ -681,6 +2282,266 @@ fn convert(matches: &ArgMatches) -> Result<(), Error> {
     Ok(())
 }
 
+/// Ed25519 public keys of the `name` option of rekey
+fn rekey_public_keys(
+    matches: &ArgMatches,
+    name: &str,
+) -> Result<Vec<x25519_dalek::PublicKey>, Error> {
+    let paths: Vec<PathBuf> = match matches.values_of_os(name) {
+        Some(values) => values.map(PathBuf::from).collect(),
+        None => Vec::new(),
+    };
+    if !open_hybrid_public_keys(&paths)?.is_empty() {
+        return Err(Error::BadAPIArgument(
+            "Hybrid recipients cannot be set by rekey".to_string(),
+        ));
+    }
+    open_ecc_public_keys(&paths)
+}
+
+fn rekey(matches: &ArgMatches) -> Result<(), Error> {
+    // Safe to use unwrap() because the options are required()
+    let path = Path::new(matches.value_of("input").unwrap());
+    let public_keys = if matches.is_present("add_public_keys")
+        || matches.is_present("remove_public_keys")
+    {
+        // Recipients are not identified in the archive, and their slots share
+        // a single ephemeral key: every slot is created again, so every
+        // current recipient must be given. Only their number can be checked
+        let current = RecipientSet::new(&rekey_public_keys(matches, "current_public_keys")?);
+        let count = recipient_slots(File::open(path)?, &[], &[])?
+            .iter()
+            .filter(|slot| slot.kind == RecipientKind::X25519)
+            .count();
+        if current.len() != count {
+            eprintln!(
+                " [!] The archive has {} recipient(s), which must all be given with --current-pub ({} given)",
+                count,
+                current.len()
+            );
+            std::process::exit(1);
+        }
+        let removed = RecipientSet::new(&rekey_public_keys(matches, "remove_public_keys")?);
+        let is_removed = |key: &x25519_dalek::PublicKey| {
+            removed
+                .keys()
+                .iter()
+                .any(|removed| removed.as_bytes() == key.as_bytes())
+        };
+        if current.keys().iter().filter(|key| is_removed(key)).count() != removed.len() {
+            eprintln!(" [!] The keys given with --remove-pub must be among --current-pub");
+            std::process::exit(1);
+        }
+        let kept: Vec<_> = current
+            .keys()
+            .iter()
+            .filter(|key| !is_removed(key))
+            .cloned()
+            .collect();
+        let mut recipients = RecipientSet::new(&kept);
+        recipients.extend(&rekey_public_keys(matches, "add_public_keys")?);
+        recipients.keys().to_vec()
+    } else {
+        rekey_public_keys(matches, "public_keys")?
+    };
+    let mut destination = destination_from_output_argument(matches.value_of("output").unwrap())?;
+
+    let mut csprng = ChaChaRng::from_entropy();
+    let config = readerconfig_from_matches(matches);
+    match mla::rekey(
+        File::open(path)?,
+        &mut destination,
+        config,
+        &public_keys,
+        &mut csprng,
+    ) {
+        // Without private key, the archive may still be opened with a password
+        Err(Error::PrivateKeyNeeded) if !matches.is_present("private_keys") => {
+            let mut config = readerconfig_from_matches(matches);
+            config.with_password(&archive_password(matches)?);
+            mla::rekey(
+                File::open(path)?,
+                &mut destination,
+                config,
+                &public_keys,
+                &mut csprng,
+            )
+        }
+        result => result,
+    }
+}
+
+/// Editor used by `edit` if `$EDITOR` is not set
+const DEFAULT_EDITOR: &str = "vi";
+
+fn edit(matches: &ArgMatches) -> Result<(), Error> {
+    // Safe to use unwrap() because the options are required()
+    let archive_path = Path::new(matches.value_of_os("input").unwrap());
+    let fname = matches.value_of("file").unwrap();
+    let mut mla = open_mla_file(matches)?;
+    // Fail early, before the user spends time editing, if the output
+    // configuration is incorrect (such as missing public keys)
+    let config = config_from_matches(matches);
+    config.check()?;
+
+    // Only the content of regular files can be edited
+    let metadata = mla.get_metadata(fname)?.cloned();
+    if let Some(FileMetadata {
+        entry_type: EntryType::Directory | EntryType::Symlink(_),
+        ..
+    }) = metadata
+    {
+        eprintln!(" [!] \"{}\" is not a regular file", fname);
+        std::process::exit(1);
+    }
+
+    // Extract the entry to a temporary file, only readable by the current
+    // user. Its name ends like the entry's, for editors detecting the type
+    let mut sub_file = match mla.get_file(fname.to_string())? {
+        Some(sub_file) => sub_file,
+        None => {
+            eprintln!(" [!] File \"{}\" not found in the archive", fname);
+            std::process::exit(1);
+        }
+    };
+    let basename = Path::new(fname)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut edited_file = tempfile::Builder::new()
+        .prefix("mlar-edit-")
+        .suffix(&format!("-{}", basename))
+        .tempfile()?;
+    io::copy(&mut sub_file.data, edited_file.as_file_mut())?;
+    drop(sub_file);
+    let hash_before = mla.get_hash(fname)?.ok_or(Error::MissingMetadata)?;
+
+    // Let the user modify it. `$EDITOR` is run by the shell, as it may
+    // contain arguments (such as "code --wait") or quotes
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| DEFAULT_EDITOR.to_string());
+    let status = editor_command(&editor, edited_file.path()).status()?;
+    if !status.success() {
+        eprintln!(" [!] Editor exited with {}, archive left untouched", status);
+        // Exiting skips destructors
+        drop(edited_file);
+        std::process::exit(1);
+    }
+
+    // Editors may replace the file instead of writing to it: open it again
+    let mut hash = Sha256::new();
+    io::copy(&mut File::open(edited_file.path())?, &mut hash)?;
+    if hash.finalize().as_slice() == &hash_before[..] {
+        eprintln!("No modification, archive left untouched");
+        return Ok(());
+    }
+
+    // Rebuild the archive next to the original one, replacing the entry, then
+    // move it over the original
+    let mut output_path = archive_path.as_os_str().to_owned();
+    output_path.push(".edit");
+    let output_path = PathBuf::from(output_path);
+    let mut mla_out =
+        ArchiveWriter::from_config(File::create(&output_path)?, config_from_matches(matches))?;
+
+    // Hard links share the offset of their content: the first name of each
+    // offset is added with the content, the others as links to it. If the
+    // edited entry is linked, all its names get the new content
+    let mut entries: Vec<(u64, String)> = mla
+        .list_files_info()?
+        .map(|info| (info.offset, info.name.to_string()))
+        .collect();
+    entries.sort();
+    let edited_offset = entries
+        .iter()
+        .find(|(_, other_fname)| other_fname == fname)
+        .map(|(offset, _)| *offset);
+    let mut previous: Option<(u64, String)> = None;
+    for (offset, other_fname) in entries {
+        if let Some((previous_offset, target)) = &previous {
+            if *previous_offset == offset {
+                mla_out.add_hardlink(&other_fname, target)?;
+                continue;
+            }
+        }
+        let metadata = mla.get_metadata(&other_fname)?.cloned();
+        if Some(offset) == edited_offset {
+            let edited = File::open(edited_file.path())?;
+            let fs_metadata = edited.metadata()?;
+            // The modification time is the one of the edition
+            let metadata = metadata.map(|metadata| FileMetadata {
+                mtime: file_metadata(&fs_metadata, EntryType::File).mtime,
+                ..metadata
+            });
+            add_entry(
+                &mut mla_out,
+                &other_fname,
+                fs_metadata.len(),
+                edited,
+                metadata,
+            )?;
+        } else {
+            let sub_file = match mla.get_file(other_fname.clone())? {
+                Some(sub_file) => sub_file,
+                None => {
+                    eprintln!(
+                        " [!] Subfile \"{}\" indexed in metadata could not be found",
+                        other_fname
+                    );
+                    continue;
+                }
+            };
+            add_entry(
+                &mut mla_out,
+                &other_fname,
+                sub_file.size,
+                sub_file.data,
+                metadata,
+            )?;
+        }
+        previous = Some((offset, other_fname));
+    }
+    mla_out.finalize()?;
+    drop(mla_out);
+
+    fs::rename(&output_path, archive_path)?;
+    Ok(())
+}
+
+/// Add an entry to `mla`, with its metadata if any
+fn add_entry<W: Write, R: Read>(
+    mla: &mut ArchiveWriter<W>,
+    fname: &str,
+    size: u64,
+    src: R,
+    metadata: Option<FileMetadata>,
+) -> Result<(), Error> {
+    match metadata {
+        Some(metadata) => mla.add_file_with_metadata(fname, size, src, metadata),
+        None => mla.add_file(fname, size, src),
+    }
+}
+
+/// Command running `editor` on `path`, through the shell
+#[cfg(unix)]
+fn editor_command(editor: &str, path: &Path) -> std::process::Command {
+    let mut command = std::process::Command::new("sh");
+    // The path is given as a positional parameter, to avoid quoting it
+    command
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(path);
+    command
+}
+
+/// Command running `editor` on `path`, through the shell
+#[cfg(not(unix))]
+fn editor_command(editor: &str, path: &Path) -> std::process::Command {
+    let mut command = std::process::Command::new("cmd");
+    command.arg("/C").arg(editor).arg(path);
+    command
+}
+
 fn keygen(matches: &ArgMatches) -> Result<(), Error> {
     // Safe to use unwrap() because of the requirement
     let output_base = matches.value_of_os("output").unwrap();
@@ -689,14 +2550,72 @@ fn keygen(matches: &ArgMatches) -> Result<(), Error> {
         .expect("Unable to create the public file");
     let mut output_priv = File::create(output_base).expect("Unable to create the private file");
 
+    // Tools reading PEM skip the text before it, such as this comment
+    if let Some(comment) = matches.value_of("comment") {
+        writeln!(output_pub, "Comment: {}", comment).expect("Error writing the public key");
+    }
+
     let mut csprng = ChaChaRng::from_entropy();
-    let key_pair = generate_keypair(&mut csprng).expect("Error while generating the key-pair");
+    if matches.value_of("type") == Some("hybrid") {
+        if matches.is_present("seed") || matches.is_present("mnemonic") {
+            eprintln!(" [!] Only 'ed25519' keys can be derived from a seed or a mnemonic");
+            std::process::exit(1);
+        }
+        // No standard format exists yet: both keys are raw bytes in a PEM
+        let private_key = HybridPrivateKey::generate(&mut csprng);
+        let mut private_pem = pem::encode(&pem::Pem {
+            tag: HYBRID_PRIVATE_KEY_TAG.to_string(),
+            contents: private_key.to_bytes(),
+        });
+        let public_pem = pem::encode(&pem::Pem {
+            tag: HYBRID_PUBLIC_KEY_TAG.to_string(),
+            contents: private_key.public_key().to_bytes(),
+        });
+        output_pub
+            .write_all(public_pem.as_bytes())
+            .expect("Error writing the public key");
+        output_priv
+            .write_all(private_pem.as_bytes())
+            .expect("Error writing the private key");
+        private_pem.zeroize();
+        println!("{}", private_key.public_key().fingerprint());
+        return Ok(());
+    }
+    let key_pair = if let Some(path) = matches.value_of_os("mnemonic") {
+        let mut mnemonic = fs::read_to_string(path)?;
+        if let Err(err) = check_mnemonic(&mnemonic) {
+            mnemonic.zeroize();
+            eprintln!(" [!] {}", err);
+            std::process::exit(1);
+        }
+        let mut seed = mnemonic_to_seed(&mnemonic, "");
+        mnemonic.zeroize();
+        let key_pair = generate_keypair_from_seed(&seed);
+        seed.zeroize();
+        key_pair.expect("Error while deriving the key-pair")
+    } else if let Some(path) = matches.value_of_os("seed") {
+        let mut seed = fs::read(path)?;
+        let key_pair = generate_keypair_from_seed(&seed);
+        seed.zeroize();
+        match key_pair {
+            Some(key_pair) => key_pair,
+            None => {
+                eprintln!(" [!] The seed must be at least 16 bytes long");
+                std::process::exit(1);
+            }
+        }
+    } else {
+        generate_keypair(&mut csprng).expect("Error while generating the key-pair")
+    };
 
     // Output the public key in PEM format, to ease integration in text based
     // configs
     output_pub
-        .write_all(&key_pair.public_as_pem().as_bytes())
+        .write_all(key_pair.public_as_pem().as_bytes())
         .expect("Error writing the public key");
+    let public_key = parse_openssl_ed25519_pubkey(&key_pair.public_der)
+        .expect("Error while parsing the generated public key");
+    println!("{}", public_key.fingerprint());
 
     // Output the private key in DER format, to avoid common mistakes
     output_priv
@@ -705,11 +2624,1650 @@ fn keygen(matches: &ArgMatches) -> Result<(), Error> {
     Ok(())
 }
 
+/// Default maximum size of trained dictionaries, as the `zstd` tool
+const DEFAULT_DICTIONARY_SIZE: u64 = 110 * 1024;
+/// Default maximum number of files sampled to train a dictionary, and of bytes
+/// read from each of them
+const DEFAULT_DICTIONARY_SAMPLES: usize = 10_000;
+const DEFAULT_DICTIONARY_SAMPLE_SIZE: u64 = 128 * 1024;
+
+fn train_dict(matches: &ArgMatches) -> Result<(), Error> {
+    let size_arg = |value: &str| match parse_size(value) {
+        Some(size) => size,
+        None => {
+            eprintln!(" [!] Invalid size {:?}", value);
+            std::process::exit(1);
+        }
+    };
+    let max_size = matches
+        .value_of("max_size")
+        .map_or(DEFAULT_DICTIONARY_SIZE, size_arg);
+    let sample_size = matches
+        .value_of("sample_size")
+        .map_or(DEFAULT_DICTIONARY_SAMPLE_SIZE, size_arg);
+    let max_samples = match matches.value_of("samples") {
+        Some(samples) => samples
+            .parse::<usize>()
+            .ok()
+            .filter(|samples| *samples > 0)
+            .expect("samples must be a positive int"),
+        None => DEFAULT_DICTIONARY_SAMPLES,
+    };
+
+    // Safe to use unwrap() because of the requirement
+    let mut files = Vec::new();
+    for path in matches.values_of_os("files").unwrap() {
+        let path = Path::new(path);
+        if path.is_dir() {
+            list_directory_files(path, &mut files)?;
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+    files.sort();
+
+    // Samples are evenly spread over the files, for a reproducible result
+    let step = std::cmp::max(files.len().div_ceil(max_samples), 1);
+    let mut samples = Vec::new();
+    for path in files.iter().step_by(step) {
+        let mut sample = Vec::new();
+        File::open(path)?
+            .take(sample_size)
+            .read_to_end(&mut sample)?;
+        if !sample.is_empty() {
+            samples.push(sample);
+        }
+    }
+
+    let dictionary = match train_compression_dictionary(&samples, max_size as usize) {
+        Ok(dictionary) => dictionary,
+        Err(err) => {
+            report_error(
+                &format!(
+                    "Unable to train a dictionary on {} samples, more may be needed",
+                    samples.len()
+                ),
+                &err,
+            );
+            std::process::exit(1);
+        }
+    };
+    // Safe to use unwrap() because of the requirement
+    fs::write(matches.value_of_os("output").unwrap(), &dictionary)?;
+    eprintln!(
+        "Dictionary of {} bytes, trained on {} samples",
+        dictionary.len(),
+        samples.len()
+    );
+    Ok(())
+}
+
+/// Recursively list the files (anything but directories) under `directory`,
+/// sorted by path whatever the order of the filesystem
+///
+/// Symbolic links are not followed
+fn list_directory_files(directory: &Path, files: &mut Vec<PathBuf>) -> Result<(), Error> {
+    let mut entries = fs::read_dir(directory)?.collect::<Result<Vec<_>, _>>()?;
+    entries.sort_by_key(|entry| entry.file_name());
+    for entry in entries {
+        if entry.file_type()?.is_dir() {
+            list_directory_files(&entry.path(), files)?;
+        } else {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+fn verify(matches: &ArgMatches) -> Result<(), Error> {
+    // The signature, if asked, is checked while opening the archive
+    let mut mla = match open_mla_file(matches) {
+        Err(Error::SignatureVerificationFailed) => {
+            eprintln!(" [!] The archive is not signed by any of the given public keys");
+            std::process::exit(1);
+        }
+        result => result?,
+    };
+    if matches.is_present("verification_keys") {
+        eprintln!("Signature verified");
+    }
+    // Without anything else to verify, check the integrity of the archive
+    if matches.is_present("integrity")
+        || !(matches.is_present("directory") || matches.is_present("verification_keys"))
+    {
+        let report = mla.check_integrity()?;
+        for (fname, status) in &report.files {
+            println!("{}: {}", fname, status);
+        }
+        let corrupted = report
+            .files
+            .iter()
+            .filter(|(_, status)| *status != FileIntegrityStatus::Valid)
+            .count();
+        if corrupted != 0 {
+            eprintln!(
+                "[!] {} corrupted file(s) out of {}",
+                corrupted,
+                report.files.len()
+            );
+            std::process::exit(1);
+        }
+        eprintln!("Integrity verified");
+    }
+    let directory = match matches.value_of_os("directory") {
+        Some(directory) => Path::new(directory),
+        None => return Ok(()),
+    };
+    let mut differences = 0;
+
+    // Archive side: each file must be present in the directory, with the same
+    // size and content. Paths are computed as in `extract`
+    let mut archive_files: Vec<String> = mla.list_files()?.cloned().collect();
+    archive_files.sort();
+    let mut expected_paths = HashSet::new();
+    for fname in archive_files {
+        let path = match get_extracted_path(directory, &fname) {
+            Some(path) => path,
+            None => {
+                differences += 1;
+                continue;
+            }
+        };
+        expected_paths.insert(path.clone());
+        if !path.is_file() {
+            println!("[-] Missing from directory: {}", fname);
+            differences += 1;
+            continue;
+        }
+
+        let size = match mla.get_file(fname.clone())? {
+            Some(subfile) => subfile.size,
+            None => {
+                eprintln!(
+                    " [!] Subfile \"{}\" indexed in metadata could not be found",
+                    fname
+                );
+                differences += 1;
+                continue;
+            }
+        };
+        let mut file = File::open(&path)?;
+        if file.metadata()?.len() != size {
+            println!("[!] Size mismatch: {}", fname);
+            differences += 1;
+            continue;
+        }
+        let mut hash = Sha256::new();
+        io::copy(&mut file, &mut hash)?;
+        let expected = mla.get_hash(&fname)?.expect("Unable to get the hash");
+        if hash.finalize().as_slice() != &expected[..] {
+            println!("[!] Content mismatch: {}", fname);
+            differences += 1;
+        }
+    }
+
+    // Directory side: every file must come from the archive
+    let mut directory_files = Vec::new();
+    list_directory_files(directory, &mut directory_files)?;
+    for path in directory_files {
+        if !expected_paths.contains(&path) {
+            println!("[+] Missing from archive: {}", path.display());
+            differences += 1;
+        }
+    }
+
+    if differences != 0 {
+        eprintln!("[!] {} difference(s) found", differences);
+        std::process::exit(1);
+    }
+    eprintln!("Archive and directory match");
+    Ok(())
+}
+
+fn diff(matches: &ArgMatches) -> Result<(), Error> {
+    let mut old = open_mla_file(matches)?;
+    // Safe to use unwrap() because the option is required()
+    let mut new = open_mla_path(matches, matches.value_of("other").unwrap())?;
+    let changes = mla_diff(&mut old, &mut new, matches.is_present("content"))?;
+
+    if matches.is_present("json") {
+        let entries: Vec<serde_json::Value> = changes
+            .iter()
+            .map(|(fname, change)| json!({"name": fname, "change": change.to_string()}))
+            .collect();
+        println!("{}", serde_json::Value::from(entries));
+    } else {
+        for (fname, change) in &changes {
+            match change {
+                EntryChange::Added => println!("[+] Added: {}", fname),
+                EntryChange::Removed => println!("[-] Removed: {}", fname),
+                EntryChange::Modified => println!("[!] Modified: {}", fname),
+            }
+        }
+    }
+
+    if !changes.is_empty() {
+        eprintln!("[!] {} difference(s) found", changes.len());
+        std::process::exit(1);
+    }
+    eprintln!("Archives match");
+    Ok(())
+}
+
+/// Decode the %XX sequences of an URL path. Return None on invalid sequences
+/// or non UTF-8 results
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Parse a `Range` header value (single range only), for a content of `size`
+/// bytes. Return the range as [start, end)
+fn parse_http_range(value: &str, size: u64) -> Option<(u64, u64)> {
+    let range = value.trim().strip_prefix("bytes=")?;
+    let mut parts = range.splitn(2, '-');
+    let start = parts.next()?.trim();
+    let end = parts.next()?.trim();
+    let (start, end) = if start.is_empty() {
+        // Suffix: last `end` bytes
+        let suffix: u64 = end.parse().ok()?;
+        (size.saturating_sub(suffix), size)
+    } else {
+        let start: u64 = start.parse().ok()?;
+        let end = if end.is_empty() {
+            size
+        } else {
+            // HTTP ranges are inclusive
+            std::cmp::min(end.parse::<u64>().ok()?.checked_add(1)?, size)
+        };
+        (start, end)
+    };
+    if start >= end {
+        return None;
+    }
+    Some((start, end))
+}
+
+fn http_header(field: &str, value: &str) -> tiny_http::Header {
+    tiny_http::Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("Invalid HTTP header")
+}
+
+fn http_error(request: tiny_http::Request, code: u16, message: &str) -> io::Result<()> {
+    request.respond(
+        tiny_http::Response::from_string(json!({ "error": message }).to_string())
+            .with_status_code(tiny_http::StatusCode(code))
+            .with_header(http_header("Content-Type", "application/json")),
+    )
+}
+
+/// Report the archive error `error`, with its code (see `--error-format`)
+fn http_archive_error(request: tiny_http::Request, error: &Error) -> io::Result<()> {
+    request.respond(
+        tiny_http::Response::from_string(
+            json!({ "error": error.to_string(), "code": error.code() }).to_string(),
+        )
+        .with_status_code(tiny_http::StatusCode(500))
+        .with_header(http_header("Content-Type", "application/json")),
+    )
+}
+
+/// Answer a request for the archive `mla`
+///
+/// API:
+/// - `GET /files`: list of files sorted by name, as JSON
+///   `[{"name": ..., "size": ...}]`, read from the footer
+/// - `GET /files/<name>`: content of the file, with `Range` support
+///
+/// Archive errors are answered with a 500, see `http_archive_error`
+fn serve_request(
+    mla: &mut ArchiveReader<InputTypes>,
+    request: tiny_http::Request,
+) -> io::Result<()> {
+    if *request.method() != tiny_http::Method::Get {
+        return http_error(request, 405, "Only GET is supported");
+    }
+    let url = request.url().to_string();
+    let path = url.split('?').next().unwrap_or("");
+
+    if path == "/files" || path == "/files/" {
+        // Sizes are read from the footer, without opening the files
+        let mut infos: Vec<ArchiveFileInfo> = match mla.list_files_info() {
+            Ok(iter) => iter.collect(),
+            Err(err) => return http_archive_error(request, &err),
+        };
+        infos.sort_by_key(|info| info.name);
+        let files = infos
+            .iter()
+            .map(|info| json!({ "name": info.name, "size": info.size }))
+            .collect();
+        return request.respond(
+            tiny_http::Response::from_string(serde_json::Value::Array(files).to_string())
+                .with_header(http_header("Content-Type", "application/json")),
+        );
+    }
+
+    let fname = match path.strip_prefix("/files/").and_then(percent_decode) {
+        Some(fname) => fname,
+        None => return http_error(request, 404, "Not found"),
+    };
+    let range = request
+        .headers()
+        .iter()
+        .find(|header| header.field.equiv("Range"))
+        .map(|header| header.value.as_str().to_string());
+    let mut subfile = match mla.get_file_seek(fname) {
+        Ok(Some(subfile)) => subfile,
+        Ok(None) => return http_error(request, 404, "Not found"),
+        Err(err) => return http_archive_error(request, &err),
+    };
+    let size = subfile.size;
+    let mut headers = vec![
+        http_header("Content-Type", "application/octet-stream"),
+        http_header("Accept-Ranges", "bytes"),
+    ];
+    let (status, start, end) = match range {
+        None => (200, 0, size),
+        Some(range) => match parse_http_range(&range, size) {
+            Some((start, end)) => {
+                headers.push(http_header(
+                    "Content-Range",
+                    &format!("bytes {}-{}/{}", start, end - 1, size),
+                ));
+                (206, start, end)
+            }
+            None => {
+                return request.respond(
+                    tiny_http::Response::empty(tiny_http::StatusCode(416))
+                        .with_header(http_header("Content-Range", &format!("bytes */{}", size))),
+                );
+            }
+        },
+    };
+
+    subfile.data.seek(SeekFrom::Start(start))?;
+    request.respond(tiny_http::Response::new(
+        tiny_http::StatusCode(status),
+        headers,
+        subfile.data.take(end - start),
+        Some((end - start) as usize),
+        None,
+    ))
+}
+
+fn serve(matches: &ArgMatches) -> Result<(), Error> {
+    let mut mla = open_mla_file(matches)?;
+    // Safe to use unwrap() because of the default value
+    let listen = matches.value_of("listen").unwrap();
+    let server =
+        tiny_http::Server::http(listen).map_err(|err| io::Error::other(err.to_string()))?;
+    eprintln!("Listening on http://{}/files", listen);
+
+    // Requests are handled one at a time, as the reader is shared
+    for request in server.incoming_requests() {
+        eprintln!("{} {}", request.method(), request.url());
+        if let Err(err) = serve_request(&mut mla, request) {
+            report_error("Error while answering", &err.into());
+        }
+    }
+    Ok(())
+}
+
+/// Inode of the root directory, as expected by FUSE
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+const FUSE_ROOT_INODE: u64 = 1;
+
+/// The archive never changes while mounted: attributes can be cached
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+const FUSE_TTL: Duration = Duration::from_secs(60);
+
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+enum MountNodeKind {
+    /// Name -> inode of the children
+    Directory(std::collections::BTreeMap<String, u64>),
+    /// File of the archive, with its name and size
+    File(String, u64),
+    Symlink(String),
+}
+
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+struct MountNode {
+    parent: u64,
+    kind: MountNodeKind,
+    metadata: Option<FileMetadata>,
+}
+
+/// Read-only filesystem showing the files of an archive as a tree, built from
+/// their names. File content is only read, decrypted and decompressed on
+/// demand, thanks to the index of content blocks
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+struct MlaFilesystem<'a> {
+    mla: ArchiveReader<'a, InputTypes>,
+    /// Nodes, the inode of `nodes[i]` being `i + 1`
+    nodes: Vec<MountNode>,
+    /// Owner of files without recorded owner: the one of the archive
+    uid: u32,
+    gid: u32,
+}
+
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+impl<'a> MlaFilesystem<'a> {
+    fn new(mut mla: ArchiveReader<'a, InputTypes>, uid: u32, gid: u32) -> Result<Self, Error> {
+        let mut nodes = vec![MountNode {
+            parent: FUSE_ROOT_INODE,
+            kind: MountNodeKind::Directory(Default::default()),
+            metadata: None,
+        }];
+        let mut fnames: Vec<String> = mla.list_files()?.cloned().collect();
+        fnames.sort();
+        'files: for fname in fnames {
+            // Name components are handled as on extraction
+            let components: Vec<&str> = fname
+                .split('/')
+                .filter(|part| !part.is_empty() && *part != ".")
+                .collect();
+            let (name, parents) = match components.split_last() {
+                Some((name, parents)) if !components.contains(&"..") => (*name, parents),
+                _ => {
+                    eprintln!(" [!] Skipping file \"{}\" because of its name", fname);
+                    continue;
+                }
+            };
+            let metadata = mla.get_metadata(&fname)?.cloned();
+            let kind = match metadata.as_ref().map(|metadata| &metadata.entry_type) {
+                Some(EntryType::Directory) => MountNodeKind::Directory(Default::default()),
+                Some(EntryType::Symlink(target)) => MountNodeKind::Symlink(target.clone()),
+                _ => {
+                    // No data is read for format v3 and later archives
+                    let size = match mla.get_file_seek(fname.clone())? {
+                        Some(file) => file.size,
+                        None => continue,
+                    };
+                    MountNodeKind::File(fname.clone(), size)
+                }
+            };
+
+            let mut parent = FUSE_ROOT_INODE;
+            for part in parents {
+                parent = match Self::directory_child(&mut nodes, parent, part) {
+                    Some(ino) => ino,
+                    None => {
+                        eprintln!(
+                            " [!] Skipping file \"{}\": a parent is not a directory",
+                            fname
+                        );
+                        continue 'files;
+                    }
+                };
+            }
+            let existing = match &nodes[(parent - 1) as usize].kind {
+                MountNodeKind::Directory(children) => children.get(name).copied(),
+                _ => None,
+            };
+            match (existing, &kind) {
+                (None, _) => {
+                    let ino = nodes.len() as u64 + 1;
+                    if let MountNodeKind::Directory(children) =
+                        &mut nodes[(parent - 1) as usize].kind
+                    {
+                        children.insert(name.to_string(), ino);
+                    }
+                    nodes.push(MountNode {
+                        parent,
+                        kind,
+                        metadata,
+                    });
+                }
+                // A directory already created for the files inside it
+                (Some(ino), MountNodeKind::Directory(_)) => {
+                    let node = &mut nodes[(ino - 1) as usize];
+                    if let MountNodeKind::Directory(_) = node.kind {
+                        node.metadata = metadata;
+                    }
+                }
+                (Some(_), _) => {
+                    eprintln!(" [!] Skipping file \"{}\": the name is already used", fname);
+                }
+            }
+        }
+        Ok(MlaFilesystem {
+            mla,
+            nodes,
+            uid,
+            gid,
+        })
+    }
+
+    /// Return the inode of the directory `name` in `parent`, created if needed,
+    /// or `None` if this name is not a directory
+    fn directory_child(nodes: &mut Vec<MountNode>, parent: u64, name: &str) -> Option<u64> {
+        let existing = match &nodes[(parent - 1) as usize].kind {
+            MountNodeKind::Directory(children) => children.get(name).copied(),
+            _ => return None,
+        };
+        match existing {
+            Some(ino) => match nodes[(ino - 1) as usize].kind {
+                MountNodeKind::Directory(_) => Some(ino),
+                _ => None,
+            },
+            None => {
+                let ino = nodes.len() as u64 + 1;
+                if let MountNodeKind::Directory(children) = &mut nodes[(parent - 1) as usize].kind {
+                    children.insert(name.to_string(), ino);
+                }
+                nodes.push(MountNode {
+                    parent,
+                    kind: MountNodeKind::Directory(Default::default()),
+                    metadata: None,
+                });
+                Some(ino)
+            }
+        }
+    }
+
+    fn node(&self, ino: u64) -> Option<&MountNode> {
+        ino.checked_sub(1)
+            .and_then(|index| self.nodes.get(index as usize))
+    }
+
+    fn file_type(&self, ino: u64) -> fuser::FileType {
+        match self.node(ino).map(|node| &node.kind) {
+            Some(MountNodeKind::File(..)) => fuser::FileType::RegularFile,
+            Some(MountNodeKind::Symlink(_)) => fuser::FileType::Symlink,
+            _ => fuser::FileType::Directory,
+        }
+    }
+
+    /// Attributes of an existing `ino`, from the recorded metadata if any
+    fn attr(&self, ino: u64) -> fuser::FileAttr {
+        let node = &self.nodes[(ino - 1) as usize];
+        let (size, nlink, default_perm) = match &node.kind {
+            MountNodeKind::Directory(_) => (0, 2, 0o555),
+            MountNodeKind::File(_, size) => (*size, 1, 0o444),
+            MountNodeKind::Symlink(target) => (target.len() as u64, 1, 0o777),
+        };
+        let metadata = node.metadata.as_ref();
+        let mtime = metadata
+            .and_then(|metadata| metadata.mtime)
+            .map(metadata_time)
+            .unwrap_or(UNIX_EPOCH);
+        fuser::FileAttr {
+            ino,
+            size,
+            blocks: size.div_ceil(512),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime: mtime,
+            kind: self.file_type(ino),
+            perm: metadata
+                .and_then(|metadata| metadata.mode)
+                .unwrap_or(default_perm) as u16
+                & 0o7777,
+            nlink,
+            uid: metadata
+                .and_then(|metadata| metadata.uid)
+                .unwrap_or(self.uid),
+            gid: metadata
+                .and_then(|metadata| metadata.gid)
+                .unwrap_or(self.gid),
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+}
+
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+impl<'a> fuser::Filesystem for MlaFilesystem<'a> {
+    fn lookup(
+        &mut self,
+        _req: &fuser::Request,
+        parent: u64,
+        name: &OsStr,
+        reply: fuser::ReplyEntry,
+    ) {
+        let child = match (self.node(parent).map(|node| &node.kind), name.to_str()) {
+            (Some(MountNodeKind::Directory(children)), Some(name)) => children.get(name).copied(),
+            _ => None,
+        };
+        match child {
+            Some(ino) => reply.entry(&FUSE_TTL, &self.attr(ino), 0),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn getattr(&mut self, _req: &fuser::Request, ino: u64, reply: fuser::ReplyAttr) {
+        match self.node(ino) {
+            Some(_) => reply.attr(&FUSE_TTL, &self.attr(ino)),
+            None => reply.error(libc::ENOENT),
+        }
+    }
+
+    fn readlink(&mut self, _req: &fuser::Request, ino: u64, reply: fuser::ReplyData) {
+        match self.node(ino).map(|node| &node.kind) {
+            Some(MountNodeKind::Symlink(target)) => reply.data(target.as_bytes()),
+            _ => reply.error(libc::EINVAL),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &fuser::Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        size: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: fuser::ReplyData,
+    ) {
+        let (fname, file_size) = match self.node(ino).map(|node| &node.kind) {
+            Some(MountNodeKind::File(fname, file_size)) => (fname.clone(), *file_size),
+            Some(_) => return reply.error(libc::EISDIR),
+            None => return reply.error(libc::ENOENT),
+        };
+        let offset = match u64::try_from(offset) {
+            Ok(offset) if offset < file_size => offset,
+            Ok(_) => return reply.data(&[]),
+            Err(_) => return reply.error(libc::EINVAL),
+        };
+        let mut buf = vec![0; std::cmp::min(size as u64, file_size - offset) as usize];
+        match self.mla.read_at(&fname, offset, &mut buf) {
+            Ok(Some(count)) => reply.data(&buf[..count]),
+            Ok(None) => reply.error(libc::ENOENT),
+            Err(err) => {
+                report_error(&format!("Error while reading \"{}\"", fname), &err);
+                reply.error(libc::EIO)
+            }
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &fuser::Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: fuser::ReplyDirectory,
+    ) {
+        let node = match self.node(ino) {
+            Some(node) => node,
+            None => return reply.error(libc::ENOENT),
+        };
+        let children = match &node.kind {
+            MountNodeKind::Directory(children) => children,
+            _ => return reply.error(libc::ENOTDIR),
+        };
+        let entries = [
+            (ino, fuser::FileType::Directory, "."),
+            (node.parent, fuser::FileType::Directory, ".."),
+        ]
+        .iter()
+        .cloned()
+        .chain(
+            children
+                .iter()
+                .map(|(name, child)| (*child, self.file_type(*child), name.as_str())),
+        );
+        for (index, (ino, kind, name)) in entries.enumerate().skip(offset as usize) {
+            // The given offset is the one of the next entry
+            if reply.add(ino, index as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+}
+
+#[cfg(all(feature = "fuse", target_os = "linux"))]
+fn mount(matches: &ArgMatches) -> Result<(), Error> {
+    use std::os::unix::fs::MetadataExt;
+
+    let mla = open_mla_file(matches)?;
+    // Safe to use unwrap() because the options are required()
+    let archive_metadata = fs::metadata(matches.value_of_os("input").unwrap())?;
+    let mountpoint = Path::new(matches.value_of_os("mountpoint").unwrap());
+    let filesystem = MlaFilesystem::new(mla, archive_metadata.uid(), archive_metadata.gid())?;
+
+    eprintln!(
+        "Mounted on {}, unmount with 'fusermount -u {}'",
+        mountpoint.display(),
+        mountpoint.display()
+    );
+    // Requests are handled one at a time, as the reader is shared
+    fuser::mount2(
+        filesystem,
+        mountpoint,
+        &[
+            fuser::MountOption::RO,
+            fuser::MountOption::FSName("mla".to_string()),
+            fuser::MountOption::DefaultPermissions,
+        ],
+    )?;
+    Ok(())
+}
+
+#[cfg(not(all(feature = "fuse", target_os = "linux")))]
+fn mount(_matches: &ArgMatches) -> Result<(), Error> {
+    eprintln!(
+        "[ERROR] 'mount' is only available on Linux, with mlar built with the 'fuse' feature"
+    );
+    std::process::exit(1);
+}
+
+fn sqlite_error(err: rusqlite::Error) -> Error {
+    Error::IOError(io::Error::other(err.to_string()))
+}
+
+/// Columns of the `files` table added after its creation, for metadata
+const INDEX_METADATA_COLUMNS: &[(&str, &str)] = &[
+    ("entry_type", "TEXT"),
+    ("link_target", "TEXT"),
+    ("mode", "INTEGER"),
+    ("uid", "INTEGER"),
+    ("gid", "INTEGER"),
+    ("mtime", "INTEGER"),
+];
+
+/// Write the list of files of the archive (name, size, SHA256, and metadata if
+/// recorded) in an SQLite database
+///
+/// A database can catalog several archives, identified by their path.
+/// Indexing an archive again replaces its previous entries. Metadata columns
+/// are NULL for files without metadata
+fn index(matches: &ArgMatches) -> Result<(), Error> {
+    let mut mla = open_mla_file(matches)?;
+    // Safe to use unwrap() because the options are required()
+    let archive_path = fs::canonicalize(matches.value_of_os("input").unwrap())?;
+    let archive_path = archive_path.to_string_lossy();
+    let output = matches.value_of("output").unwrap();
+
+    let mut db = rusqlite::Connection::open(output).map_err(sqlite_error)?;
+    let tx = db.transaction().map_err(sqlite_error)?;
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS archives (
+            id INTEGER PRIMARY KEY,
+            path TEXT NOT NULL UNIQUE
+        );
+        CREATE TABLE IF NOT EXISTS files (
+            archive_id INTEGER NOT NULL REFERENCES archives(id),
+            name TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            sha256 TEXT,
+            PRIMARY KEY (archive_id, name)
+        );",
+    )
+    .map_err(sqlite_error)?;
+    // Databases created by previous versions lack the metadata columns
+    let columns = tx
+        .prepare("SELECT name FROM pragma_table_info('files')")
+        .and_then(|mut stmt| {
+            stmt.query_map([], |row| row.get::<_, String>(0))?
+                .collect::<Result<HashSet<String>, _>>()
+        })
+        .map_err(sqlite_error)?;
+    for (column, column_type) in INDEX_METADATA_COLUMNS {
+        if !columns.contains(*column) {
+            tx.execute_batch(&format!(
+                "ALTER TABLE files ADD COLUMN {} {}",
+                column, column_type
+            ))
+            .map_err(sqlite_error)?;
+        }
+    }
+    tx.execute(
+        "INSERT OR IGNORE INTO archives (path) VALUES (?1)",
+        rusqlite::params![archive_path],
+    )
+    .map_err(sqlite_error)?;
+    let archive_id: i64 = tx
+        .query_row(
+            "SELECT id FROM archives WHERE path = ?1",
+            rusqlite::params![archive_path],
+            |row| row.get(0),
+        )
+        .map_err(sqlite_error)?;
+    tx.execute(
+        "DELETE FROM files WHERE archive_id = ?1",
+        rusqlite::params![archive_id],
+    )
+    .map_err(sqlite_error)?;
+
+    // Sizes, and hashes if recorded, are read from the footer
+    let mut infos: Vec<(String, u64, Option<[u8; 32]>)> = mla
+        .list_files_info()?
+        .map(|info| (info.name.to_string(), info.size, info.hash.copied()))
+        .collect();
+    infos.sort();
+    for (fname, size, hash) in infos {
+        // Otherwise, the hash is read from the file `EndOfFile` block
+        let hash = match hash {
+            Some(hash) => Some(hash),
+            None => mla.get_hash(&fname)?,
+        };
+        let metadata = mla.get_metadata(&fname)?;
+        let (entry_type, link_target) = match metadata.map(|metadata| &metadata.entry_type) {
+            None => (None, None),
+            Some(EntryType::File) => (Some("file"), None),
+            Some(EntryType::Directory) => (Some("directory"), None),
+            Some(EntryType::Symlink(target)) => (Some("symlink"), Some(target.as_str())),
+        };
+        tx.execute(
+            "INSERT INTO files (archive_id, name, size, sha256, entry_type, link_target, mode, uid, gid, mtime)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            rusqlite::params![
+                archive_id,
+                fname,
+                size as i64,
+                hash.map(hex::encode),
+                entry_type,
+                link_target,
+                metadata.and_then(|metadata| metadata.mode),
+                metadata.and_then(|metadata| metadata.uid),
+                metadata.and_then(|metadata| metadata.gid),
+                metadata.and_then(|metadata| metadata.mtime),
+            ],
+        )
+        .map_err(sqlite_error)?;
+    }
+    tx.commit().map_err(sqlite_error)?;
+    Ok(())
+}
+
+/// Size of the chunks read while searching an entry
+const GREP_CHUNK_SIZE: usize = 1024 * 1024;
+/// Bytes kept from the previous chunk, so that regex matches spanning two
+/// chunks are found. Longer regex matches may be missed
+const GREP_REGEX_OVERLAP: usize = 4096;
+
+/// Build the expression searched by `mlar grep`, and the overlap needed
+/// between chunks
+fn grep_regex(matches: &ArgMatches) -> Result<(Regex, usize), String> {
+    // Safe to use unwrap() because the option is required()
+    let pattern = matches.value_of("pattern").unwrap();
+    let (expr, overlap) = if matches.is_present("hex") {
+        let bytes = hex::decode(pattern.replace(' ', "")).map_err(|err| format!("{:?}", err))?;
+        // Match raw bytes, not UTF-8 codepoints
+        let expr: String = bytes.iter().map(|b| format!("\\x{:02x}", b)).collect();
+        (format!("(?-u){}", expr), bytes.len().saturating_sub(1))
+    } else if matches.is_present("fixed_strings") {
+        (regex::escape(pattern), pattern.len().saturating_sub(1))
+    } else {
+        (pattern.to_string(), GREP_REGEX_OVERLAP)
+    };
+    let regex = Regex::new(&expr).map_err(|err| err.to_string())?;
+    Ok((regex, overlap))
+}
+
+/// Return the offsets of the matches of `regex` in `data`, read by chunks
+fn grep_stream<R: Read>(regex: &Regex, overlap: usize, mut data: R) -> io::Result<Vec<u64>> {
+    let mut offsets = Vec::new();
+    let mut buf = Vec::with_capacity(GREP_CHUNK_SIZE + overlap);
+    // Offset, in the entry, of `buf[0]`
+    let mut base = 0u64;
+    // End of the last reported match, to avoid overlapping matches
+    let mut last_end = 0u64;
+    loop {
+        let read = (&mut data)
+            .take(GREP_CHUNK_SIZE as u64)
+            .read_to_end(&mut buf)?;
+        let eof = read < GREP_CHUNK_SIZE;
+        // Matches starting in the last `overlap` bytes are searched again
+        // along with the next chunk
+        let limit = if eof {
+            buf.len()
+        } else {
+            buf.len().saturating_sub(overlap)
+        };
+        for found in regex.find_iter(&buf) {
+            if found.start() >= limit {
+                break;
+            }
+            let start = base + found.start() as u64;
+            if start < last_end {
+                continue;
+            }
+            offsets.push(start);
+            last_end = base + found.end() as u64;
+        }
+        if eof {
+            return Ok(offsets);
+        }
+        buf.drain(..limit);
+        base += limit as u64;
+    }
+}
+
+/// Offsets of the matches, per entry index
+type GrepMatches = Vec<(usize, Vec<u64>)>;
+
+fn grep(matches: &ArgMatches) -> Result<(), Error> {
+    let (regex, overlap) = match grep_regex(matches) {
+        Ok(result) => result,
+        Err(err) => {
+            eprintln!(" [!] Invalid pattern ({})", err);
+            std::process::exit(1);
+        }
+    };
+    let mut fnames: Vec<String> = open_mla_file(matches)?.list_files()?.cloned().collect();
+    fnames.sort();
+    let threads = match matches.value_of("threads") {
+        Some(threads) => threads.parse::<usize>().expect("Invalid thread count"),
+        None => std::thread::available_parallelism()
+            .map(|threads| threads.get())
+            .unwrap_or(1),
+    };
+    let threads = threads.clamp(1, std::cmp::max(fnames.len(), 1));
+
+    // Entries are dispatched to threads, each one having its own reader (they
+    // can't be shared). Results are sorted afterwards for a stable output
+    let next = AtomicUsize::new(0);
+    let (next, fnames_ref, regex) = (&next, &fnames, &regex);
+    let results: Vec<Result<GrepMatches, Error>> = std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(move || -> Result<GrepMatches, Error> {
+                    let mut mla = open_mla_file(matches)?;
+                    let mut found = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let fname = match fnames_ref.get(index) {
+                            Some(fname) => fname,
+                            None => return Ok(found),
+                        };
+                        let subfile = match mla.get_file(fname.clone()) {
+                            Err(err) => {
+                                report_error(
+                                    &format!("Error while looking up file \"{}\"", fname),
+                                    &err,
+                                );
+                                continue;
+                            }
+                            Ok(None) => {
+                                eprintln!(
+                                    " [!] Subfile \"{}\" indexed in metadata could not be found",
+                                    fname
+                                );
+                                continue;
+                            }
+                            Ok(Some(subfile)) => subfile,
+                        };
+                        match grep_stream(regex, overlap, subfile.data) {
+                            Ok(offsets) if offsets.is_empty() => {}
+                            Ok(offsets) => found.push((index, offsets)),
+                            Err(err) => {
+                                eprintln!(" [!] Unable to read \"{}\" ({})", fname, err)
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().expect("Search thread panicked"))
+            .collect()
+    });
+
+    let mut found = Vec::new();
+    for result in results {
+        found.extend(result?);
+    }
+    if found.is_empty() {
+        // As grep, report the absence of match through the exit code
+        std::process::exit(1);
+    }
+    found.sort();
+    for (index, offsets) in found {
+        for offset in offsets {
+            println!("{}:{}", fnames[index], offset);
+        }
+    }
+    Ok(())
+}
+
+/// Maximum amount of new data read at once from a watched file
+const WATCH_CHUNK_SIZE: u64 = 1024 * 1024;
+
+/// Entry of a file being watched, kept opened in the archive
+struct WatchedFile {
+    /// `None` once the file has been removed
+    id: Option<mla::ArchiveFileID>,
+    /// Amount of the file already archived
+    offset: u64,
+    /// Number of entries created for this file, see `Watch::sync_file`
+    version: u64,
+}
+
+/// State of `mlar watch`
+struct Watch<'a> {
+    mla: ArchiveWriter<'a, File>,
+    /// Canonical path of the archive, not to archive it in itself
+    archive_path: PathBuf,
+    files: HashMap<PathBuf, WatchedFile>,
+    /// Data has been added since the last checkpoint
+    dirty: bool,
+}
+
+impl<'a> Watch<'a> {
+    /// Archive every file in `directory`, recursively
+    fn scan(&mut self, directory: &Path) -> Result<(), Error> {
+        let mut files = Vec::new();
+        list_directory_files(directory, &mut files)?;
+        for path in files {
+            self.sync_file(&path)?;
+        }
+        Ok(())
+    }
+
+    /// Archive the data appended to `path` since the last call
+    ///
+    /// Files are expected to only grow, as logs do. If `path` gets smaller,
+    /// or is created again after a removal, its entry is ended and its new
+    /// content is archived as `<path>;<N>`, `N` being 2, 3, ...
+    fn sync_file(&mut self, path: &Path) -> Result<(), Error> {
+        if path == self.archive_path {
+            return Ok(());
+        }
+        let mut file = match File::open(path) {
+            Ok(file) => file,
+            // Already removed, or not readable: nothing to archive
+            Err(_) => return Ok(()),
+        };
+        let metadata = file.metadata()?;
+        if !metadata.is_file() {
+            return Ok(());
+        }
+        let size = metadata.len();
+
+        let (needs_entry, version) = match self.files.get(path) {
+            Some(watched) if watched.id.is_some() && size >= watched.offset => {
+                (false, watched.version)
+            }
+            Some(watched) => (true, watched.version + 1),
+            None => (true, 1),
+        };
+        if needs_entry {
+            self.forget(path)?;
+            // A name may already be taken, by a file actually named `<path>;<N>`
+            let mut version = version;
+            let id = loop {
+                let name = if version == 1 {
+                    path.to_string_lossy().into_owned()
+                } else {
+                    format!("{};{}", path.to_string_lossy(), version)
+                };
+                match self.mla.start_file(&name) {
+                    Ok(id) => {
+                        eprintln!("{}", name);
+                        break id;
+                    }
+                    Err(Error::DuplicateFilename) => version += 1,
+                    Err(err) => return Err(err),
+                }
+            };
+            self.files.insert(
+                path.to_path_buf(),
+                WatchedFile {
+                    id: Some(id),
+                    offset: 0,
+                    version,
+                },
+            );
+        }
+
+        // Safe to use unwrap()s as the entry has been inserted above
+        let watched = self.files.get_mut(path).unwrap();
+        let id = watched.id.unwrap();
+        if watched.offset == size {
+            return Ok(());
+        }
+        file.seek(SeekFrom::Start(watched.offset))?;
+        // The file may change while being read: archive what is actually
+        // read, by bounded chunks
+        let mut buf = Vec::new();
+        while watched.offset < size {
+            buf.clear();
+            (&mut file)
+                .take(std::cmp::min(WATCH_CHUNK_SIZE, size - watched.offset))
+                .read_to_end(&mut buf)?;
+            if buf.is_empty() {
+                break;
+            }
+            self.mla
+                .append_file_content(id, buf.len() as u64, buf.as_slice())?;
+            watched.offset += buf.len() as u64;
+            self.dirty = true;
+        }
+        Ok(())
+    }
+
+    /// End the entry of `path`, if any, as the file has been removed. Its
+    /// version is kept, for a file created again with the same name
+    fn forget(&mut self, path: &Path) -> Result<(), Error> {
+        if let Some(watched) = self.files.get_mut(path) {
+            if let Some(id) = watched.id.take() {
+                self.mla.end_file(id)?;
+                watched.offset = 0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Make the data archived so far durable: once flushed and synced, it
+    /// can be recovered with `mlar repair` even if `mlar watch` is killed
+    fn checkpoint(&mut self, output: &File) -> Result<(), Error> {
+        if self.dirty {
+            self.mla.flush()?;
+            output.sync_data()?;
+            self.dirty = false;
+        }
+        Ok(())
+    }
+}
+
+fn watch_error<E: std::fmt::Display>(err: E) -> Error {
+    Error::IOError(io::Error::other(err.to_string()))
+}
+
+/// Archive the files of a directory as they are created or appended to, until
+/// Ctrl-C or SIGTERM
+///
+/// Entries are kept opened, and new data is added as it is written. Every
+/// `--checkpoint` seconds, the archive is flushed and synced to disk: blocks
+/// being compressed, and the current encrypted chunk, are written too
+fn watch(matches: &ArgMatches) -> Result<(), Error> {
+    use notify::{EventKind, RecursiveMode, Watcher};
+    use std::sync::atomic::AtomicBool;
+    use std::sync::mpsc::{channel, RecvTimeoutError};
+    use std::sync::Arc;
+
+    // Safe to use unwrap() because the options are required()
+    let directory = fs::canonicalize(matches.value_of_os("directory").unwrap())?;
+    let output = matches.value_of("output").unwrap();
+    if output == "-" {
+        eprintln!(" [!] The archive must be a file, to be synced on checkpoints");
+        std::process::exit(1);
+    }
+    let checkpoint_interval = Duration::from_secs(
+        matches
+            .value_of("checkpoint")
+            .unwrap()
+            .parse::<u64>()
+            .expect("Invalid checkpoint interval"),
+    );
+
+    let config = config_from_matches(matches);
+    // AES-GCM-SIV encrypts a chunk once complete: a checkpoint would leave the
+    // last one in memory
+    if config.is_layers_enabled(Layers::ENCRYPT)
+        && config.encryption_cipher() == EncryptionCipher::AesGcmSiv256
+    {
+        eprintln!(
+            " [!] Checkpoints need the AES-GCM cipher, \"--cipher aes-gcm-siv\" cannot be used"
+        );
+        std::process::exit(1);
+    }
+
+    let file = File::create(output)?;
+    let sync_handle = file.try_clone()?;
+    let mut state = Watch {
+        mla: ArchiveWriter::from_config(file, config)?,
+        archive_path: fs::canonicalize(output)?,
+        files: HashMap::new(),
+        dirty: false,
+    };
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stop_handler = stop.clone();
+    ctrlc::set_handler(move || stop_handler.store(true, Ordering::SeqCst)).map_err(watch_error)?;
+
+    // Start watching before the initial scan, not to miss a file created
+    // meanwhile
+    let (sender, events) = channel::<notify::Result<notify::Event>>();
+    let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+    watcher
+        .watch(&directory, RecursiveMode::Recursive)
+        .map_err(watch_error)?;
+    state.scan(&directory)?;
+    state.checkpoint(&sync_handle)?;
+    eprintln!("Watching {}", directory.display());
+
+    let mut last_checkpoint = Instant::now();
+    while !stop.load(Ordering::SeqCst) {
+        // Wake up regularly to check if a stop has been requested
+        match events.recv_timeout(Duration::from_millis(200)) {
+            Ok(Ok(event)) => {
+                if let EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_) =
+                    event.kind
+                {
+                    // Renames are reported as modifications of both paths
+                    for path in event.paths {
+                        if path.is_dir() {
+                            // Files may have been created before the new
+                            // directory is watched
+                            state.scan(&path)?;
+                        } else if path.exists() {
+                            state.sync_file(&path)?;
+                        } else {
+                            state.forget(&path)?;
+                        }
+                    }
+                }
+            }
+            Ok(Err(err)) => eprintln!(" [!] Error while watching ({})", err),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if last_checkpoint.elapsed() >= checkpoint_interval {
+            state.checkpoint(&sync_handle)?;
+            last_checkpoint = Instant::now();
+        }
+    }
+
+    // Catch up with the last changes, then close every entry
+    drop(watcher);
+    let paths: Vec<PathBuf> = state.files.keys().cloned().collect();
+    for path in paths {
+        state.sync_file(&path)?;
+    }
+    for id in state.files.values().filter_map(|watched| watched.id) {
+        state.mla.end_file(id)?;
+    }
+    state.mla.finalize()?;
+    sync_handle.sync_all()?;
+    Ok(())
+}
+
+/// State kept by `mlar daemon` between requests
+#[cfg(unix)]
+struct Daemon<'a, 'b> {
+    /// Private keys, unlocked once for all and kept in locked memory
+    private_keys: Vec<Locked<x25519_dalek::StaticSecret>>,
+    hybrid_private_keys: Vec<Locked<HybridPrivateKey>>,
+    /// Options of the archives created
+    matches: &'a ArgMatches<'b>,
+    /// Archives opened for reading, by path
+    readers: HashMap<String, ArchiveReader<'static, File>>,
+    /// Archives being created, by path
+    writers: HashMap<String, ArchiveWriter<'static, File>>,
+}
+
+#[cfg(unix)]
+impl<'a, 'b> Daemon<'a, 'b> {
+    /// Get the reader for `archive`, opening it if needed
+    fn reader(&mut self, archive: &str) -> Result<&mut ArchiveReader<'static, File>, Error> {
+        if !self.readers.contains_key(archive) {
+            let mut config = ArchiveReaderConfig::new();
+            for private_key in &self.private_keys {
+                config.add_private_keys(std::slice::from_ref(&**private_key));
+            }
+            for private_key in &self.hybrid_private_keys {
+                config.add_hybrid_private_keys(std::slice::from_ref(&**private_key));
+            }
+            let mla = ArchiveReader::from_config(File::open(archive)?, config)?;
+            self.readers.insert(archive.to_string(), mla);
+        }
+        // Safe to use unwrap() as the reader has been inserted above
+        Ok(self.readers.get_mut(archive).unwrap())
+    }
+
+    /// Handle a request, see `daemon` for the API
+    fn handle(&mut self, request: &serde_json::Value) -> Result<serde_json::Value, Error> {
+        let field = |name: &str| {
+            request[name]
+                .as_str()
+                .ok_or_else(|| Error::BadAPIArgument(format!("missing \"{}\"", name)))
+        };
+        let op = field("op")?;
+        let archive = field("archive")?;
+        match op {
+            "list" => {
+                let mla = self.reader(archive)?;
+                let mut fnames: Vec<String> = mla.list_files()?.cloned().collect();
+                fnames.sort();
+                Ok(json!({ "files": fnames }))
+            }
+            "extract" => {
+                let (fname, output) = (field("file")?, field("output")?);
+                let mla = self.reader(archive)?;
+                match mla.get_file(fname.to_string())? {
+                    Some(mut subfile) => {
+                        let mut dest = File::create(output)?;
+                        io::copy(&mut subfile.data, &mut dest)?;
+                        Ok(json!({}))
+                    }
+                    None => Err(Error::BadAPIArgument(format!(
+                        "file \"{}\" not found",
+                        fname
+                    ))),
+                }
+            }
+            "close" => {
+                self.readers.remove(archive);
+                Ok(json!({}))
+            }
+            "create" => {
+                if self.writers.contains_key(archive) {
+                    return Err(Error::BadAPIArgument(
+                        "archive already being created".to_string(),
+                    ));
+                }
+                let file = File::create(archive)?;
+                let mla = ArchiveWriter::from_config(file, config_from_matches(self.matches))?;
+                self.writers.insert(archive.to_string(), mla);
+                Ok(json!({}))
+            }
+            "add" => {
+                let (fname, path) = (field("file")?, field("path")?);
+                let mla = self.writers.get_mut(archive).ok_or_else(|| {
+                    Error::BadAPIArgument("archive not being created".to_string())
+                })?;
+                let file = File::open(path)?;
+                let length = file.metadata()?.len();
+                mla.add_file(fname, length, file)?;
+                Ok(json!({}))
+            }
+            "finalize" => {
+                let mut mla = self.writers.remove(archive).ok_or_else(|| {
+                    Error::BadAPIArgument("archive not being created".to_string())
+                })?;
+                mla.finalize()?;
+                // A reader opened before would be outdated
+                self.readers.remove(archive);
+                Ok(json!({}))
+            }
+            _ => Err(Error::BadAPIArgument(format!(
+                "unknown operation \"{}\"",
+                op
+            ))),
+        }
+    }
+}
+
+/// Serve requests on a Unix socket, keeping archives and keys opened
+///
+/// Requests and responses are JSON objects, one per line:
+/// - `{"op": "list", "archive": path}` -> `{"ok": true, "files": [...]}`
+/// - `{"op": "extract", "archive": path, "file": name, "output": path}`
+/// - `{"op": "close", "archive": path}`: forget an opened archive
+/// - `{"op": "create", "archive": path}`, `{"op": "add", "archive": path,
+///   "file": name, "path": path}`, `{"op": "finalize", "archive": path}`
+/// - `{"op": "shutdown"}`
+///
+/// On error, the response is `{"ok": false, "error": message, "code": code}`,
+/// `code` being the one of `--error-format json`
+#[cfg(unix)]
+fn daemon(matches: &ArgMatches) -> Result<(), Error> {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    // Safe to use unwrap() because the option is required()
+    let socket = Path::new(matches.value_of_os("socket").unwrap());
+    // The socket is moved over `socket`: only the one of a stopped daemon may
+    // be replaced
+    match fs::symlink_metadata(socket) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+        Ok(metadata)
+            if metadata.file_type().is_socket()
+                && matches!(
+                    UnixStream::connect(socket),
+                    Err(err) if err.kind() == io::ErrorKind::ConnectionRefused
+                ) => {}
+        Ok(_) => {
+            eprintln!(
+                " [!] {} already exists, and is not the socket of a stopped daemon",
+                socket.display()
+            );
+            std::process::exit(1);
+        }
+        Err(err) => {
+            let err = Error::from(err);
+            report_error("Error while checking the socket path", &err);
+            return Err(err);
+        }
+    }
+    // Keys are moved to locked memory as soon as parsed, so that they are
+    // never left in an ordinary heap allocation
+    let mut state = Daemon {
+        private_keys: load_ecc_private_keys(matches, Locked::new)?,
+        hybrid_private_keys: load_hybrid_private_keys(matches, Locked::new)?,
+        matches,
+        readers: HashMap::new(),
+        writers: HashMap::new(),
+    };
+    // Keys are unlocked: only the current user may send requests. The socket
+    // is created in a private directory, so it is never reachable by others
+    // before being restricted, then moved to its final path
+    let mut private_dir = socket.as_os_str().to_owned();
+    private_dir.push(format!(".{}", std::process::id()));
+    let private_dir = PathBuf::from(private_dir);
+    fs::DirBuilder::new().mode(0o700).create(&private_dir)?;
+    let private_socket = private_dir.join("socket");
+    let listener = UnixListener::bind(&private_socket).and_then(|listener| {
+        fs::set_permissions(&private_socket, fs::Permissions::from_mode(0o600))?;
+        fs::rename(&private_socket, socket)?;
+        Ok(listener)
+    });
+    fs::remove_dir(&private_dir)?;
+    let listener = listener?;
+    eprintln!("Listening on {}", socket.display());
+
+    'accept: for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                report_error("Error while accepting a connection", &err.into());
+                continue;
+            }
+        };
+        let mut output = stream.try_clone()?;
+        for line in BufReader::new(stream).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<serde_json::Value>(&line) {
+                Ok(request) if request["op"] == "shutdown" => {
+                    // Best effort, the daemon stops anyway
+                    let _ = writeln!(output, "{}", json!({ "ok": true }));
+                    break 'accept;
+                }
+                Ok(request) => state.handle(&request),
+                Err(err) => Err(Error::BadAPIArgument(format!("invalid request ({})", err))),
+            };
+            let response = match response {
+                Ok(mut response) => {
+                    response["ok"] = json!(true);
+                    response
+                }
+                Err(err) => json!({ "ok": false, "error": err.to_string(), "code": err.code() }),
+            };
+            if writeln!(output, "{}", response).is_err() {
+                break;
+            }
+        }
+    }
+
+    for archive in state.writers.keys() {
+        eprintln!("[WARNING] \"{}\" has not been finalized", archive);
+    }
+    fs::remove_file(socket)?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn daemon(_matches: &ArgMatches) -> Result<(), Error> {
+    eprintln!("[!] The daemon mode is only available on Unix systems");
+    std::process::exit(1);
+}
+
+fn info(matches: &ArgMatches) -> Result<(), Error> {
+    if matches.is_present("crypto") {
+        let backends = mla::active_backends();
+        println!("AES: {}", backends.aes);
+        println!("GHASH: {}", backends.ghash);
+    }
+    if matches.is_present("input") {
+        let mut inspection = ArchiveHeader::inspect(open_input(matches, false)?)?;
+        let private_keys = open_ecc_private_keys(matches)?;
+        let hybrid_private_keys = open_hybrid_private_keys(matches)?;
+        let slots = recipient_slots(
+            open_input(matches, false)?,
+            &private_keys,
+            &hybrid_private_keys,
+        )?;
+        // The footer of an encrypted archive is only readable with a key
+        if inspection.entries.is_none() && matches.is_present("private_keys") {
+            match open_mla_file(matches) {
+                Ok(mla) => {
+                    inspection.entries = Some(mla.list_files()?.count());
+                    inspection.truncated = Some(false);
+                }
+                // None of the keys opens the archive, nothing more is known
+                Err(Error::PrivateKeyNeeded) | Err(Error::ConfigError(_)) => (),
+                Err(_) => inspection.truncated = Some(true),
+            }
+        }
+
+        println!("Format version: {}", inspection.format_version);
+        println!(
+            "Size: {}",
+            inspection
+                .size
+                .file_size(file_size_opts::CONVENTIONAL)
+                .unwrap()
+        );
+        let layers: Vec<&str> = [
+            (Layers::COMPRESS, "compress"),
+            (Layers::ENCRYPT, "encrypt"),
+            (Layers::SIGN, "sign"),
+        ]
+        .iter()
+        .filter(|(layer, _)| inspection.layers.contains(*layer))
+        .map(|(_, name)| *name)
+        .collect();
+        if layers.is_empty() {
+            println!("Layers: none");
+        } else {
+            println!("Layers: {}", layers.join(", "));
+        }
+        if let Some(algorithm) = inspection.compression {
+            let algorithm = match algorithm {
+                CompressionAlgorithm::Brotli => "brotli",
+                CompressionAlgorithm::Zstd => "zstd",
+                CompressionAlgorithm::Lz4 => "lz4",
+            };
+            if inspection.compression_dictionary {
+                println!("Compression: {} (with dictionary)", algorithm);
+            } else {
+                println!("Compression: {}", algorithm);
+            }
+        }
+        if let Some(cipher) = inspection.cipher {
+            let cipher = match cipher {
+                EncryptionCipher::AesGcm256 => "aes-gcm",
+                EncryptionCipher::AesGcmSiv256 => "aes-gcm-siv",
+            };
+            println!("Cipher: {}", cipher);
+        }
+        if inspection.layers.contains(Layers::SIGN) {
+            println!("Signers: {}", inspection.signers);
+        }
+        if inspection.layers.contains(Layers::ENCRYPT) {
+            println!("Recipient slots: {}", slots.len());
+        }
+        for (index, slot) in slots.iter().enumerate() {
+            // Recipients are not identified in the archive: only the slots
+            // opened by the given private keys are known
+            let fingerprint = match (slot.kind, slot.key_index) {
+                (RecipientKind::X25519, Some(key_index)) => {
+                    x25519_dalek::PublicKey::from(&private_keys[key_index]).fingerprint()
+                }
+                (RecipientKind::Hybrid, Some(key_index)) => {
+                    hybrid_private_keys[key_index].public_key().fingerprint()
+                }
+                (RecipientKind::Password, _) => "password".to_string(),
+                (_, None) => "unknown".to_string(),
+            };
+            let kind = match slot.kind {
+                RecipientKind::X25519 => "x25519",
+                RecipientKind::Hybrid => "hybrid",
+                RecipientKind::Password => "password",
+            };
+            println!("  {}: {} {}", index, kind, fingerprint);
+        }
+        match inspection.entries {
+            Some(entries) => println!("Entries: {}", entries),
+            None => println!("Entries: unknown"),
+        }
+        match inspection.truncated {
+            Some(true) => println!("Truncated: yes"),
+            Some(false) => println!("Truncated: no"),
+            None => println!("Truncated: unknown"),
+        }
+    }
+    Ok(())
+}
+
+/// Whether errors are reported as JSON, see `--error-format`
+static ERROR_FORMAT_JSON: AtomicBool = AtomicBool::new(false);
+
+/// Report on stderr the error `err`, met while doing what `message`
+/// describes. With `--error-format json`, it is an object with the error, as
+/// described by `error_json`, and `message` as its "context"
+fn report_error(message: &str, err: &Error) {
+    if ERROR_FORMAT_JSON.load(Ordering::Relaxed) {
+        eprintln!(
+            "{}",
+            json!({ "error": error_json(err), "context": message })
+        );
+    } else {
+        eprintln!(" [!] {} ({})", message, err);
+    }
+}
+
+/// Describe `error` for `--error-format json`
+fn error_json(error: &Error) -> serde_json::Value {
+    let mut value = json!({
+        "code": error.code(),
+        "message": error.root().to_string(),
+    });
+    if let Some(context) = error.context() {
+        if let Some(layer) = context.layer {
+            value["layer"] = json!(layer);
+        }
+        if let Some(offset) = context.offset {
+            value["offset"] = json!(offset);
+        }
+        if let Some(entry) = &context.entry {
+            value["entry"] = json!(entry);
+        }
+    }
+    value
+}
+
 fn main() {
     // Common arguments list, for homogeneity
     let input_args = vec![
         Arg::with_name("input")
-            .help("Archive path")
+            .help("Archive path. For a multi-volume archive, path of its first volume (ending with .001)")
             .long("input")
             .short("i")
             .number_of_values(1)
@@ -717,12 +4275,21 @@ fn main() {
         Arg::with_name("private_keys")
             .long("private_keys")
             .short("k")
-            .help("Candidates ED25519 private key paths (DER or PEM format)")
+            .help("Candidates ED25519 private key paths (DER or PEM format, or OpenSSH), or hybrid ones from keygen")
+            .number_of_values(1)
+            .multiple(true)
+            .takes_value(true),
+        Arg::with_name("pkcs11_uri")
+            .long("pkcs11-uri")
+            .help("PKCS#11 URI of a candidate X25519 private key on a token (ex: 'pkcs11:token=MLA;object=key?module-path=/usr/lib/libykcs11.so'), which makes the key exchange. The PIN is taken from 'pin-value', or asked. Needs mlar built with the 'pkcs11' feature")
             .number_of_values(1)
             .multiple(true)
             .takes_value(true),
+        Arg::with_name("password_file")
+            .long("password-file")
+            .help("File containing the password of encrypted private keys, or of the archive. If not given, the password is asked when needed")
+            .number_of_values(1),
     ];
-    let layers = ["compress", "encrypt"];
     let output_args = vec![
         Arg::with_name("output")
             .help("Output file path. Use - for stdout")
@@ -731,7 +4298,7 @@ fn main() {
             .takes_value(true)
             .required(true),
         Arg::with_name("public_keys")
-            .help("ED25519 Public key paths (DER or PEM format)")
+            .help("ED25519 Public key paths (DER or PEM format, or OpenSSH authorized_keys), or hybrid ones from keygen")
             .long("pubkey")
             .short("p")
             .number_of_values(1)
@@ -740,26 +4307,187 @@ fn main() {
             .long("layers")
             .short("l")
             .help("Layers to use. Default is 'compress,encrypt'")
-            .possible_values(&layers)
+            .possible_values(&LAYER_NAMES)
             .number_of_values(1)
             .multiple(true)
             .min_values(0),
+        Arg::with_name("compression_algo")
+            .long("compression-algo")
+            .help("Compression algorithm. Default is 'brotli'; 'zstd' is faster, 'lz4' much faster but less dense")
+            .possible_values(&COMPRESSION_ALGORITHM_NAMES)
+            .takes_value(true),
         Arg::with_name("compression_level")
             .group("Compression layer")
             .short("-q")
             .long("compression_level")
-            .help("Compression level (0-11); ; bigger values cause denser, but slower compression")
+            .visible_alias("compression-level")
+            .help("Compression level (brotli: 0-11, default 5; zstd: 1-22, default 3; lz4: 0); bigger values cause denser, but slower compression")
+            .takes_value(true),
+        Arg::with_name("comp_dict")
+            .long("comp-dict")
+            .help("Zstandard dictionary (ex: from train-dict) used to compress every block, for better ratios on many small and similar files. It is stored in the archive, and implies '--compression-algo zstd'")
+            .number_of_values(1),
+        Arg::with_name("threads")
+            .long("threads")
+            .short("j")
+            .help("Number of threads compressing blocks, 0 for the number of CPUs (default: 1)")
             .takes_value(true),
+        Arg::with_name("password")
+            .long("password")
+            .takes_value(false)
+            .help("Also allow to open the archive with a password, instead of a private key. It is asked on the terminal, or read from --password-file"),
+        Arg::with_name("cipher")
+            .long("cipher")
+            .help("Cipher of the encryption layer. Default is 'aes-gcm'; 'aes-gcm-siv' resists nonce reuse, but the last chunk of a truncated archive is lost")
+            .possible_values(&["aes-gcm", "aes-gcm-siv"])
+            .takes_value(true),
+    ];
+
+    let filter_args = vec![
+        Arg::with_name("include")
+            .help("Only keep files whose name matches this glob pattern (can be repeated)")
+            .long("include")
+            .number_of_values(1)
+            .multiple(true),
+        Arg::with_name("exclude")
+            .help("Skip files whose name matches this glob pattern (can be repeated)")
+            .long("exclude")
+            .number_of_values(1)
+            .multiple(true),
+        Arg::with_name("regex")
+            .help("Treat --include and --exclude patterns as regexes, searched in file names")
+            .long("regex")
+            .takes_value(false),
+    ];
+
+    let padding_args = vec![
+        Arg::with_name("pad")
+            .long("pad")
+            .number_of_values(1)
+            .help("Pad each file to a multiple of this size (ex: 64K), and add random padding after the last one, to hide the sizes of the files. The 'compress' layer must be disabled (ex: -l encrypt)"),
+        Arg::with_name("pad_decoy")
+            .long("pad-decoy")
+            .number_of_values(1)
+            .help("Maximum size of the random padding added after the last file, with --pad (default: the --pad size)"),
     ];
 
+    let progress_arg = Arg::with_name("progress")
+        .help("Report the progress on stderr, as a progress bar with the estimated remaining time, or as one JSON object per line, with \"done\" and \"total\" bytes, \"entries_completed\", the current \"entry\" and \"eta_seconds\"")
+        .long("progress")
+        .number_of_values(1)
+        .possible_values(&["bar", "json"]);
+
     // Main parsing
-    let mut app = App::new(env!("CARGO_PKG_NAME"))
+    let app = App::new(env!("CARGO_PKG_NAME"))
         .version(env!("CARGO_PKG_VERSION"))
         .about(env!("CARGO_PKG_DESCRIPTION"))
+        .arg(
+            Arg::with_name("error_format")
+                .help("Format of the errors, on stderr. 'json' reports an object with the error \"code\", its \"message\", and, when known, the failing \"layer\", \"offset\" and \"entry\". Errors not ending the command also have a \"context\"")
+                .long("error-format")
+                .number_of_values(1)
+                .possible_values(&["text", "json"])
+                .global(true),
+        )
         .subcommand(
             SubCommand::with_name("create")
                 .about("Create a new MLA Archive")
-                .args(&output_args)
+                // The output may be named by the profile
+                .arg(output_args[0].clone().required_unless("profile"))
+                .args(&output_args[1..])
+                .arg(
+                    Arg::with_name("profile")
+                        .long("profile")
+                        .number_of_values(1)
+                        .help("TOML file with the settings to use (recipients, layers, compression, padding, include / exclude patterns, output), overridden by arguments. See README.md"),
+                )
+                .args(&filter_args)
+                // Password of the archive, with --password
+                .args(&input_args[3..])
+                .arg(
+                    Arg::with_name("autotune")
+                        .long("autotune")
+                        .takes_value(false)
+                        .help("Pick compression block and encryption chunk sizes from the files to add"),
+                )
+                .arg(
+                    Arg::with_name("digest")
+                        .long("digest")
+                        .takes_value(false)
+                        .help("Display the SHA256 of the resulting archive"),
+                )
+                .arg(
+                    Arg::with_name("dedup")
+                        .long("dedup")
+                        .takes_value(false)
+                        .help("Split the content of files into chunks according to their content, and store identical chunks once (ex: successive disk images). The resulting archive cannot be repaired"),
+                )
+                .arg(
+                    Arg::with_name("stats")
+                        .long("stats")
+                        .takes_value(false)
+                        .help("Display the number of entries and the size of their content and, with --dedup, the space saved"),
+                )
+                .args(&padding_args)
+                .arg(
+                    Arg::with_name("deterministic")
+                        .long("deterministic")
+                        .takes_value(false)
+                        .help("Create a reproducible archive: files are added sorted by name, modification times are not recorded, encryption secrets are derived from --seed-file, and the cipher is AES-GCM-SIV"),
+                )
+                .arg(
+                    Arg::with_name("seed_file")
+                        .long("seed-file")
+                        .number_of_values(1)
+                        .requires("deterministic")
+                        .help("File whose content seeds the encryption secrets, with --deterministic. Anyone with it can decrypt the archive, and archives of different contents from the same seed reveal which of their chunks are identical"),
+                )
+                .arg(
+                    Arg::with_name("volume_size")
+                        .long("volume-size")
+                        .number_of_values(1)
+                        .help("Split the archive in volumes of at most this size (ex: 4G), written to <output>.001, <output>.002, etc. To read it, use <output>.001 as input"),
+                )
+                .arg(
+                    Arg::with_name("preserve_hardlinks")
+                        .long("preserve-hardlinks")
+                        .takes_value(false)
+                        .conflicts_with("hard_dereference")
+                        .help("Store files with several names once, the other names being links to it"),
+                )
+                .arg(
+                    Arg::with_name("hard_dereference")
+                        .long("hard-dereference")
+                        .takes_value(false)
+                        .help("Store each name of files with several names as an independent entry (default)"),
+                )
+                .arg(
+                    Arg::with_name("preserve")
+                        .long("preserve")
+                        .takes_value(false)
+                        .help("Record permissions, owners and modification times, and store symbolic links and directories as such instead of following them"),
+                )
+                .arg(
+                    Arg::with_name("signing_keys")
+                        .long("sign")
+                        .help("ED25519 private key path (DER or PEM format) to sign the archive with, so that recipients can authenticate it")
+                        .number_of_values(1)
+                        .multiple(true),
+                )
+                .arg(progress_arg.clone())
+                .arg(Arg::with_name("files").help("Files to add").multiple(true)),
+        )
+        .subcommand(
+            SubCommand::with_name("append")
+                .about("Add files to an existing MLA Archive, without rewriting it. Archives encrypted with AES-GCM (the default cipher of create) or signed are refused: only the ones created with \"--cipher aes-gcm-siv\", or without encryption, can be appended to. Files added to a deduplicated archive are deduplicated too, but padding and the digest are only applied with --pad and --digest, as they are not recorded in the archive. The compression level is the default one")
+                .args(&input_args)
+                .args(&padding_args)
+                .arg(
+                    Arg::with_name("digest")
+                        .long("digest")
+                        .takes_value(false)
+                        .help("Display the SHA256 of the resulting archive"),
+                )
                 .arg(Arg::with_name("files").help("Files to add").multiple(true)),
         )
         .subcommand(
@@ -772,6 +4500,62 @@ fn main() {
                         .multiple(true)
                         .takes_value(false)
                         .help("Verbose listing, with additional information"),
+                )
+                .arg(
+                    Arg::with_name("hash")
+                        .help("Print the hash of each file, in the format of sha256sum. Hashes are read from the archive index, without extracting the files")
+                        .long("hash")
+                        .number_of_values(1)
+                        .possible_values(&["sha256"])
+                        .conflicts_with("verbose"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Output a JSON list of {\"name\", \"size\", \"offset\", \"footprint\", \"sha256\"}, read from the archive index")
+                        .long("json")
+                        .conflicts_with_all(&["verbose", "hash"]),
+                )
+                .args(&filter_args),
+        )
+        .subcommand(
+            SubCommand::with_name("find")
+                .about("Find files inside a MLA Archive, according to their name, size and modification time")
+                .args(&input_args)
+                .arg(
+                    Arg::with_name("name")
+                        .help("Only keep files whose name matches this glob pattern (can be repeated)")
+                        .long("name")
+                        .number_of_values(1)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("larger_than")
+                        .help("Only keep files strictly larger than this size (ex: 512, 10K, 3M, 1G)")
+                        .long("larger-than")
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("smaller_than")
+                        .help("Only keep files strictly smaller than this size (ex: 512, 10K, 3M, 1G)")
+                        .long("smaller-than")
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("newer_than")
+                        .help("Only keep files modified strictly after this UTC date (ex: 2024-01-01, 2024-01-01T12:00:00), as recorded with create --preserve")
+                        .long("newer-than")
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("older_than")
+                        .help("Only keep files modified strictly before this UTC date (ex: 2024-01-01, 2024-01-01T12:00:00), as recorded with create --preserve")
+                        .long("older-than")
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Output a JSON list of {\"name\", \"size\"} instead of one name per line")
+                        .long("json"),
                 ),
         )
         .subcommand(
@@ -800,7 +4584,15 @@ fn main() {
                         .short("-v")
                         .takes_value(false)
                         .help("List files as they are extracted"),
-                ),
+                )
+                .arg(
+                    Arg::with_name("preserve")
+                        .long("preserve")
+                        .takes_value(false)
+                        .help("Restore recorded permissions, modification times, and owners (if run as root)"),
+                )
+                .arg(progress_arg.clone())
+                .args(&filter_args),
         )
         .subcommand(
             SubCommand::with_name("cat")
@@ -824,27 +4616,48 @@ fn main() {
                 .arg(
                     Arg::with_name("files")
                         .required(true)
-                        .help("List of displayed files"),
+                        .multiple(true)
+                        .help("List of displayed files, in order. Fails if one of them is missing"),
                 ),
         )
         .subcommand(
             SubCommand::with_name("to-tar")
-                .about("Convert a MLA Archive to a TAR Archive")
+                .about("Convert a MLA Archive to a TAR Archive, with the recorded metadata")
                 .args(&input_args)
                 .arg(
                     Arg::with_name("output")
-                        .help("Tar Archive path")
+                        .help("Tar Archive path, or '-' for stdout")
                         .long("output")
                         .short("o")
                         .number_of_values(1)
                         .required(true),
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("from-tar")
+                .about("Convert a TAR Archive to a MLA Archive")
+                .arg(
+                    Arg::with_name("input")
+                        .help("Tar Archive path, or '-' for stdin")
+                        .long("input")
+                        .short("i")
+                        .number_of_values(1)
+                        .required(true),
+                )
+                .args(&output_args)
+                .arg(
+                    Arg::with_name("preserve")
+                        .long("preserve")
+                        .takes_value(false)
+                        .help("Record permissions, owners and modification times, and keep symbolic links and directories"),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("repair")
-                .about("Try to repair a MLA Archive into a fresh MLA Archive")
+                .about("Try to repair a MLA Archive into a fresh MLA Archive, reporting the recovery status of each file")
                 .args(&input_args)
-                .args(&output_args),
+                .args(&output_args)
+                .arg(progress_arg),
         )
         .subcommand(
             SubCommand::with_name("convert")
@@ -854,6 +4667,63 @@ fn main() {
                 .args(&input_args)
                 .args(&output_args),
         )
+        .subcommand(
+            SubCommand::with_name("rekey")
+                .about("Change the recipients of an encrypted MLA Archive, without re-encrypting its content. Recipients are not identified in the archive: the new recipients replace all the current ones, but the password and the hybrid recipients, if any, are kept. The content key is unchanged: a removed recipient who already obtained it can still decrypt the archive")
+                .args(&input_args)
+                .arg(
+                    Arg::with_name("output")
+                        .help("Output file path. Use - for stdout")
+                        .long("output")
+                        .short("o")
+                        .takes_value(true)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("public_keys")
+                        .help("ED25519 Public key paths of the new recipients (DER or PEM format, or OpenSSH authorized_keys)")
+                        .long("pubkey")
+                        .short("p")
+                        .number_of_values(1)
+                        .multiple(true)
+                        .conflicts_with_all(&["add_public_keys", "remove_public_keys"]),
+                )
+                .arg(
+                    Arg::with_name("current_public_keys")
+                        .help("ED25519 Public key paths of all the current recipients, needed by --add-pub and --remove-pub. Only their number is checked against the archive")
+                        .long("current-pub")
+                        .number_of_values(1)
+                        .multiple(true),
+                )
+                .arg(
+                    Arg::with_name("add_public_keys")
+                        .help("ED25519 Public key path of a recipient to add to the current ones")
+                        .long("add-pub")
+                        .number_of_values(1)
+                        .multiple(true)
+                        .requires("current_public_keys"),
+                )
+                .arg(
+                    Arg::with_name("remove_public_keys")
+                        .help("ED25519 Public key path of a current recipient to remove")
+                        .long("remove-pub")
+                        .number_of_values(1)
+                        .multiple(true)
+                        .requires("current_public_keys"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("edit")
+                .about("Modify a file inside a MLA Archive, using $EDITOR")
+                .args(&input_args)
+                // Options of the rewritten archive (all but the output path)
+                .args(&output_args[1..])
+                .arg(
+                    Arg::with_name("file")
+                        .help("File to modify")
+                        .required(true),
+                ),
+        )
         .subcommand(
             SubCommand::with_name("keygen")
                 .about(
@@ -865,28 +4735,279 @@ fn main() {
                         .number_of_values(1)
                         .required(true)
                 )
+                .arg(
+                    Arg::with_name("type")
+                        .long("type")
+                        .help("Key type. Default is 'ed25519'; 'hybrid' combines X25519 with the post-quantum ML-KEM-768, both PEM encoded, and is only usable by mlar")
+                        .possible_values(&["ed25519", "hybrid"])
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("seed")
+                        .long("seed")
+                        .help("File containing the seed the key is derived from, at least 16 bytes long. The same seed always gives the same key (for escrow). Only for 'ed25519' keys")
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("mnemonic")
+                        .long("mnemonic")
+                        .help("File containing the BIP39 mnemonic the key is derived from: 12 to 24 English words, with a valid checksum. The same mnemonic always gives the same key (for escrow). Only for 'ed25519' keys")
+                        .number_of_values(1)
+                        .conflicts_with("seed"),
+                )
+                .arg(
+                    Arg::with_name("comment")
+                        .long("comment")
+                        .help("Comment written before the public key, for instance to tell recipients apart")
+                        .number_of_values(1),
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("train-dict")
+                .about("Train a compression dictionary on samples of files, for create --comp-dict")
+                .arg(
+                    Arg::with_name("output")
+                        .help("Output file for the dictionary")
+                        .long("output")
+                        .short("o")
+                        .number_of_values(1)
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("max_size")
+                        .long("max-size")
+                        .number_of_values(1)
+                        .help("Maximum size of the dictionary (ex: 64K, default: 110K, at most 1M)"),
+                )
+                .arg(
+                    Arg::with_name("samples")
+                        .long("samples")
+                        .number_of_values(1)
+                        .help("Maximum number of files sampled, evenly spread over the given ones (default: 10000)"),
+                )
+                .arg(
+                    Arg::with_name("sample_size")
+                        .long("sample-size")
+                        .number_of_values(1)
+                        .help("Maximum number of bytes read from the beginning of each sampled file (default: 128K)"),
+                )
+                .arg(
+                    Arg::with_name("files")
+                        .help("Files, or directories, to sample")
+                        .multiple(true)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("verify")
+                .about("Check the integrity of a MLA Archive (every file is read and checked against its hash), authenticate its signer, and/or compare it with a directory (names, sizes, hashes). Without option, only the integrity is checked")
+                .args(&input_args)
+                .arg(
+                    Arg::with_name("directory")
+                        .help("Directory to compare with, as if the archive were extracted in it")
+                        .long("against-directory")
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("integrity")
+                        .help("Also check the integrity of the archive, when comparing with a directory or checking the signature")
+                        .long("integrity"),
+                )
+                .arg(
+                    Arg::with_name("verification_keys")
+                        .help("ED25519 Public key paths (DER or PEM format) of the trusted signers. The archive must be signed by one of them")
+                        .long("pubkey")
+                        .short("p")
+                        .number_of_values(1)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("List the files added, removed or modified between two MLA Archives, from their index (names, sizes, hashes), without extracting them. The exit code is non-zero if they differ")
+                .args(&input_args)
+                .arg(
+                    Arg::with_name("other")
+                        .help("Archive to compare with the input one, opened with the same keys")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("content")
+                        .help("Read and compare the contents byte per byte, instead of their recorded hashes")
+                        .long("content"),
+                )
+                .arg(
+                    Arg::with_name("json")
+                        .help("Output a JSON list of {\"name\", \"change\"}, change being \"added\", \"removed\" or \"modified\"")
+                        .long("json"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Serve the files of a MLA Archive over HTTP (read-only)")
+                .args(&input_args)
+                .arg(
+                    Arg::with_name("listen")
+                        .help("Address to listen on")
+                        .long("listen")
+                        .number_of_values(1)
+                        .default_value("127.0.0.1:8080"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("mount")
+                .about("Mount a MLA Archive as a read-only filesystem (FUSE), reading files on demand. Needs the 'fuse' feature")
+                .args(&input_args)
+                .arg(
+                    Arg::with_name("mountpoint")
+                        .help("Directory where the archive is mounted")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("index")
+                .about("Catalog the files of a MLA Archive (name, size, SHA256, metadata) in an SQLite database")
+                .args(&input_args)
+                .arg(
+                    Arg::with_name("output")
+                        .help("SQLite database path, created if needed")
+                        .long("output")
+                        .short("o")
+                        .number_of_values(1)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("grep")
+                .about("Search a pattern in the files of a MLA Archive, printing the file names and offsets of the matches")
+                .args(&input_args)
+                .arg(
+                    Arg::with_name("fixed_strings")
+                        .help("Interpret the pattern as a fixed string, instead of a regex")
+                        .long("fixed-strings")
+                        .short("F"),
+                )
+                .arg(
+                    Arg::with_name("hex")
+                        .help("Interpret the pattern as hex-encoded bytes (ex: \"4d5a9000\")")
+                        .long("hex")
+                        .conflicts_with("fixed_strings"),
+                )
+                .arg(
+                    Arg::with_name("threads")
+                        .help("Number of files searched in parallel (default: number of CPUs)")
+                        .long("threads")
+                        .short("j")
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("pattern")
+                        .help("Regex (on bytes), fixed string or hex pattern")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("watch")
+                .about("Archive the files of a directory as they are created or appended to, until interrupted (Ctrl-C or SIGTERM)")
+                .args(&output_args)
+                .arg(
+                    Arg::with_name("checkpoint")
+                        .help("Interval, in seconds, between checkpoints: data archived before the last one can be recovered with 'repair' if mlar is killed")
+                        .long("checkpoint")
+                        .number_of_values(1)
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("directory")
+                        .help("Directory to watch, recursively")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("daemon")
+                .about("Keep archives and keys opened, and serve requests on a Unix socket")
+                // Private keys, and options of the archives created
+                .args(&input_args[1..])
+                .args(&output_args[1..])
+                .arg(
+                    Arg::with_name("socket")
+                        .help("Unix socket path")
+                        .long("socket")
+                        .number_of_values(1)
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("info")
+                .about("Display information about mlar, or about a MLA Archive")
+                .arg(input_args[0].clone().required(false))
+                .args(&input_args[1..])
+                .arg(
+                    Arg::with_name("crypto")
+                        .long("crypto")
+                        .takes_value(false)
+                        .required_unless("input")
+                        .help("Display the cryptographic implementations used on this computer"),
+                ),
         );
 
     // Launch sub-command
     let mut help = Vec::new();
-    app.write_long_help(&mut help).unwrap();
+    // Written from a copy: on `app`, it would propagate the global arguments a
+    // second time in `get_matches`
+    app.clone().write_long_help(&mut help).unwrap();
     let matches = app.get_matches();
+    let error_format = matches
+        .subcommand()
+        .1
+        .and_then(|matches| matches.value_of("error_format"));
+    ERROR_FORMAT_JSON.store(error_format == Some("json"), Ordering::Relaxed);
     let res = if let Some(matches) = matches.subcommand_matches("create") {
         create(matches)
+    } else if let Some(matches) = matches.subcommand_matches("append") {
+        append(matches)
     } else if let Some(matches) = matches.subcommand_matches("list") {
         list(matches)
+    } else if let Some(matches) = matches.subcommand_matches("find") {
+        find(matches)
     } else if let Some(matches) = matches.subcommand_matches("extract") {
         extract(matches)
     } else if let Some(matches) = matches.subcommand_matches("cat") {
         cat(matches)
     } else if let Some(matches) = matches.subcommand_matches("to-tar") {
         to_tar(matches)
+    } else if let Some(matches) = matches.subcommand_matches("from-tar") {
+        from_tar(matches)
     } else if let Some(matches) = matches.subcommand_matches("repair") {
         repair(matches)
     } else if let Some(matches) = matches.subcommand_matches("convert") {
         convert(matches)
+    } else if let Some(matches) = matches.subcommand_matches("rekey") {
+        rekey(matches)
+    } else if let Some(matches) = matches.subcommand_matches("edit") {
+        edit(matches)
     } else if let Some(matches) = matches.subcommand_matches("keygen") {
         keygen(matches)
+    } else if let Some(matches) = matches.subcommand_matches("train-dict") {
+        train_dict(matches)
+    } else if let Some(matches) = matches.subcommand_matches("verify") {
+        verify(matches)
+    } else if let Some(matches) = matches.subcommand_matches("diff") {
+        diff(matches)
+    } else if let Some(matches) = matches.subcommand_matches("serve") {
+        serve(matches)
+    } else if let Some(matches) = matches.subcommand_matches("mount") {
+        mount(matches)
+    } else if let Some(matches) = matches.subcommand_matches("index") {
+        index(matches)
+    } else if let Some(matches) = matches.subcommand_matches("grep") {
+        grep(matches)
+    } else if let Some(matches) = matches.subcommand_matches("watch") {
+        watch(matches)
+    } else if let Some(matches) = matches.subcommand_matches("daemon") {
+        daemon(matches)
+    } else if let Some(matches) = matches.subcommand_matches("info") {
+        info(matches)
     } else {
         eprintln!("Error: at least one command required.");
         eprintln!("{}", std::str::from_utf8(&help).unwrap());
@@ -894,7 +5015,11 @@ fn main() {
     };
 
     if let Err(err) = res {
-        eprintln!("[!] Command ended with error: {:?}", err);
+        if ERROR_FORMAT_JSON.load(Ordering::Relaxed) {
+            eprintln!("{}", json!({ "error": error_json(&err) }));
+        } else {
+            eprintln!("[!] Command ended with error: {}", err);
+        }
         std::process::exit(1);
     }
 }
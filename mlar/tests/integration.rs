@@ -1,10 +1,10 @@
 use assert_cmd::Command;
 use assert_fs::fixture::{FileWriteBin, NamedTempFile, TempDir};
-use glob;
 use permutate::Permutator;
 use rand::distributions::{Alphanumeric, Distribution, Standard};
 use rand::rngs::StdRng;
 use rand::SeedableRng;
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
 use std::fs::{metadata, File};
 use std::io::{Read, Write};
@@ -184,7 +184,2937 @@ fn test_create_list_tar() {
     assert.success();
 
     // Inspect the created TAR file
-    ensure_tar_content(&tar_file.path(), &testfs.files);
+    ensure_tar_content(tar_file.path(), &testfs.files);
+}
+
+#[test]
+fn test_from_tar() {
+    let tar_file = NamedTempFile::new("input.tar").unwrap();
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let tar_file_out = NamedTempFile::new("output.tar").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files, and a tar with them
+    let testfs = setup();
+    let mut builder = tar::Builder::new(File::create(tar_file.path()).unwrap());
+    let mut names = Vec::new();
+    for file in &testfs.files {
+        let name = file
+            .path()
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        builder.append_path_with_name(file.path(), &name).unwrap();
+        names.push(name);
+    }
+    builder.finish().unwrap();
+    // Files are listed sorted by name, unlike `testfs.files`, sorted by path
+    names.sort();
+    let file_list: String = names.iter().map(|name| format!("{}\n", name)).collect();
+
+    // `mlar from-tar -i input.tar -o output.mla -p samples/test25519_pub.pem`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("from-tar")
+        .arg("-i")
+        .arg(tar_file.path())
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar list -i output.mla -k samples/test25519.pem`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stdout(file_list);
+
+    // `mlar to-tar -i output.mla -k samples/test25519.pem -o output.tar`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("to-tar")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(tar_file_out.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    ensure_tar_content(tar_file_out.path(), &testfs.files);
+}
+
+#[test]
+fn test_create_gcm_siv() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let tar_file = NamedTempFile::new("output.tar").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -o output.mla -p samples/test25519_pub.pem --cipher aes-gcm-siv file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("--cipher")
+        .arg("aes-gcm-siv");
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // The cipher is read from the header
+    // `mlar to-tar -i output.mla -k samples/test25519.pem -o output.tar`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("to-tar")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(tar_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // Inspect the created TAR file
+    ensure_tar_content(tar_file.path(), &testfs.files);
+}
+
+#[test]
+fn test_create_autotune() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let tar_file = NamedTempFile::new("output.tar").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create --autotune -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--autotune")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar to-tar -i output.mla -k samples/test25519.pem -o output.tar`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("to-tar")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(tar_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    ensure_tar_content(tar_file.path(), &testfs.files);
+}
+
+#[test]
+fn test_create_digest() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create --digest -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--digest")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+
+    let mut file_list = String::new();
+    for file in &testfs.files {
+        cmd.arg(file.path());
+        file_list.push_str(format!("{}\n", file.path().to_string_lossy()).as_str());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let assert = assert.success();
+
+    // The reported digest must be the one of the output file
+    let mut data = Vec::new();
+    File::open(mlar_file.path())
+        .unwrap()
+        .read_to_end(&mut data)
+        .unwrap();
+    assert.stderr(format!(
+        "{}SHA256: {}\n",
+        file_list,
+        hex::encode(Sha256::digest(&data))
+    ));
+}
+
+#[test]
+fn test_create_deterministic() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let mlar_file2 = NamedTempFile::new("output2.mla").unwrap();
+    let seed_file = NamedTempFile::new("seed").unwrap();
+    seed_file.write_binary(b"build 42 seed").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create --deterministic --seed-file seed -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`,
+    // the second time with files in the reverse order
+    for (output, reverse) in &[(&mlar_file, false), (&mlar_file2, true)] {
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("create")
+            .arg("--deterministic")
+            .arg("--seed-file")
+            .arg(seed_file.path())
+            .arg("-o")
+            .arg(output.path())
+            .arg("-p")
+            .arg(ecc_public);
+        let mut files: Vec<&NamedTempFile> = testfs.files.iter().collect();
+        if *reverse {
+            files.reverse();
+        }
+        for file in files {
+            cmd.arg(file.path());
+        }
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert.success();
+    }
+
+    assert_eq!(
+        std::fs::read(mlar_file.path()).unwrap(),
+        std::fs::read(mlar_file2.path()).unwrap()
+    );
+
+    // A seed is needed to encrypt
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--deterministic")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public)
+        .arg(testfs.files[0].path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure().code(1);
+
+    // AES-GCM does not withstand the reuse of the seed
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--deterministic")
+        .arg("--seed-file")
+        .arg(seed_file.path())
+        .arg("--cipher")
+        .arg("aes-gcm")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public)
+        .arg(testfs.files[0].path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure().code(1);
+}
+
+#[test]
+fn test_create_pad() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let output_dir = TempDir::new().unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // Padding is refused with the default layers, which include compression:
+    // `mlar create --pad 64K -o output.mla -p samples/test25519_pub.pem file1.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--pad")
+        .arg("64K")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public)
+        .arg(testfs.files[0].path());
+
+    println!("{:?}", cmd);
+    cmd.assert().failure();
+
+    // `mlar create -l encrypt --pad 64K --pad-decoy 1M -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("encrypt")
+        .arg("--pad")
+        .arg("64K")
+        .arg("--pad-decoy")
+        .arg("1M")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar extract -i output.mla -k samples/test25519.pem -o output_dir`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("extract")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(output_dir.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    ensure_directory_content(output_dir.path(), &testfs.files);
+}
+
+#[test]
+fn test_create_dedup() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let output_dir = TempDir::new().unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Two successive versions of the same image, a few bytes being inserted
+    let mut rng: StdRng = SeedableRng::from_seed([0u8; 32]);
+    let day1: Vec<u8> = Standard.sample_iter(&mut rng).take(1024 * 1024).collect();
+    let mut day2 = day1[..100_000].to_vec();
+    day2.extend_from_slice(b"inserted");
+    day2.extend_from_slice(&day1[100_000..]);
+    let day1_file = NamedTempFile::new("day1.img").unwrap();
+    day1_file.write_binary(&day1).unwrap();
+    let day2_file = NamedTempFile::new("day2.img").unwrap();
+    day2_file.write_binary(&day2).unwrap();
+
+    // `mlar create --dedup --stats -o output.mla -p samples/test25519_pub.pem day1.img day2.img`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--dedup")
+        .arg("--stats")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public)
+        .arg(day1_file.path())
+        .arg(day2_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let stderr = String::from_utf8(assert.success().get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("Entries: 2\n"));
+    assert!(stderr.contains("Stored after deduplication: "));
+    // Most of day2 is shared with day1
+    assert!(std::fs::metadata(mlar_file.path()).unwrap().len() < 3 * day1.len() as u64 / 2);
+
+    // `mlar extract -i output.mla -k samples/test25519.pem -o output_dir`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("extract")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(output_dir.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    ensure_directory_content(output_dir.path(), &[day1_file, day2_file]);
+}
+
+#[test]
+fn test_repair_dedup_refused() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let mlar_repaired_file = NamedTempFile::new("repaired.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Two files sharing their content, hence their chunks
+    let mut rng: StdRng = SeedableRng::from_seed([0u8; 32]);
+    let data: Vec<u8> = Standard.sample_iter(&mut rng).take(512 * 1024).collect();
+    let original_file = NamedTempFile::new("original.img").unwrap();
+    original_file.write_binary(&data).unwrap();
+    let copy_file = NamedTempFile::new("copy.img").unwrap();
+    copy_file.write_binary(&data).unwrap();
+
+    // `mlar create --dedup -o output.mla -p samples/test25519_pub.pem original.img copy.img`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--dedup")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public)
+        .arg(original_file.path())
+        .arg(copy_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+    // The copy only references the chunks of the original
+    assert!(std::fs::metadata(mlar_file.path()).unwrap().len() < 3 * data.len() as u64 / 2);
+
+    // `mlar repair -i output.mla -k samples/test25519.pem -p samples/test25519_pub.pem -o repaired.mla`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("repair")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("-o")
+        .arg(mlar_repaired_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let stderr = String::from_utf8(assert.failure().get_output().stderr.clone()).unwrap();
+    assert!(stderr.contains("deduplicated archives cannot be repaired"));
+}
+
+#[test]
+fn test_create_profile() {
+    let work_dir = TempDir::new().unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem")
+        .canonicalize()
+        .unwrap();
+    let ecc_private = Path::new("../samples/test25519.pem");
+    let log_file = work_dir.path().join("collected.log");
+    std::fs::write(&log_file, b"some logs").unwrap();
+    let tmp_file = work_dir.path().join("collected.tmp");
+    std::fs::write(&tmp_file, b"some temporary data").unwrap();
+
+    // Recipients are relative to the profile
+    std::fs::copy(&ecc_public, work_dir.path().join("team.pem")).unwrap();
+    let profile = work_dir.path().join("collect.toml");
+    std::fs::write(
+        &profile,
+        format!(
+            "recipients = [\"team.pem\"]\n\
+             layers = [\"encrypt\"]\n\
+             include = [\"*.log\"]\n\
+             output = \"{}/collect-{{date}}.mla\"\n\
+             \n\
+             [compression]\n\
+             algorithm = \"zstd\"\n\
+             level = 5\n\
+             \n\
+             [padding]\n\
+             granularity = \"4K\"\n",
+            work_dir.path().display()
+        ),
+    )
+    .unwrap();
+
+    // Entries of an archive, once extracted
+    let list = |archive: &Path| -> Vec<String> {
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("list")
+            .arg("-i")
+            .arg(archive)
+            .arg("-k")
+            .arg(ecc_private);
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        String::from_utf8(assert.success().get_output().stdout.clone())
+            .unwrap()
+            .lines()
+            .map(String::from)
+            .collect()
+    };
+
+    // `mlar create --profile collect.toml collected.log collected.tmp`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--profile")
+        .arg(&profile)
+        .arg(&log_file)
+        .arg(&tmp_file);
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let stderr = String::from_utf8(assert.success().get_output().stderr.clone()).unwrap();
+    let output = stderr
+        .lines()
+        .find_map(|line| line.strip_prefix("Output: "))
+        .expect("The output is reported");
+    assert!(!output.contains("{date}"));
+    assert_eq!(list(Path::new(output)), [log_file.to_str().unwrap()]);
+
+    // Arguments override the profile:
+    // `mlar create --profile collect.toml -o output.mla --include '*.tmp' collected.log collected.tmp`
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--profile")
+        .arg(&profile)
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("--include")
+        .arg("*.tmp")
+        .arg(&log_file)
+        .arg(&tmp_file);
+    println!("{:?}", cmd);
+    cmd.assert().success();
+    assert_eq!(list(mlar_file.path()), [tmp_file.to_str().unwrap()]);
+
+    // Invalid profiles are reported with the offending key
+    for (content, key) in &[
+        ("[compression]\nlevel = 30\n", "compression.level"),
+        (
+            "[compression]\nalgorithm = \"zip\"\n",
+            "compression.algorithm",
+        ),
+        ("[padding]\ngranularity = \"4Q\"\n", "padding.granularity"),
+        ("recipients = [\"missing.pem\"]\n", "recipients[0]"),
+        ("compresion = {}\n", "compresion"),
+    ] {
+        std::fs::write(&profile, content).unwrap();
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("create")
+            .arg("--profile")
+            .arg(&profile)
+            .arg("-o")
+            .arg(mlar_file.path())
+            .arg(&log_file);
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        let stderr = String::from_utf8(assert.failure().get_output().stderr.clone()).unwrap();
+        assert!(stderr.contains(key), "{:?} not in {:?}", key, stderr);
+    }
+}
+
+#[test]
+fn test_train_dict_and_create() {
+    let samples_dir = TempDir::new().unwrap();
+    let dict_file = NamedTempFile::new("dict.bin").unwrap();
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let output_dir = TempDir::new().unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Many small and similar files, as JSON logs
+    for i in 0..2000 {
+        std::fs::write(
+            samples_dir.path().join(format!("log_{}.json", i)),
+            format!(
+                "{{\"timestamp\":{},\"level\":\"INFO\",\"host\":\"server-{}\",\"message\":\"request {} handled in {} ms\"}}\n",
+                1_600_000_000 + i * 7,
+                i % 13,
+                i * 31,
+                i % 97
+            ),
+        )
+        .unwrap();
+    }
+
+    // `mlar train-dict -o dict.bin --max-size 4K samples_dir`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("train-dict")
+        .arg("-o")
+        .arg(dict_file.path())
+        .arg("--max-size")
+        .arg("4K")
+        .arg(samples_dir.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+    let dict_size = metadata(dict_file.path()).unwrap().len();
+    assert!(dict_size > 0 && dict_size <= 4096);
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create --comp-dict dict.bin -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--comp-dict")
+        .arg(dict_file.path())
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // A dictionary is only supported by Zstandard
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--comp-dict")
+        .arg(dict_file.path())
+        .arg("--compression-algo")
+        .arg("brotli")
+        .arg("-o")
+        .arg(output_dir.path().join("brotli.mla"))
+        .arg("-p")
+        .arg(ecc_public)
+        .arg(testfs.files[0].path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure();
+
+    // `mlar extract -i output.mla -k samples/test25519.pem -o output_dir`
+    let extract_dir = output_dir.path().join("extracted");
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("extract")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(&extract_dir);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    ensure_directory_content(&extract_dir, &testfs.files);
+}
+
+/// Parse the JSON progress reports printed on stderr, and return the last one
+fn last_progress_report(stderr: &[u8]) -> serde_json::Value {
+    let stderr = String::from_utf8(stderr.to_vec()).unwrap();
+    let mut reports: Vec<serde_json::Value> = stderr
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    reports.pop().expect("No progress report")
+}
+
+#[test]
+fn test_progress_json() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+    let output_dir = TempDir::new().unwrap();
+
+    // Create files
+    let testfs = setup();
+    let total: u64 = testfs
+        .files
+        .iter()
+        .map(|file| metadata(file.path()).unwrap().len())
+        .sum();
+
+    // `mlar create --progress json -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("--progress")
+        .arg("json")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let report = last_progress_report(&assert.success().get_output().stderr);
+    assert_eq!(report["done"], total);
+    assert_eq!(report["total"], total);
+    assert_eq!(report["entries_completed"], testfs.files.len() as u64);
+    assert_eq!(report["eta_seconds"], 0);
+
+    // `mlar extract --progress json -i output.mla -k samples/test25519.pem -o output_dir`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("extract")
+        .arg("--progress")
+        .arg("json")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(output_dir.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let report = last_progress_report(&assert.success().get_output().stderr);
+    let archive_size = metadata(mlar_file.path()).unwrap().len();
+    assert_eq!(report["done"], archive_size);
+    assert_eq!(report["total"], archive_size);
+    assert_eq!(report["entries_completed"], testfs.files.len() as u64);
+}
+
+#[test]
+fn test_list_hash() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+
+    let mut expected = String::new();
+    for file in &testfs.files {
+        cmd.arg(file.path());
+        let data = std::fs::read(file.path()).unwrap();
+        expected.push_str(&format!(
+            "{}  {}\n",
+            hex::encode(Sha256::digest(&data)),
+            file.path().to_string_lossy()
+        ));
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar list -i output.mla -k samples/test25519.pem --hash sha256`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("--hash")
+        .arg("sha256");
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stdout(expected);
+}
+
+#[test]
+fn test_list_json() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar list -i output.mla -k samples/test25519.pem --json`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("--json");
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let output = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let entries: Vec<serde_json::Value> = serde_json::from_str(&output).unwrap();
+    assert_eq!(entries.len(), testfs.files.len());
+    // Files are added one after the other, in order
+    let mut offset = 0;
+    for (entry, file) in entries.iter().zip(&testfs.files) {
+        let data = std::fs::read(file.path()).unwrap();
+        assert_eq!(entry["name"], file.path().to_string_lossy().as_ref());
+        assert_eq!(entry["size"], data.len() as u64);
+        assert_eq!(entry["sha256"], hex::encode(Sha256::digest(&data)));
+        assert_eq!(entry["offset"], offset);
+        offset += entry["footprint"].as_u64().unwrap();
+    }
+}
+
+#[test]
+fn test_verify_against_directory() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar extract -i output.mla -k samples/test25519.pem -o output_dir`
+    let output_dir = TempDir::new().unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("extract")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(output_dir.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar verify -i output.mla -k samples/test25519.pem --against-directory output_dir`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("verify")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("--against-directory")
+        .arg(output_dir.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stdout("");
+
+    // Remove an extracted file, and add an unexpected one
+    let extracted = glob::glob(&(output_dir.path().to_string_lossy() + "/**/*"))
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .find(|entry| entry.is_file())
+        .unwrap();
+    std::fs::remove_file(&extracted).unwrap();
+    let unexpected = output_dir.path().join("unexpected.bin");
+    File::create(&unexpected)
+        .unwrap()
+        .write_all(b"unexpected")
+        .unwrap();
+
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("verify")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("--against-directory")
+        .arg(output_dir.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let output = String::from_utf8(assert.failure().get_output().stdout.clone()).unwrap();
+    assert_eq!(output.matches("[-] Missing from directory: ").count(), 1);
+    assert!(output.contains(&format!(
+        "[+] Missing from archive: {}",
+        unexpected.display()
+    )));
+}
+
+#[test]
+fn test_sign_verify() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+    let signer_private = Path::new("../samples/test25519_2.pem");
+    let signer_public = Path::new("../samples/test25519_2_pub.pem");
+    let other_public = Path::new("../samples/test25519_3_pub.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -o output.mla -p samples/test25519_pub.pem --sign samples/test25519_2.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("--sign")
+        .arg(signer_private);
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar verify -i output.mla -k samples/test25519.pem -p samples/test25519_2_pub.pem`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("verify")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-p")
+        .arg(signer_public);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stderr("Signature verified\n");
+
+    // Another signer is not trusted
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("verify")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-p")
+        .arg(other_public);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure().code(1);
+
+    // The archive is still readable without checking the signature
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+}
+
+#[test]
+fn test_verify_integrity() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar verify -i output.mla -k samples/test25519.pem`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("verify")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let mut expected = String::new();
+    for file in &testfs.files {
+        expected.push_str(&format!("{}: ok\n", file.path().to_string_lossy()));
+    }
+    assert
+        .success()
+        .stdout(expected)
+        .stderr("Integrity verified\n");
+
+    // Corrupt the archive, in the middle of the files content
+    let mut data = std::fs::read(mlar_file.path()).unwrap();
+    let middle = data.len() / 2;
+    data[middle] ^= 1;
+    std::fs::write(mlar_file.path(), &data).unwrap();
+
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("verify")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure().code(1);
+}
+
+#[test]
+fn test_diff() {
+    let old_file = NamedTempFile::new("old.mla").unwrap();
+    let new_file = NamedTempFile::new("new.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -o old.mla -p samples/test25519_pub.pem file1.bin file2.bin`
+    // `mlar create -o new.mla -p samples/test25519_pub.pem file2.bin file3.bin`
+    for (mlar_file, files) in &[
+        (&old_file, &testfs.files[..2]),
+        (&new_file, &testfs.files[1..]),
+    ] {
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("create")
+            .arg("-o")
+            .arg(mlar_file.path())
+            .arg("-p")
+            .arg(ecc_public);
+        for file in files.iter() {
+            cmd.arg(file.path());
+        }
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert.success();
+    }
+
+    // `mlar diff -i old.mla -k samples/test25519.pem old.mla`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("diff")
+        .arg("-i")
+        .arg(old_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg(old_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stdout("");
+
+    // `mlar diff -i old.mla -k samples/test25519.pem new.mla`, with and
+    // without reading the contents
+    let mut expected = [
+        (
+            testfs.files[0].path().to_string_lossy().to_string(),
+            "[-] Removed",
+        ),
+        (
+            testfs.files[2].path().to_string_lossy().to_string(),
+            "[+] Added",
+        ),
+    ];
+    expected.sort();
+    let expected: String = expected
+        .iter()
+        .map(|(fname, change)| format!("{}: {}\n", change, fname))
+        .collect();
+    for extra_args in &[&[][..], &["--content"][..]] {
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("diff")
+            .arg("-i")
+            .arg(old_file.path())
+            .arg("-k")
+            .arg(ecc_private)
+            .arg(new_file.path())
+            .args(extra_args.iter());
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert
+            .failure()
+            .stdout(expected.clone())
+            .stderr("[!] 2 difference(s) found\n");
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_create_hardlinks() {
+    let preserved_file = NamedTempFile::new("preserved.mla").unwrap();
+    let dereferenced_file = NamedTempFile::new("dereferenced.mla").unwrap();
+
+    // Create files, with `link.bin` being a hard link to `file2.bin`
+    let testfs = setup();
+    let target = &testfs.files_archive_order[1];
+    let link_dir = TempDir::new().unwrap();
+    let link = link_dir.path().join("link.bin");
+    std::fs::hard_link(target, &link).unwrap();
+
+    for (output, option) in &[
+        (&preserved_file, "--preserve-hardlinks"),
+        (&dereferenced_file, "--hard-dereference"),
+    ] {
+        // `mlar create -l compress --preserve-hardlinks -o output.mla file2.bin link.bin`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("create")
+            .arg("-l")
+            .arg("compress")
+            .arg(option)
+            .arg("-o")
+            .arg(output.path())
+            .arg(target)
+            .arg(&link);
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert.success();
+    }
+
+    // The linked content is only stored once. As `file2.bin` is not
+    // compressible, its second copy takes about its size, give or take the
+    // few bytes the compression of the footer varies by
+    let preserved_len = metadata(preserved_file.path()).unwrap().len();
+    let dereferenced_len = metadata(dereferenced_file.path()).unwrap().len();
+    assert!(preserved_len + SIZE_FILE2 as u64 * 99 / 100 <= dereferenced_len);
+
+    // Both names are available
+    let mut expected = Vec::new();
+    File::open(target)
+        .unwrap()
+        .read_to_end(&mut expected)
+        .unwrap();
+    for fname in &[target, &link] {
+        // `mlar cat -i preserved.mla link.bin`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("cat")
+            .arg("-i")
+            .arg(preserved_file.path())
+            .arg(fname);
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert_eq!(assert.success().get_output().stdout, expected);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_preserve() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let input_dir = TempDir::new().unwrap();
+    let data = input_dir.path().join("data.bin");
+    std::fs::write(&data, b"ABCDEFGHIJ").unwrap();
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    File::open(&data).unwrap().set_modified(mtime).unwrap();
+    std::fs::set_permissions(&data, std::fs::Permissions::from_mode(0o640)).unwrap();
+    let link = input_dir.path().join("link");
+    symlink("data.bin", &link).unwrap();
+    let subdir = input_dir.path().join("subdir");
+    std::fs::create_dir(&subdir).unwrap();
+    std::fs::set_permissions(&subdir, std::fs::Permissions::from_mode(0o750)).unwrap();
+
+    // `mlar create -l compress --preserve -o output.mla data.bin link subdir`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("compress")
+        .arg("--preserve")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg(&data)
+        .arg(&link)
+        .arg(&subdir);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar extract -i output.mla --preserve -o output_dir`
+    let output_dir = TempDir::new().unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("extract")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("--preserve")
+        .arg("-o")
+        .arg(output_dir.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // Entries are extracted under their full path
+    let extracted = |path: &Path| output_dir.path().join(path.strip_prefix("/").unwrap());
+    let data_metadata = metadata(extracted(&data)).unwrap();
+    assert_eq!(data_metadata.permissions().mode() & 0o7777, 0o640);
+    assert_eq!(data_metadata.modified().unwrap(), mtime);
+    assert_eq!(
+        std::fs::read_link(extracted(&link)).unwrap(),
+        Path::new("data.bin")
+    );
+    assert_eq!(std::fs::read(extracted(&link)).unwrap(), b"ABCDEFGHIJ");
+    let subdir_metadata = metadata(extracted(&subdir)).unwrap();
+    assert!(subdir_metadata.is_dir());
+    assert_eq!(subdir_metadata.permissions().mode() & 0o7777, 0o750);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_extract_symlink_escape() {
+    use mla::config::ArchiveWriterConfig;
+    use mla::{ArchiveWriter, EntryType, FileMetadata, Layers};
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    // Directory outside of the output one, standing for `/etc`
+    let outside = TempDir::new().unwrap();
+    let passwd = outside.path().join("passwd");
+    std::fs::write(&passwd, b"root:x:0:0").unwrap();
+    std::fs::set_permissions(&passwd, std::fs::Permissions::from_mode(0o600)).unwrap();
+    let mtime = UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+    File::open(&passwd).unwrap().set_modified(mtime).unwrap();
+
+    // Crafted archive: a symbolic link `a` to the outside directory, then
+    // entries below `a`
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let mut config = ArchiveWriterConfig::new();
+    config.set_layers(Layers::EMPTY);
+    let mut mla =
+        ArchiveWriter::from_config(File::create(mlar_file.path()).unwrap(), config).unwrap();
+    let entry = |entry_type, mode| FileMetadata {
+        entry_type,
+        mode,
+        mtime: Some(0),
+        ..Default::default()
+    };
+    let target = outside.path().to_str().unwrap().to_string();
+    mla.add_file_with_metadata(
+        "a",
+        0,
+        std::io::empty(),
+        entry(EntryType::Symlink(target), None),
+    )
+    .unwrap();
+    mla.add_file_with_metadata(
+        "a/passwd",
+        0,
+        std::io::empty(),
+        entry(EntryType::Directory, Some(0o777)),
+    )
+    .unwrap();
+    mla.add_file_with_metadata(
+        "a/b/c",
+        0,
+        std::io::empty(),
+        entry(EntryType::Directory, None),
+    )
+    .unwrap();
+    mla.finalize().unwrap();
+
+    // `mlar extract -i output.mla --preserve -o output_dir`
+    let output_dir = TempDir::new().unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("extract")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("--preserve")
+        .arg("-o")
+        .arg(output_dir.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // The link is extracted, but nothing is changed or created through it
+    assert!(std::fs::symlink_metadata(output_dir.path().join("a"))
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    let passwd_metadata = metadata(&passwd).unwrap();
+    assert!(passwd_metadata.is_file());
+    assert_eq!(passwd_metadata.permissions().mode() & 0o7777, 0o600);
+    assert_eq!(passwd_metadata.modified().unwrap(), mtime);
+    assert!(!outside.path().join("b").exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let replacement = NamedTempFile::new("replacement.bin").unwrap();
+    replacement.write_binary(b"KLMNOPQRST").unwrap();
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -l compress -o output.mla file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("compress")
+        .arg("-o")
+        .arg(mlar_file.path());
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `EDITOR="cp replacement.bin" mlar edit -i output.mla -l compress file3.bin`
+    // `cp` stands for the user's modifications
+    let edited = &testfs.files_archive_order[2];
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.env("EDITOR", format!("cp {}", replacement.path().display()))
+        .arg("edit")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-l")
+        .arg("compress")
+        .arg(edited);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // Only the edited file has been modified
+    for file in &testfs.files {
+        let mut expected = Vec::new();
+        if file.path() == edited {
+            expected.extend_from_slice(b"KLMNOPQRST");
+        } else {
+            File::open(file.path())
+                .unwrap()
+                .read_to_end(&mut expected)
+                .unwrap();
+        }
+
+        // `mlar cat -i output.mla file.bin`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("cat")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .arg(file.path());
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert_eq!(assert.success().get_output().stdout, expected);
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn test_edit_preserve() {
+    use std::os::unix::fs::{symlink, PermissionsExt};
+
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let replacement = NamedTempFile::new("replacement.bin").unwrap();
+    replacement.write_binary(b"KLMNOPQRST").unwrap();
+    let input_dir = TempDir::new().unwrap();
+    let data = input_dir.path().join("data.bin");
+    std::fs::write(&data, b"ABCDEFGHIJ").unwrap();
+    std::fs::set_permissions(&data, std::fs::Permissions::from_mode(0o640)).unwrap();
+    let hardlink = input_dir.path().join("hardlink.bin");
+    std::fs::hard_link(&data, &hardlink).unwrap();
+    let link = input_dir.path().join("link");
+    symlink("data.bin", &link).unwrap();
+    let subdir = input_dir.path().join("subdir");
+    std::fs::create_dir(&subdir).unwrap();
+
+    // `mlar create -l compress --preserve --preserve-hardlinks -o output.mla data.bin hardlink.bin link subdir`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("compress")
+        .arg("--preserve")
+        .arg("--preserve-hardlinks")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg(&data)
+        .arg(&hardlink)
+        .arg(&link)
+        .arg(&subdir);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `EDITOR="cp 'replacement.bin'" mlar edit -i output.mla -l compress data.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.env("EDITOR", format!("cp '{}'", replacement.path().display()))
+        .arg("edit")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-l")
+        .arg("compress")
+        .arg(&data);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar extract -i output.mla --preserve -o output_dir`
+    let output_dir = TempDir::new().unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("extract")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("--preserve")
+        .arg("-o")
+        .arg(output_dir.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // The other entries, and the metadata of the edited one, are kept. Its
+    // hard link shares the new content
+    let extracted = |path: &Path| output_dir.path().join(path.strip_prefix("/").unwrap());
+    assert_eq!(std::fs::read(extracted(&data)).unwrap(), b"KLMNOPQRST");
+    assert_eq!(
+        metadata(extracted(&data)).unwrap().permissions().mode() & 0o7777,
+        0o640
+    );
+    assert_eq!(std::fs::read(extracted(&hardlink)).unwrap(), b"KLMNOPQRST");
+    assert_eq!(
+        std::fs::read_link(extracted(&link)).unwrap(),
+        Path::new("data.bin")
+    );
+    assert!(metadata(extracted(&subdir)).unwrap().is_dir());
+
+    // `mlar list --json -i output.mla`: the hard link is still one
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("--json")
+        .arg("-i")
+        .arg(mlar_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let listing: serde_json::Value =
+        serde_json::from_slice(&assert.success().get_output().stdout).unwrap();
+    let offset_of = |path: &Path| {
+        listing
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|entry| entry["name"] == *path.to_string_lossy())
+            .unwrap()["offset"]
+            .clone()
+    };
+    assert_eq!(offset_of(&data), offset_of(&hardlink));
+}
+
+#[cfg(unix)]
+/// Minimal HTTP/1.0 client, returning the status code, headers and body
+fn http_get(addr: &str, path: &str, headers: &str) -> (u16, String, Vec<u8>) {
+    let mut stream = std::net::TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {} HTTP/1.0\r\n{}\r\n", path, headers).unwrap();
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response).unwrap();
+    let split = response
+        .windows(4)
+        .position(|window| window == b"\r\n\r\n")
+        .unwrap();
+    let head = String::from_utf8(response[..split].to_vec()).unwrap();
+    let code = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (code, head, response[split + 4..].to_vec())
+}
+
+// File names are used as URLs as is
+#[cfg(unix)]
+#[test]
+fn test_serve() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -l compress -o output.mla file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("compress")
+        .arg("-o")
+        .arg(mlar_file.path());
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // Find a free port
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .to_string();
+
+    // `mlar serve -i output.mla --listen 127.0.0.1:port`
+    let mut server = std::process::Command::new(assert_cmd::cargo::cargo_bin(UTIL))
+        .arg("serve")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("--listen")
+        .arg(&addr)
+        .spawn()
+        .unwrap();
+    // Wait for the server to be ready
+    let mut ready = false;
+    for _ in 0..100 {
+        if std::net::TcpStream::connect(&addr).is_ok() {
+            ready = true;
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    assert!(ready);
+
+    // Listing, sorted by name, with the sizes of the files
+    let (code, _, body) = http_get(&addr, "/files", "");
+    assert_eq!(code, 200);
+    let listing: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let expected: Vec<serde_json::Value> = testfs
+        .files
+        .iter()
+        .map(|file| {
+            serde_json::json!({
+                "name": file.path().to_string_lossy(),
+                "size": metadata(file.path()).unwrap().len(),
+            })
+        })
+        .collect();
+    assert_eq!(listing, serde_json::Value::Array(expected));
+
+    // Whole file, and range
+    let file3 = &testfs.files_archive_order[2];
+    let url = format!(
+        "/files/{}",
+        file3
+            .to_string_lossy()
+            .replace('%', "%25")
+            .replace(' ', "%20")
+    );
+    let (code, _, body) = http_get(&addr, &url, "");
+    assert_eq!(code, 200);
+    assert_eq!(body, b"ABCDEFGHIJ");
+    let (code, head, body) = http_get(&addr, &url, "Range: bytes=2-4\r\n");
+    assert_eq!(code, 206);
+    assert!(head.contains("bytes 2-4/10"));
+    assert_eq!(body, b"CDE");
+    let (code, _, body) = http_get(&addr, &url, "Range: bytes=-3\r\n");
+    assert_eq!(code, 206);
+    assert_eq!(body, b"HIJ");
+    let (code, _, _) = http_get(&addr, &url, "Range: bytes=20-\r\n");
+    assert_eq!(code, 416);
+
+    // Unknown file
+    let (code, _, _) = http_get(&addr, "/files/unknown", "");
+    assert_eq!(code, 404);
+
+    server.kill().unwrap();
+    server.wait().unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon() {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+    let tmp_dir = TempDir::new().unwrap();
+    let socket = tmp_dir.path().join("mlar.sock");
+    let archive = tmp_dir.path().join("output.mla");
+    let extracted = tmp_dir.path().join("extracted.bin");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar daemon -k samples/test25519.pem -p samples/test25519_pub.pem --socket mlar.sock`
+    let mut daemon = std::process::Command::new(assert_cmd::cargo::cargo_bin(UTIL))
+        .arg("daemon")
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("--socket")
+        .arg(&socket)
+        .spawn()
+        .unwrap();
+    // Wait for the daemon to be ready
+    let mut stream = None;
+    for _ in 0..100 {
+        if let Ok(s) = UnixStream::connect(&socket) {
+            stream = Some(s);
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    let mut stream = stream.unwrap();
+    // Only the current user may connect
+    assert_eq!(
+        std::fs::metadata(&socket).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+    let mut responses = BufReader::new(stream.try_clone().unwrap()).lines();
+    let mut request = |request: String| -> String {
+        writeln!(stream, "{}", request).unwrap();
+        responses.next().unwrap().unwrap()
+    };
+
+    let archive = archive.to_string_lossy();
+    let ok = "{\"ok\":true}";
+    assert_eq!(
+        request(format!(
+            "{{\"op\": \"create\", \"archive\": \"{}\"}}",
+            archive
+        )),
+        ok
+    );
+    for file in &testfs.files {
+        let path = file.path().to_string_lossy();
+        assert_eq!(
+            request(format!(
+                "{{\"op\": \"add\", \"archive\": \"{}\", \"file\": \"{}\", \"path\": \"{}\"}}",
+                archive, path, path
+            )),
+            ok
+        );
+    }
+    assert_eq!(
+        request(format!(
+            "{{\"op\": \"finalize\", \"archive\": \"{}\"}}",
+            archive
+        )),
+        ok
+    );
+
+    // Read it back, with the unlocked key
+    let listing = request(format!(
+        "{{\"op\": \"list\", \"archive\": \"{}\"}}",
+        archive
+    ));
+    for file in &testfs.files {
+        assert!(listing.contains(&*file.path().to_string_lossy()));
+    }
+    let file3 = testfs.files_archive_order[2].to_string_lossy();
+    assert_eq!(
+        request(format!(
+            "{{\"op\": \"extract\", \"archive\": \"{}\", \"file\": \"{}\", \"output\": \"{}\"}}",
+            archive,
+            file3,
+            extracted.to_string_lossy()
+        )),
+        ok
+    );
+    let mut content = Vec::new();
+    File::open(&extracted)
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap();
+    assert_eq!(content, b"ABCDEFGHIJ");
+
+    // Errors are reported, with their code
+    let error: serde_json::Value = serde_json::from_str(&request(
+        "{\"op\": \"unknown\", \"archive\": \"\"}".to_string(),
+    ))
+    .unwrap();
+    assert_eq!(error["ok"], false);
+    assert_eq!(error["code"], "bad_api_argument");
+    let error: serde_json::Value = serde_json::from_str(&request(format!(
+        "{{\"op\": \"list\", \"archive\": \"{}\"}}",
+        extracted.to_string_lossy()
+    )))
+    .unwrap();
+    assert_eq!(error["code"], "wrong_magic");
+
+    assert_eq!(request("{\"op\": \"shutdown\"}".to_string()), ok);
+    assert!(daemon.wait().unwrap().success());
+    assert!(!socket.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_daemon_socket_path() {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    let ecc_private = Path::new("../samples/test25519.pem");
+    let tmp_dir = TempDir::new().unwrap();
+    let socket = tmp_dir.path().join("mlar.sock");
+
+    // An existing file is never replaced
+    std::fs::write(&socket, b"precious").unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("daemon")
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("--socket")
+        .arg(&socket);
+    cmd.assert().failure();
+    assert_eq!(std::fs::read(&socket).unwrap(), b"precious");
+    std::fs::remove_file(&socket).unwrap();
+
+    // Nor is the socket of a running daemon
+    let listener = UnixListener::bind(&socket).unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("daemon")
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("--socket")
+        .arg(&socket);
+    cmd.assert().failure();
+    assert!(UnixStream::connect(&socket).is_ok());
+
+    // The socket of a stopped daemon is replaced
+    drop(listener);
+    let mut daemon = std::process::Command::new(assert_cmd::cargo::cargo_bin(UTIL))
+        .arg("daemon")
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("--socket")
+        .arg(&socket)
+        .spawn()
+        .unwrap();
+    let mut stream = None;
+    for _ in 0..100 {
+        if let Ok(s) = UnixStream::connect(&socket) {
+            stream = Some(s);
+            break;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(100));
+    }
+    writeln!(stream.unwrap(), "{{\"op\": \"shutdown\"}}").unwrap();
+    assert!(daemon.wait().unwrap().success());
+    assert!(!socket.exists());
+}
+
+#[test]
+fn test_index() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let db_file = NamedTempFile::new("catalog.db").unwrap();
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -l compress -o output.mla file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("compress")
+        .arg("-o")
+        .arg(mlar_file.path());
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // Index twice, entries must not be duplicated
+    for _ in 0..2 {
+        // `mlar index -i output.mla -o catalog.db`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("index")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .arg("-o")
+            .arg(db_file.path());
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert.success();
+    }
+
+    let db = rusqlite::Connection::open(db_file.path()).unwrap();
+    let count: i64 = db
+        .query_row("SELECT COUNT(*) FROM files", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count as usize, testfs.files.len());
+    for file in &testfs.files {
+        let mut content = Vec::new();
+        File::open(file.path())
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        let (size, sha256): (i64, String) = db
+            .query_row(
+                "SELECT size, sha256 FROM files WHERE name = ?1",
+                rusqlite::params![file.path().to_string_lossy()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!(size as usize, content.len());
+        assert_eq!(sha256, hex::encode(Sha256::digest(&content)));
+    }
+    // Without --preserve, no metadata is recorded
+    let without_metadata: i64 = db
+        .query_row(
+            "SELECT COUNT(*) FROM files WHERE entry_type IS NULL AND mode IS NULL AND mtime IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(without_metadata as usize, testfs.files.len());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_index_metadata() {
+    use std::os::unix::fs::{symlink, MetadataExt};
+
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let db_file = NamedTempFile::new("catalog.db").unwrap();
+    let input_dir = TempDir::new().unwrap();
+    let data = input_dir.path().join("data.bin");
+    std::fs::write(&data, b"ABCD").unwrap();
+    let link = input_dir.path().join("link");
+    symlink("data.bin", &link).unwrap();
+
+    // Database created by a previous version, without metadata columns
+    rusqlite::Connection::open(db_file.path())
+        .unwrap()
+        .execute_batch(
+            "CREATE TABLE files (
+                archive_id INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                sha256 TEXT NOT NULL,
+                PRIMARY KEY (archive_id, name)
+            );",
+        )
+        .unwrap();
+
+    // `mlar create -l compress --preserve -o output.mla data.bin link`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("compress")
+        .arg("--preserve")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg(&data)
+        .arg(&link);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar index -i output.mla -o catalog.db`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("index")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-o")
+        .arg(db_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    let db = rusqlite::Connection::open(db_file.path()).unwrap();
+    let row = |path: &Path| {
+        db.query_row(
+            "SELECT entry_type, link_target, mode, uid, mtime FROM files WHERE name = ?1",
+            rusqlite::params![path.to_string_lossy()],
+            |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<u32>>(2)?,
+                    row.get::<_, Option<u32>>(3)?,
+                    row.get::<_, Option<i64>>(4)?,
+                ))
+            },
+        )
+        .unwrap()
+    };
+    let data_metadata = metadata(&data).unwrap();
+    assert_eq!(
+        row(&data),
+        (
+            "file".to_string(),
+            None,
+            Some(data_metadata.mode() & 0o7777),
+            Some(data_metadata.uid()),
+            Some(data_metadata.mtime())
+        )
+    );
+    let (entry_type, link_target, _, _, _) = row(&link);
+    assert_eq!(entry_type, "symlink");
+    assert_eq!(link_target.as_deref(), Some("data.bin"));
+}
+
+#[test]
+fn test_grep() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -l compress -o output.mla file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("compress")
+        .arg("-o")
+        .arg(mlar_file.path());
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // file3 is "ABCDEFGHIJ". Patterns are long enough not to be found in the
+    // random content of other files
+    let file3 = testfs.files_archive_order[2].to_string_lossy();
+    for (options, pattern, expected) in &[
+        (vec!["-F"], "ABCDEFGHIJ", format!("{}:0\n", file3)),
+        (vec!["--hex"], "4445464748494a", format!("{}:3\n", file3)),
+        (vec!["-j", "1"], "B[C-D]DEFGHI", format!("{}:1\n", file3)),
+    ] {
+        // `mlar grep -i output.mla [options] pattern`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("grep")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .args(options)
+            .arg(pattern);
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert.success().stdout(expected.clone());
+    }
+
+    // No match
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("grep")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-F")
+        .arg("ABCDEFGHIJABCDEFGHIJ");
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure().stdout("");
+}
+
+#[test]
+fn test_find() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -l compress -o output.mla file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("compress")
+        .arg("-o")
+        .arg(mlar_file.path());
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    let names: Vec<String> = testfs
+        .files_archive_order
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    // file1 and file2 are 10MiB large, file3 is 10 bytes
+    for (options, expected) in &[
+        (vec![], vec![0, 1, 2]),
+        (vec!["--larger-than", "1M"], vec![0, 1]),
+        (vec!["--smaller-than", "10M"], vec![2]),
+        (vec!["--name", "*file2*", "--name", "*file3*"], vec![1, 2]),
+        (vec!["--name", "*file3*", "--larger-than", "10"], vec![]),
+    ] {
+        // `mlar find -i output.mla [options]`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("find")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .args(options);
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        // Names are output sorted
+        let mut expected_names: Vec<&String> = expected.iter().map(|i| &names[*i]).collect();
+        expected_names.sort();
+        let mut expected_output = String::new();
+        for name in expected_names {
+            expected_output.push_str(&format!("{}\n", name));
+        }
+        assert.success().stdout(expected_output);
+    }
+
+    // `mlar find -i output.mla --json --smaller-than 1K`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("find")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("--json")
+        .arg("--smaller-than")
+        .arg("1K");
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let output = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    let entries: serde_json::Value = serde_json::from_str(&output).unwrap();
+    assert_eq!(entries, serde_json::json!([{"name": names[2], "size": 10}]));
+}
+
+#[test]
+fn test_find_dates() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let input_dir = TempDir::new().unwrap();
+    // Modified on 2023-06-01 and 2024-06-01
+    let mut files = Vec::new();
+    for (name, mtime) in &[("old.bin", 1_685_577_600), ("new.bin", 1_717_200_000)] {
+        let path = input_dir.path().join(name);
+        std::fs::write(&path, b"ABCD").unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(*mtime))
+            .unwrap();
+        files.push(path);
+    }
+
+    // `mlar create -l compress --preserve -o output.mla old.bin new.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("compress")
+        .arg("--preserve")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .args(&files);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    let names: Vec<String> = files
+        .iter()
+        .map(|path| path.to_string_lossy().to_string())
+        .collect();
+    for (options, expected) in &[
+        (vec!["--newer-than", "2024-01-01"], vec![1]),
+        (vec!["--older-than", "2024-01-01"], vec![0]),
+        (
+            vec![
+                "--newer-than",
+                "2023-01-01",
+                "--older-than",
+                "2024-06-01T00:00:01",
+            ],
+            vec![0, 1],
+        ),
+        (vec!["--newer-than", "2024-06-01"], vec![]),
+        (
+            vec!["--newer-than", "2023-01-01", "--larger-than", "4"],
+            vec![],
+        ),
+    ] {
+        // `mlar find -i output.mla [options]`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("find")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .args(options);
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        let mut expected_names: Vec<&String> = expected.iter().map(|i| &names[*i]).collect();
+        expected_names.sort();
+        let mut expected_output = String::new();
+        for name in expected_names {
+            expected_output.push_str(&format!("{}\n", name));
+        }
+        assert.success().stdout(expected_output);
+    }
+
+    // `mlar find -i output.mla --newer-than 2024-13-01`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("find")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("--newer-than")
+        .arg("2024-13-01");
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_watch() {
+    use std::ffi::OsStr;
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Stdio};
+
+    let tmp_dir = TempDir::new().unwrap();
+    let logs = tmp_dir.path().join("logs");
+    std::fs::create_dir(&logs).unwrap();
+    let app_log = logs.join("app.log");
+    std::fs::write(&app_log, "first line\n").unwrap();
+    let append = |path: &Path, data: &str| {
+        std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap()
+            .write_all(data.as_bytes())
+            .unwrap();
+    };
+    // Names in the archive are based on the canonical path of the directory
+    let logs_name = std::fs::canonicalize(&logs).unwrap();
+    let app_log_name = logs_name.join("app.log");
+    let new_log_name = logs_name.join("new.log");
+
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // `mlar watch -l compress --checkpoint 1 -o output.mla logs`
+    let start_watch_with = |archive: &Path, options: &[&OsStr]| -> Child {
+        let mut child = std::process::Command::new(assert_cmd::cargo::cargo_bin(UTIL))
+            .arg("watch")
+            .args(options)
+            .arg("--checkpoint")
+            .arg("1")
+            .arg("-o")
+            .arg(archive)
+            .arg(&logs)
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+        // Wait for the initial scan to be done, then keep draining stderr
+        let mut lines = BufReader::new(child.stderr.take().unwrap()).lines();
+        for line in &mut lines {
+            if line.unwrap().starts_with("Watching") {
+                break;
+            }
+        }
+        std::thread::spawn(move || lines.for_each(drop));
+        child
+    };
+    let start_watch =
+        |archive: &Path| start_watch_with(archive, &[OsStr::new("-l"), OsStr::new("compress")]);
+    let cat_with = |archive: &Path, options: &[&OsStr], name: &Path, expected: &str| {
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("cat")
+            .arg("-i")
+            .arg(archive)
+            .args(options)
+            .arg(name);
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert.success().stdout(expected.to_string());
+    };
+    let cat = |archive: &Path, name: &Path, expected: &str| cat_with(archive, &[], name, expected);
+
+    // Killed watcher: data archived before the last checkpoint is recovered
+    let archive = tmp_dir.path().join("killed.mla");
+    let mut watcher = start_watch(&archive);
+    append(&app_log, "second line\n");
+    append(&logs.join("new.log"), "new\n");
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    watcher.kill().unwrap();
+    watcher.wait().unwrap();
+
+    // `mlar repair -i killed.mla -o repaired.mla`
+    let repaired = tmp_dir.path().join("repaired.mla");
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("repair")
+        .arg("-i")
+        .arg(&archive)
+        .arg("-l")
+        .arg("compress")
+        .arg("-o")
+        .arg(&repaired);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+    cat(&repaired, &app_log_name, "first line\nsecond line\n");
+    cat(&repaired, &new_log_name, "new\n");
+
+    // Same with the default layers and compression threads: checkpoints also
+    // write the blocks being compressed, and the last encrypted chunk
+    let archive = tmp_dir.path().join("killed_threads.mla");
+    let threads_options = [
+        OsStr::new("-p"),
+        ecc_public.as_os_str(),
+        OsStr::new("--threads"),
+        OsStr::new("4"),
+    ];
+    let mut watcher = start_watch_with(&archive, &threads_options);
+    append(&logs.join("threads.log"), "threads\n");
+    std::thread::sleep(std::time::Duration::from_secs(3));
+    watcher.kill().unwrap();
+    watcher.wait().unwrap();
+
+    // `mlar repair -i killed_threads.mla -k samples/test25519.pem -p samples/test25519_pub.pem -o repaired_threads.mla`
+    let repaired = tmp_dir.path().join("repaired_threads.mla");
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("repair")
+        .arg("-i")
+        .arg(&archive)
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("-o")
+        .arg(&repaired);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+    let key_options = [OsStr::new("-k"), ecc_private.as_os_str()];
+    cat_with(
+        &repaired,
+        &key_options,
+        &app_log_name,
+        "first line\nsecond line\n",
+    );
+    cat_with(
+        &repaired,
+        &key_options,
+        &logs_name.join("threads.log"),
+        "threads\n",
+    );
+
+    // AES-GCM-SIV only writes complete chunks, which checkpoints cannot wait
+    // for
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("watch")
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("--cipher")
+        .arg("aes-gcm-siv")
+        .arg("-o")
+        .arg(tmp_dir.path().join("siv.mla"))
+        .arg(&logs);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure().code(1);
+
+    // Interrupted watcher: the archive is finalized
+    let archive = tmp_dir.path().join("stopped.mla");
+    let mut watcher = start_watch(&archive);
+    append(&app_log, "third line\n");
+    std::thread::sleep(std::time::Duration::from_secs(1));
+    let status = std::process::Command::new("kill")
+        .arg("-TERM")
+        .arg(watcher.id().to_string())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert!(watcher.wait().unwrap().success());
+    cat(
+        &archive,
+        &app_log_name,
+        "first line\nsecond line\nthird line\n",
+    );
+}
+
+#[test]
+fn test_encrypted_private_key() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let password_file = NamedTempFile::new("password.txt").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    // Encrypted with the password "mla-password"
+    let ecc_private = Path::new("../samples/test25519_encrypted.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    for (password, success) in &[("mla-password\n", true), ("wrong password\n", false)] {
+        password_file.write_binary(password.as_bytes()).unwrap();
+
+        // `mlar list -i output.mla -k samples/test25519_encrypted.pem --password-file password.txt`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("list")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .arg("-k")
+            .arg(ecc_private)
+            .arg("--password-file")
+            .arg(password_file.path());
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        if *success {
+            let mut file_list = String::new();
+            for file in &testfs.files {
+                file_list.push_str(format!("{}\n", file.path().to_string_lossy()).as_str());
+            }
+            assert.success().stdout(file_list);
+        } else {
+            assert.failure();
+        }
+    }
+}
+
+#[test]
+fn test_archive_password() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let password_file = NamedTempFile::new("password.txt").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files
+    let testfs = setup();
+    let mut file_list = String::new();
+    for file in &testfs.files {
+        file_list.push_str(format!("{}\n", file.path().to_string_lossy()).as_str());
+    }
+
+    // `mlar create -o output.mla -p samples/test25519_pub.pem --password --password-file password.txt file1.bin file2.bin file3.bin`
+    password_file.write_binary(b"archive password\n").unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("--password")
+        .arg("--password-file")
+        .arg(password_file.path());
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // The private key still opens the archive
+    // `mlar list -i output.mla -k samples/test25519.pem`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stdout(file_list.clone());
+
+    for (password, success) in &[("archive password\n", true), ("wrong password\n", false)] {
+        password_file.write_binary(password.as_bytes()).unwrap();
+
+        // `mlar list -i output.mla --password-file password.txt`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("list")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .arg("--password-file")
+            .arg(password_file.path());
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        if *success {
+            assert.success().stdout(file_list.clone());
+        } else {
+            assert.failure();
+        }
+    }
+}
+
+#[test]
+fn test_openssh_keys() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    // Keys of test25519.pem and test25519_2.pem
+    let authorized_keys = Path::new("../samples/test25519_authorized_keys");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -o output.mla -p samples/test25519_authorized_keys file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(authorized_keys);
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    let mut file_list = String::new();
+    for file in &testfs.files {
+        file_list.push_str(format!("{}\n", file.path().to_string_lossy()).as_str());
+    }
+    // Both recipients can read the archive
+    for ecc_private in &[
+        Path::new("../samples/test25519_openssh"),
+        Path::new("../samples/test25519_2.pem"),
+    ] {
+        // `mlar list -i output.mla -k samples/test25519_openssh`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("list")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .arg("-k")
+            .arg(ecc_private);
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert.success().stdout(file_list.clone());
+    }
+}
+
+#[test]
+fn test_append() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files
+    let testfs = setup();
+    let (first_files, appended_files) = testfs.files.split_at(2);
+
+    // `mlar create -o output.mla -p samples/test25519_pub.pem --cipher aes-gcm-siv file1.bin file2.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("--cipher")
+        .arg("aes-gcm-siv");
+    for file in first_files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar append -i output.mla -k samples/test25519.pem --digest file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("append")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("--digest");
+    for file in appended_files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let stderr = String::from_utf8(assert.success().get_output().stderr.clone()).unwrap();
+    // The digest covers the whole archive
+    let data = std::fs::read(mlar_file.path()).unwrap();
+    assert!(stderr.ends_with(&format!("SHA256: {}\n", hex::encode(Sha256::digest(&data)))));
+
+    // `mlar list -i output.mla -k samples/test25519.pem`
+    let mut file_list = String::new();
+    for file in &testfs.files {
+        file_list.push_str(format!("{}\n", file.path().to_string_lossy()).as_str());
+    }
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stdout(file_list);
+
+    // Old and new files are intact
+    for file in &testfs.files {
+        // `mlar cat -i output.mla -k samples/test25519.pem file.bin`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("cat")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .arg("-k")
+            .arg(ecc_private)
+            .arg(file.path());
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert_eq!(
+            assert.success().get_output().stdout,
+            std::fs::read(file.path()).unwrap()
+        );
+    }
+
+    // AES-GCM archives are refused, and left untouched
+    // `mlar create -o output.mla -p samples/test25519_pub.pem file1.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public)
+        .arg(first_files[0].path());
+    cmd.assert().success();
+    let archive = std::fs::read(mlar_file.path()).unwrap();
+
+    // `mlar append -i output.mla -k samples/test25519.pem file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("append")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg(appended_files[0].path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure();
+    assert_eq!(std::fs::read(mlar_file.path()).unwrap(), archive);
+
+    // Padding is refused on compressed archives, which are left untouched
+    // `mlar create -o output.mla -p samples/test25519_pub.pem --cipher aes-gcm-siv file1.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("--cipher")
+        .arg("aes-gcm-siv")
+        .arg(first_files[0].path());
+    cmd.assert().success();
+    let archive = std::fs::read(mlar_file.path()).unwrap();
+
+    // `mlar append -i output.mla -k samples/test25519.pem --pad 4K file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("append")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("--pad")
+        .arg("4K")
+        .arg(appended_files[0].path());
+
+    println!("{:?}", cmd);
+    cmd.assert().failure().code(1);
+    assert_eq!(std::fs::read(mlar_file.path()).unwrap(), archive);
+}
+
+#[test]
+fn test_rekey() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let mlar_rekeyed = NamedTempFile::new("rekeyed.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+    let ecc_public2 = Path::new("../samples/test25519_2_pub.pem");
+    let ecc_private2 = Path::new("../samples/test25519_2.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -o output.mla -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-p")
+        .arg(ecc_public);
+    let mut file_list = String::new();
+    for file in &testfs.files {
+        cmd.arg(file.path());
+        file_list.push_str(format!("{}\n", file.path().to_string_lossy()).as_str());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar rekey -i output.mla -k samples/test25519.pem -o rekeyed.mla -p samples/test25519_2_pub.pem`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("rekey")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(mlar_rekeyed.path())
+        .arg("-p")
+        .arg(ecc_public2);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // Only the header has been rewritten
+    assert_eq!(
+        std::fs::metadata(mlar_file.path()).unwrap().len(),
+        std::fs::metadata(mlar_rekeyed.path()).unwrap().len()
+    );
+
+    // `mlar list -i rekeyed.mla -k samples/test25519_2.pem`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(mlar_rekeyed.path())
+        .arg("-k")
+        .arg(ecc_private2);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stdout(file_list.clone());
+
+    // The former recipient has been removed
+    // `mlar list -i rekeyed.mla -k samples/test25519.pem`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(mlar_rekeyed.path())
+        .arg("-k")
+        .arg(ecc_private);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure();
+
+    // `mlar rekey -i rekeyed.mla -k samples/test25519_2.pem -o output.mla --current-pub samples/test25519_2_pub.pem --add-pub samples/test25519_pub.pem`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("rekey")
+        .arg("-i")
+        .arg(mlar_rekeyed.path())
+        .arg("-k")
+        .arg(ecc_private2)
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("--current-pub")
+        .arg(ecc_public2)
+        .arg("--add-pub")
+        .arg(ecc_public);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // Both recipients can read the archive
+    for private_key in &[ecc_private, ecc_private2] {
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("list")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .arg("-k")
+            .arg(private_key);
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert.success().stdout(file_list.clone());
+    }
+
+    // Every current recipient must be given
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("rekey")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(mlar_rekeyed.path())
+        .arg("--current-pub")
+        .arg(ecc_public)
+        .arg("--remove-pub")
+        .arg(ecc_public2);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure().code(1);
+
+    // `mlar rekey -i output.mla -k samples/test25519.pem -o rekeyed.mla --current-pub samples/test25519_pub.pem --current-pub samples/test25519_2_pub.pem --remove-pub samples/test25519_2_pub.pem`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("rekey")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(mlar_rekeyed.path())
+        .arg("--current-pub")
+        .arg(ecc_public)
+        .arg("--current-pub")
+        .arg(ecc_public2)
+        .arg("--remove-pub")
+        .arg(ecc_public2);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(mlar_rekeyed.path())
+        .arg("-k")
+        .arg(ecc_private2);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure();
+}
+
+#[test]
+fn test_volumes() {
+    let output_dir = TempDir::new().unwrap();
+    let mlar_file = output_dir.path().join("output.mla");
+    let first_volume = output_dir.path().join("output.mla.001");
+    let tar_file = NamedTempFile::new("output.tar").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -o output.mla --volume-size 4M -p samples/test25519_pub.pem file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(&mlar_file)
+        .arg("--volume-size")
+        .arg("4M")
+        .arg("-p")
+        .arg(ecc_public);
+
+    let mut file_list = String::new();
+    for file in &testfs.files {
+        cmd.arg(file.path());
+        file_list.push_str(format!("{}\n", file.path().to_string_lossy()).as_str());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // Volumes are at most 4M each
+    let volumes: Vec<PathBuf> = glob::glob(&(mlar_file.to_string_lossy() + ".*"))
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .collect();
+    assert!(volumes.len() > 1);
+    for volume in &volumes {
+        assert!(metadata(volume).unwrap().len() <= 4 * 1024 * 1024);
+    }
+
+    // `mlar list -i output.mla.001 -k samples/test25519.pem`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(&first_volume)
+        .arg("-k")
+        .arg(ecc_private);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stdout(file_list);
+
+    // `mlar to-tar -i output.mla.001 -k samples/test25519.pem -o output.tar`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("to-tar")
+        .arg("-i")
+        .arg(&first_volume)
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("-o")
+        .arg(tar_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+    ensure_tar_content(tar_file.path(), &testfs.files);
+
+    // A missing volume is detected
+    std::fs::remove_file(output_dir.path().join("output.mla.002")).unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(&first_volume)
+        .arg("-k")
+        .arg(ecc_private);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let output = String::from_utf8(assert.failure().get_output().stderr.clone()).unwrap();
+    assert!(output.contains("volume 2 is missing"));
+
+    // Also as JSON, with its code
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(&first_volume)
+        .arg("-k")
+        .arg(ecc_private)
+        .arg("--error-format")
+        .arg("json");
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let output = String::from_utf8(assert.failure().get_output().stderr.clone()).unwrap();
+    let report: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+    assert_eq!(report["error"]["code"], "missing_volume");
+    assert_eq!(report["error"]["message"], "volume 2 is missing");
+}
+
+#[test]
+fn test_error_format_context() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let not_a_directory = NamedTempFile::new("file.txt").unwrap();
+    not_a_directory.write_binary(b"ABCD").unwrap();
+
+    // Create files
+    let testfs = setup();
+
+    // `mlar create -l compress -o output.mla file1.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("compress")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg(testfs.files[0].path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // `mlar extract -i output.mla -o file.txt`, a regular file
+    for json in &[false, true] {
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("extract")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .arg("-o")
+            .arg(not_a_directory.path());
+        if *json {
+            cmd.arg("--error-format").arg("json");
+        }
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        let output = String::from_utf8(assert.failure().get_output().stderr.clone()).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        if *json {
+            // The error met, with its context, then the one ending the command
+            let report: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+            assert_eq!(report["error"]["code"], "io");
+            assert!(report["context"]
+                .as_str()
+                .unwrap()
+                .starts_with("Error while creating output directory path"));
+            let report: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+            assert_eq!(report["error"]["code"], "io");
+        } else {
+            // Errors are described, not debug-formatted
+            assert!(lines[0].starts_with(" [!] Error while creating output directory path"));
+            assert!(!output.contains("Os {"));
+        }
+    }
 }
 
 #[test]
@@ -247,7 +3177,11 @@ fn test_truncated_repair_list_tar() {
 
     println!("{:?}", cmd);
     let assert = cmd.assert();
-    assert.success();
+    // The recovery status of each file is reported
+    let first = &testfs.files_archive_order[0];
+    let report = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    assert!(report.starts_with(&format!("{}: complete\n", first.to_string_lossy())));
+    assert!(report.contains(": truncated at offset "));
 
     // `mlar list -i repaired.mla -k samples/test25519.pem`
     let mut cmd = Command::cargo_bin(UTIL).unwrap();
@@ -319,11 +3253,11 @@ fn test_truncated_repair_list_tar() {
 fn test_multiple_keys() {
     // Key parsing is common for each subcommands, so test only one: `list`
     let mlar_file = NamedTempFile::new("output.mla").unwrap();
-    let ecc_publics = vec![
+    let ecc_publics = [
         Path::new("../samples/test25519_pub.pem"),
         Path::new("../samples/test25519_3_pub.pem"),
     ];
-    let ecc_privates = vec![
+    let ecc_privates = [
         Path::new("../samples/test25519.pem"),
         Path::new("../samples/test25519_2.pem"),
     ];
@@ -362,9 +3296,9 @@ fn test_multiple_keys() {
         .arg("-i")
         .arg(mlar_file.path())
         .arg("-k")
-        .arg(&ecc_privates[0])
+        .arg(ecc_privates[0])
         .arg("-k")
-        .arg(&ecc_privates[1]);
+        .arg(ecc_privates[1]);
 
     println!("{:?}", cmd);
     let assert = cmd.assert();
@@ -388,7 +3322,7 @@ fn test_multiple_keys() {
         .arg("-i")
         .arg(mlar_file.path())
         .arg("-k")
-        .arg(&ecc_privates[1]);
+        .arg(ecc_privates[1]);
 
     println!("{:?}", cmd);
     let assert = cmd.assert();
@@ -433,7 +3367,7 @@ fn test_multiple_compression_level() {
     assert!(q5_size < q0_size);
 
     // Ensure files are correct
-    for (src, tar_name) in vec![(mlar_file_q0, &tar_file_q0), (mlar_file_q5, &tar_file_q5)] {
+    for (src, tar_name) in [(mlar_file_q0, &tar_file_q0), (mlar_file_q5, &tar_file_q5)] {
         // `mlar to-tar -i {src} -o {tar_name}`
         let mut cmd = Command::cargo_bin(UTIL).unwrap();
         cmd.arg("to-tar")
@@ -446,8 +3380,72 @@ fn test_multiple_compression_level() {
         let assert = cmd.assert();
         assert.success();
     }
-    ensure_tar_content(&tar_file_q0.path(), &testfs.files);
-    ensure_tar_content(&tar_file_q5.path(), &testfs.files);
+    ensure_tar_content(tar_file_q0.path(), &testfs.files);
+    ensure_tar_content(tar_file_q5.path(), &testfs.files);
+}
+
+#[test]
+fn test_compression_algorithms() {
+    // Create files
+    let testfs = setup();
+
+    for (algorithm, compression_level) in &[("brotli", "3"), ("zstd", "10"), ("lz4", "0")] {
+        let mlar_file = NamedTempFile::new("output.mla").unwrap();
+        let tar_file = NamedTempFile::new("output.tar").unwrap();
+
+        // `mlar create -o output.mla -l compress --compression-algo {algorithm} --compression-level {compression_level} file1.bin file2.bin file3.bin`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("create")
+            .arg("-o")
+            .arg(mlar_file.path())
+            .arg("-l")
+            .arg("compress")
+            .arg("--compression-algo")
+            .arg(algorithm)
+            .arg("--compression-level")
+            .arg(compression_level);
+        for file in &testfs.files {
+            cmd.arg(file.path());
+        }
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert.success();
+
+        // The algorithm is read from the header
+        // `mlar to-tar -i output.mla -o output.tar`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("to-tar")
+            .arg("-i")
+            .arg(mlar_file.path())
+            .arg("-o")
+            .arg(tar_file.path());
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert.success();
+        ensure_tar_content(tar_file.path(), &testfs.files);
+    }
+
+    // Levels are checked against the algorithm
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg("-l")
+        .arg("compress")
+        .arg("--compression-algo")
+        .arg("lz4")
+        .arg("-q")
+        .arg("5");
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure();
 }
 
 #[test]
@@ -521,7 +3519,7 @@ fn test_convert() {
     assert.success();
 
     // Inspect the created TAR file
-    ensure_tar_content(&tar_file.path(), &testfs.files);
+    ensure_tar_content(tar_file.path(), &testfs.files);
 }
 
 #[test]
@@ -573,7 +3571,7 @@ fn test_stdio() {
     assert.success();
 
     // Inspect the created TAR file
-    ensure_tar_content(&tar_file.path(), &testfs.files);
+    ensure_tar_content(tar_file.path(), &testfs.files);
 }
 
 #[test]
@@ -634,7 +3632,7 @@ fn test_multi_fileorders() {
         assert.success();
 
         // Inspect the created TAR file
-        ensure_tar_content(&tar_file.path(), &testfs.files);
+        ensure_tar_content(tar_file.path(), &testfs.files);
     }
 }
 
@@ -722,11 +3720,11 @@ fn test_extract() {
 
     println!("{:?}", cmd);
     let assert = cmd.assert();
-    assert.success().stdout(file_list);
+    assert.success().stdout(file_list.clone());
 
     ensure_directory_content(output_dir.path(), &testfs.files);
 
-    // Test linear extraction of all files
+    // Test extraction of all files
 
     // `mlar extract -v -i output.mla -o ouput_dir`
     let output_dir = TempDir::new().unwrap();
@@ -740,9 +3738,8 @@ fn test_extract() {
 
     println!("{:?}", cmd);
     let assert = cmd.assert();
-    assert
-        .success()
-        .stdout("Extracting the whole archive using a linear extraction\n");
+    // Files have been added by name
+    assert.success().stdout(file_list);
 
     ensure_directory_content(output_dir.path(), &testfs.files);
 
@@ -802,6 +3799,68 @@ fn test_extract() {
     ensure_directory_content(output_dir.path(), &one_file);
 }
 
+#[test]
+fn test_include_exclude() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let mut testfs = setup();
+
+    // `mlar create -l -o output.mla file1.bin file2.bin file3.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create").arg("-l").arg("-o").arg(mlar_file.path());
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success();
+
+    // Only keep file1.bin and file3.bin
+    let excluded = testfs
+        .files
+        .iter()
+        .position(|file| file.path().ends_with("file2.bin"))
+        .unwrap();
+    testfs.files.remove(excluded);
+    let mut file_list = String::new();
+    for file in &testfs.files {
+        file_list.push_str(format!("{}\n", file.path().to_string_lossy()).as_str());
+    }
+
+    // `mlar list -i output.mla --include '*.bin' --exclude '*file2.bin'`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("list")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("--include")
+        .arg("*.bin")
+        .arg("--exclude")
+        .arg("*file2.bin");
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stdout(file_list.clone());
+
+    // `mlar extract -v -i output.mla -o output_dir --regex --include 'file[13]\.bin$'`
+    let output_dir = TempDir::new().unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("extract")
+        .arg("-v")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-o")
+        .arg(output_dir.path())
+        .arg("--regex")
+        .arg("--include")
+        .arg("file[13]\\.bin$");
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stdout(file_list);
+
+    ensure_directory_content(output_dir.path(), &testfs.files);
+}
+
 #[test]
 fn test_cat() {
     let mlar_file = NamedTempFile::new("output.mla").unwrap();
@@ -837,6 +3896,48 @@ fn test_cat() {
         .read_to_end(&mut expected_content)
         .unwrap();
     assert_eq!(assert.success().get_output().stdout, expected_content);
+
+    // Several files are displayed in the given order
+    // `mlar cat -i output.mla file3 file1`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("cat")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg(&testfs.files_archive_order[2])
+        .arg(&testfs.files_archive_order[0]);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    File::open(&testfs.files_archive_order[0])
+        .unwrap()
+        .read_to_end(&mut expected_content)
+        .unwrap();
+    assert_eq!(assert.success().get_output().stdout, expected_content);
+
+    // `mlar cat -i output.mla --glob '*file3.bin'`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("cat")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("--glob")
+        .arg("*file3.bin");
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stdout("ABCDEFGHIJ");
+
+    // A missing file makes the command fail, after the other ones are displayed
+    // `mlar cat -i output.mla file3 missing.bin`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("cat")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg(&testfs.files_archive_order[2])
+        .arg("missing.bin");
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.failure().stdout("ABCDEFGHIJ");
 }
 
 #[test]
@@ -882,3 +3983,252 @@ fn test_keygen() {
     let assert = cmd.assert();
     assert.success().stdout(file_list);
 }
+
+#[test]
+fn test_keygen_hybrid() {
+    // Gen an hybrid keypair, create and list an archive using it alongside a
+    // classic key
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let output_dir = TempDir::new().unwrap();
+    let base_name = output_dir.path().join("key");
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let ecc_private = Path::new("../samples/test25519.pem");
+    let testfs = setup();
+
+    // `mlar keygen --type hybrid tempdir/key`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("keygen")
+        .arg("--type")
+        .arg("hybrid")
+        .arg(&base_name);
+    cmd.assert().success();
+
+    // `mlar create -p tempdir/key.pub -p samples/test25519_pub.pem -o output.mla file1 file2 file3`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-p")
+        .arg(base_name.with_extension("pub"))
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("-o")
+        .arg(mlar_file.path());
+
+    let mut file_list = String::new();
+    for file in &testfs.files {
+        cmd.arg(file.path());
+        file_list.push_str(format!("{}\n", file.path().to_string_lossy()).as_str());
+    }
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    assert.success().stderr(String::from(&file_list));
+
+    // Each private key opens the archive
+    for private_key in &[base_name.as_path(), ecc_private] {
+        // `mlar list -k tempdir/key -i output.mla`
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("list")
+            .arg("-k")
+            .arg(private_key)
+            .arg("-i")
+            .arg(mlar_file.path());
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        assert.success().stdout(String::from(&file_list));
+    }
+}
+
+#[test]
+fn test_keygen_seed_info() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let output_dir = TempDir::new().unwrap();
+    let mnemonic_file = NamedTempFile::new("mnemonic.txt").unwrap();
+    mnemonic_file
+        .write_binary(
+            b"legal winner thank year wave sausage worth useful legal winner thank yellow\n",
+        )
+        .unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+
+    // `mlar keygen --mnemonic mnemonic.txt --comment "Escrow key" tempdir/keyN`,
+    // twice
+    let mut fingerprints = Vec::new();
+    for name in &["key1", "key2"] {
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("keygen")
+            .arg("--mnemonic")
+            .arg(mnemonic_file.path())
+            .arg("--comment")
+            .arg("Escrow key")
+            .arg(output_dir.path().join(name));
+
+        println!("{:?}", cmd);
+        let assert = cmd.assert();
+        let output = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+        assert!(output.starts_with("SHA256:"));
+        fingerprints.push(output.trim().to_string());
+    }
+    // The same seed gives the same key
+    assert_eq!(fingerprints[0], fingerprints[1]);
+    let key = output_dir.path().join("key1");
+    assert_eq!(
+        std::fs::read(&key).unwrap(),
+        std::fs::read(output_dir.path().join("key2")).unwrap()
+    );
+    let public = std::fs::read_to_string(key.with_extension("pub")).unwrap();
+    assert!(public.starts_with("Comment: Escrow key\n-----BEGIN PUBLIC KEY-----"));
+
+    // `mlar keygen --seed mnemonic.txt tempdir/key3`: the content is used as
+    // is, giving another key
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("keygen")
+        .arg("--seed")
+        .arg(mnemonic_file.path())
+        .arg(output_dir.path().join("key3"));
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let output = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    assert_ne!(output.trim(), fingerprints[0]);
+
+    // Mnemonics with a wrong checksum, an unknown word or too few words are
+    // refused
+    let bad_file = NamedTempFile::new("bad.txt").unwrap();
+    for bad in &[
+        "legal winner thank year wave sausage worth useful legal winner thank thank",
+        "legal winner thank year wave sausage worth useful legal winner thank mla",
+        "legal winner thank year wave sausage",
+    ] {
+        bad_file.write_binary(bad.as_bytes()).unwrap();
+        let mut cmd = Command::cargo_bin(UTIL).unwrap();
+        cmd.arg("keygen")
+            .arg("--mnemonic")
+            .arg(bad_file.path())
+            .arg(output_dir.path().join("bad"));
+
+        println!("{:?}", cmd);
+        cmd.assert().failure();
+    }
+
+    // `mlar create -p tempdir/key1.pub -p samples/test25519_pub.pem -o output.mla file1`
+    let testfs = setup();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-p")
+        .arg(key.with_extension("pub"))
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg(testfs.files[0].path());
+
+    println!("{:?}", cmd);
+    cmd.assert().success();
+
+    // `mlar info -i output.mla -k tempdir/key1`: only the slot of the given
+    // key is identified
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("info")
+        .arg("-i")
+        .arg(mlar_file.path())
+        .arg("-k")
+        .arg(&key);
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let output = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    assert!(output.contains(&format!(
+        "Recipient slots: 2\n  0: x25519 {}\n  1: x25519 unknown\n",
+        fingerprints[0]
+    )));
+    // The key opens the archive, so its entries are counted
+    assert!(output.contains("Entries: 1\n"));
+    assert!(output.contains("Truncated: no\n"));
+}
+
+#[test]
+fn test_info_inspect() {
+    let mlar_file = NamedTempFile::new("output.mla").unwrap();
+    let ecc_public = Path::new("../samples/test25519_pub.pem");
+    let testfs = setup();
+
+    // `mlar create -l compress -o output.mla file1 file2 file3`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-l")
+        .arg("compress")
+        .arg("-o")
+        .arg(mlar_file.path());
+    for file in &testfs.files {
+        cmd.arg(file.path());
+    }
+
+    println!("{:?}", cmd);
+    cmd.assert().success();
+
+    // `mlar info -i output.mla`: no key is needed without encryption
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("info").arg("-i").arg(mlar_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let output = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    assert!(output.starts_with("Format version: 6\n"));
+    assert!(output.contains("Layers: compress\n"));
+    assert!(output.contains("Compression: brotli\n"));
+    assert!(!output.contains("Recipient slots"));
+    assert!(output.contains(&format!("Entries: {}\n", testfs.files.len())));
+    assert!(output.contains("Truncated: no\n"));
+
+    // The same archive, truncated, loses its footer
+    let archive = std::fs::read(mlar_file.path()).unwrap();
+    std::fs::write(mlar_file.path(), &archive[..archive.len() - 4]).unwrap();
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("info").arg("-i").arg(mlar_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let output = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    assert!(output.contains("Entries: unknown\n"));
+    assert!(output.contains("Truncated: yes\n"));
+
+    // `mlar create -p samples/test25519_pub.pem -o output.mla file1`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("create")
+        .arg("-p")
+        .arg(ecc_public)
+        .arg("-o")
+        .arg(mlar_file.path())
+        .arg(testfs.files[0].path());
+
+    println!("{:?}", cmd);
+    cmd.assert().success();
+
+    // `mlar info -i output.mla`: the layers and recipients are known without
+    // a key, but not the entries
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("info").arg("-i").arg(mlar_file.path());
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let output = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    assert!(output.contains("Layers: compress, encrypt\n"));
+    assert!(output.contains("Cipher: aes-gcm\n"));
+    assert!(output.contains("Recipient slots: 1\n  0: x25519 unknown\n"));
+    assert!(output.contains("Entries: unknown\n"));
+    assert!(output.contains("Truncated: no\n"));
+}
+
+#[test]
+fn test_info_crypto() {
+    // `mlar info --crypto`
+    let mut cmd = Command::cargo_bin(UTIL).unwrap();
+    cmd.arg("info").arg("--crypto");
+
+    println!("{:?}", cmd);
+    let assert = cmd.assert();
+    let output = String::from_utf8(assert.success().get_output().stdout.clone()).unwrap();
+    assert!(output.contains("AES: "));
+    assert!(output.contains("GHASH: "));
+}